@@ -0,0 +1,89 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.messagebackup;
+
+import static org.signal.libsignal.internal.FilterExceptions.filterExceptions;
+
+import org.signal.libsignal.internal.Native;
+import org.signal.libsignal.internal.NativeHandleGuard;
+
+/**
+ * Produces an encrypted backup file frame-by-frame.
+ *
+ * <p>Each frame is validated as it's added, with the same checks {@link MessageBackup#validate}
+ * performs, so frames must be added in an order that satisfies those checks (for example, a chat
+ * before any of its chat items). The frames are kept in memory until the backup is finished.
+ *
+ * <h2>Example</h2>
+ *
+ * <pre>
+ * const writer = new MessageBackupWriter(
+ *     backupInfoProto.serialize(),
+ *     MessageBackup.Purpose.REMOTE_BACKUP)
+ * repeat {
+ *   // ...generate Frames...
+ *   writer.addFrame(frameProto.serialize())
+ * }
+ * byte[] backupFile = writer.finishEncrypted(backupKey, forwardSecrecyMetadata)
+ * </pre>
+ */
+public class MessageBackupWriter extends NativeHandleGuard.SimpleOwner {
+  /**
+   * Starts a backup with the given BackupInfo protobuf message, which is written out unchanged.
+   *
+   * @throws ValidationError on error
+   */
+  public MessageBackupWriter(byte[] backupInfo, MessageBackup.Purpose purpose)
+      throws ValidationError {
+    super(
+        filterExceptions(
+            ValidationError.class,
+            () -> Native.MessageBackupWriter_New(backupInfo, purpose.ordinal())));
+  }
+
+  @Override
+  protected void release(long nativeHandle) {
+    Native.MessageBackupWriter_Destroy(nativeHandle);
+  }
+
+  /**
+   * Validates a single Frame protobuf message against the frames added so far, then appends it
+   * unchanged.
+   *
+   * <p>If this fails, the writer should be discarded.
+   *
+   * @throws ValidationError on error
+   */
+  public void addFrame(byte[] frame) throws ValidationError {
+    filterExceptions(
+        ValidationError.class,
+        () -> guardedRunChecked(h -> Native.MessageBackupWriter_AddFrame(h, frame)));
+  }
+
+  /**
+   * Checks that the backup is complete, then compresses, pads, and encrypts it.
+   *
+   * <p>The writer can't be used again after this, whether or not it succeeds.
+   *
+   * @param key the key to encrypt the backup with
+   * @param forwardSecrecyMetadata the serialized metadata produced by SVR-B, or {@code null} to
+   *     produce the legacy format with no unencrypted header
+   * @return the complete backup file
+   * @throws ValidationError if the backup is incomplete or can't be encrypted
+   */
+  public byte[] finishEncrypted(MessageBackupKey key, byte[] forwardSecrecyMetadata)
+      throws ValidationError {
+    try (NativeHandleGuard keyGuard = new NativeHandleGuard(key)) {
+      return filterExceptions(
+          ValidationError.class,
+          () ->
+              guardedMapChecked(
+                  h ->
+                      Native.MessageBackupWriter_FinishEncrypted(
+                          h, keyGuard.nativeHandle(), forwardSecrecyMetadata)));
+    }
+  }
+}
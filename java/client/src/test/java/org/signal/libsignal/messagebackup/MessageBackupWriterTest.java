@@ -0,0 +1,77 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.libsignal.messagebackup;
+
+import static org.junit.Assert.assertEquals;
+import static org.junit.Assert.assertThrows;
+
+import java.io.ByteArrayInputStream;
+import java.io.IOException;
+import java.io.InputStream;
+import org.junit.Test;
+
+public class MessageBackupWriterTest {
+  static final MessageBackup.Purpose BACKUP_PURPOSE = MessageBackup.Purpose.REMOTE_BACKUP;
+
+  /** Reads one varint-delimited frame, or returns null at the end of the input. */
+  private static byte[] readFrame(InputStream input) throws IOException {
+    int length = 0;
+    int shift = 0;
+    int nextByte;
+    do {
+      nextByte = input.read();
+      if (nextByte == -1) {
+        assertEquals("unexpected EOF", 0, shift);
+        return null;
+      }
+      length |= (nextByte & 0x7f) << shift;
+      shift += 7;
+    } while (nextByte >= 0x80);
+
+    final byte[] frame = new byte[length];
+    assertEquals("unexpected EOF", length, input.readNBytes(frame, 0, length));
+    return frame;
+  }
+
+  @Test
+  public void writtenBackupPassesValidation() throws IOException, ValidationError {
+    final MessageBackupKey key = MessageBackupValidationTest.makeMessageBackupKey();
+    final byte[] encrypted;
+    try (InputStream input = ComparableBackupTest.getCanonicalBackupInputStream()) {
+      final MessageBackupWriter writer = new MessageBackupWriter(readFrame(input), BACKUP_PURPOSE);
+      byte[] frame;
+      while ((frame = readFrame(input)) != null) {
+        writer.addFrame(frame);
+      }
+      encrypted = writer.finishEncrypted(key, null);
+    }
+
+    MessageBackup.validate(
+        key, BACKUP_PURPOSE, () -> new ByteArrayInputStream(encrypted), encrypted.length);
+  }
+
+  @Test
+  public void writerRejectsInvalidBackupInfo() {
+    assertThrows(ValidationError.class, () -> new MessageBackupWriter(new byte[0], BACKUP_PURPOSE));
+  }
+
+  @Test
+  public void writerRejectsInvalidFrame() throws IOException, ValidationError {
+    try (InputStream input = ComparableBackupTest.getCanonicalBackupInputStream()) {
+      final MessageBackupWriter writer = new MessageBackupWriter(readFrame(input), BACKUP_PURPOSE);
+      assertThrows(ValidationError.class, () -> writer.addFrame(new byte[0]));
+    }
+  }
+
+  @Test
+  public void writerRejectsIncompleteBackup() throws IOException, ValidationError {
+    final MessageBackupKey key = MessageBackupValidationTest.makeMessageBackupKey();
+    try (InputStream input = ComparableBackupTest.getCanonicalBackupInputStream()) {
+      final MessageBackupWriter writer = new MessageBackupWriter(readFrame(input), BACKUP_PURPOSE);
+      assertThrows(ValidationError.class, () -> writer.finishEncrypted(key, null));
+    }
+  }
+}
@@ -76,6 +76,7 @@ public class ChatServiceTest {
     assertChatSendErrorIs("WebSocketConnectionReset", ChatServiceException.class);
     assertChatSendErrorIs("IncomingDataInvalid", ChatServiceException.class);
     assertChatSendErrorIs("RequestTimedOut", ChatServiceException.class);
+    assertChatSendErrorIs("Busy", ChatServiceException.class);
     assertChatSendErrorIs("RequestHasInvalidHeader", ChatServiceException.class);
     assertChatSendErrorIs("ConnectionInvalidated", ConnectionInvalidatedException.class);
     assertChatSendErrorIs("ConnectedElsewhere", ConnectedElsewhereException.class);
@@ -696,6 +696,15 @@ internal object Native {
   @JvmStatic @Throws(Exception::class)
   public external fun MessageBackupValidator_Validate(key: ObjectHandle, firstStream: InputStream, secondStream: InputStream, len: Long, purpose: Int): Object
 
+  @JvmStatic @Throws(Exception::class)
+  public external fun MessageBackupWriter_AddFrame(writer: ObjectHandle, frame: ByteArray): Unit
+  @JvmStatic
+  public external fun MessageBackupWriter_Destroy(handle: ObjectHandle): Unit
+  @JvmStatic @Throws(Exception::class)
+  public external fun MessageBackupWriter_FinishEncrypted(writer: ObjectHandle, key: ObjectHandle, forwardSecrecyMetadata: ByteArray?): ByteArray
+  @JvmStatic @Throws(Exception::class)
+  public external fun MessageBackupWriter_New(backupInfoFrame: ByteArray, purpose: Int): ObjectHandle
+
   @JvmStatic @Throws(Exception::class)
   public external fun Mp4Sanitizer_Sanitize(input: InputStream, len: Long): ObjectHandle
   @JvmStatic @Throws(Exception::class)
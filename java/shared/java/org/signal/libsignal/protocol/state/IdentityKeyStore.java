@@ -27,7 +27,9 @@ public interface IdentityKeyStore {
   // This must be kept in sync with the Rust enum of the same name.
   public enum IdentityChange {
     NEW_OR_UNCHANGED,
-    REPLACED_EXISTING
+    REPLACED_EXISTING,
+    /** Only produced when accepting a signed identity transition. */
+    ROTATED_VERIFIED
   }
 
   /**
@@ -61,6 +61,7 @@ enum IdentityChange {
   // This must be kept in sync with the Rust enum of the same name.
   NewOrUnchanged = 0,
   ReplacedExisting = 1,
+  RotatedVerified = 2,
 }
 
 type IdentityKeyStore = {
@@ -381,6 +382,9 @@ export function MessageBackupKey_FromBackupKeyAndBackupId(backupKey: Uint8Array,
 export function MessageBackupKey_GetAesKey(key: Wrapper<MessageBackupKey>): Uint8Array;
 export function MessageBackupKey_GetHmacKey(key: Wrapper<MessageBackupKey>): Uint8Array;
 export function MessageBackupValidator_Validate(key: Wrapper<MessageBackupKey>, firstStream: InputStream, secondStream: InputStream, len: bigint, purpose: number): Promise<MessageBackupValidationOutcome>;
+export function MessageBackupWriter_AddFrame(writer: Wrapper<MessageBackupWriter>, frame: Uint8Array): void;
+export function MessageBackupWriter_FinishEncrypted(writer: Wrapper<MessageBackupWriter>, key: Wrapper<MessageBackupKey>, forwardSecrecyMetadata: Uint8Array | null): Uint8Array;
+export function MessageBackupWriter_New(backupInfoFrame: Uint8Array, purpose: number): MessageBackupWriter;
 export function MinidumpToJSONString(buffer: Uint8Array): string;
 export function Mp4Sanitizer_Sanitize(input: InputStream, len: bigint): Promise<SanitizedMetadata>;
 export function OnlineBackupValidator_AddFrame(backup: Wrapper<OnlineBackupValidator>, frame: Uint8Array): void;
@@ -756,6 +760,7 @@ interface KyberPublicKey { readonly __type: unique symbol; }
 interface KyberSecretKey { readonly __type: unique symbol; }
 interface LookupRequest { readonly __type: unique symbol; }
 interface MessageBackupKey { readonly __type: unique symbol; }
+interface MessageBackupWriter { readonly __type: unique symbol; }
 interface NonSuspendingBackgroundThreadRuntime { readonly __type: unique symbol; }
 interface OnlineBackupValidator { readonly __type: unique symbol; }
 interface OtherTestingHandleType { readonly __type: unique symbol; }
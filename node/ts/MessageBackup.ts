@@ -213,6 +213,73 @@ export class OnlineBackupValidator {
   }
 }
 
+/**
+ * Produces an encrypted backup file frame-by-frame.
+ *
+ * Each frame is validated as it's added, with the same checks {@link validate()} performs, so
+ * frames must be added in an order that satisfies those checks (for example, a chat before any of
+ * its chat items). The frames are kept in memory until the backup is finished.
+ *
+ * # Example
+ *
+ * ```
+ * const writer = new MessageBackupWriter(
+ *     backupInfoProto.serialize(),
+ *     Purpose.RemoteBackup)
+ * repeat {
+ *   // ...generate Frames...
+ *   writer.addFrame(frameProto.serialize())
+ * }
+ * const backupFile = writer.finishEncrypted(backupKey, forwardSecrecyMetadata)
+ * ```
+ */
+export class MessageBackupWriter {
+  readonly _nativeHandle: Native.MessageBackupWriter;
+
+  /**
+   * Starts a backup with the given BackupInfo protobuf message, which is written out unchanged.
+   *
+   * @throws BackupValidationError on error
+   */
+  constructor(backupInfo: Uint8Array, purpose: Purpose) {
+    this._nativeHandle = Native.MessageBackupWriter_New(backupInfo, purpose);
+  }
+
+  /**
+   * Validates a single Frame protobuf message against the frames added so far, then appends it
+   * unchanged.
+   *
+   * If this fails, the writer should be discarded.
+   *
+   * @throws BackupValidationError on error
+   */
+  addFrame(frame: Uint8Array): void {
+    Native.MessageBackupWriter_AddFrame(this, frame);
+  }
+
+  /**
+   * Checks that the backup is complete, then compresses, pads, and encrypts it.
+   *
+   * The writer can't be used again after this, whether or not it succeeds.
+   *
+   * @param backupKey The key to encrypt the backup with.
+   * @param forwardSecrecyMetadata The serialized metadata produced by SVR-B, or `null` to produce
+   * the legacy format with no unencrypted header.
+   * @returns The complete backup file.
+   * @throws BackupValidationError if the backup is incomplete or can't be encrypted
+   */
+  finishEncrypted(
+    backupKey: MessageBackupKey,
+    forwardSecrecyMetadata: Uint8Array | null
+  ): Uint8Array {
+    return Native.MessageBackupWriter_FinishEncrypted(
+      this,
+      backupKey,
+      forwardSecrecyMetadata
+    );
+  }
+}
+
 /**
  * An in-memory representation of a backup file used to compare contents.
  *
@@ -1058,6 +1058,7 @@ export enum IdentityChange {
   // This must be kept in sync with the Rust enum of the same name.
   NewOrUnchanged = 0,
   ReplacedExisting = 1,
+  RotatedVerified = 2,
 }
 
 export abstract class IdentityKeyStore implements Native.IdentityKeyStore {
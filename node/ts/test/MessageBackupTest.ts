@@ -280,3 +280,67 @@ describe('OnlineBackupValidator', () => {
     assert.throws(() => backup.finalize());
   });
 });
+
+describe('MessageBackupWriter', () => {
+  const accountEntropy = 'm'.repeat(64);
+  const aci = Aci.fromUuidBytes(new Uint8Array(16).fill(0x11));
+  const testKey = new MessageBackup.MessageBackupKey({ accountEntropy, aci });
+
+  // Splits an unencrypted backup into its varint-delimited frames, starting with the BackupInfo.
+  function splitIntoFrames(contents: Uint8Array): Uint8Array[] {
+    const frames: Uint8Array[] = [];
+    let offset = 0;
+    while (offset < contents.length) {
+      let length = 0;
+      let shift = 0;
+      let byte: number;
+      do {
+        byte = contents[offset];
+        offset += 1;
+        length |= (byte & 0x7f) << shift;
+        shift += 7;
+      } while (byte >= 0x80);
+      frames.push(contents.subarray(offset, offset + length));
+      offset += length;
+    }
+    return frames;
+  }
+
+  it('produces a backup that passes validation', async () => {
+    const [backupInfo, ...frames] = splitIntoFrames(exampleBackup);
+    const writer = new MessageBackup.MessageBackupWriter(
+      backupInfo,
+      MessageBackup.Purpose.RemoteBackup
+    );
+    for (const frame of frames) {
+      writer.addFrame(frame);
+    }
+    const encrypted = writer.finishEncrypted(testKey, null);
+
+    const outcome = await MessageBackup.validate(
+      testKey,
+      MessageBackup.Purpose.RemoteBackup,
+      () => new Uint8ArrayInputStream(encrypted),
+      BigInt(encrypted.length)
+    );
+    assert.equal(outcome.errorMessage, null);
+  });
+
+  it('rejects invalid Frames', () => {
+    const [backupInfo] = splitIntoFrames(exampleBackup);
+    const writer = new MessageBackup.MessageBackupWriter(
+      backupInfo,
+      MessageBackup.Purpose.RemoteBackup
+    );
+    assert.throws(() => writer.addFrame(Uint8Array.of()));
+  });
+
+  it('rejects incomplete backups', () => {
+    const [backupInfo] = splitIntoFrames(exampleBackup);
+    const writer = new MessageBackup.MessageBackupWriter(
+      backupInfo,
+      MessageBackup.Purpose.RemoteBackup
+    );
+    assert.throws(() => writer.finishEncrypted(testKey, null));
+  });
+});
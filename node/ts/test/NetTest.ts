@@ -85,6 +85,7 @@ describe('chat service api', () => {
       ['WebSocketConnectionReset', ErrorCode.IoError],
       ['IncomingDataInvalid', ErrorCode.IoError],
       ['RequestTimedOut', ErrorCode.IoError],
+      ['Busy', ErrorCode.IoError],
 
       ['RequestHasInvalidHeader', ErrorCode.IoError],
       ['ConnectionInvalidated', ErrorCode.ConnectionInvalidated],
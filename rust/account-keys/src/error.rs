@@ -12,6 +12,9 @@ pub enum Error {
     DecodingError(argon2::password_hash::errors::Error),
     /// Error looking up mrenclave
     MrenclaveLookupError,
+    /// Invalid PIN hashing parameters: {0}
+    #[from(ignore)]
+    InvalidHashParams(&'static str),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -21,6 +21,9 @@
 //!  3. The string must then be [NFKD normalized](https://unicode.org/reports/tr15/#Norm_Forms)
 //!
 
+use std::fmt::Display;
+use std::str::FromStr;
+
 use argon2::password_hash::{rand_core, Salt, SaltString};
 use argon2::{
     Algorithm, Argon2, ParamsBuilder, PasswordHash, PasswordHasher, PasswordVerifier, Version,
@@ -28,7 +31,104 @@ use argon2::{
 use hkdf::Hkdf;
 use sha2::Sha256;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+
+/// Argon2id cost parameters for hashing a PIN with [`PinHash::create_with_params`].
+///
+/// A PIN hashed with different parameters produces different keys, so whichever parameters are
+/// chosen have to be recorded alongside the data the PIN protects. The [`Display`] form of the
+/// parameters can be parsed back with [`FromStr`] for that purpose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PinHashParams {
+    /// Memory cost, in KiB.
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PinHashParams {
+    /// The parameters used by [`PinHash::create`].
+    pub const STANDARD: Self = Self {
+        memory_kib: 16 * 1024,
+        iterations: 32,
+        parallelism: 1,
+    };
+
+    /// A cheaper profile for devices that can't hash with [`Self::STANDARD`] in reasonable time.
+    ///
+    /// This is also the minimum accepted by [`PinHash::create_with_params`].
+    pub const LOW_END: Self = Self {
+        memory_kib: 8 * 1024,
+        iterations: 32,
+        parallelism: 1,
+    };
+
+    /// A more expensive profile for devices with memory to spare.
+    pub const STRONG: Self = Self {
+        memory_kib: 64 * 1024,
+        iterations: 32,
+        parallelism: 1,
+    };
+
+    fn to_argon2_params(self) -> Result<argon2::Params> {
+        let Self {
+            memory_kib,
+            iterations,
+            parallelism,
+        } = self;
+        if memory_kib < Self::LOW_END.memory_kib {
+            return Err(Error::InvalidHashParams("memory cost is below the minimum"));
+        }
+        if iterations < Self::LOW_END.iterations {
+            return Err(Error::InvalidHashParams(
+                "iteration count is below the minimum",
+            ));
+        }
+        Ok(ParamsBuilder::new()
+            .m_cost(memory_kib)
+            .p_cost(parallelism)
+            .t_cost(iterations)
+            .output_len(64)
+            .build()?)
+    }
+}
+
+impl Display for PinHashParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            memory_kib,
+            iterations,
+            parallelism,
+        } = self;
+        write!(f, "m={memory_kib},t={iterations},p={parallelism}")
+    }
+}
+
+impl FromStr for PinHashParams {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        const FORMAT_ERROR: Error = Error::InvalidHashParams("expected m=<KiB>,t=<n>,p=<n>");
+
+        let mut parts = s.split(',');
+        let mut next = |prefix: &str| -> Result<u32> {
+            parts
+                .next()
+                .and_then(|part| part.strip_prefix(prefix))
+                .and_then(|value| value.parse().ok())
+                .ok_or(FORMAT_ERROR)
+        };
+        let params = Self {
+            memory_kib: next("m=")?,
+            iterations: next("t=")?,
+            parallelism: next("p=")?,
+        };
+        if parts.next().is_some() {
+            return Err(FORMAT_ERROR);
+        }
+        Ok(params)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct PinHash {
@@ -39,6 +139,9 @@ pub struct PinHash {
     /// A secret that can be used to access a value in a secure store. The 32 byte suffix of
     /// the 64 byte hashed pin.
     pub access_key: [u8; 32],
+
+    /// The parameters the pin was hashed with.
+    pub params: PinHashParams,
 }
 
 impl PinHash {
@@ -49,16 +152,21 @@ impl PinHash {
     /// * `pin` - UTF-8 encoding of the pin. The pin *must* be normalized first.
     /// * `salt` - An arbitrary 32 byte value that should be unique to the user
     pub fn create(pin: &[u8], salt: &[u8; 32]) -> Result<PinHash> {
+        Self::create_with_params(pin, salt, PinHashParams::STANDARD)
+    }
+
+    /// Like [`create`](Self::create), but with caller-chosen hashing parameters.
+    ///
+    /// Fails if `params` is cheaper than [`PinHashParams::LOW_END`].
+    pub fn create_with_params(
+        pin: &[u8],
+        salt: &[u8; 32],
+        params: PinHashParams,
+    ) -> Result<PinHash> {
         let hasher = Argon2::new(
             Algorithm::Argon2id,
             Version::V0x13,
-            ParamsBuilder::new()
-                .m_cost(1024 * 16) // 16 MiB
-                .p_cost(1)
-                .t_cost(32)
-                .output_len(64)
-                .build()
-                .expect("valid params"),
+            params.to_argon2_params()?,
         );
         let mut output_key_material = [0u8; 64];
         hasher.hash_password_into(pin, salt, &mut output_key_material)?;
@@ -69,6 +177,7 @@ impl PinHash {
             access_key: output_key_material[32..]
                 .try_into()
                 .expect("target length 32"),
+            params,
         })
     }
 
@@ -208,6 +317,47 @@ mod test {
         );
     }
 
+    #[test]
+    fn hash_params() {
+        let standard = PinHash::create(b"password", &[0; 32]).expect("should hash");
+        assert_eq!(standard.params, PinHashParams::STANDARD);
+
+        let low_end = PinHash::create_with_params(b"password", &[0; 32], PinHashParams::LOW_END)
+            .expect("should hash");
+        assert_eq!(low_end.params, PinHashParams::LOW_END);
+        assert_ne!(standard.access_key, low_end.access_key);
+
+        let too_cheap = PinHashParams {
+            memory_kib: 1024,
+            ..PinHashParams::LOW_END
+        };
+        assert_eq!(
+            PinHash::create_with_params(b"password", &[0; 32], too_cheap).map(|_| ()),
+            Err(Error::InvalidHashParams("memory cost is below the minimum"))
+        );
+    }
+
+    #[test]
+    fn hash_params_round_trip() {
+        assert_eq!(PinHashParams::STANDARD.to_string(), "m=16384,t=32,p=1");
+        for params in [
+            PinHashParams::LOW_END,
+            PinHashParams::STANDARD,
+            PinHashParams::STRONG,
+        ] {
+            assert_eq!(params.to_string().parse(), Ok(params));
+        }
+        for invalid in [
+            "",
+            "m=1,t=2",
+            "t=2,m=1,p=1",
+            "m=1,t=2,p=3,x=4",
+            "m=a,t=2,p=3",
+        ] {
+            assert!(invalid.parse::<PinHashParams>().is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn known_phc_string() {
         let pin = b"apassword";
@@ -11,7 +11,7 @@ use core::{fmt, str};
 
 pub use backup::*;
 pub use error::{Error, Result};
-pub use hash::{local_pin_hash, verify_local_pin_hash, PinHash};
+pub use hash::{local_pin_hash, verify_local_pin_hash, PinHash, PinHashParams};
 use hkdf::Hkdf;
 use rand::distr::slice;
 use rand::Rng;
@@ -4,18 +4,27 @@
 //
 
 use std::collections::HashMap;
+use std::time::Duration;
 
 use prost::Message;
 
 use crate::dcap;
-use crate::enclave::{Handshake, HandshakeType, Result};
+use crate::enclave::{AttestationPolicy, Handshake, HandshakeType, Result};
 use crate::proto::cds2;
-use crate::util::get_sw_advisories;
+use crate::util::get_sw_advisories_with_extra;
 
+/// Creates a handshake with a CDSI enclave.
+///
+/// `extra_sw_advisories` are accepted in addition to the built-in advisories for `mrenclave`, and
+/// `policy` relaxes verification for non-production enclaves, and a successful verification is
+/// reused for up to `cache_validity` (see [`dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY`]).
 pub fn new_handshake(
     mrenclave: &[u8],
     attestation_msg: &[u8],
     current_time: std::time::SystemTime,
+    extra_sw_advisories: &[String],
+    policy: &AttestationPolicy,
+    cache_validity: Duration,
 ) -> Result<Handshake> {
     // Deserialize attestation handshake start.
     let handshake_start = cds2::ClientHandshakeStart::decode(attestation_msg)?;
@@ -23,9 +32,11 @@ pub fn new_handshake(
         mrenclave,
         &handshake_start.evidence,
         &handshake_start.endorsement,
-        get_sw_advisories(mrenclave),
+        &get_sw_advisories_with_extra(mrenclave, extra_sw_advisories),
         current_time,
         HandshakeType::PostQuantum,
+        policy,
+        cache_validity,
     )?
     .skip_raft_validation())
 }
@@ -60,6 +71,51 @@ mod test {
 
         let current_time = SystemTime::UNIX_EPOCH + Duration::from_millis(1655857680000);
 
-        assert!(new_handshake(&mrenclave, &attestation_msg.encode_to_vec(), current_time).is_ok());
+        assert!(new_handshake(
+            &mrenclave,
+            &attestation_msg.encode_to_vec(),
+            current_time,
+            &[],
+            &AttestationPolicy::PRODUCTION,
+            Duration::ZERO,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn alternate_measurement() {
+        let mrenclave = hex!("39d78f17f8aa9a8e9cdaf16595947a057bac21f014d1abfd6a99b2dfd4e18d1d");
+        let other_mrenclave = [0; 32];
+
+        let attestation_msg = cds2::ClientHandshakeStart {
+            evidence: include_bytes!("../tests/data/cds2_test.evidence").to_vec(),
+            endorsement: include_bytes!("../tests/data/cds2_test.endorsements").to_vec(),
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let current_time = SystemTime::UNIX_EPOCH + Duration::from_millis(1655857680000);
+
+        assert!(new_handshake(
+            &other_mrenclave,
+            &attestation_msg,
+            current_time,
+            &[],
+            &AttestationPolicy::PRODUCTION,
+            Duration::ZERO,
+        )
+        .is_err());
+        assert!(new_handshake(
+            &other_mrenclave,
+            &attestation_msg,
+            current_time,
+            &[],
+            &AttestationPolicy {
+                alternate_measurements: &[&mrenclave],
+                ..AttestationPolicy::PRODUCTION
+            },
+            Duration::ZERO,
+        )
+        .is_ok());
     }
 }
@@ -3,15 +3,17 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
+use boring_signal::asn1::{Asn1Time, Asn1TimeRef};
 use boring_signal::ec::EcKey;
 use boring_signal::pkey::Public;
 use boring_signal::stack::{Stack, Stackable};
 use boring_signal::x509::crl::X509CRLRef;
 use boring_signal::x509::store::X509StoreRef;
-use boring_signal::x509::{X509StoreContext, X509};
+use boring_signal::x509::{X509NameRef, X509StoreContext, X509};
 
+use crate::enclave::CertificateSummary;
 use crate::error::ContextError;
 use crate::expireable::Expireable;
 
@@ -104,6 +106,31 @@ impl CertChain {
         Ok(stack)
     }
 
+    /// Describes each certificate in the chain, from leaf to root
+    pub fn summary(&self) -> Result<Vec<CertificateSummary>> {
+        self.certs
+            .iter()
+            .map(|cert| {
+                Ok(CertificateSummary {
+                    subject: name_to_string(cert.subject_name()),
+                    issuer: name_to_string(cert.issuer_name()),
+                    not_before: to_system_time(cert.not_before())?,
+                    not_after: to_system_time(cert.not_after())?,
+                })
+            })
+            .collect()
+    }
+
+    /// The earliest time at which a certificate in the chain expires
+    pub fn not_after(&self) -> Result<SystemTime> {
+        let not_afters = self
+            .certs
+            .iter()
+            .map(|cert| to_system_time(cert.not_after()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(not_afters.into_iter().min().expect("chains are not empty"))
+    }
+
     /// Sorts the certificates from leaf->root, failing
     /// if the chain has any missing or extra links
     fn sort(certs: &mut [X509]) -> Result<()> {
@@ -134,6 +161,33 @@ impl CertChain {
     }
 }
 
+/// Formats `name` like "CN=Intel SGX Root CA, O=Intel Corporation"
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn to_system_time(time: &Asn1TimeRef) -> Result<SystemTime> {
+    let diff = Asn1Time::from_unix(0)
+        .expect("0 is valid unix time")
+        .diff(time)?;
+    let secs = i64::from(diff.days) * 24 * 60 * 60 + i64::from(diff.secs);
+    u64::try_from(secs)
+        .ok()
+        .and_then(|secs| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+        .ok_or_else(|| Error::new("certificate time out of range"))
+}
+
 impl Expireable for CertChain {
     fn valid_at(&self, timestamp: SystemTime) -> bool {
         let asn1_timestamp = crate::util::system_time_to_asn1_time(timestamp);
@@ -470,6 +524,19 @@ mod test {
             .expect("should validate");
     }
 
+    #[test]
+    fn summary() {
+        let chain = cert_chain(3);
+        let summary = chain.summary().expect("valid times");
+        let subjects: Vec<_> = summary.iter().map(|cert| cert.subject.as_str()).collect();
+        let issuers: Vec<_> = summary.iter().map(|cert| cert.issuer.as_str()).collect();
+        assert_eq!(subjects, ["CN=2", "CN=1", "CN=0"]);
+        assert_eq!(issuers, ["CN=1", "CN=0", "CN=0"]);
+        assert!(summary.iter().all(|cert| cert.not_before
+            < SystemTime::now() + Duration::from_secs(60)
+            && cert.not_after > SystemTime::now()));
+    }
+
     #[test]
     fn new_chain_from_unsorted_certs() {
         let mut certs = chain(5);
@@ -0,0 +1,79 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Controls the time used to check the validity periods of attestation evidence.
+//!
+//! Devices with badly skewed clocks would otherwise fail attestation spuriously. Clients can
+//! widen the tolerance for skew with [`set_clock_skew_tolerance`], or provide a clock they trust
+//! more than the system's with [`set_trusted_time_source`].
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// How much to offset SGX time-based validity checks by default, to adjust for clock skew on
+/// clients; see [`set_clock_skew_tolerance`]
+pub(crate) const DEFAULT_CLOCK_SKEW_TOLERANCE: Duration = Duration::from_secs(24 * 60 * 60);
+
+static CLOCK_SKEW_TOLERANCE: RwLock<Duration> = RwLock::new(DEFAULT_CLOCK_SKEW_TOLERANCE);
+
+static TRUSTED_TIME_SOURCE: RwLock<Option<Arc<dyn TimeSource>>> = RwLock::new(None);
+
+/// A source of the current time that is more trustworthy than the local system clock, such as
+/// one synchronized with a server.
+pub trait TimeSource: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// Sets how far SGX collateral validity checks are offset to allow for a skewed local clock.
+///
+/// Larger values accept collateral that is closer to expiring.
+pub fn set_clock_skew_tolerance(tolerance: Duration) {
+    *CLOCK_SKEW_TOLERANCE.write().expect("not poisoned") = tolerance;
+}
+
+pub(crate) fn clock_skew_tolerance() -> Duration {
+    *CLOCK_SKEW_TOLERANCE.read().expect("not poisoned")
+}
+
+/// Sets the time source consulted by [`now`], or restores the system clock if `None`.
+pub fn set_trusted_time_source(source: Option<Arc<dyn TimeSource>>) {
+    *TRUSTED_TIME_SOURCE.write().expect("not poisoned") = source;
+}
+
+/// The current time according to the trusted time source, if one has been set, or else the
+/// system clock.
+///
+/// Callers that don't have a `current_time` of their own should pass this to attestation
+/// functions.
+pub fn now() -> SystemTime {
+    match &*TRUSTED_TIME_SOURCE.read().expect("not poisoned") {
+        Some(source) => source.now(),
+        None => SystemTime::now(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct FixedTime(SystemTime);
+
+    impl TimeSource for FixedTime {
+        fn now(&self) -> SystemTime {
+            self.0
+        }
+    }
+
+    #[test]
+    fn trusted_time_source() {
+        let fixed = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        set_trusted_time_source(Some(Arc::new(FixedTime(fixed))));
+        assert_eq!(now(), fixed);
+
+        set_trusted_time_source(None);
+        assert_ne!(now(), fixed);
+    }
+}
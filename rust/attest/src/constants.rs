@@ -6,6 +6,7 @@
 use const_str::hex;
 
 use crate::enclave::HandshakeType;
+use crate::sev_snp::SnpTcb;
 use crate::svr2::RaftConfig;
 use crate::util::SmallMap;
 
@@ -180,3 +181,9 @@ pub(crate) static EXPECTED_RAFT_CONFIG_SVR2: SmallMap<&'static [u8], &'static Ra
         (ENCLAVE_ID_SVR2_STAGING, RAFT_CONFIG_SVR2_STAGING),
         (ENCLAVE_ID_SVR2_PROD, RAFT_CONFIG_SVR2_PROD),
     ]);
+
+/// Map from SEV-SNP launch measurement to the oldest platform TCB that build may run on.
+///
+/// No SEV-SNP enclaves are deployed yet; measurements must be added here before
+/// [`sev_snp::new_handshake`](crate::sev_snp::new_handshake) will accept them.
+pub(crate) const SNP_MINIMUM_TCB: &SmallMap<&'static [u8], SnpTcb, 0> = &SmallMap::new([]);
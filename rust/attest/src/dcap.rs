@@ -23,7 +23,7 @@
 
 use std::collections::HashMap;
 use std::sync::LazyLock;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use boring_signal::asn1::{Asn1Time, Asn1TimeRef};
 use boring_signal::bn::BigNumContext;
@@ -38,25 +38,30 @@ use boring_signal::x509::{X509Ref, X509};
 use hex::ToHex;
 use uuid::Uuid;
 
+use crate::cert_chain::CertChain;
+use crate::dcap::cache::AttestationCache;
 use crate::dcap::ecdsa::EcdsaSigned;
 use crate::dcap::endorsements::{
-    EnclaveType, QeTcbStatus, SgxEndorsements, TcbInfo, TcbLevel, TcbStatus,
+    EnclaveIdentity, EnclaveType, QeTcbStatus, SgxEndorsements, TcbInfo, TcbLevel, TcbStatus,
 };
 use crate::dcap::evidence::Evidence;
 pub use crate::dcap::sgx_report_body::MREnclave;
-use crate::dcap::sgx_report_body::SgxFlags;
+use crate::dcap::sgx_report_body::{SgxFlags, SgxReportBody};
 use crate::dcap::sgx_x509::SgxPckExtension;
-use crate::enclave::AttestationError;
+use crate::enclave::{
+    AttestationError, AttestationInfo, AttestationPolicy, CertificateSummary, TcbError,
+};
 use crate::error::{Context, ContextError};
 use crate::expireable::Expireable;
 
-mod ecdsa;
-mod endorsements;
+mod cache;
+pub(crate) mod ecdsa;
+pub(crate) mod endorsements;
 mod evidence;
 mod revocation_list;
-mod sgx_quote;
-mod sgx_report_body;
-mod sgx_x509;
+pub(crate) mod sgx_quote;
+pub(crate) mod sgx_report_body;
+pub(crate) mod sgx_x509;
 
 #[cfg(test)]
 mod fakes;
@@ -64,6 +69,8 @@ mod fakes;
 pub(crate) struct DcapErrorDomain;
 pub(crate) type Error = ContextError<DcapErrorDomain>;
 
+pub use endorsements::TcbStatus;
+
 type Result<T> = std::result::Result<T, Error>;
 
 /// Intel public key that signs all root certificates for DCAP
@@ -75,6 +82,15 @@ const INTEL_ROOT_PUB_KEY: &[u8] = &[
     0x94,
 ];
 
+/// How long a successful verification of some evidence and endorsements is reused for by
+/// [`verify_remote_attestation`], and a reasonable choice for enclave connections.
+///
+/// Reuse never extends past the expiry of any certificate or collateral that was checked, but
+/// revocations published within this window won't be noticed until it elapses.
+pub const DEFAULT_ATTESTATION_CACHE_VALIDITY: Duration = Duration::from_secs(10 * 60);
+
+static ATTESTATION_CACHE: LazyLock<AttestationCache> = LazyLock::new(AttestationCache::new);
+
 /// Returns a `Result` containing a map of claims extracted from the evidence when successful,
 /// or an attestation verification error when not
 ///
@@ -90,23 +106,62 @@ pub fn verify_remote_attestation(
     acceptable_sw_advisories: &[&str],
     current_time: SystemTime,
 ) -> std::result::Result<HashMap<String, Vec<u8>>, AttestationError> {
-    let attestation = attest(evidence_bytes, endorsement_bytes, current_time)?;
+    verify_attestation(
+        evidence_bytes,
+        endorsement_bytes,
+        expected_mrenclave,
+        acceptable_sw_advisories,
+        current_time,
+        &AttestationPolicy::PRODUCTION,
+        DEFAULT_ATTESTATION_CACHE_VALIDITY,
+    )
+    .map(|attestation| attestation.claims)
+}
+
+/// Like [`verify_remote_attestation`], but returns the whole verified [`Attestation`], relaxes
+/// the checks made according to `policy`, and reuses a successful result for up to
+/// `cache_validity` (zero disables reuse)
+pub(crate) fn verify_attestation(
+    evidence_bytes: &[u8],
+    endorsement_bytes: &[u8],
+    expected_mrenclave: &MREnclave,
+    acceptable_sw_advisories: &[&str],
+    current_time: SystemTime,
+    policy: &AttestationPolicy,
+    cache_validity: Duration,
+) -> std::result::Result<Attestation, AttestationError> {
+    // Only production results are cached, so that a relaxed result is never reused.
+    let attestation = if policy.is_production() {
+        ATTESTATION_CACHE.get_or_attest(
+            evidence_bytes,
+            endorsement_bytes,
+            current_time,
+            cache_validity,
+            || attest_with_expiry(evidence_bytes, endorsement_bytes, current_time, policy),
+        )?
+    } else {
+        attest(evidence_bytes, endorsement_bytes, current_time, policy)?
+    };
 
     // 4. Verify the status of the Intel® SGX TCB described in the chain.
-    if let TcbStanding::SWHardeningNeeded { advisory_ids } = attestation.tcb_standing {
-        if advisory_ids
+    if let TcbStanding::SWHardeningNeeded { advisory_ids } = &attestation.tcb_standing {
+        let unaccepted: Vec<String> = advisory_ids
             .iter()
-            .any(|id| !acceptable_sw_advisories.contains(&id.as_str()))
-        {
-            return Err(Error::new(format!(
-                "TCB contains unmitigated unaccepted advisory ids: {advisory_ids:?}"
-            ))
+            .filter(|id| !acceptable_sw_advisories.contains(&id.as_str()))
+            .cloned()
+            .collect();
+        if !unaccepted.is_empty() && !policy.allow_out_of_date_tcb {
+            return Err(TcbError {
+                status: Some(TcbStatus::SWHardeningNeeded),
+                advisory_ids: unaccepted,
+                may_succeed_with_fresh_collateral: false,
+            }
             .into());
         }
     }
 
     // 5. Verify the enclave measurements in the Quote reflect an enclave identity expected.
-    if expected_mrenclave != &attestation.mrenclave {
+    if !policy.accepts_measurement(expected_mrenclave, &attestation.mrenclave) {
         return Err(Error::new(format!(
             "expected mrenclave {}, was {}",
             expected_mrenclave.encode_hex::<String>(),
@@ -115,7 +170,7 @@ pub fn verify_remote_attestation(
         .into());
     }
 
-    Ok(attestation.claims)
+    Ok(attestation)
 }
 
 /// Parses evidence/endorsements and builds a map of metrics
@@ -198,11 +253,38 @@ pub fn attestation_metrics(
 /// - is running the expected binary (via `mrenclave`)
 /// - has a recent enough attestation (via `last_attest_time`)
 /// - has an up to date tcb OR has acceptable SW advisories
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct Attestation {
     tcb_standing: TcbStanding,
     mrenclave: MREnclave,
     claims: HashMap<String, Vec<u8>>,
+    certificate_chain: Vec<CertificateSummary>,
+}
+
+impl Attestation {
+    /// Splits into the enclave's custom claims and a description of what was verified
+    pub(crate) fn into_claims_and_info(
+        self,
+        attested_at: SystemTime,
+    ) -> (HashMap<String, Vec<u8>>, AttestationInfo) {
+        let Self {
+            tcb_standing,
+            mrenclave,
+            claims,
+            certificate_chain,
+        } = self;
+        let accepted_sw_advisories = match tcb_standing {
+            TcbStanding::UpToDate => vec![],
+            TcbStanding::SWHardeningNeeded { advisory_ids } => advisory_ids,
+        };
+        let info = AttestationInfo {
+            measurement: mrenclave.to_vec(),
+            accepted_sw_advisories,
+            attested_at,
+            certificate_chain,
+        };
+        (claims, info)
+    }
 }
 
 /// Validate that the returned report/claims are generated
@@ -215,11 +297,55 @@ fn attest(
     evidence_bytes: &[u8],
     endorsement_bytes: &[u8],
     current_time: SystemTime,
-) -> Result<Attestation> {
+    policy: &AttestationPolicy,
+) -> std::result::Result<Attestation, AttestationError> {
+    attest_with_expiry(evidence_bytes, endorsement_bytes, current_time, policy)
+        .map(|(attestation, _)| attestation)
+}
+
+/// Like [`attest`], but also returns the result of [`collateral_expiry`]
+fn attest_with_expiry(
+    evidence_bytes: &[u8],
+    endorsement_bytes: &[u8],
+    current_time: SystemTime,
+    policy: &AttestationPolicy,
+) -> std::result::Result<(Attestation, Option<SystemTime>), AttestationError> {
+    let (evidence, endorsements) = parse(evidence_bytes, endorsement_bytes)?;
+    let expiry = collateral_expiry(&evidence, &endorsements);
+    let attestation = attest_impl(evidence, endorsements, &INTEL_PKEY, current_time, policy)?;
+    Ok((attestation, expiry))
+}
+
+/// The earliest time at which any certificate chain, revocation list, TCB info, or QE identity
+/// checked by [`verify_collateral`] expires, or `None` if one of them doesn't say
+fn collateral_expiry(evidence: &Evidence, endorsements: &SgxEndorsements) -> Option<SystemTime> {
+    let chains = [
+        &evidence.quote.support.pck_cert_chain,
+        &endorsements.tcb_issuer_chain,
+        &endorsements.pck_issuer_crl_chain,
+        &endorsements.qe_id_issuer_chain,
+    ];
+    let crls = [&endorsements.pck_issuer_crl, &endorsements.root_crl];
+
+    let mut expiry = SystemTime::from(endorsements.tcb_info.next_update)
+        .min(endorsements.qe_id_info.next_update.into());
+    for chain in chains {
+        expiry = expiry.min(chain.not_after().ok()?);
+    }
+    for crl in crls {
+        expiry = expiry.min(crl.next_update()?);
+    }
+    Some(expiry)
+}
+
+fn parse<'a>(
+    evidence_bytes: &'a [u8],
+    endorsement_bytes: &[u8],
+) -> Result<(Evidence<'a>, SgxEndorsements)> {
     let evidence = evidence::Evidence::try_from(evidence_bytes).context("evidence")?;
     let endorsements =
         endorsements::SgxEndorsements::try_from(endorsement_bytes).context("endorsements")?;
-    attest_impl(evidence, endorsements, &INTEL_PKEY, current_time)
+    Ok((evidence, endorsements))
 }
 
 fn attest_impl(
@@ -227,22 +353,48 @@ fn attest_impl(
     endorsements: SgxEndorsements,
     trusted_root_pkey: &PKeyRef<Public>,
     current_time: SystemTime,
-) -> Result<Attestation> {
+    policy: &AttestationPolicy,
+) -> std::result::Result<Attestation, AttestationError> {
+    verify_collateral(&evidence, &endorsements, trusted_root_pkey, current_time)?;
+
+    // find the TCB standing of the enclave
+    let tcb_standing = verify_tcb_status(&evidence, &endorsements, policy)?;
+
+    Ok(verify_report(evidence, tcb_standing, policy)?)
+}
+
+/// Steps 1-3 of [`attest_impl`]: everything up to the platform's TCB standing
+fn verify_collateral(
+    evidence: &Evidence,
+    endorsements: &SgxEndorsements,
+    trusted_root_pkey: &PKeyRef<Public>,
+    current_time: SystemTime,
+) -> Result<()> {
     // 1. Verify the integrity of the signature chain from the Quote to the Intel-issued PCK certificate.
     // 2. Verify no keys in the chain have been revoked.
     // verify the time parameter falls within “not before” and “not after” metadata
-    verify_expiration(current_time, &evidence).context("evidence")?;
-    verify_expiration(current_time, &endorsements).context("endorsements")?;
-    verify_certificates(trusted_root_pkey, &evidence, &endorsements, current_time)?;
+    verify_expiration(current_time, evidence).context("evidence")?;
+    verify_expiration(current_time, endorsements).context("endorsements")?;
+    verify_certificates(
+        trusted_root_pkey,
+        &evidence.quote.support.pck_cert_chain,
+        endorsements,
+        current_time,
+    )?;
 
     // 3. Verify the Quoting Enclave is from a suitable source and is up to date
     // verify the quoting enclave identity
-    verify_enclave_source(&evidence, &endorsements)?;
-    verify_enclave_signatures(&evidence)?;
-
-    // find the TCB standing of the enclave
-    let tcb_standing = verify_tcb_status(&evidence, &endorsements)?;
+    verify_enclave_source(evidence, endorsements)?;
+    verify_enclave_signatures(evidence)?;
+    Ok(())
+}
 
+/// The remainder of [`attest_impl`], once the platform is known to be trustworthy
+fn verify_report(
+    evidence: Evidence,
+    tcb_standing: TcbStanding,
+    policy: &AttestationPolicy,
+) -> Result<Attestation> {
     // everything in the quote is verified. lastly, check the custom claims hash matches
     // the report data, and then return the claims map
     verify_claims_hash(&evidence)?;
@@ -251,19 +403,27 @@ fn attest_impl(
     // build. But, as an extra precaution, verify that the remote
     // enclave is not running in debug mode
     let report = &evidence.quote.quote_body.report_body;
-    if report.has_flag(SgxFlags::DEBUG) {
+    if report.has_flag(SgxFlags::DEBUG) && !policy.allow_debug {
         return Err(Error::new("Application enclave in debug mode"));
     }
 
+    let certificate_chain = evidence
+        .quote
+        .support
+        .pck_cert_chain
+        .summary()
+        .context("pck")?;
+
     Ok(Attestation {
         tcb_standing,
         mrenclave: evidence.quote.quote_body.report_body.mrenclave,
         claims: evidence.claims.map,
+        certificate_chain,
     })
 }
 
 const INTEL_QE_VENDOR_ID: Uuid = uuid::uuid!("939a7233-f79c-4ca9-940a-0db3957f0607");
-static INTEL_PKEY: LazyLock<PKey<Public>> = LazyLock::new(|| {
+pub(crate) static INTEL_PKEY: LazyLock<PKey<Public>> = LazyLock::new(|| {
     let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).expect("allocate curve");
     let mut ctx = BigNumContext::new().expect("allocate bignum");
     let point = EcPoint::from_bytes(&group, INTEL_ROOT_PUB_KEY, &mut ctx)
@@ -274,9 +434,9 @@ static INTEL_PKEY: LazyLock<PKey<Public>> = LazyLock::new(|| {
 
 /// Verify that the various certificate chains and CRLs are rooted
 /// in `trusted_pkey`
-fn verify_certificates(
+pub(crate) fn verify_certificates(
     trusted_pkey: &PKeyRef<Public>,
-    evidence: &Evidence,
+    pck_cert_chain: &CertChain,
     endorsements: &SgxEndorsements,
     current_time: SystemTime,
 ) -> Result<()> {
@@ -309,10 +469,7 @@ fn verify_certificates(
         .tcb_issuer_chain
         .validate_chain(&trusted, &[])
         .context("tcb issuer")?;
-    evidence
-        .quote
-        .support
-        .pck_cert_chain
+    pck_cert_chain
         .validate_chain(&trusted, &[])
         .context("pck")?;
     endorsements
@@ -382,7 +539,7 @@ pub(crate) fn from_trusted(
     build().map_err(|e| Error::from(e).context("building trusted certificate store"))
 }
 
-fn verify_expiration(timestamp: SystemTime, expireable: &dyn Expireable) -> Result<()> {
+pub(crate) fn verify_expiration(timestamp: SystemTime, expireable: &dyn Expireable) -> Result<()> {
     if !expireable.valid_at(timestamp) {
         let epoch_duration = timestamp
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -401,33 +558,44 @@ fn verify_expiration(timestamp: SystemTime, expireable: &dyn Expireable) -> Resu
 /// This follows the steps outlined in:
 /// <https://api.portal.trustedservices.intel.com/documentation#pcs-qe-identity-v3>
 fn verify_enclave_source(evidence: &Evidence, endorsements: &SgxEndorsements) -> Result<()> {
+    verify_quoting_enclave(
+        &evidence.quote.quote_body.qe_vendor_id,
+        &evidence.quote.support.qe_report_body,
+        &endorsements.qe_id_info,
+        EnclaveType::Qe,
+    )
+}
+
+/// Verify that `qe_report_body` is from a valid, up-to-date quoting enclave of type
+/// `expected_type`, as described by `qe_identity`
+pub(crate) fn verify_quoting_enclave(
+    qe_vendor_id: &[u8; 16],
+    qe_report_body: &SgxReportBody,
+    qe_identity: &EnclaveIdentity,
+    expected_type: EnclaveType,
+) -> Result<()> {
     // verify the qe vendor is intel
-    Uuid::from_slice(&evidence.quote.quote_body.qe_vendor_id)
+    Uuid::from_slice(qe_vendor_id)
         .ok()
         .filter(|uuid| uuid == &INTEL_QE_VENDOR_ID)
         .ok_or_else(|| {
             Error::new(format!(
                 "QE Vendor ID: {} not Intel",
-                evidence
-                    .quote
-                    .quote_body
-                    .qe_vendor_id
-                    .encode_hex::<String>()
+                qe_vendor_id.encode_hex::<String>()
             ))
         })?;
 
     // compare mrsigner from QE identity and quote’s QE report
-    let qe_identity = &endorsements.qe_id_info;
-    if qe_identity.mrsigner != evidence.quote.support.qe_report_body.mrsigner {
+    if qe_identity.mrsigner != qe_report_body.mrsigner {
         return Err(Error::new(format!(
             "qe mrsigner mismatch: expected {}, actual {}",
             hex::encode(qe_identity.mrsigner),
-            hex::encode(evidence.quote.support.qe_report_body.mrsigner)
+            hex::encode(qe_report_body.mrsigner)
         )));
     }
 
     // compare isvprodid in report vs collateral
-    let report_isvprodid = evidence.quote.support.qe_report_body.isvprodid.get();
+    let report_isvprodid = qe_report_body.isvprodid.get();
     let collateral_isvprodid = qe_identity.isvprodid;
     if report_isvprodid != collateral_isvprodid {
         return Err(Error::new(format!(
@@ -436,13 +604,13 @@ fn verify_enclave_source(evidence: &Evidence, endorsements: &SgxEndorsements) ->
     }
 
     // compare miscselect from QE identity and masked miscselect from quote’s QE report
-    let qe_report_miscselect = evidence.quote.support.qe_report_body.miscselect.get();
+    let qe_report_miscselect = qe_report_body.miscselect.get();
     if qe_report_miscselect & qe_identity.miscselect_mask.get() != qe_identity.miscselect.get() {
         return Err(Error::new("qe miscselect mismatch"));
     }
 
     // compare attributes from QE identity and masked attributes from quote’s QE report
-    let qe_report_attributes = evidence.quote.support.qe_report_body.sgx_attributes;
+    let qe_report_attributes = qe_report_body.sgx_attributes;
 
     let calculated_mask = qe_identity
         .attributes_mask
@@ -457,7 +625,7 @@ fn verify_enclave_source(evidence: &Evidence, endorsements: &SgxEndorsements) ->
         return Err(Error::new("attributes mismatch"));
     }
 
-    if qe_identity.id != EnclaveType::Qe {
+    if qe_identity.id != expected_type {
         return Err(Error::new(format!(
             "Invalid enclave identity for quoting enclave : {:?}",
             qe_identity.id
@@ -467,7 +635,7 @@ fn verify_enclave_source(evidence: &Evidence, endorsements: &SgxEndorsements) ->
     // Later, we will also lookup the tcb status in the TcbInfo but if
     // the Enclave Identity tcb status isn't up to date, we can fail right
     // away
-    let report_isvsvn = evidence.quote.support.qe_report_body.isvsvn.get();
+    let report_isvsvn = qe_report_body.isvsvn.get();
     let tcb_status = qe_identity.tcb_status(report_isvsvn);
     if tcb_status != &QeTcbStatus::UpToDate {
         return Err(Error::new(format!(
@@ -523,11 +691,34 @@ fn verify_enclave_signatures(evidence: &Evidence) -> Result<()> {
 ///
 /// This follows the steps outlined in:
 /// <https://api.portal.trustedservices.intel.com/documentation#pcs-tcb-info-v3>
-fn verify_tcb_status(evidence: &Evidence, endorsements: &SgxEndorsements) -> Result<TcbStanding> {
+fn verify_tcb_status(
+    evidence: &Evidence,
+    endorsements: &SgxEndorsements,
+    policy: &AttestationPolicy,
+) -> std::result::Result<TcbStanding, AttestationError> {
     // the tcb should be signed by the tcb issuer chain
     let tcb_info = &endorsements.tcb_info;
     let pck_ext = &evidence.quote.support.pck_extension;
 
+    verify_tcb_info_matches(pck_ext, tcb_info)?;
+
+    // Find the tcb status corresponding to our enclave in the tcb info
+    // the consumer of dcap needs to decide which statuses are acceptable (either by
+    // returning this up, or configuring acceptable statuses)
+    match TcbStanding::lookup(pck_ext, tcb_info) {
+        Err(TcbError {
+            status: Some(status),
+            advisory_ids,
+            ..
+        }) if policy.allow_out_of_date_tcb && status != TcbStatus::Revoked => {
+            Ok(TcbStanding::SWHardeningNeeded { advisory_ids })
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Verify that `tcb_info` describes the platform model/PCE version in `pck_ext`
+pub(crate) fn verify_tcb_info_matches(pck_ext: &SgxPckExtension, tcb_info: &TcbInfo) -> Result<()> {
     // make sure the tcb_info matches our enclave's model/PCE version
     if pck_ext.fmspc != tcb_info.fmspc {
         return Err(Error::new(format!(
@@ -541,11 +732,7 @@ fn verify_tcb_status(evidence: &Evidence, endorsements: &SgxEndorsements) -> Res
             &pck_ext.pceid, &tcb_info.pce_id
         )));
     }
-
-    // Find the tcb status corresponding to our enclave in the tcb info
-    // the consumer of dcap needs to decide which statuses are acceptable (either by
-    // returning this up, or configuring acceptable statuses)
-    TcbStanding::lookup(pck_ext, tcb_info)
+    Ok(())
 }
 
 /// Verify that the hash of the custom claims matches
@@ -579,8 +766,8 @@ fn verify_claims_hash(evidence: &Evidence) -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug)]
-enum TcbStanding {
+#[derive(Clone, Debug)]
+pub(crate) enum TcbStanding {
     /// The platform is trusted
     UpToDate,
 
@@ -599,15 +786,43 @@ impl TcbStanding {
     ///
     /// This follows the steps 3.a-b outlined
     /// in <https://api.portal.trustedservices.intel.com/documentation#pcs-tcb-info-v3>
-    fn lookup(pck_extension: &SgxPckExtension, tcb_info: &TcbInfo) -> Result<TcbStanding> {
+    fn lookup(
+        pck_extension: &SgxPckExtension,
+        tcb_info: &TcbInfo,
+    ) -> std::result::Result<TcbStanding, TcbError> {
+        Self::first_matching(tcb_info, |level| Self::in_tcb_level(level, pck_extension))
+    }
+
+    /// Like [`Self::lookup`], but for a TDX platform, where a level only matches if the TD's TEE
+    /// TCB SVN is also >= all of the level's TDX components
+    ///
+    /// This follows step 3.c outlined
+    /// in <https://api.portal.trustedservices.intel.com/documentation#pcs-tcb-info-tdx-v4>
+    pub(crate) fn lookup_tdx(
+        pck_extension: &SgxPckExtension,
+        tcb_info: &TcbInfo,
+        tee_tcb_svn: &[u8; 16],
+    ) -> std::result::Result<TcbStanding, TcbError> {
+        Self::first_matching(tcb_info, |level| {
+            Self::in_tcb_level(level, pck_extension)
+                && level.tcb.tdx_components().is_some_and(|components| {
+                    tee_tcb_svn
+                        .iter()
+                        .zip(components)
+                        .all(|(&svn, level_svn)| svn >= level_svn)
+                })
+        })
+    }
+
+    fn first_matching(
+        tcb_info: &TcbInfo,
+        matches: impl Fn(&TcbLevel) -> bool,
+    ) -> std::result::Result<TcbStanding, TcbError> {
         // Go over the tcb_levels in the provided order and stop on the first tcb level
         // where the pck compsvn/pcesvn is >= tcb compsvn/pcesvn.
         // We assume these are sorted in the correct order based on the tcb info
         // api docs, though intel's dcap implementation re-sorts
-        let first_matching_level = tcb_info
-            .tcb_levels
-            .iter()
-            .find(|level| Self::in_tcb_level(level, pck_extension));
+        let first_matching_level = tcb_info.tcb_levels.iter().find(|level| matches(level));
 
         first_matching_level
             .map(|level| match level.tcb_status {
@@ -615,12 +830,20 @@ impl TcbStanding {
                 TcbStatus::SWHardeningNeeded => Ok(TcbStanding::SWHardeningNeeded {
                     advisory_ids: level.advisory_ids.clone(),
                 }),
-                _ => Err(Error::new(format!(
-                    "invalid tcb status: {:?}",
-                    level.tcb_status
-                ))),
+                status => Err(TcbError {
+                    status: Some(status),
+                    advisory_ids: level.advisory_ids.clone(),
+                    may_succeed_with_fresh_collateral: false,
+                }),
+            })
+            .unwrap_or_else(|| {
+                // The platform may have a newer TCB level than the collateral knows about.
+                Err(TcbError {
+                    status: None,
+                    advisory_ids: vec![],
+                    may_succeed_with_fresh_collateral: true,
+                })
             })
-            .unwrap_or_else(|| Err(Error::new("Unsupported TCB in pck extension")))
     }
 
     /// Returns true if all the pck components are >= all the tcb level components AND
@@ -730,6 +953,32 @@ mod test {
         assert_eq!(expected_pubkey, pubkey.as_slice());
     }
 
+    #[test]
+    fn test_verify_remote_attestation_unaccepted_sw_advisories() {
+        let current_time: SystemTime =
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1674105089000);
+
+        let evidence_bytes = include_bytes!("../tests/data/dcap.evidence");
+        let endorsements_bytes = include_bytes!("../tests/data/dcap.endorsements");
+
+        let error = verify_remote_attestation(
+            evidence_bytes.as_ref(),
+            endorsements_bytes.as_ref(),
+            &EXPECTED_MRENCLAVE,
+            &ACCEPTED_SW_ADVISORIES[..1],
+            current_time,
+        )
+        .expect_err("advisory not accepted");
+        assert_eq!(
+            error.tcb_error(),
+            Some(&TcbError {
+                status: Some(TcbStatus::SWHardeningNeeded),
+                advisory_ids: vec![ACCEPTED_SW_ADVISORIES[1].to_owned()],
+                may_succeed_with_fresh_collateral: false,
+            })
+        );
+    }
+
     #[test]
     fn test_attestation_metrics() {
         const EVIDENCE_BYTES: &[u8] = include_bytes!("../tests/data/dcap.evidence");
@@ -752,6 +1001,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_collateral_expiry() {
+        const EVIDENCE_BYTES: &[u8] = include_bytes!("../tests/data/dcap.evidence");
+        const ENDORSEMENTS_BYTES: &[u8] = include_bytes!("../tests/data/dcap.endorsements");
+        let (evidence, endorsements) = parse(EVIDENCE_BYTES, ENDORSEMENTS_BYTES).unwrap();
+        let expiry = collateral_expiry(&evidence, &endorsements).expect("has expiry");
+
+        // the collateral was valid when the quote was taken...
+        let current_time = SystemTime::UNIX_EPOCH + Duration::from_millis(1674105089000);
+        assert!(expiry > current_time);
+        assert!(endorsements.valid_at(current_time) && evidence.valid_at(current_time));
+        // ...and no later than the TCB info expiry (2023-02-17 21:56:09 UTC)
+        let tcb_info_expiry = SystemTime::UNIX_EPOCH + Duration::from_secs(1676670969);
+        assert!(expiry <= tcb_info_expiry);
+        let after_expiry = expiry + Duration::from_secs(1);
+        assert!(!(endorsements.valid_at(after_expiry) && evidence.valid_at(after_expiry)));
+    }
+
     #[test]
     fn test_verify_remote_attestation_expired_attestation() {
         let current_time: SystemTime =
@@ -772,14 +1039,26 @@ mod test {
 
     #[test]
     fn debug_flag() {
-        let mut builder = FakeAttestation::builder();
-        builder
-            .uevidence
-            .quote
-            .quote_body
-            .report_body
-            .sgx_attributes[0] |= 0x2;
-        assert!(builder.sign().attest().is_err());
+        let debug_builder = || {
+            let mut builder = FakeAttestation::builder();
+            builder
+                .uevidence
+                .quote
+                .quote_body
+                .report_body
+                .sgx_attributes[0] |= 0x2;
+            builder
+        };
+        assert!(debug_builder().sign().attest().is_err());
+
+        let policy = AttestationPolicy {
+            allow_debug: true,
+            ..AttestationPolicy::PRODUCTION
+        };
+        debug_builder()
+            .sign()
+            .attest_with_policy(&policy)
+            .expect("debug allowed");
     }
 
     #[test]
@@ -1001,7 +1280,72 @@ mod test {
         builder.uevidence.quote.support.pck_extension.tcb.compsvn = [0u8; 16];
         builder.uevidence.quote.support.pck_extension.tcb.pcesvn = 0;
         // should fail, there is no tcb level that this pck is greater than
-        assert!(builder.sign().attest().is_err());
+        let error = builder.sign().attest().expect_err("no matching level");
+        assert_eq!(
+            error.tcb_error(),
+            Some(&TcbError {
+                status: None,
+                advisory_ids: vec![],
+                may_succeed_with_fresh_collateral: true,
+            })
+        );
+    }
+
+    #[test]
+    fn out_of_date_tcb_level() {
+        let advisory_ids = vec!["INTEL-SA-1234".to_owned()];
+
+        let mut builder = FakeAttestation::builder();
+        builder.uendorsements.tcb_info.tcb_levels = vec![TcbLevel::from_parts(
+            TcbInfoVersion::V3,
+            [0; 16],
+            0,
+            TcbStatus::OutOfDate,
+            advisory_ids.clone(),
+        )];
+        let error = builder.sign().attest().expect_err("out of date");
+        assert_eq!(
+            error.tcb_error(),
+            Some(&TcbError {
+                status: Some(TcbStatus::OutOfDate),
+                advisory_ids: advisory_ids.clone(),
+                may_succeed_with_fresh_collateral: false,
+            })
+        );
+    }
+
+    #[test]
+    fn out_of_date_tcb_level_allowed_by_policy() {
+        let policy = AttestationPolicy {
+            allow_out_of_date_tcb: true,
+            ..AttestationPolicy::PRODUCTION
+        };
+        let builder_with_status = |status| {
+            let mut builder = FakeAttestation::builder();
+            builder.uendorsements.tcb_info.tcb_levels = vec![TcbLevel::from_parts(
+                TcbInfoVersion::V3,
+                [0; 16],
+                0,
+                status,
+                vec!["INTEL-SA-1234".to_owned()],
+            )];
+            builder
+        };
+
+        let attestation = builder_with_status(TcbStatus::OutOfDate)
+            .sign()
+            .attest_with_policy(&policy)
+            .expect("out of date allowed");
+        assert!(matches!(
+            attestation.tcb_standing,
+            TcbStanding::SWHardeningNeeded { advisory_ids } if advisory_ids == ["INTEL-SA-1234"]
+        ));
+
+        // revoked platforms are never accepted
+        assert!(builder_with_status(TcbStatus::Revoked)
+            .sign()
+            .attest_with_policy(&policy)
+            .is_err());
     }
 
     #[test]
@@ -0,0 +1,257 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Remembers recent successful attestations, so that reconnecting to the same enclave does not
+//! repeat verification of its quote and collateral.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use sha2::Digest;
+
+use crate::dcap::Attestation;
+use crate::enclave::AttestationError;
+
+/// Digest of the evidence (which contains the enclave's measurement and report data) and the
+/// endorsements it was verified against.
+type CacheKey = [u8; 32];
+
+pub(crate) struct AttestationCache {
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+}
+
+struct Entry {
+    verified_at: SystemTime,
+    /// The earliest expiry of any certificate or collateral the attestation was verified with
+    expires_at: SystemTime,
+    attestation: Attestation,
+}
+
+impl Entry {
+    fn is_valid_at(&self, timestamp: SystemTime, validity: Duration) -> bool {
+        // A timestamp before the original verification is treated as a miss, since the
+        // collateral may not have been valid yet.
+        timestamp < self.expires_at
+            && timestamp
+                .duration_since(self.verified_at)
+                .is_ok_and(|age| age < validity)
+    }
+}
+
+impl AttestationCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns a previous result of attesting `evidence_bytes` with `endorsement_bytes` if it was
+    /// produced within `validity` and its collateral hasn't expired since, or else runs `attest`
+    /// and remembers a successful result until its collateral expires.
+    ///
+    /// `attest` returns the attestation along with the earliest expiry of the collateral it was
+    /// checked against, or `None` if that isn't known, in which case it isn't remembered.
+    /// Failures are never cached, and a zero `validity` disables the cache.
+    pub(crate) fn get_or_attest(
+        &self,
+        evidence_bytes: &[u8],
+        endorsement_bytes: &[u8],
+        current_time: SystemTime,
+        validity: Duration,
+        attest: impl FnOnce() -> Result<(Attestation, Option<SystemTime>), AttestationError>,
+    ) -> Result<Attestation, AttestationError> {
+        if validity.is_zero() {
+            return attest().map(|(attestation, _)| attestation);
+        }
+
+        let key = cache_key(evidence_bytes, endorsement_bytes);
+        {
+            let entries = self.entries.lock().expect("not poisoned");
+            if let Some(entry) = entries.get(&key) {
+                if entry.is_valid_at(current_time, validity) {
+                    log::debug!("reusing cached attestation");
+                    return Ok(entry.attestation.clone());
+                }
+            }
+        }
+
+        // Don't hold the lock while verifying; a concurrent miss for the same key just does
+        // redundant work.
+        let (attestation, expires_at) = attest()?;
+
+        let mut entries = self.entries.lock().expect("not poisoned");
+        entries.retain(|_, entry| entry.is_valid_at(current_time, validity));
+        if let Some(expires_at) = expires_at {
+            entries.insert(
+                key,
+                Entry {
+                    verified_at: current_time,
+                    expires_at,
+                    attestation: attestation.clone(),
+                },
+            );
+        }
+        Ok(attestation)
+    }
+}
+
+fn cache_key(evidence_bytes: &[u8], endorsement_bytes: &[u8]) -> CacheKey {
+    let mut hasher = sha2::Sha256::new();
+    // Length-prefix the evidence so the boundary between the two can't shift.
+    hasher.update((evidence_bytes.len() as u64).to_be_bytes());
+    hasher.update(evidence_bytes);
+    hasher.update(endorsement_bytes);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::dcap::{Error, TcbStanding};
+
+    const VALIDITY: Duration = Duration::from_secs(60);
+
+    fn start() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)
+    }
+
+    fn attestation() -> Attestation {
+        Attestation {
+            tcb_standing: TcbStanding::UpToDate,
+            mrenclave: [1; 32],
+            claims: HashMap::new(),
+            certificate_chain: vec![],
+        }
+    }
+
+    /// Runs [`AttestationCache::get_or_attest`] with collateral that expires at `expires_at`,
+    /// returning whether verification actually ran.
+    fn attested_with_expiry(
+        cache: &AttestationCache,
+        endorsements: &[u8],
+        time: SystemTime,
+        validity: Duration,
+        expires_at: Option<SystemTime>,
+    ) -> bool {
+        let ran = Cell::new(false);
+        cache
+            .get_or_attest(b"evidence", endorsements, time, validity, || {
+                ran.set(true);
+                Ok((attestation(), expires_at))
+            })
+            .expect("success");
+        ran.get()
+    }
+
+    /// Like [`attested_with_expiry`], with [`VALIDITY`] and collateral that doesn't expire soon.
+    fn attested(cache: &AttestationCache, endorsements: &[u8], time: SystemTime) -> bool {
+        attested_with_expiry(
+            cache,
+            endorsements,
+            time,
+            VALIDITY,
+            Some(start() + Duration::from_secs(24 * 60 * 60)),
+        )
+    }
+
+    #[test]
+    fn reuses_within_validity() {
+        let cache = AttestationCache::new();
+        let start = start();
+
+        assert!(attested(&cache, b"endorsements", start));
+        assert!(!attested(&cache, b"endorsements", start));
+        assert!(!attested(
+            &cache,
+            b"endorsements",
+            start + VALIDITY - Duration::from_secs(1)
+        ));
+
+        // different collateral is verified separately
+        assert!(attested(&cache, b"other endorsements", start));
+
+        // and so is a check from before the original verification
+        assert!(attested(
+            &cache,
+            b"endorsements",
+            start - Duration::from_secs(1)
+        ));
+    }
+
+    #[test]
+    fn expires() {
+        let cache = AttestationCache::new();
+        let start = start();
+
+        assert!(attested(&cache, b"endorsements", start));
+        assert!(attested(&cache, b"endorsements", start + VALIDITY));
+    }
+
+    #[test]
+    fn expires_with_collateral() {
+        let cache = AttestationCache::new();
+        let start = start();
+        let collateral_expiry = start + Duration::from_secs(1);
+
+        assert!(attested_with_expiry(
+            &cache,
+            b"endorsements",
+            start,
+            VALIDITY,
+            Some(collateral_expiry)
+        ));
+        assert!(!attested(&cache, b"endorsements", start));
+        // well within the validity window, but the collateral has expired
+        assert!(attested(&cache, b"endorsements", collateral_expiry));
+
+        // collateral without a known expiry isn't remembered at all
+        assert!(attested_with_expiry(
+            &cache,
+            b"other endorsements",
+            start,
+            VALIDITY,
+            None
+        ));
+        assert!(attested(&cache, b"other endorsements", start));
+    }
+
+    #[test]
+    fn disabled() {
+        let cache = AttestationCache::new();
+        let start = start();
+        let expires_at = Some(start + VALIDITY);
+
+        assert!(attested_with_expiry(
+            &cache,
+            b"endorsements",
+            start,
+            Duration::ZERO,
+            expires_at
+        ));
+        assert!(attested_with_expiry(
+            &cache,
+            b"endorsements",
+            start,
+            Duration::ZERO,
+            expires_at
+        ));
+    }
+
+    #[test]
+    fn failures_are_not_cached() {
+        let cache = AttestationCache::new();
+        let start = start();
+
+        cache
+            .get_or_attest(b"evidence", b"endorsements", start, VALIDITY, || {
+                Err(Error::new("failed").into())
+            })
+            .expect_err("failure");
+        assert!(attested(&cache, b"endorsements", start));
+    }
+}
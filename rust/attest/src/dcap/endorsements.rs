@@ -351,6 +351,27 @@ mod tests {
             tcb_info.tcb_levels[0].tcb.components()
         );
     }
+
+    #[test]
+    fn parse_tcb_info_tdx() {
+        const DATA: &[u8] = include_bytes!("../../tests/data/tcb_info_tdx.json");
+        let tcb_info: TcbInfo = serde_json::from_slice(DATA).unwrap();
+        assert_eq!(Some(TcbInfoId::Tdx), tcb_info.id);
+        assert_eq!(TcbInfoVersion::V3, tcb_info.version);
+        let tdx_module = tcb_info.tdx_module.expect("has TDX module");
+        assert_eq!([0; 48], tdx_module.mrsigner);
+        assert_eq!([0xff; 8], tdx_module.attributes_mask);
+        assert_eq!(
+            Some([5, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            tcb_info.tcb_levels[0].tcb.tdx_components()
+        );
+
+        const SGX_DATA: &[u8] = include_bytes!("../../tests/data/tcb_info_v3.json");
+        let tcb_info: TcbInfo = serde_json::from_slice(SGX_DATA).unwrap();
+        assert_eq!(Some(TcbInfoId::Sgx), tcb_info.id);
+        assert!(tcb_info.tdx_module.is_none());
+        assert_eq!(None, tcb_info.tcb_levels[0].tcb.tdx_components());
+    }
 }
 
 #[derive(Deserialize)]
@@ -427,6 +448,9 @@ impl TryFrom<u16> for TcbInfoVersion {
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct TcbInfo {
+    /// Which kind of platform this describes; absent before V3
+    #[serde(default)]
+    pub id: Option<TcbInfoId>,
     version: TcbInfoVersion,
     _issue_date: chrono::DateTime<Utc>,
     pub next_update: chrono::DateTime<Utc>,
@@ -437,6 +461,27 @@ pub(crate) struct TcbInfo {
     tcb_type: u16,
     _tcb_evaluation_data_number: u16,
     pub tcb_levels: Vec<TcbLevel>,
+    /// Identifies the TDX module that must be running; only present for TDX
+    #[serde(default)]
+    pub tdx_module: Option<TdxModule>,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum TcbInfoId {
+    Sgx,
+    Tdx,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TdxModule {
+    #[serde(with = "hex")]
+    pub mrsigner: [u8; 48],
+    #[serde(with = "hex")]
+    pub attributes: [u8; 8],
+    #[serde(with = "hex")]
+    pub attributes_mask: [u8; 8],
 }
 
 impl Expireable for TcbInfo {
@@ -492,6 +537,7 @@ impl TcbLevel {
             TcbInfoVersion::V3 => Tcb::V3(TcbV3 {
                 sgxtcbcomponents: tcbcompsvn.map(|x| TcbComponentV3 { svn: x }),
                 pcesvn,
+                tdxtcbcomponents: None,
             }),
         };
         Self {
@@ -503,8 +549,9 @@ impl TcbLevel {
     }
 }
 
+/// The status Intel assigns to a platform's TCB level, as listed in its TCB info.
 #[derive(Debug, Eq, PartialEq, Clone, Copy, Deserialize)]
-pub(crate) enum TcbStatus {
+pub enum TcbStatus {
     UpToDate,
     OutOfDate,
     ConfigurationNeeded,
@@ -535,6 +582,8 @@ impl Tcb {
 pub(crate) struct TcbV3 {
     sgxtcbcomponents: [TcbComponentV3; 16],
     pcesvn: u16,
+    #[serde(default)]
+    tdxtcbcomponents: Option<[TcbComponentV3; 16]>,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -594,6 +643,16 @@ impl Tcb {
             Self::V3(v3) => v3.sgxtcbcomponents.map(|comp| comp.svn),
         }
     }
+
+    /// The TDX TCB components, which are only present in TDX TCB info
+    pub fn tdx_components(&self) -> Option<[u8; 16]> {
+        match self {
+            Self::V2(_) => None,
+            Self::V3(v3) => v3
+                .tdxtcbcomponents
+                .map(|components| components.map(|comp| comp.svn)),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -695,6 +754,9 @@ pub(crate) enum EnclaveType {
     Qe,
     /// Quote Verification Enclave (which we won't use)
     Qve,
+    /// Quoting Enclave for TDX
+    #[serde(rename = "TD_QE")]
+    TdQe,
 }
 
 #[derive(Deserialize, Debug)]
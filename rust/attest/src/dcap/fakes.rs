@@ -30,6 +30,7 @@ use crate::dcap::endorsements::SgxEndorsements;
 use crate::dcap::evidence::Evidence;
 use crate::dcap::revocation_list::RevocationList;
 use crate::dcap::{attest_impl, Attestation};
+use crate::enclave::{AttestationError, AttestationPolicy};
 
 const EVIDENCE_BYTES: &[u8] = include_bytes!("../../tests/data/dcap.evidence");
 const ENDORSEMENT_BYTES: &[u8] = include_bytes!("../../tests/data/dcap.endorsements");
@@ -143,12 +144,20 @@ impl FakeAttestation {
         }
     }
 
-    pub fn attest(self) -> Result<Attestation, super::Error> {
+    pub fn attest(self) -> Result<Attestation, AttestationError> {
+        self.attest_with_policy(&AttestationPolicy::PRODUCTION)
+    }
+
+    pub fn attest_with_policy(
+        self,
+        policy: &AttestationPolicy,
+    ) -> Result<Attestation, AttestationError> {
         attest_impl(
             self.evidence,
             self.endorsements,
             &self.root_key,
             SystemTime::now(),
+            policy,
         )
     }
 }
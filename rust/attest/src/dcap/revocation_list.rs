@@ -58,4 +58,11 @@ impl RevocationList {
     pub fn crl(&self) -> &X509CRLRef {
         self.crl.as_ref()
     }
+
+    /// When the list must be replaced by a newer one, if it says
+    pub fn next_update(&self) -> Option<SystemTime> {
+        self.crl
+            .next_update()
+            .and_then(|next_update| crate::cert_chain::to_system_time(next_update).ok())
+    }
 }
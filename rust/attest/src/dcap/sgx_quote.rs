@@ -216,35 +216,7 @@ impl<'a> SgxQuoteSupport<'a> {
             return Err(Error::new("buffer underflow"));
         }
         let auth_data = util::read_bytes(src, header.auth_data_size.get() as usize);
-        let (cert_key_type, cert_data_size) = util::read_from_bytes::<UInt16LE>(src)
-            .zip(util::read_from_bytes::<UInt32LE>(src))
-            .ok_or_else(|| Error::new("buffer underflow"))?;
-
-        if cert_key_type.get() != CertificationKeyType::PckCertChain as u16 {
-            return Err(Error::new("unsupported certification key type"));
-        }
-        let cert_data_size = cert_data_size.get() as usize;
-
-        if src.len() < cert_data_size {
-            return Err(Error::new("remaining data does not match expected size"));
-        }
-
-        let pck_cert_chain = util::read_bytes(src, cert_data_size);
-        let pck_cert_chain = CertChain::from_pem_data(pck_cert_chain).context("CertChain")?;
-
-        // deserialize the custom intel sgx extension on the pck certificate
-        // find the extension on the pck_cert that has the sgx ext OID
-        let pck_ext = pck_cert_chain
-            .leaf()
-            .extensions()
-            .and_then(|extensions| {
-                extensions
-                    .iter()
-                    .find(|ext| SgxPckExtension::is_pck_ext(ext.object()))
-            })
-            .ok_or_else(|| Error::new("PCK certificate is missing SGX extension"))?;
-        let pck_extension =
-            SgxPckExtension::from_der(pck_ext.data().as_slice()).context("SgxPckExtension")?;
+        let (pck_cert_chain, pck_extension) = read_pck_cert_chain(src)?;
 
         let signature = SgxQuoteSupport {
             isv_signature: ecdsa_signature_from_bytes(&header.signature)
@@ -303,6 +275,42 @@ impl<'a> SgxQuoteSupport<'a> {
     }
 }
 
+/// Read the certification data for a PCK certificate chain from `src`, returning the chain and
+/// the SGX extension on its leaf certificate
+pub(crate) fn read_pck_cert_chain(src: &mut &[u8]) -> super::Result<(CertChain, SgxPckExtension)> {
+    let (cert_key_type, cert_data_size) = util::read_from_bytes::<UInt16LE>(src)
+        .zip(util::read_from_bytes::<UInt32LE>(src))
+        .ok_or_else(|| Error::new("buffer underflow"))?;
+
+    if cert_key_type.get() != CertificationKeyType::PckCertChain as u16 {
+        return Err(Error::new("unsupported certification key type"));
+    }
+    let cert_data_size = cert_data_size.get() as usize;
+
+    if src.len() < cert_data_size {
+        return Err(Error::new("remaining data does not match expected size"));
+    }
+
+    let pck_cert_chain = util::read_bytes(src, cert_data_size);
+    let pck_cert_chain = CertChain::from_pem_data(pck_cert_chain).context("CertChain")?;
+
+    // deserialize the custom intel sgx extension on the pck certificate
+    // find the extension on the pck_cert that has the sgx ext OID
+    let pck_ext = pck_cert_chain
+        .leaf()
+        .extensions()
+        .and_then(|extensions| {
+            extensions
+                .iter()
+                .find(|ext| SgxPckExtension::is_pck_ext(ext.object()))
+        })
+        .ok_or_else(|| Error::new("PCK certificate is missing SGX extension"))?;
+    let pck_extension =
+        SgxPckExtension::from_der(pck_ext.data().as_slice()).context("SgxPckExtension")?;
+
+    Ok((pck_cert_chain, pck_extension))
+}
+
 impl Expireable for SgxQuoteSupport<'_> {
     fn valid_at(&self, timestamp: SystemTime) -> bool {
         self.pck_cert_chain.valid_at(timestamp)
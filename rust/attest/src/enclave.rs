@@ -4,13 +4,16 @@
 //
 
 use std::collections::HashMap;
+use std::time::SystemTime;
 
 use displaydoc::Display;
 use prost::Message;
+use serde::{Deserialize, Serialize};
 
 use crate::client_connection::ClientConnection;
+use crate::dcap::TcbStatus;
 use crate::svr2::RaftConfig;
-use crate::{client_connection, dcap, proto, snow_resolver};
+use crate::{client_connection, dcap, proto, sev_snp, snow_resolver};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -19,16 +22,74 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[error("{message}")]
 pub struct AttestationError {
     message: String,
+    tcb: Option<TcbError>,
+}
+
+impl AttestationError {
+    /// The details, if the enclave's platform was rejected because of its TCB level.
+    pub fn tcb_error(&self) -> Option<&TcbError> {
+        self.tcb.as_ref()
+    }
 }
 
 impl From<dcap::Error> for AttestationError {
     fn from(e: dcap::Error) -> Self {
         Self {
             message: e.to_string(),
+            tcb: None,
+        }
+    }
+}
+
+impl From<sev_snp::Error> for AttestationError {
+    fn from(e: sev_snp::Error) -> Self {
+        Self {
+            message: e.to_string(),
+            tcb: None,
+        }
+    }
+}
+
+impl From<TcbError> for AttestationError {
+    fn from(e: TcbError) -> Self {
+        Self {
+            message: e.to_string(),
+            tcb: Some(e),
         }
     }
 }
 
+/// An enclave's platform is not at an acceptable TCB (Trusted Computing Base) level.
+///
+/// A `status` means the server's platform needs updating. Without one, the collateral the
+/// attestation was checked against didn't describe the platform at all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcbError {
+    /// The status of the platform's TCB level, or `None` if no level matched the platform
+    pub status: Option<TcbStatus>,
+    /// The advisories involved; for [`TcbStatus::SWHardeningNeeded`], only those that were not
+    /// accepted as mitigated
+    pub advisory_ids: Vec<String>,
+    /// Whether verifying again with fresher collateral could succeed
+    pub may_succeed_with_fresh_collateral: bool,
+}
+
+impl std::fmt::Display for TcbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            None => write!(f, "Unsupported TCB in pck extension"),
+            Some(TcbStatus::SWHardeningNeeded) => write!(
+                f,
+                "TCB contains unmitigated unaccepted advisory ids: {:?}",
+                self.advisory_ids
+            ),
+            Some(status) => write!(f, "invalid tcb status: {status:?}"),
+        }
+    }
+}
+
+impl std::error::Error for TcbError {}
+
 /// Error types for an enclave noise session.
 #[derive(Display, Debug, thiserror::Error)]
 pub enum Error {
@@ -52,6 +113,106 @@ impl From<prost::DecodeError> for Error {
     }
 }
 
+/// Relaxations of the checks made when attesting an SGX enclave, for testing against
+/// non-production enclaves without patching the built-in constants.
+///
+/// Production environments must use [`AttestationPolicy::PRODUCTION`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttestationPolicy<'a> {
+    /// Accept enclaves running in debug mode
+    pub allow_debug: bool,
+    /// Accept any platform TCB level short of revoked, along with any advisories it has
+    pub allow_out_of_date_tcb: bool,
+    /// Measurements to accept in addition to the expected one
+    pub alternate_measurements: &'a [&'a [u8]],
+}
+
+impl AttestationPolicy<'_> {
+    /// Makes every check.
+    pub const PRODUCTION: AttestationPolicy<'static> = AttestationPolicy {
+        allow_debug: false,
+        allow_out_of_date_tcb: false,
+        alternate_measurements: &[],
+    };
+
+    /// Whether this policy makes every check, like [`Self::PRODUCTION`].
+    pub fn is_production(&self) -> bool {
+        let Self {
+            allow_debug,
+            allow_out_of_date_tcb,
+            alternate_measurements,
+        } = self;
+        !allow_debug && !allow_out_of_date_tcb && alternate_measurements.is_empty()
+    }
+
+    pub(crate) fn accepts_measurement(&self, expected: &[u8], actual: &[u8]) -> bool {
+        expected == actual || self.alternate_measurements.iter().any(|m| *m == actual)
+    }
+}
+
+impl Default for AttestationPolicy<'_> {
+    fn default() -> Self {
+        AttestationPolicy::PRODUCTION
+    }
+}
+
+/// What was verified when attesting a remote enclave.
+///
+/// The connection has already been checked against these values; this is so callers can log
+/// them or show them to the user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationInfo {
+    /// The measurement the enclave was verified to be running (for SGX, its MRENCLAVE)
+    pub measurement: Vec<u8>,
+    /// Advisories affecting the enclave's platform that were accepted as mitigated by its
+    /// software; empty when the platform is up to date
+    pub accepted_sw_advisories: Vec<String>,
+    /// The time the attestation was checked against
+    pub attested_at: SystemTime,
+    /// The certificate chain the attestation is rooted in, from leaf to root
+    pub certificate_chain: Vec<CertificateSummary>,
+}
+
+/// Identifying details of one certificate in an [`AttestationInfo`]'s chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateSummary {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: SystemTime,
+    pub not_after: SystemTime,
+}
+
+/// A record of an attestation that was accepted when connecting to an enclave, for archiving.
+///
+/// `attestation_message` is exactly what the server sent: its quote or attestation report,
+/// along with the collateral it was verified against. It can be checked again independently by
+/// passing it to the same `new_handshake` function with `attestation_info.attested_at` as the
+/// current time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationTranscript {
+    /// The attestation message the handshake was made with
+    #[serde(with = "hex")]
+    pub attestation_message: Vec<u8>,
+    /// What was verified about the enclave, and when
+    pub attestation_info: AttestationInfo,
+    /// The hash of the Noise handshake that followed, which binds the session to the attestation
+    #[serde(with = "hex")]
+    pub handshake_hash: Vec<u8>,
+}
+
+impl AttestationTranscript {
+    /// Serializes the transcript as JSON.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("can serialize")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).map_err(|e| Error::AttestationDataError {
+            reason: format!("invalid attestation transcript: {e}"),
+        })
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum HandshakeType {
     PreQuantum,
@@ -81,6 +242,7 @@ pub struct Handshake {
     handshake: snow::HandshakeState,
     initial_request: Vec<u8>,
     claims: Claims,
+    attestation_info: AttestationInfo,
 }
 
 impl Handshake {
@@ -89,6 +251,11 @@ impl Handshake {
         &self.initial_request
     }
 
+    /// What was verified about the remote enclave.
+    pub fn attestation_info(&self) -> &AttestationInfo {
+        &self.attestation_info
+    }
+
     /// Completes client connection initiation, returns a valid client connection.
     pub fn complete(mut self, initial_received: &[u8]) -> Result<ClientConnection> {
         self.handshake.read_message(initial_received, &mut [])?;
@@ -101,7 +268,11 @@ impl Handshake {
         })
     }
 
-    pub(crate) fn with_claims(claims: Claims, typ: HandshakeType) -> Result<UnvalidatedHandshake> {
+    pub(crate) fn with_claims(
+        claims: Claims,
+        attestation_info: AttestationInfo,
+        typ: HandshakeType,
+    ) -> Result<UnvalidatedHandshake> {
         let pattern = match typ {
             HandshakeType::PreQuantum => client_connection::NOISE_PATTERN,
             HandshakeType::PostQuantum => client_connection::NOISE_PATTERN_HFS,
@@ -135,6 +306,7 @@ impl Handshake {
             handshake,
             initial_request,
             claims,
+            attestation_info,
         }))
     }
 }
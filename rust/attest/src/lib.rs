@@ -7,13 +7,16 @@
 
 pub mod cds2;
 pub mod client_connection;
+pub mod clock;
 pub mod constants;
 pub mod dcap;
 pub mod enclave;
 pub mod hsm_enclave;
+pub mod sev_snp;
 pub mod sgx_session;
 pub mod snow_resolver;
 pub mod svr2;
+pub mod tdx;
 
 mod cert_chain;
 mod endian;
@@ -0,0 +1,480 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Implements AMD SEV-SNP attestation verification.
+// https://www.amd.com/content/dam/amd/en/documents/epyc-technical-docs/specifications/56860.pdf
+// https://www.amd.com/content/dam/amd/en/documents/epyc-technical-docs/specifications/57230.pdf
+
+// 1. Verify the VCEK certificate is issued by an ASK that is issued by a known AMD root key (ARK).
+// 2. Verify the VCEK was derived for the chip and TCB described in the report.
+// 3. Verify the report is signed by the VCEK.
+// 4. Verify the reported TCB is at least the minimum TCB expected.
+// 5. Verify the guest is not debuggable, and its measurement is the one expected.
+// 6. Verify the report data commits to the attested claims.
+
+use std::sync::LazyLock;
+use std::time::SystemTime;
+
+use boring_signal::ec::EcKeyRef;
+use boring_signal::error::ErrorStack;
+use boring_signal::nid::Nid;
+use boring_signal::pkey::Public;
+use boring_signal::x509::store::{X509Store, X509StoreBuilder};
+use boring_signal::x509::verify::X509VerifyFlags;
+use boring_signal::x509::{X509Ref, X509};
+use hex::ToHex;
+use prost::Message;
+use sha2::Digest;
+
+use crate::cert_chain::CertChain;
+use crate::constants::SNP_MINIMUM_TCB;
+use crate::enclave::{
+    AttestationError, AttestationInfo, Claims, Error as EnclaveError, Handshake, HandshakeType,
+};
+use crate::error::{Context, ContextError};
+use crate::proto::svr;
+use crate::svr2::RaftConfig;
+
+mod report;
+mod vcek;
+
+pub use report::{SnpMeasurement, SnpTcb};
+use report::{SnpPolicy, SnpReport, SnpReportFlags};
+use vcek::VcekExtensions;
+
+pub(crate) struct SnpErrorDomain;
+pub(crate) type Error = ContextError<SnpErrorDomain>;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// AMD root keys for each supported processor family.
+static AMD_ROOT_CERTS: LazyLock<[X509; 2]> = LazyLock::new(|| {
+    [
+        include_bytes!("../res/ark_milan.pem").as_slice(),
+        include_bytes!("../res/ark_genoa.pem").as_slice(),
+    ]
+    .map(|pem| X509::from_pem(pem).expect("static AMD root certificate should parse"))
+});
+
+/// Creates a handshake with an SEV-SNP enclave from its attestation message.
+///
+/// The minimum acceptable TCB is looked up by `measurement`; enclaves without a configured
+/// minimum are rejected.
+pub fn new_handshake(
+    measurement: &[u8],
+    attestation_msg: &[u8],
+    current_time: SystemTime,
+    expected_raft_config: &RaftConfig,
+) -> crate::enclave::Result<Handshake> {
+    let minimum_tcb =
+        SNP_MINIMUM_TCB
+            .get(&measurement)
+            .ok_or_else(|| EnclaveError::AttestationDataError {
+                reason: format!("unknown SEV-SNP measurement {}", hex::encode(measurement)),
+            })?;
+    new_handshake_with_minimum_tcb(
+        measurement,
+        attestation_msg,
+        current_time,
+        expected_raft_config,
+        minimum_tcb,
+    )
+}
+
+pub fn new_handshake_with_minimum_tcb(
+    measurement: &[u8],
+    attestation_msg: &[u8],
+    current_time: SystemTime,
+    expected_raft_config: &RaftConfig,
+    minimum_tcb: &SnpTcb,
+) -> crate::enclave::Result<Handshake> {
+    let measurement: &SnpMeasurement =
+        measurement
+            .try_into()
+            .map_err(|_| EnclaveError::AttestationDataError {
+                reason: "SEV-SNP measurement does not fit expected format".to_string(),
+            })?;
+
+    let handshake_start = svr::ClientHandshakeStart::decode(attestation_msg)?;
+    let evidence = svr::SnpEvidence::decode(handshake_start.evidence.as_slice())?;
+    let endorsements = svr::SnpEndorsements::decode(handshake_start.endorsement.as_slice())?;
+
+    let (attestation_data, attestation_info) = verify_remote_attestation(
+        &evidence,
+        &endorsements,
+        measurement,
+        minimum_tcb,
+        current_time,
+    )
+    .map_err(AttestationError::from)?;
+
+    let claims = Claims::from_attestation_data(attestation_data)?;
+    Ok(
+        Handshake::with_claims(claims, attestation_info, HandshakeType::PostQuantum)?
+            .validate(expected_raft_config)?,
+    )
+}
+
+/// Returns the attested [`svr::AttestationData`] and a description of what was verified when
+/// successful, or a verification error when not.
+///
+/// * `expected_measurement` - The launch measurement the report must match
+/// * `minimum_tcb` - The oldest firmware the platform may be running
+/// * `current_time` - The current system time
+fn verify_remote_attestation(
+    evidence: &svr::SnpEvidence,
+    endorsements: &svr::SnpEndorsements,
+    expected_measurement: &SnpMeasurement,
+    minimum_tcb: &SnpTcb,
+    current_time: SystemTime,
+) -> Result<(svr::AttestationData, AttestationInfo)> {
+    let vcek = X509::from_der(&endorsements.vcek_cert)
+        .map_err(Error::from)
+        .context("VCEK")?;
+    let ask = X509::from_der(&endorsements.ask_cert)
+        .map_err(Error::from)
+        .context("ASK")?;
+
+    // 1. Verify the VCEK certificate is issued by an ASK that is issued by a known ARK.
+    let chain = verify_certificates(&AMD_ROOT_CERTS[..], &vcek, &ask, current_time)?;
+
+    let vcek_key = vcek
+        .public_key()
+        .and_then(|pkey| pkey.ec_key())
+        .map_err(Error::from)
+        .context("VCEK public key")?;
+    let vcek_extensions = VcekExtensions::from_cert(&vcek).context("VCEK extensions")?;
+
+    // Steps 2 through 6 only need the report and the already-validated VCEK.
+    verify_report(
+        &evidence.report,
+        &vcek_key,
+        &vcek_extensions,
+        expected_measurement,
+        minimum_tcb,
+    )?;
+    verify_report_data(&evidence.report, &evidence.attestation_data)?;
+
+    let attestation_data = svr::AttestationData::decode(evidence.attestation_data.as_slice())
+        .map_err(Error::from)
+        .context("attestation data")?;
+    let attestation_info = AttestationInfo {
+        measurement: expected_measurement.to_vec(),
+        accepted_sw_advisories: vec![],
+        attested_at: current_time,
+        certificate_chain: chain.summary().context("certificate chain")?,
+    };
+    Ok((attestation_data, attestation_info))
+}
+
+/// Verify that `vcek` is issued by `ask`, which is issued by one of `roots`, returning the
+/// whole chain.
+fn verify_certificates(
+    roots: &[X509],
+    vcek: &X509Ref,
+    ask: &X509Ref,
+    current_time: SystemTime,
+) -> Result<CertChain> {
+    let root = roots
+        .iter()
+        .find(|root| root.issued(ask).is_ok())
+        .ok_or_else(|| Error::new("ASK is not issued by a known AMD root"))?;
+
+    // AMD's roots are self-signed, but check anyway in case one was replaced by mistake.
+    let root_key = root.public_key()?;
+    if !root.verify(&root_key).unwrap_or(false) {
+        return Err(Error::new("ARK is not self-signed"));
+    }
+
+    let trusted = trust_store(root, current_time).context("trust store")?;
+    let chain = CertChain::new([vcek.to_owned(), ask.to_owned(), root.clone()])
+        .context("certificate chain")?;
+    chain
+        .validate_chain(&trusted, &[])
+        .context("certificate chain")?;
+    Ok(chain)
+}
+
+/// Create a trust store containing only `root`.
+///
+/// AMD publishes CRLs for its ASKs, but not for the short-lived VCEKs, so revocation is handled
+/// by rotating the minimum TCB instead.
+fn trust_store(root: &X509Ref, current_time: SystemTime) -> Result<X509Store> {
+    let build = || -> std::result::Result<X509Store, ErrorStack> {
+        let mut store_builder = X509StoreBuilder::new().expect("can make a fresh X509StoreBuilder");
+        store_builder
+            .param_mut()
+            .set_flags(X509VerifyFlags::X509_STRICT);
+        store_builder.param_mut().set_time(
+            current_time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .expect("current time is after 1970")
+                .as_secs()
+                .try_into()
+                .expect("haven't yet overflowed time_t"),
+        );
+        store_builder.add_cert(root.to_owned())?;
+        Ok(store_builder.build())
+    };
+    build().map_err(|e| Error::from(e).context("building trusted certificate store"))
+}
+
+/// Verify the report itself, given a VCEK that has already been validated.
+fn verify_report(
+    report_bytes: &[u8],
+    vcek_key: &EcKeyRef<Public>,
+    vcek_extensions: &VcekExtensions,
+    expected_measurement: &SnpMeasurement,
+    minimum_tcb: &SnpTcb,
+) -> Result<()> {
+    let (report, signed_bytes) = SnpReport::try_from_bytes(report_bytes).context("report")?;
+
+    // 2. Verify the VCEK was derived for the chip and TCB described in the report.
+    let flags = report.flags();
+    if flags.intersects(SnpReportFlags::SIGNING_KEY) {
+        return Err(Error::new("report is not signed by a VCEK"));
+    }
+    if flags.contains(SnpReportFlags::MASK_CHIP_KEY) {
+        return Err(Error::new("report does not identify its chip"));
+    }
+    if report.chip_id != vcek_extensions.hw_id {
+        return Err(Error::new("VCEK is for a different chip"));
+    }
+    let reported_tcb = report.reported_tcb();
+    if reported_tcb != vcek_extensions.tcb {
+        return Err(Error::new(format!(
+            "VCEK is for TCB {:?}, but report has {reported_tcb:?}",
+            vcek_extensions.tcb
+        )));
+    }
+
+    // 3. Verify the report is signed by the VCEK.
+    if vcek_key.group().curve_name() != Some(Nid::SECP384R1) {
+        return Err(Error::new("VCEK is not a P-384 key"));
+    }
+    let hash = sha2::Sha384::digest(signed_bytes);
+    if !report
+        .signature()
+        .context("signature")?
+        .verify(&hash, vcek_key)
+        .unwrap_or(false)
+    {
+        #[cfg(not(fuzzing))]
+        return Err(Error::new("report did not match signature"));
+    }
+
+    // 4. Verify the reported TCB is at least the minimum TCB expected.
+    if !reported_tcb.is_at_least(minimum_tcb) {
+        return Err(Error::new(format!(
+            "TCB {reported_tcb:?} is older than minimum {minimum_tcb:?}"
+        )));
+    }
+
+    // 5. Verify the guest is not debuggable, and its measurement is the one expected.
+    if report.policy().contains(SnpPolicy::DEBUG) {
+        return Err(Error::new("guest policy allows debugging"));
+    }
+    if report.vmpl.get() != 0 {
+        return Err(Error::new(format!(
+            "report requested from VMPL {}",
+            report.vmpl
+        )));
+    }
+    if &report.measurement != expected_measurement {
+        return Err(Error::new(format!(
+            "expected measurement {}, was {}",
+            expected_measurement.encode_hex::<String>(),
+            report.measurement.encode_hex::<String>(),
+        )));
+    }
+
+    Ok(())
+}
+
+/// 6. Verify the report data is the SHA-512 hash of the serialized attestation data.
+fn verify_report_data(report_bytes: &[u8], attestation_data: &[u8]) -> Result<()> {
+    let (report, _) = SnpReport::try_from_bytes(report_bytes).context("report")?;
+    let expected = sha2::Sha512::digest(attestation_data);
+    if report.report_data[..] != expected[..] {
+        return Err(Error::new("report data does not match attestation data"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use boring_signal::ec::{EcGroup, EcKey};
+    use boring_signal::ecdsa::EcdsaSig;
+    use boring_signal::pkey::Private;
+    use zerocopy::{FromBytes as _, IntoBytes as _};
+
+    use super::*;
+    use crate::cert_chain::testutil::TestCert;
+
+    const MEASUREMENT: SnpMeasurement = [0x42; 48];
+    const HW_ID: [u8; 64] = [0xa5; 64];
+    const TCB: SnpTcb = SnpTcb {
+        boot_loader: 3,
+        tee: 0,
+        snp: 20,
+        microcode: 209,
+    };
+
+    fn vcek_key() -> EcKey<Private> {
+        EcKey::generate(&EcGroup::from_curve_name(Nid::SECP384R1).unwrap()).unwrap()
+    }
+
+    /// Builds a report with the given attestation data, after letting `modify` change it.
+    fn signed_report(
+        key: &EcKeyRef<Private>,
+        attestation_data: &[u8],
+        modify: impl FnOnce(&mut SnpReport),
+    ) -> Vec<u8> {
+        let mut bytes = vec![0; size_of::<SnpReport>()];
+        let report = SnpReport::mut_from_bytes(&mut bytes).unwrap();
+        report.version = 3.into();
+        report.signature_algo = 1.into();
+        report.policy = (SnpPolicy::RESERVED_MUST_BE_ONE | SnpPolicy::SMT)
+            .bits()
+            .into();
+        report.reported_tcb =
+            u64::from_le_bytes([TCB.boot_loader, TCB.tee, 0, 0, 0, 0, TCB.snp, TCB.microcode])
+                .into();
+        report.chip_id = HW_ID;
+        report.measurement = MEASUREMENT;
+        report
+            .report_data
+            .copy_from_slice(&sha2::Sha512::digest(attestation_data));
+        modify(report);
+
+        let hash = sha2::Sha384::digest(&bytes[..0x2A0]);
+        let signature = EcdsaSig::sign(&hash, key).unwrap();
+        let report = SnpReport::mut_from_bytes(&mut bytes).unwrap();
+        for (component, out) in [
+            (signature.r(), &mut report.signature_r),
+            (signature.s(), &mut report.signature_s),
+        ] {
+            let mut little_endian = component.to_vec();
+            little_endian.reverse();
+            out[..little_endian.len()].copy_from_slice(&little_endian);
+        }
+        bytes
+    }
+
+    fn vcek_extensions() -> VcekExtensions {
+        VcekExtensions {
+            tcb: TCB,
+            hw_id: HW_ID,
+        }
+    }
+
+    fn verify(report: &[u8], key: &EcKeyRef<Private>) -> Result<()> {
+        let public = EcKey::from_public_key(key.group(), key.public_key()).unwrap();
+        verify_report(report, &public, &vcek_extensions(), &MEASUREMENT, &TCB)
+    }
+
+    #[test]
+    fn amd_roots_are_self_signed() {
+        for root in AMD_ROOT_CERTS.iter() {
+            assert!(root.verify(&root.public_key().unwrap()).unwrap());
+        }
+    }
+
+    #[test]
+    fn valid_report() {
+        let key = vcek_key();
+        let report = signed_report(&key, b"claims", |_| {});
+        verify(&report, &key).expect("valid");
+        verify_report_data(&report, b"claims").expect("matches");
+        assert!(verify_report_data(&report, b"other claims").is_err());
+    }
+
+    #[test]
+    fn signature_from_other_key() {
+        let report = signed_report(&vcek_key(), b"", |_| {});
+        assert_matches!(verify(&report, &vcek_key()), Err(_));
+    }
+
+    #[test]
+    fn tampered_report() {
+        let key = vcek_key();
+        let mut report = signed_report(&key, b"", |_| {});
+        SnpReport::mut_from_bytes(&mut report).unwrap().measurement[0] ^= 1;
+        assert_matches!(verify(&report, &key), Err(_));
+    }
+
+    #[test]
+    fn rejects_report_contents() {
+        let key = vcek_key();
+        let cases: [(&str, fn(&mut SnpReport)); 5] = [
+            ("measurement", |r| r.measurement = [0; 48]),
+            ("debug", |r| {
+                r.policy = (r.policy.get() | SnpPolicy::DEBUG.bits()).into()
+            }),
+            ("vmpl", |r| r.vmpl = 1.into()),
+            ("chip", |r| r.chip_id = [0; 64]),
+            ("signing key", |r| {
+                r.flags = SnpReportFlags::SIGNING_KEY.bits().into()
+            }),
+        ];
+        for (name, modify) in cases {
+            let report = signed_report(&key, b"", modify);
+            assert!(verify(&report, &key).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn rejects_old_tcb() {
+        let key = vcek_key();
+        let report = signed_report(&key, b"", |_| {});
+        let public = EcKey::from_public_key(key.group(), key.public_key()).unwrap();
+        let minimum = SnpTcb {
+            microcode: TCB.microcode + 1,
+            ..TCB
+        };
+        assert!(
+            verify_report(&report, &public, &vcek_extensions(), &MEASUREMENT, &minimum).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_chain_from_unknown_root() {
+        let chain = TestCert::chain(3);
+        let [vcek, ask, root] = [&chain[0], &chain[1], &chain[2]];
+        verify_certificates(
+            std::slice::from_ref(&root.x509),
+            &vcek.x509,
+            &ask.x509,
+            SystemTime::now(),
+        )
+        .expect("valid chain");
+
+        let other_root = TestCert::self_issued("other");
+        assert!(verify_certificates(
+            std::slice::from_ref(&other_root.x509),
+            &vcek.x509,
+            &ask.x509,
+            SystemTime::now(),
+        )
+        .is_err());
+        assert!(verify_certificates(
+            &AMD_ROOT_CERTS[..],
+            &vcek.x509,
+            &ask.x509,
+            SystemTime::now(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn report_layout_round_trips() {
+        let key = vcek_key();
+        let report = signed_report(&key, b"", |_| {});
+        let (parsed, signed) = SnpReport::try_from_bytes(&report).unwrap();
+        assert_eq!(parsed.as_bytes(), report.as_slice());
+        assert_eq!(signed.len(), 0x2A0);
+    }
+}
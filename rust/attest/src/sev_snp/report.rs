@@ -0,0 +1,224 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! SEV-SNP attestation report, as defined in the SEV Secure Nested Paging Firmware ABI
+//! Specification (AMD publication 56860), "ATTESTATION_REPORT Structure".
+
+use bitflags::bitflags;
+use boring_signal::bn::BigNum;
+use boring_signal::ecdsa::EcdsaSig;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::endian::*;
+use crate::sev_snp::{Error, Result};
+
+pub type SnpMeasurement = [u8; 48];
+
+/// The only signature algorithm defined for attestation reports: ECDSA P-384 with SHA-384.
+const SIG_ALGO_ECDSA_P384_SHA384: u32 = 1;
+
+/// The earliest report version with the fields we check.
+const MIN_REPORT_VERSION: u32 = 2;
+
+/// Length of each signature component, little-endian and zero-padded.
+const SIGNATURE_COMPONENT_LEN: usize = 72;
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub(crate) struct SnpReport {
+    // (0x00)
+    pub version: UInt32LE,
+    _guest_svn: UInt32LE,
+    pub policy: UInt64LE,
+    _family_id: [u8; 16],
+    _image_id: [u8; 16],
+    // (0x30)
+    pub vmpl: UInt32LE,
+    pub signature_algo: UInt32LE,
+    _current_tcb: UInt64LE,
+    _platform_info: UInt64LE,
+    // (0x48)
+    pub flags: UInt32LE,
+    _reserved0: UInt32LE,
+    // (0x50)
+    pub report_data: [u8; 64],
+    // (0x90)
+    pub measurement: SnpMeasurement,
+    _host_data: [u8; 32],
+    _id_key_digest: [u8; 48],
+    _author_key_digest: [u8; 48],
+    _report_id: [u8; 32],
+    _report_id_ma: [u8; 32],
+    // (0x180)
+    pub reported_tcb: UInt64LE,
+    _reserved1: [u8; 24],
+    // (0x1A0)
+    pub chip_id: [u8; 64],
+    _committed_tcb: UInt64LE,
+    _current_version: [u8; 4],
+    _committed_version: [u8; 4],
+    _launch_tcb: UInt64LE,
+    _reserved2: [u8; 168],
+    // (0x2A0)
+    pub signature_r: [u8; SIGNATURE_COMPONENT_LEN],
+    pub signature_s: [u8; SIGNATURE_COMPONENT_LEN],
+    _reserved3: [u8; 368],
+}
+
+static_assertions::const_assert_eq!(1, std::mem::align_of::<SnpReport>());
+static_assertions::const_assert_eq!(0x4A0, std::mem::size_of::<SnpReport>());
+
+/// The signed portion of the report, which is everything before the signature.
+const SIGNED_LEN: usize = 0x2A0;
+
+bitflags! {
+    /// Guest policy bits (see "GUEST_POLICY" in the ABI specification)
+    pub struct SnpPolicy : u64 {
+        const SMT = 1 << 16;
+        const RESERVED_MUST_BE_ONE = 1 << 17;
+        const MIGRATE_MA = 1 << 18;
+        const DEBUG = 1 << 19;
+        const SINGLE_SOCKET = 1 << 20;
+    }
+}
+
+bitflags! {
+    /// Report flags
+    pub struct SnpReportFlags : u32 {
+        const AUTHOR_KEY_EN = 1 << 0;
+        const MASK_CHIP_KEY = 1 << 1;
+        /// Three bits selecting the signing key; zero means the VCEK.
+        const SIGNING_KEY = 0b111 << 2;
+    }
+}
+
+/// The security patch levels of the firmware components that make up a platform's TCB.
+///
+/// Each component is compared separately; one TCB is only "at least" another if every
+/// component is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnpTcb {
+    pub boot_loader: u8,
+    pub tee: u8,
+    pub snp: u8,
+    pub microcode: u8,
+}
+
+impl SnpTcb {
+    /// Whether every component of `self` is at or above the corresponding one in `minimum`.
+    pub fn is_at_least(&self, minimum: &SnpTcb) -> bool {
+        let Self {
+            boot_loader,
+            tee,
+            snp,
+            microcode,
+        } = *self;
+        boot_loader >= minimum.boot_loader
+            && tee >= minimum.tee
+            && snp >= minimum.snp
+            && microcode >= minimum.microcode
+    }
+}
+
+impl From<u64> for SnpTcb {
+    /// Decodes a TCB_VERSION, which has the boot loader SPL in the lowest byte, the TEE SPL in
+    /// the next, the SNP SPL in the second-highest, and the microcode SPL in the highest.
+    fn from(value: u64) -> Self {
+        let bytes = value.to_le_bytes();
+        Self {
+            boot_loader: bytes[0],
+            tee: bytes[1],
+            snp: bytes[6],
+            microcode: bytes[7],
+        }
+    }
+}
+
+impl SnpReport {
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<(&Self, &[u8])> {
+        let report = Self::ref_from_bytes(bytes)
+            .map_err(|_| Error::new(format!("report must be {} bytes", size_of::<Self>())))?;
+
+        if report.version.get() < MIN_REPORT_VERSION {
+            return Err(Error::new(format!(
+                "unsupported report version {}",
+                report.version
+            )));
+        }
+        if report.signature_algo.get() != SIG_ALGO_ECDSA_P384_SHA384 {
+            return Err(Error::new(format!(
+                "unsupported signature algorithm {}",
+                report.signature_algo
+            )));
+        }
+        Ok((report, &bytes[..SIGNED_LEN]))
+    }
+
+    pub fn policy(&self) -> SnpPolicy {
+        SnpPolicy::from_bits_truncate(self.policy.get())
+    }
+
+    pub fn flags(&self) -> SnpReportFlags {
+        SnpReportFlags::from_bits_truncate(self.flags.get())
+    }
+
+    pub fn reported_tcb(&self) -> SnpTcb {
+        self.reported_tcb.get().into()
+    }
+
+    pub fn signature(&self) -> Result<EcdsaSig> {
+        fn component(little_endian: &[u8; SIGNATURE_COMPONENT_LEN]) -> BigNum {
+            let mut big_endian = *little_endian;
+            big_endian.reverse();
+            BigNum::from_slice(&big_endian).expect("can always create a 72-byte bignum")
+        }
+        Ok(EcdsaSig::from_private_components(
+            component(&self.signature_r),
+            component(&self.signature_s),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tcb_from_tcb_version() {
+        let tcb = SnpTcb::from(u64::from_le_bytes([3, 0, 0, 0, 0, 0, 20, 209]));
+        assert_eq!(
+            tcb,
+            SnpTcb {
+                boot_loader: 3,
+                tee: 0,
+                snp: 20,
+                microcode: 209
+            }
+        );
+    }
+
+    #[test]
+    fn tcb_comparison_is_per_component() {
+        let minimum = SnpTcb {
+            boot_loader: 3,
+            tee: 0,
+            snp: 8,
+            microcode: 115,
+        };
+        assert!(minimum.is_at_least(&minimum));
+        assert!(SnpTcb { snp: 9, ..minimum }.is_at_least(&minimum));
+        assert!(!SnpTcb {
+            snp: 9,
+            microcode: 114,
+            ..minimum
+        }
+        .is_at_least(&minimum));
+    }
+
+    #[test]
+    fn rejects_short_report() {
+        assert!(SnpReport::try_from_bytes(&[0; 0x2A0]).is_err());
+    }
+}
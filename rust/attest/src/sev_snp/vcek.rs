@@ -0,0 +1,107 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Extensions on a Versioned Chip Endorsement Key (VCEK) certificate, as defined in the
+//! "Versioned Chip Endorsement Key (VCEK) Certificate and KDS Interface Specification" (AMD
+//! publication 57230).
+
+use boring_signal::x509::X509Ref;
+
+use crate::error::Context;
+use crate::sev_snp::report::SnpTcb;
+use crate::sev_snp::{Error, Result};
+
+const BL_SPL_OID: &str = "1.3.6.1.4.1.3704.1.3.1";
+const TEE_SPL_OID: &str = "1.3.6.1.4.1.3704.1.3.2";
+const SNP_SPL_OID: &str = "1.3.6.1.4.1.3704.1.3.3";
+const UCODE_SPL_OID: &str = "1.3.6.1.4.1.3704.1.3.8";
+const HW_ID_OID: &str = "1.3.6.1.4.1.3704.1.4";
+
+const HW_ID_LEN: usize = 64;
+
+/// The chip and TCB that a VCEK was derived for.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct VcekExtensions {
+    pub tcb: SnpTcb,
+    pub hw_id: [u8; HW_ID_LEN],
+}
+
+impl VcekExtensions {
+    pub fn from_cert(vcek: &X509Ref) -> Result<Self> {
+        let mut boot_loader = None;
+        let mut tee = None;
+        let mut snp = None;
+        let mut microcode = None;
+        let mut hw_id = None;
+
+        for ext in vcek.extensions().into_iter().flatten() {
+            let oid = ext.object().oid_string();
+            let data = ext.data().as_slice();
+            let spl = match oid.as_str() {
+                BL_SPL_OID => &mut boot_loader,
+                TEE_SPL_OID => &mut tee,
+                SNP_SPL_OID => &mut snp,
+                UCODE_SPL_OID => &mut microcode,
+                HW_ID_OID => {
+                    hw_id = Some(parse_hw_id(data)?);
+                    continue;
+                }
+                _ => continue,
+            };
+            *spl = Some(parse_spl(data).with_context(|| oid.clone())?);
+        }
+
+        let missing = |name: &str| Error::new(format!("VCEK is missing the {name} extension"));
+        Ok(Self {
+            tcb: SnpTcb {
+                boot_loader: boot_loader.ok_or_else(|| missing("blSPL"))?,
+                tee: tee.ok_or_else(|| missing("teeSPL"))?,
+                snp: snp.ok_or_else(|| missing("snpSPL"))?,
+                microcode: microcode.ok_or_else(|| missing("ucodeSPL"))?,
+            },
+            hw_id: hw_id.ok_or_else(|| missing("hwID"))?,
+        })
+    }
+}
+
+/// Each security patch level is a DER INTEGER.
+fn parse_spl(data: &[u8]) -> Result<u8> {
+    asn1::parse_single::<u8>(data).map_err(|_| Error::new("invalid security patch level"))
+}
+
+/// The hardware ID is the raw chip ID, though some issuers wrap it in an OCTET STRING.
+fn parse_hw_id(data: &[u8]) -> Result<[u8; HW_ID_LEN]> {
+    let raw = if data.len() == HW_ID_LEN {
+        data
+    } else {
+        asn1::parse_single::<&[u8]>(data).map_err(|_| Error::new("invalid hwID"))?
+    };
+    raw.try_into()
+        .map_err(|_| Error::new("invalid hwID length"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spl() {
+        assert_eq!(parse_spl(&[0x02, 0x01, 0x08]).expect("valid"), 8);
+        assert_eq!(parse_spl(&[0x02, 0x02, 0x00, 0xd1]).expect("valid"), 209);
+        assert!(parse_spl(&[0x02, 0x02, 0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn hw_id() {
+        let raw = [0xa5; HW_ID_LEN];
+        assert_eq!(parse_hw_id(&raw).expect("valid"), raw);
+
+        let mut wrapped = vec![0x04, u8::try_from(HW_ID_LEN).expect("short")];
+        wrapped.extend_from_slice(&raw);
+        assert_eq!(parse_hw_id(&wrapped).expect("valid"), raw);
+
+        assert!(parse_hw_id(&raw[1..]).is_err());
+    }
+}
@@ -10,19 +10,17 @@
 //! [Handshake] to construct a noise encrypted session with the enclave. The attestation
 //! must contain a custom claim with the key name "pk" that represents the enclave's
 //! public key.
-use std::time::Duration;
 
+use crate::clock;
 use crate::dcap::{self, MREnclave};
-use crate::enclave::{Claims, Error, Handshake, HandshakeType, Result, UnvalidatedHandshake};
+use crate::enclave::{
+    AttestationPolicy, Claims, Error, Handshake, HandshakeType, Result, UnvalidatedHandshake,
+};
 
 const INVALID_EVIDENCE: &str = "Evidence does not fit expected format";
 const INVALID_ENDORSEMENT: &str = "Endorsement does not fit expected format";
 const INVALID_MRENCLAVE: &str = "MREnclave value does not fit expected format";
 
-/// How much to offset when checking for time-based validity checks
-/// to adjust for clock skew on clients
-const SKEW_ADJUSTMENT: Duration = Duration::from_secs(24 * 60 * 60);
-
 impl Handshake {
     pub(crate) fn for_sgx(
         mrenclave: &[u8],
@@ -31,6 +29,8 @@ impl Handshake {
         acceptable_sw_advisories: &[&str],
         current_time: std::time::SystemTime,
         handshake_type: HandshakeType,
+        policy: &AttestationPolicy,
+        cache_validity: std::time::Duration,
     ) -> Result<UnvalidatedHandshake> {
         if evidence.is_empty() {
             return Err(Error::AttestationDataError {
@@ -51,15 +51,22 @@ impl Handshake {
                 })?;
 
         // verify the remote attestation and extract the custom claims
-        let claims = dcap::verify_remote_attestation(
+        let attestation = dcap::verify_attestation(
             evidence,
             endorsements,
             &mrenclave,
             acceptable_sw_advisories,
-            current_time + SKEW_ADJUSTMENT,
+            current_time + clock::clock_skew_tolerance(),
+            policy,
+            cache_validity,
         )?;
+        let (claims, attestation_info) = attestation.into_claims_and_info(current_time);
 
-        Self::with_claims(Claims::from_custom_claims(claims)?, handshake_type)
+        Self::with_claims(
+            Claims::from_custom_claims(claims)?,
+            attestation_info,
+            handshake_type,
+        )
     }
 }
 
@@ -103,6 +110,8 @@ pub mod testutil {
             &[],
             current_time,
             HandshakeType::PreQuantum,
+            &AttestationPolicy::PRODUCTION,
+            dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
         )?
         .skip_raft_validation())
     }
@@ -127,6 +136,8 @@ mod tests {
                 &[],
                 time,
                 HandshakeType::PreQuantum,
+                &AttestationPolicy::PRODUCTION,
+                Duration::ZERO,
             );
             assert_eq!(result.is_ok(), expect_success);
         };
@@ -137,19 +148,38 @@ mod tests {
         let valid_end = valid_start + Duration::from_secs(30 * 24 * 60 * 60);
 
         // a request from slightly earlier should succeed
-        test(valid_start - SKEW_ADJUSTMENT, true);
+        test(valid_start - clock::DEFAULT_CLOCK_SKEW_TOLERANCE, true);
 
         // a request from more than the skew before should fail
         test(
-            valid_start - SKEW_ADJUSTMENT - Duration::from_secs(1),
+            valid_start - clock::DEFAULT_CLOCK_SKEW_TOLERANCE - Duration::from_secs(1),
             false,
         );
 
         // an request within a day of expiration will fail from the skew adjustment
-        test(valid_end - SKEW_ADJUSTMENT, false);
+        test(valid_end - clock::DEFAULT_CLOCK_SKEW_TOLERANCE, false);
 
         // earlier than that is fine
-        test(valid_end - SKEW_ADJUSTMENT - Duration::from_secs(1), true);
+        test(
+            valid_end - clock::DEFAULT_CLOCK_SKEW_TOLERANCE - Duration::from_secs(1),
+            true,
+        );
+    }
+
+    #[test]
+    fn test_attestation_info() -> Result<()> {
+        let handshake = testutil::handshake_from_tests_data()?;
+        let info = handshake.attestation_info();
+        assert_eq!(info.measurement, testutil::mrenclave_bytes());
+        assert_eq!(
+            info.attested_at,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1655857680000)
+        );
+        // PCK certificate, platform CA, and root CA
+        assert_eq!(info.certificate_chain.len(), 3);
+        let root = info.certificate_chain.last().expect("non-empty");
+        assert_eq!(root.subject, root.issuer);
+        Ok(())
     }
 
     #[test]
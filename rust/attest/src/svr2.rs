@@ -3,12 +3,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::time::Duration;
+
 use prost::Message;
 
 use crate::constants::{EXPECTED_RAFT_CONFIG_SVR2, SVR2_POSTQUANTUM_OVERRIDE};
-use crate::enclave::{Error, Handshake, HandshakeType, Result};
+use crate::dcap;
+use crate::enclave::{AttestationPolicy, Error, Handshake, HandshakeType, Result};
 use crate::proto::svr;
-use crate::util::get_sw_advisories;
+use crate::util::get_sw_advisories_with_extra;
 
 /// A RaftConfig that can be checked against the attested remote config
 #[derive(Debug)]
@@ -60,25 +63,38 @@ pub fn new_handshake_with_raft_config_lookup(
         attestation_msg,
         current_time,
         expected_raft_config,
+        &[],
+        &AttestationPolicy::PRODUCTION,
+        dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
     )
 }
 
+/// Creates a handshake with an SVR2 enclave.
+///
+/// `extra_sw_advisories` are accepted in addition to the built-in advisories for `mrenclave`, and
+/// `policy` relaxes verification for non-production enclaves, and a successful verification is
+/// reused for up to `cache_validity` (see [`dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY`]).
 pub fn new_handshake(
     mrenclave: &[u8],
     attestation_msg: &[u8],
     current_time: std::time::SystemTime,
     expected_raft_config: &'static RaftConfig,
+    extra_sw_advisories: &[String],
+    policy: &AttestationPolicy,
+    cache_validity: Duration,
 ) -> Result<Handshake> {
     new_handshake_with_constants(
         mrenclave,
         attestation_msg,
         current_time,
-        get_sw_advisories(mrenclave),
+        &get_sw_advisories_with_extra(mrenclave, extra_sw_advisories),
         expected_raft_config,
         SVR2_POSTQUANTUM_OVERRIDE
             .get(&mrenclave)
             .copied()
             .unwrap_or(HandshakeType::PostQuantum),
+        policy,
+        cache_validity,
     )
 }
 
@@ -89,6 +105,8 @@ fn new_handshake_with_constants(
     acceptable_sw_advisories: &[&str],
     expected_raft_config: &RaftConfig,
     handshake_type: HandshakeType,
+    policy: &AttestationPolicy,
+    cache_validity: Duration,
 ) -> Result<Handshake> {
     // Deserialize attestation handshake start.
     let handshake_start = svr::ClientHandshakeStart::decode(attestation_msg)?;
@@ -99,6 +117,8 @@ fn new_handshake_with_constants(
         acceptable_sw_advisories,
         current_time,
         handshake_type,
+        policy,
+        cache_validity,
     )?
     .validate(expected_raft_config)?;
 
@@ -134,6 +154,8 @@ mod tests {
                 simulated: false,
             },
             HandshakeType::PreQuantum,
+            &AttestationPolicy::PRODUCTION,
+            Duration::ZERO,
         )
         .unwrap();
     }
@@ -160,6 +182,8 @@ mod tests {
                 simulated: false,
             },
             HandshakeType::PreQuantum,
+            &AttestationPolicy::PRODUCTION,
+            Duration::ZERO,
         )
         .is_err());
     }
@@ -0,0 +1,401 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Implements Intel TDX attestation verification.
+// https://api.portal.trustedservices.intel.com/documentation#pcs-tcb-info-tdx-v4
+// https://download.01.org/intel-sgx/latest/dcap-latest/linux/docs/Intel_TDX_DCAP_Quoting_Library_API.pdf
+
+// TDX quotes are produced by an SGX quoting enclave (the TD QE), so most of the collateral is the
+// same as for SGX and is verified by the same code in [`crate::dcap`].
+//
+// 1. Verify the integrity of the signature chain from the Quote to the Intel-issued PCK certificate.
+// 2. Verify no keys in the chain have been revoked.
+// 3. Verify the TD Quoting Enclave is from a suitable source and is up to date.
+// 4. Verify the status of the platform and TDX module TCB described in the chain.
+// 5. Verify the TD is not debuggable, and its measurements are the ones expected.
+// 6. Verify the report data commits to the attested claims.
+
+use std::time::SystemTime;
+
+use hex::ToHex;
+use prost::Message;
+use sha2::Digest;
+
+use crate::dcap::ecdsa::EcdsaSigned;
+use crate::dcap::endorsements::{EnclaveType, SgxEndorsements, TcbInfo, TcbInfoId};
+use crate::dcap::{
+    verify_certificates, verify_expiration, verify_quoting_enclave, verify_tcb_info_matches, Error,
+    TcbStanding, INTEL_PKEY,
+};
+use crate::enclave::{
+    AttestationError, AttestationInfo, Claims, Error as EnclaveError, Handshake, HandshakeType,
+};
+use crate::error::Context;
+use crate::proto::svr;
+use crate::svr2::RaftConfig;
+use crate::util;
+
+mod td_quote;
+
+pub use td_quote::TdxMeasurement;
+use td_quote::{TdQuote, TdReportBody};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The measurements a TD must match: its build-time MRTD, and optionally a prefix of its
+/// runtime measurement registers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TdxMeasurements {
+    pub mrtd: TdxMeasurement,
+    pub rtmrs: Vec<TdxMeasurement>,
+}
+
+impl TryFrom<&[u8]> for TdxMeasurements {
+    type Error = EnclaveError;
+
+    /// Parses the MRTD followed by zero to four RTMRs, each 48 bytes.
+    fn try_from(bytes: &[u8]) -> std::result::Result<Self, Self::Error> {
+        let invalid = || EnclaveError::AttestationDataError {
+            reason: "TDX measurements do not fit expected format".to_string(),
+        };
+        let len = std::mem::size_of::<TdxMeasurement>();
+        if bytes.is_empty() || bytes.len() % len != 0 || bytes.len() > 5 * len {
+            return Err(invalid());
+        }
+        let mut measurements = bytes
+            .chunks_exact(len)
+            .map(|chunk| chunk.try_into().expect("correct size"));
+        Ok(Self {
+            mrtd: measurements.next().ok_or_else(invalid)?,
+            rtmrs: measurements.collect(),
+        })
+    }
+}
+
+/// Creates a handshake with a TDX enclave from its attestation message.
+///
+/// `measurements` is the expected MRTD, optionally followed by expected RTMRs; see
+/// [`TdxMeasurements`]. `extra_sw_advisories` are accepted in addition to the built-in
+/// advisories for `measurements`.
+pub fn new_handshake(
+    measurements: &[u8],
+    attestation_msg: &[u8],
+    current_time: SystemTime,
+    expected_raft_config: &RaftConfig,
+    extra_sw_advisories: &[String],
+) -> crate::enclave::Result<Handshake> {
+    let expected_measurements = TdxMeasurements::try_from(measurements)?;
+
+    let handshake_start = svr::ClientHandshakeStart::decode(attestation_msg)?;
+    let evidence = svr::TdxEvidence::decode(handshake_start.evidence.as_slice())?;
+
+    let (attestation_data, attestation_info) = verify_remote_attestation(
+        &evidence,
+        &handshake_start.endorsement,
+        &expected_measurements,
+        &util::get_sw_advisories_with_extra(measurements, extra_sw_advisories),
+        current_time,
+    )
+    .map_err(AttestationError::from)?;
+
+    let claims = Claims::from_attestation_data(attestation_data)?;
+    Ok(
+        Handshake::with_claims(claims, attestation_info, HandshakeType::PostQuantum)?
+            .validate(expected_raft_config)?,
+    )
+}
+
+/// Returns the attested [`svr::AttestationData`] and a description of what was verified when
+/// successful, or a verification error when not.
+///
+/// * `endorsement_bytes` - Open Enclave SGX endorsements for the platform; TDX uses the same
+///   format, with TDX TCB info and the TD QE identity
+/// * `expected_measurements` - The measurements the TD report must match
+/// * `acceptable_sw_advisories` - Advisories known to be mitigated by the expected TD
+/// * `current_time` - The current system time
+fn verify_remote_attestation(
+    evidence: &svr::TdxEvidence,
+    endorsement_bytes: &[u8],
+    expected_measurements: &TdxMeasurements,
+    acceptable_sw_advisories: &[&str],
+    current_time: SystemTime,
+) -> Result<(svr::AttestationData, AttestationInfo)> {
+    let quote = TdQuote::read(&mut evidence.quote.as_slice()).context("quote")?;
+    let endorsements = SgxEndorsements::try_from(endorsement_bytes).context("endorsements")?;
+
+    // 1. Verify the integrity of the signature chain from the Quote to the Intel-issued PCK certificate.
+    // 2. Verify no keys in the chain have been revoked.
+    verify_expiration(current_time, &quote).context("quote")?;
+    verify_expiration(current_time, &endorsements).context("endorsements")?;
+    verify_certificates(
+        &INTEL_PKEY,
+        &quote.support.pck_cert_chain,
+        &endorsements,
+        current_time,
+    )?;
+
+    // 3. Verify the TD Quoting Enclave is from a suitable source and is up to date.
+    verify_quoting_enclave(
+        &quote.quote_body.qe_vendor_id,
+        &quote.support.qe_report_body,
+        &endorsements.qe_id_info,
+        EnclaveType::TdQe,
+    )?;
+    verify_signatures(&quote)?;
+
+    // 4. Verify the status of the platform and TDX module TCB described in the chain.
+    let report = &quote.quote_body.report_body;
+    let tcb_standing = verify_tcb_status(&quote, &endorsements.tcb_info)?;
+    let accepted_sw_advisories = match tcb_standing {
+        TcbStanding::UpToDate => vec![],
+        TcbStanding::SWHardeningNeeded { advisory_ids } => advisory_ids,
+    };
+    if accepted_sw_advisories
+        .iter()
+        .any(|id| !acceptable_sw_advisories.contains(&id.as_str()))
+    {
+        return Err(Error::new(format!(
+            "TCB contains unmitigated unaccepted advisory ids: {accepted_sw_advisories:?}"
+        )));
+    }
+    verify_tdx_module(report, &endorsements.tcb_info)?;
+
+    // 5. Verify the TD is not debuggable, and its measurements are the ones expected.
+    verify_td_report(report, expected_measurements)?;
+
+    // 6. Verify the report data is the SHA-512 hash of the serialized attestation data.
+    let expected = sha2::Sha512::digest(&evidence.attestation_data);
+    if report.report_data[..] != expected[..] {
+        #[cfg(not(fuzzing))]
+        return Err(Error::new("report data does not match attestation data"));
+    }
+
+    let attestation_data = svr::AttestationData::decode(evidence.attestation_data.as_slice())
+        .map_err(Error::from)
+        .context("attestation data")?;
+    let attestation_info = AttestationInfo {
+        measurement: [
+            &expected_measurements.mrtd[..],
+            expected_measurements.rtmrs.as_flattened(),
+        ]
+        .concat(),
+        accepted_sw_advisories,
+        attested_at: current_time,
+        certificate_chain: quote.support.pck_cert_chain.summary().context("pck")?,
+    };
+    Ok((attestation_data, attestation_info))
+}
+
+/// Verify that the QE report is signed by the PCK, that it commits to the attest key, and that the
+/// TD report is signed by the attest key.
+fn verify_signatures(quote: &TdQuote) -> Result<()> {
+    let pck_pkey = quote
+        .support
+        .pck_cert_chain
+        .leaf_pub_key()
+        .context("pck cert chain")?;
+    quote
+        .support
+        .verify_signature(&pck_pkey)
+        .context("QE report")?;
+    quote.support.verify_qe_report().context("QE report")?;
+
+    let attest_key = quote.support.attest_key().context("quote attest key")?;
+    quote.verify_signature(&attest_key).context("TD report")?;
+    Ok(())
+}
+
+/// Find the TCB standing of the platform, including the TDX module's TEE TCB SVN
+fn verify_tcb_status(quote: &TdQuote, tcb_info: &TcbInfo) -> Result<TcbStanding> {
+    if tcb_info.id != Some(TcbInfoId::Tdx) {
+        return Err(Error::new(format!(
+            "expected TDX tcb info, was {:?}",
+            tcb_info.id
+        )));
+    }
+    let pck_ext = &quote.support.pck_extension;
+    verify_tcb_info_matches(pck_ext, tcb_info)?;
+    Ok(TcbStanding::lookup_tdx(
+        pck_ext,
+        tcb_info,
+        &quote.quote_body.report_body.tee_tcb_svn,
+    )?)
+}
+
+/// Verify that the TD is running on the TDX module described by `tcb_info`
+fn verify_tdx_module(report: &TdReportBody, tcb_info: &TcbInfo) -> Result<()> {
+    let tdx_module = tcb_info
+        .tdx_module
+        .as_ref()
+        .ok_or_else(|| Error::new("tcb info is missing TDX module"))?;
+
+    if report.mrsignerseam != tdx_module.mrsigner {
+        return Err(Error::new(format!(
+            "TDX module signer mismatch: expected {}, actual {}",
+            tdx_module.mrsigner.encode_hex::<String>(),
+            report.mrsignerseam.encode_hex::<String>(),
+        )));
+    }
+
+    let masked = |attributes: &[u8; 8]| -> [u8; 8] {
+        std::array::from_fn(|i| attributes[i] & tdx_module.attributes_mask[i])
+    };
+    if masked(&report.seam_attributes) != masked(&tdx_module.attributes) {
+        return Err(Error::new(format!(
+            "TDX module attributes mismatch: expected {}, actual {}",
+            tdx_module.attributes.encode_hex::<String>(),
+            report.seam_attributes.encode_hex::<String>(),
+        )));
+    }
+    Ok(())
+}
+
+/// Verify that the TD is not debuggable, and that its MRTD and any expected RTMRs match
+fn verify_td_report(report: &TdReportBody, expected: &TdxMeasurements) -> Result<()> {
+    // as with SGX, debug TDs should never have expected measurements, but check anyway
+    if report.is_debug() {
+        return Err(Error::new("TD in debug mode"));
+    }
+
+    if report.mrtd != expected.mrtd {
+        return Err(Error::new(format!(
+            "expected mrtd {}, was {}",
+            expected.mrtd.encode_hex::<String>(),
+            report.mrtd.encode_hex::<String>(),
+        )));
+    }
+
+    for (index, (actual, expected)) in report.rtmr.iter().zip(&expected.rtmrs).enumerate() {
+        if actual != expected {
+            return Err(Error::new(format!(
+                "expected rtmr{index} {}, was {}",
+                expected.encode_hex::<String>(),
+                actual.encode_hex::<String>(),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use zerocopy::FromBytes as _;
+
+    use super::*;
+
+    const MRTD: TdxMeasurement = [0x42; 48];
+    const RTMR0: TdxMeasurement = [0x43; 48];
+
+    fn report() -> Vec<u8> {
+        let mut bytes = vec![0; std::mem::size_of::<TdReportBody>()];
+        let report = TdReportBody::mut_from_bytes(&mut bytes).unwrap();
+        report.mrtd = MRTD;
+        report.rtmr[0] = RTMR0;
+        bytes
+    }
+
+    fn tcb_info() -> TcbInfo {
+        serde_json::from_slice(include_bytes!("../tests/data/tcb_info_tdx.json")).unwrap()
+    }
+
+    #[test]
+    fn parse_measurements() {
+        assert_eq!(
+            TdxMeasurements::try_from(&MRTD[..]).expect("valid"),
+            TdxMeasurements {
+                mrtd: MRTD,
+                rtmrs: vec![]
+            }
+        );
+        assert_eq!(
+            TdxMeasurements::try_from([MRTD, RTMR0].concat().as_slice()).expect("valid"),
+            TdxMeasurements {
+                mrtd: MRTD,
+                rtmrs: vec![RTMR0]
+            }
+        );
+
+        assert_matches!(TdxMeasurements::try_from(&[][..]), Err(_));
+        assert_matches!(TdxMeasurements::try_from(&MRTD[1..]), Err(_));
+        assert_matches!(TdxMeasurements::try_from(&[0; 6 * 48][..]), Err(_));
+    }
+
+    #[test]
+    fn td_report() {
+        let bytes = report();
+        let report = TdReportBody::ref_from_bytes(&bytes).unwrap();
+        let mut expected = TdxMeasurements {
+            mrtd: MRTD,
+            rtmrs: vec![],
+        };
+        verify_td_report(report, &expected).expect("only mrtd");
+
+        expected.rtmrs.push(RTMR0);
+        verify_td_report(report, &expected).expect("with rtmr0");
+
+        expected.rtmrs.push(RTMR0);
+        assert_matches!(verify_td_report(report, &expected), Err(_));
+    }
+
+    #[test]
+    fn td_report_rejects_contents() {
+        let expected = TdxMeasurements {
+            mrtd: MRTD,
+            rtmrs: vec![RTMR0],
+        };
+        let cases: [(&str, fn(&mut TdReportBody)); 3] = [
+            ("mrtd", |r| r.mrtd = [0; 48]),
+            ("rtmr", |r| r.rtmr[0] = [0; 48]),
+            ("debug", |r| r.td_attributes[0] |= 1),
+        ];
+        for (name, modify) in cases {
+            let mut bytes = report();
+            let report = TdReportBody::mut_from_bytes(&mut bytes).unwrap();
+            modify(report);
+            assert!(verify_td_report(report, &expected).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn tdx_module() {
+        let tcb_info = tcb_info();
+        let mut bytes = report();
+        let report = TdReportBody::mut_from_bytes(&mut bytes).unwrap();
+        verify_tdx_module(report, &tcb_info).expect("matches");
+
+        report.seam_attributes[0] = 1;
+        assert_matches!(verify_tdx_module(report, &tcb_info), Err(_));
+        report.seam_attributes[0] = 0;
+
+        report.mrsignerseam[0] = 1;
+        assert_matches!(verify_tdx_module(report, &tcb_info), Err(_));
+    }
+
+    #[test]
+    fn tdx_tcb_level() {
+        let tcb_info = tcb_info();
+        let mut pck_extension = crate::dcap::sgx_x509::SgxPckExtension::from_der(include_bytes!(
+            "../tests/data/sgx_x509_extension.der"
+        ))
+        .unwrap();
+        pck_extension.tcb.compsvn = [2, 2, 2, 2, 3, 1, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0];
+        pck_extension.tcb.pcesvn = 13;
+
+        let up_to_date = [5, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_matches!(
+            TcbStanding::lookup_tdx(&pck_extension, &tcb_info, &up_to_date),
+            Ok(TcbStanding::UpToDate)
+        );
+
+        // an older TDX module falls through to the out-of-date level
+        let old_module = [4, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_matches!(
+            TcbStanding::lookup_tdx(&pck_extension, &tcb_info, &old_module),
+            Err(_)
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! TDX quote, as defined in the Intel TDX DCAP Quoting Library API, appendix A.3 ("Version 4
+//! Quote Format").
+//!
+//! See <https://download.01.org/intel-sgx/latest/dcap-latest/linux/docs/Intel_TDX_DCAP_Quoting_Library_API.pdf>
+
+use std::time::SystemTime;
+
+use boring_signal::ecdsa::EcdsaSigRef;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+use crate::dcap::ecdsa::{ecdsa_signature_from_bytes, EcdsaSigned};
+use crate::dcap::sgx_quote::{read_pck_cert_chain, SgxQuoteSupport};
+use crate::dcap::sgx_report_body::SgxReportBody;
+use crate::dcap::Error;
+use crate::endian::*;
+use crate::error::Context;
+use crate::expireable::Expireable;
+use crate::tdx::Result;
+use crate::util;
+
+/// The version of the TDX quote (A.3.1)
+const QUOTE_V4: u16 = 4;
+
+/// The attestation key type for ECDSA-256-with-P-256 curve
+const ATTESTATION_KEY_ECDSA_P256: u16 = 2;
+
+/// The TEE type of a TDX quote; SGX quotes have 0
+const TEE_TYPE_TDX: u32 = 0x81;
+
+/// Certification data type for "QE Report Certification Data" (A.3.11)
+const CERT_DATA_QE_REPORT: u16 = 6;
+
+pub type TdxMeasurement = [u8; 48];
+
+pub(crate) struct TdQuote<'a> {
+    /// The Quote Header (A.3.1) and the TD report (A.3.2)
+    pub quote_body: TdQuoteBody,
+
+    /// Signatures, the quoting enclave report, and the PCK certificate chain, in the same form
+    /// as for an SGX quote
+    pub support: SgxQuoteSupport<'a>,
+}
+
+impl<'a> TdQuote<'a> {
+    /// Read a TdQuote from `bytes`, advancing bytes by the number of bytes consumed
+    pub fn read(bytes: &mut &'a [u8]) -> Result<Self> {
+        let quote_body: TdQuoteBody =
+            util::read_from_bytes(bytes).ok_or_else(|| Error::new("incorrect buffer size"))?;
+        quote_body.check_header()?;
+
+        let signature_len = util::read_from_bytes::<UInt32LE>(bytes)
+            .ok_or_else(|| Error::new("underflow reading signature length"))?
+            .get();
+        if bytes.len() < signature_len as usize {
+            return Err(Error::new("underflow reading signature"));
+        }
+
+        let header: TdSignatureHeader =
+            util::read_from_bytes(bytes).ok_or_else(|| Error::new("incorrect buffer size"))?;
+        if header.cert_data_type.get() != CERT_DATA_QE_REPORT {
+            return Err(Error::new(format!(
+                "unsupported certification data type: {}",
+                header.cert_data_type.get()
+            )));
+        }
+        if bytes.len() < header.auth_data_size.get() as usize {
+            return Err(Error::new("buffer underflow"));
+        }
+        let auth_data = util::read_bytes(bytes, header.auth_data_size.get() as usize);
+        let (pck_cert_chain, pck_extension) = read_pck_cert_chain(bytes)?;
+
+        let support = SgxQuoteSupport {
+            isv_signature: ecdsa_signature_from_bytes(&header.signature).context("td_signature")?,
+            attest_pub_key: header.attest_pub_key,
+            qe_report_body: header.qe_report_body,
+            qe_report_signature: ecdsa_signature_from_bytes(&header.qe_report_signature)
+                .context("qe_report_signature")?,
+            auth_data,
+            pck_cert_chain,
+            pck_extension,
+        };
+
+        Ok(TdQuote {
+            quote_body,
+            support,
+        })
+    }
+}
+
+/// Verifies the signature of the quote header + TD report, which must be signed
+/// by the quoting enclave attest key
+impl EcdsaSigned for TdQuote<'_> {
+    fn data(&self) -> &[u8] {
+        self.quote_body.as_bytes()
+    }
+
+    fn signature(&self) -> &EcdsaSigRef {
+        &self.support.isv_signature
+    }
+}
+
+impl Expireable for TdQuote<'_> {
+    fn valid_at(&self, timestamp: SystemTime) -> bool {
+        self.support.valid_at(timestamp)
+    }
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub(crate) struct TdQuoteBody {
+    // Quote Header (A.3.1)
+    /* (0) */
+    version: UInt16LE,
+    /* (2) */
+    attestation_key_type: UInt16LE,
+    /* (4) */
+    tee_type: UInt32LE,
+    /* (8) */
+    reserved: [u8; 4],
+    /* (12) */
+    pub qe_vendor_id: [u8; 16],
+    /* (28) */
+    user_data: [u8; 20],
+
+    // TD Quote Body (A.3.2)
+    /* (48) */
+    pub report_body: TdReportBody,
+    /* (632) */
+}
+
+static_assertions::const_assert_eq!(1, std::mem::align_of::<TdQuoteBody>());
+static_assertions::const_assert_eq!(632, std::mem::size_of::<TdQuoteBody>());
+
+impl TdQuoteBody {
+    fn check_header(&self) -> Result<()> {
+        if self.version.get() != QUOTE_V4 {
+            return Err(Error::new(format!(
+                "unsupported TDX quote version: {}",
+                self.version.get(),
+            )));
+        }
+        // we only speak ECDSA-256-with-P-256 curve
+        if self.attestation_key_type.get() != ATTESTATION_KEY_ECDSA_P256 {
+            return Err(Error::new(format!(
+                "unsupported TDX attestation key type: {}",
+                self.attestation_key_type.get(),
+            )));
+        }
+        if self.tee_type.get() != TEE_TYPE_TDX {
+            return Err(Error::new(format!(
+                "unsupported TEE type: {:#x}",
+                self.tee_type.get(),
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub(crate) struct TdReportBody {
+    /* (0) TEE TCB security version numbers, compared against tdxtcbcomponents */
+    pub tee_tcb_svn: [u8; 16],
+    /* (16) Measurement of the TDX module */
+    pub mrseam: TdxMeasurement,
+    /* (64) Signer of the TDX module */
+    pub mrsignerseam: TdxMeasurement,
+    /* (112) */
+    pub seam_attributes: [u8; 8],
+    /* (120) */
+    pub td_attributes: [u8; 8],
+    /* (128) */
+    pub xfam: [u8; 8],
+    /* (136) Measurement of the initial contents of the TD */
+    pub mrtd: TdxMeasurement,
+    /* (184) */
+    pub mrconfigid: TdxMeasurement,
+    /* (232) */
+    pub mrowner: TdxMeasurement,
+    /* (280) */
+    pub mrownerconfig: TdxMeasurement,
+    /* (328) Runtime-extendable measurement registers */
+    pub rtmr: [TdxMeasurement; 4],
+    /* (520) */
+    pub report_data: [u8; 64],
+    /* (584) */
+}
+
+static_assertions::const_assert_eq!(1, std::mem::align_of::<TdReportBody>());
+static_assertions::const_assert_eq!(584, std::mem::size_of::<TdReportBody>());
+
+impl TdReportBody {
+    /// TUD.DEBUG, bit 0 of TD_ATTRIBUTES
+    const TD_ATTRIBUTES_DEBUG: u8 = 1;
+
+    pub fn is_debug(&self) -> bool {
+        self.td_attributes[0] & Self::TD_ATTRIBUTES_DEBUG != 0
+    }
+}
+
+/// The fixed-size part of the ECDSA 256-bit Quote Signature Data (A.3.8), with its
+/// QE Report Certification Data (A.3.11) inlined
+#[derive(Debug, FromBytes)]
+#[repr(C)]
+struct TdSignatureHeader {
+    signature: [u8; 64],
+    attest_pub_key: [u8; 64],
+    cert_data_type: UInt16LE,
+    _cert_data_size: UInt32LE,
+    qe_report_body: SgxReportBody,
+    qe_report_signature: [u8; 64],
+    auth_data_size: UInt16LE,
+}
+
+static_assertions::const_assert_eq!(1, std::mem::align_of::<TdSignatureHeader>());
+static_assertions::const_assert_eq!(584, std::mem::size_of::<TdSignatureHeader>());
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quote_body() -> Vec<u8> {
+        let mut bytes = vec![0; std::mem::size_of::<TdQuoteBody>()];
+        let body = TdQuoteBody::mut_from_bytes(&mut bytes).unwrap();
+        body.version = QUOTE_V4.into();
+        body.attestation_key_type = ATTESTATION_KEY_ECDSA_P256.into();
+        body.tee_type = TEE_TYPE_TDX.into();
+        bytes
+    }
+
+    #[test]
+    fn header() {
+        let bytes = quote_body();
+        TdQuoteBody::ref_from_bytes(&bytes)
+            .unwrap()
+            .check_header()
+            .expect("valid header");
+
+        let cases: [(&str, fn(&mut TdQuoteBody)); 3] = [
+            ("version", |b| b.version = 3.into()),
+            ("key type", |b| b.attestation_key_type = 3.into()),
+            ("tee type", |b| b.tee_type = 0.into()),
+        ];
+        for (name, modify) in cases {
+            let mut bytes = quote_body();
+            let body = TdQuoteBody::mut_from_bytes(&mut bytes).unwrap();
+            modify(body);
+            assert!(body.check_header().is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn truncated_signature() {
+        let mut bytes = quote_body();
+        bytes.extend_from_slice(&100u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 50]);
+        assert!(TdQuote::read(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn debug_attribute() {
+        let mut bytes = quote_body();
+        let body = TdQuoteBody::mut_from_bytes(&mut bytes).unwrap();
+        assert!(!body.report_body.is_debug());
+        body.report_body.td_attributes[0] = 1;
+        assert!(body.report_body.is_debug());
+    }
+}
@@ -99,6 +99,22 @@ pub(crate) fn get_sw_advisories(enclave_id: &[u8]) -> &[&str] {
         .unwrap_or(&DEFAULT_SW_ADVISORIES)
 }
 
+/// Returns the built-in advisories for `enclave_id` followed by `extra`.
+///
+/// `extra` can only widen what is accepted; the built-in list is always included.
+pub(crate) fn get_sw_advisories_with_extra<'a>(
+    enclave_id: &[u8],
+    extra: &'a [String],
+) -> Vec<&'a str> {
+    let mut advisories = get_sw_advisories(enclave_id).to_vec();
+    for advisory in extra {
+        if !advisories.contains(&advisory.as_str()) {
+            advisories.push(advisory);
+        }
+    }
+    advisories
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -151,4 +167,18 @@ mod test {
         assert_eq!(&[0u8, 1], front);
         assert_eq!(&[2u8, 3, 4, 5], slice);
     }
+
+    #[test]
+    fn test_sw_advisories_with_extra() {
+        let enclave_id = crate::constants::ENCLAVE_ID_SVR2_STAGING;
+        let builtin = get_sw_advisories(enclave_id);
+        assert!(!builtin.is_empty());
+
+        assert_eq!(builtin, get_sw_advisories_with_extra(enclave_id, &[]));
+
+        let extra = [builtin[0].to_owned(), "INTEL-SA-99999".to_owned()];
+        let mut expected = builtin.to_vec();
+        expected.push("INTEL-SA-99999");
+        assert_eq!(expected, get_sw_advisories_with_extra(enclave_id, &extra));
+    }
 }
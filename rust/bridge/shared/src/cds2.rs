@@ -3,8 +3,8 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use ::attest::cds2;
-use ::attest::enclave::Result;
+use ::attest::enclave::{AttestationPolicy, Result};
+use ::attest::{cds2, dcap};
 use libsignal_bridge_macros::*;
 #[cfg(all(not(target_os = "android"), feature = "jni"))]
 use libsignal_bridge_types::cds2::Cds2Metrics;
@@ -24,6 +24,9 @@ fn new_client(
         mrenclave,
         attestation_msg,
         current_time,
+        &[],
+        &AttestationPolicy::PRODUCTION,
+        dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
     )?)
 }
 
@@ -4,6 +4,7 @@
 //
 
 use futures_util::io::BufReader;
+use futures_util::FutureExt as _;
 use libsignal_account_keys::{AccountEntropyPool, BACKUP_FORWARD_SECRECY_TOKEN_LEN};
 use libsignal_bridge_macros::*;
 use libsignal_bridge_types::message_backup::*;
@@ -11,6 +12,7 @@ use libsignal_message_backup::backup::Purpose;
 use libsignal_message_backup::frame::LimitedReaderFactory;
 use libsignal_message_backup::{BackupReader, FoundUnknownField, ReadError, ReadResult};
 use libsignal_protocol::Aci;
+use rand::RngCore as _;
 
 use crate::io::{AsyncInput, InputStream};
 use crate::support::*;
@@ -155,3 +157,41 @@ fn OnlineBackupValidator_AddFrame(
 fn OnlineBackupValidator_Finalize(backup: &mut OnlineBackupValidator) -> Result<(), ReadError> {
     backup.finalize().map_err(ReadError::with_error_only)
 }
+
+bridge_handle_fns!(MessageBackupWriter, clone = false);
+
+#[bridge_fn]
+fn MessageBackupWriter_New(
+    backup_info_frame: &[u8],
+    purpose: AsType<Purpose, u8>,
+) -> Result<MessageBackupWriter, ReadError> {
+    MessageBackupWriter::from_backup_info_frame(backup_info_frame, purpose.into_inner())
+        .map_err(ReadError::with_error_only)
+}
+
+#[bridge_fn]
+fn MessageBackupWriter_AddFrame(
+    writer: &mut MessageBackupWriter,
+    frame: &[u8],
+) -> Result<(), ReadError> {
+    writer
+        .get_mut()
+        .add_raw_frame(frame)
+        .map_err(ReadError::with_error_only)
+}
+
+#[bridge_fn]
+fn MessageBackupWriter_FinishEncrypted(
+    writer: &mut MessageBackupWriter,
+    key: &MessageBackupKey,
+    forward_secrecy_metadata: Option<&[u8]>,
+) -> Result<Vec<u8>, ReadError> {
+    let mut iv = [0; 16];
+    rand::rng().fill_bytes(&mut iv);
+    writer
+        .take()
+        .finish_encrypted(&key.0, &iv, forward_secrecy_metadata)
+        .now_or_never()
+        .expect("finishing happens in memory")
+        .map_err(ReadError::with_error_only)
+}
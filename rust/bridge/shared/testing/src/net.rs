@@ -120,9 +120,10 @@ fn TESTING_CdsiLookupErrorConvert(
             })
         }
         TestingCdsiLookupError::InvalidResponse => LookupError::InvalidResponse,
-        TestingCdsiLookupError::RetryAfter42Seconds => LookupError::RateLimited(RetryLater {
+        TestingCdsiLookupError::RetryAfter42Seconds => RetryLater {
             retry_after_seconds: 42,
-        }),
+        }
+        .into(),
         TestingCdsiLookupError::InvalidToken => LookupError::InvalidToken,
         TestingCdsiLookupError::InvalidArgument => LookupError::InvalidArgument {
             server_reason: "fake reason".into(),
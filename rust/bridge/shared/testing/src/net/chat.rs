@@ -314,6 +314,7 @@ fn TESTING_ChatConnectErrorConvert(
 make_error_testing_enum! {
     enum TestingChatSendError for SendError {
         RequestTimedOut => RequestTimedOut,
+        Busy => Busy,
         Disconnected => Disconnected,
         ConnectionInvalidated => ConnectionInvalidated,
         ConnectedElsewhere => ConnectedElsewhere,
@@ -330,6 +331,7 @@ fn TESTING_ChatSendErrorConvert(
 ) -> Result<(), SendError> {
     Err(match error_description.into_inner() {
         TestingChatSendError::RequestTimedOut => SendError::RequestTimedOut,
+        TestingChatSendError::Busy => SendError::Busy,
         TestingChatSendError::Disconnected => SendError::Disconnected,
         TestingChatSendError::ConnectionInvalidated => SendError::ConnectionInvalidated,
         TestingChatSendError::ConnectedElsewhere => SendError::ConnectedElsewhere,
@@ -165,6 +165,10 @@ impl<TestE> TestingRequestError<TestE> {
         let TestingRequestError(inner) = self;
         match inner {
             RequestError::Timeout => RequestError::Timeout,
+            RequestError::Busy => RequestError::Busy,
+            RequestError::WouldExceedRateLimit { retry_after } => {
+                RequestError::WouldExceedRateLimit { retry_after }
+            }
             RequestError::Unexpected { log_safe } => RequestError::Unexpected { log_safe },
             RequestError::Other(e) => RequestError::Other(f(e)),
             RequestError::RetryLater(retry_later) => RequestError::RetryLater(retry_later),
@@ -187,6 +191,7 @@ impl<TestE: for<'a> TryFrom<&'a str, Error = strum::ParseError>> TryFrom<String>
     fn try_from(value: String) -> Result<Self, Self::Error> {
         Ok(Self(match value.as_str() {
             "Timeout" => RequestError::Timeout,
+            "Busy" => RequestError::Busy,
             "RequestWasNotValid" => RequestError::Unexpected {
                 log_safe: "the request did not pass server validation".into(),
             },
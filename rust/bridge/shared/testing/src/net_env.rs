@@ -5,6 +5,8 @@
 use std::net::Ipv4Addr;
 use std::num::NonZeroU16;
 
+use attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY;
+use attest::enclave::AttestationPolicy;
 use attest::svr2::RaftConfig;
 use const_str::ip_addr;
 use libsignal_net::enclave::{Cdsi, EnclaveEndpoint, EndpointParams, MrEnclave, SvrSgx};
@@ -53,16 +55,25 @@ const DUMMY_RAFT_CONFIG: &RaftConfig = &RaftConfig {
 const DUMMY_CDSI_ENDPOINT_PARAMS: EndpointParams<'static, Cdsi> = EndpointParams {
     mr_enclave: MrEnclave::new(ENCLAVE_ID_MOCK_SERVER),
     raft_config: (),
+    extra_sw_advisories: &[],
+    attestation_policy: AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 const DUMMY_SVR2_ENDPOINT_PARAMS: EndpointParams<'static, SvrSgx> = EndpointParams {
     mr_enclave: MrEnclave::new(ENCLAVE_ID_MOCK_SERVER),
     raft_config: DUMMY_RAFT_CONFIG,
+    extra_sw_advisories: &[],
+    attestation_policy: AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 const DUMMY_SVRB_ENDPOINT_PARAMS: EndpointParams<'static, SvrSgx> = EndpointParams {
     mr_enclave: MrEnclave::new(ENCLAVE_ID_MOCK_SERVER),
     raft_config: DUMMY_RAFT_CONFIG,
+    extra_sw_advisories: &[],
+    attestation_policy: AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 const DUMMY_KEYTRANS_CONFIG: KeyTransConfig = KeyTransConfig {
@@ -131,7 +131,9 @@ impl ChatListener for ChatListenerStruct {
 
     fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
         let error = match disconnect_cause {
-            DisconnectCause::LocalDisconnect => None,
+            // An idle close is reported like a local disconnect; the app can reconnect when it
+            // next needs the connection.
+            DisconnectCause::LocalDisconnect | DisconnectCause::IdleTimeout => None,
             DisconnectCause::Error(c) => Some(Box::new(SignalFfiError::from(c))),
         };
         (self.0.connection_interrupted)(
@@ -96,6 +96,7 @@ pub enum SignalErrorCode {
     ChatServiceInactive = 149,
     RequestTimedOut = 150,
     RateLimitChallenge = 151,
+    TooManyRequestsInFlight = 152,
 
     SvrDataMissing = 160,
     SvrRestoreFailed = 161,
@@ -392,9 +393,10 @@ impl FfiError for EnclaveError {
 impl IntoFfiError for PinError {
     fn into_ffi_error(self) -> impl Into<SignalFfiError> {
         let code = match self {
-            Self::Argon2Error(_) | Self::DecodingError(_) | Self::MrenclaveLookupError => {
-                SignalErrorCode::InvalidArgument
-            }
+            Self::Argon2Error(_)
+            | Self::DecodingError(_)
+            | Self::MrenclaveLookupError
+            | Self::InvalidHashParams(_) => SignalErrorCode::InvalidArgument,
         };
         SimpleError::new(code, self.to_string())
     }
@@ -495,7 +497,11 @@ impl IntoFfiError for libsignal_net::cdsi::LookupError {
             )
             .into(),
             Self::AttestationError(inner) => inner.into(),
-            Self::RateLimited(inner) => inner.into(),
+            Self::RateLimited(libsignal_net::cdsi::CdsiRateLimit {
+                retry_later,
+                permits_cost: _,
+                remaining_quota: _,
+            }) => retry_later.into(),
             Self::InvalidToken => SimpleError::new(
                 SignalErrorCode::CdsiInvalidToken,
                 "CDSI request token was invalid",
@@ -566,29 +572,43 @@ impl IntoFfiError for libsignal_net::chat::SendError {
     fn into_ffi_error(self) -> impl Into<SignalFfiError> {
         match self {
             Self::WebSocket(e) => {
-                SimpleError::new(SignalErrorCode::WebSocket, format!("WebSocket error: {e}"))
+                SimpleError::new(SignalErrorCode::WebSocket, format!("WebSocket error: {e}")).into()
             }
             Self::IncomingDataInvalid => SimpleError::new(
                 SignalErrorCode::NetworkProtocol,
                 format!("Protocol error: {self}"),
-            ),
+            )
+            .into(),
             Self::RequestHasInvalidHeader => SimpleError::new(
                 SignalErrorCode::InternalError,
                 format!("internal error: {self}"),
-            ),
+            )
+            .into(),
             Self::RequestTimedOut => {
-                SimpleError::new(SignalErrorCode::RequestTimedOut, "Request timed out")
+                SimpleError::new(SignalErrorCode::RequestTimedOut, "Request timed out").into()
             }
+            Self::Busy => SimpleError::new(
+                SignalErrorCode::TooManyRequestsInFlight,
+                "Too many requests in flight",
+            )
+            .into(),
             Self::Disconnected => SimpleError::new(
                 SignalErrorCode::ChatServiceInactive,
                 "Chat service disconnected",
-            ),
+            )
+            .into(),
             Self::ConnectionInvalidated => SimpleError::new(
                 SignalErrorCode::ConnectionInvalidated,
                 "Connection invalidated",
-            ),
+            )
+            .into(),
             Self::ConnectedElsewhere => {
-                SimpleError::new(SignalErrorCode::ConnectedElsewhere, "Connected elsewhere")
+                SimpleError::new(SignalErrorCode::ConnectedElsewhere, "Connected elsewhere").into()
+            }
+            Self::WouldExceedRateLimit { retry_after } => {
+                libsignal_net::infra::errors::RetryLater::rounding_up(retry_after)
+                    .into_ffi_error()
+                    .into()
             }
         }
     }
@@ -613,6 +633,7 @@ impl IntoFfiError for crate::keytrans::BridgeError {
         let code = match self.into() {
             RequestError::Disconnected(inner) => return inner.into_ffi_error().into(),
             RequestError::Timeout => SignalErrorCode::RequestTimedOut,
+            RequestError::Busy => SignalErrorCode::TooManyRequestsInFlight,
             RequestError::Other(libsignal_net_chat::api::keytrans::Error::VerificationFailed(
                 inner,
             )) => match inner {
@@ -625,6 +646,7 @@ impl IntoFfiError for crate::keytrans::BridgeError {
             // TODO: Consider being more consistent with other APIs for RetryLater and
             // ServerSideError. (Challenge shouldn't happen in practice.)
             RequestError::RetryLater(_)
+            | RequestError::WouldExceedRateLimit { .. }
             | RequestError::Challenge { .. }
             | RequestError::ServerSideError
             | RequestError::Unexpected { .. }
@@ -709,11 +731,16 @@ mod registration {
         fn code(&self) -> SignalErrorCode {
             let error_class = match self {
                 RequestError::Timeout => return SignalErrorCode::RequestTimedOut,
+                RequestError::Busy => return SignalErrorCode::TooManyRequestsInFlight,
                 RequestError::ServerSideError | RequestError::Unexpected { log_safe: _ } => {
                     RegistrationError::Unexpected
                 }
                 RequestError::Other(err) => err.into(),
                 RequestError::RetryLater(retry_later) => return retry_later.code(),
+                RequestError::WouldExceedRateLimit { retry_after } => {
+                    return libsignal_net::infra::errors::RetryLater::rounding_up(*retry_after)
+                        .code()
+                }
                 RequestError::Challenge(challenge) => return challenge.code(),
                 RequestError::Disconnected(d) => match *d {},
             };
@@ -726,8 +753,13 @@ mod registration {
         fn provide_retry_after_seconds(&self) -> Result<u32, WrongErrorKind> {
             match self {
                 RequestError::RetryLater(retry_later) => retry_later.provide_retry_after_seconds(),
+                RequestError::WouldExceedRateLimit { retry_after } => {
+                    libsignal_net::infra::errors::RetryLater::rounding_up(*retry_after)
+                        .provide_retry_after_seconds()
+                }
                 RequestError::Other(_)
                 | RequestError::Timeout
+                | RequestError::Busy
                 | RequestError::Challenge(_)
                 | RequestError::ServerSideError
                 | RequestError::Unexpected { .. } => Err(WrongErrorKind),
@@ -743,6 +775,8 @@ mod registration {
                     _ => Err(WrongErrorKind),
                 },
                 RequestError::Timeout
+                | RequestError::Busy
+                | RequestError::WouldExceedRateLimit { .. }
                 | RequestError::RetryLater(_)
                 | RequestError::Challenge(_)
                 | RequestError::ServerSideError
@@ -757,6 +791,8 @@ mod registration {
                     _ => Err(WrongErrorKind),
                 },
                 RequestError::Timeout
+                | RequestError::Busy
+                | RequestError::WouldExceedRateLimit { .. }
                 | RequestError::RetryLater(_)
                 | RequestError::Challenge(_)
                 | RequestError::ServerSideError
@@ -769,6 +805,8 @@ mod registration {
                 RequestError::Challenge(challenge) => Ok(challenge),
                 RequestError::Other(_)
                 | RequestError::Timeout
+                | RequestError::Busy
+                | RequestError::WouldExceedRateLimit { .. }
                 | RequestError::RetryLater(_)
                 | RequestError::ServerSideError
                 | RequestError::Unexpected { .. } => Err(WrongErrorKind),
@@ -121,7 +121,9 @@ impl ChatListener for JniChatListener {
                 Ok(())
             };
             match disconnect_cause {
-                DisconnectCause::LocalDisconnect => {
+                // An idle close is reported like a local disconnect; the app can reconnect when
+                // it next needs the connection.
+                DisconnectCause::LocalDisconnect | DisconnectCause::IdleTimeout => {
                     throw_exception(env, listener, JObject::null().into())?
                 }
                 DisconnectCause::Error(disconnect_cause) => convert_to_exception(
@@ -7,7 +7,7 @@ use std::io::Error as IoError;
 
 use jni::objects::{AutoLocal, GlobalRef, JObject, JString, JThrowable};
 use jni::{JNIEnv, JavaVM};
-use libsignal_net::cdsi::CdsiProtocolError;
+use libsignal_net::cdsi::{CdsiProtocolError, CdsiRateLimit};
 use libsignal_protocol::*;
 
 use super::*;
@@ -158,7 +158,11 @@ impl From<libsignal_net::cdsi::LookupError> for SignalJniError {
             LookupError::CdsiProtocol(CdsiProtocolError::NoTokenInResponse) => {
                 CdsiError::NoTokenInResponse
             }
-            LookupError::RateLimited(retry_later) => CdsiError::RateLimited(retry_later),
+            LookupError::RateLimited(CdsiRateLimit {
+                retry_later,
+                permits_cost: _,
+                remaining_quota: _,
+            }) => CdsiError::RateLimited(retry_later),
             LookupError::ParseError => CdsiError::ParseError,
             LookupError::InvalidToken => CdsiError::InvalidToken,
             LookupError::Server { reason } => CdsiError::Server { reason },
@@ -442,7 +442,8 @@ impl MessageOnlyExceptionJniError for PinError {
         match self {
             PinError::Argon2Error(_)
             | PinError::DecodingError(_)
-            | PinError::MrenclaveLookupError => ClassName("java.lang.IllegalArgumentException"),
+            | PinError::MrenclaveLookupError
+            | PinError::InvalidHashParams(_) => ClassName("java.lang.IllegalArgumentException"),
         }
     }
 }
@@ -558,6 +559,15 @@ mod registration {
                 RequestError::Timeout => {
                     return libsignal_net::chat::SendError::RequestTimedOut.to_throwable(env)
                 }
+                RequestError::Busy => {
+                    return libsignal_net::chat::SendError::Busy.to_throwable(env)
+                }
+                RequestError::WouldExceedRateLimit { retry_after } => {
+                    return libsignal_net::chat::SendError::WouldExceedRateLimit {
+                        retry_after: *retry_after,
+                    }
+                    .to_throwable(env)
+                }
                 RequestError::RetryLater(retry_later) => return retry_later.to_throwable(env),
                 RequestError::Unexpected { log_safe } => log_safe,
                 RequestError::Challenge(rate_limit_challenge) => {
@@ -834,9 +844,9 @@ impl MessageOnlyExceptionJniError for ChatSendError {
             ChatSendError::WebSocket(_)
             | ChatSendError::IncomingDataInvalid
             | ChatSendError::RequestHasInvalidHeader
-            | ChatSendError::RequestTimedOut => {
-                ClassName("org.signal.libsignal.net.ChatServiceException")
-            }
+            | ChatSendError::RequestTimedOut
+            | ChatSendError::WouldExceedRateLimit { .. }
+            | ChatSendError::Busy => ClassName("org.signal.libsignal.net.ChatServiceException"),
         }
     }
 }
@@ -904,7 +914,9 @@ impl MessageOnlyExceptionJniError for crate::keytrans::BridgeError {
         use libsignal_net_chat::api::RequestError;
         match &**self {
             RequestError::Disconnected(inner) => inner.exception_class(),
-            RequestError::Timeout => ClassName("org.signal.libsignal.net.ChatServiceException"),
+            RequestError::Timeout | RequestError::Busy => {
+                ClassName("org.signal.libsignal.net.ChatServiceException")
+            }
             RequestError::Other(libsignal_net_chat::api::keytrans::Error::VerificationFailed(
                 inner,
             )) => match inner {
@@ -919,6 +931,7 @@ impl MessageOnlyExceptionJniError for crate::keytrans::BridgeError {
             // TODO: Consider being more consistent with other APIs for RetryLater and
             // ServerSideError. (Challenge shouldn't happen in practice.)
             RequestError::RetryLater(_)
+            | RequestError::WouldExceedRateLimit { .. }
             | RequestError::Challenge { .. }
             | RequestError::ServerSideError
             | RequestError::Unexpected { .. }
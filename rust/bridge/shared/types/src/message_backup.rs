@@ -7,6 +7,7 @@ use libsignal_account_keys::{
     AccountEntropyPool, BackupForwardSecrecyToken, BackupId, BackupKey,
     BACKUP_FORWARD_SECRECY_TOKEN_LEN, BACKUP_KEY_LEN,
 };
+use libsignal_message_backup::export::BackupWriter;
 use libsignal_message_backup::frame::ValidationError as FrameValidationError;
 use libsignal_message_backup::key::MessageBackupKey as MessageBackupKeyInner;
 use libsignal_message_backup::{backup, Error, FoundUnknownField};
@@ -77,9 +78,12 @@ impl From<Error> for MessageBackupValidationError {
             Error::BackupValidation(e) => Self::String(e.to_string()),
             Error::BackupCompletion(e) => Self::String(e.to_string()),
             Error::Parse(e) => Self::Io(e),
-            e @ Error::NoFrames | e @ Error::InvalidProtobuf(_) | e @ Error::HmacMismatch(_) => {
-                Self::String(e.to_string())
-            }
+            e @ Error::NoFrames
+            | e @ Error::InvalidProtobuf(_)
+            | e @ Error::HmacMismatch(_)
+            | e @ Error::Cancelled
+            | e @ Error::TooLargeToExport(_)
+            | e @ Error::Compression(_) => Self::String(e.to_string()),
         }
     }
 }
@@ -158,3 +162,36 @@ impl Drop for OnlineBackupValidator {
         }
     }
 }
+
+pub struct MessageBackupWriter {
+    writer: Option<BackupWriter>,
+}
+
+impl MessageBackupWriter {
+    pub fn from_backup_info_frame(
+        backup_info: &[u8],
+        purpose: backup::Purpose,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            writer: Some(BackupWriter::new_raw(backup_info, purpose)?),
+        })
+    }
+
+    pub fn get_mut(&mut self) -> &mut BackupWriter {
+        self.writer
+            .as_mut()
+            .expect("MessageBackupWriter has not yet been finished")
+    }
+
+    pub fn take(&mut self) -> BackupWriter {
+        self.writer
+            .take()
+            .expect("MessageBackupWriter has not yet been finished")
+    }
+}
+
+// See the note on OnlineBackupValidator above; the same reasoning applies.
+impl std::panic::RefUnwindSafe for MessageBackupWriter {}
+static_assertions::assert_impl_all!(MessageBackupWriter: std::panic::UnwindSafe);
+
+bridge_as_handle!(MessageBackupWriter, mut = true);
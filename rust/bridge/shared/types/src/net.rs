@@ -8,6 +8,7 @@ use std::panic::RefUnwindSafe;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use libsignal_net::chat::{InFlightLimitConfig, RateLimitConfig};
 use libsignal_net::connect_state::{
     ConnectState, ConnectionResources, DefaultConnectorFactory, PreconnectingFactory,
     SUGGESTED_CONNECT_CONFIG, SUGGESTED_TLS_PRECONNECT_LIFETIME,
@@ -16,13 +17,13 @@ use libsignal_net::enclave::{EnclaveEndpoint, EnclaveKind};
 use libsignal_net::env::{Env, UserAgent};
 use libsignal_net::infra::dns::DnsResolver;
 use libsignal_net::infra::route::{
-    ConnectionProxyConfig, DirectOrProxyProvider, RouteProvider, RouteProviderExt as _,
-    UnresolvedWebsocketServiceRoute,
+    ConnectionProxyConfig, DirectOrProxyProvider, IpOverrides, IpPreference, RouteProvider,
+    RouteProviderExt as _, UnresolvedWebsocketServiceRoute,
 };
 use libsignal_net::infra::tcp_ssl::{InvalidProxyConfig, TcpSslConnector};
 use libsignal_net::infra::{AsHttpHeader as _, EnableDomainFronting};
 
-use self::remote_config::{RemoteConfig, RemoteConfigKeys};
+use self::remote_config::{RemoteConfig, RemoteConfigKeys, RemoteConfigValue};
 use crate::*;
 
 pub mod cdsi;
@@ -107,6 +108,17 @@ impl EnclaveConnectionResources<'_> {
     }
 }
 
+/// Replaces the proxy settings of a [`ConnectionManager`] for a single connection.
+///
+/// The manager's own settings, used by every other connection, are left as they are.
+#[derive(Clone, Debug)]
+pub enum ProxyOverride {
+    /// Connect directly, even if the manager is set to use a proxy.
+    Direct,
+    /// Connect through this proxy rather than the manager's.
+    Proxy(ConnectionProxyConfig),
+}
+
 pub struct ConnectionManager {
     env: Env<'static>,
     user_agent: UserAgent,
@@ -193,6 +205,21 @@ impl ConnectionManager {
         guard.proxy().map(|proxy| proxy.is_some())
     }
 
+    /// The proxy to use for a new connection, taking `proxy_override` into account.
+    pub(crate) fn proxy_config_for_connection(
+        &self,
+        proxy_override: Option<&ProxyOverride>,
+    ) -> Result<Option<ConnectionProxyConfig>, InvalidProxyConfig> {
+        match proxy_override {
+            None => (&*self.transport_connector.lock().expect("not poisoned")).try_into(),
+            Some(ProxyOverride::Direct) => Ok(None),
+            Some(ProxyOverride::Proxy(proxy)) => Ok(Some(proxy.clone())),
+        }
+    }
+
+    /// Allows or disallows connecting over IPv6.
+    ///
+    /// This does not change the [`IpPreference`] set with [`Self::set_ip_preference`].
     pub fn set_ipv6_enabled(&self, ipv6_enabled: bool) {
         let mut guard = self.transport_connector.lock().expect("not poisoned");
         guard.set_ipv6_enabled(ipv6_enabled);
@@ -203,6 +230,29 @@ impl ConnectionManager {
             .allow_ipv6 = ipv6_enabled;
     }
 
+    /// Sets which address families to try first for future connection attempts.
+    ///
+    /// If IPv6 has been disabled with [`Self::set_ipv6_enabled`], IPv6 routes are still skipped.
+    pub fn set_ip_preference(&self, ip_preference: IpPreference) {
+        self.connect
+            .lock()
+            .expect("not poisoned")
+            .route_resolver
+            .ip_preference = ip_preference;
+    }
+
+    /// Replaces the set of hostnames that should be connected to at fixed addresses instead of
+    /// being looked up with DNS.
+    ///
+    /// Only affects future connection attempts.
+    pub fn set_ip_overrides(&self, ip_overrides: IpOverrides) {
+        self.connect
+            .lock()
+            .expect("not poisoned")
+            .route_resolver
+            .ip_overrides = ip_overrides;
+    }
+
     /// Resets the endpoint connections to include or exclude censorship circumvention routes.
     ///
     /// This is not itself a network change event; existing working connections are expected to
@@ -226,6 +276,84 @@ impl ConnectionManager {
         *self.remote_config.lock().expect("not poisoned") = RemoteConfig::new(remote_config);
     }
 
+    /// Advisories to accept from SGX and TDX enclaves beyond the ones built into libsignal, as
+    /// set by remote config.
+    pub(crate) fn extra_sw_advisories(&self) -> Vec<String> {
+        match self
+            .remote_config
+            .lock()
+            .expect("not poisoned")
+            .get(RemoteConfigKeys::AcceptedSgxAdvisories)
+        {
+            RemoteConfigValue::Disabled => vec![],
+            RemoteConfigValue::Enabled(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|advisory| !advisory.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    /// The in-flight request limit for new chat connections, as set by remote config.
+    ///
+    /// An unparseable value is logged and treated as no limit.
+    pub(crate) fn chat_in_flight_limit(&self) -> Option<InFlightLimitConfig> {
+        let value = match self
+            .remote_config
+            .lock()
+            .expect("not poisoned")
+            .get(RemoteConfigKeys::ChatInFlightLimit)
+        {
+            RemoteConfigValue::Disabled => return None,
+            RemoteConfigValue::Enabled(value) => value,
+        };
+        let parse = || {
+            let (max_in_flight, max_queued) = match value.split_once(',') {
+                Some((max_in_flight, max_queued)) => {
+                    (max_in_flight, max_queued.trim().parse().ok()?)
+                }
+                None => (value.as_str(), 0),
+            };
+            Some(InFlightLimitConfig {
+                max_in_flight: max_in_flight.trim().parse().ok()?,
+                max_queued,
+            })
+        };
+        let limit = parse();
+        if limit.is_none() {
+            log::warn!("ignoring invalid chat in-flight limit from remote config");
+        }
+        limit
+    }
+
+    /// The request rate limit for new chat connections, as set by remote config.
+    ///
+    /// An unparseable value is logged and treated as no limit.
+    pub(crate) fn chat_rate_limit(&self) -> Option<RateLimitConfig> {
+        let value = match self
+            .remote_config
+            .lock()
+            .expect("not poisoned")
+            .get(RemoteConfigKeys::ChatRateLimit)
+        {
+            RemoteConfigValue::Disabled => return None,
+            RemoteConfigValue::Enabled(value) => value,
+        };
+        let parse = || {
+            let (burst, refill_interval_ms) = value.split_once(',')?;
+            Some(RateLimitConfig {
+                burst: burst.trim().parse().ok()?,
+                refill_interval: Duration::from_millis(refill_interval_ms.trim().parse().ok()?),
+            })
+        };
+        let limit = parse();
+        if limit.is_none() {
+            log::warn!("ignoring invalid chat rate limit from remote config");
+        }
+        limit
+    }
+
     const NETWORK_CHANGE_DEBOUNCE: Duration = Duration::from_secs(1);
 
     pub fn on_network_change(&self, now: Instant) {
@@ -261,8 +389,23 @@ impl ConnectionManager {
         ),
         InvalidProxyConfig,
     > {
-        let proxy_config: Option<libsignal_net::infra::route::ConnectionProxyConfig> =
-            (&*self.transport_connector.lock().expect("not poisoned")).try_into()?;
+        self.enclave_connection_resources_with_proxy_override(enclave, None)
+    }
+
+    /// Like [`Self::enclave_connection_resources`], but with the proxy settings replaced by
+    /// `proxy_override` if present.
+    pub fn enclave_connection_resources_with_proxy_override(
+        &self,
+        enclave: &EnclaveEndpoint<impl EnclaveKind>,
+        proxy_override: Option<&ProxyOverride>,
+    ) -> Result<
+        (
+            EnclaveConnectionResources,
+            impl RouteProvider<Route = UnresolvedWebsocketServiceRoute> + '_,
+        ),
+        InvalidProxyConfig,
+    > {
+        let proxy_config = self.proxy_config_for_connection(proxy_override)?;
 
         let (enable_domain_fronting, enforce_minimum_tls) = {
             let guard = self.endpoints.lock().expect("not poisoned");
@@ -324,6 +467,124 @@ mod test {
         assert_matches!(err, ConnectError::InvalidConnectionConfiguration);
     }
 
+    #[test]
+    fn proxy_override_applies_to_one_connection() {
+        let cm =
+            ConnectionManager::new(Environment::Staging, "test-user-agent", Default::default());
+        cm.set_invalid_proxy();
+
+        assert_matches!(
+            cm.proxy_config_for_connection(Some(&ProxyOverride::Direct)),
+            Ok(None)
+        );
+        let proxy =
+            ConnectionProxyConfig::from_parts("socks5", "localhost", None, None).expect("valid");
+        assert_matches!(
+            cm.proxy_config_for_connection(Some(&ProxyOverride::Proxy(proxy))),
+            Ok(Some(ConnectionProxyConfig::Socks(_)))
+        );
+
+        // The manager's own settings are unchanged.
+        assert_matches!(
+            cm.proxy_config_for_connection(None),
+            Err(InvalidProxyConfig)
+        );
+        assert_matches!(cm.is_using_proxy(), Err(InvalidProxyConfig));
+    }
+
+    #[test_case(true; "disable IPv6 first")]
+    #[test_case(false; "set preference first")]
+    fn ipv6_toggle_keeps_ip_preference(disable_ipv6_first: bool) {
+        let cm =
+            ConnectionManager::new(Environment::Staging, "test-user-agent", Default::default());
+        if disable_ipv6_first {
+            cm.set_ipv6_enabled(false);
+            cm.set_ip_preference(IpPreference::PreferIpv4);
+        } else {
+            cm.set_ip_preference(IpPreference::PreferIpv4);
+            cm.set_ipv6_enabled(false);
+        }
+
+        let resolver = cm
+            .connect
+            .lock()
+            .expect("not poisoned")
+            .route_resolver
+            .clone();
+        assert!(!resolver.allow_ipv6);
+        assert_eq!(resolver.ip_preference, IpPreference::PreferIpv4);
+
+        cm.set_ipv6_enabled(true);
+        let resolver = cm
+            .connect
+            .lock()
+            .expect("not poisoned")
+            .route_resolver
+            .clone();
+        assert!(resolver.allow_ipv6);
+        assert_eq!(resolver.ip_preference, IpPreference::PreferIpv4);
+    }
+
+    #[test]
+    fn extra_sw_advisories_from_remote_config() {
+        let cm =
+            ConnectionManager::new(Environment::Staging, "test-user-agent", Default::default());
+        assert_eq!(cm.extra_sw_advisories(), Vec::<String>::new());
+
+        cm.set_remote_config(HashMap::from([(
+            "acceptedSgxAdvisories".to_owned(),
+            "INTEL-SA-00001, INTEL-SA-00002,,".to_owned(),
+        )]));
+        assert_eq!(
+            cm.extra_sw_advisories(),
+            vec!["INTEL-SA-00001".to_owned(), "INTEL-SA-00002".to_owned()]
+        );
+    }
+
+    #[test_case("" => None; "empty")]
+    #[test_case("0" => None; "zero in flight")]
+    #[test_case("x,1" => None; "not a number")]
+    #[test_case("4" => Some((4, 0)); "no queue")]
+    #[test_case("4, 16" => Some((4, 16)); "with queue")]
+    fn chat_in_flight_limit_from_remote_config(value: &str) -> Option<(usize, usize)> {
+        let cm =
+            ConnectionManager::new(Environment::Staging, "test-user-agent", Default::default());
+        assert_eq!(cm.chat_in_flight_limit(), None);
+
+        cm.set_remote_config(HashMap::from([(
+            "chatInFlightLimit".to_owned(),
+            value.to_owned(),
+        )]));
+        cm.chat_in_flight_limit().map(
+            |InFlightLimitConfig {
+                 max_in_flight,
+                 max_queued,
+             }| (max_in_flight.get(), max_queued),
+        )
+    }
+
+    #[test_case("" => None; "empty")]
+    #[test_case("5" => None; "no interval")]
+    #[test_case("0,1000" => None; "zero burst")]
+    #[test_case("5,x" => None; "not a number")]
+    #[test_case("5, 1000" => Some((5, 1000)); "valid")]
+    fn chat_rate_limit_from_remote_config(value: &str) -> Option<(u32, u128)> {
+        let cm =
+            ConnectionManager::new(Environment::Staging, "test-user-agent", Default::default());
+        assert_eq!(cm.chat_rate_limit(), None);
+
+        cm.set_remote_config(HashMap::from([(
+            "chatRateLimit".to_owned(),
+            value.to_owned(),
+        )]));
+        cm.chat_rate_limit().map(
+            |RateLimitConfig {
+                 burst,
+                 refill_interval,
+             }| (burst.get(), refill_interval.as_millis()),
+        )
+    }
+
     #[test]
     fn network_change_event_debounced() {
         let cm =
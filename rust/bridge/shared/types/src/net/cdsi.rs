@@ -5,6 +5,7 @@
 
 use libsignal_net::auth::Auth;
 use libsignal_net::cdsi::{self, CdsiConnection, ClientResponseCollector, Token};
+use libsignal_net::enclave::EndpointParams;
 use libsignal_net::infra::errors::RetryLater;
 use libsignal_net::infra::tcp_ssl::InvalidProxyConfig;
 
@@ -65,11 +66,17 @@ impl CdsiLookup {
                 )
             })?;
 
+        let extra_sw_advisories = connection_manager.extra_sw_advisories();
+        let params = EndpointParams {
+            extra_sw_advisories: &extra_sw_advisories,
+            ..env_cdsi.params.clone()
+        };
+
         let connected = CdsiConnection::connect_with(
             connection_resources.as_connection_resources(),
             route_provider,
             env_cdsi.ws_config,
-            &env_cdsi.params,
+            &params,
             auth,
         )
         .await?;
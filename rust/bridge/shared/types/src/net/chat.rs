@@ -26,8 +26,7 @@ use libsignal_net::chat::{
 };
 use libsignal_net::connect_state::ConnectionResources;
 use libsignal_net::infra::route::{
-    ConnectionProxyConfig, DirectOrProxyProvider, RouteProvider, RouteProviderExt,
-    UnresolvedHttpsServiceRoute,
+    DirectOrProxyProvider, RouteProvider, RouteProviderExt, UnresolvedHttpsServiceRoute,
 };
 use libsignal_net::infra::tcp_ssl::InvalidProxyConfig;
 use libsignal_net::infra::{Connection as _, EnableDomainFronting, EnforceMinimumTls};
@@ -36,7 +35,7 @@ use libsignal_protocol::Timestamp;
 use static_assertions::assert_impl_all;
 
 use crate::net::remote_config::RemoteConfigKeys;
-use crate::net::ConnectionManager;
+use crate::net::{ConnectionManager, ProxyOverride};
 use crate::*;
 
 pub type ChatConnectionInfo = ConnectionInfo;
@@ -83,11 +82,22 @@ impl UnauthenticatedChatConnection {
     pub async fn connect(
         connection_manager: &ConnectionManager,
         languages: LanguageList,
+    ) -> Result<Self, ConnectError> {
+        Self::connect_with_proxy_override(connection_manager, languages, None).await
+    }
+
+    /// Like [`Self::connect`], but with the connection manager's proxy settings replaced by
+    /// `proxy_override` if present.
+    pub async fn connect_with_proxy_override(
+        connection_manager: &ConnectionManager,
+        languages: LanguageList,
+        proxy_override: Option<&ProxyOverride>,
     ) -> Result<Self, ConnectError> {
         let inner = establish_chat_connection(
             "unauthenticated",
             connection_manager,
             Some(UnauthenticatedChatHeaders { languages }.into()),
+            proxy_override,
         )
         .await?;
         Ok(Self {
@@ -106,6 +116,25 @@ impl AuthenticatedChatConnection {
         auth: Auth,
         receive_stories: bool,
         languages: LanguageList,
+    ) -> Result<Self, ConnectError> {
+        Self::connect_with_proxy_override(
+            connection_manager,
+            auth,
+            receive_stories,
+            languages,
+            None,
+        )
+        .await
+    }
+
+    /// Like [`Self::connect`], but with the connection manager's proxy settings replaced by
+    /// `proxy_override` if present.
+    pub async fn connect_with_proxy_override(
+        connection_manager: &ConnectionManager,
+        auth: Auth,
+        receive_stories: bool,
+        languages: LanguageList,
+        proxy_override: Option<&ProxyOverride>,
     ) -> Result<Self, ConnectError> {
         let inner = establish_chat_connection(
             "authenticated",
@@ -118,6 +147,7 @@ impl AuthenticatedChatConnection {
                 }
                 .into(),
             ),
+            proxy_override,
         )
         .await?;
 
@@ -142,6 +172,7 @@ impl AuthenticatedChatConnection {
             connection_manager,
             enable_domain_fronting,
             enforce_minimum_tls,
+            None,
         )?
         .map_routes(|r| r.inner);
         let connection_resources = ConnectionResources {
@@ -265,7 +296,7 @@ pub(crate) async fn connect_registration_chat(
     connection_manager: &ConnectionManager,
     drop_on_disconnect: tokio::sync::oneshot::Sender<Infallible>,
 ) -> Result<Unauth<ChatConnection>, ConnectError> {
-    let pending = establish_chat_connection("registration", connection_manager, None).await?;
+    let pending = establish_chat_connection("registration", connection_manager, None, None).await?;
 
     let mut on_disconnect = Some(drop_on_disconnect);
     let listener = move |event| match event {
@@ -332,6 +363,7 @@ async fn establish_chat_connection(
     auth_type: &'static str,
     connection_manager: &ConnectionManager,
     headers: Option<chat::ChatHeaders>,
+    proxy_override: Option<&ProxyOverride>,
 ) -> Result<chat::PendingChatConnection, ConnectError> {
     let noise_shadow = headers.as_ref().and_then(|headers| {
         let (languages, remote_config) = match headers {
@@ -369,6 +401,9 @@ async fn establish_chat_connection(
     let libsignal_net::infra::ws::Config {
         local_idle_timeout,
         remote_idle_disconnect_timeout,
+        max_frame_size,
+        max_message_size,
+        application_idle_timeout,
         ..
     } = env.chat_ws_config;
 
@@ -385,6 +420,7 @@ async fn establish_chat_connection(
         connection_manager,
         enable_domain_fronting,
         enforce_minimum_tls,
+        proxy_override,
     )?;
 
     log::info!("connecting {auth_type} chat");
@@ -397,6 +433,11 @@ async fn establish_chat_connection(
             local_idle_timeout,
             remote_idle_timeout: remote_idle_disconnect_timeout,
             initial_request_id: 0,
+            max_frame_size,
+            max_message_size,
+            application_idle_timeout,
+            rate_limit: connection_manager.chat_rate_limit(),
+            in_flight_limit: connection_manager.chat_in_flight_limit(),
         },
         headers,
         auth_type,
@@ -435,19 +476,13 @@ fn make_route_provider(
     connection_manager: &ConnectionManager,
     enable_domain_fronting: EnableDomainFronting,
     enforce_minimum_tls: EnforceMinimumTls,
+    proxy_override: Option<&ProxyOverride>,
 ) -> Result<impl RouteProvider<Route = UnresolvedHttpsServiceRoute>, ConnectError> {
-    let ConnectionManager {
-        env,
-        transport_connector,
-        ..
-    } = connection_manager;
+    let proxy_config = connection_manager
+        .proxy_config_for_connection(proxy_override)
+        .map_err(|InvalidProxyConfig| ConnectError::InvalidConnectionConfiguration)?;
 
-    let proxy_config: Option<ConnectionProxyConfig> =
-        (&*transport_connector.lock().expect("not poisoned"))
-            .try_into()
-            .map_err(|InvalidProxyConfig| ConnectError::InvalidConnectionConfiguration)?;
-
-    let chat_connect = &env.chat_domain_config.connect;
+    let chat_connect = &connection_manager.env.chat_domain_config.connect;
 
     Ok(DirectOrProxyProvider::maybe_proxied(
         chat_connect.route_provider_with_options(enable_domain_fronting, enforce_minimum_tls),
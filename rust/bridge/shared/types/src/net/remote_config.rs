@@ -44,6 +44,15 @@ pub enum RemoteConfigKeys {
     ShadowAuthChatWithNoiseDirect,
     /// If enabled, tries to connect via Noise Direct after establishing an unauthenticated chat connection.
     ShadowUnauthChatWithNoiseDirect,
+    /// A comma-separated list of Intel security advisories to accept from SGX and TDX enclaves,
+    /// in addition to the ones built into libsignal.
+    AcceptedSgxAdvisories,
+    /// Limits how many requests each chat connection has outstanding at once, as
+    /// `max_in_flight[,max_queued]`; see [`libsignal_net::chat::InFlightLimitConfig`].
+    ChatInFlightLimit,
+    /// Limits how quickly each chat connection sends requests, as `burst,refill_interval_ms`;
+    /// see [`libsignal_net::chat::RateLimitConfig`].
+    ChatRateLimit,
 }
 
 pub enum RemoteConfigValue {
@@ -65,6 +74,15 @@ impl From<RemoteConfigKeys> for RemoteConfigKey {
             RemoteConfigKeys::ShadowUnauthChatWithNoiseDirect => RemoteConfigKey {
                 raw_key: "shadowUnauthChatWithNoise",
             },
+            RemoteConfigKeys::AcceptedSgxAdvisories => RemoteConfigKey {
+                raw_key: "acceptedSgxAdvisories",
+            },
+            RemoteConfigKeys::ChatInFlightLimit => RemoteConfigKey {
+                raw_key: "chatInFlightLimit",
+            },
+            RemoteConfigKeys::ChatRateLimit => RemoteConfigKey {
+                raw_key: "chatRateLimit",
+            },
         }
     }
 }
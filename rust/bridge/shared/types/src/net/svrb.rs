@@ -6,7 +6,7 @@
 use async_trait::async_trait;
 use libsignal_account_keys::BACKUP_KEY_LEN;
 use libsignal_net::auth::Auth;
-use libsignal_net::enclave::{EnclaveEndpoint, PpssSetup, SvrSgx};
+use libsignal_net::enclave::{EnclaveEndpoint, EndpointParams, PpssSetup, SvrSgx};
 use libsignal_net::env::SvrBEnv;
 use libsignal_net::infra::tcp_ssl::InvalidProxyConfig;
 use libsignal_net::svr::SvrConnection;
@@ -48,11 +48,17 @@ impl SvrBConnect for SvrBConnectImpl<'_> {
                 libsignal_net::ws::WebSocketServiceConnectError::invalid_proxy_configuration()
             })?;
 
+        let extra_sw_advisories = connection_manager.extra_sw_advisories();
+        let params = EndpointParams {
+            extra_sw_advisories: &extra_sw_advisories,
+            ..endpoint.params.clone()
+        };
+
         SvrConnection::connect(
             connection_resources.as_connection_resources(),
             route_provider,
             endpoint.ws_config,
-            &endpoint.params,
+            &params,
             auth,
         )
         .await
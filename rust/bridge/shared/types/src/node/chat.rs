@@ -93,7 +93,9 @@ impl ChatListener for NodeChatListener {
 
     fn connection_interrupted(&mut self, disconnect_cause: DisconnectCause) {
         let disconnect_cause = match disconnect_cause {
-            DisconnectCause::LocalDisconnect => None,
+            // An idle close is reported like a local disconnect; the app can reconnect when it
+            // next needs the connection.
+            DisconnectCause::LocalDisconnect | DisconnectCause::IdleTimeout => None,
             DisconnectCause::Error(cause) => Some(cause),
         };
         let roots_shared = self.roots.clone();
@@ -512,13 +512,18 @@ impl SignalNodeError for libsignal_net::chat::SendError {
         operation_name: &str,
     ) -> Handle<'a, JsError> {
         let name = match self {
+            Self::WouldExceedRateLimit { retry_after } => {
+                return libsignal_net::infra::errors::RetryLater::rounding_up(retry_after)
+                    .into_throwable(cx, module, operation_name);
+            }
             Self::Disconnected => Some("ChatServiceInactive"),
             Self::ConnectionInvalidated => Some("ConnectionInvalidated"),
             Self::ConnectedElsewhere => Some("ConnectedElsewhere"),
             Self::WebSocket(_)
             | Self::IncomingDataInvalid
             | Self::RequestHasInvalidHeader
-            | Self::RequestTimedOut =>
+            | Self::RequestTimedOut
+            | Self::Busy =>
             // TODO: Distinguish retryable errors from proper failures?
             {
                 Some(IO_ERROR)
@@ -619,9 +624,11 @@ impl SignalNodeError for libsignal_net::cdsi::LookupError {
         operation_name: &str,
     ) -> Handle<'a, JsError> {
         let name = match self {
-            Self::RateLimited(retry_later) => {
-                return retry_later.into_throwable(cx, module, operation_name)
-            }
+            Self::RateLimited(libsignal_net::cdsi::CdsiRateLimit {
+                retry_later,
+                permits_cost: _,
+                remaining_quota: _,
+            }) => return retry_later.into_throwable(cx, module, operation_name),
             Self::AttestationError(e) => return e.into_throwable(cx, module, operation_name),
             Self::InvalidArgument { server_reason: _ } => None,
             Self::InvalidToken => Some("CdsiInvalidToken"),
@@ -672,6 +679,17 @@ mod registration {
                         operation_name,
                     )
                 }
+                RequestError::Busy => {
+                    return libsignal_net::chat::SendError::Busy.into_throwable(
+                        cx,
+                        module,
+                        operation_name,
+                    )
+                }
+                RequestError::WouldExceedRateLimit { retry_after } => {
+                    return libsignal_net::chat::SendError::WouldExceedRateLimit { retry_after }
+                        .into_throwable(cx, module, operation_name)
+                }
                 e @ (RequestError::Unexpected { log_safe: _ } | RequestError::ServerSideError) => {
                     return new_js_error(
                         cx,
@@ -913,7 +931,7 @@ impl SignalNodeError for crate::keytrans::BridgeError {
             RequestError::Disconnected(inner) => {
                 return inner.into_throwable(cx, module, operation_name)
             }
-            RequestError::Timeout => IO_ERROR,
+            RequestError::Timeout | RequestError::Busy => IO_ERROR,
             RequestError::Other(libsignal_net_chat::api::keytrans::Error::VerificationFailed(
                 inner,
             )) => match inner {
@@ -926,6 +944,7 @@ impl SignalNodeError for crate::keytrans::BridgeError {
             // TODO: Consider being more consistent with other APIs for RetryLater and
             // ServerSideError. (Challenge shouldn't happen in practice.)
             RequestError::RetryLater(_)
+            | RequestError::WouldExceedRateLimit { .. }
             | RequestError::Challenge { .. }
             | RequestError::ServerSideError
             | RequestError::Unexpected { .. }
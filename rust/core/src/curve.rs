@@ -139,6 +139,37 @@ impl PublicKey {
         }
     }
 
+    /// Checks several `(key, message, signature)` triples at once, returning `true` only if every
+    /// signature is valid.
+    ///
+    /// This is faster than calling [`Self::verify_signature`] for each triple when all of them are
+    /// valid, so it suits checking many signatures that are expected to succeed. If this returns
+    /// `true`, every signature would also pass [`Self::verify_signature`], except with negligible
+    /// probability (about 2<sup>-128</sup>) over the choices made with `csprng`. If it returns
+    /// `false`, at least one signature is invalid or uses a point that only individual
+    /// verification accepts, so callers should check them one at a time to find out which.
+    pub fn verify_signatures_batch<R: CryptoRng + Rng>(
+        signatures: &[(&PublicKey, &[u8], &[u8])],
+        csprng: &mut R,
+    ) -> bool {
+        let messages: Vec<[&[u8]; 1]> = signatures
+            .iter()
+            .map(|(_, message, _)| [*message])
+            .collect();
+        let mut items = Vec::with_capacity(signatures.len());
+        for ((key, _, signature), message) in signatures.iter().zip(&messages) {
+            match &key.key {
+                PublicKeyData::DjbPublicKey(pub_key) => {
+                    let Ok(signature) = (*signature).try_into() else {
+                        return false;
+                    };
+                    items.push((pub_key, &message[..], signature));
+                }
+            }
+        }
+        curve25519::PrivateKey::verify_signatures_batch(&items, csprng)
+    }
+
     fn key_data(&self) -> &[u8] {
         match &self.key {
             PublicKeyData::DjbPublicKey(ref k) => k.as_ref(),
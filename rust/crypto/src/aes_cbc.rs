@@ -21,6 +21,8 @@ pub enum DecryptionError {
     BadKeyOrIv,
     /// These cases should not be distinguished; message corruption can cause either problem.
     BadCiphertext(&'static str),
+    /// The output buffer is shorter than the ciphertext.
+    OutputTooSmall,
 }
 
 pub fn aes_256_cbc_encrypt(
@@ -50,6 +52,39 @@ pub fn aes_256_cbc_decrypt(
         .map_err(|_| DecryptionError::BadCiphertext("failed to decrypt"))
 }
 
+/// Like [`aes_256_cbc_decrypt`], but decrypts into the start of `out` and returns the length of
+/// the plaintext.
+///
+/// `out` must be at least as long as `ctext`. If decryption fails, the part of `out` that was
+/// written to is zeroed.
+pub fn aes_256_cbc_decrypt_into(
+    ctext: &[u8],
+    key: &[u8],
+    iv: &[u8],
+    out: &mut [u8],
+) -> Result<usize, DecryptionError> {
+    if ctext.is_empty() || ctext.len() % 16 != 0 {
+        return Err(DecryptionError::BadCiphertext(
+            "ciphertext length must be a non-zero multiple of 16",
+        ));
+    }
+
+    let decryptor = cbc::Decryptor::<Aes256>::new_from_slices(key, iv)
+        .map_err(|_| DecryptionError::BadKeyOrIv)?;
+    let buf = out
+        .get_mut(..ctext.len())
+        .ok_or(DecryptionError::OutputTooSmall)?;
+    buf.copy_from_slice(ctext);
+    match decryptor.decrypt_padded_mut::<Pkcs7>(buf) {
+        Ok(ptext) => Ok(ptext.len()),
+        Err(_) => {
+            // Don't leave the unpadded blocks behind.
+            buf.fill(0);
+            Err(DecryptionError::BadCiphertext("failed to decrypt"))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use const_str::hex;
@@ -74,6 +109,18 @@ mod test {
 
         // padding is invalid:
         assert!(aes_256_cbc_decrypt(&recovered, &key, &iv).is_err());
+
+        let mut out = [0u8; 32];
+        let len = aes_256_cbc_decrypt_into(&ctext, &key, &iv, &mut out).expect("valid");
+        assert_eq!(hex::encode(ptext), hex::encode(&out[..len]));
+        assert!(matches!(
+            aes_256_cbc_decrypt_into(&ctext, &key, &iv, &mut out[..8]),
+            Err(DecryptionError::OutputTooSmall)
+        ));
+        // Turns the 0x06 padding into 0x00, which is invalid.
+        let bad_padding_iv = hex!("6f8a557ddc0a140c878063a6d5f31d3b");
+        assert!(aes_256_cbc_decrypt_into(&ctext, &key, &bad_padding_iv, &mut out).is_err());
+        assert_eq!(out, [0; 32]);
         assert!(aes_256_cbc_decrypt(&ctext, &key, &ctext).is_err());
 
         // bitflip the IV to cause a change in the recovered text
@@ -13,7 +13,10 @@ mod aes_cbc;
 mod aes_ctr;
 mod aes_gcm;
 
-pub use aes_cbc::{aes_256_cbc_decrypt, aes_256_cbc_encrypt, DecryptionError, EncryptionError};
+pub use aes_cbc::{
+    aes_256_cbc_decrypt, aes_256_cbc_decrypt_into, aes_256_cbc_encrypt, DecryptionError,
+    EncryptionError,
+};
 pub use aes_ctr::Aes256Ctr32;
 pub use aes_gcm::{Aes256GcmDecryption, Aes256GcmEncryption};
 pub use error::{Error, Result};
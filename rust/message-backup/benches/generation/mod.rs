@@ -32,8 +32,10 @@ pub fn generate_backup(
 
     let uncompressed = generate_backup_contents(number_of_conversations, number_of_messages);
 
-    let mut compressed_contents = gzip_compress(futures::io::BufReader::new(uncompressed));
-    pad_gzipped_bucketed(&mut compressed_contents);
+    let mut compressed_contents =
+        futures::executor::block_on(gzip_compress(futures::io::BufReader::new(uncompressed)))
+            .expect("failed to compress");
+    pad_gzipped_bucketed(&mut compressed_contents).expect("backup < 4GB");
     aes_cbc_encrypt(&message_backup_key.aes_key, &iv, &mut compressed_contents);
     let hmac = hmac_checksum(&message_backup_key.hmac_key, &iv, &compressed_contents);
     compressed_contents.splice(0..0, iv);
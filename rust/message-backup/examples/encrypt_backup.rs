@@ -72,11 +72,13 @@ fn main() {
     let contents = read_file(input);
     eprintln!("read {} bytes", contents.len());
 
-    let mut compressed_contents = gzip_compress(futures::io::Cursor::new(contents));
+    let mut compressed_contents =
+        futures::executor::block_on(gzip_compress(futures::io::Cursor::new(contents)))
+            .expect("failed to compress");
     eprintln!("compressed to {} bytes", compressed_contents.len());
 
     if pad_bucketed {
-        pad_gzipped_bucketed(&mut compressed_contents);
+        pad_gzipped_bucketed(&mut compressed_contents).expect("backup < 4GB");
         eprintln!("padded to {} bytes", compressed_contents.len());
     }
 
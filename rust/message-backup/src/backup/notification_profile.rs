@@ -158,6 +158,12 @@ impl<R: Clone, C: LookupPair<RecipientId, MinimalRecipientData, R> + ReportUnusu
     }
 }
 
+impl<R> NotificationProfile<R> {
+    pub(crate) fn id(&self) -> &[u8; 16] {
+        &self.id
+    }
+}
+
 impl<R> SerializeOrder for NotificationProfile<R> {
     fn serialize_cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.created_at.cmp(&other.created_at)
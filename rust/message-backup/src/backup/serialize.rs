@@ -23,6 +23,11 @@ use crate::backup::sticker::{PackId as StickerPackId, StickerPack};
 use crate::backup::{BackupMeta, ChatsData, CompletedBackup};
 use crate::proto::backup as proto;
 
+#[cfg(feature = "json")]
+mod diff;
+#[cfg(feature = "json")]
+pub use diff::{BackupDiff, Difference};
+
 mod unordered_list;
 pub use unordered_list::UnorderedList;
 
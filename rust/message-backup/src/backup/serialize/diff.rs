@@ -0,0 +1,303 @@
+//
+// Copyright (C) 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::collections::HashMap;
+
+use itertools::{EitherOrBoth, Itertools as _};
+use serde_json::Value;
+
+use crate::backup::chat::{ChatData, ChatItemData};
+use crate::backup::method::Store;
+use crate::backup::recipient::{FullRecipientData, MinimalRecipientData};
+use crate::backup::serialize::Backup;
+
+/// A single difference found by [`Backup::diff`].
+///
+/// Paths use the field names of the canonical serialization, with items of unordered lists
+/// identified by a stable key in brackets, e.g. `chats[group:…].items[self@1700000000000]`.
+#[derive(Clone, Debug, PartialEq, displaydoc::Display)]
+pub enum Difference {
+    /// missing {path}
+    Missing { path: String, expected: Value },
+    /// extra {path}
+    Extra { path: String, actual: Value },
+    /// changed {path}: {expected} -> {actual}
+    Changed {
+        path: String,
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl Difference {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Missing { path, .. } | Self::Extra { path, .. } | Self::Changed { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+/// The differences between two backups, as produced by [`Backup::diff`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BackupDiff(Vec<Difference>);
+
+impl BackupDiff {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn differences(&self) -> &[Difference] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BackupDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for difference in &self.0 {
+            writeln!(f, "{difference}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Backup {
+    /// Compares `self` (the expected backup) against `actual`.
+    ///
+    /// Unlike comparing serialized backups, this matches up recipients, chats, chat items, calls,
+    /// sticker packs, and notification profiles by identity rather than position, so a backup
+    /// with the same contents in a different order produces no differences, and a single changed
+    /// item is reported as a change to that item rather than as a shift of everything after it.
+    ///
+    /// To compare against a frame stream, read it into a [`Backup`] first.
+    pub fn diff(&self, actual: &Backup) -> BackupDiff {
+        let Self {
+            meta,
+            account_data,
+            recipients,
+            chats,
+            ad_hoc_calls,
+            pinned_chats,
+            sticker_packs,
+            notification_profiles,
+            chat_folders,
+        } = self;
+
+        let mut out = Vec::new();
+        diff_serialized("meta", meta, &actual.meta, &mut out);
+        diff_serialized("account_data", account_data, &actual.account_data, &mut out);
+        diff_keyed(
+            "recipients",
+            recipients.iter(),
+            actual.recipients.iter(),
+            recipient_key,
+            diff_serialized,
+            &mut out,
+        );
+        diff_keyed(
+            "chats",
+            chats.iter(),
+            actual.chats.iter(),
+            |chat| recipient_key(&chat.recipient),
+            diff_chats,
+            &mut out,
+        );
+        diff_keyed(
+            "ad_hoc_calls",
+            ad_hoc_calls.iter(),
+            actual.ad_hoc_calls.iter(),
+            |call| to_value(&call.id).to_string(),
+            diff_serialized,
+            &mut out,
+        );
+        diff_serialized("pinned_chats", pinned_chats, &actual.pinned_chats, &mut out);
+        diff_keyed(
+            "sticker_packs",
+            sticker_packs.iter(),
+            actual.sticker_packs.iter(),
+            |(pack_id, _)| to_value(pack_id).to_string(),
+            diff_serialized,
+            &mut out,
+        );
+        diff_keyed(
+            "notification_profiles",
+            notification_profiles.iter(),
+            actual.notification_profiles.iter(),
+            |profile| hex::encode(profile.id()),
+            diff_serialized,
+            &mut out,
+        );
+        // Folder order is meaningful, so these are compared by position.
+        diff_serialized("chat_folders", chat_folders, &actual.chat_folders, &mut out);
+
+        BackupDiff(out)
+    }
+}
+
+fn to_value(value: &impl serde::Serialize) -> Value {
+    serde_json::to_value(value).expect("can't fail serialization")
+}
+
+fn recipient_key(recipient: &FullRecipientData) -> String {
+    match AsRef::<MinimalRecipientData>::as_ref(recipient) {
+        MinimalRecipientData::Contact { e164, aci, pni } => {
+            if let Some(aci) = aci {
+                format!("contact:{}", aci.service_id_string())
+            } else if let Some(pni) = pni {
+                format!("contact:{}", pni.service_id_string())
+            } else if let Some(e164) = e164 {
+                format!("contact:{e164}")
+            } else {
+                "contact".to_owned()
+            }
+        }
+        MinimalRecipientData::Group { master_key } => format!("group:{}", hex::encode(master_key)),
+        MinimalRecipientData::DistributionList { distribution_id } => {
+            format!("distribution_list:{distribution_id}")
+        }
+        MinimalRecipientData::Self_ => "self".to_owned(),
+        MinimalRecipientData::ReleaseNotes => "release_notes".to_owned(),
+        MinimalRecipientData::CallLink { root_key } => {
+            format!("call_link:{}", hex::encode(root_key))
+        }
+    }
+}
+
+fn chat_item_key(item: &ChatItemData<Store>) -> String {
+    format!(
+        "{}@{}",
+        recipient_key(&item.author),
+        item.sent_at.as_millis()
+    )
+}
+
+fn diff_chats(
+    path: &str,
+    expected: &ChatData<Store>,
+    actual: &ChatData<Store>,
+    out: &mut Vec<Difference>,
+) {
+    // Items are compared separately below, by key.
+    let without_items = |chat: &ChatData<Store>| {
+        let mut value = to_value(chat);
+        if let Value::Object(fields) = &mut value {
+            fields.remove("items");
+        }
+        value
+    };
+    diff_values(path, &without_items(expected), &without_items(actual), out);
+    diff_keyed(
+        &format!("{path}.items"),
+        expected.items.iter(),
+        actual.items.iter(),
+        chat_item_key,
+        diff_serialized,
+        out,
+    );
+}
+
+/// Matches up `expected` and `actual` by `key`, then compares matching items with `compare`.
+///
+/// Items that share a key are matched in the order they appear.
+fn diff_keyed<'a, T: serde::Serialize + 'a>(
+    path: &str,
+    expected: impl IntoIterator<Item = &'a T>,
+    actual: impl IntoIterator<Item = &'a T>,
+    key: impl Fn(&T) -> String,
+    compare: impl Fn(&str, &T, &T, &mut Vec<Difference>),
+    out: &mut Vec<Difference>,
+) {
+    let keyed = |items: &mut dyn Iterator<Item = &'a T>| {
+        let mut seen = HashMap::<String, usize>::new();
+        let mut keyed = items
+            .map(|item| {
+                let key = key(item);
+                let count = seen.entry(key.clone()).or_default();
+                let key = match *count {
+                    0 => key,
+                    n => format!("{key}#{n}"),
+                };
+                *count += 1;
+                (key, item)
+            })
+            .collect_vec();
+        keyed.sort_by(|(l, _), (r, _)| l.cmp(r));
+        keyed
+    };
+    let expected = keyed(&mut expected.into_iter());
+    let actual = keyed(&mut actual.into_iter());
+
+    for pair in expected
+        .into_iter()
+        .merge_join_by(actual, |(l, _), (r, _)| l.cmp(r))
+    {
+        match pair {
+            EitherOrBoth::Left((key, item)) => out.push(Difference::Missing {
+                path: format!("{path}[{key}]"),
+                expected: to_value(item),
+            }),
+            EitherOrBoth::Right((key, item)) => out.push(Difference::Extra {
+                path: format!("{path}[{key}]"),
+                actual: to_value(item),
+            }),
+            EitherOrBoth::Both((key, expected), (_, actual)) => {
+                compare(&format!("{path}[{key}]"), expected, actual, out)
+            }
+        }
+    }
+}
+
+fn diff_serialized<T: serde::Serialize + ?Sized>(
+    path: &str,
+    expected: &T,
+    actual: &T,
+    out: &mut Vec<Difference>,
+) {
+    diff_values(path, &to_value(expected), &to_value(actual), out)
+}
+
+/// Compares serialized values field by field.
+///
+/// Arrays of the same length are compared element by element; otherwise, the whole array is
+/// reported as changed.
+fn diff_values(path: &str, expected: &Value, actual: &Value, out: &mut Vec<Difference>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (field, expected_value) in expected {
+                let path = format!("{path}.{field}");
+                match actual.get(field) {
+                    Some(actual_value) => diff_values(&path, expected_value, actual_value, out),
+                    None => out.push(Difference::Missing {
+                        path,
+                        expected: expected_value.clone(),
+                    }),
+                }
+            }
+            for (field, actual_value) in actual {
+                if !expected.contains_key(field) {
+                    out.push(Difference::Extra {
+                        path: format!("{path}.{field}"),
+                        actual: actual_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                diff_values(&format!("{path}[{i}]"), expected, actual, out);
+            }
+        }
+        (expected, actual) => {
+            if expected != actual {
+                out.push(Difference::Changed {
+                    path: path.to_owned(),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+    }
+}
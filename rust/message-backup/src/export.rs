@@ -5,30 +5,230 @@
 
 //! Utilities for exporting backups.
 //!
-//! See `encrypt_backup` or `generation/mod.rs` for how they fit together.
+//! [`BackupWriter`] produces a complete backup file from individual frames. The lower-level
+//! functions below are the steps it performs; see `encrypt_backup` or `generation/mod.rs` for
+//! using them directly.
 
 use aes::cipher::{BlockEncryptMut as _, BlockSizeUser as _, KeyIvInit as _};
 use async_compression::futures::bufread::GzipEncoder;
-use futures::{AsyncBufRead, AsyncReadExt as _};
+use futures::{AsyncBufRead, AsyncRead, AsyncReadExt as _};
 use hmac::Mac as _;
+use protobuf::Message as _;
 use sha2::Sha256;
 
-use crate::frame::{AES_IV_SIZE, AES_KEY_SIZE};
+use crate::backup::method::ValidateOnly;
+use crate::backup::{CompletedBackup, PartialBackup, Purpose};
+use crate::frame::forward_secrecy::MAGIC_NUMBER;
+use crate::frame::{VerifyHmac, AES_IV_SIZE, AES_KEY_SIZE};
+use crate::key::MessageBackupKey;
+use crate::parse::write_length_delimited;
+use crate::unknown::VisitUnknownFieldsExt as _;
+use crate::{proto, BackupReader, Error, FoundUnknownField, ReadResult};
 
-pub fn gzip_compress<R: AsyncBufRead + Unpin>(contents: R) -> Vec<u8> {
+/// Builds a backup file one frame at a time.
+///
+/// Each frame is validated as it's added, with the same checks [`BackupReader`] performs, so a
+/// frame that refers to something not yet written (such as a chat item before its chat) is
+/// rejected when it's added rather than when the backup is restored. Frames are not reordered;
+/// they must be added in an order that satisfies these constraints.
+///
+/// If adding a frame fails, the writer should be discarded, since the validation state may
+/// already reflect part of the rejected frame.
+///
+/// The serialized frames are kept in memory until the backup is finished.
+///
+/// Frames added with [`add_frame`](Self::add_frame) are re-encoded, which keeps fields this
+/// version of libsignal doesn't know about but not necessarily their exact encoding. To pass a
+/// backup (or part of one) through byte-for-byte, use [`add_raw_frame`](Self::add_raw_frame) or
+/// [`copy_from`](Self::copy_from) instead.
+///
+/// [`BackupReader`]: crate::BackupReader
+pub struct BackupWriter {
+    backup: PartialBackup<ValidateOnly>,
+    contents: Vec<u8>,
+}
+
+impl BackupWriter {
+    /// Starts a backup with the given header.
+    pub fn new(backup_info: proto::backup::BackupInfo, purpose: Purpose) -> Result<Self, Error> {
+        let contents = backup_info.write_length_delimited_to_bytes()?;
+        let backup = PartialBackup::new(backup_info, purpose)?;
+        Ok(Self { backup, contents })
+    }
+
+    /// Starts a backup with an already-serialized header, which is written out unchanged.
+    pub fn new_raw(raw_backup_info: &[u8], purpose: Purpose) -> Result<Self, Error> {
+        let backup_info = proto::backup::BackupInfo::parse_from_bytes(raw_backup_info)?;
+        let backup = PartialBackup::new(backup_info, purpose)?;
+        let mut contents = Vec::new();
+        write_length_delimited(&mut contents, raw_backup_info);
+        Ok(Self { backup, contents })
+    }
+
+    /// Reads all frames from `reader` into a new writer, keeping their exact bytes.
+    ///
+    /// Fields unknown to this version of libsignal are reported in the result but otherwise passed
+    /// through untouched, so a backup from a newer client can be rewritten (for example, with a
+    /// [`FrameFilter`](crate::filter::FrameFilter) applied) without losing data. Frames excluded by
+    /// the reader's filter are dropped.
+    ///
+    /// This validates every frame and holds the whole backup in memory. To only change the key a
+    /// backup is encrypted with, use [`frame::reencrypt`](crate::frame::reencrypt) instead, which
+    /// does neither.
+    pub async fn copy_from<R: AsyncRead + Unpin + VerifyHmac>(
+        reader: BackupReader<R>,
+    ) -> ReadResult<Self> {
+        let mut found_unknown_fields = Vec::new();
+        let result = Self::copy_frames_from(reader, &mut found_unknown_fields).await;
+        ReadResult {
+            result,
+            found_unknown_fields,
+        }
+    }
+
+    async fn copy_frames_from<R: AsyncRead + Unpin + VerifyHmac>(
+        reader: BackupReader<R>,
+        found_unknown_fields: &mut Vec<FoundUnknownField>,
+    ) -> Result<Self, Error> {
+        let BackupReader {
+            purpose,
+            mut reader,
+            visitor: _,
+            filter,
+        } = reader;
+        let mut add_found_unknown = |found: Vec<_>, frame_index| {
+            found_unknown_fields.extend(found.into_iter().map(|(path, value)| FoundUnknownField {
+                frame_index,
+                path,
+                value,
+            }))
+        };
+
+        let raw_backup_info = reader
+            .read_next()
+            .await
+            .map_err(Error::Parse)?
+            .ok_or(Error::NoFrames)?;
+        let mut writer = Self::new_raw(&raw_backup_info, purpose)?;
+        add_found_unknown(
+            proto::backup::BackupInfo::parse_from_bytes(&raw_backup_info)?.collect_unknown_fields(),
+            0,
+        );
+
+        let mut frame_index = 1;
+        while let Some(raw_frame) = reader.read_next().await.map_err(Error::Parse)? {
+            let frame = proto::backup::Frame::parse_from_bytes(&raw_frame)?;
+            if filter.includes(&frame) {
+                add_found_unknown(frame.collect_unknown_fields(), frame_index);
+                writer.backup.add_frame(frame)?;
+                write_length_delimited(&mut writer.contents, &raw_frame);
+            }
+            frame_index += 1;
+        }
+
+        reader.into_inner().verify_hmac().await?;
+        Ok(writer)
+    }
+
+    /// Validates `frame` against the frames added so far, then appends it.
+    pub fn add_frame(&mut self, frame: proto::backup::Frame) -> Result<(), Error> {
+        let serialized = frame.write_length_delimited_to_bytes()?;
+        self.backup.add_frame(frame)?;
+        self.contents.extend(serialized);
+        Ok(())
+    }
+
+    /// Like [`add_frame`](Self::add_frame), but for an already-serialized frame, which is written
+    /// out unchanged.
+    pub fn add_raw_frame(&mut self, raw_frame: &[u8]) -> Result<(), Error> {
+        let frame = proto::backup::Frame::parse_from_bytes(raw_frame)?;
+        self.backup.add_frame(frame)?;
+        write_length_delimited(&mut self.contents, raw_frame);
+        Ok(())
+    }
+
+    /// Convenience wrapper around [`add_frame`](Self::add_frame) for a single item, such as a
+    /// [`proto::backup::Chat`] or [`proto::backup::ChatItem`].
+    pub fn add_item(&mut self, item: impl Into<proto::backup::frame::Item>) -> Result<(), Error> {
+        self.add_frame(proto::backup::Frame {
+            item: Some(item.into()),
+            ..Default::default()
+        })
+    }
+
+    /// Checks that the backup is complete, then returns the frames without compressing or
+    /// encrypting them.
+    ///
+    /// The result can be read with [`BackupReader::new_unencrypted`].
+    ///
+    /// [`BackupReader::new_unencrypted`]: crate::BackupReader::new_unencrypted
+    pub fn finish_unencrypted(self) -> Result<Vec<u8>, Error> {
+        let Self { backup, contents } = self;
+        let _: CompletedBackup<ValidateOnly> = backup.try_into()?;
+        Ok(contents)
+    }
+
+    /// Checks that the backup is complete, then compresses, pads, and encrypts it.
+    ///
+    /// If `forward_secrecy_metadata` is provided, it must be a serialized `MetadataPb` as produced
+    /// by SVR-B, and it is written unencrypted after the magic number at the start of the file.
+    /// Otherwise, the legacy format with no unencrypted header is produced.
+    ///
+    /// The result can be read with [`BackupReader::new_encrypted_compressed`].
+    ///
+    /// Everything happens in memory, so the returned future completes without ever waiting.
+    ///
+    /// [`BackupReader::new_encrypted_compressed`]: crate::BackupReader::new_encrypted_compressed
+    pub async fn finish_encrypted(
+        self,
+        key: &MessageBackupKey,
+        iv: &[u8; AES_IV_SIZE],
+        forward_secrecy_metadata: Option<&[u8]>,
+    ) -> Result<Vec<u8>, Error> {
+        let contents = self.finish_unencrypted()?;
+
+        let mut encrypted = gzip_compress(futures::io::Cursor::new(contents))
+            .await
+            .map_err(Error::Compression)?;
+        pad_gzipped_bucketed(&mut encrypted)?;
+        let MessageBackupKey { hmac_key, aes_key } = key;
+        aes_cbc_encrypt(aes_key, iv, &mut encrypted);
+        let hmac = hmac_checksum(hmac_key, iv, &encrypted);
+
+        let mut out = Vec::new();
+        if let Some(metadata) = forward_secrecy_metadata {
+            out.extend_from_slice(MAGIC_NUMBER);
+            write_length_delimited(&mut out, metadata);
+        }
+        out.extend_from_slice(iv);
+        out.extend(encrypted);
+        out.extend_from_slice(&hmac);
+        Ok(out)
+    }
+}
+
+pub async fn gzip_compress<R: AsyncBufRead + Unpin>(
+    contents: R,
+) -> Result<Vec<u8>, std::io::Error> {
     let mut compressed_contents = Vec::new();
-    futures::executor::block_on(GzipEncoder::new(contents).read_to_end(&mut compressed_contents))
-        .expect("failed to compress");
+    GzipEncoder::new(contents)
+        .read_to_end(&mut compressed_contents)
+        .await?;
+
+    Ok(compressed_contents)
+}
 
-    compressed_contents
+/// Pads `out` with zeros to one of the bucketed lengths given by [`crate::padded_length`].
+///
+/// Fails with [`Error::TooLargeToExport`] if `out` is 4GB or more.
+pub fn pad_gzipped_bucketed(out: &mut Vec<u8>) -> Result<(), Error> {
+    out.resize(padded_gzipped_len(out.len())?, 0);
+    Ok(())
 }
 
-pub fn pad_gzipped_bucketed(out: &mut Vec<u8>) {
-    let len = u32::try_from(out.len()).expect("backup < 4GB");
-    out.resize(
-        crate::padded_length(len).try_into().expect("usize >= u32"),
-        0,
-    );
+fn padded_gzipped_len(len: usize) -> Result<usize, Error> {
+    let len = u32::try_from(len).map_err(|_| Error::TooLargeToExport(len))?;
+    Ok(crate::padded_length(len).try_into().expect("usize >= u32"))
 }
 
 /// Encrypts `contents` in-place.
@@ -59,3 +259,234 @@ pub fn hmac_checksum(
     hmac.update(encrypted_contents);
     hmac.finalize().into_bytes().into()
 }
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use futures::FutureExt as _;
+    use libsignal_account_keys::BACKUP_KEY_LEN;
+    use libsignal_svrb::proto::backup_metadata::{metadata_pb, MetadataPb};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::frame::CursorFactory;
+
+    const KEY: MessageBackupKey = MessageBackupKey {
+        hmac_key: [0xAA; MessageBackupKey::HMAC_KEY_LEN],
+        aes_key: [0xBB; MessageBackupKey::AES_KEY_LEN],
+    };
+    const IV: [u8; AES_IV_SIZE] = [0xCC; AES_IV_SIZE];
+
+    fn write_test_backup() -> BackupWriter {
+        let mut writer = BackupWriter::new(
+            proto::backup::BackupInfo {
+                mediaRootBackupKey: vec![0; BACKUP_KEY_LEN],
+                ..Default::default()
+            },
+            Purpose::RemoteBackup,
+        )
+        .expect("valid header");
+        writer
+            .add_item(proto::backup::AccountData::test_data())
+            .expect("valid");
+        writer
+            .add_item(proto::backup::Recipient::test_data())
+            .expect("valid");
+        writer
+            .add_item(proto::backup::Recipient::test_data_contact())
+            .expect("valid");
+        writer
+            .add_item(proto::backup::Chat::test_data())
+            .expect("valid");
+        writer
+            .add_item(proto::backup::ChatItem::test_data())
+            .expect("valid");
+        writer
+    }
+
+    fn faux_metadata() -> Vec<u8> {
+        MetadataPb {
+            iv: b"iv_12_bytes_".to_vec(),
+            pair: vec![metadata_pb::Pair {
+                ct: [0xCC; 48].to_vec(),
+                pw_salt: [0x50; 32].to_vec(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .expect("can serialize")
+    }
+
+    fn finish_encrypted(
+        writer: BackupWriter,
+        key: &MessageBackupKey,
+        iv: &[u8; AES_IV_SIZE],
+        forward_secrecy_metadata: Option<&[u8]>,
+    ) -> Vec<u8> {
+        writer
+            .finish_encrypted(key, iv, forward_secrecy_metadata)
+            .now_or_never()
+            .expect("sync")
+            .expect("complete")
+    }
+
+    #[test]
+    fn unencrypted_round_trip() {
+        let contents = write_test_backup().finish_unencrypted().expect("complete");
+        let reader = BackupReader::new_unencrypted(&contents[..], Purpose::RemoteBackup);
+        let result = futures::executor::block_on(reader.validate_all());
+        assert_matches!(result.result, Ok(()));
+    }
+
+    #[test_case(None; "legacy")]
+    #[test_case(Some(faux_metadata()); "forward secrecy")]
+    fn encrypted_round_trip(metadata: Option<Vec<u8>>) {
+        let contents = finish_encrypted(write_test_backup(), &KEY, &IV, metadata.as_deref());
+        let result = futures::executor::block_on(async {
+            BackupReader::new_encrypted_compressed(
+                &KEY,
+                CursorFactory::new(&contents),
+                Purpose::RemoteBackup,
+            )
+            .await
+            .expect("valid framing")
+            .validate_all()
+            .await
+        });
+        assert_matches!(result.result, Ok(()));
+    }
+
+    #[test]
+    fn rejects_out_of_order_frames() {
+        let mut writer = BackupWriter::new(
+            proto::backup::BackupInfo {
+                mediaRootBackupKey: vec![0; BACKUP_KEY_LEN],
+                ..Default::default()
+            },
+            Purpose::RemoteBackup,
+        )
+        .expect("valid header");
+        writer
+            .add_item(proto::backup::AccountData::test_data())
+            .expect("valid");
+        assert_matches!(
+            writer.add_item(proto::backup::ChatItem::test_data()),
+            Err(Error::BackupValidation(_))
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_backup() {
+        let writer = BackupWriter::new(
+            proto::backup::BackupInfo {
+                mediaRootBackupKey: vec![0; BACKUP_KEY_LEN],
+                ..Default::default()
+            },
+            Purpose::RemoteBackup,
+        )
+        .expect("valid header");
+        assert_matches!(writer.finish_unencrypted(), Err(Error::BackupCompletion(_)));
+    }
+
+    #[test]
+    fn padding_rejects_4gb_backups() {
+        assert_eq!(padded_gzipped_len(0).expect("small"), 541);
+        let max_len = usize::try_from(u32::MAX).expect("usize >= u32");
+        assert_matches!(padded_gzipped_len(max_len), Ok(len) if len >= max_len);
+        assert_matches!(
+            padded_gzipped_len(max_len + 1),
+            Err(Error::TooLargeToExport(len)) if len == max_len + 1
+        );
+    }
+
+    #[test]
+    fn copy_preserves_unknown_fields() {
+        let mut writer = BackupWriter::new(
+            proto::backup::BackupInfo {
+                mediaRootBackupKey: vec![0; BACKUP_KEY_LEN],
+                ..Default::default()
+            },
+            Purpose::RemoteBackup,
+        )
+        .expect("valid header");
+        let mut account_data = proto::backup::AccountData::test_data();
+        account_data
+            .special_fields
+            .mut_unknown_fields()
+            .add_varint(9999, 1);
+        writer.add_item(account_data).expect("valid");
+        writer
+            .add_item(proto::backup::Recipient::test_data())
+            .expect("valid");
+        let original = writer.finish_unencrypted().expect("complete");
+
+        let reader = BackupReader::new_unencrypted(&original[..], Purpose::RemoteBackup);
+        let ReadResult {
+            result,
+            found_unknown_fields,
+        } = futures::executor::block_on(BackupWriter::copy_from(reader));
+        let copy = result
+            .expect("valid")
+            .finish_unencrypted()
+            .expect("complete");
+
+        assert_eq!(copy, original);
+        assert_matches!(
+            &found_unknown_fields[..],
+            [FoundUnknownField { frame_index: 1, .. }]
+        );
+    }
+
+    #[test_case(None, None; "legacy")]
+    #[test_case(Some(faux_metadata()), Some(faux_metadata()); "forward secrecy")]
+    #[test_case(None, Some(faux_metadata()); "legacy to forward secrecy")]
+    fn reencrypt_matches_direct_encryption(
+        old_metadata: Option<Vec<u8>>,
+        new_metadata: Option<Vec<u8>>,
+    ) {
+        const NEW_KEY: MessageBackupKey = MessageBackupKey {
+            hmac_key: [0xDD; MessageBackupKey::HMAC_KEY_LEN],
+            aes_key: [0xEE; MessageBackupKey::AES_KEY_LEN],
+        };
+        const NEW_IV: [u8; AES_IV_SIZE] = [0xFF; AES_IV_SIZE];
+
+        let original = finish_encrypted(write_test_backup(), &KEY, &IV, old_metadata.as_deref());
+        let mut reencrypted = Vec::new();
+        futures::executor::block_on(crate::frame::reencrypt(
+            &KEY,
+            CursorFactory::new(&original),
+            &NEW_KEY,
+            &NEW_IV,
+            new_metadata.as_deref(),
+            &mut reencrypted,
+        ))
+        .expect("valid");
+
+        let expected = finish_encrypted(
+            write_test_backup(),
+            &NEW_KEY,
+            &NEW_IV,
+            new_metadata.as_deref(),
+        );
+        assert_eq!(reencrypted, expected);
+    }
+
+    #[test]
+    fn reencrypt_rejects_wrong_key() {
+        let original = finish_encrypted(write_test_backup(), &KEY, &IV, None);
+        let wrong_key = MessageBackupKey {
+            hmac_key: [0; MessageBackupKey::HMAC_KEY_LEN],
+            ..KEY
+        };
+        let result = futures::executor::block_on(crate::frame::reencrypt(
+            &wrong_key,
+            CursorFactory::new(&original),
+            &KEY,
+            &IV,
+            None,
+            futures::io::sink(),
+        ));
+        assert_matches!(result, Err(crate::frame::ValidationError::InvalidHmac(_)));
+    }
+}
@@ -0,0 +1,123 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Support for reading only part of a backup.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::proto::backup as proto;
+
+/// Restricts which chats and chat items are kept when reading a backup.
+///
+/// Frames that chats and chat items depend on (account data, recipients, sticker packs, and so
+/// on) are always kept, so that the filtered frames still form a valid backup. Since they come
+/// before the chats that refer to them, this includes recipients that none of the kept chats end
+/// up referencing.
+///
+/// The default filter keeps everything.
+#[derive(Clone, Debug, Default)]
+pub struct FrameFilter {
+    /// If set, only chats with these IDs (and their chat items) are kept.
+    pub chat_ids: Option<HashSet<u64>>,
+    /// If set, only chat items whose `dateSent` (in milliseconds since the epoch) is within this
+    /// range are kept. Chats themselves are kept even if they have no items in the range.
+    pub date_sent_ms: Option<Range<u64>>,
+}
+
+impl FrameFilter {
+    /// Keeps only the chats with the given IDs.
+    pub fn chats(chat_ids: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            chat_ids: Some(chat_ids.into_iter().collect()),
+            date_sent_ms: None,
+        }
+    }
+
+    /// Keeps only chat items sent within `date_sent_ms`.
+    pub fn date_sent(date_sent_ms: Range<u64>) -> Self {
+        Self {
+            chat_ids: None,
+            date_sent_ms: Some(date_sent_ms),
+        }
+    }
+
+    pub(crate) fn includes(&self, frame: &proto::Frame) -> bool {
+        match &frame.item {
+            Some(proto::frame::Item::Chat(chat)) => self.includes_chat(chat.id),
+            Some(proto::frame::Item::ChatItem(item)) => {
+                self.includes_chat(item.chatId)
+                    && self
+                        .date_sent_ms
+                        .as_ref()
+                        .is_none_or(|range| range.contains(&item.dateSent))
+            }
+            _ => true,
+        }
+    }
+
+    fn includes_chat(&self, chat_id: u64) -> bool {
+        self.chat_ids
+            .as_ref()
+            .is_none_or(|chat_ids| chat_ids.contains(&chat_id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn chat(id: u64) -> proto::Frame {
+        proto::Frame {
+            item: Some(
+                proto::Chat {
+                    id,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn chat_item(chat_id: u64, date_sent: u64) -> proto::Frame {
+        proto::Frame {
+            item: Some(
+                proto::ChatItem {
+                    chatId: chat_id,
+                    dateSent: date_sent,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn recipient() -> proto::Frame {
+        proto::Frame {
+            item: Some(proto::Recipient::default().into()),
+            ..Default::default()
+        }
+    }
+
+    #[test_case(FrameFilter::default(), chat(1) => true)]
+    #[test_case(FrameFilter::default(), chat_item(1, 10) => true)]
+    #[test_case(FrameFilter::chats([1]), chat(1) => true)]
+    #[test_case(FrameFilter::chats([1]), chat(2) => false)]
+    #[test_case(FrameFilter::chats([1]), chat_item(1, 10) => true)]
+    #[test_case(FrameFilter::chats([1]), chat_item(2, 10) => false)]
+    #[test_case(FrameFilter::chats([1]), recipient() => true)]
+    #[test_case(FrameFilter::date_sent(10..20), chat(1) => true)]
+    #[test_case(FrameFilter::date_sent(10..20), chat_item(1, 10) => true)]
+    #[test_case(FrameFilter::date_sent(10..20), chat_item(1, 20) => false)]
+    #[test_case(FrameFilter::date_sent(10..20), recipient() => true)]
+    #[test_case(FrameFilter { chat_ids: Some([1].into()), date_sent_ms: Some(10..20) }, chat_item(2, 15) => false)]
+    fn includes(filter: FrameFilter, frame: proto::Frame) -> bool {
+        filter.includes(&frame)
+    }
+}
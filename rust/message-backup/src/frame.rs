@@ -24,15 +24,19 @@ mod cbc;
 pub mod forward_secrecy;
 mod mac_read;
 mod reader_factory;
+mod reencrypt;
 mod unpad;
 
-#[cfg(feature = "test-util")]
-pub use aes_read::AES_KEY_SIZE;
 #[cfg_attr(feature = "test-util", visibility::make(pub))]
-use aes_read::{Aes256CbcReader, AES_IV_SIZE};
+use aes_read::Aes256CbcReader;
+#[cfg_attr(feature = "export", visibility::make(pub))]
+use aes_read::AES_IV_SIZE;
+#[cfg(feature = "export")]
+pub use aes_read::AES_KEY_SIZE;
 #[cfg_attr(feature = "test-util", visibility::make(pub))]
 use mac_read::MacReader;
 pub use reader_factory::{CursorFactory, FileReaderFactory, LimitedReaderFactory, ReaderFactory};
+pub use reencrypt::reencrypt;
 
 const HMAC_LEN: usize = <<Hmac<Sha256> as OutputSizeUser>::OutputSize as Unsigned>::USIZE;
 
@@ -40,6 +44,10 @@ const HMAC_LEN: usize = <<Hmac<Sha256> as OutputSizeUser>::OutputSize as Unsigne
 pub struct FramesReader<R: AsyncRead + Unpin> {
     reader: GzipDecoder<BufReader<Aes256CbcReader<HmacSha256Reader<Take<R>>>>>,
     expected_hmac: [u8; HMAC_LEN],
+    /// Where in the input the contents covered by the HMAC start.
+    input_offset: u64,
+    /// The length of the contents covered by the HMAC.
+    covered_len: u64,
 }
 
 /// Reader that computes a SHA256 HMAC of the yielded bytes.
@@ -73,7 +81,10 @@ pub struct HmacMismatchError {
 /// Reader that doesn't check the HMAC of the yielded contents.
 ///
 /// Implements [`VerifyHmac`] by always returning success from `verify_hmac`.
-pub struct UnvalidatedHmacReader<R>(R);
+pub struct UnvalidatedHmacReader<R> {
+    reader: R,
+    bytes_read: u64,
+}
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum VerifyHmacError {
@@ -87,13 +98,49 @@ pub enum VerifyHmacError {
 pub trait VerifyHmac: Sized {
     /// Checks that the input that was received has a valid HMAC.
     async fn verify_hmac(self) -> Result<(), VerifyHmacError>;
+
+    /// How far into the input reading has gotten, including any bytes buffered but not yet
+    /// produced.
+    fn input_bytes_read(&self) -> u64;
 }
 
 impl<R: AsyncRead + AsyncSkip + Unpin> FramesReader<R> {
     pub async fn new(
+        key: &MessageBackupKey,
+        reader_factory: impl ReaderFactory<Reader = R>,
+    ) -> Result<Self, ValidationError> {
+        Self::new_with_progress(key, reader_factory, |_| ()).await
+    }
+
+    /// Like [`Self::new`], but calls `on_hmac_progress` with how far into the input the HMAC has
+    /// been checked, as it's checked.
+    pub async fn new_with_progress(
         key: &MessageBackupKey,
         mut reader_factory: impl ReaderFactory<Reader = R>,
+        on_hmac_progress: impl FnMut(u64),
     ) -> Result<Self, ValidationError> {
+        let (start_of_encrypted_data, content_len, hmac) =
+            Self::check_header_and_hmac(key, &mut reader_factory, on_hmac_progress).await?;
+
+        let mut new_reader = reader_factory.make_reader()?;
+        new_reader.skip(start_of_encrypted_data).await?;
+        Self::with_separate_hmac(
+            key,
+            new_reader.take(content_len),
+            start_of_encrypted_data,
+            hmac,
+        )
+        .await
+    }
+
+    /// Reads past the unencrypted metadata (if present) and checks the HMAC of the rest.
+    ///
+    /// Returns the offset of the contents covered by the HMAC, their length, and the HMAC itself.
+    async fn check_header_and_hmac(
+        key: &MessageBackupKey,
+        reader_factory: &mut impl ReaderFactory<Reader = R>,
+        on_progress: impl FnMut(u64),
+    ) -> Result<(u64, u64, [u8; HMAC_LEN]), ValidationError> {
         let mut reader = reader_factory.make_reader()?;
 
         let mut maybe_magic_number = [0; forward_secrecy::MAGIC_NUMBER.len()];
@@ -109,11 +156,9 @@ impl<R: AsyncRead + AsyncSkip + Unpin> FramesReader<R> {
                 (0, &maybe_magic_number[..])
             };
 
-        let (content_len, hmac) = Self::check_hmac(key, extra_bytes_to_hmac, reader).await?;
-
-        let mut new_reader = reader_factory.make_reader()?;
-        new_reader.skip(start_of_encrypted_data).await?;
-        Self::with_separate_hmac(key, new_reader.take(content_len), hmac).await
+        let (content_len, hmac) =
+            Self::check_hmac(key, extra_bytes_to_hmac, reader, on_progress).await?;
+        Ok((start_of_encrypted_data, content_len, hmac))
     }
 
     /// Checks the contents of `reader` against the HMAC key in `key`.
@@ -125,10 +170,14 @@ impl<R: AsyncRead + AsyncSkip + Unpin> FramesReader<R> {
     /// `extra_bytes_to_hmac` can be used to include bytes that have already been read from
     /// `reader`; they will be inserted at the front of the stream for the MAC calculation and
     /// included in the returned content length.
+    ///
+    /// `on_progress` is called with the offset in the input up to which the contents have been
+    /// checked.
     async fn check_hmac(
         key: &MessageBackupKey,
         extra_bytes_to_hmac: &[u8],
         mut reader: R,
+        mut on_progress: impl FnMut(u64),
     ) -> Result<(u64, [u8; HMAC_LEN]), ValidationError> {
         let position = reader.stream_position().await?;
         let content_len = reader
@@ -139,7 +188,14 @@ impl<R: AsyncRead + AsyncSkip + Unpin> FramesReader<R> {
         log::debug!("found {content_len} bytes with a {HMAC_LEN}-byte HMAC");
 
         let truncated_reader = reader.borrow_mut().take(content_len);
-        let actual_hmac = hmac_sha256(&key.hmac_key, extra_bytes_to_hmac, truncated_reader).await?;
+        let start_of_hmac_contents = position - extra_bytes_to_hmac.len() as u64;
+        let actual_hmac = hmac_sha256(
+            &key.hmac_key,
+            extra_bytes_to_hmac,
+            truncated_reader,
+            |bytes_checked| on_progress(start_of_hmac_contents + bytes_checked),
+        )
+        .await?;
         let expected_hmac = {
             let mut buf = [0; HMAC_LEN];
             reader.read_exact(&mut buf).await?;
@@ -161,12 +217,15 @@ impl<R: AsyncRead + AsyncSkip + Unpin> FramesReader<R> {
 
     /// Creates a `FramesReader` with the specified `key` and `expected_hmac`.
     ///
-    /// `reader` should already be truncated to only the bytes covered by the HMAC, hence the type.
+    /// `reader` should already be truncated to only the bytes covered by the HMAC, hence the type,
+    /// and start at `input_offset`.
     async fn with_separate_hmac(
         key: &MessageBackupKey,
         reader: futures::io::Take<R>,
+        input_offset: u64,
         expected_hmac: [u8; HMAC_LEN],
     ) -> Result<Self, ValidationError> {
+        let covered_len = reader.limit();
         let mut content = MacReader::new_sha256(reader, &key.hmac_key);
 
         let mut iv = [0; AES_IV_SIZE];
@@ -178,6 +237,8 @@ impl<R: AsyncRead + AsyncSkip + Unpin> FramesReader<R> {
         Ok(Self {
             reader: decompressed,
             expected_hmac,
+            input_offset,
+            covered_len,
         })
     }
 }
@@ -204,7 +265,10 @@ impl<R> MacReader<R, Hmac<Sha256>> {
 
 impl<R> UnvalidatedHmacReader<R> {
     pub(crate) fn new(reader: R) -> Self {
-        Self(reader)
+        Self {
+            reader,
+            bytes_read: 0,
+        }
     }
 }
 
@@ -214,7 +278,10 @@ impl<R: AsyncRead + Unpin> AsyncRead for UnvalidatedHmacReader<R> {
         cx: &mut std::task::Context<'_>,
         buf: &mut [u8],
     ) -> std::task::Poll<std::io::Result<usize>> {
-        std::pin::Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+        let Self { reader, bytes_read } = self.get_mut();
+        let count = futures::ready!(std::pin::Pin::new(reader).poll_read(cx, buf))?;
+        *bytes_read += count as u64;
+        std::task::Poll::Ready(Ok(count))
     }
 }
 
@@ -223,6 +290,10 @@ impl<R> VerifyHmac for UnvalidatedHmacReader<R> {
     async fn verify_hmac(self) -> Result<(), VerifyHmacError> {
         Ok(())
     }
+
+    fn input_bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
 }
 
 impl std::fmt::Display for HmacMismatchError {
@@ -245,6 +316,8 @@ impl<R: AsyncRead + Unpin> VerifyHmac for FramesReader<R> {
         let Self {
             expected_hmac: expected,
             reader,
+            input_offset: _,
+            covered_len: _,
         } = self;
         // It's possible that the outer reader didn't read all the way to the
         // end. This can happen when the GZIPped data has trailing padding after
@@ -261,23 +334,42 @@ impl<R: AsyncRead + Unpin> VerifyHmac for FramesReader<R> {
             Err(HmacMismatchError { expected, found }.into())
         }
     }
+
+    fn input_bytes_read(&self) -> u64 {
+        let remaining = self.reader.get_ref().get_ref().get_ref().get_ref().limit();
+        self.input_offset + (self.covered_len - remaining)
+    }
 }
 
 /// Convenience wrapper around HMAC-ing the whole contents of an [`AsyncRead`].
 ///
 /// `extra_bytes_to_hmac` can be used to include bytes that have already been read from `reader`;
 /// they will be inserted at the front of the stream for the MAC calculation.
+///
+/// `on_progress` is called with the number of bytes processed so far, including
+/// `extra_bytes_to_hmac`.
 async fn hmac_sha256(
     hmac_key: &[u8],
     extra_bytes_to_hmac: &[u8],
     reader: impl AsyncRead + Unpin,
+    mut on_progress: impl FnMut(u64),
 ) -> Result<[u8; HMAC_LEN], futures::io::Error> {
     let mut reader = MacReader::new_sha256(
         futures::io::Cursor::new(extra_bytes_to_hmac).chain(reader),
         hmac_key,
     );
-    let mut writer = futures::io::sink();
-    futures::io::copy(&mut reader, &mut writer).await?;
+    let mut buf = [0; 8 * 1024];
+    let mut bytes_processed = 0;
+    loop {
+        let count = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == futures::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        bytes_processed += count as u64;
+        on_progress(bytes_processed);
+    }
     Ok(reader.finalize().into())
 }
 
@@ -383,7 +475,7 @@ mod test {
         ctext = iv.into_iter().chain(ctext).collect();
 
         // Append the hmac
-        let hmac = hmac_sha256(&key.hmac_key, &[], Cursor::new(&ctext))
+        let hmac = hmac_sha256(&key.hmac_key, &[], Cursor::new(&ctext), |_| ())
             .await
             .expect("can hash");
         ctext.extend_from_slice(&hmac);
@@ -57,6 +57,13 @@ impl<R: AsyncRead + Unpin> Aes256CbcReader<R> {
         }
     }
 
+    /// Returns the wrapped `R` reader.
+    ///
+    /// No guarantees are made about how much has been read from it.
+    pub fn get_ref(&self) -> std::cell::Ref<'_, R> {
+        self.inner.borrow()
+    }
+
     /// Consumes the reader and returns the wrapped `R` reader.
     ///
     /// If this reader is exhaused (a call to [`AsyncRead::poll_read`] returned
@@ -0,0 +1,111 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockEncryptMut as _, BlockSizeUser as _, KeyIvInit as _};
+use aes::Aes256;
+use futures::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use hmac::{Hmac, Mac as _};
+use mediasan_common::{AsyncSkip, AsyncSkipExt as _};
+use sha2::Sha256;
+use subtle::ConstantTimeEq as _;
+
+use super::{
+    forward_secrecy, Aes256CbcReader, FramesReader, HmacMismatchError, MacReader, ReaderFactory,
+    ValidationError, AES_IV_SIZE, HMAC_LEN,
+};
+use crate::key::MessageBackupKey;
+use crate::parse::write_length_delimited;
+
+/// How much decrypted data to process at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Re-encrypts a backup with a new key, such as after the backup key is rotated.
+///
+/// The backup is decrypted and re-encrypted a chunk at a time without being decompressed, so the
+/// plaintext is never held in memory all at once. As when reading a backup, the old HMAC is
+/// checked before anything is decrypted and again once all of the contents have been read; if
+/// the second check fails, an error is returned after output has already been written, and that
+/// output must be discarded.
+///
+/// If `new_forward_secrecy_metadata` is provided, it must be a serialized `MetadataPb` for the
+/// new key, and the output uses the forward-secrecy format; otherwise, the output uses the legacy
+/// format. The old backup's metadata is never copied over, since it doesn't apply to the new key.
+pub async fn reencrypt<R: AsyncRead + AsyncSkip + Unpin>(
+    old_key: &MessageBackupKey,
+    mut reader_factory: impl ReaderFactory<Reader = R>,
+    new_key: &MessageBackupKey,
+    new_iv: &[u8; AES_IV_SIZE],
+    new_forward_secrecy_metadata: Option<&[u8]>,
+    mut output: impl AsyncWrite + Unpin,
+) -> Result<(), ValidationError> {
+    let (start_of_encrypted_data, content_len, expected_hmac) =
+        FramesReader::<R>::check_header_and_hmac(old_key, &mut reader_factory, |_| ()).await?;
+
+    let mut reader = reader_factory.make_reader()?;
+    reader.skip(start_of_encrypted_data).await?;
+    let mut old_contents = MacReader::new_sha256(reader.take(content_len), &old_key.hmac_key);
+    let mut old_iv = [0; AES_IV_SIZE];
+    old_contents.read_exact(&mut old_iv).await?;
+    let mut decrypted = Aes256CbcReader::new(&old_key.aes_key, &old_iv, old_contents);
+
+    if let Some(metadata) = new_forward_secrecy_metadata {
+        let mut header = forward_secrecy::MAGIC_NUMBER.to_vec();
+        write_length_delimited(&mut header, metadata);
+        output.write_all(&header).await?;
+    }
+    output.write_all(new_iv).await?;
+
+    let mut new_hmac = Hmac::<Sha256>::new_from_slice(&new_key.hmac_key).expect("correct key size");
+    new_hmac.update(new_iv);
+    let mut encryptor = cbc::Encryptor::<Aes256>::new((&new_key.aes_key).into(), new_iv.into());
+
+    let block_size = Aes256::block_size();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut pending = Vec::with_capacity(CHUNK_SIZE + block_size);
+    loop {
+        let count = decrypted.read(&mut chunk).await?;
+        if count == 0 {
+            break;
+        }
+        pending.extend_from_slice(&chunk[..count]);
+
+        // Encrypt all the complete blocks, saving any remainder for next time.
+        let complete_len = pending.len() - pending.len() % block_size;
+        for block in pending[..complete_len].chunks_exact_mut(block_size) {
+            encryptor.encrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        new_hmac.update(&pending[..complete_len]);
+        output.write_all(&pending[..complete_len]).await?;
+        pending.drain(..complete_len);
+    }
+
+    // Whatever's left is less than a block, so it fits in one block with its padding.
+    let remaining_len = pending.len();
+    pending.resize(block_size, 0);
+    let last = encryptor
+        .encrypt_padded_mut::<Pkcs7>(&mut pending, remaining_len)
+        .expect("provided enough room for padding");
+    new_hmac.update(last);
+    output.write_all(last).await?;
+
+    // Check the old HMAC again now that everything has been read, as FramesReader does.
+    let mut old_contents = decrypted.into_inner();
+    futures::io::copy(&mut old_contents, &mut futures::io::sink()).await?;
+    let found: [u8; HMAC_LEN] = old_contents.finalize().into();
+    if expected_hmac.ct_ne(&found).into() {
+        return Err(HmacMismatchError {
+            expected: expected_hmac,
+            found,
+        }
+        .into());
+    }
+
+    let new_hmac: [u8; HMAC_LEN] = new_hmac.finalize().into_bytes().into();
+    output.write_all(&new_hmac).await?;
+    output.flush().await?;
+    Ok(())
+}
@@ -7,6 +7,9 @@
 //!
 //! Contains code to read and validate message backup files.
 
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 use futures::AsyncRead;
@@ -24,18 +27,19 @@ use crate::unknown::{FormatPath, PathPart, UnknownValue, VisitUnknownFieldsExt a
 
 pub mod args;
 pub mod backup;
+pub mod filter;
 pub mod frame;
 pub mod key;
 pub mod parse;
 pub mod unknown;
 
 // visibility::make isn't supported for modules, so we have to write it twice instead.
-#[cfg(feature = "test-util")]
+#[cfg(feature = "export")]
 pub mod proto;
-#[cfg(not(feature = "test-util"))]
+#[cfg(not(feature = "export"))]
 pub(crate) mod proto;
 
-#[cfg(feature = "test-util")]
+#[cfg(feature = "export")]
 pub mod export;
 
 #[cfg(feature = "scramble")]
@@ -45,6 +49,8 @@ pub struct BackupReader<R> {
     purpose: Purpose,
     reader: VarintDelimitedReader<R>,
     pub visitor: fn(&dyn std::fmt::Debug),
+    /// Which chats and chat items to keep; see [`FrameFilter`](filter::FrameFilter).
+    pub filter: filter::FrameFilter,
 }
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -61,6 +67,12 @@ pub enum Error {
     InvalidProtobuf(#[from] protobuf::Error),
     /// mismatched HMAC: {0}
     HmacMismatch(#[from] HmacMismatchError),
+    /// validation was cancelled
+    Cancelled,
+    /// backup is too large to export ({0} bytes compressed)
+    TooLargeToExport(usize),
+    /// failed to compress backup: {0}
+    Compression(std::io::Error),
 }
 
 #[must_use]
@@ -106,6 +118,23 @@ impl std::fmt::Display for ReadError {
     }
 }
 
+/// How far reading a backup has gotten, as reported to [`BackupReader::validate_all_with_progress`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReadProgress {
+    /// Bytes of the frame stream read so far, after decryption and decompression.
+    ///
+    /// For an unencrypted backup, this is the offset into the input.
+    pub bytes_read: u64,
+    /// Bytes of the backup input consumed so far, including any read ahead of the frames returned.
+    ///
+    /// Unlike `bytes_read`, this can be compared against the size of the input.
+    pub input_bytes_read: u64,
+    /// Frames fully validated so far, which may lag behind the frames read.
+    pub frames_validated: usize,
+    /// The chat most recently seen in a validated frame, if any.
+    pub current_chat: Option<u64>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FoundUnknownField {
     pub frame_index: usize,
@@ -150,26 +179,64 @@ impl<R: AsyncRead + Unpin + VerifyHmac> BackupReader<R> {
     }
 
     pub async fn validate_all(self) -> ReadResult<()> {
-        self.collect_all().await.and_then(|partial| {
-            let _: CompletedBackup<ValidateOnly> = partial.try_into()?;
-            Ok(())
-        })
+        self.validate_all_with_progress(|_| ControlFlow::Continue(()))
+            .await
+    }
+
+    /// Like [`Self::validate_all`], but calls `on_progress` as the backup is read.
+    ///
+    /// `on_progress` is called after every frame is read, so it should be cheap; it's also called
+    /// once more when all frames have been validated. Returning [`ControlFlow::Break`] stops
+    /// reading, producing [`Error::Cancelled`].
+    pub async fn validate_all_with_progress(
+        self,
+        on_progress: impl FnMut(&ReadProgress) -> ControlFlow<()>,
+    ) -> ReadResult<()> {
+        self.collect_all_with_progress(on_progress)
+            .await
+            .and_then(|partial| {
+                let _: CompletedBackup<ValidateOnly> = partial.try_into()?;
+                Ok(())
+            })
     }
 
     pub async fn collect_all<M: backup::method::Method + backup::ReferencedTypes>(
         self,
     ) -> ReadResult<backup::PartialBackup<M>>
+    where
+        backup::PartialBackup<M>: Send,
+    {
+        self.collect_all_with_progress(|_| ControlFlow::Continue(()))
+            .await
+    }
+
+    /// Like [`Self::collect_all`], but calls `on_progress` as the backup is read.
+    ///
+    /// See [`Self::validate_all_with_progress`].
+    pub async fn collect_all_with_progress<M: backup::method::Method + backup::ReferencedTypes>(
+        self,
+        on_progress: impl FnMut(&ReadProgress) -> ControlFlow<()>,
+    ) -> ReadResult<backup::PartialBackup<M>>
     where
         backup::PartialBackup<M>: Send,
     {
         let Self {
             reader,
             visitor,
+            filter,
             purpose,
         } = self;
 
         let mut found_unknown_fields = Vec::new();
-        let result = read_all_frames(purpose, reader, visitor, &mut found_unknown_fields).await;
+        let result = read_all_frames(
+            purpose,
+            reader,
+            visitor,
+            filter,
+            on_progress,
+            &mut found_unknown_fields,
+        )
+        .await;
         ReadResult {
             found_unknown_fields,
             result,
@@ -184,6 +251,7 @@ impl<R: AsyncRead + Unpin> BackupReader<UnvalidatedHmacReader<R>> {
             reader,
             purpose,
             visitor: |_| (),
+            filter: Default::default(),
         }
     }
 }
@@ -194,11 +262,23 @@ impl<R: AsyncRead + AsyncSkip + Unpin> BackupReader<frame::FramesReader<R>> {
         factory: impl ReaderFactory<Reader = R>,
         purpose: Purpose,
     ) -> Result<Self, frame::ValidationError> {
-        let reader = frame::FramesReader::new(key, factory).await?;
+        Self::new_encrypted_compressed_with_progress(key, factory, purpose, |_| ()).await
+    }
+
+    /// Like [`Self::new_encrypted_compressed`], but reports the progress of the up-front HMAC
+    /// check as the number of input bytes checked so far.
+    pub async fn new_encrypted_compressed_with_progress(
+        key: &MessageBackupKey,
+        factory: impl ReaderFactory<Reader = R>,
+        purpose: Purpose,
+        on_hmac_progress: impl FnMut(u64),
+    ) -> Result<Self, frame::ValidationError> {
+        let reader = frame::FramesReader::new_with_progress(key, factory, on_hmac_progress).await?;
         Ok(Self {
             reader: VarintDelimitedReader::new(reader),
             purpose,
             visitor: |_| (),
+            filter: Default::default(),
         })
     }
 }
@@ -207,6 +287,8 @@ async fn read_all_frames<M: backup::method::Method + backup::ReferencedTypes>(
     purpose: Purpose,
     mut reader: VarintDelimitedReader<impl AsyncRead + Unpin + VerifyHmac>,
     mut visitor: impl FnMut(&dyn std::fmt::Debug) + Send + 'static,
+    filter: filter::FrameFilter,
+    mut on_progress: impl FnMut(&ReadProgress) -> ControlFlow<()>,
     unknown_fields: &mut Vec<FoundUnknownField>,
 ) -> Result<backup::PartialBackup<M>, Error>
 where
@@ -247,6 +329,21 @@ where
     const FRAMES_IN_FLIGHT: usize = 20;
     let (frame_tx, frame_rx) = std::sync::mpsc::sync_channel::<Box<[u8]>>(FRAMES_IN_FLIGHT);
 
+    // Updated by the frame-processing thread, read when reporting progress from this one.
+    let frames_validated = Arc::new(AtomicUsize::new(0));
+    // Offset by one, so that zero can mean "no chat yet".
+    let current_chat_plus_one = Arc::new(AtomicU64::new(0));
+    let current_progress = {
+        let frames_validated = frames_validated.clone();
+        let current_chat_plus_one = current_chat_plus_one.clone();
+        move |reader: &VarintDelimitedReader<_>| ReadProgress {
+            bytes_read: reader.bytes_read(),
+            input_bytes_read: reader.get_ref().input_bytes_read(),
+            frames_validated: frames_validated.load(Ordering::Relaxed),
+            current_chat: current_chat_plus_one.load(Ordering::Relaxed).checked_sub(1),
+        }
+    };
+
     let frame_processing_thread = std::thread::Builder::new()
         .name("libsignal-backup-processing".to_owned())
         .spawn(move || {
@@ -274,13 +371,26 @@ where
                 };
 
                 let these_unknown_fields =
-                    backup.parse_and_add_frame(&frame, |frame| visitor(frame))?;
+                    backup.parse_and_add_frame_filtered(&frame, &filter, |frame| {
+                        visitor(frame);
+                        let chat_id = match &frame.item {
+                            Some(proto::backup::frame::Item::Chat(chat)) => Some(chat.id),
+                            Some(proto::backup::frame::Item::ChatItem(item)) => Some(item.chatId),
+                            _ => None,
+                        };
+                        if let Some(chat_id) = chat_id {
+                            current_chat_plus_one
+                                .store(chat_id.saturating_add(1), Ordering::Relaxed);
+                        }
+                    })?;
                 add_found_unknown(&mut unknown_fields, these_unknown_fields, frame_index);
                 frame_index += 1;
+                frames_validated.fetch_add(1, Ordering::Relaxed);
             }
         })
         .expect("can create threads");
 
+    let mut cancelled = false;
     'outer: while let Some(mut buf) = reader.read_next().await.map_err(Error::Parse)? {
         // Try to send to the processing thread in a spin-loop.
         // Normally the processing thread is faster than the reader thread, so this should only spin
@@ -299,6 +409,11 @@ where
                 }
             }
         }
+
+        if on_progress(&current_progress(&reader)).is_break() {
+            cancelled = true;
+            break;
+        }
     }
     // Let the frame-processing thread know there's nothing more to read.
     drop(frame_tx);
@@ -309,6 +424,11 @@ where
         Err(panic) => std::panic::resume_unwind(panic),
     };
     unknown_fields.extend(inner_unknown_fields);
+    if cancelled {
+        return Err(Error::Cancelled);
+    }
+    // All frames have been validated now, so report that too.
+    let _ = on_progress(&current_progress(&reader));
 
     // Before reporting success, check that the HMAC still matches. This
     // prevents TOC/TOU issues.
@@ -347,12 +467,27 @@ impl<M: backup::method::Method + backup::ReferencedTypes> backup::PartialBackup<
     pub fn parse_and_add_frame(
         &mut self,
         raw_frame: &[u8],
+        visitor: impl FnMut(&proto::backup::Frame) + Send,
+    ) -> Result<Vec<(Vec<PathPart>, UnknownValue)>, crate::Error> {
+        self.parse_and_add_frame_filtered(raw_frame, &Default::default(), visitor)
+    }
+
+    /// Like [`Self::parse_and_add_frame`], but skips the frame if `filter` excludes it.
+    ///
+    /// Skipped frames are not passed to `visitor`, and their unknown fields are not reported.
+    pub fn parse_and_add_frame_filtered(
+        &mut self,
+        raw_frame: &[u8],
+        filter: &filter::FrameFilter,
         mut visitor: impl FnMut(&proto::backup::Frame) + Send,
     ) -> Result<Vec<(Vec<PathPart>, UnknownValue)>, crate::Error> {
         // Using `merge_from_bytes` instead of `parse_from_bytes` avoids having to unpack the Ok
         // case of the Result. (This is guaranteed equivalent by protobuf.)
         let mut frame_proto = proto::backup::Frame::new();
         frame_proto.merge_from_bytes(raw_frame)?;
+        if !filter.includes(&frame_proto) {
+            return Ok(vec![]);
+        }
         visitor(&frame_proto);
         let unknown_fields = frame_proto.collect_unknown_fields();
         self.add_frame(frame_proto)?;
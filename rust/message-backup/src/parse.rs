@@ -11,6 +11,7 @@ const VARINT_MAX_LENGTH: usize = 10;
 pub struct VarintDelimitedReader<R> {
     reader: R,
     buffer: ArrayVec<u8, VARINT_MAX_LENGTH>,
+    bytes_read: u64,
 }
 
 impl<R: AsyncRead + Unpin> VarintDelimitedReader<R> {
@@ -18,16 +19,31 @@ impl<R: AsyncRead + Unpin> VarintDelimitedReader<R> {
         Self {
             reader,
             buffer: ArrayVec::new(),
+            bytes_read: 0,
         }
     }
 
+    /// The number of bytes consumed by the items returned so far, including their length
+    /// prefixes.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
     pub async fn read_next(&mut self) -> Result<Option<Box<[u8]>>, std::io::Error> {
         let length = match self.read_next_varint().await? {
             None => return Ok(None),
             Some(length) => length,
         };
 
-        let Self { reader, buffer } = self;
+        let Self {
+            reader,
+            buffer,
+            bytes_read,
+        } = self;
 
         // Read `length` bytes, first from the buffer, then from the reader.
         let mut buf = Vec::with_capacity(length);
@@ -40,6 +56,7 @@ impl<R: AsyncRead + Unpin> VarintDelimitedReader<R> {
 
             reader.read_exact(&mut buf[buffered_byte_count..]).await?;
         }
+        *bytes_read += length as u64;
 
         Ok(Some(buf.into_boxed_slice()))
     }
@@ -54,7 +71,11 @@ impl<R: AsyncRead + Unpin> VarintDelimitedReader<R> {
     }
 
     async fn read_next_varint(&mut self) -> Result<Option<usize>, std::io::Error> {
-        let Self { buffer, reader } = self;
+        let Self {
+            buffer,
+            reader,
+            bytes_read,
+        } = self;
 
         fill_buffer_from_reader(reader, buffer).await?;
 
@@ -72,6 +93,7 @@ impl<R: AsyncRead + Unpin> VarintDelimitedReader<R> {
         drop(proto_reader);
 
         buffer.drain(..consumed_byte_count);
+        *bytes_read += consumed_byte_count as u64;
 
         Ok(Some(length.try_into().expect("u32::MAX < usize::MAX")))
     }
@@ -104,12 +126,25 @@ async fn fill_buffer_from_reader<R: AsyncRead + Unpin, const N: usize>(
     Ok(())
 }
 
+/// Appends `bytes` to `out`, preceded by their length as a varint, in the format read by
+/// [`VarintDelimitedReader`].
+pub(crate) fn write_length_delimited(out: &mut Vec<u8>, bytes: &[u8]) {
+    let mut length = bytes.len() as u64;
+    while length >= 0x80 {
+        out.push(u8::try_from(length & 0x7F).expect("masked") | 0x80);
+        length >>= 7;
+    }
+    out.push(u8::try_from(length).expect("less than 0x80"));
+    out.extend_from_slice(bytes);
+}
+
 #[cfg(test)]
 mod test {
     use assert_matches::assert_matches;
     use futures::executor::block_on;
     use futures::io::Cursor;
     use futures::pin_mut;
+    use test_case::test_case;
 
     use super::*;
 
@@ -270,4 +305,26 @@ mod test {
         );
         assert_matches!(block_on(reader.read_next()), Ok(None));
     }
+
+    #[test_case(0)]
+    #[test_case(127)]
+    #[test_case(128)]
+    #[test_case(20_000)]
+    fn write_length_delimited_round_trip(len: usize) {
+        let message = vec![0xAB; len];
+        let mut buf = Vec::new();
+        write_length_delimited(&mut buf, &message);
+        write_length_delimited(&mut buf, b"next");
+
+        let mut reader = VarintDelimitedReader::new(buf.as_slice());
+        assert_eq!(
+            block_on(reader.read_next()).expect("valid").as_deref(),
+            Some(&message[..])
+        );
+        assert_eq!(
+            block_on(reader.read_next()).expect("valid").as_deref(),
+            Some(&b"next"[..])
+        );
+        assert_matches!(block_on(reader.read_next()), Ok(None));
+    }
 }
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
 
 use assert_cmd::Command;
@@ -12,10 +13,13 @@ use futures::io::Cursor;
 use futures::AsyncRead;
 use libsignal_account_keys::{BackupForwardSecrecyToken, BackupKey};
 use libsignal_core::Aci;
+use libsignal_message_backup::backup::serialize::{Backup, Difference};
 use libsignal_message_backup::backup::Purpose;
+use libsignal_message_backup::filter::FrameFilter;
 use libsignal_message_backup::frame::{FileReaderFactory, VerifyHmac};
 use libsignal_message_backup::key::MessageBackupKey;
-use libsignal_message_backup::{BackupReader, ReadResult};
+use libsignal_message_backup::{BackupReader, ReadProgress, ReadResult};
+use test_case::test_case;
 
 const BACKUP_PURPOSE: Purpose = Purpose::RemoteBackup;
 
@@ -64,6 +68,41 @@ fn can_serialize_json_proto(input: Fixture<&str>) {
     )
 }
 
+#[dir_test(
+        dir: "$CARGO_MANIFEST_DIR/tests/res/test-cases",
+        glob: "valid/*.jsonproto",
+        postfix: "progress"
+    )]
+fn reports_progress(input: Fixture<&str>) {
+    let json_contents = input.into_content();
+    let json_contents = json5::from_str(json_contents).expect("invalid JSON");
+    let json_array = assert_matches!(json_contents, serde_json::Value::Array(contents) => contents);
+    // The first item is the BackupInfo, which isn't counted as a frame.
+    let frame_count = json_array.len() - 1;
+    let binproto =
+        libsignal_message_backup::backup::convert_from_json(json_array).expect("failed to convert");
+
+    let mut last_progress = ReadProgress::default();
+    let reader = BackupReader::new_unencrypted(Cursor::new(&binproto), BACKUP_PURPOSE);
+    let ReadResult { result, .. } =
+        futures::executor::block_on(reader.validate_all_with_progress(|progress| {
+            assert!(progress.bytes_read >= last_progress.bytes_read);
+            assert_eq!(progress.input_bytes_read, progress.bytes_read);
+            assert!(progress.frames_validated >= last_progress.frames_validated);
+            last_progress = progress.clone();
+            ControlFlow::Continue(())
+        }));
+    result.expect("valid backup");
+    assert_eq!(last_progress.bytes_read, binproto.len() as u64);
+    assert_eq!(last_progress.input_bytes_read, binproto.len() as u64);
+    assert_eq!(last_progress.frames_validated, frame_count);
+
+    let reader = BackupReader::new_unencrypted(Cursor::new(&binproto), BACKUP_PURPOSE);
+    let ReadResult { result, .. } =
+        futures::executor::block_on(reader.validate_all_with_progress(|_| ControlFlow::Break(())));
+    assert_matches!(result, Err(libsignal_message_backup::Error::Cancelled));
+}
+
 #[test]
 fn serialized_account_settings_is_valid() {
     let binproto = include_bytes!("res/canonical-backup.binproto");
@@ -128,6 +167,81 @@ fn is_legacy_test(path: &Path) -> bool {
         .map(|n| n.starts_with("legacy-"))
         .unwrap_or(false)
 }
+#[test]
+fn diff_reports_changed_chat_items() {
+    let read_backup = |json_array: Vec<serde_json::Value>| {
+        let binproto = libsignal_message_backup::backup::convert_from_json(json_array)
+            .expect("failed to convert");
+        let reader = BackupReader::new_unencrypted(Cursor::new(&binproto), BACKUP_PURPOSE);
+        let backup = futures::executor::block_on(reader.read_all())
+            .result
+            .expect("valid backup");
+        Backup::from(backup)
+    };
+
+    let json_contents = json5::from_str(include_str!(
+        "res/test-cases/valid/simple-chat-update-message.jsonproto"
+    ))
+    .expect("invalid JSON");
+    let mut json_array =
+        assert_matches!(json_contents, serde_json::Value::Array(contents) => contents);
+    let expected = read_backup(json_array.clone());
+
+    assert!(expected.diff(&read_backup(json_array.clone())).is_empty());
+
+    // Drop the last chat item and change the one before it.
+    json_array.pop();
+    json_array.last_mut().expect("has frames")["chatItem"]["updateMessage"]["simpleUpdate"]
+        ["type"] = "BLOCKED".into();
+    let diff = expected.diff(&read_backup(json_array));
+
+    let differences = diff.differences();
+    assert_eq!(differences.len(), 2, "{diff}");
+    assert!(
+        differences.iter().any(|difference| matches!(
+            difference,
+            Difference::Changed { path, .. } if path.contains(".items[") && path.contains("@19].message")
+        )),
+        "{diff}"
+    );
+    assert!(
+        differences.iter().any(|difference| matches!(
+            difference,
+            Difference::Missing { path, .. } if path.ends_with("@20]")
+        )),
+        "{diff}"
+    );
+}
+
+#[test_case(FrameFilter::chats([1]); "by chat")]
+#[test_case(FrameFilter::date_sent(0..19); "by date")]
+fn filtered_read_drops_only_chat_data(filter: FrameFilter) {
+    let json_contents = json5::from_str(include_str!(
+        "res/test-cases/valid/simple-chat-update-message.jsonproto"
+    ))
+    .expect("invalid JSON");
+    let json_array = assert_matches!(json_contents, serde_json::Value::Array(contents) => contents);
+    let binproto =
+        libsignal_message_backup::backup::convert_from_json(json_array).expect("failed to convert");
+
+    let read_backup = |filter| {
+        let mut reader = BackupReader::new_unencrypted(Cursor::new(&binproto), BACKUP_PURPOSE);
+        reader.filter = filter;
+        let backup = futures::executor::block_on(reader.read_all())
+            .result
+            .expect("valid backup");
+        Backup::from(backup)
+    };
+
+    let full = read_backup(FrameFilter::default());
+    let filtered = read_backup(filter);
+    let diff = full.diff(&filtered);
+    assert!(!diff.is_empty());
+    for difference in diff.differences() {
+        assert_matches!(difference, Difference::Missing { path, .. } if path.starts_with("chats["), "{diff}");
+    }
+}
+
 #[dir_test(
         dir: "$CARGO_MANIFEST_DIR/tests/res/test-cases",
         glob: "valid-encrypted/*.binproto.encrypted",
@@ -241,6 +355,33 @@ fn is_valid_encrypted_proto(input: Fixture<PathBuf>) {
     .unwrap_or_else(|e| panic!("expected valid, got {e}"));
     validate(reader);
 
+    // Progress is reported in terms of the input, both while checking the HMAC and while reading.
+    let input_len = std::fs::metadata(path).expect("can stat").len();
+    let hmac_len = 32;
+    let mut last_hmac_progress = 0;
+    let reader = futures::executor::block_on(BackupReader::new_encrypted_compressed_with_progress(
+        &key,
+        FileReaderFactory { path },
+        Purpose::RemoteBackup,
+        |checked| {
+            assert!(checked > last_hmac_progress);
+            last_hmac_progress = checked;
+        },
+    ))
+    .unwrap_or_else(|e| panic!("expected valid, got {e}"));
+    assert_eq!(last_hmac_progress, input_len - hmac_len);
+
+    let mut last_progress = ReadProgress::default();
+    let ReadResult { result, .. } =
+        futures::executor::block_on(reader.validate_all_with_progress(|progress| {
+            assert!(progress.input_bytes_read >= last_progress.input_bytes_read);
+            assert!(progress.input_bytes_read <= input_len - hmac_len);
+            last_progress = progress.clone();
+            ControlFlow::Continue(())
+        }));
+    result.expect("valid backup");
+    assert!(last_progress.input_bytes_read > 0);
+
     // The CLI tool should agree.
     let aci_string = ACI.service_id_string();
     let mut args = vec![
@@ -301,7 +301,7 @@ async fn connect_noise_direct(
     // TODO use connect state instead of connecting directly.
     drop(connect_state);
     let (result, _updates) = libsignal_net::infra::route::connect(
-        &RouteResolver { allow_ipv6: true },
+        &RouteResolver::default(),
         NoDelay,
         std::iter::once(ChatNoiseRoute {
             fragment: (
@@ -10,7 +10,9 @@ use std::convert::Infallible;
 
 use libsignal_net::infra::errors::LogSafeDisplay;
 
+pub mod keys;
 pub mod keytrans;
+pub mod messages;
 pub mod profiles;
 pub mod registration;
 pub mod usernames;
@@ -36,6 +38,25 @@ impl<'a, T> From<&'a T> for &'a Unauth<T> {
     }
 }
 
+/// Marker wrapper for authenticated connections.
+///
+/// You can get `&Auth<Connection>` from `&Connection` using `Into`.
+#[derive(derive_more::Deref)]
+#[repr(transparent)]
+pub struct Auth<T>(pub T);
+
+impl<'a, T> From<&'a T> for &'a Auth<T> {
+    fn from(value: &'a T) -> Self {
+        // SAFETY: See the implementation for Unauth above.
+        unsafe {
+            std::ptr::from_ref(value)
+                .cast::<Auth<T>>()
+                .as_ref()
+                .unwrap()
+        }
+    }
+}
+
 /// Marker wrapper for registration connections.
 #[derive(derive_more::Deref)]
 pub struct Registration<T>(pub T);
@@ -55,6 +76,10 @@ pub enum UserBasedAuthorization {
 pub enum RequestError<E, D = DisconnectedError> {
     /// the request timed out
     Timeout,
+    /// too many requests were already in flight, so this one was not sent
+    Busy,
+    /// sending would exceed the connection's local rate limit; try again in {retry_after:?}
+    WouldExceedRateLimit { retry_after: std::time::Duration },
     /// {0}
     Disconnected(D),
     /// {0}
@@ -128,13 +153,15 @@ pub enum ChallengeOption {
 ///
 /// This should be extended to include any new submodules' traits.
 pub trait UnauthenticatedChatApi:
-    keytrans::UnauthenticatedChatApi
+    keys::UnauthenticatedChatApi
+    + keytrans::UnauthenticatedChatApi
     + profiles::UnauthenticatedChatApi
     + usernames::UnauthenticatedChatApi
 {
 }
 impl<T> UnauthenticatedChatApi for T where
-    T: keytrans::UnauthenticatedChatApi
+    T: keys::UnauthenticatedChatApi
+        + keytrans::UnauthenticatedChatApi
         + profiles::UnauthenticatedChatApi
         + usernames::UnauthenticatedChatApi
 {
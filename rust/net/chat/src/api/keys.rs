@@ -0,0 +1,40 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use async_trait::async_trait;
+use libsignal_core::{DeviceId, ServiceId};
+use libsignal_protocol::PreKeyBundle;
+
+use super::{RequestError, UserBasedAuthorization};
+
+#[derive(Debug, displaydoc::Display)]
+pub enum GetPreKeysError {
+    /// authorization failed
+    AuthFailed,
+    /// account or device not found
+    NotFound,
+}
+
+/// Which of an account's devices to fetch pre-keys for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceSpecifier {
+    AllDevices,
+    Specific(DeviceId),
+}
+
+#[async_trait]
+pub trait UnauthenticatedChatApi {
+    /// Fetches the pre-key bundles needed to start sessions with `target`, without revealing the
+    /// local user's identity (i.e. for sealed sender).
+    ///
+    /// Returns one bundle per device; with [`DeviceSpecifier::AllDevices`], the order of the
+    /// devices is up to the server.
+    async fn get_pre_keys(
+        &self,
+        target: ServiceId,
+        device: DeviceSpecifier,
+        auth: UserBasedAuthorization,
+    ) -> Result<Vec<PreKeyBundle>, RequestError<GetPreKeysError>>;
+}
@@ -0,0 +1,85 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use async_trait::async_trait;
+use libsignal_core::{DeviceId, ServiceId};
+use libsignal_protocol::Timestamp;
+
+use super::{RateLimitChallenge, RequestError};
+
+/// A challenge the server requires the sender to complete before it will accept more messages.
+///
+/// Returned from [`AuthenticatedChatApi::send_message`] as [`RequestError::Challenge`]. Complete
+/// one of the `options`, pass the result to [`AuthenticatedChatApi::submit_challenge_response`],
+/// and then retry the send.
+pub type SendChallenge = RateLimitChallenge;
+
+/// A message encrypted for one of the recipient's devices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingDeviceMessage {
+    pub device_id: DeviceId,
+    pub registration_id: u32,
+    /// The `Envelope.Type` the recipient should use to decrypt `content`.
+    pub envelope_type: u32,
+    pub content: Vec<u8>,
+}
+
+/// The server's response to a successful send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SendMessageResponse {
+    /// Whether the sender has linked devices that should be sent a sync message.
+    pub needs_sync: bool,
+}
+
+#[derive(Debug, displaydoc::Display)]
+pub enum SendMessageError {
+    /// recipient is not registered
+    UnregisteredRecipient,
+    /// recipient's device list has changed
+    MismatchedDevices {
+        /// Devices that need a message but didn't get one.
+        missing_devices: Vec<DeviceId>,
+        /// Devices that were sent a message but don't exist.
+        extra_devices: Vec<DeviceId>,
+    },
+    /// sessions are out of date for some of the recipient's devices
+    StaleDevices(Vec<DeviceId>),
+}
+
+#[derive(Debug, displaydoc::Display)]
+pub enum SubmitChallengeError {
+    /// the challenge response was not accepted
+    InvalidResponse,
+}
+
+/// A completed [`SendChallenge`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChallengeResponse {
+    /// The result of solving a captcha for the challenge with the given `token`.
+    Captcha { token: String, captcha: String },
+    /// The challenge delivered to the device in a push notification.
+    PushChallenge { challenge: String },
+}
+
+#[async_trait]
+pub trait AuthenticatedChatApi {
+    /// Sends a message to every device of `destination`.
+    ///
+    /// `messages` should contain one entry for each of the recipient's devices.
+    async fn send_message(
+        &self,
+        destination: ServiceId,
+        timestamp: Timestamp,
+        messages: &[OutgoingDeviceMessage],
+        online: bool,
+        urgent: bool,
+    ) -> Result<SendMessageResponse, RequestError<SendMessageError>>;
+
+    /// Submits the solution to a [`SendChallenge`], after which sending can be retried.
+    async fn submit_challenge_response(
+        &self,
+        response: ChallengeResponse,
+    ) -> Result<(), RequestError<SubmitChallengeError>>;
+}
@@ -3,8 +3,11 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use libsignal_core::Aci;
+use libsignal_protocol::IdentityKey;
 
 use super::{RequestError, UserBasedAuthorization};
 
@@ -16,6 +19,35 @@ pub enum ProfileKeyCredentialRequestError {
     VersionNotFound,
 }
 
+#[derive(Debug, displaydoc::Display)]
+pub enum ProfileRequestError {
+    /// authorization failed
+    AuthFailed,
+    /// profile not found
+    NotFound,
+}
+
+/// A version of another user's profile, as stored on the server.
+///
+/// The fields other than `identity_key`, `unrestricted_unidentified_access`, and `capabilities` are
+/// encrypted with the owner's profile key and are passed along as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionedProfile {
+    pub identity_key: IdentityKey,
+    pub name: Option<Vec<u8>>,
+    pub about: Option<Vec<u8>>,
+    pub about_emoji: Option<Vec<u8>>,
+    /// The CDN path of the avatar, if the user has one.
+    pub avatar: Option<String>,
+    pub payment_address: Option<Vec<u8>>,
+    pub phone_number_sharing: Option<Vec<u8>>,
+    /// A checksum for verifying an access key derived from the profile key.
+    pub unidentified_access: Option<Vec<u8>>,
+    /// Whether anyone can send sealed sender messages to this user, even without their access key.
+    pub unrestricted_unidentified_access: bool,
+    pub capabilities: HashMap<String, bool>,
+}
+
 #[async_trait]
 pub trait UnauthenticatedChatApi {
     async fn get_profile_key_credential(
@@ -28,4 +60,12 @@ pub trait UnauthenticatedChatApi {
         zkgroup::profiles::ExpiringProfileKeyCredentialResponse,
         RequestError<ProfileKeyCredentialRequestError>,
     >;
+
+    /// Fetches the version of `peer_aci`'s profile that corresponds to `profile_key`.
+    async fn get_versioned_profile(
+        &self,
+        peer_aci: Aci,
+        profile_key: zkgroup::profiles::ProfileKey,
+        auth: UserBasedAuthorization,
+    ) -> Result<VersionedProfile, RequestError<ProfileRequestError>>;
 }
@@ -0,0 +1,313 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Builds authorized multi-recipient sends to a group.
+//!
+//! A Sealed Sender v2 message to a group is authorized with a single [`GroupSendFullToken`]
+//! covering exactly the recipients of the message. [`prepare_group_send`] produces both from the
+//! endorsements the client received for the group, so the two can't get out of sync.
+
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
+
+use libsignal_core::ServiceId;
+use libsignal_protocol::{
+    sealed_sender_multi_recipient_encrypt, IdentityKeyStore, ProtocolAddress, SessionRecord,
+    SignalProtocolError, UnidentifiedSenderMessageContent,
+};
+use rand::{CryptoRng, Rng};
+use zkgroup::groups::{GroupSecretParams, GroupSendEndorsement, GroupSendFullToken};
+
+use crate::api::UserBasedAuthorization;
+
+/// The endorsements a client has received for the current members of a group.
+pub struct GroupSendEndorsements {
+    /// When every endorsement in `endorsements` expires.
+    pub expiration: zkgroup::Timestamp,
+    pub endorsements: HashMap<ServiceId, GroupSendEndorsement>,
+}
+
+/// A multi-recipient message to a group, along with the authorization to send it.
+pub struct GroupSendPayload {
+    /// The serialized Sealed Sender v2 sent message.
+    pub payload: Vec<u8>,
+    /// Authorizes sending `payload`; use it as the request's `Group-Send-Token` header.
+    pub auth: UserBasedAuthorization,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum GroupSendError {
+    /// group send endorsements have expired
+    EndorsementsExpired,
+    /// no group send endorsement for recipient {0}
+    MissingEndorsement(String),
+    /// destination {0} is not a valid service ID
+    InvalidDestination(String),
+    /// {0}
+    Protocol(#[from] SignalProtocolError),
+}
+
+/// Encrypts `usmc` for `destinations` and creates the group send token that authorizes the send.
+///
+/// `destinations` and `destination_sessions` are as for
+/// [`sealed_sender_multi_recipient_encrypt`]. The token combines the endorsements for every
+/// recipient in `destinations` (each recipient once, no matter how many devices they have), so
+/// every recipient must have an entry in `endorsements`.
+#[allow(clippy::too_many_arguments)]
+pub async fn prepare_group_send<R: Rng + CryptoRng>(
+    endorsements: &GroupSendEndorsements,
+    group_secret_params: &GroupSecretParams,
+    destinations: &[&ProtocolAddress],
+    destination_sessions: &[&SessionRecord],
+    usmc: &UnidentifiedSenderMessageContent,
+    identity_store: &dyn IdentityKeyStore,
+    now: SystemTime,
+    rng: &mut R,
+) -> Result<GroupSendPayload, GroupSendError> {
+    if SystemTime::from(endorsements.expiration) <= now {
+        return Err(GroupSendError::EndorsementsExpired);
+    }
+
+    let token = combined_token(endorsements, group_secret_params, destinations)?;
+
+    let payload = sealed_sender_multi_recipient_encrypt(
+        destinations,
+        destination_sessions,
+        [],
+        usmc,
+        identity_store,
+        rng,
+    )
+    .await?;
+
+    Ok(GroupSendPayload {
+        payload,
+        auth: UserBasedAuthorization::Group(token),
+    })
+}
+
+fn combined_token(
+    endorsements: &GroupSendEndorsements,
+    group_secret_params: &GroupSecretParams,
+    destinations: &[&ProtocolAddress],
+) -> Result<GroupSendFullToken, GroupSendError> {
+    let mut recipients = HashSet::new();
+    let mut recipient_endorsements = vec![];
+    for destination in destinations {
+        let service_id = ServiceId::parse_from_service_id_string(destination.name())
+            .ok_or_else(|| GroupSendError::InvalidDestination(destination.name().to_owned()))?;
+        if !recipients.insert(service_id) {
+            continue;
+        }
+        let endorsement = endorsements
+            .endorsements
+            .get(&service_id)
+            .ok_or_else(|| GroupSendError::MissingEndorsement(service_id.service_id_string()))?;
+        recipient_endorsements.push(*endorsement);
+    }
+
+    Ok(GroupSendEndorsement::combine(recipient_endorsements)
+        .to_token(group_secret_params)
+        .into_full_token(endorsements.expiration))
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use futures_util::FutureExt as _;
+    use libsignal_core::{Aci, DeviceId};
+    use libsignal_protocol::{
+        kem, process_prekey_bundle, CiphertextMessageType, ContentHint, GenericSignedPreKey as _,
+        IdentityKeyPair, InMemSignalProtocolStore, KeyPair, KyberPreKeyRecord, PreKeyBundle,
+        SealedSenderV2SentMessage, SenderCertificate, ServerCertificate, Timestamp, UsePQRatchet,
+    };
+    use rand::SeedableRng as _;
+    use zkgroup::groups::{GroupSendDerivedKeyPair, GroupSendEndorsementsResponse};
+    use zkgroup::{ServerSecretParams, RANDOMNESS_LEN, SECONDS_PER_DAY};
+
+    use super::*;
+
+    // 2023-04-13 00:00:00 UTC
+    const DAY_ALIGNED_TIMESTAMP: zkgroup::Timestamp =
+        zkgroup::Timestamp::from_epoch_seconds(1681344000);
+
+    fn bundle_for<R: Rng + CryptoRng>(identity: &IdentityKeyPair, rng: &mut R) -> PreKeyBundle {
+        let signed_pre_key = KeyPair::generate(rng);
+        let signed_pre_key_signature = identity
+            .private_key()
+            .calculate_signature(&signed_pre_key.public_key.serialize(), rng)
+            .expect("can sign");
+        let kyber_pre_key =
+            KyberPreKeyRecord::generate(kem::KeyType::Kyber1024, 1.into(), identity.private_key())
+                .expect("can generate");
+        PreKeyBundle::new(
+            1,
+            DeviceId::new(1).expect("valid"),
+            None,
+            1.into(),
+            signed_pre_key.public_key,
+            signed_pre_key_signature.to_vec(),
+            1.into(),
+            kyber_pre_key.public_key().expect("valid"),
+            kyber_pre_key.signature().expect("valid"),
+            *identity.identity_key(),
+        )
+        .expect("valid")
+    }
+
+    fn sender_certificate<R: Rng + CryptoRng>(
+        sender: Aci,
+        identity: &IdentityKeyPair,
+        rng: &mut R,
+    ) -> SenderCertificate {
+        let trust_root = KeyPair::generate(rng);
+        let server_key = KeyPair::generate(rng);
+        let server_cert =
+            ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, rng)
+                .expect("valid");
+        SenderCertificate::new(
+            sender.service_id_string(),
+            None,
+            *identity.public_key(),
+            DeviceId::new(1).expect("valid"),
+            Timestamp::from_epoch_millis(u64::MAX),
+            server_cert,
+            &server_key.private_key,
+            rng,
+        )
+        .expect("valid")
+    }
+
+    #[test]
+    fn group_send() {
+        let mut rng = rand_chacha::ChaChaRng::from_seed([1; 32]);
+        let now = SystemTime::from(DAY_ALIGNED_TIMESTAMP);
+
+        let alice = Aci::from_uuid_bytes([0xAA; 16]);
+        let bob = Aci::from_uuid_bytes([0xBB; 16]);
+        let carol = Aci::from_uuid_bytes([0xCC; 16]);
+        let members: [ServiceId; 3] = [alice.into(), bob.into(), carol.into()];
+
+        // The server issues endorsements for the whole group, and Alice receives them.
+        let group_secret_params = GroupSecretParams::generate([0x43; RANDOMNESS_LEN]);
+        let server_secret_params = ServerSecretParams::generate([0x44; RANDOMNESS_LEN]);
+        let todays_key = GroupSendDerivedKeyPair::for_expiration(
+            DAY_ALIGNED_TIMESTAMP.add_seconds(SECONDS_PER_DAY),
+            &server_secret_params,
+        );
+        let response = GroupSendEndorsementsResponse::issue(
+            members.map(|member| group_secret_params.encrypt_service_id(member)),
+            &todays_key,
+            [0x45; RANDOMNESS_LEN],
+        );
+        let expiration = response.expiration();
+        let received = response
+            .receive_with_service_ids(
+                members,
+                DAY_ALIGNED_TIMESTAMP,
+                &group_secret_params,
+                server_secret_params.get_public_params(),
+            )
+            .expect("valid");
+        let endorsements = GroupSendEndorsements {
+            expiration,
+            endorsements: members
+                .into_iter()
+                .zip(received.into_iter().map(|received| received.decompressed))
+                .collect(),
+        };
+
+        // Alice has sessions with Bob and Carol.
+        let alice_identity = IdentityKeyPair::generate(&mut rng);
+        let mut alice_store = InMemSignalProtocolStore::new(alice_identity, 1).expect("valid");
+        let destinations = [bob, carol].map(|recipient| {
+            ProtocolAddress::new(
+                recipient.service_id_string(),
+                DeviceId::new(1).expect("valid"),
+            )
+        });
+        for destination in &destinations {
+            let identity = IdentityKeyPair::generate(&mut rng);
+            process_prekey_bundle(
+                destination,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                &bundle_for(&identity, &mut rng),
+                now,
+                &mut rng,
+                UsePQRatchet::Yes,
+            )
+            .now_or_never()
+            .expect("sync")
+            .expect("valid");
+        }
+        let destination_refs: Vec<&ProtocolAddress> = destinations.iter().collect();
+        let session_refs = alice_store
+            .session_store
+            .load_existing_sessions(&destination_refs)
+            .expect("present");
+
+        let usmc = UnidentifiedSenderMessageContent::new(
+            CiphertextMessageType::SenderKey,
+            sender_certificate(alice, &alice_identity, &mut rng),
+            vec![1, 2, 3],
+            ContentHint::Default,
+            None,
+        )
+        .expect("valid");
+
+        let send = prepare_group_send(
+            &endorsements,
+            &group_secret_params,
+            &destination_refs,
+            &session_refs,
+            &usmc,
+            &alice_store.identity_store,
+            now,
+            &mut rng,
+        )
+        .now_or_never()
+        .expect("sync")
+        .expect("valid");
+
+        let sent = SealedSenderV2SentMessage::parse(&send.payload).expect("valid");
+        assert_eq!(sent.recipients.len(), 2);
+        let token = assert_matches!(send.auth, UserBasedAuthorization::Group(token) => token);
+        token
+            .verify(
+                [bob.into(), carol.into()],
+                DAY_ALIGNED_TIMESTAMP,
+                &todays_key,
+            )
+            .expect("token covers exactly the recipients");
+
+        // Recipients outside the group can't be authorized.
+        let dave = ProtocolAddress::new(
+            Aci::from_uuid_bytes([0xDD; 16]).service_id_string(),
+            DeviceId::new(1).expect("valid"),
+        );
+        assert_matches!(
+            combined_token(&endorsements, &group_secret_params, &[&dave]),
+            Err(GroupSendError::MissingEndorsement(_))
+        );
+
+        // Nor can anyone, once the endorsements expire.
+        assert_matches!(
+            prepare_group_send(
+                &endorsements,
+                &group_secret_params,
+                &destination_refs,
+                &session_refs,
+                &usmc,
+                &alice_store.identity_store,
+                SystemTime::from(expiration),
+                &mut rng,
+            )
+            .now_or_never()
+            .expect("sync"),
+            Err(GroupSendError::EndorsementsExpired)
+        );
+    }
+}
@@ -4,6 +4,7 @@
 //
 
 pub mod api;
+pub mod group_send;
 mod logging;
 pub mod registration;
 pub mod ws;
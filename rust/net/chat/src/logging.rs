@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use libsignal_core::Aci;
+use libsignal_core::{Aci, ServiceId};
 
 pub struct Redact<T>(pub T);
 impl std::fmt::Display for Redact<&'_ uuid::Uuid> {
@@ -28,6 +28,16 @@ impl std::fmt::Display for Redact<&'_ Aci> {
     }
 }
 
+impl std::fmt::Display for Redact<&'_ ServiceId> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            ServiceId::Aci(_) => {}
+            ServiceId::Pni(_) => write!(f, "{}:", self.0.kind())?,
+        }
+        Redact(&self.0.raw_uuid()).fmt(f)
+    }
+}
+
 /// Redacts all but the last 3 characters of its contents, which are assumed to be hex.
 ///
 /// We keep the last characters rather than the first characters for consistency with the redaction
@@ -90,6 +100,19 @@ mod test {
         );
     }
 
+    #[test]
+    fn redact_service_id() {
+        let uuid = uuid::uuid!("8c78cd2a-16ff-427d-83dc-1a5e36ce713d");
+        assert_eq!(
+            Redact(&ServiceId::from(Aci::from(uuid))).to_string(),
+            "********-****-****-****-*********13d"
+        );
+        assert_eq!(
+            Redact(&ServiceId::from(libsignal_core::Pni::from(uuid))).to_string(),
+            "PNI:********-****-****-****-*********13d"
+        );
+    }
+
     #[test_case("" => "")]
     #[test_case("ab" => "ab")]
     #[test_case("abcd" => "[REDACTED_HEX: 1 skipped]bcd")]
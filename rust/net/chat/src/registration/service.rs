@@ -308,6 +308,12 @@ async fn send_request_to_connected_chat(
             ChatSendError::RequestHasInvalidHeader => SendRequestError::Unknown {
                 log_safe: "request had invalid header".into(),
             },
+            ChatSendError::WouldExceedRateLimit { retry_after: _ } => SendRequestError::Unknown {
+                log_safe: "request was throttled locally".into(),
+            },
+            ChatSendError::Busy => SendRequestError::Unknown {
+                log_safe: "too many requests in flight".into(),
+            },
         }
     })?;
 
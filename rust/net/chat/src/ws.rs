@@ -6,7 +6,9 @@
 //! The `ws` module and its submodules implement a chat server based on REST-like requests over a
 //! websocket, as implemented in [`libsignal_net::chat`].
 
+mod keys;
 mod keytrans;
+mod messages;
 mod profiles;
 // TODO make this not pub(crate)
 pub(crate) mod registration;
@@ -19,8 +21,9 @@ use base64::prelude::BASE64_STANDARD;
 use base64::Engine as _;
 use http::StatusCode;
 use libsignal_net::chat;
-use libsignal_net::infra::errors::LogSafeDisplay;
+use libsignal_net::infra::errors::{LogSafeDisplay, RetryLater};
 use libsignal_net::infra::{extract_retry_later, AsHttpHeader};
+use libsignal_net::trace::{TraceId, Traced};
 use serde_with::serde_as;
 
 use crate::api::{
@@ -67,27 +70,35 @@ impl WsConnection for chat::ChatConnection {
         log_safe_path: &str,
         request: chat::Request,
     ) -> Result<chat::Response, chat::SendError> {
-        let request_id = rand::random::<u16>();
+        let trace_id = TraceId::random();
         let method = request.method.clone();
-        log::info!("[{log_tag} {request_id:04x}] {method} {log_safe_path}");
+        log::info!("[{log_tag} {trace_id}] {method} {log_safe_path}");
 
         // TODO: Figure out timeouts for libsignal-net-chat APIs.
-        let result = self.send(request, Duration::MAX).await;
+        let result = self
+            .send_traced(
+                request,
+                Duration::MAX,
+                chat::RequestPriority::Normal,
+                trace_id,
+            )
+            .await
+            .map_err(|Traced { trace_id: _, error }| error);
 
         match &result {
             Ok(response) => {
                 if response.status.is_success() {
                     log::info!(
-                        "[{log_tag} {request_id:04x}] {method} {log_safe_path} {}",
+                        "[{log_tag} {trace_id}] {method} {log_safe_path} {}",
                         response.status
                     )
                 } else {
                     log::warn!(
-                        "[{log_tag} {request_id:04x}] {method} {log_safe_path} {}",
+                        "[{log_tag} {trace_id}] {method} {log_safe_path} {}",
                         response.status
                     );
                     log::debug!(
-                        "[{log_tag} {request_id:04x}] {} {}: {:?}",
+                        "[{log_tag} {trace_id}] {} {}: {:?}",
                         response.status,
                         response.message.as_deref().unwrap_or_default(),
                         DebugAsStrOrBytes(response.body.as_deref().unwrap_or_default())
@@ -95,7 +106,7 @@ impl WsConnection for chat::ChatConnection {
                 }
             }
             Err(e) => log::warn!(
-                "[{log_tag} {request_id:04x}] {method} {log_safe_path} - {}",
+                "[{log_tag} {trace_id}] {method} {log_safe_path} - {}",
                 e as &dyn LogSafeDisplay
             ),
         }
@@ -108,6 +119,10 @@ impl<E> From<chat::SendError> for RequestError<E> {
     fn from(value: chat::SendError) -> Self {
         match value {
             chat::SendError::RequestTimedOut => return RequestError::Timeout,
+            chat::SendError::Busy => return RequestError::Busy,
+            chat::SendError::WouldExceedRateLimit { retry_after } => {
+                return RequestError::WouldExceedRateLimit { retry_after }
+            }
             chat::SendError::Disconnected => DisconnectedError::Closed,
             chat::SendError::ConnectedElsewhere => DisconnectedError::ConnectedElsewhere,
             chat::SendError::ConnectionInvalidated => DisconnectedError::ConnectionInvalidated,
@@ -381,7 +396,6 @@ mod testutil {
 
 #[cfg(test)]
 mod test {
-    use libsignal_net::infra::errors::RetryLater;
     use libsignal_net::infra::AsStaticHttpHeader as _;
     use test_case::test_case;
 
@@ -437,4 +451,15 @@ mod test {
             .try_into_response()
             .map_err(|e| e.into_request_error(|_| None))
     }
+
+    #[test_case(chat::SendError::RequestTimedOut => matches RequestError::Timeout)]
+    #[test_case(chat::SendError::Busy => matches RequestError::Busy)]
+    #[test_case(
+        chat::SendError::WouldExceedRateLimit { retry_after: Duration::from_millis(1500) }
+    => matches RequestError::WouldExceedRateLimit { retry_after } if retry_after == Duration::from_millis(1500))]
+    fn send_error_is_kept_distinct(
+        input: chat::SendError,
+    ) -> RequestError<std::convert::Infallible> {
+        input.into()
+    }
 }
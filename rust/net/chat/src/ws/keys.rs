@@ -0,0 +1,327 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use async_trait::async_trait;
+use libsignal_core::{DeviceId, ServiceId};
+use libsignal_net::chat::Request;
+use libsignal_net::infra::AsHttpHeader as _;
+use libsignal_protocol::{kem, IdentityKey, PreKeyBundle, PublicKey};
+use serde_with::serde_as;
+
+use super::{TryIntoResponse as _, WsConnection};
+use crate::api::keys::{DeviceSpecifier, GetPreKeysError};
+use crate::api::{RequestError, Unauth, UserBasedAuthorization};
+use crate::logging::Redact;
+
+type Base64Padded =
+    serde_with::base64::Base64<serde_with::base64::Standard, serde_with::formats::Padded>;
+
+#[serde_as]
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PreKeyResponse {
+    #[serde_as(as = "Base64Padded")]
+    identity_key: Vec<u8>,
+    devices: Vec<DevicePreKeys>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DevicePreKeys {
+    device_id: u32,
+    registration_id: u32,
+    #[serde(default)]
+    pre_key: Option<UploadedPreKey>,
+    signed_pre_key: UploadedPreKey,
+    pq_pre_key: UploadedPreKey,
+}
+
+#[serde_as]
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadedPreKey {
+    key_id: u32,
+    #[serde_as(as = "Base64Padded")]
+    public_key: Vec<u8>,
+    #[serde_as(as = "Option<Base64Padded>")]
+    #[serde(default)]
+    signature: Option<Vec<u8>>,
+}
+
+impl PreKeyResponse {
+    fn into_bundles(self) -> Result<Vec<PreKeyBundle>, &'static str> {
+        let Self {
+            identity_key,
+            devices,
+        } = self;
+        let identity_key =
+            IdentityKey::decode(&identity_key).map_err(|_| "invalid identity key")?;
+
+        devices
+            .into_iter()
+            .map(|device| {
+                let DevicePreKeys {
+                    device_id,
+                    registration_id,
+                    pre_key,
+                    signed_pre_key,
+                    pq_pre_key,
+                } = device;
+                let device_id = DeviceId::try_from(device_id).map_err(|_| "invalid device ID")?;
+
+                let pre_key = pre_key
+                    .map(|pre_key| {
+                        PublicKey::deserialize(&pre_key.public_key)
+                            .map(|public_key| (pre_key.key_id.into(), public_key))
+                            .map_err(|_| "invalid one-time pre-key")
+                    })
+                    .transpose()?;
+                let signed_pre_key_public = PublicKey::deserialize(&signed_pre_key.public_key)
+                    .map_err(|_| "invalid signed pre-key")?;
+                let kyber_pre_key_public = kem::PublicKey::deserialize(&pq_pre_key.public_key)
+                    .map_err(|_| "invalid Kyber pre-key")?;
+
+                PreKeyBundle::new(
+                    registration_id,
+                    device_id,
+                    pre_key,
+                    signed_pre_key.key_id.into(),
+                    signed_pre_key_public,
+                    signed_pre_key
+                        .signature
+                        .ok_or("missing signed pre-key signature")?,
+                    pq_pre_key.key_id.into(),
+                    kyber_pre_key_public,
+                    pq_pre_key
+                        .signature
+                        .ok_or("missing Kyber pre-key signature")?,
+                    identity_key,
+                )
+                .map_err(|_| "invalid pre-key bundle")
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl<T: WsConnection> crate::api::keys::UnauthenticatedChatApi for Unauth<T> {
+    async fn get_pre_keys(
+        &self,
+        target: ServiceId,
+        device: DeviceSpecifier,
+        auth: UserBasedAuthorization,
+    ) -> Result<Vec<PreKeyBundle>, RequestError<GetPreKeysError>> {
+        let device = match device {
+            DeviceSpecifier::AllDevices => "*".to_owned(),
+            DeviceSpecifier::Specific(device_id) => device_id.to_string(),
+        };
+        let response = self
+            .send(
+                "unauth",
+                &format!("/v2/keys/{}/{device}", Redact(&target)),
+                Request {
+                    method: http::Method::GET,
+                    path: format!("/v2/keys/{}/{device}", target.service_id_string())
+                        .parse()
+                        .expect("valid"),
+                    headers: http::HeaderMap::from_iter([auth.as_header()]),
+                    body: None,
+                },
+            )
+            .await?;
+
+        let response: PreKeyResponse = response.try_into_response().map_err(|e| {
+            e.into_request_error(|response| {
+                Some(match response.status.as_u16() {
+                    401 => GetPreKeysError::AuthFailed,
+                    404 => GetPreKeysError::NotFound,
+                    _ => return None,
+                })
+            })
+        })?;
+
+        response
+            .into_bundles()
+            .map_err(|message| RequestError::Unexpected {
+                log_safe: message.to_owned(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use base64::prelude::BASE64_STANDARD;
+    use base64::Engine as _;
+    use futures_util::FutureExt as _;
+    use libsignal_core::{Aci, Pni};
+    use libsignal_net::chat;
+    use libsignal_protocol::{IdentityKeyPair, KeyPair};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::api::keys::UnauthenticatedChatApi;
+    use crate::ws::testutil::{empty, json, ProduceResponse, RequestValidator};
+    use crate::ws::ACCESS_KEY_HEADER_NAME;
+
+    const ACI_UUID: &str = "9d0652a3-dcc3-4d11-975f-74d61598733f";
+    const PNI_UUID: &str = "796abedb-ca4e-4f18-8803-1fde5b921f9f";
+
+    struct Keys {
+        identity_key: IdentityKey,
+        pre_key: PublicKey,
+        signed_pre_key: PublicKey,
+        kyber_pre_key: kem::PublicKey,
+    }
+
+    impl Keys {
+        fn generate() -> Self {
+            let mut rng = rand::rng();
+            Self {
+                identity_key: *IdentityKeyPair::generate(&mut rng).identity_key(),
+                pre_key: KeyPair::generate(&mut rng).public_key,
+                signed_pre_key: KeyPair::generate(&mut rng).public_key,
+                kyber_pre_key: kem::KeyPair::generate(kem::KeyType::Kyber1024, &mut rng).public_key,
+            }
+        }
+
+        fn response_json(&self) -> String {
+            let Self {
+                identity_key,
+                pre_key,
+                signed_pre_key,
+                kyber_pre_key,
+            } = self;
+            let encode = |bytes: &[u8]| BASE64_STANDARD.encode(bytes);
+            format!(
+                r#"{{
+                    "identityKey": "{}",
+                    "devices": [
+                        {{
+                            "deviceId": 1,
+                            "registrationId": 1234,
+                            "preKey": {{"keyId": 10, "publicKey": "{}"}},
+                            "signedPreKey": {{"keyId": 20, "publicKey": "{}", "signature": "AQID"}},
+                            "pqPreKey": {{"keyId": 30, "publicKey": "{}", "signature": "BAUG"}}
+                        }},
+                        {{
+                            "deviceId": 2,
+                            "registrationId": 5678,
+                            "signedPreKey": {{"keyId": 21, "publicKey": "{}", "signature": "AQID"}},
+                            "pqPreKey": {{"keyId": 31, "publicKey": "{}", "signature": "BAUG"}}
+                        }}
+                    ]
+                }}"#,
+                encode(&identity_key.serialize()),
+                encode(&pre_key.serialize()),
+                encode(&signed_pre_key.serialize()),
+                encode(&kyber_pre_key.serialize()),
+                encode(&signed_pre_key.serialize()),
+                encode(&kyber_pre_key.serialize()),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_all_devices() {
+        let keys = Keys::generate();
+        let aci = Aci::parse_from_service_id_string(ACI_UUID).expect("valid");
+
+        let validator = RequestValidator {
+            expected: Request {
+                method: http::Method::GET,
+                path: format!("/v2/keys/{ACI_UUID}/*").parse().expect("valid"),
+                headers: http::HeaderMap::from_iter([(
+                    ACCESS_KEY_HEADER_NAME,
+                    http::HeaderValue::from_static("AAAAAAAAAAAAAAAAAAAAAA=="),
+                )]),
+                body: None,
+            },
+            response: json(200, keys.response_json()),
+        };
+
+        let bundles = Unauth(validator)
+            .get_pre_keys(
+                aci.into(),
+                DeviceSpecifier::AllDevices,
+                UserBasedAuthorization::AccessKey([0; zkgroup::ACCESS_KEY_LEN]),
+            )
+            .now_or_never()
+            .expect("sync")
+            .expect("success");
+
+        let [first, second] = &bundles[..] else {
+            panic!("expected two bundles, got {}", bundles.len());
+        };
+
+        assert_eq!(first.device_id().unwrap(), DeviceId::new(1).unwrap());
+        assert_eq!(first.registration_id().unwrap(), 1234);
+        assert_eq!(first.identity_key().unwrap(), &keys.identity_key);
+        assert_eq!(first.pre_key_id().unwrap(), Some(10.into()));
+        assert_eq!(first.pre_key_public().unwrap(), Some(keys.pre_key));
+        assert_eq!(first.signed_pre_key_id().unwrap(), 20.into());
+        assert_eq!(first.signed_pre_key_public().unwrap(), keys.signed_pre_key);
+        assert_eq!(first.signed_pre_key_signature().unwrap(), [1, 2, 3]);
+        assert_eq!(first.kyber_pre_key_id().unwrap(), 30.into());
+        assert_eq!(
+            first.kyber_pre_key_public().unwrap().serialize(),
+            keys.kyber_pre_key.serialize()
+        );
+        assert_eq!(first.kyber_pre_key_signature().unwrap(), [4, 5, 6]);
+
+        assert_eq!(second.device_id().unwrap(), DeviceId::new(2).unwrap());
+        assert_eq!(second.pre_key_id().unwrap(), None);
+        assert_eq!(second.kyber_pre_key_id().unwrap(), 31.into());
+    }
+
+    #[tokio::test]
+    async fn test_specific_device_for_pni() {
+        let keys = Keys::generate();
+        let pni = Pni::parse_from_service_id_string(&format!("PNI:{PNI_UUID}")).expect("valid");
+
+        let validator = RequestValidator {
+            expected: Request {
+                method: http::Method::GET,
+                path: format!("/v2/keys/PNI:{PNI_UUID}/2").parse().expect("valid"),
+                headers: http::HeaderMap::from_iter([(
+                    ACCESS_KEY_HEADER_NAME,
+                    http::HeaderValue::from_static("AAAAAAAAAAAAAAAAAAAAAA=="),
+                )]),
+                body: None,
+            },
+            response: json(200, keys.response_json()),
+        };
+
+        Unauth(validator)
+            .get_pre_keys(
+                pni.into(),
+                DeviceSpecifier::Specific(DeviceId::new(2).unwrap()),
+                UserBasedAuthorization::AccessKey([0; zkgroup::ACCESS_KEY_LEN]),
+            )
+            .now_or_never()
+            .expect("sync")
+            .expect("success");
+    }
+
+    #[test_case(empty(401) => matches RequestError::Other(GetPreKeysError::AuthFailed))]
+    #[test_case(empty(404) => matches RequestError::Other(GetPreKeysError::NotFound))]
+    #[test_case(json(200, r#"{"identityKey": "AA==", "devices": []}"#) => matches RequestError::Unexpected { .. })]
+    #[test_case(json(200, r#"{"devices": []}"#) => matches RequestError::Unexpected { .. })]
+    #[test_case(empty(500) => matches RequestError::ServerSideError)]
+    #[tokio::test]
+    async fn test_unsuccessful_requests(response: chat::Response) -> RequestError<GetPreKeysError> {
+        let aci = Aci::parse_from_service_id_string(ACI_UUID).expect("valid");
+
+        Unauth(ProduceResponse(response))
+            .get_pre_keys(
+                aci.into(),
+                DeviceSpecifier::AllDevices,
+                UserBasedAuthorization::AccessKey([0; zkgroup::ACCESS_KEY_LEN]),
+            )
+            .now_or_never()
+            .expect("sync")
+            .map(|_| ())
+            .expect_err("should have failed")
+    }
+}
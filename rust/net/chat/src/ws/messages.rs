@@ -0,0 +1,358 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use async_trait::async_trait;
+use libsignal_core::{DeviceId, ServiceId};
+use libsignal_net::chat::Request;
+use libsignal_protocol::Timestamp;
+use serde_with::serde_as;
+
+use super::{parse_json_from_body, Empty, TryIntoResponse as _, WsConnection, CONTENT_TYPE_JSON};
+use crate::api::messages::{
+    ChallengeResponse, OutgoingDeviceMessage, SendMessageError, SendMessageResponse,
+    SubmitChallengeError,
+};
+use crate::api::{Auth, RequestError};
+use crate::logging::Redact;
+
+type Base64Padded =
+    serde_with::base64::Base64<serde_with::base64::Standard, serde_with::formats::Padded>;
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendMessageRequest<'a> {
+    messages: Vec<DeviceMessage<'a>>,
+    online: bool,
+    urgent: bool,
+    timestamp: u64,
+}
+
+#[serde_as]
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceMessage<'a> {
+    #[serde(rename = "type")]
+    envelope_type: u32,
+    destination_device_id: u32,
+    destination_registration_id: u32,
+    #[serde_as(as = "Base64Padded")]
+    content: &'a [u8],
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendMessageResponseBody {
+    #[serde(default)]
+    needs_sync: bool,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MismatchedDevicesBody {
+    #[serde(default)]
+    missing_devices: Vec<u32>,
+    #[serde(default)]
+    extra_devices: Vec<u32>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StaleDevicesBody {
+    stale_devices: Vec<u32>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type")]
+enum SubmitChallengeRequest<'a> {
+    #[serde(rename = "captcha")]
+    Captcha { token: &'a str, captcha: &'a str },
+    #[serde(rename = "rateLimitPushChallenge")]
+    PushChallenge { challenge: &'a str },
+}
+
+fn device_ids(ids: Vec<u32>) -> Option<Vec<DeviceId>> {
+    ids.into_iter()
+        .map(|id| DeviceId::try_from(id).ok())
+        .collect()
+}
+
+#[async_trait]
+impl<T: WsConnection> crate::api::messages::AuthenticatedChatApi for Auth<T> {
+    async fn send_message(
+        &self,
+        destination: ServiceId,
+        timestamp: Timestamp,
+        messages: &[OutgoingDeviceMessage],
+        online: bool,
+        urgent: bool,
+    ) -> Result<SendMessageResponse, RequestError<SendMessageError>> {
+        let body = SendMessageRequest {
+            messages: messages
+                .iter()
+                .map(|message| {
+                    let OutgoingDeviceMessage {
+                        device_id,
+                        registration_id,
+                        envelope_type,
+                        content,
+                    } = message;
+                    DeviceMessage {
+                        envelope_type: *envelope_type,
+                        destination_device_id: (*device_id).into(),
+                        destination_registration_id: *registration_id,
+                        content,
+                    }
+                })
+                .collect(),
+            online,
+            urgent,
+            timestamp: timestamp.epoch_millis(),
+        };
+
+        let response = self
+            .send(
+                "auth",
+                &format!("/v1/messages/{}", Redact(&destination)),
+                Request {
+                    method: http::Method::PUT,
+                    path: format!("/v1/messages/{}", destination.service_id_string())
+                        .parse()
+                        .expect("valid"),
+                    headers: http::HeaderMap::from_iter([CONTENT_TYPE_JSON]),
+                    body: Some(
+                        serde_json::to_vec(&body)
+                            .expect("can always serialize")
+                            .into(),
+                    ),
+                },
+            )
+            .await?;
+
+        let SendMessageResponseBody { needs_sync } = response.try_into_response().map_err(|e| {
+            e.into_request_error(|response| {
+                Some(match response.status.as_u16() {
+                    404 => SendMessageError::UnregisteredRecipient,
+                    409 => {
+                        let MismatchedDevicesBody {
+                            missing_devices,
+                            extra_devices,
+                        } = parse_json_from_body(response).ok()?;
+                        SendMessageError::MismatchedDevices {
+                            missing_devices: device_ids(missing_devices)?,
+                            extra_devices: device_ids(extra_devices)?,
+                        }
+                    }
+                    410 => {
+                        let StaleDevicesBody { stale_devices } =
+                            parse_json_from_body(response).ok()?;
+                        SendMessageError::StaleDevices(device_ids(stale_devices)?)
+                    }
+                    _ => return None,
+                })
+            })
+        })?;
+
+        Ok(SendMessageResponse { needs_sync })
+    }
+
+    async fn submit_challenge_response(
+        &self,
+        response: ChallengeResponse,
+    ) -> Result<(), RequestError<SubmitChallengeError>> {
+        let body = match &response {
+            ChallengeResponse::Captcha { token, captcha } => {
+                SubmitChallengeRequest::Captcha { token, captcha }
+            }
+            ChallengeResponse::PushChallenge { challenge } => {
+                SubmitChallengeRequest::PushChallenge { challenge }
+            }
+        };
+
+        let response = self
+            .send(
+                "auth",
+                "/v1/challenge",
+                Request {
+                    method: http::Method::PUT,
+                    path: http::uri::PathAndQuery::from_static("/v1/challenge"),
+                    headers: http::HeaderMap::from_iter([CONTENT_TYPE_JSON]),
+                    body: Some(
+                        serde_json::to_vec(&body)
+                            .expect("can always serialize")
+                            .into(),
+                    ),
+                },
+            )
+            .await?;
+
+        let Empty = response.try_into_response().map_err(|e| {
+            e.into_request_error(|response| {
+                Some(match response.status.as_u16() {
+                    428 => SubmitChallengeError::InvalidResponse,
+                    _ => return None,
+                })
+            })
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use futures_util::FutureExt as _;
+    use libsignal_core::{Aci, Pni};
+    use libsignal_net::chat;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::api::messages::{AuthenticatedChatApi, SendChallenge};
+    use crate::api::ChallengeOption;
+    use crate::ws::testutil::{empty, json, ProduceResponse, RequestValidator};
+
+    const ACI_UUID: &str = "9d0652a3-dcc3-4d11-975f-74d61598733f";
+    const PNI_UUID: &str = "796abedb-ca4e-4f18-8803-1fde5b921f9f";
+
+    fn messages() -> Vec<OutgoingDeviceMessage> {
+        vec![
+            OutgoingDeviceMessage {
+                device_id: DeviceId::new(1).expect("valid"),
+                registration_id: 1234,
+                envelope_type: 1,
+                content: vec![1, 2, 3],
+            },
+            OutgoingDeviceMessage {
+                device_id: DeviceId::new(2).expect("valid"),
+                registration_id: 5678,
+                envelope_type: 3,
+                content: vec![4, 5, 6],
+            },
+        ]
+    }
+
+    fn send(
+        response: chat::Response,
+    ) -> Result<SendMessageResponse, RequestError<SendMessageError>> {
+        Auth(ProduceResponse(response))
+            .send_message(
+                Aci::parse_from_service_id_string(ACI_UUID)
+                    .expect("valid")
+                    .into(),
+                Timestamp::from_epoch_millis(1_700_000_000_000),
+                &messages(),
+                false,
+                true,
+            )
+            .now_or_never()
+            .expect("sync")
+    }
+
+    #[test_case(ServiceId::from(Aci::parse_from_service_id_string(ACI_UUID).unwrap()), ACI_UUID)]
+    #[test_case(ServiceId::from(Pni::parse_from_service_id_string(PNI_UUID).unwrap()), &format!("PNI:{PNI_UUID}"))]
+    fn test_send_message(destination: ServiceId, path_id: &str) {
+        let validator = RequestValidator {
+            expected: Request {
+                method: http::Method::PUT,
+                path: format!("/v1/messages/{path_id}").parse().expect("valid"),
+                headers: http::HeaderMap::from_iter([CONTENT_TYPE_JSON]),
+                body: Some(
+                    concat!(
+                        r#"{"messages":["#,
+                        r#"{"type":1,"destinationDeviceId":1,"destinationRegistrationId":1234,"content":"AQID"},"#,
+                        r#"{"type":3,"destinationDeviceId":2,"destinationRegistrationId":5678,"content":"BAUG"}"#,
+                        r#"],"online":false,"urgent":true,"timestamp":1700000000000}"#
+                    )
+                    .into(),
+                ),
+            },
+            response: json(200, r#"{"needsSync":true}"#),
+        };
+
+        let response = Auth(validator)
+            .send_message(
+                destination,
+                Timestamp::from_epoch_millis(1_700_000_000_000),
+                &messages(),
+                false,
+                true,
+            )
+            .now_or_never()
+            .expect("sync")
+            .expect("success");
+        assert_eq!(response, SendMessageResponse { needs_sync: true });
+    }
+
+    #[test]
+    fn test_send_message_challenge() {
+        let result = send(json(
+            428,
+            r#"{"token":"zzz","options":["captcha","pushChallenge"]}"#,
+        ));
+        assert_matches!(
+            result,
+            Err(RequestError::Challenge(SendChallenge { token, options }))
+                if token == "zzz" && options == [ChallengeOption::Captcha, ChallengeOption::PushChallenge]
+        );
+    }
+
+    #[test]
+    fn test_send_message_errors() {
+        assert_matches!(
+            send(empty(404)),
+            Err(RequestError::Other(SendMessageError::UnregisteredRecipient))
+        );
+        assert_matches!(
+            send(json(409, r#"{"missingDevices":[3],"extraDevices":[2]}"#)),
+            Err(RequestError::Other(SendMessageError::MismatchedDevices { missing_devices, extra_devices }))
+                if missing_devices == [DeviceId::new(3).unwrap()] && extra_devices == [DeviceId::new(2).unwrap()]
+        );
+        assert_matches!(
+            send(json(410, r#"{"staleDevices":[1]}"#)),
+            Err(RequestError::Other(SendMessageError::StaleDevices(devices)))
+                if devices == [DeviceId::new(1).unwrap()]
+        );
+        assert_matches!(send(empty(409)), Err(RequestError::Unexpected { .. }));
+    }
+
+    #[test_case(
+        ChallengeResponse::Captcha { token: "zzz".into(), captcha: "solved".into() },
+        r#"{"type":"captcha","token":"zzz","captcha":"solved"}"#
+    )]
+    #[test_case(
+        ChallengeResponse::PushChallenge { challenge: "pushed".into() },
+        r#"{"type":"rateLimitPushChallenge","challenge":"pushed"}"#
+    )]
+    fn test_submit_challenge_response(response: ChallengeResponse, expected_body: &'static str) {
+        let validator = RequestValidator {
+            expected: Request {
+                method: http::Method::PUT,
+                path: http::uri::PathAndQuery::from_static("/v1/challenge"),
+                headers: http::HeaderMap::from_iter([CONTENT_TYPE_JSON]),
+                body: Some(expected_body.into()),
+            },
+            response: empty(200),
+        };
+
+        Auth(validator)
+            .submit_challenge_response(response)
+            .now_or_never()
+            .expect("sync")
+            .expect("success");
+    }
+
+    #[test]
+    fn test_submit_challenge_response_rejected() {
+        let result = Auth(ProduceResponse(empty(428)))
+            .submit_challenge_response(ChallengeResponse::PushChallenge {
+                challenge: "wrong".into(),
+            })
+            .now_or_never()
+            .expect("sync");
+        assert_matches!(
+            result,
+            Err(RequestError::Other(SubmitChallengeError::InvalidResponse))
+        );
+    }
+}
@@ -3,14 +3,19 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use libsignal_core::Aci;
 use libsignal_net::chat::Request;
 use libsignal_net::infra::AsHttpHeader as _;
+use libsignal_protocol::IdentityKey;
 use serde_with::serde_as;
 
 use super::{TryIntoResponse as _, WsConnection};
-use crate::api::profiles::ProfileKeyCredentialRequestError;
+use crate::api::profiles::{
+    ProfileKeyCredentialRequestError, ProfileRequestError, VersionedProfile,
+};
 use crate::api::{RequestError, Unauth, UserBasedAuthorization};
 use crate::logging::{Redact, RedactHex};
 
@@ -79,6 +84,108 @@ impl<T: WsConnection> crate::api::profiles::UnauthenticatedChatApi for Unauth<T>
             log_safe: e.to_string(),
         })
     }
+
+    async fn get_versioned_profile(
+        &self,
+        peer_aci: Aci,
+        profile_key: zkgroup::profiles::ProfileKey,
+        auth: UserBasedAuthorization,
+    ) -> Result<VersionedProfile, RequestError<ProfileRequestError>> {
+        let profile_key_version = profile_key.get_profile_key_version(peer_aci);
+        let response = self
+            .send(
+                "unauth",
+                &format!(
+                    "/v1/profile/{}/{}",
+                    Redact(&peer_aci),
+                    RedactHex(profile_key_version.as_ref()),
+                ),
+                Request {
+                    method: http::Method::GET,
+                    path: format!(
+                        "/v1/profile/{}/{}",
+                        peer_aci.service_id_string(),
+                        profile_key_version.as_ref(),
+                    )
+                    .parse()
+                    .expect("valid"),
+                    headers: http::HeaderMap::from_iter([auth.as_header()]),
+                    body: None,
+                },
+            )
+            .await?;
+
+        #[serde_as]
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct VersionedProfileResponse {
+            #[serde_as(as = "Base64Padded")]
+            identity_key: Vec<u8>,
+            #[serde_as(as = "Option<Base64Padded>")]
+            #[serde(default)]
+            name: Option<Vec<u8>>,
+            #[serde_as(as = "Option<Base64Padded>")]
+            #[serde(default)]
+            about: Option<Vec<u8>>,
+            #[serde_as(as = "Option<Base64Padded>")]
+            #[serde(default)]
+            about_emoji: Option<Vec<u8>>,
+            #[serde(default)]
+            avatar: Option<String>,
+            #[serde_as(as = "Option<Base64Padded>")]
+            #[serde(default)]
+            payment_address: Option<Vec<u8>>,
+            #[serde_as(as = "Option<Base64Padded>")]
+            #[serde(default)]
+            phone_number_sharing: Option<Vec<u8>>,
+            #[serde_as(as = "Option<Base64Padded>")]
+            #[serde(default)]
+            unidentified_access: Option<Vec<u8>>,
+            #[serde(default)]
+            unrestricted_unidentified_access: bool,
+            #[serde(default)]
+            capabilities: HashMap<String, bool>,
+        }
+
+        let VersionedProfileResponse {
+            identity_key,
+            name,
+            about,
+            about_emoji,
+            avatar,
+            payment_address,
+            phone_number_sharing,
+            unidentified_access,
+            unrestricted_unidentified_access,
+            capabilities,
+        } = response.try_into_response().map_err(|e| {
+            e.into_request_error(|response| {
+                Some(match response.status.as_u16() {
+                    401 => ProfileRequestError::AuthFailed,
+                    404 => ProfileRequestError::NotFound,
+                    _ => return None,
+                })
+            })
+        })?;
+
+        let identity_key =
+            IdentityKey::decode(&identity_key).map_err(|_| RequestError::Unexpected {
+                log_safe: "invalid identity key".to_owned(),
+            })?;
+
+        Ok(VersionedProfile {
+            identity_key,
+            name,
+            about,
+            about_emoji,
+            avatar,
+            payment_address,
+            phone_number_sharing,
+            unidentified_access,
+            unrestricted_unidentified_access,
+            capabilities,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +194,7 @@ mod test {
     use base64::Engine as _;
     use futures_util::FutureExt as _;
     use libsignal_net::chat;
+    use libsignal_protocol::IdentityKeyPair;
     use test_case::test_case;
 
     use super::*;
@@ -203,4 +311,92 @@ mod test {
             .map(|_| ())
             .expect_err("should have failed")
     }
+
+    const PROFILE_KEY_VERSION: &str =
+        "f74078448aa501a163593a4c0b2ec4644b27a2a747639bb1a5e2af71ff355d9c";
+
+    #[tokio::test]
+    async fn test_versioned_profile() {
+        let aci = Aci::parse_from_service_id_string(ACI_UUID).expect("valid");
+        let profile_key = zkgroup::profiles::ProfileKey::create(zkgroup::TEST_ARRAY_32_1);
+        let identity_key = *IdentityKeyPair::generate(&mut rand::rng()).identity_key();
+
+        let validator = RequestValidator {
+            expected: Request {
+                method: http::Method::GET,
+                path: format!("/v1/profile/{ACI_UUID}/{PROFILE_KEY_VERSION}")
+                    .parse()
+                    .expect("valid"),
+                headers: http::HeaderMap::from_iter([(
+                    ACCESS_KEY_HEADER_NAME,
+                    http::HeaderValue::from_static("AAAAAAAAAAAAAAAAAAAAAA=="),
+                )]),
+                body: None,
+            },
+            response: json(
+                200,
+                format!(
+                    r#"{{
+                        "identityKey": "{}",
+                        "name": "AQID",
+                        "avatar": "profiles/abc",
+                        "unidentifiedAccess": "BAU=",
+                        "unrestrictedUnidentifiedAccess": false,
+                        "capabilities": {{"deleteSync": true}},
+                        "badges": []
+                    }}"#,
+                    BASE64_STANDARD.encode(identity_key.serialize())
+                ),
+            ),
+        };
+
+        let profile = Unauth(validator)
+            .get_versioned_profile(
+                aci,
+                profile_key,
+                UserBasedAuthorization::AccessKey([0; zkgroup::ACCESS_KEY_LEN]),
+            )
+            .now_or_never()
+            .expect("sync")
+            .expect("success");
+
+        assert_eq!(
+            profile,
+            VersionedProfile {
+                identity_key,
+                name: Some(vec![1, 2, 3]),
+                about: None,
+                about_emoji: None,
+                avatar: Some("profiles/abc".to_owned()),
+                payment_address: None,
+                phone_number_sharing: None,
+                unidentified_access: Some(vec![4, 5]),
+                unrestricted_unidentified_access: false,
+                capabilities: HashMap::from_iter([("deleteSync".to_owned(), true)]),
+            }
+        );
+    }
+
+    #[test_case(empty(401) => matches RequestError::Other(ProfileRequestError::AuthFailed))]
+    #[test_case(empty(404) => matches RequestError::Other(ProfileRequestError::NotFound))]
+    #[test_case(json(200, r#"{"identityKey": "AA=="}"#) => matches RequestError::Unexpected { .. })]
+    #[test_case(json(200, r#"{"name": "AA=="}"#) => matches RequestError::Unexpected { .. })]
+    #[test_case(empty(500) => matches RequestError::ServerSideError)]
+    #[tokio::test]
+    async fn test_unsuccessful_versioned_profile_requests(
+        response: chat::Response,
+    ) -> RequestError<ProfileRequestError> {
+        let aci = Aci::parse_from_service_id_string(ACI_UUID).expect("valid");
+        let profile_key = zkgroup::profiles::ProfileKey::create(zkgroup::TEST_ARRAY_32_1);
+
+        Unauth(ProduceResponse(response))
+            .get_versioned_profile(
+                aci,
+                profile_key,
+                UserBasedAuthorization::AccessKey([0; zkgroup::ACCESS_KEY_LEN]),
+            )
+            .now_or_never()
+            .expect("sync")
+            .expect_err("should have failed")
+    }
 }
@@ -174,7 +174,9 @@ mod test {
     impl<E: AsStatus> AsStatus for RequestError<E, Infallible> {
         fn as_status(&self) -> Option<u16> {
             match self {
-                RequestError::Timeout => None,
+                RequestError::Timeout
+                | RequestError::Busy
+                | RequestError::WouldExceedRateLimit { .. } => None,
                 RequestError::Other(inner) => inner.as_status(),
                 RequestError::RetryLater(retry_later) => retry_later.as_status(),
                 RequestError::Challenge(challenge) => challenge.as_status(),
@@ -347,6 +349,8 @@ mod test {
                 RequestError::Other(inner) => inner.discriminant().as_status(),
                 RequestError::RetryLater(retry) => retry.as_status(),
                 e @ (RequestError::Timeout
+                | RequestError::Busy
+                | RequestError::WouldExceedRateLimit { .. }
                 | RequestError::ServerSideError
                 | RequestError::Challenge { .. }
                 | RequestError::Unexpected { .. }) => {
@@ -75,6 +75,7 @@ async fn main() {
                         sni: Host::Domain(host.clone()),
                         alpn: Some(Alpn::Http2),
                         min_protocol_version: None,
+                        client_cert: None,
                     },
                     inner: TcpRoute {
                         address: HOST_IP,
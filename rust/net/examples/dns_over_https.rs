@@ -56,6 +56,7 @@ async fn main() {
                 sni: Host::Domain(host),
                 alpn: Some(Alpn::Http2),
                 min_protocol_version: None,
+                client_cert: None,
             },
             inner: TcpRoute {
                 address,
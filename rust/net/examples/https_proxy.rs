@@ -97,6 +97,7 @@ async fn main() {
                 sni: proxy_host.clone(),
                 alpn: Some(Alpn::Http1_1),
                 min_protocol_version: None,
+                client_cert: None,
             },
         }),
         scheme => panic!("unsupported protocol {scheme}"),
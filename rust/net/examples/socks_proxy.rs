@@ -101,6 +101,7 @@ async fn main() {
                 sni: Host::Domain(host_name),
                 alpn: None,
                 min_protocol_version: None,
+                client_cert: None,
             },
             inner: SocksRoute {
                 proxy: TcpRoute {
@@ -57,6 +57,9 @@ where
     EndpointParams {
         mr_enclave: MrEnclave::new(params.mr_enclave.as_ref()),
         raft_config: params.raft_config.clone(),
+        extra_sw_advisories: params.extra_sw_advisories,
+        attestation_policy: params.attestation_policy,
+        attestation_cache_validity: params.attestation_cache_validity,
     }
 }
 
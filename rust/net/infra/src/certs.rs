@@ -7,11 +7,13 @@ use std::borrow::Cow;
 use std::sync::{Arc, OnceLock};
 
 use boring_signal::error::ErrorStack;
+use boring_signal::pkey::PKey;
 use boring_signal::ssl::{SslAlert, SslConnectorBuilder, SslVerifyMode};
 use boring_signal::x509::store::X509StoreBuilder;
 use boring_signal::x509::X509;
 use rustls::client::danger::{ServerCertVerified, ServerCertVerifier};
 use rustls::pki_types::{CertificateDer, ServerName};
+use zeroize::Zeroizing;
 
 use crate::dns::dns_utils::log_safe_domain;
 use crate::errors::LogSafeDisplay;
@@ -38,9 +40,36 @@ pub enum RootCertificates {
     Native,
     FromStaticDers(&'static [&'static [u8]]),
     FromDer(Cow<'static, [u8]>),
+    /// Caller-supplied DER-encoded certificates, for environments that don't chain to the
+    /// certificates built into the library.
+    FromDers(Arc<[Box<[u8]>]>),
 }
 
 impl RootCertificates {
+    /// Creates a `RootCertificates` that trusts exactly the given DER-encoded certificates.
+    ///
+    /// Fails if `ders` is empty or if any of the certificates can't be parsed.
+    pub fn from_ders(ders: Vec<Vec<u8>>) -> Result<Self, Error> {
+        if ders.is_empty() {
+            return Err(Error::BadCertificate);
+        }
+        for der in &ders {
+            _ = X509::from_der(der)?;
+        }
+        Ok(Self::FromDers(
+            ders.into_iter().map(Vec::into_boxed_slice).collect(),
+        ))
+    }
+
+    /// Creates a `RootCertificates` that trusts every certificate in a PEM bundle.
+    pub fn from_pem(pem: &[u8]) -> Result<Self, Error> {
+        let ders = X509::stack_from_pem(pem)?
+            .iter()
+            .map(|cert| cert.to_der())
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_ders(ders)
+    }
+
     /// Configures `connector` to verify certificates against `self`.
     ///
     /// **Warning:** If `self` is [`RootCertificates::Native`], the resulting connector will
@@ -53,7 +82,7 @@ impl RootCertificates {
         connector: &mut SslConnectorBuilder,
         host: Host<&str>,
     ) -> Result<(), Error> {
-        let ders: &[&[u8]] = match self {
+        let ders: Vec<&[u8]> = match self {
             RootCertificates::Native => {
                 static VERIFIER: OnceLock<Box<dyn LimitedServerCertVerifier>> = OnceLock::new();
 
@@ -84,8 +113,9 @@ impl RootCertificates {
                 });
                 return set_up_platform_verifier(connector, host, &**verifier);
             }
-            RootCertificates::FromStaticDers(ders) => ders,
-            RootCertificates::FromDer(der) => &[der],
+            RootCertificates::FromStaticDers(ders) => ders.to_vec(),
+            RootCertificates::FromDer(der) => vec![der],
+            RootCertificates::FromDers(ders) => ders.iter().map(|der| &**der).collect(),
         };
         let mut store_builder = X509StoreBuilder::new()?;
         for der in ders {
@@ -96,6 +126,91 @@ impl RootCertificates {
     }
 }
 
+/// A certificate chain and private key to present to a server that requests client authentication.
+///
+/// Some TLS-intercepting proxies require this. The private key is held in memory that is zeroed
+/// when the last clone of this value is dropped, and is never included in [`Debug`] output.
+#[derive(Clone)]
+pub struct ClientCertificate {
+    /// DER-encoded certificates, leaf first.
+    chain: Arc<[Box<[u8]>]>,
+    /// PKCS#8 or traditional DER-encoded private key for the leaf certificate.
+    private_key: Arc<Zeroizing<Vec<u8>>>,
+}
+
+impl ClientCertificate {
+    /// Creates a `ClientCertificate` from a DER-encoded chain (leaf first) and private key.
+    ///
+    /// Fails if the chain is empty, if any certificate or the key can't be parsed, or if the key
+    /// doesn't match the leaf certificate.
+    pub fn from_der(chain: Vec<Vec<u8>>, private_key: Zeroizing<Vec<u8>>) -> Result<Self, Error> {
+        let chain: Arc<[Box<[u8]>]> = chain.into_iter().map(Vec::into_boxed_slice).collect();
+        let result = Self {
+            chain,
+            private_key: Arc::new(private_key),
+        };
+        result.check_key_matches_leaf()?;
+        Ok(result)
+    }
+
+    /// Creates a `ClientCertificate` from PEM-encoded certificates (leaf first) and a PEM-encoded
+    /// private key.
+    pub fn from_pem(chain_pem: &[u8], private_key_pem: &[u8]) -> Result<Self, Error> {
+        let chain = X509::stack_from_pem(chain_pem)?
+            .iter()
+            .map(|cert| cert.to_der())
+            .collect::<Result<Vec<_>, _>>()?;
+        let private_key =
+            Zeroizing::new(PKey::private_key_from_pem(private_key_pem)?.private_key_to_der()?);
+        Self::from_der(chain, private_key)
+    }
+
+    /// Configures `connector` to present this certificate if the server asks for one.
+    pub fn apply_to_connector(&self, connector: &mut SslConnectorBuilder) -> Result<(), Error> {
+        let (leaf, intermediates) = self.chain.split_first().ok_or(Error::BadCertificate)?;
+        connector.set_certificate(&X509::from_der(leaf)?)?;
+        for der in intermediates {
+            connector.add_extra_chain_cert(X509::from_der(der)?)?;
+        }
+        connector.set_private_key(&PKey::private_key_from_der(&self.private_key)?)?;
+        connector.check_private_key()?;
+        Ok(())
+    }
+
+    fn check_key_matches_leaf(&self) -> Result<(), Error> {
+        let leaf = X509::from_der(self.chain.first().ok_or(Error::BadCertificate)?)?;
+        let key = PKey::private_key_from_der(&self.private_key)?;
+        if !leaf.public_key()?.public_eq(&key) {
+            return Err(Error::BadCertificate);
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCertificate")
+            .field("chain_len", &self.chain.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for ClientCertificate {
+    fn eq(&self, other: &Self) -> bool {
+        self.chain == other.chain && self.private_key == other.private_key
+    }
+}
+
+impl Eq for ClientCertificate {}
+
+impl std::hash::Hash for ClientCertificate {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Leave the private key out; equal chains with different keys are rare and still valid
+        // for a hash implementation.
+        self.chain.hash(state);
+    }
+}
+
 /// A subset of [`ServerCertVerifier`] that only exposes
 /// [`verify_server_cert`](ServerCertVerifier::verify_server_cert).
 ///
@@ -442,4 +557,71 @@ mod test {
             Err(e) if e.code() == Some(ErrorCode::SSL)
         );
     }
+
+    #[test]
+    fn client_certificate_from_pem() {
+        let cert = ClientCertificate::from_pem(
+            SERVER_CERTIFICATE.cert.pem().as_bytes(),
+            SERVER_CERTIFICATE.key_pair.serialize_pem().as_bytes(),
+        )
+        .expect("valid");
+
+        let mut ssl = SslConnector::builder(SslMethod::tls_client()).expect("valid");
+        cert.apply_to_connector(&mut ssl).expect("can apply");
+
+        // The private key should never show up in logs.
+        assert_eq!(
+            format!("{cert:?}"),
+            "ClientCertificate { chain_len: 1, .. }"
+        );
+    }
+
+    #[test]
+    fn client_certificate_rejects_mismatched_key() {
+        assert_matches!(
+            ClientCertificate::from_pem(
+                SERVER_CERTIFICATE.cert.pem().as_bytes(),
+                PROXY_CERTIFICATE.key_pair.serialize_pem().as_bytes(),
+            ),
+            Err(Error::BadCertificate)
+        );
+        assert_matches!(
+            ClientCertificate::from_der(
+                vec![],
+                Zeroizing::new(SERVER_CERTIFICATE.key_pair.serialize_der()),
+            ),
+            Err(Error::BadCertificate)
+        );
+    }
+
+    #[test]
+    fn root_certificates_from_pem_bundle() {
+        let bundle = [SERVER_CERTIFICATE.cert.pem(), PROXY_CERTIFICATE.cert.pem()].concat();
+        let certs = RootCertificates::from_pem(bundle.as_bytes()).expect("valid");
+        assert_eq!(
+            certs,
+            RootCertificates::FromDers(Arc::from([
+                SERVER_CERTIFICATE.cert.der().to_vec().into_boxed_slice(),
+                PROXY_CERTIFICATE.cert.der().to_vec().into_boxed_slice(),
+            ]))
+        );
+
+        let mut ssl = SslConnector::builder(SslMethod::tls_client()).expect("valid");
+        certs
+            .apply_to_connector(&mut ssl, Host::Domain(SERVER_HOSTNAME))
+            .expect("can apply");
+    }
+
+    #[test]
+    fn root_certificates_reject_invalid_input() {
+        assert_matches!(RootCertificates::from_pem(b""), Err(Error::BadCertificate));
+        assert_matches!(
+            RootCertificates::from_pem(b"not a certificate"),
+            Err(Error::BadCertificate)
+        );
+        assert_matches!(
+            RootCertificates::from_ders(vec![b"not a certificate".to_vec()]),
+            Err(Error::BadCertificate)
+        );
+    }
 }
@@ -9,6 +9,7 @@ use std::str::FromStr as _;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use futures_util::stream::FuturesUnordered;
 use futures_util::{FutureExt as _, StreamExt as _};
 use oneshot_broadcast::Sender;
 use tokio::time::Instant;
@@ -53,17 +54,60 @@ fn has_dns64_prefix(addr: &Ipv6Addr) -> bool {
     addr.octets().starts_with(&DNS64_WELL_KNOWN_PREFIX)
 }
 
+/// Where the answer for a DNS lookup came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DnsSource {
+    /// The "hostname" was already an IP address; no lookup was performed.
+    IpLiteral,
+    /// The operating system's resolver.
+    System,
+    /// A resolver used when the system resolver fails, like DNS-over-HTTPS.
+    Fallback,
+    /// The fallback resolver's cache of previous answers.
+    Cache,
+    /// The statically-configured map of known-good addresses.
+    Static,
+}
+
+/// Information about how a hostname was most recently resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsResolutionInfo {
+    pub source: DnsSource,
+    /// How long it took, across all attempted strategies, to get the answer.
+    pub duration: Duration,
+    /// The number of addresses returned, after filtering by IP version.
+    pub address_count: usize,
+}
+
+/// How a [`DnsResolver`] uses its resolution strategies.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DnsLookupMode {
+    /// Try each strategy in turn, moving on only once the previous one has
+    /// failed or timed out.
+    #[default]
+    Sequential,
+    /// Start each strategy `stagger` after the previous one, without waiting
+    /// for earlier ones to finish, and use the first answer with addresses of
+    /// a permitted IP version.
+    Parallel { stagger: Duration },
+}
+
 struct DnsResolverState {
     /// Controls if lookup results will contain IPv6 entries.
     ipv6_enabled: bool,
+    lookup_mode: DnsLookupMode,
     in_flight_lookups: HashMap<String, Receiver<Result<LookupResult>>>,
+    /// The most recent successful resolution for each hostname.
+    resolutions: HashMap<String, DnsResolutionInfo>,
 }
 
 impl std::fmt::Debug for DnsResolverState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DnsResolverState")
             .field("ipv6_enabled", &self.ipv6_enabled)
+            .field("lookup_mode", &self.lookup_mode)
             .field("in_flight_lookups", &self.in_flight_lookups.keys())
+            .field("resolutions", &self.resolutions.len())
             .finish()
     }
 }
@@ -72,7 +116,9 @@ impl Default for DnsResolverState {
     fn default() -> Self {
         Self {
             ipv6_enabled: true,
+            lookup_mode: Default::default(),
             in_flight_lookups: Default::default(),
+            resolutions: Default::default(),
         }
     }
 }
@@ -90,6 +136,8 @@ struct LookupOption {
     lookup: Box<dyn DnsLookup>,
     /// How long to wait for the lookup to finish before giving up on it.
     timeout_after: Duration,
+    /// What to report as the source of a successful (non-cached) answer.
+    source: DnsSource,
 }
 
 pub fn build_custom_resolver_cloudflare_doh(
@@ -110,6 +158,7 @@ pub fn build_custom_resolver_cloudflare_doh(
                     root_certs: RootCertificates::Native,
                     alpn: Some(Alpn::Http2),
                     min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_2),
+                    client_cert: None,
                 },
                 inner: TcpRoute {
                     address: ip_addr,
@@ -133,6 +182,7 @@ impl DnsResolver {
             .map(|(lookup, timeout_after)| LookupOption {
                 lookup,
                 timeout_after,
+                source: DnsSource::Fallback,
             })
             .collect();
 
@@ -155,6 +205,7 @@ impl DnsResolver {
             lookup_options: Arc::new([LookupOption {
                 lookup: Box::new(StaticDnsMap(static_map)),
                 timeout_after: Duration::from_millis(1),
+                source: DnsSource::Static,
             }]),
             state: Default::default(),
             known_good_results: Arc::new(HashMap::new()),
@@ -180,14 +231,17 @@ impl DnsResolver {
             LookupOption {
                 lookup: Box::new(SystemDnsLookup),
                 timeout_after: DNS_SYSTEM_LOOKUP_TIMEOUT,
+                source: DnsSource::System,
             },
             LookupOption {
                 lookup: cloudflare_doh,
                 timeout_after: DOH_FALLBACK_LOOKUP_TIMEOUT,
+                source: DnsSource::Fallback,
             },
             LookupOption {
                 lookup: Box::new(StaticDnsMap(static_map)),
                 timeout_after: Duration::from_secs(1),
+                source: DnsSource::Static,
             },
         ];
 
@@ -203,22 +257,45 @@ impl DnsResolver {
         if guard.ipv6_enabled != ipv6_enabled {
             guard.ipv6_enabled = ipv6_enabled;
             guard.in_flight_lookups.clear();
+            guard.resolutions.clear();
         }
     }
 
+    /// Changes how lookups are performed.
+    ///
+    /// Lookups that are already in progress are unaffected.
+    pub fn set_lookup_mode(&self, lookup_mode: DnsLookupMode) {
+        self.state.lock().expect("not poisoned").lookup_mode = lookup_mode;
+    }
+
     pub fn on_network_change(&self, now: Instant) {
+        self.state.lock().expect("not poisoned").resolutions.clear();
         for option in &self.lookup_options[..] {
             option.lookup.on_network_change(now);
         }
     }
 
+    /// Returns details about the most recent successful lookup of `hostname`, if any.
+    ///
+    /// Lookups are forgotten on network changes.
+    pub fn resolution_info(&self, hostname: &str) -> Option<DnsResolutionInfo> {
+        if parse_ip_literal(hostname).is_some() {
+            return Some(DnsResolutionInfo {
+                source: DnsSource::IpLiteral,
+                duration: Duration::ZERO,
+                address_count: 1,
+            });
+        }
+        self.state
+            .lock()
+            .expect("not poisoned")
+            .resolutions
+            .get(hostname)
+            .cloned()
+    }
+
     pub async fn lookup_ip(&self, hostname: &str) -> Result<LookupResult> {
-        let parse_as_ip_addr = hostname.parse().ok().or_else(|| {
-            let hostname = hostname.strip_prefix('[')?;
-            let hostname = hostname.strip_suffix(']')?;
-            Ipv6Addr::from_str(hostname).ok().map(std::net::IpAddr::V6)
-        });
-        if let Some(addr) = parse_as_ip_addr {
+        if let Some(addr) = parse_ip_literal(hostname) {
             let (ipv4, ipv6) = match addr {
                 std::net::IpAddr::V4(ip) => (vec![ip], vec![]),
                 std::net::IpAddr::V6(ip) => (vec![], vec![ip]),
@@ -237,12 +314,13 @@ impl DnsResolver {
     fn start_or_join_lookup(&self, hostname: &str) -> Receiver<Result<LookupResult>> {
         let mut guard = self.state.lock().expect("not poisoned");
         let ipv6_enabled = guard.ipv6_enabled;
+        let lookup_mode = guard.lookup_mode;
         guard
             .in_flight_lookups
             .entry(hostname.to_string())
             .or_insert_with(|| {
                 let (tx, rx) = oneshot_broadcast::channel();
-                self.spawn_lookup(hostname.to_string(), tx, ipv6_enabled);
+                self.spawn_lookup(hostname.to_string(), tx, ipv6_enabled, lookup_mode);
                 rx
             })
             .clone()
@@ -253,6 +331,7 @@ impl DnsResolver {
         hostname: String,
         result_sender: Sender<Result<LookupResult>>,
         ipv6_enabled: bool,
+        lookup_mode: DnsLookupMode,
     ) {
         let Self {
             lookup_options,
@@ -260,27 +339,39 @@ impl DnsResolver {
             known_good_results,
         } = self.clone();
         tokio::spawn(async move {
+            let started_at = Instant::now();
             let request = DnsLookupRequest {
                 hostname: Arc::from(hostname.as_str()),
                 ipv6_enabled,
             };
 
-            let successful_lookups = futures_util::stream::iter(lookup_options.iter())
-                .filter_map(|lookup_option| lookup_option.attempt(request.clone()).map(Result::ok));
-            let mut perform_lookups = std::pin::pin!(successful_lookups);
-
-            let result = perform_lookups
-                .next()
-                .await
-                .ok_or(Error::LookupFailed)
-                .and_then(|res| match ipv6_enabled {
-                    true => Ok(res),
-                    false if res.ipv4.is_empty() => Err(Error::RequestedIpTypeNotFound),
-                    false => Ok(LookupResult {
-                        ipv6: vec![],
-                        ..res
-                    }),
+            let result = match lookup_mode {
+                DnsLookupMode::Sequential => {
+                    let successful_lookups = futures_util::stream::iter(lookup_options.iter())
+                        .filter_map(|lookup_option| {
+                            lookup_option.attempt(request.clone()).map(Result::ok)
+                        });
+                    let mut perform_lookups = std::pin::pin!(successful_lookups);
+
+                    perform_lookups
+                        .next()
+                        .await
+                        .ok_or(Error::LookupFailed)
+                        .and_then(|answer| filter_by_ip_version(answer, ipv6_enabled))
+                }
+                DnsLookupMode::Parallel { stagger } => {
+                    race_lookups(&lookup_options, &request, stagger).await
+                }
+            };
+            let resolution_info = result
+                .as_ref()
+                .ok()
+                .map(|(lookup, source)| DnsResolutionInfo {
+                    source: *source,
+                    duration: started_at.elapsed(),
+                    address_count: lookup.ipv4.len() + lookup.ipv6.len(),
                 });
+            let result = result.map(|(lookup, _source)| lookup);
 
             let log_safe_hostname = log_safe_domain(&hostname);
 
@@ -309,11 +400,13 @@ impl DnsResolver {
                 }
             }
 
-            state
-                .lock()
-                .expect("not poisoned")
-                .in_flight_lookups
-                .remove(&hostname);
+            {
+                let mut guard = state.lock().expect("not poisoned");
+                guard.in_flight_lookups.remove(&hostname);
+                if let Some(info) = resolution_info {
+                    guard.resolutions.insert(hostname.clone(), info);
+                }
+            }
             if result_sender.send(result).is_err() {
                 log::debug!("No DNS result listeners left for domain [{log_safe_hostname}]",);
             }
@@ -321,16 +414,88 @@ impl DnsResolver {
     }
 }
 
+/// Drops IPv6 addresses from `answer` if IPv6 is disabled, failing if that leaves no addresses.
+fn filter_by_ip_version(
+    (res, source): (LookupResult, DnsSource),
+    ipv6_enabled: bool,
+) -> Result<(LookupResult, DnsSource)> {
+    match ipv6_enabled {
+        true => Ok((res, source)),
+        false if res.ipv4.is_empty() => Err(Error::RequestedIpTypeNotFound),
+        false => Ok((
+            LookupResult {
+                ipv6: vec![],
+                ..res
+            },
+            source,
+        )),
+    }
+}
+
+/// Runs every lookup option with staggered starts and returns the first usable answer.
+async fn race_lookups(
+    lookup_options: &[LookupOption],
+    request: &DnsLookupRequest,
+    stagger: Duration,
+) -> Result<(LookupResult, DnsSource)> {
+    let ipv6_enabled = request.ipv6_enabled;
+    let mut attempts = lookup_options
+        .iter()
+        .zip(0u32..)
+        .map(|(lookup_option, index)| {
+            let request = request.clone();
+            async move {
+                tokio::time::sleep(stagger * index).await;
+                lookup_option
+                    .attempt(request)
+                    .await
+                    .and_then(|answer| filter_by_ip_version(answer, ipv6_enabled))
+            }
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    let mut error = Error::LookupFailed;
+    while let Some(result) = attempts.next().await {
+        match result {
+            Ok(answer) => return Ok(answer),
+            Err(e @ Error::RequestedIpTypeNotFound) => error = e,
+            Err(_) => {}
+        }
+    }
+    Err(error)
+}
+
+fn parse_ip_literal(hostname: &str) -> Option<IpAddr> {
+    hostname.parse().ok().or_else(|| {
+        let hostname = hostname.strip_prefix('[')?;
+        let hostname = hostname.strip_suffix(']')?;
+        Ipv6Addr::from_str(hostname).ok().map(IpAddr::V6)
+    })
+}
+
 impl LookupOption {
-    async fn attempt(&self, request: DnsLookupRequest) -> Result<LookupResult> {
+    async fn attempt(&self, request: DnsLookupRequest) -> Result<(LookupResult, DnsSource)> {
         let Self {
             lookup,
             timeout_after,
+            source,
         } = self;
         let started_at = Instant::now();
         let log_safe_domain = log_safe_domain(&request.hostname).to_string();
-        let result =
-            utils::timeout(*timeout_after, Error::Timeout, lookup.dns_lookup(request)).await;
+        let result = utils::timeout(
+            *timeout_after,
+            Error::Timeout,
+            lookup.dns_lookup_reporting_cache_hit(request),
+        )
+        .await
+        .map(|(result, from_cache)| {
+            let source = if from_cache {
+                DnsSource::Cache
+            } else {
+                *source
+            };
+            (result, source)
+        });
         match &result {
             Ok(_) => {
                 log::debug!(
@@ -560,6 +725,124 @@ mod test {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_resolution_info() {
+        let dns_resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            DUAL_STACK_DOMAIN,
+            (IPV4, IPV6).into(),
+        )]));
+
+        assert_eq!(dns_resolver.resolution_info(DUAL_STACK_DOMAIN), None);
+        assert_eq!(
+            dns_resolver.resolution_info("[3fff::1]"),
+            Some(DnsResolutionInfo {
+                source: DnsSource::IpLiteral,
+                duration: Duration::ZERO,
+                address_count: 1,
+            })
+        );
+
+        let _ = dns_resolver
+            .lookup_ip(DUAL_STACK_DOMAIN)
+            .await
+            .expect("success");
+        assert_eq!(
+            dns_resolver.resolution_info(DUAL_STACK_DOMAIN),
+            Some(DnsResolutionInfo {
+                source: DnsSource::Static,
+                duration: Duration::ZERO,
+                address_count: 2,
+            })
+        );
+
+        dns_resolver.on_network_change(Instant::now());
+        assert_eq!(dns_resolver.resolution_info(DUAL_STACK_DOMAIN), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_resolution_info_reports_winning_fallback() {
+        let primary_timeout = Duration::from_secs(2);
+        let primary_lookup = TestLookup::with_custom_response(Duration::from_secs(3), IPV6);
+        let fallback_lookup = StaticDnsMap(HashMap::from([(CUSTOM_DOMAIN, IPV4.into())]));
+
+        let dns_resolver = DnsResolver {
+            lookup_options: Arc::new([
+                LookupOption {
+                    lookup: primary_lookup,
+                    timeout_after: primary_timeout,
+                    source: DnsSource::System,
+                },
+                LookupOption {
+                    lookup: Box::new(fallback_lookup),
+                    timeout_after: ATTEMPT_TIMEOUT,
+                    source: DnsSource::Static,
+                },
+            ]),
+            state: Default::default(),
+            known_good_results: Default::default(),
+        };
+
+        let _ = dns_resolver
+            .lookup_ip(CUSTOM_DOMAIN)
+            .await
+            .expect("success");
+        assert_eq!(
+            dns_resolver.resolution_info(CUSTOM_DOMAIN),
+            Some(DnsResolutionInfo {
+                source: DnsSource::Static,
+                duration: primary_timeout,
+                address_count: 1,
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parallel_lookup_uses_first_usable_answer() {
+        const STAGGER: Duration = Duration::from_millis(100);
+        let slow_lookup = TestLookup::with_custom_response(Duration::from_secs(3), IPV4);
+        let ipv6_only_lookup = TestLookup::with_custom_response(Duration::ZERO, IPV6);
+        let static_lookup = StaticDnsMap(HashMap::from([(CUSTOM_DOMAIN, IPV4.into())]));
+
+        let dns_resolver = DnsResolver {
+            lookup_options: Arc::new([
+                LookupOption {
+                    lookup: slow_lookup,
+                    timeout_after: Duration::from_secs(5),
+                    source: DnsSource::System,
+                },
+                LookupOption {
+                    lookup: ipv6_only_lookup,
+                    timeout_after: ATTEMPT_TIMEOUT,
+                    source: DnsSource::Fallback,
+                },
+                LookupOption {
+                    lookup: Box::new(static_lookup),
+                    timeout_after: ATTEMPT_TIMEOUT,
+                    source: DnsSource::Static,
+                },
+            ]),
+            state: Default::default(),
+            known_good_results: Default::default(),
+        };
+        dns_resolver.set_ipv6_enabled(false);
+        dns_resolver.set_lookup_mode(DnsLookupMode::Parallel { stagger: STAGGER });
+
+        let (elapsed, result) = timed(dns_resolver.lookup_ip(CUSTOM_DOMAIN)).await;
+
+        // The fallback answers first, but it only has IPv6 addresses, so the
+        // static map's answer wins without waiting for the slow system lookup.
+        assert_eq!(result.expect("success").ipv4, vec![IPV4]);
+        assert_eq!(elapsed, STAGGER * 2);
+        assert_eq!(
+            dns_resolver.resolution_info(CUSTOM_DOMAIN),
+            Some(DnsResolutionInfo {
+                source: DnsSource::Static,
+                duration: STAGGER * 2,
+                address_count: 1,
+            })
+        );
+    }
+
     #[tokio::test]
     async fn test_dns_lookup_ipv6_disabled() {
         let static_dns_map =
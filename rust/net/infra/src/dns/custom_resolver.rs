@@ -135,20 +135,30 @@ where
     }
 
     pub async fn resolve(&self, request: DnsLookupRequest) -> dns::Result<LookupResult> {
+        self.resolve_reporting_cache_hit(request)
+            .await
+            .map(|(result, _from_cache)| result)
+    }
+
+    /// Like [`Self::resolve`], but also reports whether the result was served from the cache.
+    pub(crate) async fn resolve_reporting_cache_hit(
+        &self,
+        request: DnsLookupRequest,
+    ) -> dns::Result<(LookupResult, bool)> {
         match self.cache_get(&request.hostname) {
             Some(res) => {
                 log::info!(
                     "DNS record for {} found in cache",
                     log_safe_domain(&request.hostname)
                 );
-                Ok(res)
+                Ok((res, true))
             }
             None => {
                 log::info!(
                     "Starting DNS lookup for {}",
                     log_safe_domain(&request.hostname)
                 );
-                self.lookup(request).await
+                self.lookup(request).await.map(|res| (res, false))
             }
         }
     }
@@ -29,6 +29,17 @@ pub struct DnsLookupRequest {
 #[async_trait]
 pub trait DnsLookup: Debug + Send + Sync {
     async fn dns_lookup(&self, request: DnsLookupRequest) -> dns::Result<LookupResult>;
+
+    /// Like [`Self::dns_lookup`], but also reports whether the answer came from a local cache.
+    ///
+    /// The default implementation never reports a cache hit.
+    async fn dns_lookup_reporting_cache_hit(
+        &self,
+        request: DnsLookupRequest,
+    ) -> dns::Result<(LookupResult, bool)> {
+        self.dns_lookup(request).await.map(|result| (result, false))
+    }
+
     fn on_network_change(&self, _now: Instant) {}
 }
 
@@ -81,6 +92,13 @@ where
         self.resolve(request).await
     }
 
+    async fn dns_lookup_reporting_cache_hit(
+        &self,
+        request: DnsLookupRequest,
+    ) -> dns::Result<(LookupResult, bool)> {
+        self.resolve_reporting_cache_hit(request).await
+    }
+
     fn on_network_change(&self, now: Instant) {
         // Forward to the non-trait method.
         self.on_network_change(now);
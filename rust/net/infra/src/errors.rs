@@ -40,6 +40,8 @@ pub enum TransportConnectError {
     ProxyProtocol,
     /// Abort due to local error
     ClientAbort,
+    /// Connection attempt timed out
+    AttemptTimedOut,
 }
 impl LogSafeDisplay for TransportConnectError {}
 
@@ -110,6 +112,14 @@ impl RetryLater {
     pub fn duration(&self) -> Duration {
         Duration::from_secs(self.retry_after_seconds.into())
     }
+
+    /// Converts a wait time to a `RetryLater`, rounding up to the next whole second.
+    pub fn rounding_up(duration: Duration) -> Self {
+        let whole_seconds = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+        Self {
+            retry_after_seconds: whole_seconds.try_into().unwrap_or(u32::MAX),
+        }
+    }
 }
 
 impl AsStaticHttpHeader for RetryLater {
@@ -149,6 +159,7 @@ impl From<TransportConnectError> for std::io::Error {
             | TransportConnectError::CertError
             | TransportConnectError::ProxyProtocol => ErrorKind::InvalidData,
             TransportConnectError::ClientAbort => ErrorKind::ConnectionAborted,
+            TransportConnectError::AttemptTimedOut => ErrorKind::TimedOut,
         };
         Self::new(kind, value.to_string())
     }
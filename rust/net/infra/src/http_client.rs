@@ -346,6 +346,7 @@ mod test {
                         )),
                         alpn: None,
                         min_protocol_version: None,
+                        client_cert: None,
                     },
                     inner: TcpRoute {
                         address: Ipv6Addr::LOCALHOST.into(),
@@ -423,6 +424,7 @@ mod test {
                         )),
                         alpn: None,
                         min_protocol_version: None,
+                        client_cert: None,
                     },
                     inner: TcpRoute {
                         address: Ipv6Addr::LOCALHOST.into(),
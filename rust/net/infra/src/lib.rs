@@ -209,6 +209,8 @@ pub enum RouteType {
     TlsProxy,
     /// Connection over a SOCKS proxy
     SocksProxy,
+    /// Connection over an HTTP(S) proxy
+    HttpProxy,
     /// Test-only value
     #[cfg(any(test, feature = "test-util"))]
     Test,
@@ -250,11 +252,25 @@ impl AsRef<[u8]> for Alpn {
     }
 }
 
+/// The largest websocket frame we expect from any Signal service.
+///
+/// This matches tungstenite's default; it's spelled out so that it can't change out from under us.
+pub const WS_MAX_FRAME_SIZE: usize = 16 << 20;
+
+/// The largest websocket message we expect from any Signal service.
+///
+/// This is lower than tungstenite's default of 64MiB, which is more than any
+/// of our services will ever send.
+pub const WS_MAX_MESSAGE_SIZE: usize = 16 << 20;
+
 pub const RECOMMENDED_WS_CONFIG: ws::Config = {
     ws::Config {
         local_idle_timeout: WS_KEEP_ALIVE_INTERVAL,
         remote_idle_ping_timeout: WS_KEEP_ALIVE_INTERVAL,
         remote_idle_disconnect_timeout: WS_MAX_IDLE_INTERVAL,
+        max_frame_size: Some(WS_MAX_FRAME_SIZE),
+        max_message_size: Some(WS_MAX_MESSAGE_SIZE),
+        application_idle_timeout: None,
     }
 };
 
@@ -696,6 +696,7 @@ mod test {
                             sni: Host::Domain("sni-name".into()),
                             alpn: Some(Alpn::Http1_1),
                             min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_3),
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("target-host".into()),
@@ -722,6 +723,7 @@ mod test {
                             sni: Host::Domain("front-sni1".into()),
                             alpn: Some(Alpn::Http2),
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("front-sni1".into()),
@@ -748,6 +750,7 @@ mod test {
                             sni: Host::Domain("front-sni2".into()),
                             alpn: Some(Alpn::Http2),
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("front-sni2".into()),
@@ -782,6 +785,7 @@ mod test {
                 proxy_host: Host::Domain("tls-proxy".into()),
                 proxy_port: PROXY_PORT,
                 proxy_certs: PROXY_CERTS,
+                proxy_client_cert: None,
             }
             .into(),
             inner: direct_provider,
@@ -797,6 +801,7 @@ mod test {
                     sni: Host::Domain("direct-sni".into()),
                     alpn: None,
                     min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_1),
+                    client_cert: None,
                 },
                 inner: ConnectionProxyRoute::Tls {
                     proxy: TlsRoute {
@@ -809,6 +814,7 @@ mod test {
                             sni: Host::Domain("tls-proxy".into()),
                             alpn: None,
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                     },
                 },
@@ -853,6 +859,7 @@ mod test {
                 sni: Host::Domain("direct-sni".into()),
                 alpn: None,
                 min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_1),
+                client_cert: None,
             },
             inner: ConnectionProxyRoute::Socks(SocksRoute {
                 proxy: TcpRoute {
@@ -40,6 +40,9 @@ pub use variable_timeout::*;
 mod static_tcp_timeout;
 pub use static_tcp_timeout::*;
 
+mod attempt_timeout;
+pub use attempt_timeout::*;
+
 /// Establishes a connection to a route over an inner transport.
 pub trait Connector<R, Inner> {
     /// The type of connection returned on success.
@@ -0,0 +1,119 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::fmt::Debug;
+use std::future::Future;
+
+use derive_where::derive_where;
+use tokio::time::Duration;
+
+use crate::errors::TransportConnectError;
+use crate::route::Connector;
+
+/// A [`Connector`] that bounds the total time spent on a single connection attempt.
+///
+/// Unlike [`StaticTcpTimeoutConnector`](super::StaticTcpTimeoutConnector), which only covers the
+/// TCP handshake, this is meant to wrap the full stack for a route, so that one route that hangs
+/// partway through (say, after TLS but before the websocket upgrade completes) can't use up the
+/// whole budget for a multi-route connect operation.
+///
+/// If the timeout elapses, the attempt fails with [`TransportConnectError::AttemptTimedOut`].
+#[derive_where(Debug; Inner: Debug)]
+pub struct AttemptTimeoutConnector<Inner> {
+    inner_connector: Inner,
+    timeout: Duration,
+}
+
+impl<I> AttemptTimeoutConnector<I> {
+    pub fn new(inner: I, timeout: Duration) -> Self {
+        Self {
+            inner_connector: inner,
+            timeout,
+        }
+    }
+}
+
+impl<Inner, Route, Transport> Connector<Route, Transport> for AttemptTimeoutConnector<Inner>
+where
+    Inner: Connector<Route, Transport, Error: From<TransportConnectError>> + Sync,
+    Inner::Connection: Send,
+    Route: Send,
+    Transport: Send,
+{
+    type Connection = Inner::Connection;
+
+    type Error = Inner::Error;
+
+    fn connect_over(
+        &self,
+        transport: Transport,
+        route: Route,
+        log_tag: &str,
+    ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        let Self {
+            inner_connector,
+            timeout,
+        } = self;
+
+        async move {
+            tokio::time::timeout(
+                *timeout,
+                inner_connector.connect_over(transport, route, log_tag),
+            )
+            .await
+            .map_err(|_| {
+                log::info!("[{log_tag}] connection attempt timed out after {timeout:?}");
+                TransportConnectError::AttemptTimedOut
+            })?
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+    use crate::route::connect::testutils::DummyDelayConnector;
+
+    const TEST_TRANSPORT: () = ();
+    const TEST_ROUTE: () = ();
+    const LOG_TAG: &str = "test";
+
+    #[tokio::test(start_paused = true)]
+    async fn test_completes_before_timeout() {
+        let connector = AttemptTimeoutConnector::new(
+            DummyDelayConnector {
+                delay: Duration::from_millis(100),
+            },
+            Duration::from_secs(1),
+        );
+
+        let start = tokio::time::Instant::now();
+        let result = connector
+            .connect_over(TEST_TRANSPORT, TEST_ROUTE, LOG_TAG)
+            .await;
+        assert_matches!(result, Ok(_));
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_times_out() {
+        let timeout = Duration::from_secs(1);
+        let connector = AttemptTimeoutConnector::new(
+            DummyDelayConnector {
+                delay: Duration::from_secs(10),
+            },
+            timeout,
+        );
+
+        let start = tokio::time::Instant::now();
+        let result = connector
+            .connect_over(TEST_TRANSPORT, TEST_ROUTE, LOG_TAG)
+            .await;
+        assert_matches!(result, Err(TransportConnectError::AttemptTimedOut));
+        assert_eq!(start.elapsed(), timeout);
+    }
+}
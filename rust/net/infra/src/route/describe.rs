@@ -142,6 +142,13 @@ impl UnresolvedRouteDescription {
         self.front
     }
 
+    /// The host the route ultimately connects to.
+    ///
+    /// For proxied routes, this may be resolved by the proxy rather than locally.
+    pub fn target_host(&self) -> &Host<Arc<str>> {
+        &self.target.0
+    }
+
     pub fn fake() -> Self {
         Self {
             front: None,
@@ -136,6 +136,7 @@ impl RouteProvider for DomainFrontRouteProvider {
                             sni: Host::Domain(Arc::clone(sni)),
                             alpn: Some((*http_version).into()),
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                     },
                     fragment: HttpRouteFragment {
@@ -282,6 +283,7 @@ mod test {
                             sni: Host::Domain("direct-host".into()),
                             alpn: Some(Alpn::Http2),
                             min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_1),
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("direct-tcp-host".into()),
@@ -301,6 +303,7 @@ mod test {
                             sni: Host::Domain("front-sni-1a".into()),
                             alpn: Some(Alpn::Http1_1),
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("front-sni-1a".into()),
@@ -320,6 +323,7 @@ mod test {
                             sni: Host::Domain("front-sni-1b".into()),
                             alpn: Some(Alpn::Http1_1),
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("front-sni-1b".into()),
@@ -339,6 +343,7 @@ mod test {
                             sni: Host::Domain("front-sni-2b".into()),
                             alpn: Some(Alpn::Http1_1),
                             min_protocol_version: None,
+                            client_cert: None,
                         },
                         inner: TcpRoute {
                             address: UnresolvedHost("front-sni-2b".into()),
@@ -9,7 +9,7 @@ use std::sync::Arc;
 use either::Either;
 use nonzero_ext::nonzero;
 
-use crate::certs::RootCertificates;
+use crate::certs::{ClientCertificate, RootCertificates};
 use crate::errors::LogSafeDisplay;
 use crate::host::Host;
 use crate::route::{
@@ -17,7 +17,7 @@ use crate::route::{
     TlsRouteFragment, UnresolvedHost,
 };
 use crate::tcp_ssl::proxy::socks;
-use crate::Alpn;
+use crate::{Alpn, RouteType};
 
 pub const SIGNAL_TLS_PROXY_SCHEME: &str = "org.signal.tls";
 
@@ -30,6 +30,10 @@ pub struct SocksRoute<Addr> {
 }
 
 /// Route for connecting via an HTTPS proxy.
+///
+/// The connection to the proxy itself can be plain TCP or, for "secure
+/// proxies", TLS. In the latter case a TLS connection to the target made over
+/// this route is nested inside the TLS session with the proxy.
 pub type HttpsProxyRoute<Addr> =
     SimpleRoute<HttpProxyRouteFragment<Addr>, Either<TlsRoute<TcpRoute<Addr>>, TcpRoute<Addr>>>;
 
@@ -67,6 +71,19 @@ pub enum ConnectionProxyRoute<Addr> {
     Https(HttpsProxyRoute<Addr>),
 }
 
+impl From<ConnectionProxyKind> for RouteType {
+    fn from(value: ConnectionProxyKind) -> Self {
+        match value {
+            ConnectionProxyKind::Tls => RouteType::TlsProxy,
+            // Stands in for a TLS proxy in tests.
+            #[cfg(feature = "dev-util")]
+            ConnectionProxyKind::Tcp => RouteType::TlsProxy,
+            ConnectionProxyKind::Socks => RouteType::SocksProxy,
+            ConnectionProxyKind::Https => RouteType::HttpProxy,
+        }
+    }
+}
+
 /// Target address for proxy protocols that support remote resolution.
 ///
 /// SOCKS and HTTPS proxies support making a connection to a remote host
@@ -103,6 +120,8 @@ pub struct TlsProxy {
     pub proxy_host: Host<Arc<str>>,
     pub proxy_port: NonZeroU16,
     pub proxy_certs: RootCertificates,
+    /// Presented to the proxy if it requests client authentication.
+    pub proxy_client_cert: Option<ClientCertificate>,
 }
 
 #[cfg(feature = "dev-util")]
@@ -125,6 +144,10 @@ pub struct HttpProxy {
     pub proxy_host: Host<Arc<str>>,
     pub proxy_port: NonZeroU16,
     pub proxy_tls: Option<RootCertificates>,
+    /// Presented to the proxy if it requests client authentication.
+    ///
+    /// Ignored if `proxy_tls` is `None`.
+    pub proxy_client_cert: Option<ClientCertificate>,
     pub proxy_authorization: Option<HttpProxyAuth>,
     pub resolve_hostname_locally: bool,
 }
@@ -198,6 +221,7 @@ impl ConnectionProxyConfig {
                         proxy_host: host,
                         proxy_port: port.unwrap_or(nonzero!(443u16)),
                         proxy_certs: CERTS_FOR_ARBITRARY_PROXY,
+                        proxy_client_cert: None,
                     }
                     .into(),
                 }
@@ -206,6 +230,7 @@ impl ConnectionProxyConfig {
                 proxy_host: host,
                 proxy_port: port.unwrap_or(nonzero!(80u16)),
                 proxy_tls: None,
+                proxy_client_cert: None,
                 proxy_authorization: auth,
                 resolve_hostname_locally: true,
             }
@@ -214,6 +239,7 @@ impl ConnectionProxyConfig {
                 proxy_host: host,
                 proxy_port: port.unwrap_or(nonzero!(443u16)),
                 proxy_tls: Some(CERTS_FOR_ARBITRARY_PROXY),
+                proxy_client_cert: None,
                 proxy_authorization: auth,
                 resolve_hostname_locally: true,
             }
@@ -407,12 +433,14 @@ impl AsReplacer for TlsProxy {
             proxy_host,
             proxy_port,
             proxy_certs,
+            proxy_client_cert,
         } = self;
         let tls_fragment = TlsRouteFragment {
             root_certs: proxy_certs.clone(),
             sni: proxy_host.clone(),
             alpn: None,
             min_protocol_version: None,
+            client_cert: proxy_client_cert.clone(),
         };
 
         let tcp = TcpRoute {
@@ -479,6 +507,7 @@ impl AsReplacer for HttpProxy {
             resolve_hostname_locally,
             proxy_authorization,
             proxy_tls,
+            proxy_client_cert,
         } = self;
         let proxy_tcp_route = TcpRoute {
             address: proxy_host.clone().map_domain(UnresolvedHost::from),
@@ -492,6 +521,7 @@ impl AsReplacer for HttpProxy {
                     sni: proxy_host.clone(),
                     alpn: Some(Alpn::Http1_1),
                     min_protocol_version: None,
+                    client_cert: proxy_client_cert.clone(),
                 },
             }),
             None => Either::Right(proxy_tcp_route),
@@ -552,6 +582,7 @@ mod test {
             proxy_host,
             proxy_port,
             proxy_certs,
+            proxy_client_cert,
         } = {
             let port = port.map(|p| NonZeroU16::try_from(p).expect("valid for testing"));
             assert_matches!(
@@ -562,6 +593,7 @@ mod test {
         assert_eq!(expected_host.into(), proxy_host.as_deref());
         assert_eq!(port.unwrap_or(443), proxy_port.get());
         assert_matches!(proxy_certs, RootCertificates::Native);
+        assert_matches!(proxy_client_cert, None);
     }
 
     #[cfg(feature = "dev-util")]
@@ -609,6 +641,7 @@ mod test {
             proxy_host,
             proxy_port,
             proxy_tls,
+            proxy_client_cert,
             proxy_authorization,
             resolve_hostname_locally,
         } = {
@@ -638,6 +671,7 @@ mod test {
                 .as_ref()
                 .map(|auth| (auth.username.as_str(), auth.password.as_str())),
         );
+        assert_matches!(proxy_client_cert, None);
         assert!(
             resolve_hostname_locally,
             "this endpoint never produces a config that defers to the proxy"
@@ -3,11 +3,13 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
 use std::net::IpAddr;
 use std::sync::Arc;
 
+use derive_where::derive_where;
 use either::Either;
 use futures_util::FutureExt as _;
 use itertools::Itertools;
@@ -70,6 +72,67 @@ impl Resolver for DnsResolver {
     }
 }
 
+/// Fixed addresses to use for particular hostnames instead of looking them up.
+///
+/// Overrides only change which IP address is connected to. The original
+/// hostname is still used everywhere else, including for TLS SNI and
+/// certificate validation.
+#[derive(Clone, Debug, Default)]
+pub struct IpOverrides(Arc<HashMap<Arc<str>, LookupResult>>);
+
+impl IpOverrides {
+    pub fn new<H: Into<Arc<str>>>(
+        overrides: impl IntoIterator<Item = (H, impl IntoIterator<Item = IpAddr>)>,
+    ) -> Self {
+        let overrides = overrides
+            .into_iter()
+            .map(|(hostname, addrs)| {
+                let (ipv4, ipv6) = addrs.into_iter().partition_map(|addr| match addr {
+                    IpAddr::V4(v4) => Either::Left(v4),
+                    IpAddr::V6(v6) => Either::Right(v6),
+                });
+                (hostname.into(), LookupResult::new(ipv4, ipv6))
+            })
+            .collect();
+        Self(Arc::new(overrides))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Wraps `resolver` so that overridden hostnames are answered without consulting it.
+    pub fn wrap<'a, R>(&'a self, resolver: &'a R) -> OverridingResolver<'a, R> {
+        OverridingResolver {
+            overrides: self,
+            inner: resolver,
+        }
+    }
+}
+
+/// A [`Resolver`] that checks [`IpOverrides`] before delegating to another resolver.
+///
+/// Produced by [`IpOverrides::wrap`].
+#[derive_where(Clone, Copy)]
+pub struct OverridingResolver<'a, R> {
+    overrides: &'a IpOverrides,
+    inner: &'a R,
+}
+
+impl<R: Resolver> Resolver for OverridingResolver<'_, R> {
+    fn lookup_ip(
+        &self,
+        hostname: &str,
+    ) -> impl Future<Output = Result<LookupResult, DnsError>> + Send {
+        match self.overrides.0.get(hostname) {
+            Some(result) => {
+                futures_util::future::Either::Left(std::future::ready(Ok(result.clone())))
+            }
+            None => futures_util::future::Either::Right(self.inner.lookup_ip(hostname)),
+        }
+    }
+}
+
 /// The output of [`resolve_route`] on successful resolution.
 ///
 /// The actual type isn't important, but writing it out lets the compiler infer
@@ -705,6 +768,7 @@ mod test {
             sni: Host::Domain("target-domain".into()),
             alpn: None,
             min_protocol_version: None,
+            client_cert: None,
         };
 
         fn socks_route<A>(proxy: A, target: A) -> ConnectionProxyRoute<A> {
@@ -19,7 +19,9 @@ use tokio::time::{Duration, Instant};
 
 use crate::dns::dns_utils::log_safe_domain;
 use crate::dns::DnsError;
-use crate::route::{ResolveHostnames, ResolvedRoute, Resolver, TransportRoute, UsesTransport};
+use crate::route::{
+    IpOverrides, ResolveHostnames, ResolvedRoute, Resolver, TransportRoute, UsesTransport,
+};
 use crate::utils::binary_heap::{MinKeyValueQueue, Queue};
 use crate::utils::future::SomeOrPending;
 use crate::utils::NetworkChangeEvent;
@@ -30,7 +32,52 @@ use crate::utils::NetworkChangeEvent;
 /// to provide some named state that is used as input to that function.
 #[derive(Clone)]
 pub struct RouteResolver {
+    /// Whether routes that connect directly to IPv6 addresses may be used at all.
+    ///
+    /// This is independent of [`Self::ip_preference`], which is applied to whatever routes
+    /// remain.
     pub allow_ipv6: bool,
+    pub ip_preference: IpPreference,
+    /// Hostnames that should bypass DNS entirely.
+    pub ip_overrides: IpOverrides,
+}
+
+/// Which address families to use for resolved routes, and in what order.
+///
+/// This is applied to the address a route connects to directly (see
+/// [`ResolvedRoute::immediate_target`]), so for a proxied route it affects the proxy's address
+/// rather than the final destination's.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum IpPreference {
+    /// Alternate between IPv6 and IPv4 routes, starting with IPv6.
+    #[default]
+    PreferIpv6,
+    /// Alternate between IPv4 and IPv6 routes, starting with IPv4.
+    PreferIpv4,
+    /// Only use routes that connect to IPv4 addresses.
+    Ipv4Only,
+    /// Only use routes that connect to IPv6 addresses.
+    Ipv6Only,
+}
+
+impl IpPreference {
+    /// Filters and reorders `routes` according to the preference.
+    ///
+    /// The relative order of routes with the same address family is preserved.
+    fn apply<R: ResolvedRoute>(self, routes: &mut Vec<R>) {
+        match self {
+            // This is the order produced by resolve_route already.
+            IpPreference::PreferIpv6 => {}
+            IpPreference::PreferIpv4 => {
+                let (v4, v6): (Vec<_>, Vec<_>) = std::mem::take(routes)
+                    .into_iter()
+                    .partition(|route| route.immediate_target().is_ipv4());
+                *routes = itertools::interleave(v4, v6).collect();
+            }
+            IpPreference::Ipv4Only => routes.retain(|route| route.immediate_target().is_ipv4()),
+            IpPreference::Ipv6Only => routes.retain(|route| route.immediate_target().is_ipv6()),
+        }
+    }
 }
 
 /// A policy object that decides how much to delay a route.
@@ -99,7 +146,11 @@ pub struct ConnectionOutcomeParams {
 
 impl Default for RouteResolver {
     fn default() -> Self {
-        Self { allow_ipv6: true }
+        Self {
+            allow_ipv6: true,
+            ip_preference: IpPreference::default(),
+            ip_overrides: IpOverrides::default(),
+        }
     }
 }
 
@@ -120,10 +171,14 @@ impl RouteResolver {
     where
         R: ResolveHostnames<Resolved: ResolvedRoute> + Clone + 'static,
     {
-        let Self { allow_ipv6 } = self;
+        let Self {
+            allow_ipv6,
+            ip_preference,
+            ip_overrides,
+        } = self;
 
-        let resolved = eagerly_resolve_each(ordered_routes, resolver).filter_map(
-            |(resolution_result, meta)| {
+        let resolved = eagerly_resolve_each(ordered_routes, ip_overrides.wrap(resolver))
+            .filter_map(|(resolution_result, meta)| {
                 std::future::ready(match resolution_result {
                     Ok(route_group) => Some((route_group, meta)),
                     Err((name, err)) => {
@@ -134,16 +189,16 @@ impl RouteResolver {
                         None
                     }
                 })
-            },
-        );
+            });
 
-        // Prune routes that connect directly to IPv6 addresses if necessary.
+        // Prune or reorder routes based on the address family they connect to directly.
         resolved.map(|(mut routes, meta)| {
             if !*allow_ipv6 {
                 routes
                     .routes
                     .retain(|route| route.immediate_target().is_ipv4())
             }
+            ip_preference.apply(&mut routes.routes);
             (routes, meta)
         })
     }
@@ -609,14 +664,15 @@ type EagerResolutionResult<R> = Result<ResolvedRoutes<R>, (Arc<str>, DnsError)>;
 /// Resolves all the input routes in parallel.
 fn eagerly_resolve_each<'r, R: ResolveHostnames + Clone + 'static>(
     routes: impl Iterator<Item = R> + 'r,
-    resolver: &'r impl Resolver,
+    resolver: impl Resolver + Copy + 'r,
 ) -> impl FusedStream<Item = (EagerResolutionResult<R::Resolved>, ResolveMeta)> + 'r {
-    FuturesUnordered::from_iter(routes.enumerate().map(|(index, route)| async move {
-        let resolution = super::resolve_route(resolver, route)
-            .await
-            .map(|routes| ResolvedRoutes {
-                routes: routes.collect(),
-            });
+    FuturesUnordered::from_iter(routes.enumerate().map(move |(index, route)| async move {
+        let resolution =
+            super::resolve_route(&resolver, route)
+                .await
+                .map(|routes| ResolvedRoutes {
+                    routes: routes.collect(),
+                });
 
         (
             resolution,
@@ -806,7 +862,7 @@ mod test {
 
     #[tokio::test(start_paused = true)]
     async fn single_resolved_route_e2e() {
-        let resolver = RouteResolver { allow_ipv6: true };
+        let resolver = RouteResolver::default();
         let name_resolver = HashMap::from([(
             "domain-name",
             LookupResult {
@@ -837,9 +893,92 @@ mod test {
         );
     }
 
+    #[test_case::test_case(IpPreference::PreferIpv6, &[ip_addr!("3fff::1"), ip_addr!("192.0.2.1"), ip_addr!("3fff::2"), ip_addr!("192.0.2.2")]; "prefer v6")]
+    #[test_case::test_case(IpPreference::PreferIpv4, &[ip_addr!("192.0.2.1"), ip_addr!("3fff::1"), ip_addr!("192.0.2.2"), ip_addr!("3fff::2")]; "prefer v4")]
+    #[test_case::test_case(IpPreference::Ipv4Only, &[ip_addr!("192.0.2.1"), ip_addr!("192.0.2.2")]; "v4 only")]
+    #[test_case::test_case(IpPreference::Ipv6Only, &[ip_addr!("3fff::1"), ip_addr!("3fff::2")]; "v6 only")]
+    #[tokio::test(start_paused = true)]
+    async fn ip_preference_filters_and_orders(
+        ip_preference: IpPreference,
+        expected: &'static [IpAddr],
+    ) {
+        let resolver = RouteResolver {
+            ip_preference,
+            ..Default::default()
+        };
+        let name_resolver = HashMap::from([(
+            "domain-name",
+            LookupResult {
+                ipv4: vec![ip_addr!(v4, "192.0.2.1"), ip_addr!(v4, "192.0.2.2")],
+                ipv6: vec![ip_addr!(v6, "3fff::1"), ip_addr!(v6, "3fff::2")],
+            },
+        )]);
+
+        let unresolved_routes = [FakeRoute(UnresolvedHost("domain-name".into()))];
+
+        let resolved: Vec<_> = resolver
+            .resolve(unresolved_routes.into_iter(), &name_resolver)
+            .flat_map(|(routes, _meta)| futures_util::stream::iter(routes.routes))
+            .collect()
+            .await;
+
+        assert_eq!(
+            resolved,
+            expected.iter().copied().map(FakeRoute).collect_vec()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ip_overrides_bypass_name_resolver() {
+        let resolver = RouteResolver {
+            ip_overrides: IpOverrides::new([(
+                "overridden-name",
+                [ip_addr!("192.0.2.99"), ip_addr!("3fff::99")],
+            )]),
+            ..Default::default()
+        };
+        let name_resolver = HashMap::from([
+            (
+                "overridden-name",
+                LookupResult {
+                    ipv4: vec![ip_addr!(v4, "192.0.2.1")],
+                    ipv6: vec![],
+                },
+            ),
+            (
+                "other-name",
+                LookupResult {
+                    ipv4: vec![ip_addr!(v4, "192.0.2.2")],
+                    ipv6: vec![],
+                },
+            ),
+        ]);
+
+        let unresolved_routes = [
+            FakeRoute(UnresolvedHost("overridden-name".into())),
+            FakeRoute(UnresolvedHost("other-name".into())),
+        ];
+
+        let resolved: Vec<_> = resolver
+            .resolve(unresolved_routes.into_iter(), &name_resolver)
+            .flat_map(|(routes, _meta)| futures_util::stream::iter(routes.routes))
+            .collect()
+            .await;
+
+        assert_eq!(
+            resolved,
+            [
+                ip_addr!("3fff::99"),
+                ip_addr!("192.0.2.99"),
+                ip_addr!("192.0.2.2")
+            ]
+            .map(FakeRoute)
+        );
+    }
+
     #[tokio::test(start_paused = true)]
     async fn multiple_resolved_routes_e2e() {
-        let resolver = RouteResolver { allow_ipv6: true };
+        let resolver = RouteResolver::default();
 
         let name_resolver = HashMap::from([
             (
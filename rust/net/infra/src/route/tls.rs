@@ -7,7 +7,7 @@ use std::sync::Arc;
 
 use boring_signal::ssl::SslVersion;
 
-use crate::certs::RootCertificates;
+use crate::certs::{ClientCertificate, RootCertificates};
 use crate::host::Host;
 use crate::route::{ReplaceFragment, RouteProvider, RouteProviderContext, SimpleRoute};
 use crate::Alpn;
@@ -18,6 +18,8 @@ pub struct TlsRouteFragment {
     pub sni: Host<Arc<str>>,
     pub alpn: Option<Alpn>,
     pub min_protocol_version: Option<SslVersion>,
+    /// Certificate to present if the server requests client authentication.
+    pub client_cert: Option<ClientCertificate>,
 }
 
 impl std::hash::Hash for TlsRouteFragment {
@@ -25,6 +27,7 @@ impl std::hash::Hash for TlsRouteFragment {
         self.root_certs.hash(state);
         self.sni.hash(state);
         self.alpn.hash(state);
+        self.client_cert.hash(state);
         // Ignore SslVersion, an opaque enum. Unfortunate, but a valid hash implementation.
     }
 }
@@ -81,6 +84,7 @@ impl<P: RouteProvider> RouteProvider for TlsRouteProvider<P> {
                 sni: sni.clone(),
                 alpn: None,
                 min_protocol_version: *min_protocol_version,
+                client_cert: None,
             },
             inner: route,
         })
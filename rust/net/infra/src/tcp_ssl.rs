@@ -10,7 +10,7 @@ use std::time::Duration;
 use boring_signal::ssl::{ConnectConfiguration, SslConnector, SslMethod, SslSignatureAlgorithm};
 use tokio_boring_signal::SslStream;
 
-use crate::certs::RootCertificates;
+use crate::certs::{ClientCertificate, RootCertificates};
 use crate::dns::DnsResolver;
 use crate::errors::TransportConnectError;
 use crate::host::Host;
@@ -152,10 +152,17 @@ where
             sni,
             alpn,
             min_protocol_version,
+            client_cert,
         } = fragment;
         let host = sni;
 
-        let ssl_config = ssl_config(&root_certs, host.as_deref(), alpn, min_protocol_version);
+        let ssl_config = ssl_config(
+            &root_certs,
+            host.as_deref(),
+            alpn,
+            min_protocol_version,
+            client_cert.as_ref(),
+        );
 
         async move {
             let domain = match &host {
@@ -182,9 +189,13 @@ fn ssl_config(
     host: Host<&str>,
     alpn: Option<Alpn>,
     min_required_tls_version: Option<boring_signal::ssl::SslVersion>,
+    client_cert: Option<&ClientCertificate>,
 ) -> Result<ConnectConfiguration, TransportConnectError> {
     let mut ssl = SslConnector::builder(SslMethod::tls_client())?;
     certs.apply_to_connector(&mut ssl, host)?;
+    if let Some(client_cert) = client_cert {
+        client_cert.apply_to_connector(&mut ssl)?;
+    }
     if let Some(alpn) = alpn {
         ssl.set_alpn_protos(alpn.as_ref())?;
     }
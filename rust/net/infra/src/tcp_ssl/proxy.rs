@@ -378,6 +378,7 @@ mod test {
                     sni: Host::Domain(PROXY_HOSTNAME.into()),
                     alpn: None,
                     min_protocol_version: None,
+                    client_cert: None,
                 },
                 inner: TcpRoute {
                     address: proxy_addr.ip(),
@@ -403,6 +404,7 @@ mod test {
                     sni: Host::Domain(SERVER_HOSTNAME.into()),
                     alpn: Some(Alpn::Http1_1),
                     min_protocol_version: None,
+                    client_cert: None,
                 },
                 "tcp proxy test",
             )
@@ -448,6 +450,7 @@ mod test {
                     sni: Host::Domain(SERVER_HOSTNAME.into()),
                     alpn: Some(Alpn::Http1_1),
                     min_protocol_version: None,
+                    client_cert: None,
                 },
                 "tcp proxy test",
             )
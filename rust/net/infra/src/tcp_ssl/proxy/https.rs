@@ -266,6 +266,8 @@ impl Connection for HttpProxyStream {
 
 #[cfg(test)]
 mod test {
+    use std::borrow::Cow;
+
     use assert_matches::assert_matches;
     use either::Either;
     use futures_util::future::BoxFuture;
@@ -277,12 +279,16 @@ mod test {
     use hyper::service::Service;
     use hyper::{Request, Response};
     use nonzero_ext::nonzero;
+    use test_case::test_case;
     use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
     use tokio::sync::mpsc;
 
     use super::*;
-    use crate::route::TcpRoute;
-    use crate::tcp_ssl::proxy::testutil::TcpServer;
+    use crate::certs::RootCertificates;
+    use crate::route::{TcpRoute, TlsRoute, TlsRouteFragment};
+    use crate::tcp_ssl::proxy::testutil::{
+        TcpServer, TlsServer, PROXY_CERTIFICATE, PROXY_HOSTNAME,
+    };
 
     /// [`hyper::service::HttpService`] that handles [`http::Method::CONNECT`]s.
     #[derive(Clone)]
@@ -382,14 +388,45 @@ mod test {
         server_addr.try_into().unwrap()
     }
 
+    /// Like [`spawn_localhost_proxy`], but the proxy expects clients to
+    /// establish a TLS session before sending the CONNECT request.
+    fn spawn_localhost_tls_proxy(service: ProxyService) -> TlsRoute<TcpRoute<IpAddr>> {
+        let tls_server = TlsServer::new(TcpServer::bind_localhost(), &PROXY_CERTIFICATE);
+        let server_addr = tls_server.tcp.listen_addr;
+
+        let _task_handle = tokio::spawn(async move {
+            loop {
+                let (stream, _info) = tls_server.accept().await;
+                hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), &service)
+                    .with_upgrades()
+                    .await
+                    .expect("handles connection")
+            }
+        });
+
+        TlsRoute {
+            fragment: TlsRouteFragment {
+                root_certs: RootCertificates::FromDer(Cow::Borrowed(PROXY_CERTIFICATE.cert.der())),
+                sni: Host::Domain(PROXY_HOSTNAME.into()),
+                alpn: None,
+                min_protocol_version: None,
+                client_cert: None,
+            },
+            inner: server_addr.try_into().unwrap(),
+        }
+    }
+
     const TARGET_PORT: NonZeroU16 = nonzero!(1234u16);
     const TARGET_HOST: &str = "fake-target.example.com";
     const EXPECTED_AUTHORITY: &str = "fake-target.example.com:1234";
     const USERNAME: &str = "fake-username";
     const PASSWORD: &str = "fake-password";
 
+    #[test_case(false; "plaintext to proxy")]
+    #[test_case(true; "TLS to proxy")]
     #[test_log::test(tokio::test)]
-    async fn successful_connect() {
+    async fn successful_connect(tls_to_proxy: bool) {
         let authorization = Some(HttpProxyAuth {
             username: USERNAME.to_owned(),
             password: PASSWORD.to_owned(),
@@ -397,10 +434,15 @@ mod test {
 
         let (proxy_upstream_tx, mut proxy_upstream_rx) = mpsc::unbounded_channel();
 
-        let route_to_proxy = spawn_localhost_proxy(ProxyService {
+        let service = ProxyService {
             upgrades_tx: proxy_upstream_tx,
             expected_auth: authorization.clone(),
-        });
+        };
+        let route_to_proxy = if tls_to_proxy {
+            Either::Left(spawn_localhost_tls_proxy(service))
+        } else {
+            Either::Right(spawn_localhost_proxy(service))
+        };
 
         let route = HttpsProxyRoute {
             fragment: HttpProxyRouteFragment {
@@ -410,7 +452,7 @@ mod test {
                 target_port: TARGET_PORT,
                 authorization,
             },
-            inner: Either::Right(route_to_proxy),
+            inner: route_to_proxy,
         };
 
         let mut client_stream = super::super::StatelessProxied
@@ -19,6 +19,7 @@ use crate::errors::LogSafeDisplay;
 use crate::utils::NetworkChangeEvent;
 
 pub mod fake_transport;
+pub mod plaintext;
 
 #[derive(Debug, Display)]
 pub enum TestError {
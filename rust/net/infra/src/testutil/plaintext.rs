@@ -0,0 +1,135 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Unencrypted websocket connections, for testing against local servers.
+//!
+//! Routes are still [`TransportRoute`]s so that they work with the usual connection machinery
+//! (including outcome tracking), but [`PlaintextTransportConnector`] ignores their TLS fragment
+//! and hands back the bare TCP stream.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use http::uri::PathAndQuery;
+use http::{HeaderMap, Uri};
+
+use crate::certs::RootCertificates;
+use crate::errors::TransportConnectError;
+use crate::host::Host;
+use crate::route::{
+    Connector, ConnectorFactory, DirectOrProxyRoute, HttpRouteFragment, HttpsTlsRoute, TcpRoute,
+    TlsRoute, TlsRouteFragment, TransportRoute, UnresolvedHost, UnresolvedWebsocketServiceRoute,
+    WebSocketRoute, WebSocketRouteFragment,
+};
+use crate::tcp_ssl::StatelessTcp;
+
+/// Creates a route for a `ws://` URL, to be connected with [`PlaintextTransportConnector`].
+///
+/// Returns `None` if `url` doesn't use the `ws` scheme or doesn't include both a host and a port.
+pub fn plaintext_ws_route(url: &Uri) -> Option<UnresolvedWebsocketServiceRoute> {
+    if url.scheme_str() != Some("ws") {
+        return None;
+    }
+    let host: Arc<str> = url.host()?.into();
+    let port = url.port_u16()?.try_into().ok()?;
+    let endpoint = url
+        .path_and_query()
+        .cloned()
+        .unwrap_or_else(|| PathAndQuery::from_static("/"));
+
+    Some(WebSocketRoute {
+        fragment: WebSocketRouteFragment {
+            ws_config: Default::default(),
+            endpoint,
+            headers: HeaderMap::new(),
+        },
+        inner: HttpsTlsRoute {
+            fragment: HttpRouteFragment {
+                host_header: Arc::clone(&host),
+                path_prefix: "".into(),
+                front_name: None,
+            },
+            inner: TlsRoute {
+                // Never used; see PlaintextTransportConnector.
+                fragment: TlsRouteFragment {
+                    root_certs: RootCertificates::Native,
+                    sni: Host::Domain(Arc::clone(&host)),
+                    alpn: None,
+                    min_protocol_version: None,
+                    client_cert: None,
+                },
+                inner: DirectOrProxyRoute::Direct(TcpRoute {
+                    address: UnresolvedHost::from(host),
+                    port,
+                }),
+            },
+        },
+    })
+}
+
+/// [`Connector`] for [`TransportRoute`]s that skips the TLS handshake.
+///
+/// Only direct routes are supported; proxied routes fail with
+/// [`TransportConnectError::InvalidConfiguration`].
+#[derive(Clone, Debug, Default)]
+pub struct PlaintextTransportConnector;
+
+impl Connector<TransportRoute, ()> for PlaintextTransportConnector {
+    type Connection = tokio::net::TcpStream;
+
+    type Error = TransportConnectError;
+
+    fn connect_over(
+        &self,
+        (): (),
+        route: TransportRoute,
+        log_tag: &str,
+    ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        let TlsRoute { fragment: _, inner } = route;
+        let tcp_connect = match inner {
+            DirectOrProxyRoute::Direct(tcp) => Ok(StatelessTcp.connect_over((), tcp, log_tag)),
+            DirectOrProxyRoute::Proxy(_) => Err(TransportConnectError::InvalidConfiguration),
+        };
+        async move { tcp_connect?.await }
+    }
+}
+
+impl ConnectorFactory<TransportRoute> for PlaintextTransportConnector {
+    type Connector = Self;
+    type Connection = <Self as Connector<TransportRoute, ()>>::Connection;
+
+    fn make(&self) -> Self::Connector {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn route_from_ws_url() {
+        let route = plaintext_ws_route(&Uri::from_static("ws://127.0.0.1:8080/v1/websocket/?x=1"))
+            .expect("valid");
+        assert_eq!(route.fragment.endpoint, "/v1/websocket/?x=1");
+        assert_eq!(&*route.inner.fragment.host_header, "127.0.0.1");
+        assert_matches!(
+            route.inner.inner.inner,
+            DirectOrProxyRoute::Direct(TcpRoute { address, port })
+                if address == UnresolvedHost::from(Arc::from("127.0.0.1")) && port.get() == 8080
+        );
+    }
+
+    #[test_case("wss://127.0.0.1:8080/"; "TLS scheme")]
+    #[test_case("ws://127.0.0.1/"; "no port")]
+    #[test_case("ws://127.0.0.1:0/"; "zero port")]
+    #[test_case("/v1/websocket/"; "no host")]
+    fn route_from_unsupported_url(url: &'static str) {
+        assert_eq!(plaintext_ws_route(&Uri::from_static(url)), None);
+    }
+}
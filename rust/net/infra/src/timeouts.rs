@@ -43,6 +43,10 @@ pub const WS_MAX_IDLE_INTERVAL: Duration = Duration::from_secs(45);
 /// (this includes DNS resolution, TCP connection, and SSL handshake)
 pub const ONE_ROUTE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// Timeout for a single route within a connect operation that may attempt several
+/// (this includes the TCP connection, TLS handshake, and any protocol upgrade on top)
+pub const ROUTE_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// How often to check if the network interface has changed (without an OS-provided network change
 /// event).
 pub const NETWORK_INTERFACE_POLL_INTERVAL: Duration = Duration::from_secs(5);
@@ -8,7 +8,7 @@ use std::time::Duration;
 
 use futures_util::{Sink, Stream, TryFutureExt};
 use http::uri::PathAndQuery;
-use tungstenite::protocol::CloseFrame;
+use tungstenite::protocol::{CloseFrame, WebSocketConfig};
 use tungstenite::{http, Message, Utf8Bytes};
 
 use crate::errors::LogSafeDisplay;
@@ -22,6 +22,8 @@ pub use error::WebSocketConnectError;
 pub mod connection;
 pub use connection::Connection;
 
+pub mod keepalive;
+
 pub mod attested;
 
 pub mod noise;
@@ -54,6 +56,32 @@ pub struct Config {
     /// the server time to respond to a sent ping before determining that the
     /// connection is dead.
     pub remote_idle_disconnect_timeout: Duration,
+
+    /// The largest single frame that will be accepted from the server, or `None` for no limit.
+    pub max_frame_size: Option<usize>,
+
+    /// The largest message, after reassembling frames, that will be accepted
+    /// from the server, or `None` for no limit.
+    ///
+    /// Messages that exceed this or [`Self::max_frame_size`] result in
+    /// [`SpaceError::Capacity`] errors.
+    pub max_message_size: Option<usize>,
+
+    /// How long to wait without any messages sent or received before closing
+    /// the connection, or `None` to keep it open indefinitely.
+    ///
+    /// Only application messages count; pings and pongs don't reset the timer.
+    /// When this elapses, the connection is closed with
+    /// [`FinishReason::IdleTimeout`](connection::FinishReason::IdleTimeout).
+    pub application_idle_timeout: Option<Duration>,
+}
+
+impl Config {
+    /// Applies the size limits from this config to a tungstenite [`WebSocketConfig`].
+    pub fn apply_size_limits(&self, ws_config: &mut WebSocketConfig) {
+        ws_config.max_frame_size = self.max_frame_size;
+        ws_config.max_message_size = self.max_message_size;
+    }
 }
 
 /// A type that can be used like a [`tokio_tungstenite::WebSocketStream`].
@@ -86,6 +114,19 @@ pub enum WebSocketError {
     Other(&'static str),
 }
 
+impl WebSocketError {
+    /// If this error was caused by data exceeding [`Config::max_frame_size`] or
+    /// [`Config::max_message_size`], returns the size of the data and the limit it exceeded.
+    pub fn size_limit_exceeded(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Capacity(SpaceError::Capacity(
+                tungstenite::error::CapacityError::MessageTooLong { size, max_size },
+            )) => Some((*size, *max_size)),
+            _ => None,
+        }
+    }
+}
+
 /// Stateless [`Connector`] implementation for websocket-over-HTTPS routes.
 #[derive(Default)]
 pub struct Stateless;
@@ -314,12 +355,19 @@ pub mod testutil {
 
     pub async fn fake_websocket() -> (WebSocketStream<DuplexStream>, WebSocketStream<DuplexStream>)
     {
+        fake_websocket_with_config(WebSocketConfig::default()).await
+    }
+
+    /// Like [`fake_websocket`], but uses `ws_config` for the client end.
+    pub async fn fake_websocket_with_config(
+        ws_config: WebSocketConfig,
+    ) -> (WebSocketStream<DuplexStream>, WebSocketStream<DuplexStream>) {
         let (client, server) = tokio::io::duplex(1024);
         let client_future = Stateless.connect_over(
             client,
             (
                 WebSocketRouteFragment {
-                    ws_config: WebSocketConfig::default(),
+                    ws_config,
                     endpoint: PathAndQuery::from_static("/"),
                     headers: Default::default(),
                 },
@@ -346,6 +394,7 @@ pub mod testutil {
 mod test {
 
     use futures_util::{SinkExt as _, StreamExt as _};
+    use test_case::test_case;
 
     use super::testutil::*;
     use super::*;
@@ -364,4 +413,37 @@ mod test {
             .expect("ok result");
         assert_eq!(response, Message::Pong(vec![].into()));
     }
+
+    #[test_case(Some(10), None; "frame")]
+    #[test_case(None, Some(10); "message")]
+    #[tokio::test]
+    async fn websocket_client_enforces_size_limits(
+        max_frame_size: Option<usize>,
+        max_message_size: Option<usize>,
+    ) {
+        let config = Config {
+            local_idle_timeout: Duration::MAX,
+            remote_idle_ping_timeout: Duration::MAX,
+            remote_idle_disconnect_timeout: Duration::MAX,
+            max_frame_size,
+            max_message_size,
+        };
+        let mut ws_config = WebSocketConfig::default();
+        config.apply_size_limits(&mut ws_config);
+
+        let (mut server, mut client) = fake_websocket_with_config(ws_config).await;
+        server
+            .send(Message::Binary(vec![0; 100].into()))
+            .await
+            .unwrap();
+        let error = client
+            .next()
+            .await
+            .expect("some result")
+            .expect_err("message is too large");
+        assert_eq!(
+            WebSocketError::from(error).size_limit_exceeded(),
+            Some((100, 10))
+        );
+    }
 }
@@ -6,6 +6,7 @@
 use std::sync::Arc;
 
 use attest::client_connection::ClientConnection;
+use attest::enclave::{AttestationInfo, AttestationTranscript};
 use tokio::sync::{mpsc, oneshot};
 use tokio_stream::wrappers::ReceiverStream;
 use tungstenite::protocol::frame::coding::CloseCode;
@@ -23,6 +24,8 @@ use crate::ws::{NextOrClose, TextOrBinary, WebSocketError, WebSocketStreamLike};
 pub struct AttestedConnection {
     ws_client: WsClient,
     client_connection: ClientConnection,
+    attestation_info: AttestationInfo,
+    attestation_message: bytes::Bytes,
 }
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -77,11 +80,16 @@ impl AttestedConnection {
     {
         let mut ws_client = WsClient::new(ws, ws_config, log_tag);
 
-        let client_connection = authenticate(&mut ws_client, new_handshake).await?;
+        let attestation_msg = read_attestation(&mut ws_client).await?;
+        let handshake = new_handshake(&attestation_msg)?;
+        let (client_connection, attestation_info) =
+            complete_handshake(&mut ws_client, handshake).await?;
 
         Ok(Self {
             client_connection,
             ws_client,
+            attestation_info,
+            attestation_message: attestation_msg,
         })
     }
 
@@ -96,6 +104,8 @@ impl AttestedConnection {
         let Self {
             ws_client,
             client_connection,
+            attestation_info: _,
+            attestation_message: _,
         } = self;
 
         let message = ws_client.read().await?;
@@ -113,6 +123,8 @@ impl AttestedConnection {
         let Self {
             ws_client,
             client_connection,
+            attestation_info: _,
+            attestation_message: _,
         } = self;
 
         let message = client_connection.send(plaintext)?;
@@ -147,6 +159,21 @@ impl AttestedConnection {
     pub fn handshake_hash(&self) -> &[u8] {
         &self.client_connection.handshake_hash
     }
+
+    /// Get a description of what was verified about the remote enclave.
+    pub fn attestation_info(&self) -> &AttestationInfo {
+        &self.attestation_info
+    }
+
+    /// Get a record of the attestation this session was established with, which can be
+    /// archived and verified again later.
+    pub fn attestation_transcript(&self) -> AttestationTranscript {
+        AttestationTranscript {
+            attestation_message: self.attestation_message.to_vec(),
+            attestation_info: self.attestation_info.clone(),
+            handshake_hash: self.client_connection.handshake_hash.clone(),
+        }
+    }
 }
 
 impl AsMut<Self> for AttestedConnection {
@@ -293,7 +320,7 @@ async fn spawned_task_body(
                 }
                 MessageEvent::SentPing | MessageEvent::ReceivedPingPong => (),
             },
-            Outcome::Finished(Ok(FinishReason::RemoteDisconnect)) => {
+            Outcome::Finished(Ok(FinishReason::RemoteDisconnect | FinishReason::IdleTimeout)) => {
                 if incoming_tx
                     .send(Ok(NextOrClose::Close(None)))
                     .await
@@ -356,15 +383,18 @@ async fn spawned_task_body(
     }
 }
 
-async fn authenticate(
+async fn read_attestation(
     websocket: &mut WsClient,
-    new_handshake: impl FnOnce(&[u8]) -> attest::enclave::Result<attest::enclave::Handshake>,
-) -> Result<ClientConnection, AttestedConnectionError> {
-    let attestation_msg = websocket.read().await?.next_or_else(|close| {
+) -> Result<bytes::Bytes, AttestedConnectionError> {
+    websocket.read().await?.next_or_else(|close| {
         AttestedConnectionError::Protocol(AttestedProtocolError::UnexpectedClose(close.into()))
-    })?;
-    let handshake = new_handshake(attestation_msg.as_ref())?;
+    })
+}
 
+async fn complete_handshake(
+    websocket: &mut WsClient,
+    handshake: attest::enclave::Handshake,
+) -> Result<(ClientConnection, AttestationInfo), AttestedConnectionError> {
     websocket
         .write(Vec::from(handshake.initial_request()))
         .await?;
@@ -373,7 +403,8 @@ async fn authenticate(
         AttestedConnectionError::Protocol(AttestedProtocolError::UnexpectedClose(close.into()))
     })?;
 
-    Ok(handshake.complete(&initial_response)?)
+    let attestation_info = handshake.attestation_info().clone();
+    Ok((handshake.complete(&initial_response)?, attestation_info))
 }
 
 impl From<oneshot::error::RecvError> for SendError {
@@ -648,6 +679,9 @@ mod test {
         local_idle_timeout: Duration::from_secs(10),
         remote_idle_ping_timeout: Duration::from_secs(10),
         remote_idle_disconnect_timeout: Duration::from_secs(20),
+        max_frame_size: None,
+        max_message_size: None,
+        application_idle_timeout: None,
     };
 
     #[tokio::test]
@@ -671,6 +705,11 @@ mod test {
         .await
         .unwrap();
 
+        assert_eq!(
+            connection.attestation_info().measurement,
+            attest::sgx_session::testutil::mrenclave_bytes()
+        );
+
         connection.send(Vec::from(ECHO_BYTES)).await.unwrap();
         let response: Vec<u8> = connection.receive().await.unwrap().unwrap_next();
         assert_eq!(&response, ECHO_BYTES);
@@ -6,7 +6,7 @@
 use std::fmt::Display;
 use std::io::Error as IoError;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use futures_util::{SinkExt as _, Stream, StreamExt as _};
 use pin_project::pin_project;
@@ -17,6 +17,7 @@ use tungstenite::protocol::CloseFrame;
 use tungstenite::Message;
 
 use crate::errors::LogSafeDisplay;
+use crate::ws::keepalive::{AdaptiveKeepalive, KeepaliveTuner};
 use crate::ws::{Config, TextOrBinary, WebSocketError, WebSocketStreamLike};
 
 /// An established websocket connection.
@@ -69,13 +70,43 @@ pub struct Connection<S, R> {
     /// The last time that a message was received from the server.
     last_heard_from_server: Option<Instant>,
 
+    /// The last time that a non-ping/pong message was sent or received.
+    last_application_traffic: Option<Instant>,
+
     /// Configuration for this websocket client's behavior.
     config: Config,
 
+    /// Overrides the ping timeouts in `config`, if set.
+    keepalive: Option<KeepaliveTuner>,
+
+    /// The close frame to send when `outgoing_rx` ends.
+    local_close_frame: LocalCloseFrame,
+
     /// A tag to include in log lines, to disambiguate multiple websockets.
     log_tag: Arc<str>,
 }
 
+/// The close frame a [`Connection`] sends when its outgoing stream ends.
+///
+/// This is a shared handle, so whoever owns the sending end of the outgoing
+/// stream can choose a close code and reason just before hanging up. If none
+/// is set, the close frame is sent without a code.
+#[derive(Clone, Debug, Default)]
+pub struct LocalCloseFrame(Arc<Mutex<Option<CloseFrame>>>);
+
+impl LocalCloseFrame {
+    pub fn set(&self, code: CloseCode, reason: &str) {
+        *self.0.lock().expect("not poisoned") = Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        });
+    }
+
+    fn take(&self) -> Option<CloseFrame> {
+        self.0.lock().expect("not poisoned").take()
+    }
+}
+
 /// Fatal error that causes a connection to be closed.
 #[derive(Debug, displaydoc::Display, thiserror::Error)]
 pub enum NextEventError {
@@ -123,6 +154,9 @@ pub enum FinishReason {
     LocalDisconnect,
     /// The remote end disconnected first.
     RemoteDisconnect,
+    /// The local end disconnected because there was no application traffic for
+    /// [`Config::application_idle_timeout`].
+    IdleTimeout,
 }
 
 /// Errors that can occur when sending.
@@ -185,10 +219,34 @@ where
             last_heard_from_server: None,
             last_sent_to_server: None,
             last_sent_ping_to_server: None,
+            last_application_traffic: None,
+            keepalive: None,
+            local_close_frame: LocalCloseFrame::default(),
             log_tag,
         }
     }
 
+    /// Adjusts how often pings are sent based on whether they get answered,
+    /// instead of using the fixed timeouts in the [`Config`].
+    ///
+    /// The grace period the server has to answer a ping before the connection
+    /// is considered dead is kept the same.
+    pub fn with_adaptive_keepalive(self, keepalive: AdaptiveKeepalive) -> Self {
+        Self {
+            keepalive: Some(KeepaliveTuner::new(keepalive)),
+            ..self
+        }
+    }
+
+    /// Sends the close frame from `local_close_frame`, if one has been set by
+    /// then, when the outgoing stream ends.
+    pub fn with_local_close_frame(self, local_close_frame: LocalCloseFrame) -> Self {
+        Self {
+            local_close_frame,
+            ..self
+        }
+    }
+
     /// Wait for the first available event, returning the outcome.
     ///
     /// The events that can be handled include
@@ -197,6 +255,8 @@ where
     /// - the server closes the connection
     /// - the client hangs up on the outgoing queue
     /// - the websocket is quiet for too long and a Ping is sent
+    /// - no messages have been sent or received for too long and the connection
+    ///   is closed
     ///
     /// Events that should terminate the connection are returned as a
     /// [`Outcome::Finished`] value; others are returned as
@@ -224,13 +284,34 @@ where
                     local_idle_timeout,
                     remote_idle_ping_timeout,
                     remote_idle_disconnect_timeout,
+                    max_frame_size: _,
+                    max_message_size: _,
+                    application_idle_timeout,
                 },
             last_sent_to_server,
             last_sent_ping_to_server,
             last_heard_from_server,
+            last_application_traffic,
+            keepalive,
+            local_close_frame,
             log_tag,
         } = self.project();
 
+        let (local_idle_timeout, remote_idle_ping_timeout, remote_idle_disconnect_timeout) =
+            match keepalive.as_ref() {
+                None => (
+                    *local_idle_timeout,
+                    *remote_idle_ping_timeout,
+                    *remote_idle_disconnect_timeout,
+                ),
+                Some(keepalive) => {
+                    let interval = keepalive.interval();
+                    let grace_period =
+                        remote_idle_disconnect_timeout.saturating_sub(*remote_idle_ping_timeout);
+                    (interval, interval, interval + grace_period)
+                }
+            };
+
         // For the first call this function, assume we just heard from & sent to
         // the server. Later calls will use the recorded values from previous
         // calls.
@@ -238,6 +319,7 @@ where
         let last_heard_from_server = last_heard_from_server.get_or_insert(now);
         let last_sent_to_server = last_sent_to_server.get_or_insert(now);
         let last_sent_ping_to_server = last_sent_ping_to_server.get_or_insert(now);
+        let last_application_traffic = last_application_traffic.get_or_insert(now);
 
         #[derive(Debug)]
         enum Event<M> {
@@ -247,13 +329,14 @@ where
             Received(Result<Message, tungstenite::Error>),
             ConnectionIdle,
             RemoteDisconnectedTimeout,
+            ApplicationIdle,
         }
 
         let (earliest_timeout, inactivity_event) = {
             // If we haven't sent anything to the server in a while, send a ping to
             // make sure that it knows we're still around.
             let local_connection_idle_timeout = (
-                *last_sent_to_server + *local_idle_timeout,
+                *last_sent_to_server + local_idle_timeout,
                 Event::ConnectionIdle,
             );
 
@@ -262,23 +345,29 @@ where
             // we sent a ping recently, don't keep spamming the server.
             let remote_connection_idle = (
                 Instant::max(*last_sent_ping_to_server, *last_heard_from_server)
-                    + *remote_idle_ping_timeout,
+                    + remote_idle_ping_timeout,
                 Event::ConnectionIdle,
             );
 
             // If we haven't heard from the server for long enough, declare the
             // connection dead.
             let remote_connection_disconnected = (
-                *last_heard_from_server + *remote_idle_disconnect_timeout,
+                *last_heard_from_server + remote_idle_disconnect_timeout,
                 Event::RemoteDisconnectedTimeout,
             );
 
+            // If nothing but pings and pongs have gone back and forth for long
+            // enough, close the connection.
+            let application_idle = application_idle_timeout
+                .map(|timeout| (*last_application_traffic + timeout, Event::ApplicationIdle));
+
             [
                 local_connection_idle_timeout,
                 remote_connection_idle,
                 remote_connection_disconnected,
             ]
             .into_iter()
+            .chain(application_idle)
             .min_by_key(|(time, _)| *time)
             .expect("non-empty array")
         };
@@ -296,10 +385,32 @@ where
                 // The server is expected to send frames every so often (either
                 // messages or responses to our pings). We haven't gotten one in
                 // a while, so assume the connection was broken.
+                if let Some(keepalive) = keepalive.as_mut() {
+                    keepalive.on_missed_response();
+                }
                 Outcome::Finished(Err(NextEventError::ServerIdleTimeout(
-                    *remote_idle_disconnect_timeout,
+                    remote_idle_disconnect_timeout,
                 )))
             }
+            Event::ApplicationIdle => {
+                log::info!(
+                    "[{log_tag}] no messages sent or received in {:.3?}; closing the connection",
+                    last_application_traffic.elapsed()
+                );
+                let result = stream
+                    .send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Normal,
+                        reason: "idle".into(),
+                    })))
+                    .await;
+                Outcome::Finished(match result {
+                    Ok(()) => Ok(FinishReason::IdleTimeout),
+                    Err(e) => Err({
+                        let e = TungsteniteSendError::from(TungsteniteError::from(e));
+                        NextEventError::CloseFailed(e)
+                    }),
+                })
+            }
             Event::ConnectionIdle => {
                 if last_sent_to_server > last_heard_from_server {
                     // Differentiate between local-idle and remote-idle pings by checking if we have a
@@ -308,6 +419,9 @@ where
                         "[{log_tag}] server hasn't responded in {:.3?}; sending a ping",
                         last_heard_from_server.elapsed()
                     );
+                    if let Some(keepalive) = keepalive.as_mut() {
+                        keepalive.on_missed_response();
+                    }
                 }
                 *ping_count = ping_count.wrapping_add(1);
                 match stream
@@ -319,6 +433,9 @@ where
                         let now = Instant::now();
                         *last_sent_to_server = now;
                         *last_sent_ping_to_server = now;
+                        if let Some(keepalive) = keepalive.as_mut() {
+                            keepalive.on_ping_sent();
+                        }
                         Outcome::Continue(MessageEvent::SentPing)
                     }
                     Err(err) => Outcome::Finished(Err(NextEventError::PingFailed(err))),
@@ -327,7 +444,7 @@ where
             Event::ClientDisconnect => {
                 // The client has been closed, so there aren't any more messages
                 // coming in. Tell the server we're done.
-                let result = stream.send(Message::Close(None)).await;
+                let result = stream.send(Message::Close(local_close_frame.take())).await;
                 Outcome::Finished(match result {
                     Ok(()) => Ok(FinishReason::LocalDisconnect),
                     Err(e) => Err({
@@ -339,7 +456,9 @@ where
             Event::ToSend((message, meta)) => {
                 let event = match stream.send(message.into()).await {
                     Ok(()) => {
-                        *last_sent_to_server = Instant::now();
+                        let now = Instant::now();
+                        *last_sent_to_server = now;
+                        *last_application_traffic = now;
                         MessageEvent::SentMessage(meta)
                     }
                     Err(e) => {
@@ -356,13 +475,20 @@ where
             }
             Event::Received(Ok(message)) => {
                 *last_heard_from_server = Instant::now();
+                if let Some(keepalive) = keepalive.as_mut() {
+                    keepalive.on_heard_from_server();
+                }
                 match message {
                     Message::Text(text) => {
+                        *last_application_traffic = *last_heard_from_server;
                         Outcome::Continue(MessageEvent::ReceivedMessage(TextOrBinary::Text(text)))
                     }
-                    Message::Binary(binary) => Outcome::Continue(MessageEvent::ReceivedMessage(
-                        TextOrBinary::Binary(binary),
-                    )),
+                    Message::Binary(binary) => {
+                        *last_application_traffic = *last_heard_from_server;
+                        Outcome::Continue(MessageEvent::ReceivedMessage(TextOrBinary::Binary(
+                            binary,
+                        )))
+                    }
                     Message::Ping(_) | Message::Pong(_) => {
                         // tungstenite handles pings internally, nothing to do here.
                         Outcome::Continue(MessageEvent::ReceivedPingPong)
@@ -635,6 +761,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -671,6 +800,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -721,6 +853,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -789,6 +924,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -801,6 +939,40 @@ mod test {
         )
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn sends_chosen_close_frame_after_outgoing_close() {
+        let (mut ws_server, ws_client) = TestStream::new_pair(1);
+        let (outgoing_tx, outgoing_rx) = mpsc::channel::<(_, ())>(1);
+        let local_close_frame = LocalCloseFrame::default();
+        let connection = Connection::new(
+            ws_client,
+            ReceiverStream::new(outgoing_rx),
+            Config {
+                local_idle_timeout: FOREVER,
+                remote_idle_ping_timeout: FOREVER,
+                remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
+            },
+            "test".into(),
+        )
+        .with_local_close_frame(local_close_frame.clone());
+        pin_mut!(connection);
+
+        local_close_frame.set(CloseCode::Away, "going to sleep");
+        drop(outgoing_tx);
+        assert_matches!(
+            connection.handle_next_event().await,
+            Outcome::Finished(Ok(FinishReason::LocalDisconnect))
+        );
+        assert_matches!(
+            ws_server.next().await,
+            Some(Ok(Message::Close(Some(CloseFrame { code: CloseCode::Away, reason }))))
+                if reason.as_str() == "going to sleep"
+        );
+    }
+
     #[tokio::test(start_paused = true)]
     async fn handles_remote_close_with_error() {
         let (mut ws_server, ws_client) = TestStream::new_pair(5);
@@ -812,6 +984,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -845,6 +1020,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -877,6 +1055,9 @@ mod test {
                 local_idle_timeout: LOCAL_IDLE_TIMEOUT,
                 remote_idle_ping_timeout: FOREVER,
                 remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -905,6 +1086,123 @@ mod test {
         assert_ne!(first_ping, second_ping);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn adaptive_keepalive_lengthens_interval_after_pong() {
+        use nonzero_ext::nonzero;
+
+        use crate::ws::keepalive::{AdaptiveKeepaliveConfig, KeepaliveIntervals};
+
+        const INITIAL_INTERVAL: Duration = Duration::from_secs(10);
+        const STEP: Duration = Duration::from_secs(5);
+
+        let (mut ws_server, ws_client) = TestStream::new_pair(10);
+        let outgoing_rx = futures_util::stream::pending::<(_, ())>();
+        let intervals = KeepaliveIntervals::default();
+        let connection = Connection::new(
+            ws_client,
+            outgoing_rx,
+            Config {
+                local_idle_timeout: FOREVER,
+                remote_idle_ping_timeout: INITIAL_INTERVAL,
+                remote_idle_disconnect_timeout: INITIAL_INTERVAL * 2,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
+            },
+            "test".into(),
+        )
+        .with_adaptive_keepalive(AdaptiveKeepalive {
+            config: AdaptiveKeepaliveConfig {
+                initial_interval: INITIAL_INTERVAL,
+                min_interval: INITIAL_INTERVAL,
+                max_interval: Duration::from_secs(60),
+                step: STEP,
+                successes_before_increase: nonzero!(1u32),
+            },
+            intervals: intervals.clone(),
+            network: "test network".into(),
+        });
+        pin_mut!(connection);
+
+        let start = Instant::now();
+        assert_matches!(
+            connection.as_mut().handle_next_event().await,
+            Outcome::Continue(MessageEvent::SentPing)
+        );
+        assert_eq!(Instant::now() - start, INITIAL_INTERVAL);
+        assert_matches!(
+            ws_server.next().now_or_never().expect("now"),
+            Some(Ok(Message::Ping(_)))
+        );
+
+        ws_server
+            .send(Message::Pong(Default::default()))
+            .await
+            .expect("can send from server");
+        assert_matches!(
+            connection.as_mut().handle_next_event().await,
+            Outcome::Continue(MessageEvent::ReceivedPingPong)
+        );
+        assert_eq!(intervals.get("test network"), Some(INITIAL_INTERVAL + STEP));
+
+        let second_start = Instant::now();
+        assert_matches!(
+            connection.handle_next_event().await,
+            Outcome::Continue(MessageEvent::SentPing)
+        );
+        assert_eq!(Instant::now() - second_start, INITIAL_INTERVAL + STEP);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closes_after_application_inactivity() {
+        const LOCAL_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+        const APPLICATION_IDLE_TIMEOUT: Duration = Duration::from_secs(25);
+
+        let (mut ws_server, ws_client) = TestStream::new_pair(10);
+        let outgoing_rx = futures_util::stream::pending::<(_, ())>();
+        let connection = Connection::new(
+            ws_client,
+            outgoing_rx,
+            Config {
+                local_idle_timeout: LOCAL_IDLE_TIMEOUT,
+                remote_idle_ping_timeout: FOREVER,
+                remote_idle_disconnect_timeout: FOREVER,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: Some(APPLICATION_IDLE_TIMEOUT),
+            },
+            "test".into(),
+        );
+        pin_mut!(connection);
+
+        let start = Instant::now();
+
+        // Keepalive pings don't count as application traffic.
+        for _ in 0..2 {
+            assert_matches!(
+                connection.as_mut().handle_next_event().await,
+                Outcome::Continue(MessageEvent::SentPing)
+            );
+            assert_matches!(
+                ws_server.next().now_or_never().expect("now"),
+                Some(Ok(Message::Ping(_)))
+            );
+        }
+
+        assert_matches!(
+            connection.handle_next_event().await,
+            Outcome::Finished(Ok(FinishReason::IdleTimeout))
+        );
+        assert_eq!(Instant::now() - start, APPLICATION_IDLE_TIMEOUT);
+        assert_matches!(
+            ws_server.next().now_or_never().expect("now"),
+            Some(Ok(Message::Close(Some(CloseFrame {
+                code: CloseCode::Normal,
+                ..
+            }))))
+        );
+    }
+
     #[tokio::test(start_paused = true)]
     async fn sends_ping_after_remote_inactivity_then_time_out() {
         // A single ping will be sent locally before the server times out.
@@ -919,6 +1217,9 @@ mod test {
             Config {
                 remote_idle_ping_timeout: REMOTE_IDLE_PING_TIMEOUT,
                 remote_idle_disconnect_timeout: REMOTE_DISCONNECT_TIMEOUT,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
                 local_idle_timeout: FOREVER,
             },
             "test".into(),
@@ -956,6 +1257,9 @@ mod test {
                 local_idle_timeout: FOREVER,
                 remote_idle_ping_timeout: REMOTE_IDLE_TIMEOUT,
                 remote_idle_disconnect_timeout: REMOTE_DISCONNECT_TIMEOUT,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
             },
             "test".into(),
         );
@@ -0,0 +1,249 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Adaptive tuning of the websocket keepalive interval.
+//!
+//! NATs and stateful firewalls drop idle mappings after a timeout that varies
+//! from network to network. Pinging more often than necessary wastes battery,
+//! but pinging less often than the timeout makes the connection unreachable.
+//! [`AdaptiveKeepalive`] probes for the longest interval that works by
+//! stretching the interval after a run of answered pings and backing off after
+//! one goes unanswered.
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Parameters for adjusting the keepalive interval of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdaptiveKeepaliveConfig {
+    /// The interval to start with on a network with nothing learned yet.
+    pub initial_interval: Duration,
+    /// The shortest interval to back off to.
+    pub min_interval: Duration,
+    /// The longest interval to try.
+    pub max_interval: Duration,
+    /// How much to lengthen or shorten the interval by at a time.
+    pub step: Duration,
+    /// How many pings in a row must be answered before the interval is
+    /// lengthened.
+    pub successes_before_increase: NonZeroU32,
+}
+
+/// The keepalive intervals learned so far, by network.
+///
+/// This is a shared handle; clones refer to the same record, so the interval
+/// learned by one connection is used as the starting point for the next
+/// connection on the same network.
+#[derive(Clone, Debug, Default)]
+pub struct KeepaliveIntervals(Arc<Mutex<HashMap<Arc<str>, Duration>>>);
+
+impl KeepaliveIntervals {
+    /// Returns the interval learned for `network`, if any.
+    pub fn get(&self, network: &str) -> Option<Duration> {
+        self.0.lock().expect("not poisoned").get(network).copied()
+    }
+
+    /// Records `interval` as the one to use for `network`.
+    ///
+    /// This can be used to restore intervals saved from a previous run.
+    pub fn insert(&self, network: impl Into<Arc<str>>, interval: Duration) {
+        self.0
+            .lock()
+            .expect("not poisoned")
+            .insert(network.into(), interval);
+    }
+}
+
+/// Everything a connection needs to adapt its keepalive interval.
+#[derive(Clone, Debug)]
+pub struct AdaptiveKeepalive {
+    pub config: AdaptiveKeepaliveConfig,
+    pub intervals: KeepaliveIntervals,
+    /// Identifies the network the connection is made over.
+    ///
+    /// The value is opaque; it only needs to be the same whenever the device
+    /// is on the same network.
+    pub network: Arc<str>,
+}
+
+/// Per-connection state for [`AdaptiveKeepalive`].
+#[derive(Debug)]
+pub(super) struct KeepaliveTuner {
+    keepalive: AdaptiveKeepalive,
+    interval: Duration,
+    consecutive_successes: u32,
+    awaiting_response: bool,
+}
+
+impl KeepaliveTuner {
+    pub(super) fn new(keepalive: AdaptiveKeepalive) -> Self {
+        let AdaptiveKeepaliveConfig {
+            initial_interval,
+            min_interval,
+            max_interval,
+            step: _,
+            successes_before_increase: _,
+        } = keepalive.config;
+        let interval = keepalive
+            .intervals
+            .get(&keepalive.network)
+            .unwrap_or(initial_interval)
+            .clamp(min_interval, max_interval.max(min_interval));
+        Self {
+            keepalive,
+            interval,
+            consecutive_successes: 0,
+            awaiting_response: false,
+        }
+    }
+
+    /// How long the connection can be quiet before a ping is sent.
+    pub(super) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub(super) fn on_ping_sent(&mut self) {
+        self.awaiting_response = true;
+    }
+
+    /// Called whenever anything is received from the server.
+    pub(super) fn on_heard_from_server(&mut self) {
+        if !std::mem::take(&mut self.awaiting_response) {
+            return;
+        }
+        let AdaptiveKeepaliveConfig {
+            max_interval,
+            step,
+            successes_before_increase,
+            ..
+        } = self.keepalive.config;
+
+        self.consecutive_successes += 1;
+        if self.consecutive_successes < successes_before_increase.get() {
+            return;
+        }
+        self.consecutive_successes = 0;
+        let longer = (self.interval + step).min(max_interval);
+        if longer > self.interval {
+            self.set_interval(longer);
+        }
+    }
+
+    /// Called when a ping went unanswered for long enough that it was probably
+    /// lost.
+    pub(super) fn on_missed_response(&mut self) {
+        if !std::mem::take(&mut self.awaiting_response) {
+            return;
+        }
+        let AdaptiveKeepaliveConfig {
+            min_interval, step, ..
+        } = self.keepalive.config;
+
+        self.consecutive_successes = 0;
+        let shorter = self.interval.saturating_sub(step).max(min_interval);
+        self.set_interval(shorter);
+    }
+
+    fn set_interval(&mut self, interval: Duration) {
+        let AdaptiveKeepalive {
+            intervals, network, ..
+        } = &self.keepalive;
+        self.interval = interval;
+        intervals.insert(network.clone(), interval);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nonzero_ext::nonzero;
+
+    use super::*;
+
+    const CONFIG: AdaptiveKeepaliveConfig = AdaptiveKeepaliveConfig {
+        initial_interval: Duration::from_secs(30),
+        min_interval: Duration::from_secs(20),
+        max_interval: Duration::from_secs(50),
+        step: Duration::from_secs(10),
+        successes_before_increase: nonzero!(2u32),
+    };
+
+    fn keepalive(intervals: &KeepaliveIntervals, network: &str) -> AdaptiveKeepalive {
+        AdaptiveKeepalive {
+            config: CONFIG,
+            intervals: intervals.clone(),
+            network: network.into(),
+        }
+    }
+
+    fn answered_ping(tuner: &mut KeepaliveTuner) {
+        tuner.on_ping_sent();
+        tuner.on_heard_from_server();
+    }
+
+    #[test]
+    fn lengthens_after_consecutive_successes_up_to_max() {
+        let intervals = KeepaliveIntervals::default();
+        let mut tuner = KeepaliveTuner::new(keepalive(&intervals, "wifi"));
+        assert_eq!(tuner.interval(), Duration::from_secs(30));
+
+        answered_ping(&mut tuner);
+        assert_eq!(tuner.interval(), Duration::from_secs(30));
+        answered_ping(&mut tuner);
+        assert_eq!(tuner.interval(), Duration::from_secs(40));
+
+        for _ in 0..10 {
+            answered_ping(&mut tuner);
+        }
+        assert_eq!(tuner.interval(), Duration::from_secs(50));
+        assert_eq!(intervals.get("wifi"), Some(Duration::from_secs(50)));
+    }
+
+    #[test]
+    fn traffic_without_a_ping_is_not_a_success() {
+        let intervals = KeepaliveIntervals::default();
+        let mut tuner = KeepaliveTuner::new(keepalive(&intervals, "wifi"));
+
+        for _ in 0..5 {
+            tuner.on_heard_from_server();
+        }
+        assert_eq!(tuner.interval(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn shortens_after_miss_down_to_min() {
+        let intervals = KeepaliveIntervals::default();
+        let mut tuner = KeepaliveTuner::new(keepalive(&intervals, "cell"));
+
+        // A success followed by a miss starts the count over.
+        answered_ping(&mut tuner);
+        tuner.on_ping_sent();
+        tuner.on_missed_response();
+        assert_eq!(tuner.interval(), Duration::from_secs(20));
+        answered_ping(&mut tuner);
+        assert_eq!(tuner.interval(), Duration::from_secs(20));
+
+        tuner.on_ping_sent();
+        tuner.on_missed_response();
+        assert_eq!(tuner.interval(), Duration::from_secs(20));
+        assert_eq!(intervals.get("cell"), Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn learned_interval_is_per_network() {
+        let intervals = KeepaliveIntervals::default();
+        intervals.insert("wifi", Duration::from_secs(45));
+
+        assert_eq!(
+            KeepaliveTuner::new(keepalive(&intervals, "wifi")).interval(),
+            Duration::from_secs(45)
+        );
+        assert_eq!(
+            KeepaliveTuner::new(keepalive(&intervals, "cell")).interval(),
+            Duration::from_secs(30)
+        );
+    }
+}
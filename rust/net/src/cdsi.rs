@@ -3,8 +3,13 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::HashSet;
 use std::default::Default;
+use std::future::Future;
+use std::num::NonZeroUsize;
 
+use futures_util::TryStreamExt as _;
+use itertools::Itertools as _;
 use libsignal_core::{Aci, Pni, E164};
 use libsignal_net_infra::errors::{LogSafeDisplay, RetryLater, TransportConnectError};
 use libsignal_net_infra::route::{RouteProvider, UnresolvedWebsocketServiceRoute};
@@ -23,6 +28,9 @@ use crate::connect_state::{ConnectionResources, WebSocketTransportConnectorFacto
 use crate::enclave::{Cdsi, EndpointParams};
 use crate::proto::cds2::{ClientRequest, ClientResponse};
 
+mod cache;
+pub use cache::{lookup_with_cache, CdsiCache, InMemoryCdsiCache};
+
 trait FixedLengthSerializable {
     const SERIALIZED_LEN: usize;
 
@@ -61,7 +69,7 @@ impl FixedLengthSerializable for Uuid {
     }
 }
 
-#[cfg_attr(test, derive(Clone))]
+#[derive(Clone)]
 pub struct AciAndAccessKey {
     pub aci: Aci,
     pub access_key: [u8; 16],
@@ -83,15 +91,153 @@ impl FixedLengthSerializable for AciAndAccessKey {
 pub struct LookupRequest {
     pub new_e164s: Vec<E164>,
     pub prev_e164s: Vec<E164>,
+    /// Numbers from `prev_e164s` that should not be covered by the token issued for this lookup.
+    pub discard_e164s: Vec<E164>,
     pub acis_and_access_keys: Vec<AciAndAccessKey>,
     pub token: Box<[u8]>,
 }
 
 impl LookupRequest {
+    /// Builds a request to look up `current`, given the numbers from the previous lookup that
+    /// issued `token`.
+    ///
+    /// The server only discounts `prev_e164s` if they match the numbers the token was issued
+    /// for, so all of `previous` is sent as `prev_e164s`. Numbers in `current` but not `previous`
+    /// are sent as new, and numbers no longer in `current` are discarded so that they aren't
+    /// covered by the next token.
+    pub fn delta(
+        previous: impl IntoIterator<Item = E164>,
+        current: impl IntoIterator<Item = E164>,
+        token: Token,
+        acis_and_access_keys: Vec<AciAndAccessKey>,
+    ) -> Self {
+        let mut previous_set = HashSet::new();
+        let prev_e164s = previous
+            .into_iter()
+            .filter(|e164| previous_set.insert(*e164))
+            .collect::<Vec<_>>();
+
+        let mut current_set = HashSet::new();
+        let new_e164s = current
+            .into_iter()
+            .filter(|e164| current_set.insert(*e164) && !previous_set.contains(e164))
+            .collect();
+        let discard_e164s = prev_e164s
+            .iter()
+            .filter(|e164| !current_set.contains(*e164))
+            .copied()
+            .collect();
+
+        let Token(token) = token;
+        Self {
+            new_e164s,
+            prev_e164s,
+            discard_e164s,
+            acis_and_access_keys,
+            token,
+        }
+    }
+
+    /// Splits the request so that none of the resulting requests has more than `max_e164s`
+    /// numbers.
+    ///
+    /// A request that's already small enough is returned unchanged. Otherwise, each part of the
+    /// token is replayed in its own request along with exactly the numbers it was issued for,
+    /// since the server won't discount `prev_e164s` that don't match. A plain token is treated as
+    /// a single part, and a [`ChunkedToken`] from an earlier split lookup has one part per
+    /// request. Numbers no longer wanted are discarded from their part, and the remaining
+    /// numbers are looked up as new, first filling the room left in those requests and then in
+    /// requests of their own. The ACIs and access keys are sent with every request, since the
+    /// server only returns an ACI for a number if its access key is in the same request.
+    fn into_chunks(self, max_e164s: NonZeroUsize) -> Vec<Self> {
+        let Self {
+            new_e164s,
+            prev_e164s,
+            discard_e164s,
+            acis_and_access_keys,
+            token,
+        } = self;
+
+        let chunked_token = ChunkedToken::parse(&token);
+        if chunked_token.is_none() && new_e164s.len() + prev_e164s.len() <= max_e164s.get() {
+            return vec![Self {
+                new_e164s,
+                prev_e164s,
+                discard_e164s,
+                acis_and_access_keys,
+                token,
+            }];
+        }
+
+        let token_parts = match chunked_token {
+            Some(ChunkedToken(parts)) => parts,
+            None if !token.is_empty() => vec![(token, prev_e164s.clone())],
+            None => vec![],
+        };
+
+        let discarded = HashSet::<E164>::from_iter(discard_e164s);
+        let mut wanted = HashSet::new();
+        let wanted_e164s = prev_e164s
+            .into_iter()
+            .filter(|e164| !discarded.contains(e164))
+            .chain(new_e164s)
+            .filter(|e164| wanted.insert(*e164))
+            .collect_vec();
+
+        let mut covered = HashSet::new();
+        let mut chunks = token_parts
+            .into_iter()
+            .filter(|(_, part_e164s)| part_e164s.len() <= max_e164s.get())
+            .map(|(token, part_e164s)| {
+                covered.extend(part_e164s.iter().copied());
+                Self {
+                    new_e164s: Vec::new(),
+                    discard_e164s: part_e164s
+                        .iter()
+                        .filter(|e164| !wanted.contains(*e164))
+                        .copied()
+                        .collect(),
+                    prev_e164s: part_e164s,
+                    acis_and_access_keys: acis_and_access_keys.clone(),
+                    token,
+                }
+            })
+            .collect_vec();
+
+        let mut uncovered = wanted_e164s
+            .into_iter()
+            .filter(|e164| !covered.contains(e164));
+        for chunk in &mut chunks {
+            let room = max_e164s.get() - chunk.prev_e164s.len();
+            chunk.new_e164s.extend(uncovered.by_ref().take(room));
+        }
+        let uncovered = uncovered.collect_vec();
+        chunks.extend(uncovered.chunks(max_e164s.get()).map(|chunk| Self {
+            new_e164s: chunk.to_vec(),
+            prev_e164s: Vec::new(),
+            discard_e164s: Vec::new(),
+            acis_and_access_keys: acis_and_access_keys.clone(),
+            token: Default::default(),
+        }));
+        chunks
+    }
+
+    /// The numbers that the token issued for this request will cover.
+    fn token_e164s(&self) -> Vec<E164> {
+        let discarded = HashSet::<&E164>::from_iter(&self.discard_e164s);
+        self.prev_e164s
+            .iter()
+            .filter(|e164| !discarded.contains(e164))
+            .chain(&self.new_e164s)
+            .copied()
+            .collect()
+    }
+
     fn into_client_request(self) -> ClientRequest {
         let Self {
             new_e164s,
             prev_e164s,
+            discard_e164s,
             acis_and_access_keys,
             token,
         } = self;
@@ -99,6 +245,7 @@ impl LookupRequest {
         let aci_uak_pairs = acis_and_access_keys.into_iter().collect_serialized();
         let new_e164s = new_e164s.into_iter().collect_serialized();
         let prev_e164s = prev_e164s.into_iter().collect_serialized();
+        let discard_e164s = discard_e164s.into_iter().collect_serialized();
 
         ClientRequest {
             aci_uak_pairs,
@@ -106,8 +253,7 @@ impl LookupRequest {
             prev_e164s,
             token: token.into_vec(),
             token_ack: false,
-            // TODO: use these for supporting non-desktop client requirements.
-            discard_e164s: Vec::new(),
+            discard_e164s,
         }
     }
 }
@@ -116,6 +262,64 @@ impl LookupRequest {
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Token(pub Box<[u8]>);
 
+/// The tokens from a lookup that [`lookup_in_chunks`] split into several requests, each with the
+/// numbers it covers.
+///
+/// This is encoded into a single [`Token`] so that it can be saved and passed back in the same
+/// way, and is told apart from the server's tokens by [`ChunkedToken::PREFIX`]. The encoding is
+/// the prefix followed by, for each part, the token and then the numbers, each preceded by its
+/// big-endian `u32` length (in bytes for the token, and in numbers for the numbers).
+#[derive(Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+struct ChunkedToken(Vec<(Box<[u8]>, Vec<E164>)>);
+
+impl ChunkedToken {
+    const PREFIX: &'static [u8] = b"\0libsignal chunked CDSI token\0";
+
+    fn parse(token: &[u8]) -> Option<Self> {
+        fn take_len(bytes: &mut &[u8]) -> Option<usize> {
+            let (len, rest) = bytes.split_first_chunk::<4>()?;
+            *bytes = rest;
+            usize::try_from(u32::from_be_bytes(*len)).ok()
+        }
+
+        let mut bytes = token.strip_prefix(Self::PREFIX)?;
+        let mut parts = Vec::new();
+        while !bytes.is_empty() {
+            let token_len = take_len(&mut bytes)?;
+            let (token, rest) = bytes.split_at_checked(token_len)?;
+            bytes = rest;
+
+            let e164_count = take_len(&mut bytes)?;
+            let (e164s, rest) =
+                bytes.split_at_checked(e164_count.checked_mul(E164::SERIALIZED_LEN)?)?;
+            bytes = rest;
+            let e164s = e164s
+                .chunks_exact(E164::SERIALIZED_LEN)
+                .map(|e164| E164::from_be_bytes(e164.try_into().expect("correct length")))
+                .collect::<Option<Vec<_>>>()?;
+
+            parts.push((token.into(), e164s));
+        }
+        Some(Self(parts))
+    }
+
+    fn serialize(&self) -> Box<[u8]> {
+        let Self(parts) = self;
+        let mut bytes = Self::PREFIX.to_vec();
+        for (token, e164s) in parts {
+            let token_len = u32::try_from(token.len()).expect("tokens are short");
+            bytes.extend_from_slice(&token_len.to_be_bytes());
+            bytes.extend_from_slice(token);
+
+            let e164_count = u32::try_from(e164s.len()).expect("requests are limited in size");
+            bytes.extend_from_slice(&e164_count.to_be_bytes());
+            bytes.extend(e164s.iter().copied().collect_serialized());
+        }
+        bytes.into_boxed_slice()
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct LookupResponse {
@@ -233,8 +437,8 @@ pub enum LookupError {
     AttestationError(attest::enclave::Error),
     /// invalid response received from the server
     InvalidResponse,
-    /// retry later
-    RateLimited(#[from] RetryLater),
+    /// {0}
+    RateLimited(#[from] CdsiRateLimit),
     /// request token was invalid
     InvalidToken,
     /// failed to parse the response from the server
@@ -255,6 +459,40 @@ pub enum LookupError {
     CdsiProtocol(CdsiProtocolError),
 }
 
+/// Details of a rate-limited CDSI request.
+#[derive(Copy, Clone, Debug, Error, displaydoc::Display)]
+/// rate limited; {retry_later}
+pub struct CdsiRateLimit {
+    pub retry_later: RetryLater,
+    /// The number of permits the rejected request would have used, if reported by the server.
+    pub permits_cost: Option<u32>,
+    /// The number of permits still available to the account, if reported by the server.
+    pub remaining_quota: Option<u32>,
+}
+
+impl CdsiRateLimit {
+    /// The amount of time to wait before retrying.
+    pub fn retry_after(&self) -> std::time::Duration {
+        self.retry_later.duration()
+    }
+}
+
+impl From<RetryLater> for CdsiRateLimit {
+    fn from(retry_later: RetryLater) -> Self {
+        Self {
+            retry_later,
+            permits_cost: None,
+            remaining_quota: None,
+        }
+    }
+}
+
+impl From<RetryLater> for LookupError {
+    fn from(value: RetryLater) -> Self {
+        Self::RateLimited(value.into())
+    }
+}
+
 #[derive(Debug, Error, displaydoc::Display)]
 pub enum CdsiProtocolError {
     /// no token found in response
@@ -279,7 +517,7 @@ impl From<crate::enclave::Error> for LookupError {
                 WebSocketConnectError::Transport(e) => Self::ConnectTransport(e),
                 WebSocketConnectError::WebSocketError(e) => Self::WebSocket(e),
             },
-            Error::RateLimited(inner) => Self::RateLimited(inner),
+            Error::RateLimited(inner) => inner.into(),
             Error::AttestationError(err) => Self::AttestationError(err),
             Error::WebSocket(err) => Self::WebSocket(err),
             Error::Protocol(error) => Self::EnclaveProtocol(error),
@@ -298,6 +536,10 @@ impl From<prost::DecodeError> for LookupError {
 struct RateLimitExceededResponse {
     #[serde(rename = "retry_after")]
     retry_after_seconds: u32,
+    #[serde(default)]
+    permits_cost: Option<u32>,
+    #[serde(default)]
+    remaining_quota: Option<u32>,
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -341,6 +583,139 @@ impl CdsiConnection {
             ClientResponseCollector(self),
         ))
     }
+
+    /// Performs a complete lookup, using `store` to manage the request token.
+    ///
+    /// If `request` doesn't already have a token, the one saved for `mr_enclave` is used. The
+    /// newly issued token is saved only once the lookup has completed successfully, and the saved
+    /// token is invalidated if the server rejects it.
+    pub async fn lookup_with_token_store(
+        self,
+        request: LookupRequest,
+        mr_enclave: &[u8],
+        store: &(impl CdsiTokenStore + ?Sized),
+    ) -> Result<LookupResponse, LookupError> {
+        lookup_with_saved_token(request, mr_enclave, store, |request| async move {
+            let (token, collector) = self.send_request(request).await?;
+            let response = collector.collect().await?;
+            Ok((Some(token), response))
+        })
+        .await
+    }
+}
+
+/// Performs a lookup, splitting it into several sequential requests if it has more than
+/// `max_e164s_per_request` numbers.
+///
+/// `connect` is called to establish a new connection for each request. The responses are
+/// combined into a single [`LookupResponse`].
+///
+/// If the request was split, the returned token combines the tokens for each part, so that
+/// passing it to a later call replays each of them along with the numbers it covers. It's
+/// `None` only if there was nothing to look up.
+pub async fn lookup_in_chunks<Fut>(
+    request: LookupRequest,
+    max_e164s_per_request: NonZeroUsize,
+    mut connect: impl FnMut() -> Fut,
+) -> Result<(Option<Token>, LookupResponse), LookupError>
+where
+    Fut: Future<Output = Result<CdsiConnection, LookupError>>,
+{
+    let chunks = request.into_chunks(max_e164s_per_request);
+    let chunk_count = chunks.len();
+    if chunk_count > 1 {
+        log::info!("splitting CDSI lookup into {chunk_count} requests");
+    }
+
+    let mut token_parts = Vec::with_capacity(chunk_count);
+    let mut combined = LookupResponse {
+        records: Vec::new(),
+        debug_permits_used: 0,
+    };
+    for chunk in chunks {
+        let token_e164s = chunk.token_e164s();
+        let (Token(token), collector) = connect().await?.send_request(chunk).await?;
+        let LookupResponse {
+            records,
+            debug_permits_used,
+        } = collector.collect().await?;
+        combined.records.extend(records);
+        combined.debug_permits_used += debug_permits_used;
+        token_parts.push((token, token_e164s));
+    }
+
+    let token = match <[_; 1]>::try_from(token_parts) {
+        Ok([(token, _)]) => Some(Token(token)),
+        Err(token_parts) if token_parts.is_empty() => None,
+        Err(token_parts) => Some(Token(ChunkedToken(token_parts).serialize())),
+    };
+    Ok((token, combined))
+}
+
+/// Like [`lookup_in_chunks`], but uses `store` to manage the token as in
+/// [`CdsiConnection::lookup_with_token_store`].
+pub async fn lookup_in_chunks_with_token_store<Fut>(
+    request: LookupRequest,
+    max_e164s_per_request: NonZeroUsize,
+    mr_enclave: &[u8],
+    store: &(impl CdsiTokenStore + ?Sized),
+    connect: impl FnMut() -> Fut,
+) -> Result<LookupResponse, LookupError>
+where
+    Fut: Future<Output = Result<CdsiConnection, LookupError>>,
+{
+    lookup_with_saved_token(request, mr_enclave, store, |request| {
+        lookup_in_chunks(request, max_e164s_per_request, connect)
+    })
+    .await
+}
+
+async fn lookup_with_saved_token<Fut>(
+    mut request: LookupRequest,
+    mr_enclave: &[u8],
+    store: &(impl CdsiTokenStore + ?Sized),
+    lookup: impl FnOnce(LookupRequest) -> Fut,
+) -> Result<LookupResponse, LookupError>
+where
+    Fut: Future<Output = Result<(Option<Token>, LookupResponse), LookupError>>,
+{
+    if request.token.is_empty() {
+        if let Some(Token(saved)) = store.load(mr_enclave) {
+            log::info!("using saved CDSI token");
+            request.token = saved;
+        }
+    }
+
+    match lookup(request).await {
+        Ok((token, response)) => {
+            if let Some(token) = token {
+                store.save(mr_enclave, &token);
+            }
+            Ok(response)
+        }
+        Err(LookupError::InvalidToken) => {
+            log::info!("discarding rejected CDSI token");
+            store.invalidate(mr_enclave);
+            Err(LookupError::InvalidToken)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Persistent storage for the tokens returned by CDSI lookups.
+///
+/// Replaying the token from a previous lookup lets the server charge only for new entries in the
+/// request. Tokens are only meaningful to the enclave that issued them, so they're keyed by the
+/// enclave's MRENCLAVE.
+///
+/// See [`CdsiConnection::lookup_with_token_store`].
+pub trait CdsiTokenStore {
+    /// Returns the token saved for `mr_enclave`, if any.
+    fn load(&self, mr_enclave: &[u8]) -> Option<Token>;
+    /// Saves `token` for `mr_enclave`, replacing any previously saved token.
+    fn save(&self, mr_enclave: &[u8], token: &Token);
+    /// Removes any token saved for `mr_enclave`.
+    fn invalidate(&self, mr_enclave: &[u8]);
 }
 
 impl ClientResponseCollector {
@@ -379,10 +754,134 @@ impl ClientResponseCollector {
     }
 }
 
+/// An incremental result from [`ClientResponseCollector::into_stream`].
+#[derive(Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum LookupStreamItem {
+    /// A single matched entry.
+    Entry(LookupResponseEntry),
+    /// A replacement for the token returned by [`CdsiConnection::send_request`].
+    ///
+    /// If the lookup completes successfully, the most recent token should be used for the next
+    /// lookup.
+    TokenUpdated(Token),
+}
+
+/// State carried between messages by [`ClientResponseCollector::into_stream`].
+struct ResponseStreamState {
+    connection: CdsiConnection,
+    token_acked: bool,
+    /// The bytes of a record that was split across response messages.
+    partial_record: Vec<u8>,
+}
+
+impl ClientResponseCollector {
+    /// Like [`collect`](Self::collect), but produces entries as each response message arrives
+    /// instead of buffering the whole response.
+    ///
+    /// The stream ends after the server closes the connection normally, or after the first
+    /// error.
+    pub fn into_stream(
+        self,
+    ) -> impl futures_util::Stream<Item = Result<LookupStreamItem, LookupError>> + Send {
+        let Self(connection) = self;
+        let state = ResponseStreamState {
+            connection,
+            token_acked: false,
+            partial_record: Vec::new(),
+        };
+
+        futures_util::stream::try_unfold(state, |mut state| async move {
+            let items = state.next_items().await?;
+            Ok(items.map(|items| {
+                (
+                    futures_util::stream::iter(items.into_iter().map(Ok::<_, LookupError>)),
+                    state,
+                )
+            }))
+        })
+        .try_flatten()
+    }
+}
+
+impl ResponseStreamState {
+    /// Receives the next response message and splits it into stream items.
+    ///
+    /// Returns `None` once the server has finished sending responses.
+    async fn next_items(&mut self) -> Result<Option<Vec<LookupStreamItem>>, LookupError> {
+        let Self {
+            connection,
+            token_acked,
+            partial_record,
+        } = self;
+
+        let response: ClientResponse = if !*token_acked {
+            connection
+                .0
+                .send(ClientRequest {
+                    token_ack: true,
+                    ..Default::default()
+                })
+                .await?;
+            *token_acked = true;
+            // As in `collect`, the server must send at least one message after the ack.
+            connection.0.receive().await?.next_or_else(err_for_close)?
+        } else {
+            match connection.0.receive_bytes().await? {
+                NextOrClose::Next(decoded) => ClientResponse::decode(decoded.as_ref())?,
+                NextOrClose::Close(
+                    None
+                    | Some(CloseFrame {
+                        code: CloseCode::Normal,
+                        reason: _,
+                    }),
+                ) => {
+                    if !partial_record.is_empty() {
+                        return Err(LookupResponseParseError::InvalidNumberOfBytes {
+                            actual_length: partial_record.len(),
+                        }
+                        .into());
+                    }
+                    log::info!("finished CDSI lookup");
+                    return Ok(None);
+                }
+                NextOrClose::Close(Some(close)) => return Err(err_for_close(Some(close))),
+            }
+        };
+
+        let ClientResponse {
+            e164_pni_aci_triples,
+            token,
+            debug_permits_used: _,
+        } = response;
+
+        partial_record.extend_from_slice(&e164_pni_aci_triples);
+        let complete_len =
+            partial_record.len() - partial_record.len() % LookupResponseEntry::SERIALIZED_LEN;
+
+        let token_update =
+            (!token.is_empty()).then(|| LookupStreamItem::TokenUpdated(Token(token.into())));
+        let items = partial_record
+            .drain(..complete_len)
+            .as_slice()
+            .chunks(LookupResponseEntry::SERIALIZED_LEN)
+            .flat_map(|record| {
+                LookupResponseEntry::try_parse_from(
+                    record.try_into().expect("chunk size is correct"),
+                )
+            })
+            .map(LookupStreamItem::Entry)
+            .chain(token_update)
+            .collect();
+        Ok(Some(items))
+    }
+}
+
 /// For logging information about an initiated CDSI request.
 struct LookupRequestDebugInfo {
     new_e164s: usize,
     prev_e164s: usize,
+    discard_e164s: usize,
     acis_and_access_keys: usize,
     token: usize,
 }
@@ -392,6 +891,7 @@ impl std::fmt::Display for LookupRequestDebugInfo {
         f.debug_struct("LookupRequestDebugInfo")
             .field("new_e164s", &self.new_e164s)
             .field("prev_e164s", &self.prev_e164s)
+            .field("discard_e164s", &self.discard_e164s)
             .field("acis_and_access_keys", &self.acis_and_access_keys)
             .field("token", &self.token)
             .finish()
@@ -405,12 +905,14 @@ impl From<&LookupRequest> for LookupRequestDebugInfo {
         let LookupRequest {
             new_e164s,
             prev_e164s,
+            discard_e164s,
             acis_and_access_keys,
             token,
         } = value;
         Self {
             new_e164s: new_e164s.len(),
             prev_e164s: prev_e164s.len(),
+            discard_e164s: discard_e164s.len(),
             acis_and_access_keys: acis_and_access_keys.len(),
             token: token.len(),
         }
@@ -456,13 +958,19 @@ fn err_for_close(close: Option<CloseFrame>) -> LookupError {
         CdsiCloseCode::RateLimitExceeded => {
             let Some(RateLimitExceededResponse {
                 retry_after_seconds,
+                permits_cost,
+                remaining_quota,
             }) = serde_json::from_str(reason).ok()
             else {
                 log::warn!("failed to parse rate limit from reason");
                 return unexpected_close(close);
             };
-            LookupError::RateLimited(RetryLater {
-                retry_after_seconds,
+            LookupError::RateLimited(CdsiRateLimit {
+                retry_later: RetryLater {
+                    retry_after_seconds,
+                },
+                permits_cost,
+                remaining_quota,
             })
         }
         CdsiCloseCode::ServerInternalError | CdsiCloseCode::ServerUnavailable => {
@@ -679,6 +1187,9 @@ mod test {
         local_idle_timeout: Duration::from_secs(5),
         remote_idle_ping_timeout: Duration::from_secs(100),
         remote_idle_disconnect_timeout: Duration::from_secs(100),
+        max_frame_size: None,
+        max_message_size: None,
+        application_idle_timeout: None,
     };
 
     #[tokio::test]
@@ -727,6 +1238,45 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn lookup_success_streamed() {
+        let (server, client) = fake_websocket().await;
+
+        let fake_server = FakeServerState::default().into_handler();
+        tokio::spawn(run_attested_server(
+            server,
+            attest::sgx_session::testutil::private_key(),
+            fake_server,
+        ));
+
+        let cdsi_connection = CdsiConnection(
+            AttestedConnection::connect(client, FAKE_WS_CONFIG, "test".into(), |_| {
+                attest::sgx_session::testutil::handshake_from_tests_data()
+            })
+            .await
+            .expect("handshake failed"),
+        );
+
+        let (_token, collector) = cdsi_connection
+            .send_request(LookupRequest {
+                token: b"valid but ignored token".as_slice().into(),
+                ..Default::default()
+            })
+            .await
+            .expect("request accepted");
+
+        let items: Vec<_> = collector
+            .into_stream()
+            .try_collect()
+            .await
+            .expect("successful request");
+
+        assert_eq!(
+            items,
+            [LookupStreamItem::Entry(FakeServerState::RESPONSE_RECORD)]
+        );
+    }
+
     #[tokio::test]
     async fn large_request_split() {
         // Large requests should be split into multiple Noise packets, but those
@@ -800,6 +1350,7 @@ mod test {
             token: b"valid but ignored token".as_slice().into(),
             new_e164s: large_number_of_e164s.clone(),
             prev_e164s: large_number_of_e164s,
+            discard_e164s: vec![],
             acis_and_access_keys: (1..=LARGE_NUMBER_OF_ENTRIES)
                 .map(|i| {
                     let mut bytes = [0; 16];
@@ -874,8 +1425,12 @@ mod test {
 
         assert_matches!(
             response,
-            Err(LookupError::RateLimited(RetryLater {
-                retry_after_seconds: 12345
+            Err(LookupError::RateLimited(CdsiRateLimit {
+                retry_later: RetryLater {
+                    retry_after_seconds: 12345
+                },
+                permits_cost: None,
+                remaining_quota: None,
             }))
         );
     }
@@ -888,7 +1443,7 @@ mod test {
             &FakeServerState::AwaitingTokenAck,
             CloseFrame {
                 code: CloseCode::Bad(4008),
-                reason: r#"{"retry_after": 513}"#.into(),
+                reason: r#"{"retry_after": 513, "permits_cost": 20, "remaining_quota": 5}"#.into(),
             },
         );
 
@@ -924,8 +1479,12 @@ mod test {
 
         assert_matches!(
             response,
-            Err(LookupError::RateLimited(RetryLater {
-                retry_after_seconds: 513
+            Err(LookupError::RateLimited(CdsiRateLimit {
+                retry_later: RetryLater {
+                    retry_after_seconds: 513
+                },
+                permits_cost: Some(20),
+                remaining_quota: Some(5),
             }))
         )
     }
@@ -987,8 +1546,12 @@ mod test {
 
         assert_matches!(
             result,
-            Err(LookupError::RateLimited(RetryLater {
-                retry_after_seconds: 100
+            Err(LookupError::RateLimited(CdsiRateLimit {
+                retry_later: RetryLater {
+                    retry_after_seconds: 100
+                },
+                permits_cost: None,
+                remaining_quota: None,
             }))
         )
     }
@@ -1035,4 +1598,260 @@ mod test {
 
         assert_matches!(response, Err(LookupError::InvalidToken));
     }
+
+    #[derive(Default)]
+    struct InMemoryTokenStore(std::sync::Mutex<HashMap<Vec<u8>, Box<[u8]>>>);
+
+    impl CdsiTokenStore for InMemoryTokenStore {
+        fn load(&self, mr_enclave: &[u8]) -> Option<Token> {
+            self.0.lock().unwrap().get(mr_enclave).cloned().map(Token)
+        }
+
+        fn save(&self, mr_enclave: &[u8], token: &Token) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(mr_enclave.to_vec(), token.0.clone());
+        }
+
+        fn invalidate(&self, mr_enclave: &[u8]) {
+            self.0.lock().unwrap().remove(mr_enclave);
+        }
+    }
+
+    async fn connect_to_fake_server(
+        handler: impl FnMut(NextOrClose<Vec<u8>>) -> AttestedServerOutput + Send + 'static,
+    ) -> CdsiConnection {
+        let (server, client) = fake_websocket().await;
+        tokio::spawn(run_attested_server(
+            server,
+            attest::sgx_session::testutil::private_key(),
+            handler,
+        ));
+
+        CdsiConnection(
+            AttestedConnection::connect(client, FAKE_WS_CONFIG, "test".into(), |_| {
+                attest::sgx_session::testutil::handshake_from_tests_data()
+            })
+            .await
+            .expect("handshake failed"),
+        )
+    }
+
+    const FAKE_MR_ENCLAVE: &[u8] = b"mrenclave";
+
+    #[tokio::test]
+    async fn token_store_saves_token_after_lookup() {
+        let store = InMemoryTokenStore::default();
+        store.save(FAKE_MR_ENCLAVE, &Token(b"old token".as_slice().into()));
+
+        let response = connect_to_fake_server(FakeServerState::default().into_handler())
+            .await
+            .lookup_with_token_store(LookupRequest::default(), FAKE_MR_ENCLAVE, &store)
+            .await
+            .expect("successful request");
+        assert_eq!(response.records, [FakeServerState::RESPONSE_RECORD]);
+
+        assert_eq!(
+            store.load(FAKE_MR_ENCLAVE),
+            Some(Token(FakeServerState::RESPONSE_TOKEN.into()))
+        );
+        assert_eq!(store.load(b"other enclave"), None);
+    }
+
+    #[tokio::test]
+    async fn token_store_invalidates_rejected_token() {
+        let store = InMemoryTokenStore::default();
+        store.save(FAKE_MR_ENCLAVE, &Token(b"invalid token".as_slice().into()));
+
+        let fake_server = FakeServerState::default().into_handler_with_close_from(
+            &FakeServerState::AwaitingLookupRequest,
+            CloseFrame {
+                code: CloseCode::Bad(4101),
+                reason: "invalid token".into(),
+            },
+        );
+        let response = connect_to_fake_server(fake_server)
+            .await
+            .lookup_with_token_store(LookupRequest::default(), FAKE_MR_ENCLAVE, &store)
+            .await;
+
+        assert_matches!(response, Err(LookupError::InvalidToken));
+        assert_eq!(store.load(FAKE_MR_ENCLAVE), None);
+    }
+
+    #[test]
+    fn small_request_is_not_split() {
+        let chunks = LookupRequest {
+            new_e164s: vec![E164::new(nonzero!(18005550101u64))],
+            prev_e164s: vec![E164::new(nonzero!(18005550102u64))],
+            token: b"token".as_slice().into(),
+            ..Default::default()
+        }
+        .into_chunks(nonzero!(2usize));
+
+        assert_matches!(
+            &*chunks,
+            [LookupRequest { new_e164s, prev_e164s, token, .. }]
+                if new_e164s.len() == 1 && prev_e164s.len() == 1 && &**token == b"token"
+        );
+    }
+
+    #[test]
+    fn large_request_is_split_around_token() {
+        let e164s = (1..=5)
+            .map(|n| E164::new(NonZeroU64::new(18005550100 + n).unwrap()))
+            .collect_vec();
+        let chunks = LookupRequest {
+            new_e164s: e164s[2..].to_vec(),
+            prev_e164s: e164s[..2].to_vec(),
+            discard_e164s: vec![],
+            acis_and_access_keys: vec![AciAndAccessKey {
+                aci: Aci::from_uuid_bytes([b'a'; 16]),
+                access_key: [b'k'; 16],
+            }],
+            token: b"token".as_slice().into(),
+        }
+        .into_chunks(nonzero!(2usize));
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (
+                    chunk.prev_e164s.clone(),
+                    chunk.new_e164s.clone(),
+                    &*chunk.token
+                ))
+                .collect_vec(),
+            [
+                (e164s[..2].to_vec(), vec![], b"token".as_slice()),
+                (vec![], e164s[2..4].to_vec(), b"".as_slice()),
+                (vec![], e164s[4..].to_vec(), b"".as_slice()),
+            ]
+        );
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.acis_and_access_keys.len())
+                .collect_vec(),
+            [1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn chunked_token_is_replayed_per_part() {
+        let [a, b, c, d, e] =
+            [1, 2, 3, 4, 5].map(|n| E164::new(NonZeroU64::new(18005550100 + n).unwrap()));
+        let token = ChunkedToken(vec![
+            (b"first".as_slice().into(), vec![a, b]),
+            (b"second".as_slice().into(), vec![c]),
+        ])
+        .serialize();
+
+        let chunks = LookupRequest::delta([a, b, c], [a, c, d, e], Token(token), vec![])
+            .into_chunks(nonzero!(2usize));
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (
+                    chunk.prev_e164s.clone(),
+                    chunk.discard_e164s.clone(),
+                    chunk.new_e164s.clone(),
+                    &*chunk.token
+                ))
+                .collect_vec(),
+            [
+                (vec![a, b], vec![b], vec![], b"first".as_slice()),
+                (vec![c], vec![], vec![d], b"second".as_slice()),
+                (vec![], vec![], vec![e], b"".as_slice()),
+            ]
+        );
+        assert_eq!(
+            chunks.iter().map(LookupRequest::token_e164s).collect_vec(),
+            [vec![a], vec![c, d], vec![e]]
+        );
+    }
+
+    #[test]
+    fn chunked_token_round_trip() {
+        let [a, b] = [1, 2].map(|n| E164::new(NonZeroU64::new(18005550100 + n).unwrap()));
+        let token = ChunkedToken(vec![
+            (b"first".as_slice().into(), vec![a, b]),
+            (b"".as_slice().into(), vec![]),
+        ]);
+        let serialized = token.serialize();
+        assert_eq!(ChunkedToken::parse(&serialized), Some(token));
+
+        assert_eq!(ChunkedToken::parse(b"server token"), None);
+        assert_eq!(
+            ChunkedToken::parse(&serialized[..serialized.len() - 1]),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn lookup_in_chunks_combines_responses() {
+        let request_e164s = (1..=3)
+            .map(|n| E164::new(NonZeroU64::new(18005550100 + n).unwrap()))
+            .collect_vec();
+        let request = LookupRequest {
+            new_e164s: request_e164s.clone(),
+            ..Default::default()
+        };
+
+        let mut connections = 0;
+        let (token, response) = lookup_in_chunks(request, nonzero!(2usize), || {
+            connections += 1;
+            async { Ok(connect_to_fake_server(FakeServerState::default().into_handler()).await) }
+        })
+        .await
+        .expect("successful request");
+
+        assert_eq!(connections, 2);
+        assert_eq!(
+            token,
+            Some(Token(
+                ChunkedToken(vec![
+                    (
+                        FakeServerState::RESPONSE_TOKEN.into(),
+                        request_e164s[..2].to_vec()
+                    ),
+                    (
+                        FakeServerState::RESPONSE_TOKEN.into(),
+                        request_e164s[2..].to_vec()
+                    ),
+                ])
+                .serialize()
+            ))
+        );
+        assert_eq!(
+            response,
+            LookupResponse {
+                records: vec![
+                    FakeServerState::RESPONSE_RECORD,
+                    FakeServerState::RESPONSE_RECORD
+                ],
+                debug_permits_used: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn delta_request() {
+        let [a, b, c, d] =
+            [1, 2, 3, 4].map(|n| E164::new(NonZeroU64::new(18005550100 + n).unwrap()));
+
+        let request = LookupRequest::delta(
+            [a, b, c, a],
+            [b, d, c, d],
+            Token(b"token".as_slice().into()),
+            vec![],
+        );
+
+        assert_eq!(request.prev_e164s, [a, b, c]);
+        assert_eq!(request.new_e164s, [d]);
+        assert_eq!(request.discard_e164s, [a]);
+        assert_eq!(&*request.token, b"token");
+    }
 }
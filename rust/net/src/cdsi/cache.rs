@@ -0,0 +1,296 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use libsignal_core::{Aci, E164};
+use tokio::time::Instant;
+
+use super::{LookupError, LookupRequest, LookupResponse, LookupResponseEntry, Token};
+
+/// Storage for recent CDSI results, so that numbers looked up again soon after don't count
+/// against the rate limit.
+///
+/// See [`lookup_with_cache`].
+pub trait CdsiCache {
+    /// Returns the cached entry for `e164`, if there is one that hasn't expired.
+    fn get(&self, e164: E164) -> Option<LookupResponseEntry>;
+    /// Caches `entry`, replacing any existing entry for the same number.
+    fn insert(&self, entry: LookupResponseEntry);
+    /// Removes any entries for `aci`.
+    ///
+    /// This should be called when a send to `aci` fails because the account no longer exists,
+    /// so that the next lookup goes to the server.
+    fn invalidate_aci(&self, aci: Aci);
+}
+
+/// A [`CdsiCache`] that keeps each entry in memory for a fixed amount of time.
+#[derive(Debug)]
+pub struct InMemoryCdsiCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<E164, (LookupResponseEntry, Instant)>>,
+}
+
+impl InMemoryCdsiCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+}
+
+impl CdsiCache for InMemoryCdsiCache {
+    fn get(&self, e164: E164) -> Option<LookupResponseEntry> {
+        let mut entries = self.entries.lock().expect("not poisoned");
+        let (entry, expiration) = entries.get(&e164)?;
+        if *expiration <= Instant::now() {
+            entries.remove(&e164);
+            return None;
+        }
+        Some(entry.clone())
+    }
+
+    fn insert(&self, entry: LookupResponseEntry) {
+        let expiration = Instant::now() + self.ttl;
+        self.entries
+            .lock()
+            .expect("not poisoned")
+            .insert(entry.e164, (entry, expiration));
+    }
+
+    fn invalidate_aci(&self, aci: Aci) {
+        self.entries
+            .lock()
+            .expect("not poisoned")
+            .retain(|_, (entry, _)| entry.aci != Some(aci));
+    }
+}
+
+/// Performs a lookup, answering from `cache` where possible.
+///
+/// The token the server issues only covers the numbers that were actually sent, so the cache is
+/// only consulted for requests without a token:
+///
+/// - If the request has a token, it is sent to `lookup` unchanged, and the returned token covers
+///   the full set of numbers as usual.
+/// - Otherwise, cached numbers are removed from the request's `new_e164s` before it's passed to
+///   `lookup`, and their entries are added to the response. If every number is cached, `lookup`
+///   isn't called at all. If any number was answered from the cache, the server's token doesn't
+///   cover the full set and is not returned.
+/// - Whether the server returns an ACI depends on the access keys sent with a request, so cached
+///   entries without an ACI are not used for requests that include access keys.
+///
+/// In both cases, entries from the server's response are added to the cache.
+pub async fn lookup_with_cache<Fut>(
+    mut request: LookupRequest,
+    cache: &(impl CdsiCache + ?Sized),
+    lookup: impl FnOnce(LookupRequest) -> Fut,
+) -> Result<(Option<Token>, LookupResponse), LookupError>
+where
+    Fut: Future<Output = Result<(Token, LookupResponse), LookupError>>,
+{
+    let mut cached = Vec::new();
+    if request.token.is_empty() {
+        let has_access_keys = !request.acis_and_access_keys.is_empty();
+        request.new_e164s.retain(|e164| match cache.get(*e164) {
+            // An entry without an ACI may only mean the earlier lookup didn't have the right
+            // access key, so ask the server again if this one might.
+            Some(entry) if entry.aci.is_none() && has_access_keys => true,
+            Some(entry) => {
+                cached.push(entry);
+                false
+            }
+            None => true,
+        });
+    }
+
+    if request.new_e164s.is_empty() && request.prev_e164s.is_empty() {
+        log::info!("all {} CDSI entries were cached", cached.len());
+        return Ok((
+            None,
+            LookupResponse {
+                records: cached,
+                debug_permits_used: 0,
+            },
+        ));
+    }
+    log::info!("{} CDSI entries were cached", cached.len());
+
+    let (token, mut response) = lookup(request).await?;
+    for entry in &response.records {
+        cache.insert(entry.clone());
+    }
+    let token = cached.is_empty().then_some(token);
+    response.records.extend(cached);
+    Ok((token, response))
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU64;
+
+    use futures_util::FutureExt as _;
+    use libsignal_core::Pni;
+
+    use super::*;
+    use crate::cdsi::AciAndAccessKey;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn entry(n: u64) -> LookupResponseEntry {
+        let byte = u8::try_from(n).expect("small test index");
+        LookupResponseEntry {
+            e164: E164::new(NonZeroU64::new(18005550100 + n).unwrap()),
+            aci: Some(Aci::from_uuid_bytes([byte; 16])),
+            pni: Some(Pni::from_uuid_bytes([byte; 16])),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn entries_expire() {
+        let cache = InMemoryCdsiCache::new(TTL);
+        cache.insert(entry(1));
+        assert_eq!(cache.get(entry(1).e164), Some(entry(1)));
+
+        tokio::time::advance(TTL).await;
+        assert_eq!(cache.get(entry(1).e164), None);
+    }
+
+    #[test]
+    fn invalidate_by_aci() {
+        let cache = InMemoryCdsiCache::new(TTL);
+        cache.insert(entry(1));
+        cache.insert(entry(2));
+
+        cache.invalidate_aci(entry(1).aci.unwrap());
+        assert_eq!(cache.get(entry(1).e164), None);
+        assert_eq!(cache.get(entry(2).e164), Some(entry(2)));
+    }
+
+    #[test]
+    fn lookup_only_sends_uncached_numbers() {
+        let cache = InMemoryCdsiCache::new(TTL);
+        cache.insert(entry(1));
+
+        let request = LookupRequest {
+            new_e164s: vec![entry(1).e164, entry(2).e164],
+            ..Default::default()
+        };
+        let (token, response) = lookup_with_cache(request, &cache, |request| async move {
+            assert_eq!(request.new_e164s, [entry(2).e164]);
+            Ok((
+                Token(b"token".as_slice().into()),
+                LookupResponse {
+                    records: vec![entry(2)],
+                    debug_permits_used: 1,
+                },
+            ))
+        })
+        .now_or_never()
+        .expect("sync")
+        .expect("success");
+
+        // The token only covers entry(2), so it isn't returned.
+        assert_eq!(token, None);
+        assert_eq!(response.records, [entry(2), entry(1)]);
+        assert_eq!(cache.get(entry(2).e164), Some(entry(2)));
+
+        // Now everything is cached, so there's no need to contact the server.
+        let request = LookupRequest {
+            new_e164s: vec![entry(1).e164, entry(2).e164],
+            ..Default::default()
+        };
+        let (token, response) = lookup_with_cache(request, &cache, |_| async {
+            unreachable!("all numbers are cached")
+        })
+        .now_or_never()
+        .expect("sync")
+        .expect("success");
+        assert_eq!(token, None);
+        assert_eq!(response.records, [entry(1), entry(2)]);
+    }
+
+    #[test]
+    fn cached_entry_without_aci_is_retried_with_access_keys() {
+        let cache = InMemoryCdsiCache::new(TTL);
+        let without_aci = LookupResponseEntry {
+            aci: None,
+            ..entry(1)
+        };
+        cache.insert(without_aci.clone());
+
+        // Without access keys, the cached entry is as good as a fresh one.
+        let request = LookupRequest {
+            new_e164s: vec![entry(1).e164],
+            ..Default::default()
+        };
+        let (_token, response) = lookup_with_cache(request, &cache, |_| async {
+            unreachable!("all numbers are cached")
+        })
+        .now_or_never()
+        .expect("sync")
+        .expect("success");
+        assert_eq!(response.records, [without_aci]);
+
+        // With access keys, the server might return the ACI this time.
+        let request = LookupRequest {
+            new_e164s: vec![entry(1).e164],
+            acis_and_access_keys: vec![AciAndAccessKey {
+                aci: entry(1).aci.unwrap(),
+                access_key: [1; 16],
+            }],
+            ..Default::default()
+        };
+        let (token, response) = lookup_with_cache(request, &cache, |request| async move {
+            assert_eq!(request.new_e164s, [entry(1).e164]);
+            Ok((
+                Token(b"token".as_slice().into()),
+                LookupResponse {
+                    records: vec![entry(1)],
+                    debug_permits_used: 1,
+                },
+            ))
+        })
+        .now_or_never()
+        .expect("sync")
+        .expect("success");
+        assert_eq!(token, Some(Token(b"token".as_slice().into())));
+        assert_eq!(response.records, [entry(1)]);
+        assert_eq!(cache.get(entry(1).e164), Some(entry(1)));
+    }
+
+    #[test]
+    fn lookup_with_token_bypasses_cache() {
+        let cache = InMemoryCdsiCache::new(TTL);
+        cache.insert(entry(1));
+
+        let request = LookupRequest {
+            new_e164s: vec![entry(1).e164, entry(2).e164],
+            token: b"previous".as_slice().into(),
+            ..Default::default()
+        };
+        let (token, response) = lookup_with_cache(request, &cache, |request| async move {
+            assert_eq!(request.new_e164s, [entry(1).e164, entry(2).e164]);
+            Ok((
+                Token(b"token".as_slice().into()),
+                LookupResponse {
+                    records: vec![entry(1), entry(2)],
+                    debug_permits_used: 1,
+                },
+            ))
+        })
+        .now_or_never()
+        .expect("sync")
+        .expect("success");
+
+        assert_eq!(token, Some(Token(b"token".as_slice().into())));
+        assert_eq!(response.records, [entry(1), entry(2)]);
+        assert_eq!(cache.get(entry(2).e164), Some(entry(2)));
+    }
+}
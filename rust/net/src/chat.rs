@@ -17,23 +17,40 @@ use libsignal_net_infra::route::{
     UnresolvedHttpsServiceRoute, UnresolvedWebsocketServiceRoute, UsePreconnect, WebSocketRoute,
     WebSocketRouteFragment,
 };
+use libsignal_net_infra::ws::keepalive::AdaptiveKeepalive;
 use libsignal_net_infra::ws::StreamWithResponseHeaders;
 use libsignal_net_infra::{AsHttpHeader, AsStaticHttpHeader, Connection, IpType, TransportInfo};
 use tokio_tungstenite::WebSocketStream;
+use tungstenite::protocol::frame::coding::CloseCode;
 
 use crate::auth::Auth;
 use crate::connect_state::{
     ConnectionResources, DefaultTransportConnector, RouteInfo, WebSocketTransportConnectorFactory,
 };
 use crate::env::UserAgent;
+use crate::events::{
+    record_to, ChatStateMonitor, ConnectionStateReporter, NetEvent, SharedEventSink,
+};
 use crate::proto;
+use crate::trace::{TraceId, Traced};
 
 mod error;
 pub use error::{ConnectError, SendError};
+mod in_flight;
+pub use in_flight::InFlightLimitConfig;
+use in_flight::InFlightLimiter;
+mod metadata;
+pub use metadata::ServerConnectionMetadata;
+mod rate_limit;
+pub use rate_limit::RateLimitConfig;
+use rate_limit::RateLimiter;
 
 pub mod fake;
 pub mod noise;
+pub mod receipts;
 pub mod server_requests;
+use server_requests::{ReceiveProgress, ReceiveProgressTracker};
+pub mod supervisor;
 pub mod ws;
 
 pub type MessageProto = proto::chat_websocket::WebSocketMessage;
@@ -62,6 +79,18 @@ pub struct Request {
     pub body: Option<Bytes>,
 }
 
+/// How urgently a [`Request`] should be sent.
+///
+/// High-priority requests skip ahead of normal-priority requests that are still waiting to be
+/// sent, but not of requests that have already been sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RequestPriority {
+    #[default]
+    Normal,
+    /// For latency-sensitive requests, like message sends and receipts.
+    High,
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Response {
@@ -153,11 +182,28 @@ impl LanguageList {
 pub struct ConnectionInfo {
     pub route_info: RouteInfo,
     pub transport_info: TransportInfo,
+    pub server_metadata: ServerConnectionMetadata,
 }
 
+/// An active connection to the chat service.
+///
+/// Each `ChatConnection` owns its own websocket, so a client with both an authenticated and an
+/// unauthenticated connection has two sockets. They can't currently share one: the chat server
+/// has no way to carry more than one logical session over a websocket, and because the
+/// websocket is established with an HTTP/1.1 upgrade, it takes over the whole TLS stream it
+/// runs on. Sharing would need server support, either for multiplexing at the websocket layer
+/// or for websockets over HTTP/2 (RFC 8441). Until then, the [`PreconnectingFactory`] is the
+/// main tool for cutting connection overhead.
+///
+/// [`PreconnectingFactory`]: crate::connect_state::PreconnectingFactory
 pub struct ChatConnection {
     inner: self::ws::Chat,
     connection_info: ConnectionInfo,
+    rate_limiter: Option<RateLimiter>,
+    in_flight_limiter: Option<InFlightLimiter>,
+    receive_progress: Arc<ReceiveProgressTracker>,
+    log_tag: Arc<str>,
+    event_sink: Option<SharedEventSink>,
 }
 
 type ChatTransportConnection =
@@ -173,6 +219,9 @@ pub struct PendingChatConnection<T = ChatTransportConnection> {
     ws_config: ws::Config,
     route_info: RouteInfo,
     log_tag: Arc<str>,
+    event_sink: Option<SharedEventSink>,
+    adaptive_keepalive: Option<AdaptiveKeepalive>,
+    state_reporter: Option<ConnectionStateReporter>,
 }
 
 #[cfg_attr(test, derive(Clone))]
@@ -256,11 +305,13 @@ impl ChatConnection {
             .into_iter()
             .flat_map(ChatHeaders::iter_headers)
             .chain([user_agent.as_header()]);
-        let ws_fragment = WebSocketRouteFragment {
+        let mut ws_fragment = WebSocketRouteFragment {
             ws_config: Default::default(),
             endpoint: PathAndQuery::from_static(crate::env::constants::WEB_SOCKET_PATH),
             headers: HeaderMap::from_iter(headers),
         };
+        ws_fragment.ws_config.max_frame_size = ws_config.max_frame_size;
+        ws_fragment.ws_config.max_message_size = ws_config.max_message_size;
 
         let ws_routes = http_route_provider.map_routes(move |http| WebSocketRoute {
             inner: HttpsTlsRoute {
@@ -274,6 +325,17 @@ impl ChatConnection {
         });
 
         let log_tag: Arc<str> = log_tag.into();
+        let (event_sink, adaptive_keepalive, state_reporter) = {
+            let connect_state = connection_resources
+                .connect_state
+                .lock()
+                .expect("not poisoned");
+            (
+                connect_state.event_sink.clone(),
+                connect_state.adaptive_keepalive.clone(),
+                connect_state.state_reporter(log_tag),
+            )
+        };
         let (connection, route_info) = connection_resources
             .connect_ws(
                 ws_routes,
@@ -301,6 +363,9 @@ impl ChatConnection {
             route_info,
             ws_config,
             log_tag,
+            event_sink,
+            adaptive_keepalive,
+            state_reporter,
         })
     }
 
@@ -315,37 +380,148 @@ impl ChatConnection {
             ws_config,
             route_info,
             log_tag,
+            event_sink,
+            adaptive_keepalive,
+            state_reporter,
         } = pending;
+        let receive_progress = Arc::new(ReceiveProgressTracker::new(
+            event_sink.clone(),
+            log_tag.clone(),
+        ));
+        let listener = receive_progress.track(listener);
+        let listener = match event_sink.clone() {
+            Some(sink) => crate::events::report_chat_finish(sink, log_tag.clone(), listener),
+            None => listener,
+        };
+        let hooks = ws::ConnectStateHooks {
+            adaptive_keepalive,
+            state_monitor: state_reporter
+                .map(|reporter| ChatStateMonitor::new(reporter, route_info.clone())),
+        };
         Self {
             connection_info: ConnectionInfo {
                 route_info,
                 transport_info: connection.transport_info(),
+                server_metadata: ServerConnectionMetadata::from_headers(&connect_response_headers),
             },
+            rate_limiter: ws_config.rate_limit.map(RateLimiter::new),
+            in_flight_limiter: ws_config.in_flight_limit.map(InFlightLimiter::new),
+            receive_progress,
             inner: ws::Chat::new(
                 tokio_runtime,
                 connection,
                 connect_response_headers,
                 ws_config,
-                log_tag,
+                hooks,
+                log_tag.clone(),
                 listener,
             ),
+            log_tag,
+            event_sink,
         }
     }
 
+    /// Sends a request and waits up to `timeout` for the response.
+    ///
+    /// If the connection was configured with a [`RateLimitConfig`] and sending
+    /// now would exceed it, the request is not sent and
+    /// [`SendError::WouldExceedRateLimit`] is returned instead. Similarly, if
+    /// the connection was configured with an [`InFlightLimitConfig`], the
+    /// request may wait for earlier requests to finish, or fail with
+    /// [`SendError::Busy`] if too many are already waiting. Time spent waiting
+    /// counts towards `timeout`.
     pub async fn send(&self, msg: Request, timeout: Duration) -> Result<Response, SendError> {
-        let send_result = tokio::time::timeout(timeout, self.inner.send(msg))
+        self.send_with_priority(msg, timeout, RequestPriority::Normal)
+            .await
+    }
+
+    /// Like [`Self::send`], but with an explicit [`RequestPriority`].
+    ///
+    /// A request that times out fails with [`SendError::RequestTimedOut`]
+    /// without affecting the connection or any other request.
+    pub async fn send_with_priority(
+        &self,
+        msg: Request,
+        timeout: Duration,
+        priority: RequestPriority,
+    ) -> Result<Response, SendError> {
+        self.send_traced(msg, timeout, priority, TraceId::random())
             .await
-            .map_err(|_elapsed| SendError::RequestTimedOut)?;
-        Ok(send_result?)
+            .map_err(|Traced { trace_id: _, error }| error)
+    }
+
+    /// Like [`Self::send_with_priority`], but tags the request with the given
+    /// [`TraceId`].
+    ///
+    /// The ID is sent to the server along with the request, appears in the
+    /// log lines and [`NetEvent`]s for it, and is returned with any error.
+    /// [`Self::send_with_priority`] does the same with a random ID.
+    pub async fn send_traced(
+        &self,
+        mut msg: Request,
+        timeout: Duration,
+        priority: RequestPriority,
+        trace_id: TraceId,
+    ) -> Result<Response, Traced<SendError>> {
+        let (name, value) = trace_id.as_header();
+        msg.headers.insert(name, value);
+
+        let log_tag = &self.log_tag;
+        log::debug!("[{log_tag}] sending request {trace_id}");
+        let result = self.send_untraced(msg, timeout, priority).await;
+        if let Err(error) = &result {
+            log::debug!("[{log_tag}] request {trace_id} failed: {error}");
+            record_to(self.event_sink.as_ref(), || NetEvent::RequestFailed {
+                tag: log_tag.to_string(),
+                trace_id: trace_id.to_string(),
+                error: error.to_string(),
+            });
+        }
+        result.map_err(|error| Traced { trace_id, error })
+    }
+
+    async fn send_untraced(
+        &self,
+        msg: Request,
+        timeout: Duration,
+        priority: RequestPriority,
+    ) -> Result<Response, SendError> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .try_acquire()
+                .map_err(|retry_after| SendError::WouldExceedRateLimit { retry_after })?;
+        }
+        let send = async {
+            let _permit = match &self.in_flight_limiter {
+                Some(limiter) => Some(limiter.acquire().await.map_err(|_busy| SendError::Busy)?),
+                None => None,
+            };
+            Ok::<_, SendError>(self.inner.send_with_priority(msg, priority).await?)
+        };
+        tokio::time::timeout(timeout, send)
+            .await
+            .map_err(|_elapsed| SendError::RequestTimedOut)?
     }
 
     pub async fn disconnect(&self) {
         self.inner.disconnect().await
     }
 
+    /// Like [`Self::disconnect`], but tells the server why with the given
+    /// websocket close code and reason.
+    pub async fn disconnect_with_close_frame(&self, code: CloseCode, reason: &str) {
+        self.inner.disconnect_with_close_frame(code, reason).await
+    }
+
     pub fn connection_info(&self) -> &ConnectionInfo {
         &self.connection_info
     }
+
+    /// Reports how many envelopes the server has delivered on this connection, and whether it
+    /// has finished delivering the ones that were queued when the connection was made.
+    pub fn receive_progress(&self) -> ReceiveProgress {
+        self.receive_progress.snapshot()
+    }
 }
 
 impl PendingChatConnection {
@@ -353,6 +529,7 @@ impl PendingChatConnection {
         ConnectionInfo {
             route_info: self.route_info.clone(),
             transport_info: self.connection.transport_info(),
+            server_metadata: ServerConnectionMetadata::from_headers(&self.connect_response_headers),
         }
     }
 
@@ -375,6 +552,7 @@ impl Display for ConnectionInfo {
                     ip_version,
                 },
             route_info,
+            server_metadata: _,
         } = self;
         write!(f, "from {ip_version}:{local_port} via {route_info}")
     }
@@ -425,6 +603,11 @@ pub mod test_support {
 
         let ws_config = ws::Config {
             initial_request_id: 0,
+            max_frame_size: None,
+            max_message_size: None,
+            application_idle_timeout: None,
+            rate_limit: None,
+            in_flight_limit: None,
             local_idle_timeout: Duration::from_secs(60),
             remote_idle_timeout: Duration::from_secs(60),
         };
@@ -700,6 +883,7 @@ pub(crate) mod test {
                         sni: Host::Domain(CHAT_DOMAIN.into()),
                         alpn: Some(Alpn::Http1_1),
                         min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_3),
+                        client_cert: None,
                     },
                     inner: DirectOrProxyRoute::Direct(TcpRoute {
                         address: UnresolvedHost(CHAT_DOMAIN.into()),
@@ -713,6 +897,11 @@ pub(crate) mod test {
                 local_idle_timeout: Duration::ZERO,
                 remote_idle_timeout: Duration::ZERO,
                 initial_request_id: 0,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
+                rate_limit: None,
+                in_flight_limit: None,
             },
             None,
             "fake chat",
@@ -762,6 +951,7 @@ pub(crate) mod test {
                     sni: Host::Domain(CHAT_DOMAIN.into()),
                     alpn: Some(Alpn::Http1_1),
                     min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_3),
+                    client_cert: None,
                 },
                 inner: DirectOrProxyRoute::Direct(TcpRoute {
                     address: UnresolvedHost(CHAT_DOMAIN.into()),
@@ -811,6 +1001,11 @@ pub(crate) mod test {
                 local_idle_timeout: Duration::ZERO,
                 remote_idle_timeout: Duration::ZERO,
                 initial_request_id: 0,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
+                rate_limit: None,
+                in_flight_limit: None,
             },
             Some(auth_headers.clone().into()),
             "fake chat",
@@ -831,6 +1026,11 @@ pub(crate) mod test {
                 local_idle_timeout: Duration::ZERO,
                 remote_idle_timeout: Duration::ZERO,
                 initial_request_id: 0,
+                max_frame_size: None,
+                max_message_size: None,
+                application_idle_timeout: None,
+                rate_limit: None,
+                in_flight_limit: None,
             },
             Some(auth_headers.into()),
             "fake chat",
@@ -841,4 +1041,154 @@ pub(crate) mod test {
         assert_matches!(err, ConnectError::AllAttemptsFailed);
         assert_eq!(number_of_times_called.load(atomic::Ordering::SeqCst), 4);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_is_throttled_by_rate_limit() {
+        let (mut chat, _remote) =
+            ChatConnection::new_fake(tokio::runtime::Handle::current(), Box::new(|_event| {}), []);
+        chat.rate_limiter = Some(RateLimiter::new(RateLimitConfig {
+            burst: nonzero_ext::nonzero!(1u32),
+            refill_interval: Duration::from_secs(10),
+        }));
+
+        let request = || Request {
+            method: ::http::Method::GET,
+            path: PathAndQuery::from_static("/"),
+            headers: HeaderMap::new(),
+            body: None,
+        };
+
+        // The fake remote never responds, so the first request times out.
+        assert_matches!(
+            chat.send(request(), Duration::from_secs(1)).await,
+            Err(SendError::RequestTimedOut)
+        );
+        assert_matches!(
+            chat.send(request(), Duration::from_secs(1)).await,
+            Err(SendError::WouldExceedRateLimit { retry_after }) if retry_after == Duration::from_secs(9)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_is_rejected_when_too_many_in_flight() {
+        let (mut chat, _remote) =
+            ChatConnection::new_fake(tokio::runtime::Handle::current(), Box::new(|_event| {}), []);
+        chat.in_flight_limiter = Some(InFlightLimiter::new(InFlightLimitConfig {
+            max_in_flight: nonzero_ext::nonzero!(1usize),
+            max_queued: 1,
+        }));
+
+        let request = || Request {
+            method: ::http::Method::GET,
+            path: PathAndQuery::from_static("/"),
+            headers: HeaderMap::new(),
+            body: None,
+        };
+
+        // The fake remote never responds, so the first two requests occupy the
+        // in-flight and queued slots until they time out.
+        let (first, second, third) = tokio::join!(
+            chat.send(request(), Duration::from_secs(1)),
+            chat.send(request(), Duration::from_secs(1)),
+            chat.send(request(), Duration::from_secs(1)),
+        );
+        assert_matches!(first, Err(SendError::RequestTimedOut));
+        assert_matches!(second, Err(SendError::RequestTimedOut));
+        assert_matches!(third, Err(SendError::Busy));
+
+        // Once they've timed out, their slots are available again.
+        assert_matches!(
+            chat.send(request(), Duration::from_secs(1)).await,
+            Err(SendError::RequestTimedOut)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_out_request_does_not_break_connection() {
+        let (chat, remote) =
+            ChatConnection::new_fake(tokio::runtime::Handle::current(), Box::new(|_event| {}), []);
+
+        let request = |path| Request {
+            method: ::http::Method::GET,
+            path: PathAndQuery::from_static(path),
+            headers: HeaderMap::new(),
+            body: None,
+        };
+
+        assert_matches!(
+            chat.send(request("/slow"), Duration::from_secs(1)).await,
+            Err(SendError::RequestTimedOut)
+        );
+
+        let (response, ()) = tokio::join!(
+            chat.send_with_priority(
+                request("/fast"),
+                Duration::from_secs(10),
+                RequestPriority::High
+            ),
+            async {
+                let slow = remote
+                    .receive_request()
+                    .await
+                    .expect("valid")
+                    .expect("request");
+                assert_eq!(slow.path.as_deref(), Some("/slow"));
+                let fast = remote
+                    .receive_request()
+                    .await
+                    .expect("valid")
+                    .expect("request");
+                assert_eq!(fast.path.as_deref(), Some("/fast"));
+
+                // The late response to the abandoned request should be ignored.
+                for id in [slow.id, fast.id] {
+                    remote
+                        .send_response(ResponseProto {
+                            id,
+                            status: Some(200),
+                            ..Default::default()
+                        })
+                        .expect("still connected");
+                }
+            }
+        );
+        assert_matches!(response, Ok(Response { status, .. }) if status.as_u16() == 200);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn traced_request_sends_trace_id_and_returns_it_on_failure() {
+        let (chat, remote) =
+            ChatConnection::new_fake(tokio::runtime::Handle::current(), Box::new(|_event| {}), []);
+        let trace_id = TraceId::random();
+
+        let (result, ()) = tokio::join!(
+            chat.send_traced(
+                Request {
+                    method: ::http::Method::GET,
+                    path: PathAndQuery::from_static("/traced"),
+                    headers: HeaderMap::new(),
+                    body: None,
+                },
+                Duration::from_secs(1),
+                RequestPriority::Normal,
+                trace_id,
+            ),
+            async {
+                let (request, sent_trace_id) = remote
+                    .receive_traced_request()
+                    .await
+                    .expect("valid")
+                    .expect("request");
+                assert_eq!(request.headers, Vec::<String>::new());
+                assert_eq!(sent_trace_id, Some(trace_id.to_string()));
+            }
+        );
+        assert_matches!(
+            result,
+            Err(Traced {
+                trace_id: failed_id,
+                error: SendError::RequestTimedOut,
+            }) if failed_id == trace_id
+        );
+    }
 }
@@ -3,6 +3,8 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::time::Duration;
+
 use libsignal_net_infra::errors::{LogSafeDisplay, RetryLater, TransportConnectError};
 use libsignal_net_infra::extract_retry_later;
 use libsignal_net_infra::route::ConnectError as RouteConnectError;
@@ -28,6 +30,10 @@ pub enum SendError {
     IncomingDataInvalid,
     /// request object must contain only ASCII text as header names and values.
     RequestHasInvalidHeader,
+    /// sending the request would exceed the local rate limit; retry after {retry_after:?}
+    WouldExceedRateLimit { retry_after: Duration },
+    /// too many requests are already in flight on this connection
+    Busy,
 }
 impl LogSafeDisplay for SendError where WebSocketError: LogSafeDisplay {}
 
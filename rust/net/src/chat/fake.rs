@@ -4,6 +4,7 @@
 //
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::time::Duration;
 
 use futures_util::{Sink, Stream};
@@ -12,9 +13,13 @@ use pin_project::pin_project;
 use prost::Message;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use crate::chat::{ws, ChatConnection, ConnectionInfo, MessageProto, RequestProto, ResponseProto};
+use crate::chat::server_requests::ReceiveProgressTracker;
+use crate::chat::{
+    ws, ChatConnection, ConnectionInfo, MessageProto, RequestProto, ResponseProto,
+    ServerConnectionMetadata,
+};
 use crate::connect_state::RouteInfo;
-use crate::env::ALERT_HEADER_NAME;
+use crate::env::{ALERT_HEADER_NAME, TRACE_ID_HEADER_NAME};
 
 /// The remote end of a fake connection to the chat server.
 #[derive(Debug)]
@@ -59,18 +64,16 @@ impl ChatConnection {
         });
         let local = StreamSink(incoming, outgoing, PhantomData);
 
-        let connection_info = ConnectionInfo {
-            route_info: RouteInfo::fake(),
-            transport_info: TransportInfo {
-                ip_version: IpType::V4,
-                local_port: 0,
-            },
-        };
-        let log_tag = "fake chat".into();
+        let log_tag: Arc<str> = "fake chat".into();
         let config = crate::chat::ws::Config {
             local_idle_timeout: Duration::from_secs(86400),
             remote_idle_timeout: Duration::from_secs(86400),
             initial_request_id: 0,
+            max_frame_size: None,
+            max_message_size: None,
+            application_idle_timeout: None,
+            rate_limit: None,
+            in_flight_limit: None,
         };
         let headers = http::HeaderMap::from_iter(alerts.into_iter().map(|alert| {
             (
@@ -79,16 +82,32 @@ impl ChatConnection {
                     .expect("valid headers only for a fake connection"),
             )
         }));
+        let connection_info = ConnectionInfo {
+            route_info: RouteInfo::fake(),
+            transport_info: TransportInfo {
+                ip_version: IpType::V4,
+                local_port: 0,
+            },
+            server_metadata: ServerConnectionMetadata::from_headers(&headers),
+        };
+        let receive_progress = Arc::new(ReceiveProgressTracker::new(None, log_tag.clone()));
+        let listener = receive_progress.track(listener);
         let chat = Self {
             inner: crate::chat::ws::Chat::new(
                 tokio_runtime,
                 local,
                 headers,
                 config,
-                log_tag,
+                Default::default(),
+                log_tag.clone(),
                 listener,
             ),
             connection_info,
+            rate_limiter: None,
+            in_flight_limiter: None,
+            receive_progress,
+            log_tag,
+            event_sink: None,
         };
         (chat, remote)
     }
@@ -125,7 +144,21 @@ impl FakeChatRemote {
             .map_err(|_failed_send| Disconnected)
     }
 
+    /// Receives the next request from the client.
+    ///
+    /// Trace IDs are random, so the [`TRACE_ID_HEADER_NAME`] header is removed to keep requests
+    /// comparable. Use [`Self::receive_traced_request`] to see it.
     pub async fn receive_request(&self) -> Result<Option<RequestProto>, ReceiveRequestError> {
+        Ok(self
+            .receive_traced_request()
+            .await?
+            .map(|(request, _trace_id)| request))
+    }
+
+    /// Like [`Self::receive_request`], but also returns the request's trace ID, if it had one.
+    pub async fn receive_traced_request(
+        &self,
+    ) -> Result<Option<(RequestProto, Option<String>)>, ReceiveRequestError> {
         log::debug!("waiting for next request");
         let Some(message) = self.rx.lock().await.recv().await else {
             return Ok(None);
@@ -136,7 +169,21 @@ impl FakeChatRemote {
             _ => return Err(ReceiveRequestError::InvalidWebsocketMessageType),
         };
         match proto {
-            ws::ChatMessageProto::Request(request) => Ok(Some(request)),
+            ws::ChatMessageProto::Request(mut request) => {
+                let mut trace_id = None;
+                request
+                    .headers
+                    .retain(|header| match header.split_once(':') {
+                        Some((name, value))
+                            if name.trim().eq_ignore_ascii_case(TRACE_ID_HEADER_NAME) =>
+                        {
+                            trace_id = Some(value.trim().to_owned());
+                            false
+                        }
+                        _ => true,
+                    });
+                Ok(Some((request, trace_id)))
+            }
             ws::ChatMessageProto::Response(_) => Err(ReceiveRequestError::GotResponse),
         }
     }
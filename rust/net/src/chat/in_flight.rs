@@ -0,0 +1,117 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Limits on how many requests can be outstanding on a connection at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InFlightLimitConfig {
+    /// How many requests can be waiting for a response at the same time.
+    pub max_in_flight: NonZeroUsize,
+    /// How many more requests can wait for one of the in-flight requests to
+    /// finish before new requests are rejected outright.
+    ///
+    /// Zero means requests are rejected as soon as `max_in_flight` is reached.
+    pub max_queued: usize,
+}
+
+/// Returned when a request can't be sent or queued because the connection is at
+/// capacity.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Busy;
+
+/// Locally enforces an [`InFlightLimitConfig`].
+#[derive(Debug)]
+pub(crate) struct InFlightLimiter {
+    max_queued: usize,
+    permits: Semaphore,
+    queued: AtomicUsize,
+}
+
+impl InFlightLimiter {
+    pub(crate) fn new(config: InFlightLimitConfig) -> Self {
+        let InFlightLimitConfig {
+            max_in_flight,
+            max_queued,
+        } = config;
+        Self {
+            max_queued,
+            permits: Semaphore::new(max_in_flight.get()),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a slot to send a request in, which is held until the returned
+    /// permit is dropped.
+    ///
+    /// Fails immediately if all slots are taken and the queue is already full.
+    pub(crate) async fn acquire(&self) -> Result<SemaphorePermit<'_>, Busy> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(permit);
+        }
+
+        self.queued
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| {
+                (queued < self.max_queued).then_some(queued + 1)
+            })
+            .map_err(|_| Busy)?;
+        // Give up our place in the queue even if the caller stops waiting.
+        let _queued = scopeguard::guard((), |()| {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        });
+
+        Ok(self
+            .permits
+            .acquire()
+            .await
+            .expect("semaphore is never closed"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use futures_util::FutureExt as _;
+    use nonzero_ext::nonzero;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn queues_up_to_limit_then_rejects() {
+        let limiter = InFlightLimiter::new(InFlightLimitConfig {
+            max_in_flight: nonzero!(1usize),
+            max_queued: 1,
+        });
+
+        let first = limiter.acquire().await.expect("has capacity");
+
+        let mut queued = std::pin::pin!(limiter.acquire());
+        assert_matches!(queued.as_mut().now_or_never(), None);
+        assert_matches!(limiter.acquire().now_or_never(), Some(Err(Busy)));
+
+        drop(first);
+        let second = queued.await.expect("was queued");
+        assert_matches!(limiter.acquire().now_or_never(), None);
+        drop(second);
+        assert_matches!(limiter.acquire().now_or_never(), Some(Ok(_)));
+    }
+
+    #[tokio::test]
+    async fn abandoned_waiter_frees_queue_slot() {
+        let limiter = InFlightLimiter::new(InFlightLimitConfig {
+            max_in_flight: nonzero!(1usize),
+            max_queued: 1,
+        });
+
+        let _first = limiter.acquire().await.expect("has capacity");
+        assert_matches!(limiter.acquire().now_or_never(), None);
+        // The waiter above was dropped, so there's room to queue again.
+        let mut queued = std::pin::pin!(limiter.acquire());
+        assert_matches!(queued.as_mut().now_or_never(), None);
+    }
+}
@@ -0,0 +1,89 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use itertools::Itertools as _;
+use libsignal_protocol::Timestamp;
+
+use crate::env::{ALERT_HEADER_NAME, TIMESTAMP_HEADER_NAME};
+
+/// Information the chat server sends along with its response to the websocket
+/// upgrade request.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerConnectionMetadata {
+    /// Alerts for the client, in the order the server sent them.
+    pub alerts: Vec<String>,
+    /// The server's clock at the time it accepted the connection, if it was
+    /// reported and valid.
+    pub server_timestamp: Option<Timestamp>,
+}
+
+impl ServerConnectionMetadata {
+    pub fn from_headers(headers: &http::HeaderMap) -> Self {
+        let alerts = headers
+            .get_all(ALERT_HEADER_NAME)
+            .iter()
+            .flat_map(|value| {
+                value
+                    .to_str()
+                    .unwrap_or("[non-ASCII alert]")
+                    .split_terminator(',')
+                    .map(|individual_value| individual_value.trim_ascii().to_owned())
+            })
+            .collect_vec();
+
+        let server_timestamp = headers
+            .get(TIMESTAMP_HEADER_NAME)
+            .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+            .map(Timestamp::from_epoch_millis);
+
+        Self {
+            alerts,
+            server_timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_alerts_and_timestamp() {
+        let headers = http::HeaderMap::from_iter(
+            [
+                (ALERT_HEADER_NAME, "first"),
+                (ALERT_HEADER_NAME, "second, third"),
+                (TIMESTAMP_HEADER_NAME, "1700000000000"),
+            ]
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    http::HeaderValue::from_static(value),
+                )
+            }),
+        );
+
+        assert_eq!(
+            ServerConnectionMetadata::from_headers(&headers),
+            ServerConnectionMetadata {
+                alerts: vec!["first".into(), "second".into(), "third".into()],
+                server_timestamp: Some(Timestamp::from_epoch_millis(1_700_000_000_000)),
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_timestamp() {
+        let headers = http::HeaderMap::from_iter([(
+            http::HeaderName::from_static(TIMESTAMP_HEADER_NAME),
+            http::HeaderValue::from_static("yesterday"),
+        )]);
+
+        assert_eq!(
+            ServerConnectionMetadata::from_headers(&headers),
+            ServerConnectionMetadata::default()
+        );
+    }
+}
@@ -129,7 +129,8 @@ impl NoiseDirectConnectShadow<'_> {
                         | TransportConnectError::SslError(_)
                         | TransportConnectError::CertError
                         | TransportConnectError::SslFailedHandshake(_)
-                        | TransportConnectError::ProxyProtocol => ControlFlow::Continue(()),
+                        | TransportConnectError::ProxyProtocol
+                        | TransportConnectError::AttemptTimedOut => ControlFlow::Continue(()),
                     },
                     ConnectError::WrongPublicKey
                     | ConnectError::ClientVersionTooOld
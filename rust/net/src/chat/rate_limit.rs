@@ -0,0 +1,131 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Token-bucket parameters for limiting outgoing requests on a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// How many requests can be sent back-to-back before any are throttled.
+    pub burst: NonZeroU32,
+    /// How long it takes for one more request to become available.
+    pub refill_interval: Duration,
+}
+
+/// Locally enforces a [`RateLimitConfig`].
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    available: u32,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            bucket: Bucket {
+                available: config.burst.get(),
+                last_refill: Instant::now(),
+            }
+            .into(),
+        }
+    }
+
+    /// Takes a token from the bucket if one is available.
+    ///
+    /// Otherwise, returns how long the caller would need to wait for one.
+    pub(crate) fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let RateLimitConfig {
+            burst,
+            refill_interval,
+        } = self.config;
+        if refill_interval.is_zero() {
+            return Ok(());
+        }
+
+        let mut bucket = self.bucket.lock().expect("not poisoned");
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        let refills = elapsed.as_nanos() / refill_interval.as_nanos();
+        if refills > 0 {
+            let refills = u32::try_from(refills).unwrap_or(u32::MAX);
+            bucket.available = bucket.available.saturating_add(refills).min(burst.get());
+            bucket.last_refill = if bucket.available == burst.get() {
+                now
+            } else {
+                bucket.last_refill + refill_interval * refills
+            };
+        }
+
+        match bucket.available.checked_sub(1) {
+            Some(remaining) => {
+                bucket.available = remaining;
+                Ok(())
+            }
+            None => Err(refill_interval.saturating_sub(now - bucket.last_refill)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nonzero_ext::nonzero;
+
+    use super::*;
+
+    const CONFIG: RateLimitConfig = RateLimitConfig {
+        burst: nonzero!(2u32),
+        refill_interval: Duration::from_secs(10),
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn allows_burst_then_throttles() {
+        let limiter = RateLimiter::new(CONFIG);
+        let start = Instant::now();
+
+        assert_eq!(limiter.try_acquire_at(start), Ok(()));
+        assert_eq!(limiter.try_acquire_at(start), Ok(()));
+        assert_eq!(
+            limiter.try_acquire_at(start + Duration::from_secs(3)),
+            Err(Duration::from_secs(7))
+        );
+
+        assert_eq!(
+            limiter.try_acquire_at(start + Duration::from_secs(10)),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.try_acquire_at(start + Duration::from_secs(10)),
+            Err(Duration::from_secs(10))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refills_up_to_burst() {
+        let limiter = RateLimiter::new(CONFIG);
+        let start = Instant::now();
+
+        assert_eq!(limiter.try_acquire_at(start), Ok(()));
+        assert_eq!(limiter.try_acquire_at(start), Ok(()));
+
+        let later = start + Duration::from_secs(1000);
+        assert_eq!(limiter.try_acquire_at(later), Ok(()));
+        assert_eq!(limiter.try_acquire_at(later), Ok(()));
+        assert_eq!(limiter.try_acquire_at(later), Err(Duration::from_secs(10)));
+    }
+}
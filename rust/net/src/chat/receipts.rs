@@ -0,0 +1,551 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Coalesces delivery and read receipts so that each sender gets one request per window rather
+//! than one per message.
+
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use libsignal_core::ServiceId;
+use libsignal_protocol::Timestamp;
+use tokio::task::{AbortHandle, JoinSet};
+
+use crate::chat::supervisor::ChatConnectionSupervisor;
+use crate::chat::{ChatConnection, Request, Response, SendError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReceiptKind {
+    Delivery,
+    Read,
+}
+
+/// Receipts of one kind for messages from one sender, identified by the messages' timestamps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceiptBatch {
+    pub sender: ServiceId,
+    pub kind: ReceiptKind,
+    pub timestamps: Vec<Timestamp>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceiptBatchConfig {
+    /// How long to hold on to the first receipt for a sender before sending.
+    pub window: Duration,
+    /// The most timestamps to put in one request; a batch that reaches this size is sent right
+    /// away.
+    pub max_timestamps_per_request: NonZeroUsize,
+    /// How long to wait for the response to each request.
+    pub request_timeout: Duration,
+}
+
+/// Builds the request that delivers a batch of receipts.
+///
+/// Receipts are end-to-end encrypted messages, so producing the request body is up to the caller.
+pub type BuildReceiptRequest = Box<dyn Fn(&ReceiptBatch) -> Request + Send + Sync>;
+
+/// Something receipts can be sent over.
+pub trait ReceiptTransport: Send + Sync + 'static {
+    fn send(
+        &self,
+        request: Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, Result<Response, SendError>>;
+}
+
+impl ReceiptTransport for ChatConnection {
+    fn send(
+        &self,
+        request: Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, Result<Response, SendError>> {
+        Box::pin(ChatConnection::send(self, request, timeout))
+    }
+}
+
+impl ReceiptTransport for ChatConnectionSupervisor {
+    fn send(
+        &self,
+        request: Request,
+        timeout: Duration,
+    ) -> BoxFuture<'_, Result<Response, SendError>> {
+        Box::pin(ChatConnectionSupervisor::send(self, request, timeout))
+    }
+}
+
+/// Collects receipts and sends them in batches, one per sender and [`ReceiptKind`].
+///
+/// Batches that can't be sent because of a [`SendError`] are kept. They're retried with the next
+/// window, or, if the connection is gone, along with the next receipt added or when
+/// [`finish`](Self::finish) is called. A batch the server responds to with an error status is
+/// dropped.
+///
+/// Dropping the batcher without calling `finish` leaves any scheduled flush and in-flight requests
+/// running on the runtime.
+pub struct ReceiptBatcher {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    tokio_runtime: tokio::runtime::Handle,
+    config: ReceiptBatchConfig,
+    transport: Arc<dyn ReceiptTransport>,
+    build_request: BuildReceiptRequest,
+    pending: Mutex<Pending>,
+    /// Requests that have been started but not yet completed.
+    ///
+    /// Always locked after `pending` when both are needed.
+    in_flight: Mutex<JoinSet<()>>,
+}
+
+#[derive(Default)]
+struct Pending {
+    batches: BTreeMap<(ServiceId, ReceiptKind), Vec<Timestamp>>,
+    /// The task waiting out the current window, if any.
+    scheduled_flush: Option<AbortHandle>,
+    finished: bool,
+}
+
+impl ReceiptBatcher {
+    pub fn new(
+        tokio_runtime: &tokio::runtime::Handle,
+        transport: Arc<dyn ReceiptTransport>,
+        config: ReceiptBatchConfig,
+        build_request: BuildReceiptRequest,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                tokio_runtime: tokio_runtime.clone(),
+                config,
+                transport,
+                build_request,
+                pending: Default::default(),
+                in_flight: Default::default(),
+            }),
+        }
+    }
+
+    /// Queues a receipt for the message from `sender` sent at `timestamp`.
+    pub fn add(&self, sender: ServiceId, kind: ReceiptKind, timestamp: Timestamp) {
+        let shared = &self.shared;
+        let mut pending = shared.pending.lock().expect("not poisoned");
+        let timestamps = pending.batches.entry((sender, kind)).or_default();
+        timestamps.push(timestamp);
+
+        if timestamps.len() >= shared.config.max_timestamps_per_request.get() {
+            let timestamps = pending.batches.remove(&(sender, kind)).expect("just added");
+            let batch = ReceiptBatch {
+                sender,
+                kind,
+                timestamps,
+            };
+            drop(pending);
+            shared.spawn_send(vec![batch]);
+            return;
+        }
+
+        shared.schedule_flush(&mut pending);
+    }
+
+    /// Sends all pending receipts now.
+    pub async fn flush(&self) {
+        let batches = self.shared.take_pending();
+        self.shared.send_all(batches).await;
+    }
+
+    /// Sends all pending receipts and stops batching.
+    ///
+    /// Call this before disconnecting the underlying connection so that no receipts are left
+    /// behind. This cancels any scheduled flush and waits for requests already in flight, so
+    /// nothing is sent once it returns. Any batches that still can't be sent are returned, so they
+    /// can be saved or handed to the batcher for the next connection.
+    pub async fn finish(self) -> Vec<ReceiptBatch> {
+        let shared = &self.shared;
+        {
+            let mut pending = shared.pending.lock().expect("not poisoned");
+            pending.finished = true;
+            // The flush task only awaits while waiting out the window, so aborting it can't lose
+            // any batches; they're still in `pending`.
+            if let Some(scheduled_flush) = pending.scheduled_flush.take() {
+                scheduled_flush.abort();
+            }
+        }
+
+        // With `finished` set, nothing else will be added to `in_flight`, and failed requests put
+        // their batches back in `pending` without scheduling a retry.
+        let mut in_flight = std::mem::take(&mut *shared.in_flight.lock().expect("not poisoned"));
+        while let Some(result) = in_flight.join_next().await {
+            if let Err(e) = result {
+                if e.is_panic() {
+                    std::panic::resume_unwind(e.into_panic());
+                }
+            }
+        }
+
+        let batches = shared.take_pending();
+        shared.send_all(batches).await;
+        shared.take_pending()
+    }
+}
+
+impl Shared {
+    fn schedule_flush(self: &Arc<Self>, pending: &mut Pending) {
+        if pending.scheduled_flush.is_some() || pending.finished || pending.batches.is_empty() {
+            return;
+        }
+
+        let shared = Arc::clone(self);
+        let task = self.tokio_runtime.spawn(async move {
+            tokio::time::sleep(shared.config.window).await;
+            let mut pending = shared.pending.lock().expect("not poisoned");
+            pending.scheduled_flush = None;
+            if pending.finished {
+                return;
+            }
+            let batches = take_batches(&mut pending);
+            // Start the send before releasing `pending`, so that `finish` either sees it in
+            // `in_flight` or has already stopped this task from getting here.
+            shared.spawn_send(batches);
+        });
+        pending.scheduled_flush = Some(task.abort_handle());
+    }
+
+    fn spawn_send(self: &Arc<Self>, batches: Vec<ReceiptBatch>) {
+        let shared = Arc::clone(self);
+        let mut in_flight = self.in_flight.lock().expect("not poisoned");
+        // Clean up after requests that have already completed.
+        while in_flight.try_join_next().is_some() {}
+        in_flight.spawn_on(
+            async move { shared.send_all(batches).await },
+            &self.tokio_runtime,
+        );
+    }
+
+    fn take_pending(&self) -> Vec<ReceiptBatch> {
+        take_batches(&mut self.pending.lock().expect("not poisoned"))
+    }
+
+    async fn send_all(self: &Arc<Self>, batches: Vec<ReceiptBatch>) {
+        let max = self.config.max_timestamps_per_request.get();
+        let requests = batches.into_iter().flat_map(|batch| {
+            let ReceiptBatch {
+                sender,
+                kind,
+                timestamps,
+            } = batch;
+            timestamps
+                .chunks(max)
+                .map(|chunk| ReceiptBatch {
+                    sender,
+                    kind,
+                    timestamps: chunk.to_vec(),
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let mut unsent = vec![];
+        let mut disconnected = false;
+        for batch in requests {
+            if !unsent.is_empty() {
+                // Stop at the first failure; everything after it is retried along with it.
+                unsent.push(batch);
+                continue;
+            }
+            let request = (self.build_request)(&batch);
+            match self
+                .transport
+                .send(request, self.config.request_timeout)
+                .await
+            {
+                Ok(response) if response.status.is_success() => {}
+                Ok(response) => {
+                    log::warn!(
+                        "dropping {} {:?} receipt(s) rejected with {}",
+                        batch.timestamps.len(),
+                        batch.kind,
+                        response.status
+                    );
+                }
+                Err(error) => {
+                    log::info!("failed to send receipts, will retry: {error}");
+                    disconnected = matches!(
+                        error,
+                        SendError::Disconnected
+                            | SendError::ConnectedElsewhere
+                            | SendError::ConnectionInvalidated
+                            | SendError::WebSocket(_)
+                    );
+                    unsent.push(batch);
+                }
+            }
+        }
+
+        if unsent.is_empty() {
+            return;
+        }
+        let mut pending = self.pending.lock().expect("not poisoned");
+        for ReceiptBatch {
+            sender,
+            kind,
+            timestamps,
+        } in unsent
+        {
+            // Put the older receipts back in front of any that arrived in the meantime.
+            let queued = pending.batches.entry((sender, kind)).or_default();
+            queued.splice(0..0, timestamps);
+        }
+        // Retrying on a schedule won't help if the connection is gone.
+        if !disconnected {
+            self.schedule_flush(&mut pending);
+        }
+    }
+}
+
+fn take_batches(pending: &mut Pending) -> Vec<ReceiptBatch> {
+    std::mem::take(&mut pending.batches)
+        .into_iter()
+        .map(|((sender, kind), timestamps)| ReceiptBatch {
+            sender,
+            kind,
+            timestamps,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use libsignal_core::Aci;
+    use nonzero_ext::nonzero;
+
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(5);
+
+    /// Records the batches it's asked to send, failing with `error` if one is set.
+    ///
+    /// Each request takes `delay` to complete.
+    #[derive(Default)]
+    struct FakeTransport {
+        sent: Mutex<Vec<ReceiptBatch>>,
+        error: Mutex<Option<fn() -> SendError>>,
+        delay: Duration,
+    }
+
+    impl ReceiptTransport for FakeTransport {
+        fn send(
+            &self,
+            request: Request,
+            _timeout: Duration,
+        ) -> BoxFuture<'_, Result<Response, SendError>> {
+            Box::pin(async move {
+                if !self.delay.is_zero() {
+                    tokio::time::sleep(self.delay).await;
+                }
+                match *self.error.lock().unwrap() {
+                    Some(make_error) => Err(make_error()),
+                    None => {
+                        self.sent.lock().unwrap().push(
+                            serde_json::from_slice::<TestBatch>(&request.body.unwrap())
+                                .unwrap()
+                                .into(),
+                        );
+                        Ok(Response {
+                            status: http::StatusCode::OK,
+                            message: None,
+                            body: None,
+                            headers: Default::default(),
+                        })
+                    }
+                }
+            })
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct TestBatch {
+        sender: String,
+        read: bool,
+        timestamps: Vec<u64>,
+    }
+
+    impl From<TestBatch> for ReceiptBatch {
+        fn from(value: TestBatch) -> Self {
+            Self {
+                sender: ServiceId::parse_from_service_id_string(&value.sender).unwrap(),
+                kind: if value.read {
+                    ReceiptKind::Read
+                } else {
+                    ReceiptKind::Delivery
+                },
+                timestamps: value
+                    .timestamps
+                    .into_iter()
+                    .map(Timestamp::from_epoch_millis)
+                    .collect(),
+            }
+        }
+    }
+
+    fn build_request(batch: &ReceiptBatch) -> Request {
+        let body = TestBatch {
+            sender: batch.sender.service_id_string(),
+            read: batch.kind == ReceiptKind::Read,
+            timestamps: batch.timestamps.iter().map(|t| t.epoch_millis()).collect(),
+        };
+        Request {
+            method: http::Method::PUT,
+            path: http::uri::PathAndQuery::from_static("/v1/messages"),
+            headers: Default::default(),
+            body: Some(serde_json::to_vec(&body).unwrap().into()),
+        }
+    }
+
+    fn batcher(transport: &Arc<FakeTransport>) -> ReceiptBatcher {
+        ReceiptBatcher::new(
+            &tokio::runtime::Handle::current(),
+            Arc::clone(transport),
+            ReceiptBatchConfig {
+                window: WINDOW,
+                max_timestamps_per_request: nonzero!(3usize),
+                request_timeout: Duration::from_secs(10),
+            },
+            Box::new(build_request),
+        )
+    }
+
+    fn sender(n: u8) -> ServiceId {
+        Aci::from_uuid_bytes([n; 16]).into()
+    }
+
+    fn ts(millis: u64) -> Timestamp {
+        Timestamp::from_epoch_millis(millis)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn coalesces_per_sender_and_kind_within_window() {
+        let transport = Arc::new(FakeTransport::default());
+        let batcher = batcher(&transport);
+
+        batcher.add(sender(1), ReceiptKind::Delivery, ts(1));
+        batcher.add(sender(2), ReceiptKind::Delivery, ts(2));
+        batcher.add(sender(1), ReceiptKind::Delivery, ts(3));
+        batcher.add(sender(1), ReceiptKind::Read, ts(1));
+
+        tokio::time::sleep(WINDOW / 2).await;
+        assert!(transport.sent.lock().unwrap().is_empty());
+
+        tokio::time::sleep(WINDOW).await;
+        assert_eq!(
+            *transport.sent.lock().unwrap(),
+            [
+                ReceiptBatch {
+                    sender: sender(1),
+                    kind: ReceiptKind::Delivery,
+                    timestamps: vec![ts(1), ts(3)],
+                },
+                ReceiptBatch {
+                    sender: sender(1),
+                    kind: ReceiptKind::Read,
+                    timestamps: vec![ts(1)],
+                },
+                ReceiptBatch {
+                    sender: sender(2),
+                    kind: ReceiptKind::Delivery,
+                    timestamps: vec![ts(2)],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_batch_is_sent_immediately() {
+        let transport = Arc::new(FakeTransport::default());
+        let batcher = batcher(&transport);
+
+        for millis in 1..=4 {
+            batcher.add(sender(1), ReceiptKind::Read, ts(millis));
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(
+            *transport.sent.lock().unwrap(),
+            [ReceiptBatch {
+                sender: sender(1),
+                kind: ReceiptKind::Read,
+                timestamps: vec![ts(1), ts(2), ts(3)],
+            }]
+        );
+
+        tokio::time::sleep(WINDOW * 2).await;
+        assert_eq!(transport.sent.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finish_flushes_and_returns_unsent_batches() {
+        let transport = Arc::new(FakeTransport::default());
+        let batcher = batcher(&transport);
+
+        batcher.add(sender(1), ReceiptKind::Delivery, ts(1));
+        batcher.flush().await;
+        assert_eq!(transport.sent.lock().unwrap().len(), 1);
+
+        *transport.error.lock().unwrap() = Some(|| SendError::Disconnected);
+        batcher.add(sender(1), ReceiptKind::Delivery, ts(2));
+        tokio::time::sleep(WINDOW * 2).await;
+
+        batcher.add(sender(1), ReceiptKind::Delivery, ts(3));
+        let unsent = batcher.finish().await;
+        assert_matches!(
+            &unsent[..],
+            [ReceiptBatch { sender: s, kind: ReceiptKind::Delivery, timestamps }]
+                if *s == sender(1) && *timestamps == [ts(2), ts(3)]
+        );
+        assert_eq!(transport.sent.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finish_waits_for_in_flight_requests() {
+        let transport = Arc::new(FakeTransport {
+            delay: Duration::from_secs(1),
+            ..Default::default()
+        });
+        let batcher = batcher(&transport);
+
+        // A full batch, which is sent right away...
+        for millis in 1..=3 {
+            batcher.add(sender(1), ReceiptKind::Read, ts(millis));
+        }
+        // ...and one waiting for the window.
+        batcher.add(sender(2), ReceiptKind::Read, ts(4));
+        tokio::task::yield_now().await;
+
+        let start = tokio::time::Instant::now();
+        let unsent = batcher.finish().await;
+        assert_eq!(unsent, []);
+        assert!(start.elapsed() < WINDOW, "should not wait out the window");
+        assert_eq!(
+            *transport.sent.lock().unwrap(),
+            [
+                ReceiptBatch {
+                    sender: sender(1),
+                    kind: ReceiptKind::Read,
+                    timestamps: vec![ts(1), ts(2), ts(3)],
+                },
+                ReceiptBatch {
+                    sender: sender(2),
+                    kind: ReceiptKind::Read,
+                    timestamps: vec![ts(4)],
+                },
+            ]
+        );
+
+        // Nothing is sent after finishing.
+        tokio::time::sleep(WINDOW * 2).await;
+        assert_eq!(transport.sent.lock().unwrap().len(), 2);
+    }
+}
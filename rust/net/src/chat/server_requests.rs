@@ -3,12 +3,24 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
 use bytes::Bytes;
+use futures_util::Stream;
 use libsignal_net_infra::ws::WebSocketError;
 use libsignal_protocol::Timestamp;
+use tokio::sync::mpsc;
 
 use crate::chat::{ws, RequestProto, SendError};
 use crate::env::TIMESTAMP_HEADER_NAME;
+use crate::events::{NetEvent, SharedEventSink};
+
+const MESSAGE_PATH: &str = "/api/v1/message";
+const QUEUE_EMPTY_PATH: &str = "/api/v1/queue/empty";
 
 pub type ResponseEnvelopeSender =
     Box<dyn FnOnce(http::StatusCode) -> Result<(), SendError> + Send + Sync>;
@@ -28,6 +40,8 @@ pub enum ServerEvent {
 #[derive(Debug, derive_more::From)]
 pub enum DisconnectCause {
     LocalDisconnect,
+    /// The connection was closed locally after going unused for its configured idle timeout.
+    IdleTimeout,
     Error(#[from] SendError),
 }
 
@@ -80,18 +94,102 @@ impl TryFrom<ws::ListenerEvent> for ServerEvent {
 
             ws::ListenerEvent::Finished(reason) => Ok(ServerEvent::Stopped(match reason {
                 Ok(ws::FinishReason::LocalDisconnect) => DisconnectCause::LocalDisconnect,
+                Ok(ws::FinishReason::IdleTimeout) => DisconnectCause::IdleTimeout,
                 Ok(ws::FinishReason::RemoteDisconnect) => {
                     DisconnectCause::Error(SendError::WebSocket(WebSocketError::ChannelClosed))
                 }
                 Err(ws::FinishError::Unknown) => DisconnectCause::Error(SendError::WebSocket(
                     WebSocketError::Other("unexpected exit"),
                 )),
+                Err(ws::FinishError::ServerClosed(reason)) => DisconnectCause::Error(reason.into()),
                 Err(ws::FinishError::Error(e)) => DisconnectCause::Error(e.into()),
             })),
         }
     }
 }
 
+/// Stream of [`ServerEvent`]s for a single chat connection, created by [`server_event_stream`].
+///
+/// The stream ends after yielding [`ServerEvent::Stopped`]. If the connection's task exits
+/// abnormally, the stream may end without it.
+#[derive(Debug)]
+pub struct ServerEventStream {
+    messages: mpsc::Receiver<ServerEvent>,
+    control: mpsc::UnboundedReceiver<ServerEvent>,
+    stopped: Option<ServerEvent>,
+}
+
+/// Creates a listener for a chat connection whose events can be consumed as a [`Stream`].
+///
+/// Up to `buffer` server requests (incoming messages and queue-empty notifications) are queued
+/// for the stream; after that, the connection stops reading from the server until the stream
+/// catches up. Alerts and the final [`ServerEvent::Stopped`] are never held up this way.
+///
+/// Server requests that can't be converted to a `ServerEvent` are logged and dropped, as they
+/// are for callback-style listeners.
+pub fn server_event_stream(buffer: NonZeroUsize) -> (ws::EventListener, ServerEventStream) {
+    let (messages_tx, messages) = mpsc::channel(buffer.get());
+    let (control_tx, control) = mpsc::unbounded_channel();
+
+    let listener: ws::EventListener = Box::new(move |event| {
+        let event = match ServerEvent::try_from(event) {
+            Ok(event) => event,
+            Err(err) => {
+                log::error!("{err}");
+                return;
+            }
+        };
+        let sent = match event {
+            ServerEvent::Alerts(_) | ServerEvent::Stopped(_) => control_tx.send(event).is_ok(),
+            // Server requests are only delivered from a blocking thread, so this won't stall the
+            // runtime, only the connection that's waiting for this listener to return.
+            ServerEvent::QueueEmpty | ServerEvent::IncomingMessage { .. } => {
+                messages_tx.blocking_send(event).is_ok()
+            }
+        };
+        if !sent {
+            log::debug!("server event stream was dropped; discarding event");
+        }
+    });
+
+    (
+        listener,
+        ServerEventStream {
+            messages,
+            control,
+            stopped: None,
+        },
+    )
+}
+
+impl Stream for ServerEventStream {
+    type Item = ServerEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self {
+            messages,
+            control,
+            stopped,
+        } = self.get_mut();
+
+        while let Poll::Ready(Some(event)) = control.poll_recv(cx) {
+            match event {
+                ServerEvent::Stopped(_) => *stopped = Some(event),
+                event => return Poll::Ready(Some(event)),
+            }
+        }
+
+        // Hold on to Stopped until every queued message has been yielded. Both senders are owned
+        // by the listener, so once the message channel is closed the control channel has nothing
+        // more to give.
+        match messages.poll_recv(cx) {
+            Poll::Ready(Some(event)) => Poll::Ready(Some(event)),
+            Poll::Ready(None) => Poll::Ready(stopped.take()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
 fn convert_received_message(
     proto: crate::proto::chat_websocket::WebSocketRequestMessage,
     make_send_ack: impl FnOnce() -> ResponseEnvelopeSender,
@@ -110,8 +208,8 @@ fn convert_received_message(
 
     let path = path.unwrap_or_default();
     match &*path {
-        "/api/v1/queue/empty" => Ok(ServerEvent::QueueEmpty),
-        "/api/v1/message" => {
+        QUEUE_EMPTY_PATH => Ok(ServerEvent::QueueEmpty),
+        MESSAGE_PATH => {
             let raw_timestamp = headers
                 .iter()
                 .filter_map(|header| {
@@ -144,3 +242,171 @@ fn convert_received_message(
         _unknown_path => Err(ServerEventError::UnrecognizedPath(path)),
     }
 }
+
+/// How far a chat connection has gotten through the messages the server had queued for it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReceiveProgress {
+    /// How many envelopes the server has delivered since the connection was established.
+    pub envelopes_received: u64,
+    /// Whether the server has reported that there are no more queued messages to deliver.
+    ///
+    /// Messages sent after this point are still delivered, and still counted in
+    /// `envelopes_received`.
+    pub queue_drained: bool,
+}
+
+/// Keeps the [`ReceiveProgress`] for a single chat connection.
+#[derive(Debug)]
+pub(crate) struct ReceiveProgressTracker {
+    envelopes_received: AtomicU64,
+    queue_drained: AtomicBool,
+    event_sink: Option<SharedEventSink>,
+    log_tag: Arc<str>,
+}
+
+impl ReceiveProgressTracker {
+    pub(crate) fn new(event_sink: Option<SharedEventSink>, log_tag: Arc<str>) -> Self {
+        Self {
+            envelopes_received: AtomicU64::new(0),
+            queue_drained: AtomicBool::new(false),
+            event_sink,
+            log_tag,
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ReceiveProgress {
+        ReceiveProgress {
+            envelopes_received: self.envelopes_received.load(Ordering::Relaxed),
+            queue_drained: self.queue_drained.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wraps `listener` to count the server requests it receives.
+    ///
+    /// The count is updated before `listener` sees each request, so a listener that checks the
+    /// progress on [`ServerEvent::QueueEmpty`] sees every envelope delivered before it.
+    pub(crate) fn track(self: &Arc<Self>, mut listener: ws::EventListener) -> ws::EventListener {
+        let tracker = Arc::clone(self);
+        Box::new(move |event| {
+            if let ws::ListenerEvent::ReceivedMessage(proto, _) = &event {
+                tracker.observe(proto);
+            }
+            listener(event)
+        })
+    }
+
+    fn observe(&self, proto: &RequestProto) {
+        if proto.verb.as_deref() != Some(http::Method::PUT.as_str()) {
+            return;
+        }
+        match proto.path.as_deref() {
+            Some(MESSAGE_PATH) => {
+                self.envelopes_received.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(QUEUE_EMPTY_PATH) => {
+                if !self.queue_drained.swap(true, Ordering::Relaxed) {
+                    crate::events::record_to(self.event_sink.as_ref(), || NetEvent::QueueDrained {
+                        tag: self.log_tag.to_string(),
+                        envelopes_received: self.envelopes_received.load(Ordering::Relaxed),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use futures_util::StreamExt as _;
+    use nonzero_ext::nonzero;
+
+    use super::*;
+    use crate::chat::ChatConnection;
+
+    fn put_request(id: u64, path: &str, body: Option<&[u8]>) -> RequestProto {
+        RequestProto {
+            verb: Some("PUT".to_owned()),
+            path: Some(path.to_owned()),
+            body: body.map(Bytes::copy_from_slice),
+            headers: vec![format!("{TIMESTAMP_HEADER_NAME}: 1000")],
+            id: Some(id),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_yields_events_in_order_then_stopped() {
+        let (listener, mut events) = server_event_stream(nonzero!(1usize));
+        let (_chat, remote) =
+            ChatConnection::new_fake(tokio::runtime::Handle::current(), listener, ["alert"]);
+
+        remote
+            .send_request(put_request(1, "/api/v1/message", Some(b"envelope")))
+            .expect("connected");
+        remote
+            .send_request(put_request(2, "/api/v1/bogus", None))
+            .expect("connected");
+        remote
+            .send_request(put_request(3, "/api/v1/queue/empty", None))
+            .expect("connected");
+        remote.send_close(None).expect("connected");
+
+        assert_matches!(events.next().await, Some(ServerEvent::Alerts(alerts)) if alerts == ["alert"]);
+        assert_matches!(
+            events.next().await,
+            Some(ServerEvent::IncomingMessage { request_id: 1, envelope, server_delivery_timestamp, send_ack: _ })
+                if &*envelope == b"envelope" && server_delivery_timestamp.epoch_millis() == 1000
+        );
+        assert_matches!(events.next().await, Some(ServerEvent::QueueEmpty));
+        assert_matches!(events.next().await, Some(ServerEvent::Stopped(_)));
+        assert_matches!(events.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn receive_progress_counts_envelopes_and_queue_drain() {
+        let (listener, mut events) = server_event_stream(nonzero!(4usize));
+        let (chat, remote) =
+            ChatConnection::new_fake(tokio::runtime::Handle::current(), listener, []);
+        assert_eq!(chat.receive_progress(), ReceiveProgress::default());
+
+        for (id, path) in [
+            (1, MESSAGE_PATH),
+            (2, "/api/v1/bogus"),
+            (3, MESSAGE_PATH),
+            (4, QUEUE_EMPTY_PATH),
+            (5, MESSAGE_PATH),
+        ] {
+            remote
+                .send_request(put_request(id, path, Some(b"envelope")))
+                .expect("connected");
+        }
+
+        assert_matches!(
+            events.next().await,
+            Some(ServerEvent::IncomingMessage { .. })
+        );
+        assert_matches!(
+            events.next().await,
+            Some(ServerEvent::IncomingMessage { .. })
+        );
+        assert_matches!(events.next().await, Some(ServerEvent::QueueEmpty));
+        // The last message may or may not have been counted yet, but everything before the
+        // queue-empty marker has been.
+        let progress = chat.receive_progress();
+        assert!(progress.queue_drained);
+        assert!((2..=3).contains(&progress.envelopes_received));
+
+        assert_matches!(
+            events.next().await,
+            Some(ServerEvent::IncomingMessage { .. })
+        );
+        assert_eq!(
+            chat.receive_progress(),
+            ReceiveProgress {
+                envelopes_received: 3,
+                queue_drained: true,
+            }
+        );
+    }
+}
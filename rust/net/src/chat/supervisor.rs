@@ -0,0 +1,465 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Keeps a chat connection alive by reconnecting whenever it drops.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use libsignal_net_infra::route::{
+    AttemptOutcome, ConnectionOutcomeParams, ConnectionOutcomes, RouteDelayPolicy as _,
+    UnsuccessfulOutcome,
+};
+use libsignal_net_infra::ws::connection::FinishReason;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::chat::ws::{self, ListenerEvent};
+use crate::chat::{ChatConnection, ConnectError, ConnectionInfo, Request, Response, SendError};
+
+/// Makes a single attempt to connect to the chat service.
+///
+/// This is invoked again for every reconnect, so implementations should fetch fresh credentials
+/// (and any other headers) each time rather than capturing them up front.
+pub trait ChatConnector: Send + Sync + 'static {
+    fn connect(
+        &self,
+        listener: ws::EventListener,
+    ) -> BoxFuture<'_, Result<ChatConnection, ConnectError>>;
+}
+
+impl<F> ChatConnector for F
+where
+    F: Fn(ws::EventListener) -> BoxFuture<'static, Result<ChatConnection, ConnectError>>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn connect(
+        &self,
+        listener: ws::EventListener,
+    ) -> BoxFuture<'_, Result<ChatConnection, ConnectError>> {
+        self(listener)
+    }
+}
+
+/// Lifecycle events reported by a [`ChatConnectionSupervisor`].
+#[derive(Debug)]
+pub enum SupervisorEvent {
+    /// A new connection was established.
+    Connected(ConnectionInfo),
+    /// A connection attempt failed; the next one will start after `retry_after`.
+    ConnectFailed {
+        error: ConnectError,
+        retry_after: Duration,
+    },
+    /// An established connection ended.
+    ///
+    /// If `reconnecting` is false, the supervisor has stopped, because the connection was ended
+    /// on purpose (locally, or by the idle timeout) or because the server closed it in a way
+    /// that reconnecting won't fix.
+    Disconnected { reason: String, reconnecting: bool },
+    /// The supervisor stopped reconnecting because the server rejected this client outright.
+    GaveUp(ConnectError),
+}
+
+pub type SupervisorListener = Box<dyn FnMut(SupervisorEvent) + Send>;
+
+/// Owns a chat connection and reconnects, with backoff, whenever it ends.
+///
+/// Events other than [`ListenerEvent::Finished`] from each underlying connection are forwarded
+/// to the listener passed to [`start`](Self::start); the end of a connection is reported as a
+/// [`SupervisorEvent`] instead.
+///
+/// Dropping the supervisor stops it as well, but without waiting for the current connection to
+/// be disconnected.
+pub struct ChatConnectionSupervisor {
+    current: Arc<Mutex<Option<Arc<ChatConnection>>>>,
+    stop: Option<oneshot::Sender<()>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ChatConnectionSupervisor {
+    /// Starts connecting in the background.
+    ///
+    /// Delays between attempts are computed as for routes in
+    /// [`ConnectState`](crate::connect_state::ConnectState), using `backoff`.
+    pub fn start(
+        tokio_runtime: &tokio::runtime::Handle,
+        connector: impl ChatConnector,
+        backoff: ConnectionOutcomeParams,
+        listener: ws::EventListener,
+        on_event: SupervisorListener,
+    ) -> Self {
+        let current = Arc::new(Mutex::new(None));
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let task = tokio_runtime.spawn(supervise(
+            connector,
+            ConnectionOutcomes::new(backoff),
+            Arc::new(Mutex::new(listener)),
+            on_event,
+            Arc::clone(&current),
+            stop_rx,
+        ));
+        Self {
+            current,
+            stop: Some(stop_tx),
+            task,
+        }
+    }
+
+    /// The current connection, if there is one.
+    pub fn connection(&self) -> Option<Arc<ChatConnection>> {
+        self.current.lock().expect("not poisoned").clone()
+    }
+
+    /// Sends a request on the current connection.
+    ///
+    /// Fails with [`SendError::Disconnected`] if there's no connection right now.
+    pub async fn send(&self, msg: Request, timeout: Duration) -> Result<Response, SendError> {
+        let connection = self.connection().ok_or(SendError::Disconnected)?;
+        connection.send(msg, timeout).await
+    }
+
+    /// Stops reconnecting and disconnects the current connection, if any.
+    pub async fn stop(mut self) {
+        if let Some(stop) = self.stop.take() {
+            // The task might have already given up.
+            let _ = stop.send(());
+        }
+        if let Err(e) = (&mut self.task).await {
+            log::warn!("chat supervisor task failed: {e}");
+        }
+    }
+}
+
+async fn supervise(
+    connector: impl ChatConnector,
+    mut outcomes: ConnectionOutcomes<()>,
+    listener: Arc<Mutex<ws::EventListener>>,
+    mut on_event: SupervisorListener,
+    current: Arc<Mutex<Option<Arc<ChatConnection>>>>,
+    mut stop: oneshot::Receiver<()>,
+) {
+    // Set when the server asks us to wait longer than the backoff would.
+    let mut retry_later = Duration::ZERO;
+
+    loop {
+        let delay = outcomes.compute_delay(&(), Instant::now()).max(retry_later);
+        tokio::select! {
+            _ = &mut stop => return,
+            () = tokio::time::sleep(delay) => {}
+        }
+
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let started = Instant::now();
+        let result = tokio::select! {
+            _ = &mut stop => return,
+            result = connector.connect(forward_events(Arc::clone(&listener), finished_tx)) => result,
+        };
+
+        let connection = match result {
+            Ok(connection) => Arc::new(connection),
+            Err(error) => {
+                outcomes.apply_outcome_updates(
+                    [(
+                        (),
+                        AttemptOutcome {
+                            started,
+                            result: Err(UnsuccessfulOutcome),
+                        },
+                    )],
+                    Instant::now(),
+                );
+                if matches!(
+                    error,
+                    ConnectError::AppExpired | ConnectError::DeviceDeregistered
+                ) {
+                    log::warn!("chat supervisor giving up: {error}");
+                    on_event(SupervisorEvent::GaveUp(error));
+                    return;
+                }
+                retry_later = match &error {
+                    ConnectError::RetryLater(retry_later) => {
+                        Duration::from_secs(retry_later.retry_after_seconds.into())
+                    }
+                    _ => Duration::ZERO,
+                };
+                let retry_after = outcomes.compute_delay(&(), Instant::now()).max(retry_later);
+                log::info!("chat supervisor connect failed: {error}; retrying in {retry_after:?}");
+                on_event(SupervisorEvent::ConnectFailed { error, retry_after });
+                continue;
+            }
+        };
+
+        outcomes.apply_outcome_updates(
+            [(
+                (),
+                AttemptOutcome {
+                    started,
+                    result: Ok(()),
+                },
+            )],
+            Instant::now(),
+        );
+        retry_later = Duration::ZERO;
+        *current.lock().expect("not poisoned") = Some(Arc::clone(&connection));
+        on_event(SupervisorEvent::Connected(
+            connection.connection_info().clone(),
+        ));
+
+        let finished = tokio::select! {
+            _ = &mut stop => {
+                current.lock().expect("not poisoned").take();
+                connection.disconnect().await;
+                return;
+            }
+            finished = finished_rx => finished,
+        };
+        current.lock().expect("not poisoned").take();
+
+        let (reason, reconnecting) = describe_finish(finished);
+        if !reconnecting {
+            log::info!("chat supervisor connection ended ({reason}); stopping");
+            on_event(SupervisorEvent::Disconnected {
+                reason,
+                reconnecting,
+            });
+            return;
+        }
+        log::info!("chat supervisor connection ended ({reason}); reconnecting");
+        on_event(SupervisorEvent::Disconnected {
+            reason,
+            reconnecting,
+        });
+
+        // Count the drop as a failure so that a server that keeps dropping us gets backed off.
+        outcomes.apply_outcome_updates(
+            [(
+                (),
+                AttemptOutcome {
+                    started: Instant::now(),
+                    result: Err(UnsuccessfulOutcome),
+                },
+            )],
+            Instant::now(),
+        );
+    }
+}
+
+/// Describes how a connection ended, and whether it's worth reconnecting.
+fn describe_finish(
+    finished: Result<Result<FinishReason, ws::FinishError>, oneshot::error::RecvError>,
+) -> (String, bool) {
+    match finished {
+        // Someone disconnected this connection on purpose, or it went unused for long enough to
+        // be closed; respect that.
+        Ok(Ok(reason @ (FinishReason::LocalDisconnect | FinishReason::IdleTimeout))) => {
+            (reason.to_string(), false)
+        }
+        Ok(Ok(reason @ FinishReason::RemoteDisconnect)) => (reason.to_string(), true),
+        // Reconnecting would just kick off the other device, or fail with the same credentials.
+        Ok(Err(ws::FinishError::ServerClosed(
+            reason @ (ws::ServerCloseReason::ConnectedElsewhere
+            | ws::ServerCloseReason::ConnectionInvalidated),
+        ))) => (reason.to_string(), false),
+        Ok(Err(ws::FinishError::ServerClosed(reason @ ws::ServerCloseReason::Other { .. }))) => {
+            (reason.to_string(), true)
+        }
+        Ok(Err(ws::FinishError::Error(e))) => (e.to_string(), true),
+        Ok(Err(ws::FinishError::Unknown)) | Err(_) => ("unknown".to_owned(), true),
+    }
+}
+
+/// Forwards every event to `listener` except the end of the connection, which goes to
+/// `finished` instead.
+fn forward_events(
+    listener: Arc<Mutex<ws::EventListener>>,
+    finished: oneshot::Sender<Result<FinishReason, ws::FinishError>>,
+) -> ws::EventListener {
+    let mut finished = Some(finished);
+    Box::new(move |event| match event {
+        ListenerEvent::Finished(result) => {
+            if let Some(finished) = finished.take() {
+                // The supervisor might have stopped already.
+                let _ = finished.send(result);
+            }
+        }
+        event => (listener.lock().expect("not poisoned"))(event),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use assert_matches::assert_matches;
+    use futures_util::FutureExt as _;
+    use libsignal_net_infra::errors::RetryLater;
+    use test_case::test_case;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::chat::fake::FakeChatRemote;
+    use crate::connect_state::SUGGESTED_CONNECT_PARAMS;
+
+    fn event_channel() -> (SupervisorListener, mpsc::UnboundedReceiver<SupervisorEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Box::new(move |event| {
+                let _ = tx.send(event);
+            }),
+            rx,
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reconnects_after_remote_disconnect() {
+        let (remotes_tx, mut remotes_rx) = mpsc::unbounded_channel::<FakeChatRemote>();
+        let connector = move |listener: ws::EventListener| {
+            let (chat, remote) =
+                ChatConnection::new_fake(tokio::runtime::Handle::current(), listener, []);
+            remotes_tx.send(remote).expect("test is running");
+            std::future::ready(Ok(chat)).boxed()
+        };
+        let (on_event, mut events) = event_channel();
+
+        let supervisor = ChatConnectionSupervisor::start(
+            &tokio::runtime::Handle::current(),
+            connector,
+            SUGGESTED_CONNECT_PARAMS,
+            Box::new(|_| {}),
+            on_event,
+        );
+
+        assert_matches!(events.recv().await, Some(SupervisorEvent::Connected(_)));
+        let first_remote = remotes_rx.recv().await.expect("connected");
+        assert!(supervisor.connection().is_some());
+
+        first_remote.send_close(None).expect("still connected");
+        assert_matches!(
+            events.recv().await,
+            Some(SupervisorEvent::Disconnected { reason, reconnecting: true }) if reason == "remotedisconnect"
+        );
+
+        let start = Instant::now();
+        assert_matches!(events.recv().await, Some(SupervisorEvent::Connected(_)));
+        assert!(start.elapsed() > Duration::ZERO, "should have backed off");
+        let _second_remote = remotes_rx.recv().await.expect("reconnected");
+        assert!(supervisor.connection().is_some());
+
+        supervisor.stop().await;
+        assert_matches!(events.recv().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_and_gives_up_when_deregistered() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let connector = {
+            let attempts = Arc::clone(&attempts);
+            move |_listener: ws::EventListener| {
+                let error = match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => ConnectError::AllAttemptsFailed,
+                    1 => ConnectError::RetryLater(RetryLater {
+                        retry_after_seconds: 100,
+                    }),
+                    _ => ConnectError::DeviceDeregistered,
+                };
+                std::future::ready(Err(error)).boxed()
+            }
+        };
+        let (on_event, mut events) = event_channel();
+
+        let supervisor = ChatConnectionSupervisor::start(
+            &tokio::runtime::Handle::current(),
+            connector,
+            SUGGESTED_CONNECT_PARAMS,
+            Box::new(|_| {}),
+            on_event,
+        );
+
+        assert_matches!(
+            events.recv().await,
+            Some(SupervisorEvent::ConnectFailed {
+                error: ConnectError::AllAttemptsFailed,
+                retry_after,
+            }) if retry_after > Duration::ZERO
+        );
+        assert_matches!(
+            events.recv().await,
+            Some(SupervisorEvent::ConnectFailed {
+                error: ConnectError::RetryLater(_),
+                retry_after,
+            }) if retry_after >= Duration::from_secs(100)
+        );
+        let start = Instant::now();
+        assert_matches!(
+            events.recv().await,
+            Some(SupervisorEvent::GaveUp(ConnectError::DeviceDeregistered))
+        );
+        assert!(start.elapsed() >= Duration::from_secs(100));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(supervisor.connection().is_none());
+
+        supervisor.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_when_connected_elsewhere() {
+        let connector = move |listener: ws::EventListener| {
+            let (chat, remote) =
+                ChatConnection::new_fake(tokio::runtime::Handle::current(), listener, []);
+            remote
+                .send_close(Some(crate::env::CONNECTED_ELSEWHERE_CLOSE_CODE))
+                .expect("still connected");
+            std::future::ready(Ok(chat)).boxed()
+        };
+        let (on_event, mut events) = event_channel();
+
+        let supervisor = ChatConnectionSupervisor::start(
+            &tokio::runtime::Handle::current(),
+            connector,
+            SUGGESTED_CONNECT_PARAMS,
+            Box::new(|_| {}),
+            on_event,
+        );
+
+        assert_matches!(events.recv().await, Some(SupervisorEvent::Connected(_)));
+        assert_matches!(
+            events.recv().await,
+            Some(SupervisorEvent::Disconnected {
+                reconnecting: false,
+                ..
+            })
+        );
+        // The supervisor task has finished, so no more events will arrive.
+        assert_matches!(events.recv().await, None);
+        assert!(supervisor.connection().is_none());
+
+        supervisor.stop().await;
+    }
+
+    #[test_case(Ok(FinishReason::LocalDisconnect) => false; "local disconnect")]
+    #[test_case(Ok(FinishReason::IdleTimeout) => false; "idle timeout")]
+    #[test_case(Ok(FinishReason::RemoteDisconnect) => true; "remote disconnect")]
+    #[test_case(
+        Err(ws::FinishError::ServerClosed(ws::ServerCloseReason::ConnectedElsewhere)) => false;
+        "connected elsewhere"
+    )]
+    #[test_case(
+        Err(ws::FinishError::ServerClosed(ws::ServerCloseReason::ConnectionInvalidated)) => false;
+        "connection invalidated"
+    )]
+    #[test_case(
+        Err(ws::FinishError::ServerClosed(ws::ServerCloseReason::Other { code: 1011, reason: "".into() })) => true;
+        "other server close"
+    )]
+    #[test_case(Err(ws::FinishError::Unknown) => true; "unknown")]
+    fn reconnects_only_when_useful(finished: Result<FinishReason, ws::FinishError>) -> bool {
+        let (_reason, reconnecting) = describe_finish(Ok(finished));
+        reconnecting
+    }
+}
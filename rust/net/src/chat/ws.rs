@@ -11,10 +11,10 @@ use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use futures_util::stream::PollNext;
 use futures_util::{pin_mut, Stream, StreamExt as _};
 use http::uri::PathAndQuery;
 use http::{Method, StatusCode};
-use itertools::Itertools as _;
 pub use libsignal_net_infra::ws::connection::FinishReason;
 use libsignal_net_infra::ws::connection::Outcome;
 use libsignal_net_infra::ws::{WebSocketError, WebSocketStreamLike};
@@ -26,11 +26,16 @@ use tokio::time::Duration;
 use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
 use tungstenite::protocol::frame::coding::CloseCode;
 
-use crate::chat::{ChatMessageType, MessageProto, Request, RequestProto, Response, ResponseProto};
-use crate::env::{
-    ALERT_HEADER_NAME, CONNECTED_ELSEWHERE_CLOSE_CODE, CONNECTION_INVALIDATED_CLOSE_CODE,
+use crate::chat::{
+    ChatMessageType, MessageProto, Request, RequestPriority, RequestProto, Response, ResponseProto,
+    ServerConnectionMetadata,
 };
-use crate::infra::ws::connection::{MessageEvent, NextEventError, TungsteniteSendError};
+use crate::env::{CONNECTED_ELSEWHERE_CLOSE_CODE, CONNECTION_INVALIDATED_CLOSE_CODE};
+use crate::events::ChatStateMonitor;
+use crate::infra::ws::connection::{
+    LocalCloseFrame, MessageEvent, NextEventError, TungsteniteSendError,
+};
+use crate::infra::ws::keepalive::AdaptiveKeepalive;
 use crate::infra::ws::TextOrBinary;
 
 /// Chat service avilable via a connected websocket.
@@ -49,6 +54,9 @@ pub struct Chat {
     /// points. If it were a regular [`Mutex`] the futures produced by methods
     /// on `Chat` would not be `Send`.
     state: TokioMutex<TaskState>,
+
+    /// The close frame sent to the server when disconnecting.
+    local_close_frame: LocalCloseFrame,
 }
 
 /// Instantiation-time configuration for a [`Chat`] instance.
@@ -70,6 +78,31 @@ pub struct Config {
 
     /// The value to use as the ID for the first outgoing request.
     pub initial_request_id: u64,
+
+    /// The largest single frame that will be accepted from the server, or `None` for no limit.
+    pub max_frame_size: Option<usize>,
+
+    /// The largest message that will be accepted from the server, or `None` for no limit.
+    pub max_message_size: Option<usize>,
+
+    /// How long to keep the connection open with no requests or responses
+    /// going in either direction, or `None` to keep it open indefinitely.
+    ///
+    /// When this elapses, the connection is closed and the listener receives
+    /// [`ListenerEvent::Finished`] with [`FinishReason::IdleTimeout`].
+    pub application_idle_timeout: Option<Duration>,
+
+    /// Limits on how quickly requests can be sent, or `None` to send them as
+    /// fast as they are made.
+    ///
+    /// This is enforced by [`ChatConnection::send`](crate::chat::ChatConnection::send).
+    pub rate_limit: Option<crate::chat::RateLimitConfig>,
+
+    /// Limits on how many requests can be outstanding at once, or `None` for
+    /// no limit.
+    ///
+    /// This is enforced by [`ChatConnection::send`](crate::chat::ChatConnection::send).
+    pub in_flight_limit: Option<crate::chat::InFlightLimitConfig>,
 }
 
 #[derive(Debug)]
@@ -132,8 +165,35 @@ pub enum InvalidRequestError {
     InvalidHeader,
 }
 
+/// Why the server closed the connection, decoded from its close frame.
+#[derive(Clone, Debug, PartialEq, Eq, displaydoc::Display)]
+pub enum ServerCloseReason {
+    /// the server closed the connection because we connected elsewhere with the same credentials
+    ConnectedElsewhere,
+    /// the server closed the connection because our credentials were invalidated
+    ConnectionInvalidated,
+    /// the server closed the connection with code {code}
+    Other { code: u16, reason: String },
+}
+
+impl ServerCloseReason {
+    fn from_close_frame(code: CloseCode, reason: String) -> Self {
+        match code {
+            CloseCode::Library(CONNECTED_ELSEWHERE_CLOSE_CODE) => Self::ConnectedElsewhere,
+            CloseCode::Library(CONNECTION_INVALIDATED_CLOSE_CODE) => Self::ConnectionInvalidated,
+            code => Self::Other {
+                code: code.into(),
+                reason,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FinishError {
+    /// The server closed the connection with a close code other than a normal
+    /// closure.
+    ServerClosed(ServerCloseReason),
     Error(TaskExitError),
     /// The task exited for an unknown reason.
     ///
@@ -142,6 +202,17 @@ pub enum FinishError {
     Unknown,
 }
 
+impl From<TaskExitError> for FinishError {
+    fn from(value: TaskExitError) -> Self {
+        match value {
+            TaskExitError::WebsocketError(NextEventError::AbnormalServerClose { code, reason }) => {
+                Self::ServerClosed(ServerCloseReason::from_close_frame(code, reason))
+            }
+            e => Self::Error(e),
+        }
+    }
+}
+
 /// Sends a response to an incoming [`RequestProto`] to the server.
 #[derive(Debug)]
 pub struct Responder {
@@ -164,12 +235,22 @@ pub struct Responder {
 
 pub type EventListener = Box<dyn FnMut(ListenerEvent) + Send>;
 
+/// Per-connection behavior that comes from the
+/// [`ConnectState`](crate::connect_state::ConnectState) the connection was made
+/// with.
+#[derive(Debug, Default)]
+pub struct ConnectStateHooks {
+    pub adaptive_keepalive: Option<AdaptiveKeepalive>,
+    pub state_monitor: Option<ChatStateMonitor>,
+}
+
 impl Chat {
     pub fn new<T>(
         tokio_runtime: tokio::runtime::Handle,
         transport: T,
         connect_response_headers: http::HeaderMap,
         config: Config,
+        hooks: ConnectStateHooks,
         log_tag: Arc<str>,
         mut listener: EventListener,
     ) -> Self
@@ -180,10 +261,21 @@ impl Chat {
             initial_request_id,
             local_idle_timeout,
             remote_idle_timeout,
+            max_frame_size,
+            max_message_size,
+            application_idle_timeout,
+            rate_limit: _,
+            in_flight_limit: _,
         } = config;
+        let ConnectStateHooks {
+            adaptive_keepalive,
+            state_monitor,
+        } = hooks;
 
         Self::report_alerts(connect_response_headers, &mut listener);
 
+        let local_close_frame = LocalCloseFrame::default();
+
         // Enable access to tokio types like Sleep, but only for the duration of this call.
         let _enable_tokio_types = tokio_runtime.enter();
         Self::new_inner(
@@ -193,9 +285,16 @@ impl Chat {
                     local_idle_timeout,
                     remote_idle_ping_timeout: local_idle_timeout,
                     remote_idle_disconnect_timeout: remote_idle_timeout,
+                    max_frame_size,
+                    max_message_size,
+                    application_idle_timeout,
                 },
+                adaptive_keepalive,
+                local_close_frame.clone(),
             ),
             initial_request_id,
+            state_monitor,
+            local_close_frame,
             log_tag,
             listener,
             tokio_runtime,
@@ -203,17 +302,10 @@ impl Chat {
     }
 
     fn report_alerts(connect_response_headers: http::HeaderMap, listener: &mut EventListener) {
-        let alerts = connect_response_headers
-            .get_all(ALERT_HEADER_NAME)
-            .iter()
-            .flat_map(|value| {
-                value
-                    .to_str()
-                    .unwrap_or("[non-ASCII alert]")
-                    .split_terminator(',')
-                    .map(|individual_value| individual_value.trim_ascii().to_owned())
-            })
-            .collect_vec();
+        let ServerConnectionMetadata {
+            alerts,
+            server_timestamp: _,
+        } = ServerConnectionMetadata::from_headers(&connect_response_headers);
         listener(ListenerEvent::ReceivedAlerts(alerts))
     }
 
@@ -222,6 +314,17 @@ impl Chat {
     /// If the request can't be sent or the response isn't received, this
     /// returns an error.
     pub async fn send(&self, request: Request) -> Result<Response, SendError> {
+        self.send_with_priority(request, RequestPriority::Normal)
+            .await
+    }
+
+    /// Like [`Self::send`], but requests with [`RequestPriority::High`] are
+    /// sent ahead of any normal-priority requests still waiting to go out.
+    pub async fn send_with_priority(
+        &self,
+        request: Request,
+        priority: RequestPriority,
+    ) -> Result<Response, SendError> {
         let Self { state } = self;
 
         let Request {
@@ -243,7 +346,7 @@ impl Chat {
             headers,
         };
 
-        send_request(state, request).await
+        send_request(state, request, priority).await
     }
 
     /// Requests a graceful disconnect from the server.
@@ -263,13 +366,14 @@ impl Chat {
         let new_state = match state {
             TaskState::MaybeStillRunning {
                 request_tx,
+                priority_request_tx,
                 response_tx,
                 task,
             } => {
                 // Signal to the task, if it's still running, that it should
                 // quit. Do this by hanging up on it, at which point it will
                 // exit.
-                drop((request_tx, response_tx));
+                drop((request_tx, priority_request_tx, response_tx));
                 TaskState::SignaledToEnd(task)
             }
             state @ (TaskState::SignaledToEnd(_) | TaskState::Finished(_)) => state,
@@ -277,6 +381,13 @@ impl Chat {
         *guard = new_state
     }
 
+    /// Like [`Self::disconnect`], but tells the server why with the given
+    /// close code and reason.
+    pub async fn disconnect_with_close_frame(&self, code: CloseCode, reason: &str) {
+        self.local_close_frame.set(code, reason);
+        self.disconnect().await
+    }
+
     /// Returns `true` if the websocket is known to be connected.
     ///
     /// If this returns `false`, the websocket is either disconnected or in the
@@ -289,6 +400,7 @@ impl Chat {
             TaskState::SignaledToEnd(_) | TaskState::Finished(_) => false,
             TaskState::MaybeStillRunning {
                 request_tx: _,
+                priority_request_tx: _,
                 response_tx: _,
                 task,
             } => {
@@ -313,11 +425,14 @@ impl Chat {
     fn new_inner(
         into_inner_connection: impl IntoInnerConnection,
         initial_request_id: u64,
+        state_monitor: Option<ChatStateMonitor>,
+        local_close_frame: LocalCloseFrame,
         log_tag: Arc<str>,
         listener: EventListener,
         tokio_runtime: tokio::runtime::Handle,
     ) -> Self {
         let (request_tx, request_rx) = mpsc::channel(1);
+        let (priority_request_tx, priority_request_rx) = mpsc::channel(1);
         let (response_tx, response_rx) = mpsc::unbounded_channel();
 
         let requests_in_flight = InFlightRequests {
@@ -326,7 +441,13 @@ impl Chat {
         };
 
         let mut request_id = initial_request_id;
-        let request_rx = ReceiverStream::new(request_rx).map(move |request: OutgoingRequest| {
+        // Always drain the priority lane first.
+        let request_rx = futures_util::stream::select_with_strategy(
+            ReceiverStream::new(priority_request_rx),
+            ReceiverStream::new(request_rx),
+            |_: &mut ()| PollNext::Left,
+        );
+        let request_rx = request_rx.map(move |request: OutgoingRequest| {
             let id = {
                 let next_id = request_id.wrapping_add(1);
                 std::mem::replace(&mut request_id, next_id)
@@ -351,6 +472,7 @@ impl Chat {
         let connection = ConnectionImpl {
             inner: inner_connection,
             requests_in_flight,
+            state_monitor,
         };
 
         let task = tokio_runtime.spawn(spawned_task_body(
@@ -361,12 +483,14 @@ impl Chat {
         ));
         let state = TaskState::MaybeStillRunning {
             request_tx,
+            priority_request_tx,
             response_tx,
             task,
         };
 
         Self {
             state: TokioMutex::new(state),
+            local_close_frame,
         }
     }
 }
@@ -398,6 +522,7 @@ enum TaskState {
     /// The task isn't known to have finished, and might still be listening for events.
     MaybeStillRunning {
         request_tx: mpsc::Sender<OutgoingRequest>,
+        priority_request_tx: mpsc::Sender<OutgoingRequest>,
         response_tx: mpsc::UnboundedSender<OutgoingResponse>,
         task: JoinHandle<Result<FinishReason, TaskErrorState>>,
     },
@@ -542,6 +667,7 @@ struct ConnectionImpl<I> {
     #[pin]
     inner: I,
     requests_in_flight: InFlightRequests,
+    state_monitor: Option<ChatStateMonitor>,
 }
 
 /// The metadata for an outgoing message.
@@ -670,7 +796,7 @@ async fn spawned_task_body<I: InnerConnection>(
     listener
         .send_event(
             &tokio_rt,
-            ListenerEvent::Finished(result.map_err(FinishError::Error)),
+            ListenerEvent::Finished(result.map_err(FinishError::from)),
         )
         .await;
 
@@ -703,6 +829,7 @@ async fn get_task_finish_error(state: &TokioMutex<TaskState>, _reason: &'static
 async fn send_request(
     state: &TokioMutex<TaskState>,
     request: PartialRequestProto,
+    priority: RequestPriority,
 ) -> Result<Response, SendError> {
     // Use a block to limit the scope of the lock guard's lifetime. We don't
     // want the lock to be held for the entire send, just the outgoing bit.
@@ -710,9 +837,13 @@ async fn send_request(
         match &mut *state.lock().await {
             TaskState::MaybeStillRunning {
                 request_tx,
+                priority_request_tx,
                 response_tx: _,
                 task: _,
-            } => request_tx.clone(),
+            } => match priority {
+                RequestPriority::Normal => request_tx.clone(),
+                RequestPriority::High => priority_request_tx.clone(),
+            },
             TaskState::SignaledToEnd(_) => {
                 return Err(SendError::Disconnected(DisconnectedReason::SocketClosed {
                     #[cfg(test)]
@@ -767,6 +898,7 @@ async fn wait_for_task_to_finish(state: &mut TaskState) -> &Result<FinishReason,
         TaskState::MaybeStillRunning {
             task,
             request_tx: _,
+            priority_request_tx: _,
             response_tx: _,
         } => {
             // The send can only fail if the task has ended since it owns the
@@ -853,7 +985,13 @@ trait IntoInnerConnection {
         R: Stream<Item = (TextOrBinary, OutgoingMeta)> + Send + 'static;
 }
 
-impl<S> IntoInnerConnection for (S, crate::infra::ws::Config)
+impl<S> IntoInnerConnection
+    for (
+        S,
+        crate::infra::ws::Config,
+        Option<AdaptiveKeepalive>,
+        LocalCloseFrame,
+    )
 where
     S: WebSocketStreamLike + Send + 'static,
 {
@@ -865,8 +1003,14 @@ where
     where
         R: Stream<Item = (TextOrBinary, OutgoingMeta)> + Send + 'static,
     {
-        let (stream, config) = self;
-        crate::infra::ws::Connection::new(stream, outgoing_stream, config, log_tag)
+        let (stream, config, adaptive_keepalive, local_close_frame) = self;
+        let connection =
+            crate::infra::ws::Connection::new(stream, outgoing_stream, config, log_tag)
+                .with_local_close_frame(local_close_frame);
+        match adaptive_keepalive {
+            Some(keepalive) => connection.with_adaptive_keepalive(keepalive),
+            None => connection,
+        }
     }
 }
 
@@ -903,11 +1047,27 @@ impl<I: InnerConnection> ConnectionImpl<I> {
         let ConnectionImplProj {
             mut inner,
             requests_in_flight,
+            state_monitor,
         } = self.project();
 
         let inner_event = inner.as_mut().handle_next_event().await;
 
-        Self::handle_inner_response(requests_in_flight, inner_event)
+        if let Some(state_monitor) = state_monitor.as_mut() {
+            match &inner_event {
+                Outcome::Continue(MessageEvent::SentPing) => state_monitor.sent_ping(),
+                Outcome::Continue(
+                    MessageEvent::ReceivedPingPong | MessageEvent::ReceivedMessage(_),
+                ) => state_monitor.heard_from_server(),
+                Outcome::Continue(MessageEvent::SentMessage(_) | MessageEvent::SendFailed(..))
+                | Outcome::Finished(_) => {}
+            }
+        }
+
+        let outcome = Self::handle_inner_response(requests_in_flight, inner_event);
+        if let (Some(state_monitor), Outcome::Finished(result)) = (state_monitor, &outcome) {
+            state_monitor.finished(result);
+        }
+        outcome
     }
 
     fn handle_inner_response(
@@ -1236,6 +1396,16 @@ impl From<TaskExitError> for crate::chat::SendError {
     }
 }
 
+impl From<ServerCloseReason> for crate::chat::SendError {
+    fn from(value: ServerCloseReason) -> Self {
+        match value {
+            ServerCloseReason::ConnectedElsewhere => Self::ConnectedElsewhere,
+            ServerCloseReason::ConnectionInvalidated => Self::ConnectionInvalidated,
+            ServerCloseReason::Other { .. } => Self::WebSocket(WebSocketError::ChannelClosed),
+        }
+    }
+}
+
 impl From<SendError> for super::SendError {
     fn from(value: SendError) -> Self {
         match value {
@@ -1269,12 +1439,15 @@ mod test {
 
     use assert_matches::assert_matches;
     use futures_util::stream::FuturesUnordered;
+    use futures_util::FutureExt as _;
     use http::HeaderMap;
+    use itertools::Itertools as _;
     use test_case::test_case;
     use tokio::select;
     use tokio::sync::mpsc::error::TryRecvError;
 
     use super::*;
+    use crate::env::ALERT_HEADER_NAME;
 
     mod fake {
         use futures_util::future::Either;
@@ -1331,6 +1504,8 @@ mod test {
                     incoming_events: incoming_events_rx,
                 },
                 initial_request_id,
+                None,
+                LocalCloseFrame::default(),
                 "test".into(),
                 listener,
                 tokio::runtime::Handle::current(),
@@ -1555,6 +1730,40 @@ mod test {
         assert_eq!(received_responses, expected_responses);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn high_priority_requests_skip_the_queue() {
+        let (chat, (mut chat_events, _inner_responses)) = fake::new_chat(Box::new(|_| ()));
+
+        let request = |path| Request {
+            method: Method::GET,
+            path: PathAndQuery::from_static(path),
+            headers: HeaderMap::new(),
+            body: None,
+        };
+
+        // Queue both requests before the connection task gets a chance to run.
+        let mut normal = std::pin::pin!(chat.send(request("/normal")));
+        let mut high =
+            std::pin::pin!(chat.send_with_priority(request("/urgent"), RequestPriority::High));
+        assert_matches!(normal.as_mut().now_or_never(), None);
+        assert_matches!(high.as_mut().now_or_never(), None);
+
+        let mut sent_paths = vec![];
+        for _ in 0..2 {
+            let fake::OutgoingMessage(message, _meta) =
+                chat_events.recv().await.expect("not ended");
+            let TextOrBinary::Binary(message) = message else {
+                panic!("expected binary message");
+            };
+            let request = assert_matches!(
+                decode_and_validate(&message),
+                Ok(ChatMessageProto::Request(request)) => request
+            );
+            sent_paths.push(request.path.expect("has path"));
+        }
+        assert_eq!(sent_paths, ["/urgent", "/normal"]);
+    }
+
     #[test_log::test(tokio::test(start_paused = true))]
     async fn receives_incoming_server_requests_and_responds() {
         const INITIAL_INCOMING_REQUEST_ID: u64 = 88;
@@ -1979,25 +2188,29 @@ mod test {
             .await
             .expect("should receive an event");
 
-        // Extract the TaskExitError from the listener event
-        let task_exit_error = match listener_event {
-            ListenerEvent::Finished(Err(FinishError::Error(
-                task_exit_error @ TaskExitError::WebsocketError(
-                    NextEventError::AbnormalServerClose { code, reason: _ },
-                ),
-            ))) => {
-                assert_eq!(code, close_code);
-                task_exit_error
-            }
+        // Extract the ServerCloseReason from the listener event
+        let reason = match listener_event {
+            ListenerEvent::Finished(Err(FinishError::ServerClosed(reason))) => reason,
             other => panic!("Unexpected listener event: {other:?}"),
         };
+        assert_eq!(
+            reason,
+            ServerCloseReason::from_close_frame(close_code, format!("close code: {close_code}"))
+        );
 
-        // Convert the TaskExitError to a SendError, which is closest to what is eventually passed up to
-        //   the clients across the bridge.
-        let actual_error: crate::chat::SendError = task_exit_error.into();
+        // Convert the ServerCloseReason to a SendError, which is closest to what is eventually
+        //   passed up to the clients across the bridge.
+        let actual_error: crate::chat::SendError = reason.into();
         actual_error
     }
 
+    #[test_case(CloseCode::from(CONNECTED_ELSEWHERE_CLOSE_CODE) => ServerCloseReason::ConnectedElsewhere)]
+    #[test_case(CloseCode::from(CONNECTION_INVALIDATED_CLOSE_CODE) => ServerCloseReason::ConnectionInvalidated)]
+    #[test_case(CloseCode::Away => ServerCloseReason::Other { code: 1001, reason: "bye".to_owned() })]
+    fn server_close_reason_from_close_frame(code: CloseCode) -> ServerCloseReason {
+        ServerCloseReason::from_close_frame(code, "bye".to_owned())
+    }
+
     impl From<MessageProto> for TextOrBinary {
         fn from(proto: MessageProto) -> Self {
             TextOrBinary::Binary(proto.encode_to_vec().into())
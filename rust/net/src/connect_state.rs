@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::HashMap;
 use std::default::Default;
 use std::fmt::Debug;
 use std::future::Future;
@@ -13,28 +14,30 @@ use std::time::Duration;
 use futures_util::TryFutureExt as _;
 use http::HeaderName;
 use itertools::Itertools as _;
-use libsignal_net_infra::dns::DnsResolver;
+use libsignal_net_infra::dns::{DnsResolutionInfo, DnsResolver};
 use libsignal_net_infra::errors::{LogSafeDisplay, TransportConnectError};
+use libsignal_net_infra::host::Host;
 use libsignal_net_infra::route::{
-    ComposedConnector, ConnectError, ConnectionOutcomeParams, ConnectionOutcomes,
-    ConnectionProxyKind, Connector, ConnectorFactory, DelayBasedOnTransport, DescribeForLog,
-    DescribedRouteConnector, DirectOrProxy, HttpRouteFragment, InterfaceChangedOr,
-    InterfaceMonitor, LoggingConnector, ResettingConnectionOutcomes, ResolveHostnames,
-    ResolveWithSavedDescription, ResolvedRoute, RouteProvider, RouteProviderContext,
-    RouteProviderExt as _, RouteResolver, StaticTcpTimeoutConnector, ThrottlingConnector,
-    TransportRoute, UnresolvedRouteDescription, UnresolvedTransportRoute,
+    AttemptTimeoutConnector, ComposedConnector, ConnectError, ConnectionOutcomeParams,
+    ConnectionOutcomes, ConnectionProxyKind, Connector, ConnectorFactory, DelayBasedOnTransport,
+    DescribeForLog, DescribedRouteConnector, DirectOrProxy, HttpRouteFragment, InterfaceChangedOr,
+    InterfaceMonitor, IpPreference, LoggingConnector, ResettingConnectionOutcomes,
+    ResolveHostnames, ResolveWithSavedDescription, ResolvedRoute, RouteProvider,
+    RouteProviderContext, RouteProviderExt as _, RouteResolver, StaticTcpTimeoutConnector,
+    ThrottlingConnector, TransportRoute, UnresolvedRouteDescription, UnresolvedTransportRoute,
     UnresolvedWebsocketServiceRoute, UsePreconnect, UsesTransport, VariableTlsTimeoutConnector,
     WebSocketRouteFragment, WebSocketServiceRoute,
 };
 use libsignal_net_infra::tcp_ssl::{LONG_TCP_HANDSHAKE_THRESHOLD, LONG_TLS_HANDSHAKE_THRESHOLD};
 use libsignal_net_infra::timeouts::{
     TimeoutOr, MIN_TLS_HANDSHAKE_TIMEOUT, NETWORK_INTERFACE_POLL_INTERVAL,
-    ONE_ROUTE_CONNECTION_TIMEOUT, POST_ROUTE_CHANGE_CONNECTION_TIMEOUT,
+    ONE_ROUTE_CONNECTION_TIMEOUT, POST_ROUTE_CHANGE_CONNECTION_TIMEOUT, ROUTE_ATTEMPT_TIMEOUT,
 };
 use libsignal_net_infra::utils::NetworkChangeEvent;
 use libsignal_net_infra::ws::attested::AttestedConnection;
+use libsignal_net_infra::ws::keepalive::AdaptiveKeepalive;
 use libsignal_net_infra::ws::WebSocketConnectError;
-use libsignal_net_infra::{AsHttpHeader as _, AsyncDuplexStream};
+use libsignal_net_infra::{AsHttpHeader as _, AsyncDuplexStream, RouteType};
 use rand::distr::uniform::{UniformSampler, UniformUsize};
 use rand_core::{OsRng, UnwrapErr};
 use static_assertions::assert_eq_size_val;
@@ -42,7 +45,15 @@ use tokio::time::Instant;
 
 use crate::auth::Auth;
 use crate::enclave::{EndpointParams, NewHandshake};
-use crate::ws::WebSocketServiceConnectError;
+use crate::events::{
+    as_millis, record_to, ConnectionState, ConnectionStateReporter, ConnectionStateUpdate,
+    ConnectionStateUpdates, DisconnectCause, NetEvent, SharedEventSink,
+};
+use crate::trace::TraceId;
+use crate::ws::{ErrorClass, WebSocketServiceConnectError};
+
+mod latency;
+pub use latency::{LatencyHistogram, LATENCY_BUCKET_BOUNDS};
 
 /// Suggested values for [`ConnectionOutcomeParams`].
 pub const SUGGESTED_CONNECT_PARAMS: ConnectionOutcomeParams = ConnectionOutcomeParams {
@@ -57,6 +68,8 @@ pub const SUGGESTED_CONNECT_PARAMS: ConnectionOutcomeParams = ConnectionOutcomeP
 pub const SUGGESTED_CONNECT_CONFIG: Config = Config {
     connect_params: SUGGESTED_CONNECT_PARAMS,
     connect_timeout: ONE_ROUTE_CONNECTION_TIMEOUT,
+    per_attempt_timeout: ROUTE_ATTEMPT_TIMEOUT,
+    ip_preference: IpPreference::PreferIpv6,
     network_interface_poll_interval: NETWORK_INTERFACE_POLL_INTERVAL,
     post_route_change_connect_timeout: POST_ROUTE_CHANGE_CONNECTION_TIMEOUT,
 };
@@ -93,8 +106,10 @@ impl<F, Transport> WebSocketTransportConnectorFactory<Transport> for F where
 /// Templated over the type of the transport connector to support testing.
 pub struct ConnectState<ConnectorFactory = DefaultConnectorFactory> {
     pub route_resolver: RouteResolver,
-    /// The amount of time allowed for each connection attempt.
+    /// The amount of time allowed for a whole connect operation, across all routes.
     pub connect_timeout: Duration,
+    /// The amount of time allowed for any single route to connect.
+    pub per_attempt_timeout: Duration,
     /// How often to check if the network interface has changed, given no other info.
     network_interface_poll_interval: Duration,
     /// The amount of time allowed for a connection attempt after a network change.
@@ -105,6 +120,16 @@ pub struct ConnectState<ConnectorFactory = DefaultConnectorFactory> {
     attempts_record: ConnectionOutcomes<TransportRoute>,
     /// [`RouteProviderContext`] passed to route providers.
     route_provider_context: RouteProviderContextImpl,
+    /// Receives lifecycle events for connections made with this state, if set.
+    pub event_sink: Option<SharedEventSink>,
+    /// Tunes the keepalive interval of chat connections made with this state, if set.
+    ///
+    /// Callers should update [`AdaptiveKeepalive::network`] when the network changes.
+    pub adaptive_keepalive: Option<AdaptiveKeepalive>,
+    /// Subscribers to [`ConnectionStateUpdate`]s for connections made with this state.
+    state_updates: ConnectionStateUpdates,
+    /// How long successful connect operations took, by the type of route that succeeded.
+    connect_latencies: HashMap<RouteType, LatencyHistogram>,
 }
 
 pub type DefaultTransportConnector = VariableTlsTimeoutConnector<
@@ -120,7 +145,13 @@ pub type DefaultTransportConnector = VariableTlsTimeoutConnector<
 #[derive(Clone, Debug, PartialEq)]
 pub struct Config {
     pub connect_params: ConnectionOutcomeParams,
+    /// Overall deadline for a connect operation, including every route attempted.
     pub connect_timeout: Duration,
+    /// Deadline for a single route, from the start of its transport connection through the end
+    /// of the websocket handshake.
+    pub per_attempt_timeout: Duration,
+    /// Which address families to connect over, and in what order.
+    pub ip_preference: IpPreference,
     pub network_interface_poll_interval: Duration,
     pub post_route_change_connect_timeout: Duration,
 }
@@ -176,17 +207,27 @@ impl<ConnectorFactory> ConnectState<ConnectorFactory> {
         let Config {
             connect_params,
             connect_timeout,
+            per_attempt_timeout,
+            ip_preference,
             network_interface_poll_interval,
             post_route_change_connect_timeout,
         } = config;
         Self {
-            route_resolver: RouteResolver::default(),
+            route_resolver: RouteResolver {
+                ip_preference,
+                ..Default::default()
+            },
             connect_timeout,
+            per_attempt_timeout,
             network_interface_poll_interval,
             post_route_change_connect_timeout,
             make_transport_connector,
             attempts_record: ConnectionOutcomes::new(connect_params),
             route_provider_context: RouteProviderContextImpl::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: HashMap::new(),
         }
         .into()
     }
@@ -194,17 +235,42 @@ impl<ConnectorFactory> ConnectState<ConnectorFactory> {
     pub fn network_changed(&mut self, network_change_time: Instant) {
         self.attempts_record.reset(network_change_time);
     }
+
+    /// Returns a copy of the connect latencies recorded so far, by route type.
+    ///
+    /// Only successful connect operations are recorded, and the time includes any attempts on
+    /// other routes that failed first.
+    pub fn connect_latency_snapshot(&self) -> HashMap<RouteType, LatencyHistogram> {
+        self.connect_latencies.clone()
+    }
+
+    /// Subscribes to state changes for connections made with this state from now on.
+    ///
+    /// Chat connections report every kind of [`ConnectionState`]; other connections only
+    /// report the outcome of connecting.
+    pub fn subscribe_to_state_updates(
+        &mut self,
+    ) -> tokio::sync::broadcast::Receiver<ConnectionStateUpdate> {
+        self.state_updates.subscribe()
+    }
+
+    /// Returns a reporter for state changes on an established connection tagged `tag`, if
+    /// anyone is subscribed.
+    pub(crate) fn state_reporter(&self, tag: &str) -> Option<ConnectionStateReporter> {
+        self.state_updates.reporter(tag)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct RouteInfo {
     unresolved: UnresolvedRouteDescription,
+    dns: Option<DnsResolutionInfo>,
 }
 
 impl LogSafeDisplay for RouteInfo {}
 impl std::fmt::Display for RouteInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Self { unresolved } = self;
+        let Self { unresolved, dns: _ } = self;
         (unresolved as &dyn LogSafeDisplay).fmt(f)
     }
 }
@@ -218,9 +284,18 @@ impl RouteInfo {
         self.unresolved.domain_front()
     }
 
+    /// How the target host was resolved, if it was resolved locally.
+    ///
+    /// This is `None` for proxied connections, where the proxy is responsible for resolving the
+    /// target.
+    pub fn dns(&self) -> Option<&DnsResolutionInfo> {
+        self.dns.as_ref()
+    }
+
     pub fn fake() -> Self {
         Self {
             unresolved: UnresolvedRouteDescription::fake(),
+            dns: None,
         }
     }
 }
@@ -231,11 +306,13 @@ impl RouteInfo {
 struct ConnectStateSnapshot<C> {
     route_resolver: RouteResolver,
     connect_timeout: Duration,
+    per_attempt_timeout: Duration,
     network_interface_poll_interval: Duration,
     post_route_change_connect_timeout: Duration,
     transport_connector: C,
     attempts_record: ConnectionOutcomes<TransportRoute>,
     route_provider_context: RouteProviderContextImpl,
+    event_sink: Option<SharedEventSink>,
 }
 
 impl<TC> ConnectState<TC> {
@@ -246,25 +323,60 @@ impl<TC> ConnectState<TC> {
         let Self {
             route_resolver,
             connect_timeout,
+            per_attempt_timeout,
             network_interface_poll_interval,
             post_route_change_connect_timeout,
             make_transport_connector,
             attempts_record,
             route_provider_context,
+            event_sink,
+            adaptive_keepalive: _,
+            state_updates: _,
+            connect_latencies: _,
         } = self;
 
         ConnectStateSnapshot {
             route_resolver: route_resolver.clone(),
             connect_timeout: *connect_timeout,
+            per_attempt_timeout: *per_attempt_timeout,
             network_interface_poll_interval: *network_interface_poll_interval,
             post_route_change_connect_timeout: *post_route_change_connect_timeout,
             transport_connector: make_transport_connector.make(),
             attempts_record: attempts_record.clone(),
             route_provider_context: route_provider_context.clone(),
+            event_sink: event_sink.clone(),
         }
     }
 }
 
+/// Policy for deciding which failed connection attempts end a connect operation.
+///
+/// [`DefaultErrorClassifier`] is appropriate for production use; other implementations can be
+/// passed to [`ConnectionResources::connect_ws_with_classifier`] and
+/// [`ConnectionResources::connect_attested_ws_with_classifier`].
+pub trait ErrorClassifier: Send + Sync {
+    fn classify(&self, error: &WebSocketServiceConnectError) -> ErrorClass;
+}
+
+/// Uses [`WebSocketServiceConnectError::classify`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultErrorClassifier;
+
+impl ErrorClassifier for DefaultErrorClassifier {
+    fn classify(&self, error: &WebSocketServiceConnectError) -> ErrorClass {
+        error.classify()
+    }
+}
+
+impl<F> ErrorClassifier for F
+where
+    F: Fn(&WebSocketServiceConnectError) -> ErrorClass + Send + Sync,
+{
+    fn classify(&self, error: &WebSocketServiceConnectError) -> ErrorClass {
+        self(error)
+    }
+}
+
 impl<TC> ConnectionResources<'_, TC> {
     pub async fn connect_ws<WC, UR, Transport>(
         self,
@@ -272,6 +384,38 @@ impl<TC> ConnectionResources<'_, TC> {
         ws_connector: WC,
         log_tag: &str,
     ) -> Result<(WC::Connection, RouteInfo), TimeoutOr<ConnectError<WebSocketServiceConnectError>>>
+    where
+        UR: ResolveHostnames<Resolved = WebSocketServiceRoute<Transport>>
+            + DescribeForLog<Description = UnresolvedRouteDescription>
+            + Clone
+            + 'static,
+        Transport: Clone + Send + UsesTransport + ResolvedRoute,
+        TC: ConnectorFactory<
+            Transport,
+            Connection: Send,
+            Connector: Sync + Connector<Transport, (), Error: Into<WebSocketConnectError>>,
+        >,
+        WC: Connector<
+                (WebSocketRouteFragment, HttpRouteFragment),
+                TC::Connection,
+                Connection: Send,
+                Error = WebSocketConnectError,
+            > + Send
+            + Sync,
+    {
+        self.connect_ws_with_classifier(routes, ws_connector, &DefaultErrorClassifier, log_tag)
+            .await
+    }
+
+    /// Like [`connect_ws`](Self::connect_ws), but uses `error_classifier` to decide which
+    /// failures should stop further attempts.
+    pub async fn connect_ws_with_classifier<WC, UR, Transport>(
+        self,
+        routes: impl RouteProvider<Route = UR>,
+        ws_connector: WC,
+        error_classifier: &(impl ErrorClassifier + ?Sized),
+        log_tag: &str,
+    ) -> Result<(WC::Connection, RouteInfo), TimeoutOr<ConnectError<WebSocketServiceConnectError>>>
     where
         UR: ResolveHostnames<Resolved = WebSocketServiceRoute<Transport>>
             + DescribeForLog<Description = UnresolvedRouteDescription>
@@ -303,12 +447,27 @@ impl<TC> ConnectionResources<'_, TC> {
         let ConnectStateSnapshot {
             route_resolver,
             connect_timeout,
+            per_attempt_timeout,
             network_interface_poll_interval,
             post_route_change_connect_timeout,
             transport_connector,
             attempts_record,
             route_provider_context,
+            event_sink,
         } = connect_state.lock().expect("not poisoned").snapshot();
+        let event_sink = event_sink.as_ref();
+        let state_reporter = connect_state
+            .lock()
+            .expect("not poisoned")
+            .state_updates
+            .start_connect(log_tag);
+        let report_connect_failed = || {
+            if let Some(reporter) = &state_reporter {
+                reporter.report(ConnectionState::Disconnected {
+                    cause: DisconnectCause::ConnectFailed,
+                });
+            }
+        };
 
         let routes = routes.routes(&route_provider_context).collect_vec();
 
@@ -316,13 +475,20 @@ impl<TC> ConnectionResources<'_, TC> {
             "[{log_tag}] starting connection attempt with {} routes",
             routes.len()
         );
+        record_to(event_sink, || NetEvent::ConnectStarted {
+            tag: log_tag.to_owned(),
+            route_count: routes.len(),
+        });
 
         let route_provider = routes.into_iter().map(ResolveWithSavedDescription);
         let connector = InterfaceMonitor::new(
-            DescribedRouteConnector(ComposedConnector::new(
-                LoggingConnector::new(ws_connector, Duration::from_secs(3), "websocket"),
-                &transport_connector,
-            )),
+            AttemptTimeoutConnector::new(
+                DescribedRouteConnector(ComposedConnector::new(
+                    LoggingConnector::new(ws_connector, Duration::from_secs(3), "websocket"),
+                    &transport_connector,
+                )),
+                per_attempt_timeout,
+            ),
             network_change_event.clone(),
             network_interface_poll_interval,
             post_route_change_connect_timeout,
@@ -351,70 +517,114 @@ impl<TC> ConnectionResources<'_, TC> {
                     Instant::now(),
                 );
                 log::debug!("[{log_tag}] connection attempt failed with {error}");
-                let is_fatal = match &error {
-                    WebSocketServiceConnectError::RejectedByServer {
-                        response,
-                        received_at: _,
-                    } => {
-                        log::trace!("[{log_tag}] full response: {response:?}");
-                        // Retry-After takes precedence over everything else.
-                        libsignal_net_infra::extract_retry_later(response.headers()).is_some() ||
-                        // If we're rejected based on the request (4xx), there's no point in retrying.
-                        response.status().is_client_error()
-                    }
-                    WebSocketServiceConnectError::Connect(
-                        connect_error,
-                        crate::ws::NotRejectedByServer { .. },
-                    ) => {
-                        // If we *locally* chose to abort, that isn't route-specific; treat it as fatal.
-                        // In any other case, if we didn't make it to the server, we should retry.
-                        matches!(
-                            connect_error,
-                            WebSocketConnectError::Transport(TransportConnectError::ClientAbort)
-                        )
-                    }
-                };
-                if is_fatal {
-                    ControlFlow::Break(error)
-                } else {
-                    ControlFlow::Continue(())
+                record_to(event_sink, || NetEvent::AttemptFailed {
+                    tag: log_tag.to_owned(),
+                    error: error.to_string(),
+                });
+                if let WebSocketServiceConnectError::RejectedByServer {
+                    response,
+                    received_at: _,
+                } = &error
+                {
+                    log::trace!("[{log_tag}] full response: {response:?}");
+                }
+                match error_classifier.classify(&error) {
+                    ErrorClass::Fatal => ControlFlow::Break(error),
+                    ErrorClass::Intermittent => ControlFlow::Continue(()),
                 }
             },
         );
 
         let (result, updates) = tokio::time::timeout(connect_timeout, connect)
             .await
-            .map_err(|_: tokio::time::error::Elapsed| TimeoutOr::Timeout {
-                attempt_duration: connect_timeout,
+            .map_err(|_: tokio::time::error::Elapsed| {
+                record_to(event_sink, || NetEvent::ConnectFailed {
+                    tag: log_tag.to_owned(),
+                    error: "timed out".to_owned(),
+                });
+                connect_state
+                    .lock()
+                    .expect("not poisoned")
+                    .state_updates
+                    .finish_connect(log_tag, false);
+                report_connect_failed();
+                TimeoutOr::Timeout {
+                    attempt_duration: connect_timeout,
+                }
             })?;
 
         match &result {
-            Ok((_connection, route)) => log::info!(
-                "[{log_tag}] connection through {route} succeeded after {:.3?}",
-                updates.finished_at - start
-            ),
-            Err(e) => log::info!("[{log_tag}] connection failed with {e}"),
+            Ok((_connection, route)) => {
+                log::info!(
+                    "[{log_tag}] connection through {route} succeeded after {:.3?}",
+                    updates.finished_at - start
+                );
+                record_to(event_sink, || NetEvent::Connected {
+                    tag: log_tag.to_owned(),
+                    route: route.to_string(),
+                    elapsed_ms: as_millis(updates.finished_at - start),
+                });
+            }
+            Err(e) => {
+                log::info!("[{log_tag}] connection failed with {e}");
+                record_to(event_sink, || NetEvent::ConnectFailed {
+                    tag: log_tag.to_owned(),
+                    error: e.to_string(),
+                });
+                report_connect_failed();
+            }
         }
 
-        connect_state
-            .lock()
-            .expect("not poisoned")
-            .attempts_record
-            .apply_outcome_updates(
+        let latency_sample = result.as_ref().ok().and_then(|(_connection, route)| {
+            Some((latency::route_type(route)?, updates.finished_at - start))
+        });
+
+        {
+            let mut connect_state = connect_state.lock().expect("not poisoned");
+            connect_state.attempts_record.apply_outcome_updates(
                 updates
                     .outcomes
                     .into_iter()
                     .map(|(route, outcome)| (route.into_transport_part(), outcome)),
                 updates.finished_at,
             );
+            if let Some((route_type, latency)) = latency_sample {
+                connect_state
+                    .connect_latencies
+                    .entry(route_type)
+                    .or_default()
+                    .record(latency);
+            }
+            connect_state
+                .state_updates
+                .finish_connect(log_tag, result.is_ok());
+        }
 
         let (connection, description) = result?;
-        Ok((
-            connection,
-            RouteInfo {
-                unresolved: description,
-            },
-        ))
+        let dns = match (description.proxy(), description.target_host()) {
+            (None, Host::Domain(hostname)) => dns_resolver.resolution_info(hostname),
+            (None, Host::Ip(_)) | (Some(_), _) => None,
+        };
+        if let Some(DnsResolutionInfo {
+            source,
+            duration,
+            address_count,
+        }) = &dns
+        {
+            log::debug!(
+                "[{log_tag}] target was resolved by {source:?} in {duration:.3?} ({address_count} addresses)"
+            );
+        }
+        let route_info = RouteInfo {
+            unresolved: description,
+            dns,
+        };
+        if let Some(reporter) = &state_reporter {
+            reporter.report(ConnectionState::Connected {
+                route_info: route_info.clone(),
+            });
+        }
+        Ok((connection, route_info))
     }
 
     pub(crate) async fn connect_attested_ws<E>(
@@ -429,8 +639,41 @@ impl<TC> ConnectionResources<'_, TC> {
         TC: WebSocketTransportConnectorFactory,
         E: NewHandshake,
     {
+        self.connect_attested_ws_with_classifier(
+            routes,
+            auth,
+            ws_config,
+            log_tag,
+            params,
+            &DefaultErrorClassifier,
+        )
+        .await
+    }
+
+    /// Like `connect_attested_ws`, but uses `error_classifier` to decide which failures should
+    /// stop further attempts.
+    pub async fn connect_attested_ws_with_classifier<E>(
+        self,
+        routes: impl RouteProvider<Route = UnresolvedWebsocketServiceRoute>,
+        auth: &Auth,
+        ws_config: libsignal_net_infra::ws::Config,
+        log_tag: Arc<str>,
+        params: &EndpointParams<'_, E>,
+        error_classifier: &(impl ErrorClassifier + ?Sized),
+    ) -> Result<(AttestedConnection, RouteInfo), crate::enclave::Error>
+    where
+        TC: WebSocketTransportConnectorFactory,
+        E: NewHandshake,
+    {
+        // The whole attested session counts as a single request for tracing.
+        let trace_id = TraceId::random();
+        log::info!("[{log_tag}] starting request {trace_id}");
         let ws_routes = routes.map_routes(|mut route| {
-            route.fragment.headers.extend([auth.as_header()]);
+            route
+                .fragment
+                .headers
+                .extend([auth.as_header(), trace_id.as_header()]);
+            ws_config.apply_size_limits(&mut route.fragment.ws_config);
             route
         });
 
@@ -444,7 +687,7 @@ impl<TC> ConnectionResources<'_, TC> {
             ThrottlingConnector::new(crate::infra::ws::WithoutResponseHeaders::new(), 1);
 
         let (ws, route_info) = self
-            .connect_ws(ws_routes, ws_connector, &log_tag)
+            .connect_ws_with_classifier(ws_routes, ws_connector, error_classifier, &log_tag)
             .await
             .map_err(|e| match e {
                 TimeoutOr::Other(
@@ -454,13 +697,18 @@ impl<TC> ConnectionResources<'_, TC> {
                     attempt_duration: _,
                 } => crate::enclave::Error::AllConnectionAttemptsFailed,
                 TimeoutOr::Other(ConnectError::FatalConnect(e)) => e.into(),
-            })?;
-
-        let connection =
-            AttestedConnection::connect(ws, ws_config, log_tag, move |attestation_message| {
-                E::new_handshake(params, attestation_message)
             })
-            .await?;
+            .inspect_err(|e| log::info!("[{log_tag}] request {trace_id} failed: {e}"))?;
+
+        let connection = AttestedConnection::connect(
+            ws,
+            ws_config,
+            log_tag.clone(),
+            move |attestation_message| E::new_handshake(params, attestation_message),
+        )
+        .await
+        .map_err(crate::enclave::Error::from)
+        .inspect_err(|e| log::info!("[{log_tag}] request {trace_id} failed: {e}"))?;
         Ok((connection, route_info))
     }
 }
@@ -486,11 +734,13 @@ where
         let ConnectStateSnapshot {
             route_resolver,
             connect_timeout,
+            per_attempt_timeout,
             network_interface_poll_interval,
             post_route_change_connect_timeout,
             transport_connector,
             attempts_record,
             route_provider_context,
+            event_sink: _,
         } = connect_state
             .lock()
             .expect("not poisoned")
@@ -534,7 +784,10 @@ where
 
         let route_provider = routes.into_iter();
         let connector = InterfaceMonitor::new(
-            ConnectWithSavedRoute(&transport_connector),
+            AttemptTimeoutConnector::new(
+                ConnectWithSavedRoute(&transport_connector),
+                per_attempt_timeout,
+            ),
             network_change_event.clone(),
             network_interface_poll_interval,
             post_route_change_connect_timeout,
@@ -641,7 +894,7 @@ mod test {
     use http::HeaderMap;
     use libsignal_net_infra::certs::RootCertificates;
     use libsignal_net_infra::dns::lookup_result::LookupResult;
-    use libsignal_net_infra::host::Host;
+    use libsignal_net_infra::dns::DnsSource;
     use libsignal_net_infra::route::testutils::ConnectFn;
     use libsignal_net_infra::route::{
         AttemptOutcome, DirectOrProxyRoute, HttpsTlsRoute, TcpRoute, TlsRoute, TlsRouteFragment,
@@ -653,6 +906,7 @@ mod test {
     use nonzero_ext::nonzero;
 
     use super::*;
+    use crate::events::EventSink;
     use crate::ws::NotRejectedByServer;
 
     const FAKE_HOST_NAME: &str = "direct-host";
@@ -662,6 +916,7 @@ mod test {
             sni: Host::Domain("fake-sni".into()),
             alpn: Some(Alpn::Http1_1),
             min_protocol_version: Some(boring_signal::ssl::SslVersion::TLS1_3),
+            client_cert: None,
         },
         inner: DirectOrProxyRoute::Direct(TcpRoute {
             address: UnresolvedHost::from(Arc::from(FAKE_HOST_NAME)),
@@ -733,10 +988,15 @@ mod test {
             connect_timeout: Duration::MAX,
             network_interface_poll_interval: Duration::MAX,
             post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
             route_resolver: RouteResolver::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
             make_transport_connector: fake_transport_connector,
             route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
         }
         .into();
 
@@ -761,9 +1021,249 @@ mod test {
             connection,
             (succeeding_route.fragment, succeeding_route.inner.fragment)
         );
-        let RouteInfo { unresolved } = info;
+        let RouteInfo { unresolved, dns } = info;
 
         assert_eq!(unresolved.to_string(), "REDACTED:1234 fronted by proxyf");
+        assert_eq!(
+            dns,
+            Some(DnsResolutionInfo {
+                source: DnsSource::Static,
+                duration: Duration::ZERO,
+                address_count: 1,
+            })
+        );
+
+        let latencies = state
+            .lock()
+            .expect("not poisoned")
+            .connect_latency_snapshot();
+        assert_eq!(latencies.keys().collect_vec(), [&RouteType::ProxyF]);
+        assert_eq!(
+            latencies[&RouteType::ProxyF].percentile(50.0),
+            Some(LATENCY_BUCKET_BOUNDS[0])
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordingSink(Mutex<Vec<NetEvent>>);
+
+    impl EventSink for RecordingSink {
+        fn record(&self, event: NetEvent) {
+            self.0.lock().expect("not poisoned").push(event)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_reports_events() {
+        let [failing_route, succeeding_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route| {
+            let (ws, http) = &route;
+            std::future::ready(
+                if (ws, http) == (&failing_route.fragment, &failing_route.inner.fragment) {
+                    Err(tungstenite::Error::ConnectionClosed.into())
+                } else {
+                    Ok(route)
+                },
+            )
+        });
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let fake_transport_connector =
+            ConnectFn(move |(), _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
+
+        let sink = Arc::new(RecordingSink::default());
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_interface_poll_interval: Duration::MAX,
+            post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
+            route_resolver: RouteResolver::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: fake_transport_connector,
+            route_provider_context: Default::default(),
+            event_sink: Some(sink.clone()),
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
+        }
+        .into();
+
+        let connection_resources = ConnectionResources {
+            connect_state: &state,
+            dns_resolver: &resolver,
+            network_change_event: &no_network_change_events(),
+            confirmation_header_name: None,
+        };
+
+        let _ = connection_resources
+            .connect_ws(
+                vec![failing_route.clone(), succeeding_route.clone()],
+                ws_connector,
+                "test",
+            )
+            .await
+            .expect("succeeded");
+
+        let events = std::mem::take(&mut *sink.0.lock().expect("not poisoned"));
+        assert_matches!(
+            &*events,
+            [
+                NetEvent::ConnectStarted { tag, route_count: 2 },
+                NetEvent::AttemptFailed { .. },
+                NetEvent::Connected { route, .. },
+            ] if tag == "test" && route == "REDACTED:1234 fronted by proxyf"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_reports_state_updates() {
+        let [failing_route, succeeding_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route| {
+            let (ws, http) = &route;
+            std::future::ready(
+                if (ws, http) == (&failing_route.fragment, &failing_route.inner.fragment) {
+                    Err(tungstenite::Error::ConnectionClosed.into())
+                } else {
+                    Ok(route)
+                },
+            )
+        });
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let fake_transport_connector =
+            ConnectFn(move |(), _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
+
+        let state = std::sync::Mutex::new(ConnectState {
+            connect_timeout: Duration::MAX,
+            network_interface_poll_interval: Duration::MAX,
+            post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
+            route_resolver: RouteResolver::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: fake_transport_connector,
+            route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
+        });
+        let mut updates = state
+            .lock()
+            .expect("not poisoned")
+            .subscribe_to_state_updates();
+
+        let network_change_event = no_network_change_events();
+        let connection_resources = || ConnectionResources {
+            connect_state: &state,
+            dns_resolver: &resolver,
+            network_change_event: &network_change_event,
+            confirmation_header_name: None,
+        };
+
+        let _ = connection_resources()
+            .connect_ws(vec![failing_route.clone()], &ws_connector, "test")
+            .await
+            .expect_err("failed");
+        let _ = connection_resources()
+            .connect_ws(
+                vec![failing_route.clone(), succeeding_route.clone()],
+                &ws_connector,
+                "test",
+            )
+            .await
+            .expect("succeeded");
+
+        let mut states = vec![];
+        while let Ok(ConnectionStateUpdate { tag, state }) = updates.try_recv() {
+            assert_eq!(&*tag, "test");
+            states.push(state);
+        }
+        assert_matches!(
+            &*states,
+            [
+                ConnectionState::Connecting { attempt: 1 },
+                ConnectionState::Disconnected {
+                    cause: DisconnectCause::ConnectFailed
+                },
+                ConnectionState::Connecting { attempt: 2 },
+                ConnectionState::Connected { route_info },
+            ] if route_info.to_string() == "REDACTED:1234 fronted by proxyf"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_with_custom_classifier() {
+        let [failing_route, succeeding_route] = (*FAKE_WEBSOCKET_ROUTES).clone();
+
+        let ws_connector = ConnectFn(|(), route| {
+            let (ws, http) = &route;
+            std::future::ready(
+                if (ws, http) == (&failing_route.fragment, &failing_route.inner.fragment) {
+                    Err(tungstenite::Error::ConnectionClosed.into())
+                } else {
+                    Ok(route)
+                },
+            )
+        });
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let fake_transport_connector =
+            ConnectFn(move |(), _| std::future::ready(Ok::<_, WebSocketConnectError>(())));
+
+        let state = ConnectState {
+            connect_timeout: Duration::MAX,
+            network_interface_poll_interval: Duration::MAX,
+            post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
+            route_resolver: RouteResolver::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: fake_transport_connector,
+            route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
+        }
+        .into();
+
+        let connection_resources = ConnectionResources {
+            connect_state: &state,
+            dns_resolver: &resolver,
+            network_change_event: &no_network_change_events(),
+            confirmation_header_name: None,
+        };
+
+        // The default classifier would retry with the second route; this one gives up
+        // immediately.
+        let result = connection_resources
+            .connect_ws_with_classifier(
+                vec![failing_route.clone(), succeeding_route.clone()],
+                ws_connector,
+                &|_: &WebSocketServiceConnectError| ErrorClass::Fatal,
+                "test",
+            )
+            .await;
+
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::FatalConnect(
+                WebSocketServiceConnectError::Connect(
+                    WebSocketConnectError::WebSocketError(_),
+                    NotRejectedByServer { .. }
+                )
+            )))
+        );
     }
 
     #[tokio::test(start_paused = true)]
@@ -784,10 +1284,15 @@ mod test {
             connect_timeout: CONNECT_TIMEOUT,
             network_interface_poll_interval: Duration::MAX,
             post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
             route_resolver: RouteResolver::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
             make_transport_connector: always_hangs_connector,
             route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
         }
         .into();
 
@@ -818,6 +1323,115 @@ mod test {
         assert_eq!(start.elapsed(), CONNECT_TIMEOUT);
     }
 
+    #[tokio::test]
+    async fn connect_ws_plaintext_to_local_server() {
+        use futures_util::{SinkExt as _, StreamExt as _};
+        use libsignal_net_infra::testutil::plaintext::{
+            plaintext_ws_route, PlaintextTransportConnector,
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("can bind");
+        let addr = listener.local_addr().expect("bound");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("incoming connection");
+            let mut ws = tokio_tungstenite::accept_async(stream)
+                .await
+                .expect("handshake");
+            ws.send(tungstenite::Message::text("hello"))
+                .await
+                .expect("can send");
+        });
+
+        let route = plaintext_ws_route(
+            &format!("ws://{addr}/v1/websocket/")
+                .parse()
+                .expect("valid URL"),
+        )
+        .expect("ws URL");
+        let state = ConnectState::new_with_transport_connector(
+            SUGGESTED_CONNECT_CONFIG,
+            PlaintextTransportConnector,
+        );
+        let connection_resources = ConnectionResources {
+            connect_state: &state,
+            dns_resolver: &DnsResolver::new_from_static_map(HashMap::new()),
+            network_change_event: &no_network_change_events(),
+            confirmation_header_name: None,
+        };
+
+        let (mut ws, _info) = connection_resources
+            .connect_ws(
+                vec![route],
+                crate::infra::ws::WithoutResponseHeaders::new(),
+                "test",
+            )
+            .await
+            .expect("connected");
+
+        assert_matches!(
+            ws.next().await,
+            Some(Ok(tungstenite::Message::Text(text))) if text.as_str() == "hello"
+        );
+        server.await.expect("server finished");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_ws_per_attempt_timeout() {
+        let ws_connector = crate::infra::ws::Stateless;
+        let resolver = DnsResolver::new_from_static_map(HashMap::from([(
+            FAKE_HOST_NAME,
+            LookupResult::new(vec![ip_addr!(v4, "192.0.2.1")], vec![]),
+        )]));
+
+        let always_hangs_connector = ConnectFn(|(), _| {
+            std::future::pending::<Result<tokio::io::DuplexStream, WebSocketConnectError>>()
+        });
+
+        const CONNECT_TIMEOUT: Duration = Duration::from_secs(31);
+        const PER_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+        let state = ConnectState {
+            connect_timeout: CONNECT_TIMEOUT,
+            per_attempt_timeout: PER_ATTEMPT_TIMEOUT,
+            network_interface_poll_interval: Duration::MAX,
+            post_route_change_connect_timeout: Duration::MAX,
+            route_resolver: RouteResolver::default(),
+            attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
+            make_transport_connector: always_hangs_connector,
+            route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
+        }
+        .into();
+
+        let connection_resources = ConnectionResources {
+            connect_state: &state,
+            dns_resolver: &resolver,
+            network_change_event: &no_network_change_events(),
+            confirmation_header_name: None,
+        };
+
+        let connect = connection_resources.connect_ws(
+            vec![FAKE_WEBSOCKET_ROUTES[0].clone()],
+            ws_connector,
+            "test",
+        );
+
+        let start = Instant::now();
+        let result: Result<_, TimeoutOr<ConnectError<_>>> = connect.await;
+
+        // The single route gives up well before the overall deadline.
+        assert_matches!(
+            result,
+            Err(TimeoutOr::Other(ConnectError::AllAttemptsFailed))
+        );
+        assert_eq!(start.elapsed(), PER_ATTEMPT_TIMEOUT);
+    }
+
     #[tokio::test(start_paused = true)]
     async fn client_abort_transport_error_is_fatal() {
         // We can't directly test the ClientAbort produced for a network change without *more*
@@ -843,10 +1457,15 @@ mod test {
             connect_timeout: CONNECT_TIMEOUT,
             network_interface_poll_interval: Duration::MAX,
             post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
             route_resolver: RouteResolver::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
             make_transport_connector: client_abort_connector,
             route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
         }
         .into();
 
@@ -906,10 +1525,15 @@ mod test {
             connect_timeout: Duration::MAX,
             network_interface_poll_interval: Duration::MAX,
             post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
             route_resolver: RouteResolver::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
             make_transport_connector: fake_transport_connector,
             route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
         };
 
         let past_failure = AttemptOutcome {
@@ -991,10 +1615,15 @@ mod test {
             connect_timeout: CONNECT_TIMEOUT,
             network_interface_poll_interval: Duration::MAX,
             post_route_change_connect_timeout: Duration::MAX,
+            per_attempt_timeout: Duration::MAX,
             route_resolver: RouteResolver::default(),
             attempts_record: ConnectionOutcomes::new(SUGGESTED_CONNECT_PARAMS),
             make_transport_connector,
             route_provider_context: Default::default(),
+            event_sink: None,
+            adaptive_keepalive: None,
+            state_updates: Default::default(),
+            connect_latencies: Default::default(),
         }
         .into();
 
@@ -0,0 +1,142 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::time::Duration;
+
+use libsignal_net_infra::route::UnresolvedRouteDescription;
+use libsignal_net_infra::RouteType;
+
+/// Upper bounds of the buckets in a [`LatencyHistogram`].
+///
+/// Anything slower than the last bound goes in a final overflow bucket.
+pub const LATENCY_BUCKET_BOUNDS: [Duration; 10] = [
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(20),
+    Duration::from_secs(30),
+];
+
+/// Fixed-bucket histogram of how long successful connect operations took.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKET_BOUNDS.len() + 1],
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let index = LATENCY_BUCKET_BOUNDS.partition_point(|bound| *bound < latency);
+        self.counts[index] = self.counts[index].saturating_add(1);
+    }
+
+    /// The total number of samples recorded.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The number of samples in each bucket, paired with the bucket's upper bound.
+    ///
+    /// The overflow bucket has an upper bound of `None`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BUCKET_BOUNDS
+            .iter()
+            .copied()
+            .map(Some)
+            .chain([None])
+            .zip(self.counts.iter().copied())
+    }
+
+    /// An upper bound on the given percentile (between 0 and 100), at bucket granularity.
+    ///
+    /// Returns `None` if no samples have been recorded, and [`Duration::MAX`] if the percentile
+    /// falls in the overflow bucket.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        // The clamped fraction of `count` is between 0 and `count`, so it fits in a u64; only
+        // the fractional part is discarded, and `ceil` has already rounded that up.
+        #[expect(clippy::cast_possible_truncation)]
+        let rank = ((percent.clamp(0.0, 100.0) / 100.0) * count as f64).ceil() as u64;
+        let rank = rank.clamp(1, count);
+
+        let mut seen = 0;
+        for (bound, bucket_count) in self.buckets() {
+            seen += bucket_count;
+            if seen >= rank {
+                return Some(bound.unwrap_or(Duration::MAX));
+            }
+        }
+        unreachable!("rank is at most the total count")
+    }
+}
+
+/// Categorizes a successful route for [`LatencyHistogram`] purposes.
+///
+/// Returns `None` for domain fronts that don't correspond to a known [`RouteType`].
+pub(crate) fn route_type(description: &UnresolvedRouteDescription) -> Option<RouteType> {
+    if let Some(proxy) = description.proxy() {
+        return Some(proxy.into());
+    }
+    match description.domain_front() {
+        None => Some(RouteType::Direct),
+        Some(front) => [RouteType::ProxyF, RouteType::ProxyG]
+            .into_iter()
+            .find(|route_type| <&'static str>::from(*route_type) == front),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        for millis in [10, 50, 51, 900, 60_000] {
+            histogram.record(Duration::from_millis(millis));
+        }
+
+        assert_eq!(histogram.count(), 5);
+        let nonempty = histogram
+            .buckets()
+            .filter(|(_, count)| *count != 0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            nonempty,
+            [
+                (Some(Duration::from_millis(50)), 2),
+                (Some(Duration::from_millis(100)), 1),
+                (Some(Duration::from_secs(1)), 1),
+                (None, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn histogram_percentiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(50.0), None);
+
+        for _ in 0..19 {
+            histogram.record(Duration::from_millis(150));
+        }
+        histogram.record(Duration::from_secs(4));
+
+        assert_eq!(histogram.percentile(0.0), Some(Duration::from_millis(200)));
+        assert_eq!(histogram.percentile(50.0), Some(Duration::from_millis(200)));
+        assert_eq!(histogram.percentile(95.0), Some(Duration::from_millis(200)));
+        assert_eq!(histogram.percentile(99.0), Some(Duration::from_secs(5)));
+
+        histogram.record(Duration::from_secs(100));
+        assert_eq!(histogram.percentile(100.0), Some(Duration::MAX));
+    }
+}
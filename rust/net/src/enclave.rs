@@ -4,8 +4,9 @@
 //
 
 use std::marker::PhantomData;
-use std::time::SystemTime;
+use std::time::Duration;
 
+use attest::enclave::AttestationPolicy;
 use attest::svr2::RaftConfig;
 use attest::{cds2, enclave};
 use derive_where::derive_where;
@@ -53,6 +54,17 @@ pub enum Cdsi {}
 
 pub enum SvrSgx {}
 
+/// An SVR enclave running in an AMD SEV-SNP guest rather than SGX.
+///
+/// Its [`MrEnclave`] is the guest's launch measurement.
+pub enum SvrSnp {}
+
+/// An SVR enclave running in an Intel TDX trust domain.
+///
+/// Its [`MrEnclave`] is the TD's MRTD, optionally followed by the expected values of its
+/// runtime measurement registers (RTMR0 onwards).
+pub enum SvrTdx {}
+
 impl EnclaveKind for Cdsi {
     type RaftConfigType = ();
     fn url_path(enclave: &[u8]) -> PathAndQuery {
@@ -67,6 +79,20 @@ impl EnclaveKind for SvrSgx {
     }
 }
 
+impl EnclaveKind for SvrSnp {
+    type RaftConfigType = &'static RaftConfig;
+    fn url_path(enclave: &[u8]) -> PathAndQuery {
+        PathAndQuery::try_from(format!("/v1/{}", hex::encode(enclave))).unwrap()
+    }
+}
+
+impl EnclaveKind for SvrTdx {
+    type RaftConfigType = &'static RaftConfig;
+    fn url_path(enclave: &[u8]) -> PathAndQuery {
+        PathAndQuery::try_from(format!("/v1/{}", hex::encode(enclave))).unwrap()
+    }
+}
+
 impl SvrBFlavor for SvrSgx {}
 
 /// Log-safe human-readable label for a connection.
@@ -199,6 +225,19 @@ impl<Bytes: AsRef<[u8]>, S> AsRef<[u8]> for MrEnclave<Bytes, S> {
 pub struct EndpointParams<'a, E: EnclaveKind> {
     pub mr_enclave: MrEnclave<&'a [u8], E>,
     pub raft_config: E::RaftConfigType,
+    /// Intel security advisories to accept in addition to the ones built in for `mr_enclave`.
+    ///
+    /// Only used for SGX and TDX enclaves. These can widen, but never narrow, the built-in list.
+    pub extra_sw_advisories: &'a [String],
+    /// Checks to relax when attesting the enclave, for non-production environments.
+    ///
+    /// Only used for SGX enclaves.
+    pub attestation_policy: AttestationPolicy<'a>,
+    /// How long a successful attestation of the same evidence may be reused for; zero disables
+    /// reuse.
+    ///
+    /// Only used for SGX enclaves.
+    pub attestation_cache_validity: Duration,
 }
 
 #[derive_where(Clone)]
@@ -319,11 +358,49 @@ impl NewHandshake for SvrSgx {
         attest::svr2::new_handshake(
             params.mr_enclave.as_ref(),
             attestation_message,
-            SystemTime::now(),
+            attest::clock::now(),
             params
                 .raft_config
                 .as_raft_config()
                 .expect("Raft config must be present for SGX"),
+            params.extra_sw_advisories,
+            &params.attestation_policy,
+            params.attestation_cache_validity,
+        )
+    }
+}
+
+impl NewHandshake for SvrSnp {
+    fn new_handshake(
+        params: &EndpointParams<Self>,
+        attestation_message: &[u8],
+    ) -> enclave::Result<enclave::Handshake> {
+        attest::sev_snp::new_handshake(
+            params.mr_enclave.as_ref(),
+            attestation_message,
+            attest::clock::now(),
+            params
+                .raft_config
+                .as_raft_config()
+                .expect("Raft config must be present for SEV-SNP"),
+        )
+    }
+}
+
+impl NewHandshake for SvrTdx {
+    fn new_handshake(
+        params: &EndpointParams<Self>,
+        attestation_message: &[u8],
+    ) -> enclave::Result<enclave::Handshake> {
+        attest::tdx::new_handshake(
+            params.mr_enclave.as_ref(),
+            attestation_message,
+            attest::clock::now(),
+            params
+                .raft_config
+                .as_raft_config()
+                .expect("Raft config must be present for TDX"),
+            params.extra_sw_advisories,
         )
     }
 }
@@ -336,7 +413,10 @@ impl NewHandshake for Cdsi {
         cds2::new_handshake(
             params.mr_enclave.as_ref(),
             attestation_message,
-            SystemTime::now(),
+            attest::clock::now(),
+            params.extra_sw_advisories,
+            &params.attestation_policy,
+            params.attestation_cache_validity,
         )
     }
 }
@@ -34,6 +34,7 @@ use crate::enclave::{Cdsi, EnclaveEndpoint, EndpointParams, MrEnclave, SvrSgx};
 const DEFAULT_HTTPS_PORT: NonZeroU16 = nonzero!(443_u16);
 pub const TIMESTAMP_HEADER_NAME: &str = "x-signal-timestamp";
 pub(crate) const ALERT_HEADER_NAME: &str = "x-signal-alert";
+pub const TRACE_ID_HEADER_NAME: &str = "x-signal-trace-id";
 pub(crate) const CONNECTION_INVALIDATED_CLOSE_CODE: u16 = 4401;
 pub(crate) const CONNECTED_ELSEWHERE_CLOSE_CODE: u16 = 4409;
 
@@ -233,31 +234,49 @@ pub const PROXY_CONFIG_G: ProxyConfig = ProxyConfig {
 pub(crate) const ENDPOINT_PARAMS_CDSI_STAGING: EndpointParams<'static, Cdsi> = EndpointParams {
     mr_enclave: MrEnclave::new(attest::constants::ENCLAVE_ID_CDSI_STAGING),
     raft_config: (),
+    extra_sw_advisories: &[],
+    attestation_policy: attest::enclave::AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 pub(crate) const ENDPOINT_PARAMS_SVR2_STAGING: EndpointParams<'static, SvrSgx> = EndpointParams {
     mr_enclave: MrEnclave::new(attest::constants::ENCLAVE_ID_SVR2_STAGING),
     raft_config: attest::constants::RAFT_CONFIG_SVR2_STAGING,
+    extra_sw_advisories: &[],
+    attestation_policy: attest::enclave::AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 pub(crate) const ENDPOINT_PARAMS_SVRB_STAGING: EndpointParams<'static, SvrSgx> = EndpointParams {
     mr_enclave: MrEnclave::new(attest::constants::ENCLAVE_ID_SVRB_STAGING),
     raft_config: attest::constants::RAFT_CONFIG_SVRB_STAGING,
+    extra_sw_advisories: &[],
+    attestation_policy: attest::enclave::AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 pub(crate) const ENDPOINT_PARAMS_SVRB_PROD: EndpointParams<'static, SvrSgx> = EndpointParams {
     mr_enclave: MrEnclave::new(attest::constants::ENCLAVE_ID_SVRB_PROD),
     raft_config: attest::constants::RAFT_CONFIG_SVRB_PROD,
+    extra_sw_advisories: &[],
+    attestation_policy: attest::enclave::AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 pub(crate) const ENDPOINT_PARAMS_CDSI_PROD: EndpointParams<'static, Cdsi> = EndpointParams {
     mr_enclave: MrEnclave::new(attest::constants::ENCLAVE_ID_CDSI_PROD),
     raft_config: (),
+    extra_sw_advisories: &[],
+    attestation_policy: attest::enclave::AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 pub(crate) const ENDPOINT_PARAMS_SVR2_PROD: EndpointParams<'static, SvrSgx> = EndpointParams {
     mr_enclave: MrEnclave::new(attest::constants::ENCLAVE_ID_SVR2_PROD),
     raft_config: attest::constants::RAFT_CONFIG_SVR2_PROD,
+    extra_sw_advisories: &[],
+    attestation_policy: attest::enclave::AttestationPolicy::PRODUCTION,
+    attestation_cache_validity: attest::dcap::DEFAULT_ATTESTATION_CACHE_VALIDITY,
 };
 
 pub(crate) const KEYTRANS_SIGNING_KEY_MATERIAL_STAGING: &[u8; 32] =
@@ -474,6 +493,22 @@ impl ConnectionConfig {
         permissive_config.min_tls_version = None;
         permissive_config
     }
+
+    /// Returns a copy of this config that verifies servers against `certs` instead of the
+    /// built-in certificates.
+    ///
+    /// This applies to the domain-fronting proxies as well, so that it can be used for
+    /// private staging environments or networks with a TLS-intercepting proxy.
+    pub fn config_with_root_certificates(&self, certs: RootCertificates) -> Self {
+        let mut config = self.clone();
+        if let Some(proxy) = &mut config.proxy {
+            for proxy_config in &mut proxy.configs {
+                proxy_config.certs = certs.clone();
+            }
+        }
+        config.cert = certs;
+        config
+    }
 }
 
 #[derive(Clone)]
@@ -835,6 +870,7 @@ mod test {
                     sni: Host::Domain("host".into()),
                     alpn: Some(Alpn::Http1_1),
                     min_protocol_version: Some(SslVersion::TLS1_2),
+                    client_cert: None,
                 },
                 inner: TcpRoute {
                     address: UnresolvedHost::from(Arc::from("host")),
@@ -851,6 +887,23 @@ mod test {
         };
     }
 
+    #[test]
+    fn config_with_root_certificates_applies_to_all_routes() {
+        let certs = RootCertificates::FromDer(std::borrow::Cow::Borrowed(b"custom"));
+        let config = DOMAIN_CONFIG_CHAT_STAGING
+            .connect
+            .config_with_root_certificates(certs.clone());
+
+        let routes = config
+            .route_provider(EnableDomainFronting::AllDomains)
+            .routes(&FakeContext::new())
+            .collect_vec();
+        assert!(routes.len() > 1, "{routes:?}");
+        for route in routes {
+            assert_eq!(route.inner.fragment.root_certs, certs);
+        }
+    }
+
     #[tokio::test]
     #[test_matrix([&DOMAIN_CONFIG_CHAT, &DOMAIN_CONFIG_CHAT_STAGING, &DOMAIN_CONFIG_CDSI, &DOMAIN_CONFIG_CDSI_STAGING])]
     async fn live_resolve_eq_static_resolution(config: &DomainConfig) {
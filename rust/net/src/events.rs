@@ -0,0 +1,439 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Structured connection lifecycle events.
+//!
+//! An [`EventSink`] installed on a [`ConnectState`](crate::connect_state::ConnectState) receives
+//! events for every connection made through it, which covers chat as well as the enclave
+//! services. All text in an event comes from log-safe `Display` impls, so hostnames are already
+//! redacted.
+//!
+//! For driving connectivity indicators, [`ConnectState::subscribe_to_state_updates`] provides
+//! typed [`ConnectionStateUpdate`]s instead.
+//!
+//! [`ConnectState::subscribe_to_state_updates`]: crate::connect_state::ConnectState::subscribe_to_state_updates
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::broadcast;
+
+use crate::chat::ws::{EventListener, FinishError, FinishReason, ListenerEvent, TaskExitError};
+use crate::connect_state::RouteInfo;
+use crate::infra::ws::connection::NextEventError;
+
+/// A single connection lifecycle event.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NetEvent {
+    /// A connect operation is starting.
+    ConnectStarted { tag: String, route_count: usize },
+    /// A single route failed; the connect operation might still succeed.
+    AttemptFailed { tag: String, error: String },
+    /// A connect operation succeeded.
+    Connected {
+        tag: String,
+        route: String,
+        elapsed_ms: u64,
+    },
+    /// A connect operation failed.
+    ConnectFailed { tag: String, error: String },
+    /// An established connection ended.
+    Disconnected { tag: String, reason: String },
+    /// An established connection ended because the server stopped responding.
+    KeepaliveMissed { tag: String, idle_ms: u64 },
+    /// A chat request failed without getting a response.
+    RequestFailed {
+        tag: String,
+        trace_id: String,
+        error: String,
+    },
+    /// The chat server finished delivering the messages it had queued for a connection.
+    QueueDrained {
+        tag: String,
+        envelopes_received: u64,
+    },
+}
+
+/// Receives [`NetEvent`]s.
+pub trait EventSink: Debug + Send + Sync {
+    fn record(&self, event: NetEvent);
+}
+
+/// Convenience alias for the form sinks are stored in.
+pub type SharedEventSink = Arc<dyn EventSink>;
+
+/// [`EventSink`] that writes each event as a line of JSON.
+///
+/// Each line is the serialized [`NetEvent`] plus a `timestamp_ms` field with the time since the
+/// Unix epoch.
+pub struct JsonLinesSink<W> {
+    writer: Mutex<W>,
+}
+
+#[derive(serde::Serialize)]
+struct TimestampedEvent<'a> {
+    timestamp_ms: u64,
+    #[serde(flatten)]
+    event: &'a NetEvent,
+}
+
+impl<W> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: writer.into(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().expect("not poisoned")
+    }
+}
+
+impl<W> Debug for JsonLinesSink<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonLinesSink").finish_non_exhaustive()
+    }
+}
+
+impl<W: std::io::Write + Send> EventSink for JsonLinesSink<W> {
+    fn record(&self, event: NetEvent) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, as_millis);
+        let mut line = serde_json::to_vec(&TimestampedEvent {
+            timestamp_ms,
+            event: &event,
+        })
+        .expect("can serialize");
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().expect("not poisoned");
+        if let Err(e) = writer.write_all(&line).and_then(|()| writer.flush()) {
+            log::warn!("failed to write net event: {e}");
+        }
+    }
+}
+
+/// The state a connection has moved into.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// A connect operation has started.
+    ///
+    /// `attempt` counts the connect operations with the same tag since the last one that
+    /// succeeded, starting at 1.
+    Connecting { attempt: u32 },
+    /// The connection was established, or has recovered from being [`Self::Degraded`].
+    Connected { route_info: RouteInfo },
+    /// The server hasn't answered the last `missed_keepalives` keepalive pings.
+    Degraded { missed_keepalives: u32 },
+    /// The connection ended, or couldn't be established.
+    Disconnected { cause: DisconnectCause },
+}
+
+/// Why a connection reached [`ConnectionState::Disconnected`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisconnectCause {
+    /// The connection couldn't be established.
+    ConnectFailed,
+    /// The local end closed the connection.
+    Local,
+    /// The server closed the connection.
+    Remote,
+    /// The connection was closed for lack of application traffic.
+    IdleTimeout,
+    /// The server stopped answering keepalive pings.
+    KeepaliveTimeout,
+    /// The connection failed; the description is log-safe.
+    Error(String),
+}
+
+/// A [`ConnectionState`] change for the connection with the given log tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectionStateUpdate {
+    pub tag: Arc<str>,
+    pub state: ConnectionState,
+}
+
+/// How many updates a slow subscriber can fall behind before it starts missing them.
+const STATE_UPDATE_CAPACITY: usize = 32;
+
+/// Bookkeeping for [`ConnectionStateUpdate`]s, kept in a
+/// [`ConnectState`](crate::connect_state::ConnectState).
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionStateUpdates {
+    /// Created on first subscription, so there's no cost when nobody is listening.
+    tx: Option<broadcast::Sender<ConnectionStateUpdate>>,
+    failed_attempts: HashMap<Arc<str>, u32>,
+}
+
+impl ConnectionStateUpdates {
+    pub(crate) fn subscribe(&mut self) -> broadcast::Receiver<ConnectionStateUpdate> {
+        self.tx
+            .get_or_insert_with(|| broadcast::Sender::new(STATE_UPDATE_CAPACITY))
+            .subscribe()
+    }
+
+    /// Returns a reporter for the connection tagged `tag`, if anyone is subscribed.
+    pub(crate) fn reporter(&self, tag: &str) -> Option<ConnectionStateReporter> {
+        let tx = self.tx.as_ref()?;
+        Some(ConnectionStateReporter {
+            tx: tx.clone(),
+            tag: tag.into(),
+        })
+    }
+
+    /// Reports the start of a connect operation for `tag`.
+    ///
+    /// Returns a reporter to use for the rest of the operation, if anyone is subscribed.
+    pub(crate) fn start_connect(&mut self, tag: &str) -> Option<ConnectionStateReporter> {
+        let reporter = self.reporter(tag)?;
+        let attempt = self
+            .failed_attempts
+            .get(tag)
+            .copied()
+            .unwrap_or_default()
+            .saturating_add(1);
+        reporter.report(ConnectionState::Connecting { attempt });
+        Some(reporter)
+    }
+
+    pub(crate) fn finish_connect(&mut self, tag: &str, succeeded: bool) {
+        if self.tx.is_none() {
+            return;
+        }
+        if succeeded {
+            self.failed_attempts.remove(tag);
+        } else {
+            let failed = self.failed_attempts.entry(tag.into()).or_default();
+            *failed = failed.saturating_add(1);
+        }
+    }
+}
+
+/// Sends [`ConnectionStateUpdate`]s for a single connection.
+#[derive(Clone, Debug)]
+pub struct ConnectionStateReporter {
+    tx: broadcast::Sender<ConnectionStateUpdate>,
+    tag: Arc<str>,
+}
+
+impl ConnectionStateReporter {
+    pub(crate) fn report(&self, state: ConnectionState) {
+        // Having no subscribers left is fine.
+        let _ = self.tx.send(ConnectionStateUpdate {
+            tag: self.tag.clone(),
+            state,
+        });
+    }
+}
+
+/// Watches a chat connection's events to produce [`ConnectionState`] changes.
+#[derive(Debug)]
+pub struct ChatStateMonitor {
+    reporter: ConnectionStateReporter,
+    route_info: RouteInfo,
+    awaiting_pong: bool,
+    missed_keepalives: u32,
+}
+
+impl ChatStateMonitor {
+    pub(crate) fn new(reporter: ConnectionStateReporter, route_info: RouteInfo) -> Self {
+        Self {
+            reporter,
+            route_info,
+            awaiting_pong: false,
+            missed_keepalives: 0,
+        }
+    }
+
+    pub(crate) fn sent_ping(&mut self) {
+        if std::mem::replace(&mut self.awaiting_pong, true) {
+            self.missed_keepalives = self.missed_keepalives.saturating_add(1);
+            self.reporter.report(ConnectionState::Degraded {
+                missed_keepalives: self.missed_keepalives,
+            });
+        }
+    }
+
+    pub(crate) fn heard_from_server(&mut self) {
+        self.awaiting_pong = false;
+        if std::mem::take(&mut self.missed_keepalives) > 0 {
+            self.reporter.report(ConnectionState::Connected {
+                route_info: self.route_info.clone(),
+            });
+        }
+    }
+
+    pub(crate) fn finished(&self, result: &Result<FinishReason, TaskExitError>) {
+        let cause = match result {
+            Ok(FinishReason::LocalDisconnect) => DisconnectCause::Local,
+            Ok(FinishReason::RemoteDisconnect) => DisconnectCause::Remote,
+            Ok(FinishReason::IdleTimeout) => DisconnectCause::IdleTimeout,
+            Err(TaskExitError::WebsocketError(NextEventError::ServerIdleTimeout(_))) => {
+                DisconnectCause::KeepaliveTimeout
+            }
+            Err(e) => DisconnectCause::Error(e.to_string()),
+        };
+        self.reporter
+            .report(ConnectionState::Disconnected { cause });
+    }
+}
+
+/// Records the event produced by `make_event`, if there's a sink to record it.
+pub(crate) fn record_to(sink: Option<&SharedEventSink>, make_event: impl FnOnce() -> NetEvent) {
+    if let Some(sink) = sink {
+        sink.record(make_event())
+    }
+}
+
+pub(crate) fn as_millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Wraps a chat [`EventListener`] to also report the end of the connection to `sink`.
+pub(crate) fn report_chat_finish(
+    sink: SharedEventSink,
+    tag: Arc<str>,
+    mut listener: EventListener,
+) -> EventListener {
+    Box::new(move |event| {
+        if let ListenerEvent::Finished(result) = &event {
+            sink.record(chat_finish_event(tag.to_string(), result));
+        }
+        listener(event)
+    })
+}
+
+fn chat_finish_event(
+    tag: String,
+    result: &Result<crate::chat::ws::FinishReason, FinishError>,
+) -> NetEvent {
+    let reason = match result {
+        Ok(reason) => reason.to_string(),
+        Err(FinishError::Error(TaskExitError::WebsocketError(
+            NextEventError::ServerIdleTimeout(idle),
+        ))) => {
+            return NetEvent::KeepaliveMissed {
+                tag,
+                idle_ms: as_millis(*idle),
+            }
+        }
+        Err(FinishError::ServerClosed(reason)) => reason.to_string(),
+        Err(FinishError::Error(e)) => e.to_string(),
+        Err(FinishError::Unknown) => "unknown".to_owned(),
+    };
+    NetEvent::Disconnected { tag, reason }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn json_lines_sink_writes_one_object_per_event() {
+        let sink = JsonLinesSink::new(Vec::new());
+        sink.record(NetEvent::ConnectStarted {
+            tag: "chat".to_owned(),
+            route_count: 3,
+        });
+        sink.record(NetEvent::Disconnected {
+            tag: "chat".to_owned(),
+            reason: "remotedisconnect".to_owned(),
+        });
+
+        let output = String::from_utf8(sink.into_inner()).expect("UTF-8");
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid JSON"))
+            .collect();
+
+        assert_matches!(&*lines, [started, disconnected] => {
+            assert_eq!(started["event"], "connect_started");
+            assert_eq!(started["tag"], "chat");
+            assert_eq!(started["route_count"], 3);
+            assert!(started["timestamp_ms"].is_u64());
+            assert_eq!(disconnected["event"], "disconnected");
+            assert_eq!(disconnected["reason"], "remotedisconnect");
+        });
+    }
+
+    #[test]
+    fn chat_finish_reports_keepalive_separately() {
+        assert_eq!(
+            chat_finish_event("chat".to_owned(), &Ok(FinishReason::RemoteDisconnect)),
+            NetEvent::Disconnected {
+                tag: "chat".to_owned(),
+                reason: "remotedisconnect".to_owned()
+            }
+        );
+        assert_eq!(
+            chat_finish_event(
+                "chat".to_owned(),
+                &Err(FinishError::Error(TaskExitError::WebsocketError(
+                    NextEventError::ServerIdleTimeout(Duration::from_secs(30))
+                )))
+            ),
+            NetEvent::KeepaliveMissed {
+                tag: "chat".to_owned(),
+                idle_ms: 30_000,
+            }
+        );
+    }
+
+    #[test]
+    fn chat_state_monitor_reports_degraded_and_recovered() {
+        let mut updates = ConnectionStateUpdates::default();
+        let mut rx = updates.subscribe();
+        let mut monitor = ChatStateMonitor::new(
+            updates.reporter("chat").expect("subscribed"),
+            RouteInfo::fake(),
+        );
+
+        // An answered ping isn't worth reporting.
+        monitor.sent_ping();
+        monitor.heard_from_server();
+        monitor.sent_ping();
+        assert_matches!(rx.try_recv(), Err(broadcast::error::TryRecvError::Empty));
+        let mut next_state = || rx.try_recv().expect("has update").state;
+
+        monitor.sent_ping();
+        monitor.sent_ping();
+        assert_eq!(
+            next_state(),
+            ConnectionState::Degraded {
+                missed_keepalives: 1
+            }
+        );
+        assert_eq!(
+            next_state(),
+            ConnectionState::Degraded {
+                missed_keepalives: 2
+            }
+        );
+
+        monitor.heard_from_server();
+        assert_eq!(
+            next_state(),
+            ConnectionState::Connected {
+                route_info: RouteInfo::fake()
+            }
+        );
+
+        monitor.finished(&Err(TaskExitError::WebsocketError(
+            NextEventError::ServerIdleTimeout(Duration::from_secs(30)),
+        )));
+        assert_eq!(
+            next_state(),
+            ConnectionState::Disconnected {
+                cause: DisconnectCause::KeepaliveTimeout
+            }
+        );
+    }
+}
@@ -10,9 +10,12 @@ pub mod chat;
 pub mod connect_state;
 pub mod enclave;
 pub mod env;
+pub mod events;
 pub mod proto;
+pub mod proxy_link;
 pub mod svr;
 pub mod svrb;
+pub mod trace;
 pub mod ws;
 
 // Re-export from `libsignal_net_infra`.
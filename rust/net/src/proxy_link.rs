@@ -0,0 +1,218 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Support for the Signal proxy links users share with each other.
+//!
+//! A Signal proxy link looks like `https://signal.tube/#proxy.example` or
+//! `sgnl://signal.tube/#proxy.example`, where the fragment names a TLS proxy
+//! (optionally with a port). Parsing lives here so that every platform applies
+//! the same validation rules.
+
+use std::num::NonZeroU16;
+
+use libsignal_net_infra::errors::LogSafeDisplay;
+use libsignal_net_infra::route::{
+    ConnectionProxyConfig, ConnectionProxyRouteProvider, DirectOrProxyProvider, RouteProvider,
+    RouteProviderContext, SIGNAL_TLS_PROXY_SCHEME,
+};
+
+/// The host used for all Signal proxy links.
+pub const SIGNAL_PROXY_LINK_HOST: &str = "signal.tube";
+
+const SIGNAL_PROXY_LINK_SCHEMES: [&str; 2] = ["https", "sgnl"];
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum ProxyLinkError {
+    /// proxy link was not a URL
+    NotAUrl,
+    /// proxy link scheme was not supported
+    UnsupportedScheme,
+    /// link was not a Signal proxy link
+    NotAProxyLink,
+    /// proxy link did not include a proxy address
+    MissingProxyAddress,
+    /// proxy address in link was invalid
+    InvalidProxyHost,
+    /// proxy port in link was invalid
+    InvalidProxyPort,
+}
+
+impl LogSafeDisplay for ProxyLinkError {}
+
+/// Parses a Signal proxy link into the config for the proxy it names.
+///
+/// The scheme and link host are matched case-insensitively. The proxy is
+/// always a TLS proxy; if the link doesn't include a port, the default port for
+/// [`SIGNAL_TLS_PROXY_SCHEME`] is used.
+pub fn parse_signal_proxy_link(link: &str) -> Result<ConnectionProxyConfig, ProxyLinkError> {
+    let (scheme, rest) = link
+        .trim()
+        .split_once("://")
+        .ok_or(ProxyLinkError::NotAUrl)?;
+    if !SIGNAL_PROXY_LINK_SCHEMES
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case(scheme))
+    {
+        return Err(ProxyLinkError::UnsupportedScheme);
+    }
+
+    let (link_host, rest) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+    if !link_host.eq_ignore_ascii_case(SIGNAL_PROXY_LINK_HOST) {
+        return Err(ProxyLinkError::NotAProxyLink);
+    }
+
+    let proxy_address = match rest.split_once('#') {
+        Some((_, address)) if !address.is_empty() => address,
+        _ => return Err(ProxyLinkError::MissingProxyAddress),
+    };
+
+    let (host, port) = match proxy_address.rsplit_once(':') {
+        // A bare IPv6 literal has colons but no port.
+        Some(_) if proxy_address.ends_with(']') => (proxy_address, None),
+        Some((host, port)) => {
+            let port = port
+                .parse::<NonZeroU16>()
+                .map_err(|_| ProxyLinkError::InvalidProxyPort)?;
+            (host, Some(port))
+        }
+        None => (proxy_address, None),
+    };
+
+    let is_valid_host = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '[' | ']' | ':'));
+    if !is_valid_host {
+        return Err(ProxyLinkError::InvalidProxyHost);
+    }
+
+    ConnectionProxyConfig::from_parts(SIGNAL_TLS_PROXY_SCHEME, host, port, None)
+        .map_err(|_| ProxyLinkError::InvalidProxyHost)
+}
+
+/// [`RouteProvider`] that produces every proxied route before any direct one.
+///
+/// Where [`DirectOrProxyProvider::maybe_proxied`] only ever connects through
+/// the proxy when one is configured, this provider falls back to connecting
+/// directly once the proxied routes have been exhausted.
+pub struct PreferProxyProvider<D> {
+    proxied: DirectOrProxyProvider<D, ConnectionProxyRouteProvider<D>>,
+    direct: DirectOrProxyProvider<D, ConnectionProxyRouteProvider<D>>,
+}
+
+impl<D> PreferProxyProvider<D> {
+    /// Creates a provider that wraps routes from `make_direct` with `proxy`.
+    ///
+    /// `make_direct` is invoked twice: once for the proxied routes and once for
+    /// the direct fallback.
+    pub fn new(proxy: ConnectionProxyConfig, make_direct: impl Fn() -> D) -> Self {
+        Self {
+            proxied: DirectOrProxyProvider::maybe_proxied(make_direct(), Some(proxy)),
+            direct: DirectOrProxyProvider::Direct(make_direct()),
+        }
+    }
+}
+
+impl<D> RouteProvider for PreferProxyProvider<D>
+where
+    DirectOrProxyProvider<D, ConnectionProxyRouteProvider<D>>: RouteProvider,
+{
+    type Route =
+        <DirectOrProxyProvider<D, ConnectionProxyRouteProvider<D>> as RouteProvider>::Route;
+
+    fn routes<'s>(
+        &'s self,
+        context: &impl RouteProviderContext,
+    ) -> impl Iterator<Item = Self::Route> + 's {
+        let Self { proxied, direct } = self;
+        proxied.routes(context).chain(direct.routes(context))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use itertools::Itertools as _;
+    use libsignal_net_infra::host::Host;
+    use libsignal_net_infra::route::testutils::FakeContext;
+    use libsignal_net_infra::route::{
+        ConnectionProxyRoute, DirectOrProxyRoute, DirectTcpRouteProvider, TcpRoute, TlsProxy,
+        TlsRoute, UnresolvedHost,
+    };
+    use nonzero_ext::nonzero;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("https://signal.tube/#proxy.example", "proxy.example", 443; "https")]
+    #[test_case("sgnl://signal.tube/#proxy.example", "proxy.example", 443; "sgnl")]
+    #[test_case("SGNL://Signal.Tube/#proxy.example", "proxy.example", 443; "case insensitive")]
+    #[test_case("  https://signal.tube/#proxy.example\n", "proxy.example", 443; "whitespace")]
+    #[test_case("https://signal.tube#proxy.example:8443", "proxy.example", 8443; "with port")]
+    #[test_case("https://signal.tube/#127.0.0.1", "127.0.0.1", 443; "IPv4")]
+    #[test_case("https://signal.tube/#[::1]", "::1", 443; "IPv6")]
+    #[test_case("https://signal.tube/#[::1]:8443", "::1", 8443; "IPv6 with port")]
+    fn parse_valid_link(link: &str, expected_host: &str, expected_port: u16) {
+        let proxy = assert_matches!(
+            parse_signal_proxy_link(link),
+            Ok(ConnectionProxyConfig::Tls(proxy)) => proxy
+        );
+        let TlsProxy {
+            proxy_host,
+            proxy_port,
+            proxy_certs: _,
+            proxy_client_cert,
+        } = proxy;
+        assert_eq!(proxy_host, Host::parse_as_ip_or_domain(expected_host));
+        assert_eq!(proxy_port.get(), expected_port);
+        assert!(proxy_client_cert.is_none());
+    }
+
+    #[test_case("proxy.example" => ProxyLinkError::NotAUrl)]
+    #[test_case("http://signal.tube/#proxy.example" => ProxyLinkError::UnsupportedScheme)]
+    #[test_case("https://proxy.example/#proxy.example" => ProxyLinkError::NotAProxyLink)]
+    #[test_case("https://signal.tube.example/#proxy.example" => ProxyLinkError::NotAProxyLink)]
+    #[test_case("https://signal.tube/" => ProxyLinkError::MissingProxyAddress)]
+    #[test_case("https://signal.tube/#" => ProxyLinkError::MissingProxyAddress)]
+    #[test_case("https://signal.tube/#proxy.example:0" => ProxyLinkError::InvalidProxyPort)]
+    #[test_case("https://signal.tube/#proxy.example:http" => ProxyLinkError::InvalidProxyPort)]
+    #[test_case("https://signal.tube/#user@proxy.example" => ProxyLinkError::InvalidProxyHost)]
+    #[test_case("https://signal.tube/#proxy.example/path" => ProxyLinkError::InvalidProxyHost)]
+    #[test_case("https://signal.tube/#:443" => ProxyLinkError::InvalidProxyHost)]
+    fn parse_invalid_link(link: &str) -> ProxyLinkError {
+        parse_signal_proxy_link(link).expect_err("should fail")
+    }
+
+    #[test]
+    fn prefer_proxy_provider_orders_proxy_first() {
+        const DIRECT_HOST: &str = "direct.example";
+        const DIRECT_PORT: NonZeroU16 = nonzero!(443u16);
+
+        let proxy = parse_signal_proxy_link("https://signal.tube/#proxy.example").expect("valid");
+        let provider = PreferProxyProvider::new(proxy, || {
+            DirectTcpRouteProvider::new(DIRECT_HOST.into(), DIRECT_PORT)
+        });
+
+        let routes = provider.routes(&FakeContext::new()).collect_vec();
+
+        let (proxy_route, direct_route) = assert_matches!(
+            &*routes,
+            [DirectOrProxyRoute::Proxy(proxied), DirectOrProxyRoute::Direct(direct)] => (proxied, direct)
+        );
+        assert_matches!(
+            proxy_route,
+            ConnectionProxyRoute::Tls {
+                proxy: TlsRoute { inner: TcpRoute { address: Host::Domain(UnresolvedHost(host)), port }, .. }
+            } if &**host == "proxy.example" && port.get() == 443
+        );
+        assert_eq!(
+            direct_route,
+            &TcpRoute {
+                address: UnresolvedHost(DIRECT_HOST.into()),
+                port: DIRECT_PORT,
+            }
+        );
+    }
+}
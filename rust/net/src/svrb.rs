@@ -24,6 +24,8 @@ use thiserror::Error;
 
 mod ppss_ops;
 
+pub mod progress;
+pub mod shares;
 pub mod traits;
 
 #[cfg(any(test, feature = "test-util"))]
@@ -482,6 +484,97 @@ pub async fn remove_backup<R: traits::Remove>(
     .swap_remove(0) // We only care about the first element (for current_svrb) - this removes it from the vec and returns it.
 }
 
+/// The outcome of [`remove_and_verify`] for a single server.
+#[derive(Debug)]
+pub enum RemovalVerification {
+    /// The data was removed, and a follow-up query confirmed that it's gone.
+    Removed,
+    /// The removal request succeeded, but the server still reports data.
+    StillPresent,
+    /// The removal or the follow-up query couldn't be completed.
+    Failed(Error),
+}
+
+/// Removes the stored data from every server in `svrb`'s enclave, then checks that each server
+/// no longer has it.
+///
+/// Unlike [`remove_backup`], this reports the outcome for each server separately, in server
+/// order, so that a caller can tell exactly where data may remain.
+pub async fn remove_and_verify<T: traits::SvrBConnect + Sync>(
+    svrb: &T,
+) -> Vec<RemovalVerification> {
+    ppss_ops::do_remove_and_verify(svrb.connect().await).await
+}
+
+/// Errors from [`migrate_backup`], identifying which half of the migration failed.
+#[derive(Debug, Error, displaydoc::Display)]
+pub enum MigrateError {
+    /// failed to restore from the previous enclaves: {0}
+    Restore(Error),
+    /// restored from a previous enclave, but failed to store to the current one: {0}
+    Store(Error),
+}
+
+impl LogSafeDisplay for MigrateError {}
+
+pub struct BackupMigrateResponse {
+    /// The token for the backup file described by the metadata passed to [`migrate_backup`].
+    pub previous_forward_secrecy_token: BackupForwardSecrecyToken,
+    /// The result of storing to the current enclave, to be used as with [`store_backup`].
+    pub store: BackupStoreResponse,
+}
+
+/// Moves a backup secret from the previous enclaves to the current one, after enclave rotation.
+///
+/// This restores from `previous_svrbs` using `metadata`, then stores a new secret in
+/// `current_svrb` that the returned [`BackupStoreResponse`] can be decrypted with. Nothing is
+/// removed from the previous enclaves, since the existing backup file still depends on them
+/// until it's replaced by one using the new metadata; the next [`store_backup`] will remove them.
+///
+/// `previous_svrbs` must not be empty.
+pub async fn migrate_backup<B, R>(
+    current_svrb: &B,
+    previous_svrbs: &[R],
+    backup_key: &BackupKey,
+    metadata: BackupFileMetadataRef<'_>,
+) -> Result<BackupMigrateResponse, MigrateError>
+where
+    B: traits::Backup + traits::Prepare,
+    R: traits::Restore,
+{
+    let BackupRestoreResponse {
+        forward_secrecy_token: previous_forward_secrecy_token,
+        next_backup_data,
+    } = restore_backup(previous_svrbs, backup_key, metadata)
+        .await
+        .map_err(MigrateError::Restore)?;
+
+    // Storing after a restore only prepares a new secret; it has to be stored again to actually
+    // write that secret to the current enclave.
+    let no_previous: &[B] = &[];
+    let prepared = store_backup(
+        current_svrb,
+        no_previous,
+        backup_key,
+        next_backup_data.as_ref(),
+    )
+    .await
+    .map_err(MigrateError::Store)?;
+    let store = store_backup(
+        current_svrb,
+        no_previous,
+        backup_key,
+        prepared.next_backup_data.as_ref(),
+    )
+    .await
+    .map_err(MigrateError::Store)?;
+
+    Ok(BackupMigrateResponse {
+        previous_forward_secrecy_token,
+        store,
+    })
+}
+
 #[cfg(feature = "test-util")]
 pub mod test_support {
 
@@ -741,6 +834,100 @@ mod test {
         assert!(restored2.forward_secrecy_token.0 != restored.forward_secrecy_token.0);
     }
 
+    static MIGRATE_FINALIZE_CALLED: AtomicU8 = AtomicU8::new(0);
+
+    #[tokio::test]
+    async fn migrate_moves_secret_to_current_enclave() {
+        let previous = TestSvrBClient {
+            prepare_fn: || Backup4 {
+                requests: vec![],
+                output: [1u8; 32],
+            },
+            finalize_fn: || Ok(()),
+            restore_fn: || Ok([1u8; 32]),
+            ..TestSvrBClient::default()
+        };
+        let aep = AccountEntropyPool::from_str(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        )
+        .expect("should create AEP");
+        let backup_key = BackupKey::derive_from_account_entropy_pool(&aep);
+        let backup = store_backup(
+            &previous,
+            &EMPTY,
+            &backup_key,
+            create_new_backup_chain(&previous, &backup_key).as_ref(),
+        )
+        .await
+        .expect("should store");
+
+        let current = TestSvrBClient {
+            prepare_fn: || Backup4 {
+                requests: vec![],
+                output: [2u8; 32],
+            },
+            finalize_fn: || {
+                MIGRATE_FINALIZE_CALLED.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            },
+            restore_fn: || Ok([2u8; 32]),
+            ..TestSvrBClient::default()
+        };
+        let migrated = migrate_backup(
+            &current,
+            &[previous],
+            &backup_key,
+            BackupFileMetadataRef(&backup.metadata.0),
+        )
+        .await
+        .expect("should migrate");
+        assert_eq!(
+            backup.forward_secrecy_token.0,
+            migrated.previous_forward_secrecy_token.0
+        );
+        assert_eq!(MIGRATE_FINALIZE_CALLED.load(Ordering::Relaxed), 1);
+
+        // The new metadata can be restored using only the current enclave.
+        let restored = restore_backup(
+            &[current],
+            &backup_key,
+            BackupFileMetadataRef(&migrated.store.metadata.0),
+        )
+        .await
+        .expect("should restore");
+        assert_eq!(
+            migrated.store.forward_secrecy_token.0,
+            restored.forward_secrecy_token.0
+        );
+    }
+
+    #[tokio::test]
+    async fn migrate_reports_restore_failure() {
+        let previous = TestSvrBClient {
+            restore_fn: || Err(Error::DataMissing),
+            ..TestSvrBClient::default()
+        };
+        let aep = AccountEntropyPool::from_str(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        )
+        .expect("should create AEP");
+        let backup_key = BackupKey::derive_from_account_entropy_pool(&aep);
+        let metadata = backup_metadata::MetadataPb {
+            iv: vec![0; IV_SIZE],
+            pair: vec![Default::default()],
+            ..Default::default()
+        };
+
+        let result = migrate_backup(
+            &TestSvrBClient::default(),
+            &[previous],
+            &backup_key,
+            BackupFileMetadataRef(&metadata.write_to_bytes().expect("can serialize")),
+        )
+        .await;
+        assert_matches!(result, Err(MigrateError::Restore(Error::DataMissing)));
+    }
+
     #[tokio::test]
     async fn restore_primary_success() {
         let svrb = TestSvrBClient {
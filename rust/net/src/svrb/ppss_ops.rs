@@ -18,7 +18,7 @@ use libsignal_svrb::{Query4, Remove4, Restore1};
 use rand::rngs::OsRng;
 use rand::TryRngCore;
 
-use super::Error;
+use super::{Error, RemovalVerification};
 use crate::enclave::{
     ArrayIsh, ConnectionLabel, IntoConnectionResults, LabeledConnection, PpssSetup,
 };
@@ -34,8 +34,8 @@ pub fn do_prepare<Env: PpssSetup>(password: &[u8]) -> Backup4 {
     )
 }
 
-pub async fn do_backup<Env: PpssSetup>(
-    connect_results: Env::ConnectionResults,
+pub async fn do_backup(
+    connect_results: impl IntoConnectionResults,
     backup: &Backup4,
 ) -> Result<(), Error> {
     let ConnectionContext {
@@ -57,6 +57,40 @@ pub async fn do_backup<Env: PpssSetup>(
     Ok(())
 }
 
+/// Like [`do_backup`], but writes each server's share independently and reports the outcome for
+/// each one.
+///
+/// Only the shares for which `should_write` returns `true` are written; the outcome for the rest
+/// is `None`.
+pub async fn do_backup_shares(
+    connect_results: impl IntoConnectionResults,
+    backup: &Backup4,
+    should_write: impl Fn(usize) -> bool,
+) -> Vec<Option<Result<(), Error>>> {
+    let futures = connect_results
+        .into_connection_results()
+        .into_iter()
+        .zip(&backup.requests)
+        .enumerate()
+        .map(|(index, (connect_result, request))| {
+            let write = should_write(index);
+            async move {
+                if !write {
+                    return None;
+                }
+                let result = async move {
+                    let mut connection = connect_result?;
+                    let response = run_attested_interaction(&mut connection, request).await?;
+                    collect_responses([response])?;
+                    Ok::<_, Error>(())
+                }
+                .await;
+                Some(result)
+            }
+        });
+    join_all(futures).await
+}
+
 pub async fn do_restore<Env: PpssSetup>(
     connect_results: impl IntoConnectionResults,
     password: &[u8],
@@ -133,6 +167,36 @@ pub async fn do_remove(connect_results: impl IntoConnectionResults) -> Result<()
     Ok(())
 }
 
+/// Removes the data from each server, then queries each one to confirm that it's gone.
+pub async fn do_remove_and_verify(
+    connect_results: impl IntoConnectionResults,
+) -> Vec<RemovalVerification> {
+    let futures = connect_results
+        .into_connection_results()
+        .into_iter()
+        .zip(Remove4::requests().zip(Query4::requests()))
+        .map(
+            |(connect_result, (remove_request, query_request))| async move {
+                let result = async move {
+                    let mut connection = connect_result?;
+                    let response =
+                        run_attested_interaction(&mut connection, remove_request).await?;
+                    collect_responses([response])?;
+                    let response = run_attested_interaction(&mut connection, query_request).await?;
+                    let responses = collect_responses([response])?;
+                    Ok::<_, Error>(Query4::finalize(&responses)?)
+                }
+                .await;
+                match result {
+                    Ok(_tries_remaining) => RemovalVerification::StillPresent,
+                    Err(Error::DataMissing) => RemovalVerification::Removed,
+                    Err(e) => RemovalVerification::Failed(e),
+                }
+            },
+        );
+    join_all(futures).await
+}
+
 pub async fn do_query(connect_results: impl IntoConnectionResults) -> Result<u32, Error> {
     let ConnectionContext {
         mut connections,
@@ -235,10 +299,20 @@ mod test {
     #[tokio::test]
     async fn do_backup_fails_with_the_first_error() {
         let backup = do_prepare::<TestEnv>(b"");
-        let result = do_backup::<TestEnv>(NotConnectedResults, &backup).await;
+        let result = do_backup(NotConnectedResults, &backup).await;
         assert_matches!(result, Err(crate::svrb::Error::AllConnectionAttemptsFailed));
     }
 
+    #[tokio::test]
+    async fn do_backup_shares_reports_each_server() {
+        let backup = do_prepare::<TestEnv>(b"");
+        let results = do_backup_shares(NotConnectedResults, &backup, |index| index == 1).await;
+        assert_matches!(
+            &*results,
+            [None, Some(Err(crate::svrb::Error::AttestationError(_)))]
+        );
+    }
+
     #[tokio::test]
     async fn do_restore_fails_with_the_first_error() {
         let result = do_restore::<TestEnv>(NotConnectedResults, b"").await;
@@ -251,6 +325,18 @@ mod test {
         assert_matches!(result, Err(crate::svrb::Error::AllConnectionAttemptsFailed));
     }
 
+    #[tokio::test]
+    async fn do_remove_and_verify_reports_each_server() {
+        let results = do_remove_and_verify(NotConnectedResults).await;
+        assert_matches!(
+            &*results,
+            [
+                RemovalVerification::Failed(crate::svrb::Error::AllConnectionAttemptsFailed),
+                RemovalVerification::Failed(crate::svrb::Error::AttestationError(_)),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn do_remove_fails_if_all_connections_failed() {
         let result = do_remove(NotConnectedResults).await;
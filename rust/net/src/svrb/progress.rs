@@ -0,0 +1,183 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Progress reporting for SVRB operations.
+//!
+//! Wrap each enclave's [`SvrBConnect`] in a [`WithProgress`] before passing it to
+//! [`store_backup`](super::store_backup), [`restore_backup`](super::restore_backup), or
+//! [`remove_backup`](super::remove_backup) to be told as each enclave moves through the
+//! operation.
+
+use async_trait::async_trait;
+
+use super::traits::{Backup, Prepare, Query, Remove, Restore, SvrBConnect};
+use super::{ppss_ops, Backup4, Error, Secret};
+use crate::enclave::{self, ArrayIsh, IntoConnectionResults, LabeledConnection, PpssSetup};
+
+/// Which enclave a [`Phase`] is being reported for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnclaveRole {
+    Current,
+    /// The enclave at this index in the list of previous enclaves.
+    Previous(usize),
+}
+
+/// How far an operation against a single enclave has gotten.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Connecting and performing the attested handshake.
+    Connecting,
+    /// Connected; exchanging requests with the enclave.
+    Connected,
+    /// The operation finished successfully.
+    Complete,
+    /// The operation failed, either while connecting or afterwards.
+    Failed,
+}
+
+pub trait ProgressObserver: Sync {
+    fn on_progress(&self, enclave: EnclaveRole, phase: Phase);
+}
+
+/// Reports the progress of each operation on `inner` to `observer`.
+pub struct WithProgress<'a, T> {
+    inner: &'a T,
+    role: EnclaveRole,
+    observer: &'a dyn ProgressObserver,
+}
+
+impl<'a, T> WithProgress<'a, T> {
+    pub fn new(inner: &'a T, role: EnclaveRole, observer: &'a dyn ProgressObserver) -> Self {
+        Self {
+            inner,
+            role,
+            observer,
+        }
+    }
+
+    fn report(&self, phase: Phase) {
+        self.observer.on_progress(self.role, phase)
+    }
+
+    fn finish<R>(&self, result: Result<R, Error>) -> Result<R, Error> {
+        self.report(if result.is_ok() {
+            Phase::Complete
+        } else {
+            Phase::Failed
+        });
+        result
+    }
+}
+
+/// Connection results that have already been checked for failures.
+struct ConnectResults<R>(R);
+
+impl<R> IntoConnectionResults for ConnectResults<R>
+where
+    R: ArrayIsh<Result<LabeledConnection, enclave::Error>> + Send,
+{
+    type ConnectionResults = R;
+
+    fn into_connection_results(self) -> Self::ConnectionResults {
+        self.0
+    }
+}
+
+type ConnectionResultsFor<T> =
+    <<<T as SvrBConnect>::Env as PpssSetup>::ConnectionResults as IntoConnectionResults>::ConnectionResults;
+
+impl<T: SvrBConnect + Sync> WithProgress<'_, T> {
+    async fn connect(&self) -> ConnectResults<ConnectionResultsFor<T>> {
+        self.report(Phase::Connecting);
+        let results = self.inner.connect().await.into_connection_results();
+        // A failure will be reported once the operation gives up.
+        if results.as_ref().iter().all(Result::is_ok) {
+            self.report(Phase::Connected);
+        }
+        ConnectResults(results)
+    }
+}
+
+impl<T: SvrBConnect> Prepare for WithProgress<'_, T> {
+    fn prepare(&self, password: &[u8]) -> Backup4 {
+        ppss_ops::do_prepare::<T::Env>(password)
+    }
+}
+
+#[async_trait]
+impl<T: SvrBConnect + Sync> Backup for WithProgress<'_, T> {
+    async fn finalize(&self, backup: &Backup4) -> Result<(), Error> {
+        let results = self.connect().await;
+        self.finish(ppss_ops::do_backup(results, backup).await)
+    }
+}
+
+#[async_trait]
+impl<T: SvrBConnect + Sync> Restore for WithProgress<'_, T> {
+    async fn restore(&self, password: &[u8]) -> Result<Secret, Error> {
+        let results = self.connect().await;
+        self.finish(ppss_ops::do_restore::<T::Env>(results, password).await)
+    }
+}
+
+#[async_trait]
+impl<T: SvrBConnect + Sync> Remove for WithProgress<'_, T> {
+    async fn remove(&self) -> Result<(), Error> {
+        let results = self.connect().await;
+        self.finish(ppss_ops::do_remove(results).await)
+    }
+}
+
+#[async_trait]
+impl<T: SvrBConnect + Sync> Query for WithProgress<'_, T> {
+    async fn query(&self) -> Result<u32, Error> {
+        let results = self.connect().await;
+        self.finish(ppss_ops::do_query(results).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::env::SvrBEnv;
+
+    struct FailingConnect;
+
+    #[async_trait]
+    impl SvrBConnect for FailingConnect {
+        type Env = SvrBEnv<'static>;
+
+        async fn connect(&self) -> <Self::Env as PpssSetup>::ConnectionResults {
+            Err(enclave::Error::AllConnectionAttemptsFailed)
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver(Mutex<Vec<(EnclaveRole, Phase)>>);
+
+    impl ProgressObserver for RecordingObserver {
+        fn on_progress(&self, enclave: EnclaveRole, phase: Phase) {
+            self.0.lock().unwrap().push((enclave, phase));
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_connect_failure() {
+        let observer = RecordingObserver::default();
+        let svrb = WithProgress::new(&FailingConnect, EnclaveRole::Previous(1), &observer);
+
+        svrb.remove().await.expect_err("cannot connect");
+
+        assert_eq!(
+            *observer.0.lock().unwrap(),
+            [
+                (EnclaveRole::Previous(1), Phase::Connecting),
+                (EnclaveRole::Previous(1), Phase::Failed),
+            ]
+        );
+    }
+}
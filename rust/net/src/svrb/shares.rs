@@ -0,0 +1,140 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Writing a backup's shares to each server individually.
+//!
+//! [`Backup::finalize`](super::traits::Backup::finalize) fails as a whole if any server fails, and
+//! doesn't say which shares were written. [`finalize_shares`] reports the outcome for each
+//! server instead, and [`repair_shares`] retries only the servers that failed, reusing the same
+//! prepared [`Backup4`] rather than starting over with a new one.
+
+use super::traits::SvrBConnect;
+use super::{ppss_ops, Backup4, Error};
+
+/// The outcome of writing each server's share of a backup, in server order.
+#[derive(Debug)]
+pub struct ShareOutcomes(Vec<Result<(), Error>>);
+
+impl ShareOutcomes {
+    pub fn outcomes(&self) -> &[Result<(), Error>] {
+        &self.0
+    }
+
+    /// Whether every share was written successfully.
+    pub fn is_complete(&self) -> bool {
+        self.0.iter().all(Result::is_ok)
+    }
+
+    /// Converts to a single result, failing with the first error if any share wasn't written.
+    pub fn into_result(self) -> Result<(), Error> {
+        self.0.into_iter().collect()
+    }
+}
+
+/// Writes each server's share of `backup`, reporting the outcome for each.
+pub async fn finalize_shares<T: SvrBConnect + Sync>(svrb: &T, backup: &Backup4) -> ShareOutcomes {
+    let results = ppss_ops::do_backup_shares(svrb.connect().await, backup, |_| true).await;
+    ShareOutcomes(
+        results
+            .into_iter()
+            .map(|result| result.expect("all shares attempted"))
+            .collect(),
+    )
+}
+
+/// Retries writing the shares of `backup` that failed according to `outcomes`.
+///
+/// `backup` must be the same one `outcomes` was produced for.
+pub async fn repair_shares<T: SvrBConnect + Sync>(
+    svrb: &T,
+    backup: &Backup4,
+    outcomes: ShareOutcomes,
+) -> ShareOutcomes {
+    if outcomes.is_complete() {
+        return outcomes;
+    }
+    let ShareOutcomes(previous) = outcomes;
+    assert_eq!(
+        previous.len(),
+        backup.requests.len(),
+        "outcomes are for a different backup"
+    );
+
+    let failed = previous.iter().map(Result::is_err).collect::<Vec<_>>();
+    log::info!(
+        "repairing {} of {} SVRB shares",
+        failed.iter().filter(|f| **f).count(),
+        failed.len()
+    );
+    let retried =
+        ppss_ops::do_backup_shares(svrb.connect().await, backup, |index| failed[index]).await;
+
+    ShareOutcomes(
+        previous
+            .into_iter()
+            .zip(retried)
+            .map(|(previous, retried)| retried.unwrap_or(previous))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use assert_matches::assert_matches;
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::enclave::{self, PpssSetup};
+    use crate::env::SvrBEnv;
+
+    struct FailingConnect;
+
+    #[async_trait]
+    impl SvrBConnect for FailingConnect {
+        type Env = SvrBEnv<'static>;
+
+        async fn connect(&self) -> <Self::Env as PpssSetup>::ConnectionResults {
+            Err(enclave::Error::AllConnectionAttemptsFailed)
+        }
+    }
+
+    struct UnusedConnect;
+
+    #[async_trait]
+    impl SvrBConnect for UnusedConnect {
+        type Env = SvrBEnv<'static>;
+
+        async fn connect(&self) -> <Self::Env as PpssSetup>::ConnectionResults {
+            panic!("should not connect")
+        }
+    }
+
+    fn backup() -> Backup4 {
+        ppss_ops::do_prepare::<SvrBEnv<'static>>(b"password")
+    }
+
+    #[tokio::test]
+    async fn reports_failed_share() {
+        let backup = backup();
+        let outcomes = finalize_shares(&FailingConnect, &backup).await;
+        assert!(!outcomes.is_complete());
+        assert_matches!(
+            outcomes.outcomes(),
+            [Err(Error::AllConnectionAttemptsFailed)]
+        );
+
+        let outcomes = repair_shares(&FailingConnect, &backup, outcomes).await;
+        assert_matches!(
+            outcomes.into_result(),
+            Err(Error::AllConnectionAttemptsFailed)
+        );
+    }
+
+    #[tokio::test]
+    async fn repair_skips_complete_backup() {
+        let outcomes = repair_shares(&UnusedConnect, &backup(), ShareOutcomes(vec![Ok(())])).await;
+        assert!(outcomes.is_complete());
+    }
+}
@@ -64,7 +64,7 @@ where
     T: SvrBConnect + Sync,
 {
     async fn finalize(&self, backup: &Backup4) -> Result<(), Error> {
-        ppss_ops::do_backup::<T::Env>(self.connect().await, backup).await
+        ppss_ops::do_backup(self.connect().await, backup).await
     }
 }
 
@@ -0,0 +1,89 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Identifiers for following a single request through client and server logs.
+
+use std::fmt::{Debug, Display};
+
+use http::{HeaderName, HeaderValue};
+use libsignal_net_infra::errors::LogSafeDisplay;
+use libsignal_net_infra::AsStaticHttpHeader;
+
+use crate::env::TRACE_ID_HEADER_NAME;
+
+/// A randomly generated identifier for a single request.
+///
+/// The ID is sent to the server in the [`TRACE_ID_HEADER_NAME`] header and included in log
+/// lines and [`NetEvent`](crate::events::NetEvent)s about the request, so a failure reported
+/// by a user can be matched up with what the client and server each saw.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceId(u64);
+
+impl TraceId {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl Display for TraceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl Debug for TraceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TraceId({self})")
+    }
+}
+
+impl LogSafeDisplay for TraceId {}
+
+impl AsStaticHttpHeader for TraceId {
+    const HEADER_NAME: HeaderName = HeaderName::from_static(TRACE_ID_HEADER_NAME);
+
+    fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.to_string()).expect("hex digits are a valid header value")
+    }
+}
+
+/// An error along with the [`TraceId`] of the request that produced it.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+/// {error} (trace ID {trace_id})
+pub struct Traced<E> {
+    pub trace_id: TraceId,
+    #[source]
+    pub error: E,
+}
+
+impl<E: LogSafeDisplay> LogSafeDisplay for Traced<E> {}
+
+#[cfg(test)]
+mod test {
+    use libsignal_net_infra::AsHttpHeader as _;
+
+    use super::*;
+
+    #[test]
+    fn formats_as_fixed_width_hex() {
+        let trace_id = TraceId(0xabc);
+        assert_eq!(trace_id.to_string(), "0000000000000abc");
+        assert_eq!(
+            trace_id.as_header(),
+            (
+                HeaderName::from_static(TRACE_ID_HEADER_NAME),
+                HeaderValue::from_static("0000000000000abc")
+            )
+        );
+        assert_eq!(
+            Traced {
+                trace_id,
+                error: "failed"
+            }
+            .to_string(),
+            "failed (trace ID 0000000000000abc)"
+        );
+    }
+}
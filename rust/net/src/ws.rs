@@ -57,6 +57,40 @@ impl WebSocketServiceConnectError {
         }
     }
 
+    /// Decides whether this error should stop further connection attempts.
+    ///
+    /// This is the policy used by [`DefaultErrorClassifier`](crate::connect_state::DefaultErrorClassifier).
+    pub fn classify(&self) -> ErrorClass {
+        match self {
+            WebSocketServiceConnectError::RejectedByServer {
+                response,
+                received_at: _,
+            } => {
+                // Retry-After takes precedence over everything else.
+                if libsignal_net_infra::extract_retry_later(response.headers()).is_some() ||
+                    // If we're rejected based on the request (4xx), there's no point in retrying.
+                    response.status().is_client_error()
+                {
+                    ErrorClass::Fatal
+                } else {
+                    ErrorClass::Intermittent
+                }
+            }
+            WebSocketServiceConnectError::Connect(connect_error, NotRejectedByServer { .. }) => {
+                // If we *locally* chose to abort, that isn't route-specific; treat it as fatal.
+                // In any other case, if we didn't make it to the server, we should retry.
+                if matches!(
+                    connect_error,
+                    WebSocketConnectError::Transport(TransportConnectError::ClientAbort)
+                ) {
+                    ErrorClass::Fatal
+                } else {
+                    ErrorClass::Intermittent
+                }
+            }
+        }
+    }
+
     pub fn invalid_proxy_configuration() -> Self {
         Self::Connect(
             WebSocketConnectError::Transport(TransportConnectError::InvalidConfiguration),
@@ -88,6 +122,15 @@ impl Display for WebSocketServiceConnectError {
     }
 }
 
+/// How a failed connection attempt should affect the overall connect operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The failure is specific to the route; other routes should still be tried.
+    Intermittent,
+    /// The failure applies to every route; stop and report the error.
+    Fatal,
+}
+
 /// Marker that indicates an error was not a rejection from a Signal server
 ///
 /// This type is intentionally only constructible by code in this module. To
@@ -130,6 +173,8 @@ mod test {
             )
         );
 
+        assert_eq!(non_http_error.classify(), ErrorClass::Intermittent);
+
         let mut response_4xx = http::Response::new(None);
         *response_4xx.status_mut() = http::StatusCode::BAD_REQUEST;
 
@@ -171,6 +216,7 @@ mod test {
                 error_with_header,
                 WebSocketServiceConnectError::RejectedByServer { response: _, received_at } if received_at == now
             );
+            assert_eq!(error_with_header.classify(), ErrorClass::Fatal);
         }
     }
 }
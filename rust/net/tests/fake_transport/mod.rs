@@ -204,6 +204,9 @@ impl FakeDeps {
             local_idle_timeout,
             remote_idle_ping_timeout,
             remote_idle_disconnect_timeout: _,
+            max_frame_size,
+            max_message_size,
+            application_idle_timeout,
         } = RECOMMENDED_WS_CONFIG;
         let connection_resources = ConnectionResources {
             connect_state,
@@ -225,6 +228,11 @@ impl FakeDeps {
                 local_idle_timeout,
                 remote_idle_timeout: remote_idle_ping_timeout,
                 initial_request_id: 0,
+                max_frame_size,
+                max_message_size,
+                application_idle_timeout,
+                rate_limit: None,
+                in_flight_limit: None,
             },
             None,
             "fake chat",
@@ -6,6 +6,7 @@
 fn main() {
     let protos = [
         "src/proto/fingerprint.proto",
+        "src/proto/provisioning.proto",
         "src/proto/sealed_sender.proto",
         "src/proto/service.proto",
         "src/proto/storage.proto",
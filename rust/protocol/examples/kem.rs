@@ -19,7 +19,6 @@ struct Cli {
 #[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 enum KeyType {
     Kyber,
-    #[cfg(feature = "mlkem1024")]
     #[value(name = "mlkem")]
     MlKem,
 }
@@ -28,7 +27,6 @@ impl From<KeyType> for libsignal_protocol::kem::KeyType {
     fn from(value: KeyType) -> Self {
         match value {
             KeyType::Kyber => Self::Kyber1024,
-            #[cfg(feature = "mlkem1024")]
             KeyType::MlKem => Self::MLKEM1024,
         }
     }
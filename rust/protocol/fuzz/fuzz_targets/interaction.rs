@@ -220,7 +220,9 @@ impl Participant {
     async fn archive_session(&mut self, their_address: &ProtocolAddress) {
         if let Some(mut session) = self.store.load_session(their_address).await.unwrap() {
             info!("{}: archiving session", self.name);
-            session.archive_current_state().unwrap();
+            session
+                .archive_current_state_at(SystemTime::UNIX_EPOCH)
+                .unwrap();
             self.store
                 .store_session(their_address, &session)
                 .await
@@ -0,0 +1,109 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Moving protocol state between addresses for the same device.
+//!
+//! A conversation can start out addressed to a contact's PNI and later switch to their ACI once
+//! it's discovered. The session, identity key, and sender keys recorded for the old address then
+//! need to be available under the new one.
+
+use uuid::Uuid;
+
+use crate::{
+    ArchiveCollision, IdentityKeyStore, ProtocolAddress, Result, SenderKeyStore, SessionStore,
+    SignalProtocolError,
+};
+
+/// What [`migrate_address`] did.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AddressMigration {
+    /// Whether the session was copied.
+    pub session: bool,
+    /// Whether the identity key was copied.
+    pub identity: bool,
+    /// The distribution IDs whose sender keys were copied.
+    pub sender_keys: Vec<Uuid>,
+    /// Whether anything was skipped because the new address already had a different entry.
+    pub kept_existing: bool,
+}
+
+/// Copies the session, identity key, and sender keys for `old` to `new`.
+///
+/// The stores can't be enumerated, so the caller lists the `distribution_ids` whose sender keys
+/// should be copied. Entries `new` already has are handled according to `on_collision`. A
+/// session is only copied along with the identity key it was established with: if `new` keeps a
+/// different identity key, its session is kept too.
+///
+/// The store interfaces have no way to remove entries, so those for `old` are left in place; the
+/// caller should delete them once the migration has been saved.
+#[allow(clippy::too_many_arguments)]
+pub async fn migrate_address(
+    old: &ProtocolAddress,
+    new: &ProtocolAddress,
+    distribution_ids: &[Uuid],
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    sender_key_store: &mut dyn SenderKeyStore,
+    on_collision: ArchiveCollision,
+) -> Result<AddressMigration> {
+    if old == new {
+        return Err(SignalProtocolError::InvalidArgument(
+            "cannot migrate an address to itself".to_string(),
+        ));
+    }
+
+    let mut result = AddressMigration::default();
+
+    let keep_identity = match identity_store.get_identity(old).await? {
+        None => false,
+        Some(identity) => match identity_store.get_identity(new).await? {
+            Some(existing) if existing == identity => false,
+            Some(_) if on_collision == ArchiveCollision::KeepExisting => true,
+            _ => {
+                identity_store.save_identity(new, &identity).await?;
+                result.identity = true;
+                false
+            }
+        },
+    };
+
+    if let Some(session) = session_store.load_session(old).await? {
+        if keep_identity
+            || (on_collision == ArchiveCollision::KeepExisting
+                && session_store.load_session(new).await?.is_some())
+        {
+            result.kept_existing = true;
+        } else {
+            session_store.store_session(new, &session).await?;
+            result.session = true;
+        }
+    } else if keep_identity {
+        result.kept_existing = true;
+    }
+
+    for &distribution_id in distribution_ids {
+        let Some(record) = sender_key_store
+            .load_sender_key(old, distribution_id)
+            .await?
+        else {
+            continue;
+        };
+        if on_collision == ArchiveCollision::KeepExisting
+            && sender_key_store
+                .load_sender_key(new, distribution_id)
+                .await?
+                .is_some()
+        {
+            result.kept_existing = true;
+            continue;
+        }
+        sender_key_store
+            .store_sender_key(new, distribution_id, &record)
+            .await?;
+        result.sender_keys.push(distribution_id);
+    }
+
+    Ok(result)
+}
@@ -11,6 +11,12 @@ pub const MAX_RECEIVER_CHAINS: usize = 5;
 pub const ARCHIVED_STATES_MAX_LENGTH: usize = 40;
 pub const MAX_SENDER_KEY_STATES: usize = 5;
 
+// Hard upper bounds for the configurable limits in ProtocolLimits, whose defaults are above.
+// Keeping more message keys than the largest allowed forward jump would never be useful.
+pub const MAX_MESSAGE_KEYS_UPPER_BOUND: usize = MAX_FORWARD_JUMPS;
+pub const ARCHIVED_STATES_UPPER_BOUND: usize = 200;
+pub const MAX_SENDER_KEY_STATES_UPPER_BOUND: usize = 50;
+
 /// Sessions that have not gotten a response after this interval will be considered "soft archived"
 /// and will not be available for sending.
 ///
@@ -44,6 +44,17 @@ fn aes_256_ctr_decrypt(ctext: &[u8], key: &[u8]) -> Result<Vec<u8>, DecryptionEr
     })
 }
 
+/// Returns `result`, first zeroing `output` if it's an error.
+///
+/// Used by the `*_decrypt_into` functions, so that a plaintext already written to the caller's
+/// buffer isn't left there when a later step fails.
+pub(crate) fn zero_output_on_error<T, E>(output: &mut [u8], result: Result<T, E>) -> Result<T, E> {
+    if result.is_err() {
+        output.fill(0);
+    }
+    result
+}
+
 pub(crate) fn hmac_sha256(key: &[u8], input: &[u8]) -> [u8; 32] {
     let mut hmac =
         Hmac::<Sha256>::new_from_slice(key).expect("HMAC-SHA256 should accept any size key");
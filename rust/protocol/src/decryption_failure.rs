@@ -0,0 +1,186 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Helpers for the retry-receipt workflow, in which a recipient that can't decrypt a message asks
+//! the sender to send it again.
+//!
+//! The recipient calls [`handle_decryption_failure`], which produces a [`DecryptionErrorMessage`]
+//! to send back (wrapped in a [`PlaintextContent`](crate::PlaintextContent)) and archives the
+//! session the message was meant for if it appears to be broken. The original sender passes the
+//! receipt to [`handle_retry_receipt`], which archives its own session if the failure was for the
+//! session it is currently using, so that the resent message starts a new one.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    CiphertextMessageType, DecryptionErrorMessage, ProtocolAddress, Result, SessionStore,
+    SignalProtocolError, Timestamp,
+};
+
+/// How long a [`RetryReceiptTracker`] remembers that a message has already been handled.
+///
+/// Senders stop retrying well within this window, so a repeated failure after it is treated as a
+/// new message.
+pub const RETRY_RECEIPT_DEDUPE_WINDOW: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// The minimum time between archiving sessions with the same address in
+/// [`handle_decryption_failure`].
+///
+/// Without this, a burst of undecryptable messages (such as a backlog sent with a broken session)
+/// would archive each new session as soon as it was established.
+pub const MIN_SESSION_ARCHIVE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Remembers which failures have already been handled, so that each message gets at most one
+/// retry receipt and sessions aren't archived over and over.
+///
+/// This state is only kept in memory; losing it (e.g. on restart) at worst results in one extra
+/// receipt per message.
+#[derive(Clone, Debug, Default)]
+pub struct RetryReceiptTracker {
+    receipts_sent: HashMap<(ProtocolAddress, Timestamp), SystemTime>,
+    sessions_archived: HashMap<ProtocolAddress, SystemTime>,
+}
+
+impl RetryReceiptTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets anything that no longer affects the outcome of a failure at `now`.
+    fn expire(&mut self, now: SystemTime) {
+        // Anything handled "after" `now` is kept, in case the clock went backwards.
+        let is_recent = |handled_at: &SystemTime, window: Duration| {
+            !now.duration_since(*handled_at)
+                .is_ok_and(|age| age >= window)
+        };
+        self.receipts_sent
+            .retain(|_, sent_at| is_recent(sent_at, RETRY_RECEIPT_DEDUPE_WINDOW));
+        self.sessions_archived
+            .retain(|_, archived_at| is_recent(archived_at, MIN_SESSION_ARCHIVE_INTERVAL));
+    }
+}
+
+/// What the app should do about a message that failed to decrypt.
+#[derive(Debug)]
+pub struct DecryptionFailureResponse {
+    /// The receipt to send back to the message's sender.
+    pub retry_receipt: DecryptionErrorMessage,
+    /// Whether the session with the sender was archived, in which case the next message to them
+    /// will need a new pre-key bundle.
+    pub session_archived: bool,
+}
+
+/// Handles a message from `sender` that failed to decrypt with `error`.
+///
+/// Returns `None` if nothing should be sent: either a receipt for this message was already
+/// produced, or the failure is one that resending won't fix (a duplicate message or an untrusted
+/// identity). Otherwise, returns a retry receipt. If the 1:1 message couldn't be decrypted with
+/// the session with `sender`, that session is also archived (at most once per
+/// [`MIN_SESSION_ARCHIVE_INTERVAL`]). Other failures, such as errors from the stores, leave the
+/// session alone.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_decryption_failure(
+    error: &SignalProtocolError,
+    original_bytes: &[u8],
+    original_type: CiphertextMessageType,
+    original_timestamp: Timestamp,
+    sender: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    tracker: &mut RetryReceiptTracker,
+    now: SystemTime,
+) -> Result<Option<DecryptionFailureResponse>> {
+    match error {
+        SignalProtocolError::DuplicatedMessage(..) | SignalProtocolError::UntrustedIdentity(_) => {
+            return Ok(None);
+        }
+        _ => {}
+    }
+
+    tracker.expire(now);
+    let receipt_key = (sender.clone(), original_timestamp);
+    if tracker.receipts_sent.contains_key(&receipt_key) {
+        log::info!(
+            "{sender} retry receipt already sent for message at {}",
+            original_timestamp.epoch_millis()
+        );
+        return Ok(None);
+    }
+
+    let retry_receipt = DecryptionErrorMessage::for_original(
+        original_bytes,
+        original_type,
+        original_timestamp,
+        sender.device_id().into(),
+    )?;
+
+    let mut session_archived = false;
+    let is_session_message = matches!(
+        original_type,
+        CiphertextMessageType::Whisper | CiphertextMessageType::PreKey
+    );
+    if is_session_message
+        && indicates_broken_session(error)
+        && !tracker.sessions_archived.contains_key(sender)
+    {
+        if let Some(mut session_record) = session_store.load_session(sender).await? {
+            if session_record.session_state().is_some() {
+                log::info!("{sender} archiving session after decryption failure: {error}");
+                session_record.archive_current_state_at(now)?;
+                session_store.store_session(sender, &session_record).await?;
+                tracker.sessions_archived.insert(sender.clone(), now);
+                session_archived = true;
+            }
+        }
+    }
+
+    tracker.receipts_sent.insert(receipt_key, now);
+    Ok(Some(DecryptionFailureResponse {
+        retry_receipt,
+        session_archived,
+    }))
+}
+
+/// Whether `error` means the message couldn't be decrypted with the session it was meant for, as
+/// opposed to a problem on this device (such as a store failure) that a new session won't fix.
+fn indicates_broken_session(error: &SignalProtocolError) -> bool {
+    matches!(
+        error,
+        SignalProtocolError::InvalidMessage(..)
+            | SignalProtocolError::InvalidSessionStructure(_)
+            | SignalProtocolError::InvalidPreKeyId
+            | SignalProtocolError::InvalidSignedPreKeyId
+            | SignalProtocolError::InvalidKyberPreKeyId
+    )
+}
+
+/// Handles a retry receipt from `from`, for a message this device sent.
+///
+/// If the receipt says the failure was with the session currently in use with `from`, the session
+/// is archived, so that the resent message (and any after it) will start a new one. Returns
+/// whether that happened; either way, the app should go on to resend the message identified by
+/// [`DecryptionErrorMessage::timestamp`].
+pub async fn handle_retry_receipt(
+    receipt: &DecryptionErrorMessage,
+    from: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    now: SystemTime,
+) -> Result<bool> {
+    let Some(ratchet_key) = receipt.ratchet_key() else {
+        // A sender key message, which doesn't involve the 1:1 session.
+        return Ok(false);
+    };
+    let Some(mut session_record) = session_store.load_session(from).await? else {
+        return Ok(false);
+    };
+    if !session_record.current_ratchet_key_matches(ratchet_key)? {
+        return Ok(false);
+    }
+
+    log::info!("{from} archiving session after retry receipt");
+    session_record.archive_current_state_at(now)?;
+    session_store.store_session(from, &session_record).await?;
+    Ok(true)
+}
@@ -11,7 +11,32 @@ use sha2::digest::Digest;
 use sha2::Sha512;
 use subtle::ConstantTimeEq;
 
-use crate::{proto, IdentityKey, Result, SignalProtocolError};
+use crate::{proto, Aci, IdentityKey, Result, SignalProtocolError};
+
+/// How many digits a [`DisplayableFingerprint`] shows for each party.
+///
+/// Both parties must use the same encoding for their safety numbers to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FingerprintDisplayEncoding {
+    /// 30 digits per party, 60 in total. This is the only encoding before
+    /// [`Fingerprint::ACI_VERSION`].
+    #[default]
+    Full,
+    /// 20 digits per party, 40 in total.
+    ///
+    /// Each party's digits still come from an iterated hash, so a collision remains expensive to
+    /// find, but the margin is smaller than with [`Full`](Self::Full).
+    Short,
+}
+
+impl FingerprintDisplayEncoding {
+    fn digit_groups(self) -> usize {
+        match self {
+            Self::Full => 6,
+            Self::Short => 4,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DisplayableFingerprint {
@@ -29,8 +54,8 @@ impl fmt::Display for DisplayableFingerprint {
     }
 }
 
-fn get_encoded_string(fprint: &[u8]) -> Result<String> {
-    if fprint.len() < 30 {
+fn get_encoded_string(fprint: &[u8], digit_groups: usize) -> Result<String> {
+    if fprint.len() < 5 * digit_groups {
         return Err(SignalProtocolError::InvalidArgument(
             "DisplayableFingerprint created with short encoding".to_string(),
         ));
@@ -42,22 +67,31 @@ fn get_encoded_string(fprint: &[u8]) -> Result<String> {
         x % 100_000
     }
 
-    let s = fprint.chunks_exact(5).take(6).map(read5_mod_100k).fold(
-        String::with_capacity(5 * 6),
-        |mut s, n| {
+    let s = fprint
+        .chunks_exact(5)
+        .take(digit_groups)
+        .map(read5_mod_100k)
+        .fold(String::with_capacity(5 * digit_groups), |mut s, n| {
             write!(s, "{n:05}").expect("can always write to a String");
             s
-        },
-    );
+        });
 
     Ok(s)
 }
 
 impl DisplayableFingerprint {
     pub fn new(local: &[u8], remote: &[u8]) -> Result<Self> {
+        Self::with_encoding(local, remote, FingerprintDisplayEncoding::Full)
+    }
+
+    pub fn with_encoding(
+        local: &[u8],
+        remote: &[u8],
+        encoding: FingerprintDisplayEncoding,
+    ) -> Result<Self> {
         Ok(Self {
-            local: get_encoded_string(local)?,
-            remote: get_encoded_string(remote)?,
+            local: get_encoded_string(local, encoding.digit_groups())?,
+            remote: get_encoded_string(remote, encoding.digit_groups())?,
         })
     }
 }
@@ -78,6 +112,10 @@ impl ScannableFingerprint {
         }
     }
 
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     pub fn deserialize(protobuf: &[u8]) -> Result<Self> {
         let fingerprint = proto::fingerprint::CombinedFingerprints::decode(protobuf)
             .map_err(|_| SignalProtocolError::FingerprintParsingError)?;
@@ -145,6 +183,77 @@ impl ScannableFingerprint {
 
         Ok(same1.into() && same2.into())
     }
+
+    /// Compares a scanned fingerprint against whichever of `candidates` has the same version.
+    ///
+    /// While clients are moving to a new version, an app can compute its fingerprint in every
+    /// version it supports and compare against whatever the other client shows. Fails with
+    /// [`FingerprintVersionMismatch`](SignalProtocolError::FingerprintVersionMismatch), reporting
+    /// the highest candidate version, if none of the candidates match the scanned version.
+    ///
+    /// Use [`compare_scanned`](Self::compare_scanned) to tell that case apart without matching on
+    /// the error.
+    pub fn compare_any_version(
+        candidates: &[&ScannableFingerprint],
+        combined: &[u8],
+    ) -> Result<bool> {
+        match Self::compare_scanned(candidates, combined)? {
+            FingerprintComparison::Match { .. } => Ok(true),
+            FingerprintComparison::KeyMismatch { .. } => Ok(false),
+            FingerprintComparison::VersionMismatch { theirs, ours } => Err(
+                SignalProtocolError::FingerprintVersionMismatch(theirs, ours),
+            ),
+        }
+    }
+
+    /// Compares a scanned fingerprint against whichever of `candidates` has the same version,
+    /// reporting which version the other client used.
+    ///
+    /// Unlike [`compare_any_version`](Self::compare_any_version), a version mismatch is a
+    /// successful result, so that an app can tell the user to update (or to ask their contact to
+    /// update) rather than warning that the keys don't match. Only a scanned payload that can't be
+    /// parsed is an error.
+    pub fn compare_scanned(
+        candidates: &[&ScannableFingerprint],
+        combined: &[u8],
+    ) -> Result<FingerprintComparison> {
+        let their_version = proto::fingerprint::CombinedFingerprints::decode(combined)
+            .map_err(|_| SignalProtocolError::FingerprintParsingError)?
+            .version
+            .unwrap_or(0);
+
+        let Some(candidate) = candidates.iter().find(|c| c.version == their_version) else {
+            return Ok(FingerprintComparison::VersionMismatch {
+                theirs: their_version,
+                ours: candidates.iter().map(|c| c.version).max().unwrap_or(0),
+            });
+        };
+
+        Ok(if candidate.compare(combined)? {
+            FingerprintComparison::Match {
+                version: their_version,
+            }
+        } else {
+            FingerprintComparison::KeyMismatch {
+                version: their_version,
+            }
+        })
+    }
+}
+
+/// The result of [`ScannableFingerprint::compare_scanned`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintComparison {
+    /// Both clients computed the same fingerprint in `version`.
+    Match { version: u32 },
+    /// Both clients used `version`, but the fingerprints differ: one of the identity keys (or
+    /// identifiers) is not what the other client expects.
+    KeyMismatch { version: u32 },
+    /// The other client used a version none of ours support, so the keys could not be compared.
+    ///
+    /// `ours` is the highest version we computed. If `theirs` is greater, the other client is
+    /// newer; otherwise it is older.
+    VersionMismatch { theirs: u32, ours: u32 },
 }
 
 #[derive(Debug, Clone)]
@@ -154,8 +263,12 @@ pub struct Fingerprint {
 }
 
 impl Fingerprint {
+    /// The scannable fingerprint version produced by [`for_acis`](Self::for_acis).
+    pub const ACI_VERSION: u32 = 3;
+
     fn get_fingerprint(
         iterations: u32,
+        fingerprint_version: [u8; 2],
         local_id: &[u8],
         local_key: &IdentityKey,
     ) -> Result<Vec<u8>> {
@@ -165,7 +278,6 @@ impl Fingerprint {
             )));
         }
 
-        let fingerprint_version = [0u8, 0u8]; // 0x0000
         let key_bytes = local_key.serialize();
 
         let mut sha512 = Sha512::new();
@@ -197,8 +309,10 @@ impl Fingerprint {
         remote_id: &[u8],
         remote_key: &IdentityKey,
     ) -> Result<Fingerprint> {
-        let local_fingerprint = Fingerprint::get_fingerprint(iterations, local_id, local_key)?;
-        let remote_fingerprint = Fingerprint::get_fingerprint(iterations, remote_id, remote_key)?;
+        let local_fingerprint =
+            Fingerprint::get_fingerprint(iterations, [0, 0], local_id, local_key)?;
+        let remote_fingerprint =
+            Fingerprint::get_fingerprint(iterations, [0, 0], remote_id, remote_key)?;
 
         Ok(Fingerprint {
             display: DisplayableFingerprint::new(&local_fingerprint, &remote_fingerprint)?,
@@ -206,6 +320,62 @@ impl Fingerprint {
         })
     }
 
+    /// Creates a fingerprint that binds each party's ACI as well as their identity key.
+    ///
+    /// Earlier versions hash whatever stable identifier the app passes in, so the same keys with
+    /// a different (or reused) identifier can produce a matching fingerprint. This version always
+    /// hashes the fixed-width binary form of the ACI, under its own hash prefix, so its output
+    /// never coincides with an earlier version's.
+    pub fn for_acis(
+        iterations: u32,
+        local_aci: Aci,
+        local_key: &IdentityKey,
+        remote_aci: Aci,
+        remote_key: &IdentityKey,
+        display_encoding: FingerprintDisplayEncoding,
+    ) -> Result<Fingerprint> {
+        let fingerprint_version = [
+            0,
+            u8::try_from(Self::ACI_VERSION).expect("version fits in a byte"),
+        ];
+        let local_fingerprint = Fingerprint::get_fingerprint(
+            iterations,
+            fingerprint_version,
+            &local_aci.service_id_fixed_width_binary(),
+            local_key,
+        )?;
+        let remote_fingerprint = Fingerprint::get_fingerprint(
+            iterations,
+            fingerprint_version,
+            &remote_aci.service_id_fixed_width_binary(),
+            remote_key,
+        )?;
+
+        Ok(Fingerprint {
+            display: DisplayableFingerprint::with_encoding(
+                &local_fingerprint,
+                &remote_fingerprint,
+                display_encoding,
+            )?,
+            scannable: ScannableFingerprint::new(
+                Self::ACI_VERSION,
+                &local_fingerprint,
+                &remote_fingerprint,
+            ),
+        })
+    }
+
+    /// Picks the version to show a user, given the versions each client supports.
+    ///
+    /// Returns the highest version both clients support, or `None` if they have none in common.
+    pub fn negotiate_version(local_supported: &[u32], remote_supported: &[u32]) -> Option<u32> {
+        local_supported
+            .iter()
+            .filter(|v| remote_supported.contains(v))
+            .max()
+            .copied()
+    }
+
     pub fn display_string(&self) -> Result<String> {
         Ok(format!("{}", self.display))
     }
@@ -552,4 +722,174 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn fingerprint_aci_version() -> Result<()> {
+        let a_key = IdentityKey::decode(ALICE_IDENTITY)?;
+        let b_key = IdentityKey::decode(BOB_IDENTITY)?;
+        let a_aci = Aci::from_uuid_bytes([0xAA; 16]);
+        let b_aci = Aci::from_uuid_bytes([0xBB; 16]);
+
+        let iterations = 5200;
+        let encoding = FingerprintDisplayEncoding::Short;
+
+        let a_fprint = Fingerprint::for_acis(iterations, a_aci, &a_key, b_aci, &b_key, encoding)?;
+        let b_fprint = Fingerprint::for_acis(iterations, b_aci, &b_key, a_aci, &a_key, encoding)?;
+
+        assert_eq!(a_fprint.scannable.version(), Fingerprint::ACI_VERSION);
+        assert_eq!(
+            format!("{}", a_fprint.display),
+            format!("{}", b_fprint.display)
+        );
+        assert_eq!(format!("{}", a_fprint.display).len(), 40);
+        assert!(a_fprint
+            .scannable
+            .compare(&b_fprint.scannable.serialize()?)?);
+
+        let a_full = Fingerprint::for_acis(
+            iterations,
+            a_aci,
+            &a_key,
+            b_aci,
+            &b_key,
+            FingerprintDisplayEncoding::Full,
+        )?;
+        assert_eq!(format!("{}", a_full.display).len(), 60);
+
+        // A different ACI with the same key doesn't match.
+        let m_aci = Aci::from_uuid_bytes([0xCC; 16]);
+        let m_fprint = Fingerprint::for_acis(iterations, m_aci, &b_key, a_aci, &a_key, encoding)?;
+        assert_ne!(
+            format!("{}", a_fprint.display),
+            format!("{}", m_fprint.display)
+        );
+        assert!(!a_fprint
+            .scannable
+            .compare(&m_fprint.scannable.serialize()?)?);
+
+        // An earlier version can't be compared directly.
+        let b_fprint_v2 = Fingerprint::new(
+            2,
+            iterations,
+            BOB_STABLE_ID.as_bytes(),
+            &b_key,
+            ALICE_STABLE_ID.as_bytes(),
+            &a_key,
+        )?;
+        assert!(matches!(
+            a_fprint
+                .scannable
+                .compare(&b_fprint_v2.scannable.serialize()?),
+            Err(SignalProtocolError::FingerprintVersionMismatch(2, 3))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprint_version_negotiation() -> Result<()> {
+        let a_key = IdentityKey::decode(ALICE_IDENTITY)?;
+        let b_key = IdentityKey::decode(BOB_IDENTITY)?;
+        let a_aci = Aci::from_uuid_bytes([0xAA; 16]);
+        let b_aci = Aci::from_uuid_bytes([0xBB; 16]);
+        let iterations = 5200;
+
+        assert_eq!(
+            Fingerprint::negotiate_version(&[2, Fingerprint::ACI_VERSION], &[1, 2]),
+            Some(2)
+        );
+        assert_eq!(
+            Fingerprint::negotiate_version(
+                &[2, Fingerprint::ACI_VERSION],
+                &[Fingerprint::ACI_VERSION, 2]
+            ),
+            Some(Fingerprint::ACI_VERSION)
+        );
+        assert_eq!(Fingerprint::negotiate_version(&[2], &[1]), None);
+
+        // Alice supports both versions; Bob only the old one.
+        let a_fprint_v2 = Fingerprint::new(
+            2,
+            iterations,
+            ALICE_STABLE_ID.as_bytes(),
+            &a_key,
+            BOB_STABLE_ID.as_bytes(),
+            &b_key,
+        )?;
+        let a_fprint_v3 = Fingerprint::for_acis(
+            iterations,
+            a_aci,
+            &a_key,
+            b_aci,
+            &b_key,
+            FingerprintDisplayEncoding::Full,
+        )?;
+        let b_fprint_v2 = Fingerprint::new(
+            2,
+            iterations,
+            BOB_STABLE_ID.as_bytes(),
+            &b_key,
+            ALICE_STABLE_ID.as_bytes(),
+            &a_key,
+        )?;
+        let b_fprint_v1 = Fingerprint::new(
+            1,
+            iterations,
+            BOB_STABLE_ID.as_bytes(),
+            &b_key,
+            ALICE_STABLE_ID.as_bytes(),
+            &a_key,
+        )?;
+
+        let candidates = [&a_fprint_v3.scannable, &a_fprint_v2.scannable];
+        assert!(ScannableFingerprint::compare_any_version(
+            &candidates,
+            &b_fprint_v2.scannable.serialize()?
+        )?);
+        assert!(matches!(
+            ScannableFingerprint::compare_any_version(
+                &candidates,
+                &b_fprint_v1.scannable.serialize()?
+            ),
+            Err(SignalProtocolError::FingerprintVersionMismatch(1, 3))
+        ));
+
+        // Bob shows a fingerprint for the wrong key for Alice.
+        let b_fprint_v2_wrong_key = Fingerprint::new(
+            2,
+            iterations,
+            BOB_STABLE_ID.as_bytes(),
+            &b_key,
+            ALICE_STABLE_ID.as_bytes(),
+            &b_key,
+        )?;
+
+        assert_eq!(
+            ScannableFingerprint::compare_scanned(
+                &candidates,
+                &b_fprint_v2.scannable.serialize()?
+            )?,
+            FingerprintComparison::Match { version: 2 }
+        );
+        assert_eq!(
+            ScannableFingerprint::compare_scanned(
+                &candidates,
+                &b_fprint_v2_wrong_key.scannable.serialize()?
+            )?,
+            FingerprintComparison::KeyMismatch { version: 2 }
+        );
+        assert_eq!(
+            ScannableFingerprint::compare_scanned(
+                &candidates,
+                &b_fprint_v1.scannable.serialize()?
+            )?,
+            FingerprintComparison::VersionMismatch { theirs: 1, ours: 3 }
+        );
+        assert!(matches!(
+            ScannableFingerprint::compare_scanned(&candidates, &[0xFF; 3]),
+            Err(SignalProtocolError::FingerprintParsingError)
+        ));
+
+        Ok(())
+    }
 }
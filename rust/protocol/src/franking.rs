@@ -0,0 +1,173 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Message franking, which lets a recipient prove to the server what a sender sent.
+//!
+//! [`message_encrypt_franked`] generates a one-time [`FrankingKey`], encrypts it along with the
+//! message contents, and then uses it to commit to the contents, the sender, and the resulting
+//! ciphertext, producing a [`FrankingTag`]. The tag is sent alongside the ciphertext, where the
+//! server can record it. [`message_decrypt_franked`] rejects a message whose tag doesn't match what
+//! was decrypted, so a recipient never accepts a message they couldn't report. When reporting
+//! abuse, the recipient reveals the contents and the key for just that message; the server can
+//! then check them against the tag it recorded with [`FrankingTag::verify`], without learning
+//! anything about other messages.
+//!
+//! [`message_encrypt_franked`]: crate::message_encrypt_franked
+//! [`message_decrypt_franked`]: crate::message_decrypt_franked
+
+use rand::{CryptoRng, Rng};
+use subtle::ConstantTimeEq;
+
+use crate::{crypto, ProtocolAddress, Result, SignalProtocolError};
+
+/// Domain separation for franking commitments.
+const FRANKING_TAG_LABEL: &[u8] = b"Signal_Franking_Tag_v1";
+
+/// The length of a [`FrankingKey`] in bytes.
+pub const FRANKING_KEY_LEN: usize = 32;
+/// The length of a [`FrankingTag`] in bytes.
+pub const FRANKING_TAG_LEN: usize = 32;
+
+/// A single-use key that opens a [`FrankingTag`].
+///
+/// A new key must be generated for every message.
+#[derive(Clone)]
+pub struct FrankingKey([u8; FRANKING_KEY_LEN]);
+
+impl FrankingKey {
+    /// Generates a fresh key.
+    pub fn generate<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        Self(rng.random())
+    }
+
+    /// The bytes of the key, as revealed in an abuse report.
+    pub fn as_bytes(&self) -> &[u8; FRANKING_KEY_LEN] {
+        &self.0
+    }
+
+    /// Commits to `contents`, as sent by `sender` in the serialized message `ciphertext`.
+    pub(crate) fn commit(
+        &self,
+        sender: &ProtocolAddress,
+        ciphertext: &[u8],
+        contents: &[u8],
+    ) -> FrankingTag {
+        FrankingTag(self.compute_tag(sender, ciphertext, contents))
+    }
+
+    fn compute_tag(
+        &self,
+        sender: &ProtocolAddress,
+        ciphertext: &[u8],
+        contents: &[u8],
+    ) -> [u8; FRANKING_TAG_LEN] {
+        // Every field but the last is length-prefixed, so the input can only be split one way.
+        let sender_name = sender.name().as_bytes();
+        let sender_name_len = u64::try_from(sender_name.len()).expect("fits");
+        let device_id = u32::from(sender.device_id());
+        let ciphertext_len = u64::try_from(ciphertext.len()).expect("fits");
+        let fields: [&[u8]; 7] = [
+            FRANKING_TAG_LABEL,
+            &sender_name_len.to_be_bytes(),
+            sender_name,
+            &device_id.to_be_bytes(),
+            &ciphertext_len.to_be_bytes(),
+            ciphertext,
+            contents,
+        ];
+        // The key is fixed-length, so no two keys are equivalent under HMAC; that, plus SHA-256's
+        // collision resistance, means a tag can't be opened to two different messages.
+        crypto::hmac_sha256(&self.0, &fields.concat())
+    }
+}
+
+impl TryFrom<&[u8]> for FrankingKey {
+    type Error = SignalProtocolError;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Ok(Self(value.try_into().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "franking key must be {FRANKING_KEY_LEN} bytes, not {}",
+                value.len()
+            ))
+        })?))
+    }
+}
+
+/// A commitment to a message's contents, sent outside the encryption so the server can record it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrankingTag([u8; FRANKING_TAG_LEN]);
+
+impl FrankingTag {
+    /// The bytes of the tag.
+    pub fn as_bytes(&self) -> &[u8; FRANKING_TAG_LEN] {
+        &self.0
+    }
+
+    /// Checks that `contents` and `key`, as revealed by a recipient, are what `sender` committed to
+    /// when sending the serialized message `ciphertext`.
+    pub fn verify(
+        &self,
+        key: &FrankingKey,
+        sender: &ProtocolAddress,
+        ciphertext: &[u8],
+        contents: &[u8],
+    ) -> bool {
+        key.compute_tag(sender, ciphertext, contents)
+            .ct_eq(&self.0)
+            .into()
+    }
+}
+
+impl TryFrom<&[u8]> for FrankingTag {
+    type Error = SignalProtocolError;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Ok(Self(value.try_into().map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "franking tag must be {FRANKING_TAG_LEN} bytes, not {}",
+                value.len()
+            ))
+        })?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+    use rand::TryRngCore as _;
+
+    use super::*;
+    use crate::DeviceId;
+
+    #[test]
+    fn commit_and_verify() {
+        let mut rng = OsRng.unwrap_err();
+        let sender = ProtocolAddress::new("sender".to_owned(), DeviceId::new(1).unwrap());
+        let other_sender = ProtocolAddress::new("sender".to_owned(), DeviceId::new(2).unwrap());
+        let key = FrankingKey::generate(&mut rng);
+        let tag = key.commit(&sender, b"ciphertext", b"abusive message");
+
+        let key = FrankingKey::try_from(&key.as_bytes()[..]).expect("valid");
+        let tag = FrankingTag::try_from(&tag.as_bytes()[..]).expect("valid");
+        assert!(tag.verify(&key, &sender, b"ciphertext", b"abusive message"));
+
+        assert!(!tag.verify(&key, &sender, b"ciphertext", b"innocuous message"));
+        assert!(!tag.verify(&key, &other_sender, b"ciphertext", b"abusive message"));
+        assert!(!tag.verify(&key, &sender, b"other ciphertext", b"abusive message"));
+        assert!(!tag.verify(
+            &FrankingKey::generate(&mut rng),
+            &sender,
+            b"ciphertext",
+            b"abusive message"
+        ));
+    }
+
+    #[test]
+    fn wrong_lengths() {
+        assert!(FrankingKey::try_from(&[0; FRANKING_KEY_LEN - 1][..]).is_err());
+        assert!(FrankingTag::try_from(&[0; FRANKING_TAG_LEN + 1][..]).is_err());
+    }
+}
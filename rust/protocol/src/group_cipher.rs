@@ -6,6 +6,7 @@
 use rand::{CryptoRng, Rng};
 use uuid::Uuid;
 
+use crate::crypto::zero_output_on_error;
 use crate::protocol::SENDERKEY_MESSAGE_CURRENT_VERSION;
 use crate::sender_keys::{SenderKeyState, SenderMessageKey};
 use crate::{
@@ -76,6 +77,7 @@ fn get_sender_key(
     state: &mut SenderKeyState,
     iteration: u32,
     distribution_id: Uuid,
+    max_message_keys: usize,
 ) -> Result<SenderMessageKey> {
     let sender_chain_key = state
         .sender_chain_key()
@@ -113,7 +115,7 @@ fn get_sender_key(
     let mut sender_chain_key = sender_chain_key;
 
     while sender_chain_key.iteration() < iteration {
-        state.add_sender_message_key(&sender_chain_key.sender_message_key());
+        state.add_sender_message_key(&sender_chain_key.sender_message_key(), max_message_keys);
         sender_chain_key = sender_chain_key.next()?;
     }
 
@@ -126,7 +128,43 @@ pub async fn group_decrypt(
     sender_key_store: &mut dyn SenderKeyStore,
     sender: &ProtocolAddress,
 ) -> Result<Vec<u8>> {
+    // The plaintext is always shorter than the serialized message.
+    let mut plaintext = vec![0; skm_bytes.len()];
+    let plaintext_len =
+        group_decrypt_into(skm_bytes, sender_key_store, sender, &mut plaintext).await?;
+    plaintext.truncate(plaintext_len);
+    Ok(plaintext)
+}
+
+/// Like [`group_decrypt`], but writes the plaintext into the start of `output` and returns its
+/// length.
+///
+/// `output` must be at least as long as the message's ciphertext; if it isn't, this fails before
+/// touching the store. If this fails for any reason, `output` is zeroed.
+pub async fn group_decrypt_into(
+    skm_bytes: &[u8],
+    sender_key_store: &mut dyn SenderKeyStore,
+    sender: &ProtocolAddress,
+    output: &mut [u8],
+) -> Result<usize> {
+    let result = group_decrypt_into_impl(skm_bytes, sender_key_store, sender, output).await;
+    zero_output_on_error(output, result)
+}
+
+async fn group_decrypt_into_impl(
+    skm_bytes: &[u8],
+    sender_key_store: &mut dyn SenderKeyStore,
+    sender: &ProtocolAddress,
+    output: &mut [u8],
+) -> Result<usize> {
     let skm = SenderKeyMessage::try_from(skm_bytes)?;
+    if output.len() < skm.ciphertext().len() {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "output buffer must be at least {} bytes, but is only {}",
+            skm.ciphertext().len(),
+            output.len()
+        )));
+    }
 
     let distribution_id = skm.distribution_id();
     let chain_id = skm.chain_id();
@@ -163,20 +201,29 @@ pub async fn group_decrypt(
         return Err(SignalProtocolError::SignatureValidationFailed);
     }
 
-    let sender_key = get_sender_key(sender_key_state, skm.iteration(), distribution_id)?;
+    let sender_key = get_sender_key(
+        sender_key_state,
+        skm.iteration(),
+        distribution_id,
+        sender_key_store.sender_key_limits().max_message_keys(),
+    )?;
 
-    let plaintext = match signal_crypto::aes_256_cbc_decrypt(
+    let plaintext_len = match signal_crypto::aes_256_cbc_decrypt_into(
         skm.ciphertext(),
         sender_key.cipher_key(),
         sender_key.iv(),
+        output,
     ) {
-        Ok(plaintext) => plaintext,
+        Ok(plaintext_len) => plaintext_len,
         Err(signal_crypto::DecryptionError::BadKeyOrIv) => {
             log::error!(
                 "incoming sender key state corrupt for {sender}, distribution ID {distribution_id}, chain ID {chain_id}",
             );
             return Err(SignalProtocolError::InvalidSenderKeySession { distribution_id });
         }
+        Err(signal_crypto::DecryptionError::OutputTooSmall) => {
+            unreachable!("checked above")
+        }
         Err(signal_crypto::DecryptionError::BadCiphertext(msg)) => {
             log::error!("sender key decryption failed: {msg}");
             return Err(SignalProtocolError::InvalidMessage(
@@ -190,7 +237,7 @@ pub async fn group_decrypt(
         .store_sender_key(sender, distribution_id, &record)
         .await?;
 
-    Ok(plaintext)
+    Ok(plaintext_len)
 }
 
 pub async fn process_sender_key_distribution_message(
@@ -218,6 +265,7 @@ pub async fn process_sender_key_distribution_message(
         skdm.chain_key()?,
         *skdm.signing_key()?,
         None,
+        sender_key_store.sender_key_limits().max_sender_key_states(),
     );
     sender_key_store
         .store_sender_key(sender, distribution_id, &sender_key_record)
@@ -238,23 +286,12 @@ pub async fn create_sender_key_distribution_message<R: Rng + CryptoRng>(
     let sender_key_record = match sender_key_record {
         Some(record) => record,
         None => {
-            // libsignal-protocol-java uses 31-bit integers for sender key chain IDs
-            let chain_id = (csprng.random::<u32>()) >> 1;
-            log::info!(
-                "Creating SenderKey for distribution {distribution_id} with chain ID {chain_id}"
-            );
-
-            let iteration = 0;
-            let sender_key: [u8; 32] = csprng.random();
-            let signing_key = KeyPair::generate(csprng);
             let mut record = SenderKeyRecord::new_empty();
-            record.add_sender_key_state(
-                SENDERKEY_MESSAGE_CURRENT_VERSION,
-                chain_id,
-                iteration,
-                &sender_key,
-                signing_key.public_key,
-                Some(signing_key.private_key),
+            add_new_sender_key_state(
+                &mut record,
+                distribution_id,
+                sender_key_store.sender_key_limits().max_sender_key_states(),
+                csprng,
             );
             sender_key_store
                 .store_sender_key(sender, distribution_id, &record)
@@ -263,6 +300,68 @@ pub async fn create_sender_key_distribution_message<R: Rng + CryptoRng>(
         }
     };
 
+    distribution_message_for_current_state(&sender_key_record, distribution_id)
+}
+
+/// Replaces the sender key `sender` uses for `distribution_id` with a freshly generated one, and
+/// returns the distribution message for the new key.
+///
+/// Call this when a member leaves the group, then send the result to every remaining member;
+/// until then, subsequent [`group_encrypt`] calls use a key the remaining members can't decrypt
+/// with. The previous key is kept as an older state, as when processing a distribution message,
+/// so this device can still decrypt any of its own messages that were encrypted with it.
+pub async fn rotate_sender_key<R: Rng + CryptoRng>(
+    sender: &ProtocolAddress,
+    distribution_id: Uuid,
+    sender_key_store: &mut dyn SenderKeyStore,
+    csprng: &mut R,
+) -> Result<SenderKeyDistributionMessage> {
+    let mut sender_key_record = sender_key_store
+        .load_sender_key(sender, distribution_id)
+        .await?
+        .unwrap_or_else(SenderKeyRecord::new_empty);
+
+    add_new_sender_key_state(
+        &mut sender_key_record,
+        distribution_id,
+        sender_key_store.sender_key_limits().max_sender_key_states(),
+        csprng,
+    );
+    sender_key_store
+        .store_sender_key(sender, distribution_id, &sender_key_record)
+        .await?;
+
+    distribution_message_for_current_state(&sender_key_record, distribution_id)
+}
+
+fn add_new_sender_key_state<R: Rng + CryptoRng>(
+    record: &mut SenderKeyRecord,
+    distribution_id: Uuid,
+    max_states: usize,
+    csprng: &mut R,
+) {
+    // libsignal-protocol-java uses 31-bit integers for sender key chain IDs
+    let chain_id = (csprng.random::<u32>()) >> 1;
+    log::info!("Creating SenderKey for distribution {distribution_id} with chain ID {chain_id}");
+
+    let iteration = 0;
+    let sender_key: [u8; 32] = csprng.random();
+    let signing_key = KeyPair::generate(csprng);
+    record.add_sender_key_state(
+        SENDERKEY_MESSAGE_CURRENT_VERSION,
+        chain_id,
+        iteration,
+        &sender_key,
+        signing_key.public_key,
+        Some(signing_key.private_key),
+        max_states,
+    );
+}
+
+fn distribution_message_for_current_state(
+    sender_key_record: &SenderKeyRecord,
+    distribution_id: Uuid,
+) -> Result<SenderKeyDistributionMessage> {
     let state = sender_key_record
         .sender_key_state()
         .map_err(|_| SignalProtocolError::InvalidSenderKeySession { distribution_id })?;
@@ -10,11 +10,13 @@
 use prost::Message;
 use rand::{CryptoRng, Rng};
 
-use crate::{proto, KeyPair, PrivateKey, PublicKey, Result, SignalProtocolError};
+use crate::{proto, KeyPair, PrivateKey, PrivateKeySigner, PublicKey, Result, SignalProtocolError};
 
 // Used for domain separation between alternate-identity signatures and other key-to-key signatures.
 const ALTERNATE_IDENTITY_SIGNATURE_PREFIX_1: &[u8] = &[0xFF; 32];
 const ALTERNATE_IDENTITY_SIGNATURE_PREFIX_2: &[u8] = b"Signal_PNI_Signature";
+// Likewise for identity transition statements, which must never verify as alternate identities.
+const IDENTITY_TRANSITION_SIGNATURE_PREFIX: &[u8] = b"Signal_Identity_Transition";
 
 /// A public key that represents the identity of a user.
 ///
@@ -132,19 +134,147 @@ impl IdentityKeyPair {
     }
 
     /// Generate a signature claiming that `other` represents the same user as `self`.
+    ///
+    /// See [`PrivateKeySigner::sign_alternate_identity`] for identities that aren't held in memory.
     pub fn sign_alternate_identity<R: Rng + CryptoRng>(
         &self,
         other: &IdentityKey,
         rng: &mut R,
     ) -> Result<Box<[u8]>> {
-        Ok(self.private_key.calculate_signature_for_multipart_message(
+        PrivateKeySigner::sign_alternate_identity(self, other, rng)
+    }
+
+    /// Generate a statement that this user's identity is being replaced by `new_identity`.
+    ///
+    /// The statement is signed with `self`, so peers that already trust `self` can use
+    /// [`IdentityKeyTransition::verify_from`] to accept the new key without re-verifying it.
+    ///
+    /// See [`PrivateKeySigner::sign_identity_transition`] for identities that aren't held in
+    /// memory.
+    pub fn sign_identity_transition<R: Rng + CryptoRng>(
+        &self,
+        new_identity: &IdentityKey,
+        rng: &mut R,
+    ) -> Result<IdentityKeyTransition> {
+        PrivateKeySigner::sign_identity_transition(self, new_identity, rng)
+    }
+}
+
+pub(crate) fn sign_alternate_identity(
+    signer: &(impl PrivateKeySigner + ?Sized),
+    other: &IdentityKey,
+    rng: &mut dyn CryptoRng,
+) -> Result<Box<[u8]>> {
+    signer.calculate_signature_for_multipart_message(
+        &[
+            ALTERNATE_IDENTITY_SIGNATURE_PREFIX_1,
+            ALTERNATE_IDENTITY_SIGNATURE_PREFIX_2,
+            &other.serialize(),
+        ],
+        rng,
+    )
+}
+
+/// A statement, signed by a user's old identity key, that it has been replaced by a new one.
+///
+/// Created by [`IdentityKeyPair::sign_identity_transition`].
+#[derive(Debug, Clone)]
+pub struct IdentityKeyTransition {
+    old_identity: IdentityKey,
+    new_identity: IdentityKey,
+    signature: Box<[u8]>,
+}
+
+impl IdentityKeyTransition {
+    pub(crate) fn sign(
+        signer: &(impl PrivateKeySigner + ?Sized),
+        new_identity: &IdentityKey,
+        rng: &mut dyn CryptoRng,
+    ) -> Result<Self> {
+        let old_identity = signer.identity_key();
+        let signature = signer.calculate_signature_for_multipart_message(
             &[
                 ALTERNATE_IDENTITY_SIGNATURE_PREFIX_1,
-                ALTERNATE_IDENTITY_SIGNATURE_PREFIX_2,
-                &other.serialize(),
+                IDENTITY_TRANSITION_SIGNATURE_PREFIX,
+                &old_identity.serialize(),
+                &new_identity.serialize(),
             ],
             rng,
-        )?)
+        )?;
+        Ok(Self {
+            old_identity,
+            new_identity: *new_identity,
+            signature,
+        })
+    }
+
+    /// The identity being replaced, which signed this statement.
+    #[inline]
+    pub fn old_identity(&self) -> &IdentityKey {
+        &self.old_identity
+    }
+
+    /// The identity replacing [`Self::old_identity`].
+    #[inline]
+    pub fn new_identity(&self) -> &IdentityKey {
+        &self.new_identity
+    }
+
+    /// Check that the statement was signed by [`Self::old_identity`].
+    ///
+    /// This says nothing about whether the old identity should be trusted; see
+    /// [`Self::verify_from`].
+    pub fn verify(&self) -> bool {
+        self.old_identity
+            .public_key
+            .verify_signature_for_multipart_message(
+                &[
+                    ALTERNATE_IDENTITY_SIGNATURE_PREFIX_1,
+                    IDENTITY_TRANSITION_SIGNATURE_PREFIX,
+                    &self.old_identity.serialize(),
+                    &self.new_identity.serialize(),
+                ],
+                &self.signature,
+            )
+    }
+
+    /// Check that the statement replaces `trusted`, and was signed by it.
+    pub fn verify_from(&self, trusted: &IdentityKey) -> bool {
+        &self.old_identity == trusted && self.verify()
+    }
+
+    /// Return a byte slice which can later be deserialized with [`Self::try_from`].
+    pub fn serialize(&self) -> Box<[u8]> {
+        proto::wire::IdentityKeyTransition {
+            old_identity_key: Some(self.old_identity.serialize().into_vec()),
+            new_identity_key: Some(self.new_identity.serialize().into_vec()),
+            signature: Some(self.signature.to_vec()),
+        }
+        .encode_to_vec()
+        .into_boxed_slice()
+    }
+}
+
+impl TryFrom<&[u8]> for IdentityKeyTransition {
+    type Error = SignalProtocolError;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let proto::wire::IdentityKeyTransition {
+            old_identity_key,
+            new_identity_key,
+            signature,
+        } = proto::wire::IdentityKeyTransition::decode(value)
+            .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)?;
+        let (Some(old_identity_key), Some(new_identity_key), Some(signature)) =
+            (old_identity_key, new_identity_key, signature)
+        else {
+            return Err(SignalProtocolError::InvalidProtobufEncoding);
+        };
+        Ok(Self {
+            old_identity: IdentityKey::decode(&old_identity_key)?,
+            new_identity: IdentityKey::decode(&new_identity_key)?,
+            signature: signature.into_boxed_slice(),
+        })
     }
 }
 
@@ -253,4 +383,39 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_identity_transition() -> Result<()> {
+        let mut rng = OsRng.unwrap_err();
+        let old = IdentityKeyPair::generate(&mut rng);
+        let new = IdentityKeyPair::generate(&mut rng);
+
+        let transition = old.sign_identity_transition(new.identity_key(), &mut rng)?;
+        assert_eq!(transition.old_identity(), old.identity_key());
+        assert_eq!(transition.new_identity(), new.identity_key());
+        assert!(transition.verify());
+        assert!(transition.verify_from(old.identity_key()));
+        assert!(!transition.verify_from(new.identity_key()));
+
+        let round_tripped = IdentityKeyTransition::try_from(&transition.serialize()[..])?;
+        assert!(round_tripped.verify_from(old.identity_key()));
+        assert_eq!(round_tripped.new_identity(), new.identity_key());
+
+        // Claiming someone else signed it doesn't work.
+        let unrelated = IdentityKeyPair::generate(&mut rng);
+        let forged = IdentityKeyTransition {
+            old_identity: *unrelated.identity_key(),
+            ..transition.clone()
+        };
+        assert!(!forged.verify());
+
+        // Nor can an alternate identity signature be passed off as a transition.
+        let forged = IdentityKeyTransition {
+            signature: old.sign_alternate_identity(new.identity_key(), &mut rng)?,
+            ..transition
+        };
+        assert!(!forged.verify());
+
+        Ok(())
+    }
 }
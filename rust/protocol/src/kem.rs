@@ -12,7 +12,9 @@
 //! `SecretKey::decapsulate(ct: Ciphertext)` to construct the same `SharedSecret`.
 //!
 //! # Supported KEMs
-//! The NIST standardized Kyber1024 and Kyber768 KEMs are currently supported.
+//! Kyber1024, as submitted to the NIST competition, and ML-KEM-1024, as standardized in FIPS 203,
+//! are currently supported. The two are not interoperable, so a key's type travels with it.
+//! Kyber768 is available with the `kyber768` feature.
 //!
 //! # Serialization
 //! `PublicKey`s and `SecretKey`s have serialization functions that encode the
@@ -55,7 +57,6 @@
 mod kyber1024;
 #[cfg(any(feature = "kyber768", test))]
 mod kyber768;
-#[cfg(feature = "mlkem1024")]
 mod mlkem1024;
 
 use std::marker::PhantomData;
@@ -203,8 +204,7 @@ pub enum KeyType {
     Kyber768,
     /// Kyber1024 key
     Kyber1024,
-    /// ML-KEM 1024 key
-    #[cfg(feature = "mlkem1024")]
+    /// ML-KEM-1024 key, per the final FIPS 203 standard
     MLKEM1024,
 }
 
@@ -214,7 +214,6 @@ impl KeyType {
             #[cfg(any(feature = "kyber768", test))]
             KeyType::Kyber768 => 0x07,
             KeyType::Kyber1024 => 0x08,
-            #[cfg(feature = "mlkem1024")]
             KeyType::MLKEM1024 => 0x0A,
         }
     }
@@ -227,7 +226,6 @@ impl KeyType {
             #[cfg(any(feature = "kyber768", test))]
             KeyType::Kyber768 => &kyber768::Parameters,
             KeyType::Kyber1024 => &kyber1024::Parameters,
-            #[cfg(feature = "mlkem1024")]
             KeyType::MLKEM1024 => &mlkem1024::Parameters,
         }
     }
@@ -241,7 +239,6 @@ impl TryFrom<u8> for KeyType {
             #[cfg(any(feature = "kyber768", test))]
             0x07 => Ok(KeyType::Kyber768),
             0x08 => Ok(KeyType::Kyber1024),
-            #[cfg(feature = "mlkem1024")]
             0x0A => Ok(KeyType::MLKEM1024),
             t => Err(SignalProtocolError::BadKEMKeyType(t)),
         }
@@ -567,7 +564,6 @@ mod tests {
         assert_eq!(ss_for_sender, ss_for_recipient);
     }
 
-    #[cfg(feature = "mlkem1024")]
     #[test]
     fn test_mlkem1024_kem() {
         // test data for kyber1024
@@ -635,7 +631,6 @@ mod tests {
         assert_eq!(ss_for_recipient, ss_for_sender);
     }
 
-    #[cfg(feature = "mlkem1024")]
     #[test]
     fn test_mlkem1024_keypair() {
         let mut rng = rand::rngs::OsRng.unwrap_err();
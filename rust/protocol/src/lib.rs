@@ -22,66 +22,110 @@
 // https://doc.rust-lang.org/rustdoc/what-to-include.html for background.
 // #![warn(missing_docs)]
 
+mod address_migration;
 mod consts;
 mod crypto;
+mod decryption_failure;
 pub mod error;
 mod fingerprint;
+mod franking;
 mod group_cipher;
 mod identity_key;
 pub mod incremental_mac;
 pub mod kem;
+mod limits;
+mod padding;
+mod prekey_inventory;
 mod proto;
 mod protocol;
+mod provisioning;
 mod ratchet;
 mod sealed_sender;
+mod sender_certificate_watcher;
 mod sender_keys;
 mod session;
+mod session_archive;
 mod session_cipher;
+mod signer;
 mod state;
 mod storage;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
 mod timestamp;
 
+pub use address_migration::{migrate_address, AddressMigration};
+pub use decryption_failure::{
+    handle_decryption_failure, handle_retry_receipt, DecryptionFailureResponse,
+    RetryReceiptTracker, MIN_SESSION_ARCHIVE_INTERVAL, RETRY_RECEIPT_DEDUPE_WINDOW,
+};
 use error::Result;
 pub use error::SignalProtocolError;
-pub use fingerprint::{DisplayableFingerprint, Fingerprint, ScannableFingerprint};
+pub use fingerprint::{
+    DisplayableFingerprint, Fingerprint, FingerprintComparison, FingerprintDisplayEncoding,
+    ScannableFingerprint,
+};
+pub use franking::{FrankingKey, FrankingTag, FRANKING_KEY_LEN, FRANKING_TAG_LEN};
 pub use group_cipher::{
-    create_sender_key_distribution_message, group_decrypt, group_encrypt,
-    process_sender_key_distribution_message,
+    create_sender_key_distribution_message, group_decrypt, group_decrypt_into, group_encrypt,
+    process_sender_key_distribution_message, rotate_sender_key,
 };
-pub use identity_key::{IdentityKey, IdentityKeyPair};
+pub use identity_key::{IdentityKey, IdentityKeyPair, IdentityKeyTransition};
 pub use libsignal_core::curve::{KeyPair, PrivateKey, PublicKey};
 pub use libsignal_core::{
     Aci, DeviceId, Pni, ProtocolAddress, ServiceId, ServiceIdFixedWidthBinaryBytes, ServiceIdKind,
 };
+pub use limits::ProtocolLimits;
+pub use padding::{
+    pad_plaintext, strip_padding, BlockPadding, NoPadding, PaddingScheme, PadmePadding,
+};
+pub use prekey_inventory::{PreKeyBatch, PreKeyInventory, PreKeyInventoryReport, PreKeyUpload};
 pub use protocol::{
     extract_decryption_error_message_from_serialized_content, CiphertextMessage,
     CiphertextMessageType, DecryptionErrorMessage, KyberPayload, PlaintextContent,
     PreKeySignalMessage, SenderKeyDistributionMessage, SenderKeyMessage, SignalMessage,
 };
+pub use provisioning::{encrypt_provision_message, ProvisionMessage, ProvisioningReceiver};
 pub use ratchet::{
     initialize_alice_session_record, initialize_bob_session_record, AliceSignalProtocolParameters,
     BobSignalProtocolParameters, UsePQRatchet,
 };
 pub use sealed_sender::{
-    sealed_sender_decrypt, sealed_sender_decrypt_to_usmc, sealed_sender_encrypt,
-    sealed_sender_encrypt_from_usmc, sealed_sender_multi_recipient_encrypt, ContentHint,
-    SealedSenderDecryptionResult, SealedSenderV2SentMessage, SealedSenderV2SentMessageRecipient,
-    SenderCertificate, ServerCertificate, UnidentifiedSenderMessageContent,
+    encrypt_for_recipients, sealed_sender_decrypt, sealed_sender_decrypt_into,
+    sealed_sender_decrypt_to_usmc, sealed_sender_encrypt, sealed_sender_encrypt_from_usmc,
+    sealed_sender_encrypt_padded, sealed_sender_multi_recipient_encrypt, ContentHint,
+    MultiRecipientEncryptResult, RecipientEncryptionFailure, SealedSenderDecryptIntoResult,
+    SealedSenderDecryptionResult, SealedSenderV2BuildError, SealedSenderV2ReceivedMessage,
+    SealedSenderV2SentMessage, SealedSenderV2SentMessageBuilder,
+    SealedSenderV2SentMessageRecipient, SenderCertificate, ServerCertificate,
+    UnidentifiedSenderMessageContent,
 };
+pub use sender_certificate_watcher::{SenderCertificateRefresher, SenderCertificateWatcher};
 pub use sender_keys::SenderKeyRecord;
-pub use session::{process_prekey, process_prekey_bundle};
+pub use session::{
+    process_prekey, process_prekey_bundle, process_prekey_bundle_in_transaction,
+    process_verified_prekey_bundle,
+};
+pub use session_archive::{
+    export_session_archive, import_session_archive, ArchiveCollision, SessionArchiveImport,
+    SESSION_ARCHIVE_VERSION,
+};
 pub use session_cipher::{
-    message_decrypt, message_decrypt_prekey, message_decrypt_signal, message_encrypt,
+    message_decrypt, message_decrypt_franked, message_decrypt_in_transaction, message_decrypt_into,
+    message_decrypt_padded, message_decrypt_prekey, message_decrypt_prekey_into,
+    message_decrypt_signal, message_decrypt_signal_into, message_encrypt, message_encrypt_franked,
+    message_encrypt_padded,
 };
+pub use signer::PrivateKeySigner;
 pub use state::{
     GenericSignedPreKey, KyberPreKeyId, KyberPreKeyRecord, PreKeyBundle, PreKeyBundleContent,
-    PreKeyId, PreKeyRecord, SessionRecord, SessionUsabilityRequirements, SignedPreKeyId,
-    SignedPreKeyRecord,
+    PreKeyId, PreKeyRecord, SessionDiagnostics, SessionRecord, SessionUsabilityRequirements,
+    SignedPreKeyId, SignedPreKeyRecord, VerifiedPreKeyBundle,
 };
 pub use storage::{
-    Direction, IdentityChange, IdentityKeyStore, InMemIdentityKeyStore, InMemKyberPreKeyStore,
-    InMemPreKeyStore, InMemSenderKeyStore, InMemSessionStore, InMemSignalProtocolStore,
-    InMemSignedPreKeyStore, KyberPreKeyStore, PreKeyStore, ProtocolStore, SenderKeyStore,
-    SessionStore, SignedPreKeyStore,
+    Direction, EncryptedStore, IdentityChange, IdentityKeyStore, InMemIdentityKeyStore,
+    InMemKyberPreKeyStore, InMemPreKeyStore, InMemSenderKeyStore, InMemSessionStore,
+    InMemSignalProtocolStore, InMemSignedPreKeyStore, KyberPreKeyStore, PreKeyBlobStore,
+    PreKeyInventoryStore, PreKeyStore, ProtocolStore, SenderKeyStore, SessionBlobStore,
+    SessionStore, SignedPreKeyStore, TransactionalStores, ENCRYPTED_RECORD_VERSION,
 };
 pub use timestamp::Timestamp;
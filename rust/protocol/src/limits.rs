@@ -0,0 +1,145 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use crate::{consts, Result, SignalProtocolError};
+
+/// Limits on how much old state is kept around to decrypt late or out-of-order messages.
+///
+/// Higher limits mean fewer messages are lost when delivery is unreliable or a peer resets their
+/// session, at the cost of larger records. Each limit has a hard upper bound, so that a record
+/// can't grow without limit even if a store is misconfigured.
+///
+/// Stores provide limits through [`SessionStore::session_limits`] and
+/// [`SenderKeyStore::sender_key_limits`]. Operations that are given a store apply its limits when
+/// they update a record. Operations without a store, such as
+/// [`SessionRecord::archive_current_state`], use the default limits.
+///
+/// [`SessionStore::session_limits`]: crate::SessionStore::session_limits
+/// [`SenderKeyStore::sender_key_limits`]: crate::SenderKeyStore::sender_key_limits
+/// [`SessionRecord::archive_current_state`]: crate::SessionRecord::archive_current_state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    max_message_keys: usize,
+    max_archived_states: usize,
+    max_sender_key_states: usize,
+}
+
+impl ProtocolLimits {
+    /// The most message keys that can be kept for skipped messages, per receiving chain.
+    pub const MESSAGE_KEYS_UPPER_BOUND: usize = consts::MAX_MESSAGE_KEYS_UPPER_BOUND;
+    /// The most previous sessions that can be kept in a session record.
+    pub const ARCHIVED_STATES_UPPER_BOUND: usize = consts::ARCHIVED_STATES_UPPER_BOUND;
+    /// The most sender key states that can be kept in a sender key record.
+    pub const SENDER_KEY_STATES_UPPER_BOUND: usize = consts::MAX_SENDER_KEY_STATES_UPPER_BOUND;
+
+    /// The maximum number of message keys kept for skipped messages, per receiving chain (for
+    /// sessions) or per sender key state (for groups).
+    pub fn max_message_keys(&self) -> usize {
+        self.max_message_keys
+    }
+
+    /// The maximum number of previous sessions kept in a session record.
+    pub fn max_archived_states(&self) -> usize {
+        self.max_archived_states
+    }
+
+    /// The maximum number of sender key states kept in a sender key record.
+    pub fn max_sender_key_states(&self) -> usize {
+        self.max_sender_key_states
+    }
+
+    /// Sets [`max_message_keys`](Self::max_message_keys), which may be zero to drop skipped
+    /// messages entirely.
+    pub fn with_max_message_keys(self, max_message_keys: usize) -> Result<Self> {
+        check_limit(
+            "max_message_keys",
+            max_message_keys,
+            0,
+            Self::MESSAGE_KEYS_UPPER_BOUND,
+        )?;
+        Ok(Self {
+            max_message_keys,
+            ..self
+        })
+    }
+
+    /// Sets [`max_archived_states`](Self::max_archived_states), which may be zero to keep only
+    /// the current session.
+    pub fn with_max_archived_states(self, max_archived_states: usize) -> Result<Self> {
+        check_limit(
+            "max_archived_states",
+            max_archived_states,
+            0,
+            Self::ARCHIVED_STATES_UPPER_BOUND,
+        )?;
+        Ok(Self {
+            max_archived_states,
+            ..self
+        })
+    }
+
+    /// Sets [`max_sender_key_states`](Self::max_sender_key_states), which must be at least one.
+    pub fn with_max_sender_key_states(self, max_sender_key_states: usize) -> Result<Self> {
+        check_limit(
+            "max_sender_key_states",
+            max_sender_key_states,
+            1,
+            Self::SENDER_KEY_STATES_UPPER_BOUND,
+        )?;
+        Ok(Self {
+            max_sender_key_states,
+            ..self
+        })
+    }
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        Self {
+            max_message_keys: consts::MAX_MESSAGE_KEYS,
+            max_archived_states: consts::ARCHIVED_STATES_MAX_LENGTH,
+            max_sender_key_states: consts::MAX_SENDER_KEY_STATES,
+        }
+    }
+}
+
+fn check_limit(name: &str, value: usize, min: usize, max: usize) -> Result<()> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(SignalProtocolError::InvalidArgument(format!(
+            "{name} must be between {min} and {max}, not {value}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let limits = ProtocolLimits::default()
+            .with_max_message_keys(0)
+            .and_then(|limits| limits.with_max_archived_states(100))
+            .and_then(|limits| limits.with_max_sender_key_states(1))
+            .expect("valid");
+        assert_eq!(limits.max_message_keys(), 0);
+        assert_eq!(limits.max_archived_states(), 100);
+        assert_eq!(limits.max_sender_key_states(), 1);
+
+        let defaults = ProtocolLimits::default();
+        assert!(defaults
+            .with_max_message_keys(ProtocolLimits::MESSAGE_KEYS_UPPER_BOUND + 1)
+            .is_err());
+        assert!(defaults
+            .with_max_archived_states(ProtocolLimits::ARCHIVED_STATES_UPPER_BOUND + 1)
+            .is_err());
+        assert!(defaults.with_max_sender_key_states(0).is_err());
+        assert!(defaults
+            .with_max_sender_key_states(ProtocolLimits::SENDER_KEY_STATES_UPPER_BOUND + 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Padding applied to plaintexts before encryption, to hide their exact length.
+//!
+//! Every scheme produces the same format: the message, a boundary byte of `0x80`, and then zero or
+//! more zero bytes. Schemes only differ in how much padding they add, so [`strip_padding`] can
+//! remove any of them without knowing which one the sender used.
+
+use crate::{Result, SignalProtocolError};
+
+/// Marks the end of a message and the start of any padding.
+pub(crate) const PADDING_BOUNDARY_BYTE: u8 = 0x80;
+
+/// Decides how long a padded plaintext should be.
+pub trait PaddingScheme {
+    /// The length to pad a message of `message_len` bytes to.
+    ///
+    /// This must leave room for the boundary byte; a smaller result is treated as
+    /// `message_len + 1`.
+    fn padded_len(&self, message_len: usize) -> usize;
+}
+
+/// Adds only the boundary byte, for uses where bandwidth matters more than hiding message length.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoPadding;
+
+impl PaddingScheme for NoPadding {
+    fn padded_len(&self, message_len: usize) -> usize {
+        message_len + 1
+    }
+}
+
+/// Pads messages up to a multiple of a fixed block size.
+///
+/// The default block size of 160 bytes matches what Signal clients have historically used.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockPadding {
+    block_size: usize,
+}
+
+impl BlockPadding {
+    pub const DEFAULT_BLOCK_SIZE: usize = 160;
+
+    pub fn new(block_size: usize) -> Result<Self> {
+        if block_size == 0 {
+            return Err(SignalProtocolError::InvalidArgument(
+                "block size must be nonzero".to_string(),
+            ));
+        }
+        Ok(Self { block_size })
+    }
+}
+
+impl Default for BlockPadding {
+    fn default() -> Self {
+        Self {
+            block_size: Self::DEFAULT_BLOCK_SIZE,
+        }
+    }
+}
+
+impl PaddingScheme for BlockPadding {
+    fn padded_len(&self, message_len: usize) -> usize {
+        (message_len + 1).div_ceil(self.block_size) * self.block_size
+    }
+}
+
+/// Pads messages using the [Padmé] scheme, whose buckets grow with the message length.
+///
+/// This leaks only O(log log n) bits about a message of length n, while adding at most about 12%
+/// overhead, so it hides the length of large messages much better than [`BlockPadding`].
+///
+/// [Padmé]: https://lbarman.ch/blog/padme/
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PadmePadding;
+
+impl PaddingScheme for PadmePadding {
+    fn padded_len(&self, message_len: usize) -> usize {
+        let len = message_len + 1;
+        // floor(log2(len)), then the number of bits needed to represent that.
+        let exponent = usize::BITS - 1 - len.leading_zeros();
+        let exponent_bits = u32::BITS - exponent.leading_zeros();
+        let mask = (1usize << (exponent - exponent_bits)) - 1;
+        (len + mask) & !mask
+    }
+}
+
+/// Returns `message` padded according to `scheme`.
+pub fn pad_plaintext(message: &[u8], scheme: &dyn PaddingScheme) -> Vec<u8> {
+    let padded_len = scheme.padded_len(message.len()).max(message.len() + 1);
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(message);
+    padded.push(PADDING_BOUNDARY_BYTE);
+    padded.resize(padded_len, 0);
+    padded
+}
+
+/// Returns `padded` without the padding added by [`pad_plaintext`], whatever the scheme.
+pub fn strip_padding(padded: &[u8]) -> Result<&[u8]> {
+    match padded.iter().rposition(|&b| b != 0) {
+        Some(boundary) if padded[boundary] == PADDING_BOUNDARY_BYTE => Ok(&padded[..boundary]),
+        _ => Err(SignalProtocolError::InvalidArgument(
+            "plaintext padding has no boundary byte".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn padded_lengths() {
+        assert_eq!(NoPadding.padded_len(0), 1);
+        assert_eq!(NoPadding.padded_len(200), 201);
+
+        let block = BlockPadding::default();
+        assert_eq!(block.padded_len(0), 160);
+        assert_eq!(block.padded_len(159), 160);
+        assert_eq!(block.padded_len(160), 320);
+        assert!(BlockPadding::new(0).is_err());
+
+        assert_eq!(PadmePadding.padded_len(0), 1);
+        assert_eq!(PadmePadding.padded_len(99), 104);
+        assert_eq!(PadmePadding.padded_len(1000), 1024);
+        for len in [1, 10, 100, 1000, 10_000, 100_000] {
+            let padded = PadmePadding.padded_len(len);
+            assert!(padded > len);
+            assert!(padded <= (len + 1) * 112 / 100 + 1, "{len} -> {padded}");
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let schemes: [&dyn PaddingScheme; 3] =
+            [&NoPadding, &BlockPadding::default(), &PadmePadding];
+        for message in [&b""[..], b"hello", &[0x80, 0, 0], &[0; 500]] {
+            for scheme in schemes {
+                let padded = pad_plaintext(message, scheme);
+                assert_eq!(padded.len(), scheme.padded_len(message.len()));
+                assert_eq!(strip_padding(&padded).expect("valid"), message);
+            }
+        }
+
+        assert!(strip_padding(&[]).is_err());
+        assert!(strip_padding(&[1, 2, 0]).is_err());
+    }
+}
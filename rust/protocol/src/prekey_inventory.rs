@@ -0,0 +1,300 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Bookkeeping for the pre-keys a client keeps uploaded to the server.
+
+use std::time::{Duration, SystemTime};
+
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    kem, GenericSignedPreKey, KeyPair, KyberPreKeyId, KyberPreKeyRecord, KyberPreKeyStore,
+    PreKeyId, PreKeyInventoryStore, PreKeyRecord, PreKeyStore, PrivateKeySigner, PublicKey, Result,
+    SignalProtocolError, SignedPreKeyId, SignedPreKeyRecord, SignedPreKeyStore, Timestamp,
+};
+
+/// Tracks pre-key IDs and generates new batches of pre-keys.
+///
+/// Each kind of pre-key has its own sequence of IDs in the range `1..=`[`Self::MAX_ID`], wrapping
+/// around at the end. Apps should persist the `next_*_id` values after generating a batch and
+/// pass them to [`new`](Self::new) next time, so that IDs aren't reused while the server may still
+/// hand out the old keys.
+#[derive(Clone, Debug)]
+pub struct PreKeyInventory {
+    next_pre_key_id: u32,
+    next_signed_pre_key_id: u32,
+    next_kyber_pre_key_id: u32,
+    signed_pre_key_rotation_age: Duration,
+}
+
+/// How many pre-keys remain, and which signed pre-keys should be replaced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreKeyInventoryReport {
+    pub unused_pre_keys: usize,
+    pub unused_kyber_pre_keys: usize,
+    /// Signed pre-keys older than the rotation age, oldest first.
+    pub signed_pre_keys_due_for_rotation: Vec<SignedPreKeyId>,
+}
+
+/// Newly generated pre-keys, to be saved with [`save`](Self::save) and uploaded using the
+/// contents of [`upload`](Self::upload).
+#[derive(Clone, Debug)]
+pub struct PreKeyBatch {
+    pub pre_keys: Vec<PreKeyRecord>,
+    pub signed_pre_key: Option<SignedPreKeyRecord>,
+    pub kyber_pre_keys: Vec<KyberPreKeyRecord>,
+}
+
+/// The public parts of a [`PreKeyBatch`], as uploaded to the server.
+#[derive(Clone)]
+pub struct PreKeyUpload {
+    pub pre_keys: Vec<(PreKeyId, PublicKey)>,
+    /// The ID, public key, and signature of the new signed pre-key, if there is one.
+    pub signed_pre_key: Option<(SignedPreKeyId, PublicKey, Vec<u8>)>,
+    /// The IDs, public keys, and signatures of the new Kyber pre-keys.
+    pub kyber_pre_keys: Vec<(KyberPreKeyId, kem::PublicKey, Vec<u8>)>,
+}
+
+impl PreKeyInventory {
+    /// The largest pre-key ID, matching the 24-bit IDs used by other Signal implementations.
+    pub const MAX_ID: u32 = 0xFF_FFFF;
+
+    /// How old a signed pre-key can get before [`report`](Self::report) says to replace it, unless
+    /// changed with [`with_signed_pre_key_rotation_age`](Self::with_signed_pre_key_rotation_age).
+    pub const DEFAULT_SIGNED_PRE_KEY_ROTATION_AGE: Duration = Duration::from_secs(60 * 60 * 24 * 2);
+
+    /// Resumes from previously persisted IDs.
+    pub fn new(
+        next_pre_key_id: u32,
+        next_signed_pre_key_id: u32,
+        next_kyber_pre_key_id: u32,
+    ) -> Result<Self> {
+        for (name, id) in [
+            ("next_pre_key_id", next_pre_key_id),
+            ("next_signed_pre_key_id", next_signed_pre_key_id),
+            ("next_kyber_pre_key_id", next_kyber_pre_key_id),
+        ] {
+            if !(1..=Self::MAX_ID).contains(&id) {
+                return Err(SignalProtocolError::InvalidArgument(format!(
+                    "{name} must be between 1 and {}, not {id}",
+                    Self::MAX_ID
+                )));
+            }
+        }
+        Ok(Self {
+            next_pre_key_id,
+            next_signed_pre_key_id,
+            next_kyber_pre_key_id,
+            signed_pre_key_rotation_age: Self::DEFAULT_SIGNED_PRE_KEY_ROTATION_AGE,
+        })
+    }
+
+    /// Starts each ID sequence at a random point, for a newly registered device.
+    pub fn new_random<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        Self {
+            next_pre_key_id: rng.random_range(1..=Self::MAX_ID),
+            next_signed_pre_key_id: rng.random_range(1..=Self::MAX_ID),
+            next_kyber_pre_key_id: rng.random_range(1..=Self::MAX_ID),
+            signed_pre_key_rotation_age: Self::DEFAULT_SIGNED_PRE_KEY_ROTATION_AGE,
+        }
+    }
+
+    pub fn with_signed_pre_key_rotation_age(self, signed_pre_key_rotation_age: Duration) -> Self {
+        Self {
+            signed_pre_key_rotation_age,
+            ..self
+        }
+    }
+
+    pub fn next_pre_key_id(&self) -> u32 {
+        self.next_pre_key_id
+    }
+
+    pub fn next_signed_pre_key_id(&self) -> u32 {
+        self.next_signed_pre_key_id
+    }
+
+    pub fn next_kyber_pre_key_id(&self) -> u32 {
+        self.next_kyber_pre_key_id
+    }
+
+    /// Counts the unused one-time pre-keys and finds signed pre-keys that are due for rotation
+    /// at `now`.
+    pub async fn report(
+        &self,
+        inventory_store: &dyn PreKeyInventoryStore,
+        signed_pre_key_store: &dyn SignedPreKeyStore,
+        now: SystemTime,
+    ) -> Result<PreKeyInventoryReport> {
+        let mut signed_pre_keys = vec![];
+        for id in inventory_store.signed_pre_key_ids().await? {
+            let created_at = SystemTime::from(
+                signed_pre_key_store
+                    .get_signed_pre_key(id)
+                    .await?
+                    .timestamp()?,
+            );
+            // A key from the future (due to clock skew) isn't due yet.
+            let is_due = now
+                .duration_since(created_at)
+                .is_ok_and(|age| age >= self.signed_pre_key_rotation_age);
+            if is_due {
+                signed_pre_keys.push((created_at, id));
+            }
+        }
+        signed_pre_keys.sort();
+
+        Ok(PreKeyInventoryReport {
+            unused_pre_keys: inventory_store.unused_pre_key_ids().await?.len(),
+            unused_kyber_pre_keys: inventory_store
+                .unused_one_time_kyber_pre_key_ids()
+                .await?
+                .len(),
+            signed_pre_keys_due_for_rotation: signed_pre_keys
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect(),
+        })
+    }
+
+    /// Generates `one_time_count` one-time pre-keys and as many one-time Kyber pre-keys, plus a
+    /// new signed pre-key if `new_signed_pre_key` is set, advancing the ID sequences.
+    pub fn generate<R: Rng + CryptoRng>(
+        &mut self,
+        identity: &dyn PrivateKeySigner,
+        one_time_count: usize,
+        new_signed_pre_key: bool,
+        now: SystemTime,
+        rng: &mut R,
+    ) -> Result<PreKeyBatch> {
+        let timestamp = Timestamp::from_epoch_millis(
+            now.duration_since(SystemTime::UNIX_EPOCH)
+                .map_err(|_| {
+                    SignalProtocolError::InvalidArgument("now must be after 1970".to_string())
+                })?
+                .as_millis()
+                .try_into()
+                .map_err(|_| {
+                    SignalProtocolError::InvalidArgument("now is too large".to_string())
+                })?,
+        );
+        let pre_keys = (0..one_time_count)
+            .map(|_| {
+                let id = take_id(&mut self.next_pre_key_id);
+                PreKeyRecord::new(id.into(), &KeyPair::generate(rng))
+            })
+            .collect();
+
+        let signed_pre_key = if new_signed_pre_key {
+            let id = take_id(&mut self.next_signed_pre_key_id);
+            let key_pair = KeyPair::generate(rng);
+            let signature = identity.calculate_signature(&key_pair.public_key.serialize(), rng)?;
+            Some(SignedPreKeyRecord::new(
+                id.into(),
+                timestamp,
+                &key_pair,
+                &signature,
+            ))
+        } else {
+            None
+        };
+
+        let kyber_pre_keys = (0..one_time_count)
+            .map(|_| {
+                let id = take_id(&mut self.next_kyber_pre_key_id);
+                let key_pair = kem::KeyPair::generate(kem::KeyType::Kyber1024, rng);
+                let signature =
+                    identity.calculate_signature(&key_pair.public_key.serialize(), rng)?;
+                Ok(KyberPreKeyRecord::new(
+                    id.into(),
+                    timestamp,
+                    &key_pair,
+                    &signature,
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(PreKeyBatch {
+            pre_keys,
+            signed_pre_key,
+            kyber_pre_keys,
+        })
+    }
+}
+
+/// Returns the next ID in a sequence and advances it, wrapping from [`PreKeyInventory::MAX_ID`]
+/// back to 1.
+fn take_id(next_id: &mut u32) -> u32 {
+    let id = *next_id;
+    *next_id = id % PreKeyInventory::MAX_ID + 1;
+    id
+}
+
+impl PreKeyBatch {
+    /// Saves every record in the batch to the appropriate store.
+    pub async fn save(
+        &self,
+        pre_key_store: &mut dyn PreKeyStore,
+        signed_pre_key_store: &mut dyn SignedPreKeyStore,
+        kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    ) -> Result<()> {
+        for record in &self.pre_keys {
+            pre_key_store.save_pre_key(record.id()?, record).await?;
+        }
+        if let Some(record) = &self.signed_pre_key {
+            signed_pre_key_store
+                .save_signed_pre_key(record.id()?, record)
+                .await?;
+        }
+        for record in &self.kyber_pre_keys {
+            kyber_pre_key_store
+                .save_kyber_pre_key(record.id()?, record)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The public keys and signatures in the batch.
+    pub fn upload(&self) -> Result<PreKeyUpload> {
+        Ok(PreKeyUpload {
+            pre_keys: self
+                .pre_keys
+                .iter()
+                .map(|record| Ok((record.id()?, record.public_key()?)))
+                .collect::<Result<_>>()?,
+            signed_pre_key: self
+                .signed_pre_key
+                .as_ref()
+                .map(|record| Ok((record.id()?, record.public_key()?, record.signature()?)))
+                .transpose()?,
+            kyber_pre_keys: self
+                .kyber_pre_keys
+                .iter()
+                .map(|record| Ok((record.id()?, record.public_key()?, record.signature()?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ids_wrap() {
+        let mut id = PreKeyInventory::MAX_ID - 1;
+        assert_eq!(take_id(&mut id), PreKeyInventory::MAX_ID - 1);
+        assert_eq!(take_id(&mut id), PreKeyInventory::MAX_ID);
+        assert_eq!(take_id(&mut id), 1);
+        assert_eq!(id, 2);
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        assert!(PreKeyInventory::new(0, 1, 1).is_err());
+        assert!(PreKeyInventory::new(1, PreKeyInventory::MAX_ID + 1, 1).is_err());
+        assert!(PreKeyInventory::new(1, 1, PreKeyInventory::MAX_ID).is_ok());
+    }
+}
@@ -4,6 +4,7 @@
 //
 
 pub mod fingerprint;
+pub mod provisioning;
 pub mod sealed_sender;
 pub mod service;
 pub mod storage;
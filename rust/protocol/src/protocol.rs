@@ -752,7 +752,7 @@ impl PlaintextContent {
     ///
     /// Usually messages are padded to avoid exposing patterns,
     /// but PlaintextContent messages are all fixed-length anyway, so there won't be any padding.
-    const PADDING_BOUNDARY_BYTE: u8 = 0x80;
+    const PADDING_BOUNDARY_BYTE: u8 = crate::padding::PADDING_BOUNDARY_BYTE;
 
     #[inline]
     pub fn body(&self) -> &[u8] {
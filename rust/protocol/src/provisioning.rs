@@ -0,0 +1,341 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Encryption for the messages used to link a new device to an existing account.
+//!
+//! The new (secondary) device generates a [`ProvisioningReceiver`] and shows its public key to the
+//! primary device, usually in a QR code. The primary device fills in a [`ProvisionMessage`] and
+//! encrypts it with [`encrypt_provision_message`], and the server passes the resulting
+//! `ProvisionEnvelope` along to the new device, which opens it with
+//! [`ProvisioningReceiver::decrypt`].
+
+use prost::Message;
+use rand::{CryptoRng, Rng};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::{
+    crypto, proto, Aci, IdentityKey, IdentityKeyPair, KeyPair, Pni, PrivateKey, PublicKey, Result,
+    SignalProtocolError,
+};
+
+/// The first byte of an encrypted `ProvisionMessage`.
+const PROVISIONING_CIPHER_VERSION: u8 = 1;
+const KDF_INFO: &[u8] = b"TextSecure Provisioning Message";
+const IV_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+
+/// The contents of a provisioning message, sent from the primary device to a new device.
+#[derive(Clone)]
+pub struct ProvisionMessage {
+    pub aci: Aci,
+    pub pni: Pni,
+    pub number: String,
+    pub aci_identity_key_pair: IdentityKeyPair,
+    pub pni_identity_key_pair: IdentityKeyPair,
+    pub profile_key: [u8; 32],
+    /// Proves to the server that the primary device approved the link.
+    pub provisioning_code: String,
+    pub read_receipts: bool,
+    pub user_agent: Option<String>,
+    pub provisioning_version: Option<u32>,
+    pub master_key: Option<[u8; 32]>,
+    pub account_entropy_pool: Option<String>,
+    pub ephemeral_backup_key: Option<[u8; 32]>,
+    pub media_root_backup_key: Option<[u8; 32]>,
+}
+
+/// The new device's side of provisioning.
+///
+/// The key pair is only used for a single link attempt, so it is never persisted.
+pub struct ProvisioningReceiver {
+    key_pair: KeyPair,
+}
+
+impl ProvisioningReceiver {
+    pub fn generate<R: Rng + CryptoRng>(csprng: &mut R) -> Self {
+        Self {
+            key_pair: KeyPair::generate(csprng),
+        }
+    }
+
+    /// The public key to share with the primary device.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.key_pair.public_key
+    }
+
+    /// Decrypts and parses a serialized `ProvisionEnvelope` from the primary device.
+    pub fn decrypt(&self, envelope: &[u8]) -> Result<ProvisionMessage> {
+        let envelope = proto::provisioning::ProvisionEnvelope::decode(envelope)
+            .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)?;
+        let their_public_key = PublicKey::deserialize(&required(envelope.public_key)?)?;
+        let body = required(envelope.body)?;
+
+        if body.len() < 1 + IV_LEN + MAC_LEN {
+            return Err(SignalProtocolError::InvalidArgument(
+                "provisioning message is too short".to_string(),
+            ));
+        }
+        if body[0] != PROVISIONING_CIPHER_VERSION {
+            return Err(SignalProtocolError::UnrecognizedMessageVersion(
+                body[0].into(),
+            ));
+        }
+
+        let (cipher_key, mac_key) = provisioning_keys(
+            &self
+                .key_pair
+                .private_key
+                .calculate_agreement(&their_public_key)?,
+        );
+
+        let (signed, their_mac) = body.split_at(body.len() - MAC_LEN);
+        let our_mac = crypto::hmac_sha256(&mac_key, signed);
+        if !bool::from(our_mac.ct_eq(their_mac)) {
+            return Err(SignalProtocolError::InvalidArgument(
+                "provisioning message failed integrity check".to_string(),
+            ));
+        }
+
+        let (iv, ciphertext) = signed[1..].split_at(IV_LEN);
+        let plaintext =
+            signal_crypto::aes_256_cbc_decrypt(ciphertext, &cipher_key, iv).map_err(|_| {
+                SignalProtocolError::InvalidArgument(
+                    "provisioning message failed to decrypt".to_string(),
+                )
+            })?;
+
+        proto::provisioning::ProvisionMessage::decode(plaintext.as_slice())
+            .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)?
+            .try_into()
+    }
+}
+
+/// Encrypts `message` for the new device whose [`ProvisioningReceiver`] has
+/// `receiver_public_key`, returning a serialized `ProvisionEnvelope`.
+pub fn encrypt_provision_message<R: Rng + CryptoRng>(
+    message: &ProvisionMessage,
+    receiver_public_key: &PublicKey,
+    csprng: &mut R,
+) -> Result<Vec<u8>> {
+    let our_key_pair = KeyPair::generate(csprng);
+    let (cipher_key, mac_key) = provisioning_keys(
+        &our_key_pair
+            .private_key
+            .calculate_agreement(receiver_public_key)?,
+    );
+
+    let iv: [u8; IV_LEN] = csprng.random();
+    let ciphertext = signal_crypto::aes_256_cbc_encrypt(
+        &proto::provisioning::ProvisionMessage::from(message).encode_to_vec(),
+        &cipher_key,
+        &iv,
+    )
+    .expect("valid key and IV");
+
+    let mut body = Vec::with_capacity(1 + IV_LEN + ciphertext.len() + MAC_LEN);
+    body.push(PROVISIONING_CIPHER_VERSION);
+    body.extend_from_slice(&iv);
+    body.extend_from_slice(&ciphertext);
+    let mac = crypto::hmac_sha256(&mac_key, &body);
+    body.extend_from_slice(&mac);
+
+    Ok(proto::provisioning::ProvisionEnvelope {
+        public_key: Some(our_key_pair.public_key.serialize().into_vec()),
+        body: Some(body),
+    }
+    .encode_to_vec())
+}
+
+/// Derives the cipher key and MAC key from the shared secret.
+fn provisioning_keys(shared_secret: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut derived = [0; 64];
+    hkdf::Hkdf::<sha2::Sha256>::new(None, shared_secret)
+        .expand(KDF_INFO, &mut derived)
+        .expect("valid output length");
+    let (cipher_key, mac_key) = derived.split_at(32);
+    (
+        cipher_key.try_into().expect("correct length"),
+        mac_key.try_into().expect("correct length"),
+    )
+}
+
+fn required<T>(field: Option<T>) -> Result<T> {
+    field.ok_or(SignalProtocolError::InvalidProtobufEncoding)
+}
+
+fn key_bytes(bytes: Vec<u8>) -> Result<[u8; 32]> {
+    bytes
+        .try_into()
+        .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)
+}
+
+fn identity_key_pair(public: Option<Vec<u8>>, private: Option<Vec<u8>>) -> Result<IdentityKeyPair> {
+    let identity_key = IdentityKey::decode(&required(public)?)?;
+    let private_key = PrivateKey::deserialize(&required(private)?)?;
+    if private_key.public_key()? != *identity_key.public_key() {
+        return Err(SignalProtocolError::InvalidArgument(
+            "provisioned identity key pair does not match".to_string(),
+        ));
+    }
+    Ok(IdentityKeyPair::new(identity_key, private_key))
+}
+
+impl TryFrom<proto::provisioning::ProvisionMessage> for ProvisionMessage {
+    type Error = SignalProtocolError;
+
+    fn try_from(message: proto::provisioning::ProvisionMessage) -> Result<Self> {
+        let aci = Aci::parse_from_service_id_string(&required(message.aci)?)
+            .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
+        // PNIs are usually sent as bare UUIDs, but accept the prefixed form too.
+        let pni = required(message.pni)?;
+        let pni = Pni::parse_from_service_id_string(&pni)
+            .or_else(|| Uuid::try_parse(&pni).ok().map(Pni::from))
+            .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
+
+        Ok(Self {
+            aci,
+            pni,
+            number: required(message.number)?,
+            aci_identity_key_pair: identity_key_pair(
+                message.aci_identity_key_public,
+                message.aci_identity_key_private,
+            )?,
+            pni_identity_key_pair: identity_key_pair(
+                message.pni_identity_key_public,
+                message.pni_identity_key_private,
+            )?,
+            profile_key: key_bytes(required(message.profile_key)?)?,
+            provisioning_code: required(message.provisioning_code)?,
+            read_receipts: message.read_receipts.unwrap_or_default(),
+            user_agent: message.user_agent,
+            provisioning_version: message.provisioning_version,
+            master_key: message.master_key.map(key_bytes).transpose()?,
+            account_entropy_pool: message.account_entropy_pool,
+            ephemeral_backup_key: message.ephemeral_backup_key.map(key_bytes).transpose()?,
+            media_root_backup_key: message.media_root_backup_key.map(key_bytes).transpose()?,
+        })
+    }
+}
+
+impl From<&ProvisionMessage> for proto::provisioning::ProvisionMessage {
+    fn from(message: &ProvisionMessage) -> Self {
+        Self {
+            aci_identity_key_public: Some(
+                message
+                    .aci_identity_key_pair
+                    .identity_key()
+                    .serialize()
+                    .into_vec(),
+            ),
+            aci_identity_key_private: Some(message.aci_identity_key_pair.private_key().serialize()),
+            pni_identity_key_public: Some(
+                message
+                    .pni_identity_key_pair
+                    .identity_key()
+                    .serialize()
+                    .into_vec(),
+            ),
+            pni_identity_key_private: Some(message.pni_identity_key_pair.private_key().serialize()),
+            aci: Some(message.aci.service_id_string()),
+            pni: Some(Uuid::from(message.pni).to_string()),
+            number: Some(message.number.clone()),
+            provisioning_code: Some(message.provisioning_code.clone()),
+            user_agent: message.user_agent.clone(),
+            profile_key: Some(message.profile_key.to_vec()),
+            read_receipts: Some(message.read_receipts),
+            provisioning_version: message.provisioning_version,
+            master_key: message.master_key.map(Vec::from),
+            ephemeral_backup_key: message.ephemeral_backup_key.map(Vec::from),
+            account_entropy_pool: message.account_entropy_pool.clone(),
+            media_root_backup_key: message.media_root_backup_key.map(Vec::from),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+    use rand::TryRngCore as _;
+
+    use super::*;
+
+    fn test_message<R: Rng + CryptoRng>(csprng: &mut R) -> ProvisionMessage {
+        ProvisionMessage {
+            aci: Aci::from_uuid_bytes([0xAA; 16]),
+            pni: Pni::from_uuid_bytes([0xBB; 16]),
+            number: "+14155550100".to_string(),
+            aci_identity_key_pair: IdentityKeyPair::generate(csprng),
+            pni_identity_key_pair: IdentityKeyPair::generate(csprng),
+            profile_key: [1; 32],
+            provisioning_code: "123456".to_string(),
+            read_receipts: true,
+            user_agent: None,
+            provisioning_version: Some(1),
+            master_key: Some([2; 32]),
+            account_entropy_pool: Some("a".repeat(64)),
+            ephemeral_backup_key: None,
+            media_root_backup_key: Some([3; 32]),
+        }
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let mut csprng = OsRng.unwrap_err();
+        let receiver = ProvisioningReceiver::generate(&mut csprng);
+        let message = test_message(&mut csprng);
+
+        let envelope = encrypt_provision_message(&message, receiver.public_key(), &mut csprng)?;
+        let received = receiver.decrypt(&envelope)?;
+
+        assert_eq!(received.aci, message.aci);
+        assert_eq!(received.pni, message.pni);
+        assert_eq!(received.number, message.number);
+        assert_eq!(
+            received.aci_identity_key_pair.serialize(),
+            message.aci_identity_key_pair.serialize()
+        );
+        assert_eq!(
+            received.pni_identity_key_pair.serialize(),
+            message.pni_identity_key_pair.serialize()
+        );
+        assert_eq!(received.profile_key, message.profile_key);
+        assert_eq!(received.provisioning_code, message.provisioning_code);
+        assert!(received.read_receipts);
+        assert_eq!(received.master_key, message.master_key);
+        assert_eq!(received.account_entropy_pool, message.account_entropy_pool);
+        assert_eq!(received.ephemeral_backup_key, None);
+        assert_eq!(
+            received.media_root_backup_key,
+            message.media_root_backup_key
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_receivers_and_tampering() -> Result<()> {
+        let mut csprng = OsRng.unwrap_err();
+        let receiver = ProvisioningReceiver::generate(&mut csprng);
+        let message = test_message(&mut csprng);
+        let envelope = encrypt_provision_message(&message, receiver.public_key(), &mut csprng)?;
+
+        let other_receiver = ProvisioningReceiver::generate(&mut csprng);
+        assert!(other_receiver.decrypt(&envelope).is_err());
+
+        let mut parsed =
+            proto::provisioning::ProvisionEnvelope::decode(envelope.as_slice()).expect("valid");
+        let body = parsed.body.as_mut().expect("present");
+        body[20] ^= 1;
+        assert!(receiver.decrypt(&parsed.encode_to_vec()).is_err());
+
+        body[20] ^= 1;
+        body[0] = 2;
+        assert!(matches!(
+            receiver.decrypt(&parsed.encode_to_vec()),
+            Err(SignalProtocolError::UnrecognizedMessageVersion(2))
+        ));
+        Ok(())
+    }
+}
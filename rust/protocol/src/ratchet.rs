@@ -55,7 +55,7 @@ pub(crate) fn initialize_alice_session<R: Rng + CryptoRng>(
     parameters: &AliceSignalProtocolParameters,
     mut csprng: &mut R,
 ) -> Result<SessionState> {
-    let local_identity = parameters.our_identity_key_pair().identity_key();
+    let local_identity = &parameters.our_identity().identity_key();
 
     let mut secrets = Vec::with_capacity(32 * 6);
 
@@ -65,8 +65,7 @@ pub(crate) fn initialize_alice_session<R: Rng + CryptoRng>(
 
     secrets.extend_from_slice(
         &parameters
-            .our_identity_key_pair()
-            .private_key()
+            .our_identity()
             .calculate_agreement(parameters.their_signed_pre_key())?,
     );
 
@@ -139,7 +138,7 @@ pub(crate) fn initialize_alice_session<R: Rng + CryptoRng>(
 pub(crate) fn initialize_bob_session(
     parameters: &BobSignalProtocolParameters,
 ) -> Result<SessionState> {
-    let local_identity = parameters.our_identity_key_pair().identity_key();
+    let local_identity = &parameters.our_identity().identity_key();
 
     let mut secrets = Vec::with_capacity(32 * 6);
 
@@ -154,8 +153,7 @@ pub(crate) fn initialize_bob_session(
 
     secrets.extend_from_slice(
         &parameters
-            .our_identity_key_pair()
-            .private_key()
+            .our_identity()
             .calculate_agreement(parameters.their_base_key())?,
     );
 
@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use crate::{kem, IdentityKey, IdentityKeyPair, KeyPair, PublicKey};
+use crate::{kem, IdentityKey, IdentityKeyPair, KeyPair, PrivateKeySigner, PublicKey};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum UsePQRatchet {
@@ -22,7 +22,7 @@ impl From<bool> for UsePQRatchet {
 }
 
 pub struct AliceSignalProtocolParameters {
-    our_identity_key_pair: IdentityKeyPair,
+    our_identity: Box<dyn PrivateKeySigner>,
     our_base_key_pair: KeyPair,
 
     their_identity_key: IdentityKey,
@@ -36,7 +36,7 @@ pub struct AliceSignalProtocolParameters {
 
 impl AliceSignalProtocolParameters {
     pub fn new(
-        our_identity_key_pair: IdentityKeyPair,
+        our_identity: impl PrivateKeySigner + 'static,
         our_base_key_pair: KeyPair,
         their_identity_key: IdentityKey,
         their_signed_pre_key: PublicKey,
@@ -45,7 +45,7 @@ impl AliceSignalProtocolParameters {
         use_pq_ratchet: UsePQRatchet,
     ) -> Self {
         Self {
-            our_identity_key_pair,
+            our_identity: Box::new(our_identity),
             our_base_key_pair,
             their_identity_key,
             their_signed_pre_key,
@@ -66,8 +66,15 @@ impl AliceSignalProtocolParameters {
     }
 
     #[inline]
-    pub fn our_identity_key_pair(&self) -> &IdentityKeyPair {
-        &self.our_identity_key_pair
+    pub fn our_identity(&self) -> &dyn PrivateKeySigner {
+        &*self.our_identity
+    }
+
+    /// Returns `None` if the identity isn't an in-memory [`IdentityKeyPair`].
+    #[inline]
+    #[deprecated = "use our_identity instead, which also covers identities held in a keystore"]
+    pub fn our_identity_key_pair(&self) -> Option<&IdentityKeyPair> {
+        self.our_identity.as_identity_key_pair()
     }
 
     #[inline]
@@ -107,7 +114,7 @@ impl AliceSignalProtocolParameters {
 }
 
 pub struct BobSignalProtocolParameters<'a> {
-    our_identity_key_pair: IdentityKeyPair,
+    our_identity: Box<dyn PrivateKeySigner>,
     our_signed_pre_key_pair: KeyPair,
     our_one_time_pre_key_pair: Option<KeyPair>,
     our_ratchet_key_pair: KeyPair,
@@ -123,7 +130,7 @@ pub struct BobSignalProtocolParameters<'a> {
 impl<'a> BobSignalProtocolParameters<'a> {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        our_identity_key_pair: IdentityKeyPair,
+        our_identity: impl PrivateKeySigner + 'static,
         our_signed_pre_key_pair: KeyPair,
         our_one_time_pre_key_pair: Option<KeyPair>,
         our_ratchet_key_pair: KeyPair,
@@ -134,7 +141,7 @@ impl<'a> BobSignalProtocolParameters<'a> {
         use_pq_ratchet: UsePQRatchet,
     ) -> Self {
         Self {
-            our_identity_key_pair,
+            our_identity: Box::new(our_identity),
             our_signed_pre_key_pair,
             our_one_time_pre_key_pair,
             our_ratchet_key_pair,
@@ -147,8 +154,15 @@ impl<'a> BobSignalProtocolParameters<'a> {
     }
 
     #[inline]
-    pub fn our_identity_key_pair(&self) -> &IdentityKeyPair {
-        &self.our_identity_key_pair
+    pub fn our_identity(&self) -> &dyn PrivateKeySigner {
+        &*self.our_identity
+    }
+
+    /// Returns `None` if the identity isn't an in-memory [`IdentityKeyPair`].
+    #[inline]
+    #[deprecated = "use our_identity instead, which also covers identities held in a keystore"]
+    pub fn our_identity_key_pair(&self) -> Option<&IdentityKeyPair> {
+        self.our_identity.as_identity_key_pair()
     }
 
     #[inline]
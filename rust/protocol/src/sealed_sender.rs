@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use std::collections::BTreeMap;
 use std::ops::Range;
 use std::time::SystemTime;
 
@@ -14,14 +15,17 @@ use prost::Message;
 use proto::sealed_sender::unidentified_sender_message::message::Type as ProtoMessageType;
 use rand::{CryptoRng, Rng, TryRngCore as _};
 use subtle::ConstantTimeEq;
+use uuid::Uuid;
 use zerocopy::{FromBytes, Immutable, KnownLayout};
 
+use crate::padding::{pad_plaintext, strip_padding, PaddingScheme};
 use crate::{
-    crypto, message_encrypt, proto, ratchet, session_cipher, Aci, CiphertextMessageType, DeviceId,
-    Direction, IdentityKey, IdentityKeyPair, IdentityKeyStore, KeyPair, KyberPreKeyStore,
-    PreKeySignalMessage, PreKeyStore, PrivateKey, ProtocolAddress, PublicKey, Result, ServiceId,
-    ServiceIdFixedWidthBinaryBytes, SessionRecord, SessionStore, SignalMessage,
-    SignalProtocolError, SignedPreKeyStore, Timestamp,
+    crypto, group_encrypt, message_encrypt, proto, ratchet, session_cipher, Aci,
+    CiphertextMessageType, DeviceId, Direction, IdentityKey, IdentityKeyStore, KeyPair,
+    KyberPreKeyStore, PreKeySignalMessage, PreKeyStore, PrivateKey, PrivateKeySigner,
+    ProtocolAddress, PublicKey, Result, SenderKeyStore, ServiceId, ServiceIdFixedWidthBinaryBytes,
+    SessionRecord, SessionStore, SessionUsabilityRequirements, SignalMessage, SignalProtocolError,
+    SignedPreKeyStore, Timestamp,
 };
 
 #[derive(Debug, Clone)]
@@ -603,6 +607,8 @@ mod sealed_sender_v1 {
     use zerocopy::IntoBytes;
 
     use super::*;
+    #[cfg(test)]
+    use crate::IdentityKeyPair;
 
     /// A symmetric cipher key and a MAC key, along with a "chain key" consumed in
     /// [`StaticKeys::calculate`].
@@ -618,11 +624,11 @@ mod sealed_sender_v1 {
         /// Derive a set of symmetric keys from the key agreement between the sender and
         /// recipient's identities.
         pub(super) fn calculate(
-            our_keys: &KeyPair,
+            our_keys: &dyn PrivateKeySigner,
             their_public: &PublicKey,
             direction: Direction,
         ) -> Result<Self> {
-            let our_pub_key = our_keys.public_key.serialize();
+            let our_pub_key = our_keys.public_key().serialize();
             let their_pub_key = their_public.serialize();
             let ephemeral_salt = match direction {
                 Direction::Sending => [SALT_PREFIX, &their_pub_key, &our_pub_key],
@@ -630,7 +636,7 @@ mod sealed_sender_v1 {
             }
             .concat();
 
-            let shared_secret = our_keys.private_key.calculate_agreement(their_public)?;
+            let shared_secret = our_keys.calculate_agreement(their_public)?;
             #[derive(Default, KnownLayout, IntoBytes, FromBytes)]
             #[repr(C, packed)]
             struct DerivedValues([u8; 32], [u8; 32], [u8; 32]);
@@ -682,14 +688,14 @@ mod sealed_sender_v1 {
         /// Derive a set of symmetric keys from the agreement between the sender and
         /// recipient's identities, as well as [`EphemeralKeys::chain_key`].
         pub(super) fn calculate(
-            our_keys: &IdentityKeyPair,
+            our_keys: &dyn PrivateKeySigner,
             their_key: &PublicKey,
             chain_key: &[u8; 32],
             ctext: &[u8],
         ) -> Result<Self> {
             let salt = [chain_key, ctext].concat();
 
-            let shared_secret = our_keys.private_key().calculate_agreement(their_key)?;
+            let shared_secret = our_keys.calculate_agreement(their_key)?;
             // 96 bytes are derived, but the first 32 are discarded/unused. This is intended to
             // mirror the way the EphemeralKeys are derived, even though StaticKeys does not end up
             // requiring a third "chain key".
@@ -751,11 +757,8 @@ mod sealed_sender_v1 {
         .expect("just generated these keys, they should be correct");
 
         // The message recipient calculates the ephemeral key and the sender's public key.
-        let recipient_eph_keys = EphemeralKeys::calculate(
-            &recipient_identity.into(),
-            &ephemeral_public,
-            Direction::Receiving,
-        )?;
+        let recipient_eph_keys =
+            EphemeralKeys::calculate(&recipient_identity, &ephemeral_public, Direction::Receiving)?;
         assert_eq!(sender_eph_keys, recipient_eph_keys);
 
         let recipient_message_key_bytes = crypto::aes256_ctr_hmacsha256_decrypt(
@@ -813,6 +816,31 @@ pub async fn sealed_sender_encrypt<R: Rng + CryptoRng>(
     sealed_sender_encrypt_from_usmc(destination, &usmc, identity_store, rng).await
 }
 
+/// Like [`sealed_sender_encrypt`], but pads `ptext` according to `padding` first.
+///
+/// The recipient can remove the padding with [`SealedSenderDecryptionResult::unpadded_message`].
+pub async fn sealed_sender_encrypt_padded<R: Rng + CryptoRng>(
+    destination: &ProtocolAddress,
+    sender_cert: &SenderCertificate,
+    ptext: &[u8],
+    padding: &dyn PaddingScheme,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    now: SystemTime,
+    rng: &mut R,
+) -> Result<Vec<u8>> {
+    sealed_sender_encrypt(
+        destination,
+        sender_cert,
+        &pad_plaintext(ptext, padding),
+        session_store,
+        identity_store,
+        now,
+        rng,
+    )
+    .await
+}
+
 /// This method implements the single-key single-recipient [KEM] described in [this Signal blog
 /// post], a.k.a. Sealed Sender v1.
 ///
@@ -869,7 +897,7 @@ pub async fn sealed_sender_encrypt_from_usmc<R: Rng + CryptoRng>(
     identity_store: &dyn IdentityKeyStore,
     rng: &mut R,
 ) -> Result<Vec<u8>> {
-    let our_identity = identity_store.get_identity_key_pair().await?;
+    let our_identity = identity_store.get_identity_key_signer().await?;
     let their_identity = identity_store
         .get_identity(destination)
         .await?
@@ -891,7 +919,7 @@ pub async fn sealed_sender_encrypt_from_usmc<R: Rng + CryptoRng>(
     .expect("just generated these keys, they should be correct");
 
     let static_keys = sealed_sender_v1::StaticKeys::calculate(
-        &our_identity,
+        &*our_identity,
         their_identity.public_key(),
         &eph_keys.chain_key,
         &static_key_ctext,
@@ -918,6 +946,8 @@ pub async fn sealed_sender_encrypt_from_usmc<R: Rng + CryptoRng>(
 
 mod sealed_sender_v2 {
     use super::*;
+    #[cfg(test)]
+    use crate::IdentityKeyPair;
 
     // Static byte strings used as part of a MAC in HKDF.
     const LABEL_R: &[u8] = b"Sealed Sender v2: r (2023-08)";
@@ -972,7 +1002,7 @@ mod sealed_sender_v2 {
     /// the original `input` bytes if called with [`Direction::Receiving`] with `our_keys` and
     /// `their_key` swapped.
     pub(super) fn apply_agreement_xor(
-        our_keys: &KeyPair,
+        our_keys: &dyn PrivateKeySigner,
         their_key: &PublicKey,
         direction: Direction,
         input: &[u8; MESSAGE_KEY_LEN],
@@ -981,13 +1011,13 @@ mod sealed_sender_v2 {
         let agreement_key_input = match direction {
             Direction::Sending => [
                 agreement,
-                our_keys.public_key.serialize(),
+                our_keys.public_key().serialize(),
                 their_key.serialize(),
             ],
             Direction::Receiving => [
                 agreement,
                 their_key.serialize(),
-                our_keys.public_key.serialize(),
+                our_keys.public_key().serialize(),
             ],
         }
         .concat();
@@ -1012,15 +1042,13 @@ mod sealed_sender_v2 {
     /// calling this method with [`Direction::Receiving`] with `our_keys` and `their_key`
     /// swapped, if `ephemeral_pub_key` and `encrypted_message_key` are the same.
     pub(super) fn compute_authentication_tag(
-        our_keys: &IdentityKeyPair,
+        our_keys: &dyn PrivateKeySigner,
         their_key: &IdentityKey,
         direction: Direction,
         ephemeral_pub_key: &PublicKey,
         encrypted_message_key: &[u8; MESSAGE_KEY_LEN],
     ) -> Result<[u8; AUTH_TAG_LEN]> {
-        let agreement = our_keys
-            .private_key()
-            .calculate_agreement(their_key.public_key())?;
+        let agreement = our_keys.calculate_agreement(their_key.public_key())?;
         let mut agreement_key_input = agreement.into_vec();
         agreement_key_input.extend_from_slice(&ephemeral_pub_key.serialize());
         agreement_key_input.extend_from_slice(encrypted_message_key);
@@ -1068,7 +1096,7 @@ mod sealed_sender_v2 {
 
         // The message recipient calculates the original random bytes and authenticates the result.
         let recv_m = apply_agreement_xor(
-            &recipient_identity.into(),
+            &recipient_identity,
             &e.public_key,
             Direction::Receiving,
             &sender_c_0,
@@ -1273,6 +1301,283 @@ where
     .await
 }
 
+/// Why a recipient was left out of a message built by [`encrypt_for_recipients`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientEncryptionFailure {
+    /// There is no usable session with the recipient (or no identity key recorded for them).
+    MissingSession,
+    /// The recipient's identity key is not trusted for sending.
+    UntrustedIdentity,
+}
+
+/// The result of [`encrypt_for_recipients`].
+#[derive(Debug)]
+pub struct MultiRecipientEncryptResult {
+    /// The Sealed Sender v2 "sent message" for the server, addressed to every recipient not
+    /// listed in `failures`.
+    ///
+    /// `None` if every recipient failed, in which case nothing was encrypted and the sender key
+    /// was not advanced.
+    pub message: Option<Vec<u8>>,
+    /// The recipients that were left out, and why.
+    pub failures: Vec<(ServiceId, RecipientEncryptionFailure)>,
+}
+
+/// Encrypts `plaintext` for a group of recipients as a single Sealed Sender v2 message.
+///
+/// The plaintext is encrypted with the sender key for `distribution_id` (see [`group_encrypt`]),
+/// wrapped as an [`UnidentifiedSenderMessageContent`] from `sender_cert`, and then passed to
+/// [`sealed_sender_multi_recipient_encrypt`] along with the sessions for `destinations`.
+///
+/// `destinations` must list every device of every recipient, since the stores don't know which
+/// devices a recipient has. If any device of a recipient lacks a usable session or has an
+/// untrusted identity, the whole recipient is listed as excluded in the message and reported in
+/// [`MultiRecipientEncryptResult::failures`] rather than failing the whole operation; the server
+/// would reject a message that reached only some of a recipient's devices. The caller is
+/// expected to repair those sessions and send to them separately.
+///
+/// The sender key distribution message must already have been sent to every destination.
+#[allow(clippy::too_many_arguments)]
+pub async fn encrypt_for_recipients<R: Rng + CryptoRng>(
+    destinations: &[&ProtocolAddress],
+    sender_cert: &SenderCertificate,
+    distribution_id: Uuid,
+    plaintext: &[u8],
+    content_hint: ContentHint,
+    group_id: Option<Vec<u8>>,
+    session_store: &dyn SessionStore,
+    identity_store: &dyn IdentityKeyStore,
+    sender_key_store: &mut dyn SenderKeyStore,
+    now: SystemTime,
+    rng: &mut R,
+) -> Result<MultiRecipientEncryptResult> {
+    let mut devices_by_recipient: IndexMap<ServiceId, Vec<&ProtocolAddress>> = IndexMap::new();
+    for &destination in destinations {
+        let service_id =
+            ServiceId::parse_from_service_id_string(destination.name()).ok_or_else(|| {
+                SignalProtocolError::InvalidArgument(format!(
+                    "multi-recipient sealed sender requires recipients' ServiceId (not {})",
+                    destination.name()
+                ))
+            })?;
+        devices_by_recipient
+            .entry(service_id)
+            .or_default()
+            .push(destination);
+    }
+
+    let mut accepted_destinations = Vec::with_capacity(destinations.len());
+    let mut accepted_sessions = Vec::with_capacity(destinations.len());
+    let mut failures = vec![];
+
+    'recipients: for (service_id, devices) in devices_by_recipient {
+        let mut sessions = Vec::with_capacity(devices.len());
+        for &destination in &devices {
+            if let Some(failure) = check_recipient_device(
+                destination,
+                session_store,
+                identity_store,
+                now,
+                &mut sessions,
+            )
+            .await?
+            {
+                failures.push((service_id, failure));
+                continue 'recipients;
+            }
+        }
+        accepted_destinations.extend(devices);
+        accepted_sessions.extend(sessions);
+    }
+
+    if accepted_destinations.is_empty() {
+        return Ok(MultiRecipientEncryptResult {
+            message: None,
+            failures,
+        });
+    }
+
+    let sender = ProtocolAddress::new(
+        sender_cert.sender_uuid()?.to_owned(),
+        sender_cert.sender_device_id()?,
+    );
+    let sender_key_message =
+        group_encrypt(sender_key_store, &sender, distribution_id, plaintext, rng).await?;
+    let usmc = UnidentifiedSenderMessageContent::new(
+        CiphertextMessageType::SenderKey,
+        sender_cert.clone(),
+        sender_key_message.serialized().to_vec(),
+        content_hint,
+        group_id,
+    )?;
+
+    let accepted_sessions: Vec<&SessionRecord> = accepted_sessions.iter().collect();
+    let message = sealed_sender_multi_recipient_encrypt(
+        &accepted_destinations,
+        &accepted_sessions,
+        failures
+            .iter()
+            .map(|(service_id, _)| *service_id)
+            .collect::<Vec<_>>(),
+        &usmc,
+        identity_store,
+        rng,
+    )
+    .await?;
+
+    Ok(MultiRecipientEncryptResult {
+        message: Some(message),
+        failures,
+    })
+}
+
+/// Checks that `destination` can be sent to, pushing its session onto `sessions` if so.
+async fn check_recipient_device(
+    destination: &ProtocolAddress,
+    session_store: &dyn SessionStore,
+    identity_store: &dyn IdentityKeyStore,
+    now: SystemTime,
+    sessions: &mut Vec<SessionRecord>,
+) -> Result<Option<RecipientEncryptionFailure>> {
+    let session = match session_store.load_session(destination).await? {
+        Some(session)
+            if session.has_usable_sender_chain(now, SessionUsabilityRequirements::empty())? =>
+        {
+            session
+        }
+        _ => return Ok(Some(RecipientEncryptionFailure::MissingSession)),
+    };
+    let Some(their_identity) = identity_store.get_identity(destination).await? else {
+        return Ok(Some(RecipientEncryptionFailure::MissingSession));
+    };
+    if !identity_store
+        .is_trusted_identity(destination, &their_identity, Direction::Sending)
+        .await?
+    {
+        return Ok(Some(RecipientEncryptionFailure::UntrustedIdentity));
+    }
+    sessions.push(session);
+    Ok(None)
+}
+
+/// Errors from [`SealedSenderV2SentMessageBuilder::build`].
+#[derive(Debug, displaydoc::Display, thiserror::Error)]
+pub enum SealedSenderV2BuildError {
+    /// recipient {0:?} was added more than once
+    DuplicateRecipient(ServiceId),
+    /// no current session with {0}
+    MissingSession(ProtocolAddress),
+    /// session with {address} has registration ID {found}, expected {expected}
+    RegistrationIdMismatch {
+        address: ProtocolAddress,
+        expected: u32,
+        found: u32,
+    },
+    /// {0}
+    Protocol(#[from] SignalProtocolError),
+}
+
+/// Builds a Sealed Sender v2 sent message from each recipient's list of devices.
+///
+/// Unlike [`sealed_sender_multi_recipient_encrypt`], the caller doesn't have to flatten device
+/// lists into matching address and session slices. Instead, the builder loads each device's
+/// session itself and checks it against the registration ID the caller expects (usually the one
+/// the server last reported), so that a stale device list is reported before the server rejects
+/// the message.
+#[derive(Debug, Default)]
+pub struct SealedSenderV2SentMessageBuilder {
+    recipients: IndexMap<ServiceId, BTreeMap<DeviceId, u32>>,
+    first_duplicate: Option<ServiceId>,
+}
+
+impl SealedSenderV2SentMessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `recipient`, with the expected registration ID for each of their devices.
+    ///
+    /// A recipient with no devices is treated as excluded; see [`Self::exclude_recipient`].
+    pub fn add_recipient(
+        &mut self,
+        recipient: ServiceId,
+        devices: impl IntoIterator<Item = (DeviceId, u32)>,
+    ) -> &mut Self {
+        if self
+            .recipients
+            .insert(recipient, devices.into_iter().collect())
+            .is_some()
+        {
+            self.first_duplicate.get_or_insert(recipient);
+        }
+        self
+    }
+
+    /// Adds `recipient` as excluded: they are listed in the message, so the server knows they
+    /// were deliberately left out, but receive no key material.
+    pub fn exclude_recipient(&mut self, recipient: ServiceId) -> &mut Self {
+        self.add_recipient(recipient, [])
+    }
+
+    /// Encrypts `usmc` for every device that was added.
+    ///
+    /// Fails without producing a message if any device lacks a current session, or if a session's
+    /// registration ID doesn't match the expected one.
+    pub async fn build<R: Rng + CryptoRng>(
+        &self,
+        usmc: &UnidentifiedSenderMessageContent,
+        session_store: &dyn SessionStore,
+        identity_store: &dyn IdentityKeyStore,
+        rng: &mut R,
+    ) -> std::result::Result<Vec<u8>, SealedSenderV2BuildError> {
+        if let Some(duplicate) = self.first_duplicate {
+            return Err(SealedSenderV2BuildError::DuplicateRecipient(duplicate));
+        }
+
+        let mut destinations = vec![];
+        let mut sessions = vec![];
+        let mut excluded_recipients = vec![];
+        for (&recipient, devices) in &self.recipients {
+            if devices.is_empty() {
+                excluded_recipients.push(recipient);
+                continue;
+            }
+            let name = recipient.service_id_string();
+            for (&device_id, &expected_registration_id) in devices {
+                let address = ProtocolAddress::new(name.clone(), device_id);
+                let Some(session) = session_store.load_session(&address).await? else {
+                    return Err(SealedSenderV2BuildError::MissingSession(address));
+                };
+                // Fails if the session has been archived.
+                let Ok(session_registration_id) = session.remote_registration_id() else {
+                    return Err(SealedSenderV2BuildError::MissingSession(address));
+                };
+                if session_registration_id != expected_registration_id {
+                    return Err(SealedSenderV2BuildError::RegistrationIdMismatch {
+                        address,
+                        expected: expected_registration_id,
+                        found: session_registration_id,
+                    });
+                }
+                destinations.push(address);
+                sessions.push(session);
+            }
+        }
+
+        let destinations: Vec<&ProtocolAddress> = destinations.iter().collect();
+        let sessions: Vec<&SessionRecord> = sessions.iter().collect();
+        Ok(sealed_sender_multi_recipient_encrypt_impl(
+            &destinations,
+            &sessions,
+            excluded_recipients,
+            usmc,
+            identity_store,
+            rng,
+        )
+        .await?)
+    }
+}
+
 async fn sealed_sender_multi_recipient_encrypt_impl<
     R: Rng + CryptoRng,
     X: IntoIterator<Item = ServiceId>,
@@ -1294,7 +1599,7 @@ where
     }
 
     let excluded_recipients = excluded_recipients.into_iter();
-    let our_identity = identity_store.get_identity_key_pair().await?;
+    let our_identity = identity_store.get_identity_key_signer().await?;
 
     let m: [u8; sealed_sender_v2::MESSAGE_KEY_LEN] = rng.random();
     let keys = sealed_sender_v2::DerivedKeys::new(&m);
@@ -1419,7 +1724,7 @@ where
         serialized.extend_from_slice(&c_i);
 
         let at_i = sealed_sender_v2::compute_authentication_tag(
-            &our_identity,
+            &*our_identity,
             their_identity,
             Direction::Sending,
             e_pub,
@@ -1730,6 +2035,76 @@ impl<'a> SealedSenderV2SentMessage<'a> {
         self.offset_within_full_message(self.shared_bytes.as_ptr())
             .expect("constructed correctly")
     }
+
+    /// Returns the ReceivedMessage for `recipient`, to be delivered to each of its devices.
+    ///
+    /// This is the concatenation of [`Self::received_message_parts_for_recipient`].
+    pub fn received_message_for_recipient(
+        &self,
+        recipient: &SealedSenderV2SentMessageRecipient<'a>,
+    ) -> Vec<u8> {
+        self.received_message_parts_for_recipient(recipient)
+            .as_ref()
+            .concat()
+    }
+
+    /// Returns the recipients that were explicitly excluded from the message, in order.
+    ///
+    /// These have no devices, and so get no message from [`Self::fan_out`].
+    pub fn excluded_recipients(&self) -> impl Iterator<Item = &ServiceId> {
+        self.recipients
+            .iter()
+            .filter(|(_, recipient)| recipient.devices.is_empty())
+            .map(|(service_id, _)| service_id)
+    }
+
+    /// Splits the message into one ReceivedMessage per recipient device, as a server would.
+    ///
+    /// Messages are produced in the order of [`Self::recipients`]. Unlike [`Self::parse`], this
+    /// rejects a message that lists the same device of a recipient more than once, since that
+    /// device would otherwise get two copies.
+    pub fn fan_out(&self) -> Result<Vec<SealedSenderV2ReceivedMessage>> {
+        let mut messages = Vec::new();
+        for (&service_id, recipient) in &self.recipients {
+            if recipient.devices.is_empty() {
+                continue;
+            }
+            let message = self.received_message_for_recipient(recipient);
+            for (i, &(device_id, registration_id)) in recipient.devices.iter().enumerate() {
+                if recipient.devices[..i]
+                    .iter()
+                    .any(|(earlier, _)| *earlier == device_id)
+                {
+                    return Err(SignalProtocolError::InvalidSealedSenderMessage(format!(
+                        "device {device_id} of {} listed more than once",
+                        service_id.service_id_string()
+                    )));
+                }
+                messages.push(SealedSenderV2ReceivedMessage {
+                    service_id,
+                    device_id,
+                    registration_id,
+                    message: message.clone(),
+                });
+            }
+        }
+        Ok(messages)
+    }
+}
+
+/// One device's copy of a Sealed Sender v2 message, produced by
+/// [`SealedSenderV2SentMessage::fan_out`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedSenderV2ReceivedMessage {
+    /// The recipient the message is for.
+    pub service_id: ServiceId,
+    /// The recipient's device the message is for.
+    pub device_id: DeviceId,
+    /// The registration ID the sender expects the device to have.
+    pub registration_id: u16,
+    /// The serialized ReceivedMessage, which the device can decrypt with
+    /// [`sealed_sender_decrypt`].
+    pub message: Vec<u8>,
 }
 
 /// Decrypt the payload of a sealed-sender message in either the v1 or v2 format.
@@ -1740,7 +2115,7 @@ pub async fn sealed_sender_decrypt_to_usmc(
     ciphertext: &[u8],
     identity_store: &dyn IdentityKeyStore,
 ) -> Result<UnidentifiedSenderMessageContent> {
-    let our_identity = identity_store.get_identity_key_pair().await?;
+    let our_identity = identity_store.get_identity_key_signer().await?;
 
     match UnidentifiedSenderMessage::deserialize(ciphertext)? {
         UnidentifiedSenderMessage::V1 {
@@ -1749,7 +2124,7 @@ pub async fn sealed_sender_decrypt_to_usmc(
             encrypted_message,
         } => {
             let eph_keys = sealed_sender_v1::EphemeralKeys::calculate(
-                &our_identity.into(),
+                &*our_identity,
                 &ephemeral_public,
                 Direction::Receiving,
             )?;
@@ -1774,7 +2149,7 @@ pub async fn sealed_sender_decrypt_to_usmc(
             let static_key = PublicKey::try_from(&message_key_bytes[..])?;
 
             let static_keys = sealed_sender_v1::StaticKeys::calculate(
-                &our_identity,
+                &*our_identity,
                 &static_key,
                 &eph_keys.chain_key,
                 &encrypted_static,
@@ -1814,7 +2189,7 @@ pub async fn sealed_sender_decrypt_to_usmc(
             encrypted_message,
         } => {
             let m = sealed_sender_v2::apply_agreement_xor(
-                &our_identity.into(),
+                &*our_identity,
                 &ephemeral_public,
                 Direction::Receiving,
                 encrypted_message_key,
@@ -1845,7 +2220,7 @@ pub async fn sealed_sender_decrypt_to_usmc(
             let usmc = UnidentifiedSenderMessageContent::deserialize(&message_bytes)?;
 
             let at = sealed_sender_v2::compute_authentication_tag(
-                &our_identity,
+                &*our_identity,
                 &usmc.sender()?.key()?.into(),
                 Direction::Receiving,
                 &ephemeral_public,
@@ -1886,6 +2261,11 @@ impl SealedSenderDecryptionResult {
     pub fn message(&self) -> Result<&[u8]> {
         Ok(self.message.as_ref())
     }
+
+    /// The message with any padding removed, whichever [`PaddingScheme`] the sender used.
+    pub fn unpadded_message(&self) -> Result<&[u8]> {
+        strip_padding(&self.message)
+    }
 }
 
 /// Decrypt a Sealed Sender message `ciphertext` in either the v1 or v2 format, validate its sender
@@ -1910,6 +2290,100 @@ pub async fn sealed_sender_decrypt(
     kyber_pre_key_store: &mut dyn KyberPreKeyStore,
     use_pq_ratchet: ratchet::UsePQRatchet,
 ) -> Result<SealedSenderDecryptionResult> {
+    let mut message = vec![0; ciphertext.len()];
+    let result = sealed_sender_decrypt_into(
+        ciphertext,
+        trust_root,
+        timestamp,
+        local_e164,
+        local_uuid,
+        local_device_id,
+        identity_store,
+        session_store,
+        pre_key_store,
+        signed_pre_key_store,
+        kyber_pre_key_store,
+        &mut message,
+        use_pq_ratchet,
+    )
+    .await?;
+    message.truncate(result.message_len);
+
+    Ok(SealedSenderDecryptionResult {
+        sender_uuid: result.sender_uuid,
+        sender_e164: result.sender_e164,
+        device_id: result.device_id,
+        message,
+    })
+}
+
+/// The result of [`sealed_sender_decrypt_into`]: the sender, and the length of the message written
+/// to the output buffer.
+#[derive(Debug)]
+pub struct SealedSenderDecryptIntoResult {
+    pub sender_uuid: String,
+    pub sender_e164: Option<String>,
+    pub device_id: DeviceId,
+    pub message_len: usize,
+}
+
+/// Like [`sealed_sender_decrypt`], but writes the inner message's plaintext into the start of
+/// `output`.
+///
+/// An `output` buffer as long as `ciphertext` is always big enough. If `output` is too small for
+/// the inner message, this fails before any session state is changed. If this fails for any
+/// reason, `output` is zeroed.
+#[expect(clippy::too_many_arguments)]
+pub async fn sealed_sender_decrypt_into(
+    ciphertext: &[u8],
+    trust_root: &PublicKey,
+    timestamp: Timestamp,
+    local_e164: Option<String>,
+    local_uuid: String,
+    local_device_id: DeviceId,
+    identity_store: &mut dyn IdentityKeyStore,
+    session_store: &mut dyn SessionStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    output: &mut [u8],
+    use_pq_ratchet: ratchet::UsePQRatchet,
+) -> Result<SealedSenderDecryptIntoResult> {
+    let result = sealed_sender_decrypt_into_impl(
+        ciphertext,
+        trust_root,
+        timestamp,
+        local_e164,
+        local_uuid,
+        local_device_id,
+        identity_store,
+        session_store,
+        pre_key_store,
+        signed_pre_key_store,
+        kyber_pre_key_store,
+        output,
+        use_pq_ratchet,
+    )
+    .await;
+    crypto::zero_output_on_error(output, result)
+}
+
+#[expect(clippy::too_many_arguments)]
+async fn sealed_sender_decrypt_into_impl(
+    ciphertext: &[u8],
+    trust_root: &PublicKey,
+    timestamp: Timestamp,
+    local_e164: Option<String>,
+    local_uuid: String,
+    local_device_id: DeviceId,
+    identity_store: &mut dyn IdentityKeyStore,
+    session_store: &mut dyn SessionStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    output: &mut [u8],
+    use_pq_ratchet: ratchet::UsePQRatchet,
+) -> Result<SealedSenderDecryptIntoResult> {
     let usmc = sealed_sender_decrypt_to_usmc(ciphertext, identity_store).await?;
 
     if !usmc.sender()?.validate(trust_root, timestamp)? {
@@ -1936,21 +2410,22 @@ pub async fn sealed_sender_decrypt(
         usmc.sender()?.sender_device_id()?,
     );
 
-    let message = match usmc.msg_type()? {
+    let message_len = match usmc.msg_type()? {
         CiphertextMessageType::Whisper => {
             let ctext = SignalMessage::try_from(usmc.contents()?)?;
-            session_cipher::message_decrypt_signal(
+            session_cipher::message_decrypt_signal_into(
                 &ctext,
                 &remote_address,
                 session_store,
                 identity_store,
+                output,
                 &mut rng,
             )
             .await?
         }
         CiphertextMessageType::PreKey => {
             let ctext = PreKeySignalMessage::try_from(usmc.contents()?)?;
-            session_cipher::message_decrypt_prekey(
+            session_cipher::message_decrypt_prekey_into(
                 &ctext,
                 &remote_address,
                 session_store,
@@ -1958,6 +2433,7 @@ pub async fn sealed_sender_decrypt(
                 pre_key_store,
                 signed_pre_key_store,
                 kyber_pre_key_store,
+                output,
                 &mut rng,
                 use_pq_ratchet,
             )
@@ -1971,11 +2447,11 @@ pub async fn sealed_sender_decrypt(
         }
     };
 
-    Ok(SealedSenderDecryptionResult {
+    Ok(SealedSenderDecryptIntoResult {
         sender_uuid: usmc.sender()?.sender_uuid()?.to_string(),
         sender_e164: usmc.sender()?.sender_e164()?.map(|s| s.to_string()),
         device_id: usmc.sender()?.sender_device_id()?,
-        message,
+        message_len,
     })
 }
 
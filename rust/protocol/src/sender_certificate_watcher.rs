@@ -0,0 +1,233 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+
+use crate::{Result, SenderCertificate, SignalProtocolError};
+
+/// Fetches a new sender certificate from the server, on behalf of a [`SenderCertificateWatcher`].
+#[async_trait(?Send)]
+pub trait SenderCertificateRefresher {
+    async fn refresh_sender_certificate(&mut self) -> Result<SenderCertificate>;
+}
+
+/// Keeps the sender certificate used for sealed sender up to date.
+///
+/// Call [`certificate_for_send`](Self::certificate_for_send) before each sealed sender send
+/// instead of holding on to a certificate. Once the current certificate is within
+/// `refresh_ahead` of expiring, this asks the app's [`SenderCertificateRefresher`] for a new one
+/// and swaps it in. Sends that are already in progress keep the certificate they started with,
+/// since each caller gets its own reference.
+pub struct SenderCertificateWatcher {
+    current: Mutex<Arc<SenderCertificate>>,
+    refresh_ahead: Duration,
+}
+
+impl SenderCertificateWatcher {
+    /// A reasonable `refresh_ahead` to pass to [`new`](Self::new).
+    ///
+    /// Certificates issued by the Signal server are valid for a day.
+    pub const DEFAULT_REFRESH_AHEAD: Duration = Duration::from_secs(60 * 60);
+
+    pub fn new(certificate: SenderCertificate, refresh_ahead: Duration) -> Result<Self> {
+        // Make sure the expiration can be read, so it can't fail later.
+        certificate.expiration()?;
+        Ok(Self {
+            current: Mutex::new(Arc::new(certificate)),
+            refresh_ahead,
+        })
+    }
+
+    /// The current certificate, whether or not it has expired.
+    pub fn current(&self) -> Arc<SenderCertificate> {
+        self.current.lock().expect("not poisoned").clone()
+    }
+
+    /// Whether the current certificate expires within `refresh_ahead` of `now`.
+    pub fn needs_refresh(&self, now: SystemTime) -> bool {
+        expires_at(&self.current()) <= now + self.refresh_ahead
+    }
+
+    /// Replaces the current certificate, unless `certificate` expires no later than it does.
+    ///
+    /// Returns whether the certificate was replaced. Keeping the later certificate means
+    /// overlapping refreshes can't go backwards.
+    pub fn replace(&self, certificate: SenderCertificate) -> Result<bool> {
+        let new_expiration = certificate.expiration()?;
+        let mut current = self.current.lock().expect("not poisoned");
+        if new_expiration <= current.expiration()? {
+            return Ok(false);
+        }
+        *current = Arc::new(certificate);
+        Ok(true)
+    }
+
+    /// Returns a certificate to use for a send at `now`, refreshing it first if needed.
+    ///
+    /// If the refresh fails, the current certificate is still returned as long as it hasn't
+    /// expired yet; the next call will try again.
+    pub async fn certificate_for_send(
+        &self,
+        refresher: &mut dyn SenderCertificateRefresher,
+        now: SystemTime,
+    ) -> Result<Arc<SenderCertificate>> {
+        if !self.needs_refresh(now) {
+            return Ok(self.current());
+        }
+
+        match refresher.refresh_sender_certificate().await {
+            Ok(certificate) => {
+                if expires_at(&certificate) <= now {
+                    log::warn!("refreshed sender certificate has already expired");
+                } else {
+                    self.replace(certificate)?;
+                }
+            }
+            Err(e) => log::warn!("failed to refresh sender certificate: {e}"),
+        }
+
+        let current = self.current();
+        if expires_at(&current) <= now {
+            return Err(SignalProtocolError::InvalidState(
+                "certificate_for_send",
+                "sender certificate has expired and could not be refreshed".to_string(),
+            ));
+        }
+        Ok(current)
+    }
+}
+
+fn expires_at(certificate: &SenderCertificate) -> SystemTime {
+    certificate
+        .expiration()
+        .expect("checked when the certificate was added")
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use futures_util::FutureExt;
+    use rand::rngs::OsRng;
+    use rand::TryRngCore as _;
+
+    use super::*;
+    use crate::{DeviceId, KeyPair, ServerCertificate, Timestamp};
+
+    const START: Duration = Duration::from_secs(1_700_000_000);
+    const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
+    fn certificate_expiring_at(expiration: SystemTime) -> SenderCertificate {
+        let mut rng = OsRng.unwrap_err();
+        let trust_root = KeyPair::generate(&mut rng);
+        let server_key = KeyPair::generate(&mut rng);
+        let server_cert =
+            ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, &mut rng)
+                .expect("valid");
+        let expiration = expiration
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("after epoch");
+        SenderCertificate::new(
+            "9d0652a3-dcc3-4d11-975f-74d61598733f".to_string(),
+            None,
+            KeyPair::generate(&mut rng).public_key,
+            DeviceId::new(1).expect("valid"),
+            Timestamp::from_epoch_millis(expiration.as_millis().try_into().expect("fits")),
+            server_cert,
+            &server_key.private_key,
+            &mut rng,
+        )
+        .expect("valid")
+    }
+
+    struct Refresher {
+        next: Option<Result<SenderCertificate>>,
+        calls: usize,
+    }
+
+    #[async_trait(?Send)]
+    impl SenderCertificateRefresher for Refresher {
+        async fn refresh_sender_certificate(&mut self) -> Result<SenderCertificate> {
+            self.calls += 1;
+            self.next.take().unwrap_or_else(|| {
+                Err(SignalProtocolError::InvalidState(
+                    "refresh_sender_certificate",
+                    "unavailable".to_string(),
+                ))
+            })
+        }
+    }
+
+    #[test]
+    fn refreshes_ahead_of_expiry() -> Result<()> {
+        async {
+            let start = SystemTime::UNIX_EPOCH + START;
+            let original = certificate_expiring_at(start + DAY);
+            let watcher = SenderCertificateWatcher::new(
+                original.clone(),
+                SenderCertificateWatcher::DEFAULT_REFRESH_AHEAD,
+            )?;
+            let mut refresher = Refresher {
+                next: Some(Ok(certificate_expiring_at(start + 2 * DAY))),
+                calls: 0,
+            };
+
+            let in_flight = watcher.certificate_for_send(&mut refresher, start).await?;
+            assert_eq!(refresher.calls, 0);
+            assert_eq!(in_flight.serialized()?, original.serialized()?);
+
+            let almost_expired = start + DAY - Duration::from_secs(60);
+            let refreshed = watcher
+                .certificate_for_send(&mut refresher, almost_expired)
+                .await?;
+            assert_eq!(refresher.calls, 1);
+            assert_ne!(refreshed.serialized()?, original.serialized()?);
+            // The earlier send still has the certificate it started with.
+            assert_eq!(in_flight.serialized()?, original.serialized()?);
+            assert!(!watcher.needs_refresh(almost_expired));
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+
+    #[test]
+    fn failed_refresh() -> Result<()> {
+        async {
+            let start = SystemTime::UNIX_EPOCH + START;
+            let original = certificate_expiring_at(start + DAY);
+            let watcher = SenderCertificateWatcher::new(
+                original.clone(),
+                SenderCertificateWatcher::DEFAULT_REFRESH_AHEAD,
+            )?;
+            let mut refresher = Refresher {
+                next: None,
+                calls: 0,
+            };
+
+            // Still usable until it actually expires.
+            let almost_expired = start + DAY - Duration::from_secs(60);
+            let certificate = watcher
+                .certificate_for_send(&mut refresher, almost_expired)
+                .await?;
+            assert_eq!(certificate.serialized()?, original.serialized()?);
+
+            let expired = start + DAY;
+            assert!(watcher
+                .certificate_for_send(&mut refresher, expired)
+                .await
+                .is_err());
+            assert_eq!(refresher.calls, 2);
+
+            // An older certificate never replaces a newer one.
+            assert!(!watcher.replace(certificate_expiring_at(start))?);
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+}
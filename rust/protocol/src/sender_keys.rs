@@ -212,11 +212,15 @@ impl SenderKeyState {
         self.state.clone()
     }
 
-    pub(crate) fn add_sender_message_key(&mut self, sender_message_key: &SenderMessageKey) {
+    pub(crate) fn add_sender_message_key(
+        &mut self,
+        sender_message_key: &SenderMessageKey,
+        max_message_keys: usize,
+    ) {
         self.state
             .sender_message_keys
             .push(sender_message_key.as_protobuf());
-        while self.state.sender_message_keys.len() > consts::MAX_MESSAGE_KEYS {
+        while self.state.sender_message_keys.len() > max_message_keys {
             self.state.sender_message_keys.remove(0);
         }
     }
@@ -299,6 +303,7 @@ impl SenderKeyRecord {
         chain_key: &[u8],
         signature_key: PublicKey,
         signature_private_key: Option<PrivateKey>,
+        max_states: usize,
     ) {
         let existing_state = self.remove_state(chain_id, signature_key);
 
@@ -320,7 +325,7 @@ impl SenderKeyRecord {
             Some(state) => state,
         };
 
-        while self.states.len() >= consts::MAX_SENDER_KEY_STATES {
+        while self.states.len() >= max_states {
             self.states.pop_back();
         }
 
@@ -395,8 +400,15 @@ mod sender_key_record_add_sender_key_state_tests {
         /// method under test in this module.
         fn add_sender_key_state_record(&mut self, record_key: (PublicKey, u32), chain_key: &[u8]) {
             let (public_key, chain_id) = record_key;
-            self.sender_key_record
-                .add_sender_key_state(1, chain_id, 1, chain_key, public_key, None);
+            self.sender_key_record.add_sender_key_state(
+                1,
+                chain_id,
+                1,
+                chain_key,
+                public_key,
+                None,
+                consts::MAX_SENDER_KEY_STATES,
+            );
         }
 
         fn assert_number_of_states(&self, expected: usize) {
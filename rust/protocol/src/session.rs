@@ -10,10 +10,12 @@ use rand::{CryptoRng, Rng};
 use crate::protocol::CIPHERTEXT_MESSAGE_PRE_KYBER_VERSION;
 use crate::ratchet::{AliceSignalProtocolParameters, BobSignalProtocolParameters};
 use crate::state::GenericSignedPreKey;
+use crate::storage::run_in_transaction;
 use crate::{
     ratchet, CiphertextMessageType, Direction, IdentityKey, IdentityKeyStore, KeyPair,
     KyberPreKeyId, KyberPreKeyStore, PreKeyBundle, PreKeyId, PreKeySignalMessage, PreKeyStore,
-    ProtocolAddress, Result, SessionRecord, SessionStore, SignalProtocolError, SignedPreKeyStore,
+    ProtocolAddress, ProtocolLimits, Result, SessionRecord, SessionStore, SignalProtocolError,
+    SignedPreKeyStore, TransactionalStores, VerifiedPreKeyBundle,
 };
 
 #[derive(Default)]
@@ -51,6 +53,36 @@ pub async fn process_prekey<'a>(
     signed_prekey_store: &dyn SignedPreKeyStore,
     kyber_prekey_store: &dyn KyberPreKeyStore,
     use_pq_ratchet: ratchet::UsePQRatchet,
+) -> Result<(PreKeysUsed, IdentityToSave<'a>)> {
+    process_prekey_with_limits(
+        message,
+        remote_address,
+        session_record,
+        identity_store,
+        pre_key_store,
+        signed_prekey_store,
+        kyber_prekey_store,
+        &ProtocolLimits::default(),
+        SystemTime::now(),
+        use_pq_ratchet,
+    )
+    .await
+}
+
+/// Like [`process_prekey`], but keeps archived session states according to `limits` rather than
+/// the defaults.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn process_prekey_with_limits<'a>(
+    message: &'a PreKeySignalMessage,
+    remote_address: &'a ProtocolAddress,
+    session_record: &mut SessionRecord,
+    identity_store: &dyn IdentityKeyStore,
+    pre_key_store: &dyn PreKeyStore,
+    signed_prekey_store: &dyn SignedPreKeyStore,
+    kyber_prekey_store: &dyn KyberPreKeyStore,
+    limits: &ProtocolLimits,
+    now: SystemTime,
+    use_pq_ratchet: ratchet::UsePQRatchet,
 ) -> Result<(PreKeysUsed, IdentityToSave<'a>)> {
     let their_identity_key = message.identity_key();
 
@@ -71,6 +103,8 @@ pub async fn process_prekey<'a>(
         kyber_prekey_store,
         pre_key_store,
         identity_store,
+        limits.max_archived_states(),
+        now,
         use_pq_ratchet,
     )
     .await?;
@@ -91,11 +125,15 @@ async fn process_prekey_impl(
     kyber_prekey_store: &dyn KyberPreKeyStore,
     pre_key_store: &dyn PreKeyStore,
     identity_store: &dyn IdentityKeyStore,
+    max_archived_states: usize,
+    now: SystemTime,
     use_pq_ratchet: ratchet::UsePQRatchet,
 ) -> Result<PreKeysUsed> {
     if session_record.promote_matching_session(
         message.message_version() as u32,
         &message.base_key().serialize(),
+        max_archived_states,
+        now,
     )? {
         // We've already set up a session for this message, we can exit early.
         return Ok(Default::default());
@@ -146,7 +184,7 @@ async fn process_prekey_impl(
     };
 
     let parameters = BobSignalProtocolParameters::new(
-        identity_store.get_identity_key_pair().await?,
+        identity_store.get_identity_key_signer().await?,
         our_signed_pre_key_pair, // signed pre key
         our_one_time_pre_key_pair,
         our_signed_pre_key_pair, // ratchet key
@@ -162,7 +200,7 @@ async fn process_prekey_impl(
     new_session.set_local_registration_id(identity_store.get_local_registration_id().await?);
     new_session.set_remote_registration_id(message.registration_id());
 
-    session_record.promote_state(new_session);
+    session_record.promote_state(new_session, max_archived_states, now);
 
     let pre_keys_used = PreKeysUsed {
         pre_key_id: message.pre_key_id(),
@@ -171,12 +209,88 @@ async fn process_prekey_impl(
     Ok(pre_keys_used)
 }
 
+/// Like [`process_prekey_bundle`], but with all store accesses made inside a transaction of
+/// `transaction`, so that the new identity and session are saved together or not at all.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_prekey_bundle_in_transaction<R: Rng + CryptoRng>(
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    transaction: &mut dyn TransactionalStores,
+    bundle: &PreKeyBundle,
+    now: SystemTime,
+    csprng: &mut R,
+    use_pq_ratchet: ratchet::UsePQRatchet,
+) -> Result<()> {
+    run_in_transaction(
+        transaction,
+        process_prekey_bundle(
+            remote_address,
+            session_store,
+            identity_store,
+            bundle,
+            now,
+            csprng,
+            use_pq_ratchet,
+        ),
+    )
+    .await
+}
+
 pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
     remote_address: &ProtocolAddress,
     session_store: &mut dyn SessionStore,
     identity_store: &mut dyn IdentityKeyStore,
     bundle: &PreKeyBundle,
     now: SystemTime,
+    csprng: &mut R,
+    use_pq_ratchet: ratchet::UsePQRatchet,
+) -> Result<()> {
+    process_prekey_bundle_impl(
+        remote_address,
+        session_store,
+        identity_store,
+        bundle,
+        true,
+        now,
+        csprng,
+        use_pq_ratchet,
+    )
+    .await
+}
+
+/// Like [`process_prekey_bundle`], but for a bundle whose signatures were already checked by
+/// [`PreKeyBundle::verify_batch`].
+pub async fn process_verified_prekey_bundle<R: Rng + CryptoRng>(
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    bundle: &VerifiedPreKeyBundle,
+    now: SystemTime,
+    csprng: &mut R,
+    use_pq_ratchet: ratchet::UsePQRatchet,
+) -> Result<()> {
+    process_prekey_bundle_impl(
+        remote_address,
+        session_store,
+        identity_store,
+        bundle.bundle(),
+        false,
+        now,
+        csprng,
+        use_pq_ratchet,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_prekey_bundle_impl<R: Rng + CryptoRng>(
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    bundle: &PreKeyBundle,
+    verify_signatures: bool,
+    now: SystemTime,
     mut csprng: &mut R,
     use_pq_ratchet: ratchet::UsePQRatchet,
 ) -> Result<()> {
@@ -191,18 +305,20 @@ pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
         ));
     }
 
-    if !their_identity_key.public_key().verify_signature(
-        &bundle.signed_pre_key_public()?.serialize(),
-        bundle.signed_pre_key_signature()?,
-    ) {
-        return Err(SignalProtocolError::SignatureValidationFailed);
-    }
-
-    if !their_identity_key.public_key().verify_signature(
-        &bundle.kyber_pre_key_public()?.serialize(),
-        bundle.kyber_pre_key_signature()?,
-    ) {
-        return Err(SignalProtocolError::SignatureValidationFailed);
+    if verify_signatures {
+        if !their_identity_key.public_key().verify_signature(
+            &bundle.signed_pre_key_public()?.serialize(),
+            bundle.signed_pre_key_signature()?,
+        ) {
+            return Err(SignalProtocolError::SignatureValidationFailed);
+        }
+
+        if !their_identity_key.public_key().verify_signature(
+            &bundle.kyber_pre_key_public()?.serialize(),
+            bundle.kyber_pre_key_signature()?,
+        ) {
+            return Err(SignalProtocolError::SignatureValidationFailed);
+        }
     }
 
     let mut session_record = session_store
@@ -216,10 +332,10 @@ pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
 
     let their_one_time_prekey_id = bundle.pre_key_id()?;
 
-    let our_identity_key_pair = identity_store.get_identity_key_pair().await?;
+    let our_identity = identity_store.get_identity_key_signer().await?;
 
     let mut parameters = AliceSignalProtocolParameters::new(
-        our_identity_key_pair,
+        our_identity,
         our_base_key_pair,
         *their_identity_key,
         their_signed_prekey,
@@ -254,7 +370,11 @@ pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
         .save_identity(remote_address, their_identity_key)
         .await?;
 
-    session_record.promote_state(session);
+    session_record.promote_state(
+        session,
+        session_store.session_limits().max_archived_states(),
+        now,
+    );
 
     session_store
         .store_session(remote_address, &session_record)
@@ -0,0 +1,390 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Encrypted archives of session state, for moving it to a new device.
+//!
+//! An archive holds the [`SessionRecord`]s, [`SenderKeyRecord`]s, and remote identity keys for a
+//! set of addresses, along with the local identity key they were established with. It is
+//! serialized as:
+//!
+//! ```text
+//! SessionArchive {
+//!     version: u8,
+//!     salt: [u8; 32],
+//!     ciphertext: [u8], // AES-256-GCM-SIV, including the 16-byte tag
+//! }
+//! ```
+//!
+//! The encryption key is derived from the caller's archive key and the salt, and the version
+//! and salt are authenticated along with the contents.
+
+use std::collections::HashSet;
+
+use aes_gcm_siv::{AeadInPlace, Aes256GcmSiv, KeyInit};
+use prost::Message;
+use rand::{CryptoRng, Rng};
+use uuid::Uuid;
+
+use crate::proto::storage::{session_archive_structure, SessionArchiveStructure};
+use crate::{
+    DeviceId, IdentityKey, IdentityKeyStore, ProtocolAddress, Result, SenderKeyRecord,
+    SenderKeyStore, SessionRecord, SessionStore, SignalProtocolError,
+};
+
+/// The current (and only) archive format version.
+pub const SESSION_ARCHIVE_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 32;
+const HEADER_LEN: usize = 1 + SALT_LEN;
+const KDF_LABEL: &[u8] = b"Signal Session Archive";
+
+/// What to do when an archive entry is already present in the destination stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveCollision {
+    /// Leave the existing entry alone.
+    KeepExisting,
+    /// Overwrite the existing entry with the archived one.
+    Replace,
+}
+
+/// What [`import_session_archive`] did.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SessionArchiveImport {
+    /// How many sessions were stored.
+    pub sessions: usize,
+    /// How many sender keys were stored.
+    pub sender_keys: usize,
+    /// How many remote identity keys were stored.
+    pub identities: usize,
+    /// Addresses with an entry that was skipped because the destination already had a different
+    /// one, listed once per skipped entry.
+    pub kept_existing: Vec<ProtocolAddress>,
+}
+
+/// Encrypts the session state for `addresses` and `sender_keys` into a single archive.
+///
+/// The stores can't be enumerated, so the caller lists the addresses to include; entries missing
+/// from the stores are left out of the archive. `archive_key` must be shared with the new device
+/// by some other means.
+pub async fn export_session_archive<R: Rng + CryptoRng>(
+    addresses: &[ProtocolAddress],
+    sender_keys: &[(ProtocolAddress, Uuid)],
+    session_store: &dyn SessionStore,
+    identity_store: &dyn IdentityKeyStore,
+    sender_key_store: &mut dyn SenderKeyStore,
+    archive_key: &[u8; 32],
+    rng: &mut R,
+) -> Result<Vec<u8>> {
+    let mut archive = SessionArchiveStructure {
+        local_identity_key: identity_store
+            .get_identity_key_signer()
+            .await?
+            .public_key()
+            .serialize()
+            .into_vec(),
+        ..Default::default()
+    };
+
+    for address in addresses {
+        if let Some(session) = session_store.load_session(address).await? {
+            archive.sessions.push(session_archive_structure::Session {
+                address: Some(address.into()),
+                record: session.serialize()?,
+            });
+        }
+        if let Some(identity) = identity_store.get_identity(address).await? {
+            archive
+                .identities
+                .push(session_archive_structure::Identity {
+                    address: Some(address.into()),
+                    identity_key: identity.serialize().into_vec(),
+                });
+        }
+    }
+
+    for (sender, distribution_id) in sender_keys {
+        if let Some(record) = sender_key_store
+            .load_sender_key(sender, *distribution_id)
+            .await?
+        {
+            archive
+                .sender_keys
+                .push(session_archive_structure::SenderKey {
+                    address: Some(sender.into()),
+                    distribution_id: distribution_id.as_bytes().to_vec(),
+                    record: record.serialize()?,
+                });
+        }
+    }
+
+    let salt: [u8; SALT_LEN] = rng.random();
+    let mut serialized = Vec::with_capacity(HEADER_LEN);
+    serialized.push(SESSION_ARCHIVE_VERSION);
+    serialized.extend_from_slice(&salt);
+
+    let mut ciphertext = archive.encode_to_vec();
+    archive_cipher(archive_key, &salt)
+        .encrypt_in_place(
+            // There's no nonce because the key is only used once.
+            &aes_gcm_siv::Nonce::default(),
+            // The header is authenticated as associated data.
+            &serialized,
+            &mut ciphertext,
+        )
+        .expect("AES-GCM-SIV encryption should not fail with a just-computed key");
+    serialized.extend_from_slice(&ciphertext);
+    Ok(serialized)
+}
+
+/// Decrypts an archive made by [`export_session_archive`] and saves its contents to the stores.
+///
+/// The archive must have been exported with the same local identity key as `identity_store` has,
+/// since its sessions are bound to that key. Entries the stores already have are handled
+/// according to `on_collision`, except that remote identity keys matching the archived ones are
+/// simply left alone. If a different identity key is kept for an address, its archived session
+/// is skipped too, since it was established with the archived key.
+///
+/// The archive is checked in full before anything is stored.
+pub async fn import_session_archive(
+    archive: &[u8],
+    archive_key: &[u8; 32],
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    sender_key_store: &mut dyn SenderKeyStore,
+    on_collision: ArchiveCollision,
+) -> Result<SessionArchiveImport> {
+    let archive = decrypt_archive(archive, archive_key)?;
+
+    let local_identity_key = identity_store.get_identity_key_signer().await?;
+    if archive.local_identity_key.as_slice() != &*local_identity_key.public_key().serialize() {
+        return Err(SignalProtocolError::InvalidState(
+            "import_session_archive",
+            "archive was exported with a different local identity key".to_string(),
+        ));
+    }
+
+    // Parse everything up front, so a malformed entry doesn't leave a partial import.
+    let sessions = archive
+        .sessions
+        .iter()
+        .map(|session| {
+            Ok((
+                address_from_proto(session.address.as_ref())?,
+                SessionRecord::deserialize(&session.record)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let identities = archive
+        .identities
+        .iter()
+        .map(|identity| {
+            Ok((
+                address_from_proto(identity.address.as_ref())?,
+                IdentityKey::decode(&identity.identity_key)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let sender_keys = archive
+        .sender_keys
+        .iter()
+        .map(|sender_key| {
+            let distribution_id = Uuid::from_slice(&sender_key.distribution_id)
+                .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)?;
+            Ok((
+                address_from_proto(sender_key.address.as_ref())?,
+                distribution_id,
+                SenderKeyRecord::deserialize(&sender_key.record)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut result = SessionArchiveImport::default();
+    let mut kept_identities = HashSet::new();
+
+    for (address, identity) in identities {
+        match identity_store.get_identity(&address).await? {
+            Some(existing) if existing == identity => {}
+            Some(_) if on_collision == ArchiveCollision::KeepExisting => {
+                kept_identities.insert(address.clone());
+                result.kept_existing.push(address);
+            }
+            _ => {
+                identity_store.save_identity(&address, &identity).await?;
+                result.identities += 1;
+            }
+        }
+    }
+
+    for (address, session) in sessions {
+        if kept_identities.contains(&address)
+            || (on_collision == ArchiveCollision::KeepExisting
+                && session_store.load_session(&address).await?.is_some())
+        {
+            result.kept_existing.push(address);
+            continue;
+        }
+        session_store.store_session(&address, &session).await?;
+        result.sessions += 1;
+    }
+
+    for (sender, distribution_id, record) in sender_keys {
+        if on_collision == ArchiveCollision::KeepExisting
+            && sender_key_store
+                .load_sender_key(&sender, distribution_id)
+                .await?
+                .is_some()
+        {
+            result.kept_existing.push(sender);
+            continue;
+        }
+        sender_key_store
+            .store_sender_key(&sender, distribution_id, &record)
+            .await?;
+        result.sender_keys += 1;
+    }
+
+    Ok(result)
+}
+
+fn decrypt_archive(archive: &[u8], archive_key: &[u8; 32]) -> Result<SessionArchiveStructure> {
+    let (&version, rest) = archive
+        .split_first()
+        .ok_or_else(|| SignalProtocolError::InvalidArgument("empty session archive".to_string()))?;
+    if version != SESSION_ARCHIVE_VERSION {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "unrecognized session archive version {version}"
+        )));
+    }
+
+    if rest.len() < SALT_LEN {
+        return Err(SignalProtocolError::InvalidArgument(
+            "session archive is truncated".to_string(),
+        ));
+    }
+    let (salt, ciphertext) = rest.split_at(SALT_LEN);
+
+    let mut plaintext = ciphertext.to_vec();
+    archive_cipher(archive_key, salt.try_into().expect("correct length"))
+        .decrypt_in_place(
+            &aes_gcm_siv::Nonce::default(),
+            &archive[..HEADER_LEN],
+            &mut plaintext,
+        )
+        .map_err(|_| {
+            SignalProtocolError::InvalidArgument(
+                "session archive failed integrity check".to_string(),
+            )
+        })?;
+
+    SessionArchiveStructure::decode(plaintext.as_slice())
+        .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)
+}
+
+fn archive_cipher(archive_key: &[u8; 32], salt: &[u8; SALT_LEN]) -> Aes256GcmSiv {
+    let mut key = [0; 32];
+    hkdf::Hkdf::<sha2::Sha256>::new(Some(salt), archive_key)
+        .expand(KDF_LABEL, &mut key)
+        .expect("valid output length");
+    Aes256GcmSiv::new(&key.into())
+}
+
+impl From<&ProtocolAddress> for session_archive_structure::Address {
+    fn from(address: &ProtocolAddress) -> Self {
+        Self {
+            name: address.name().to_owned(),
+            device_id: address.device_id().into(),
+        }
+    }
+}
+
+fn address_from_proto(
+    address: Option<&session_archive_structure::Address>,
+) -> Result<ProtocolAddress> {
+    let address = address.ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
+    let device_id = DeviceId::try_from(address.device_id).map_err(|_| {
+        SignalProtocolError::InvalidProtocolAddress {
+            name: address.name.clone(),
+            device_id: address.device_id,
+        }
+    })?;
+    Ok(ProtocolAddress::new(address.name.clone(), device_id))
+}
+
+#[cfg(test)]
+mod test {
+    use futures_util::FutureExt;
+    use rand::TryRngCore as _;
+
+    use super::*;
+    use crate::{IdentityKeyPair, InMemSignalProtocolStore};
+
+    async fn import_into(
+        store: &mut InMemSignalProtocolStore,
+        archive: &[u8],
+        archive_key: &[u8; 32],
+    ) -> Result<SessionArchiveImport> {
+        import_session_archive(
+            archive,
+            archive_key,
+            &mut store.session_store,
+            &mut store.identity_store,
+            &mut store.sender_key_store,
+            ArchiveCollision::Replace,
+        )
+        .await
+    }
+
+    #[test]
+    fn rejects_tampering_and_wrong_key() {
+        async {
+            let mut rng = rand::rngs::OsRng.unwrap_err();
+            let identity = IdentityKeyPair::generate(&mut rng);
+            let mut store = InMemSignalProtocolStore::new(identity, 1).expect("valid");
+            let archive_key = [7; 32];
+
+            let archive = export_session_archive(
+                &[],
+                &[],
+                &store.session_store,
+                &store.identity_store,
+                &mut store.sender_key_store,
+                &archive_key,
+                &mut rng,
+            )
+            .await
+            .expect("can export");
+
+            assert_eq!(
+                import_into(&mut store, &archive, &archive_key)
+                    .await
+                    .expect("valid"),
+                SessionArchiveImport::default()
+            );
+            assert!(import_into(&mut store, &archive, &[8; 32]).await.is_err());
+
+            let mut tampered = archive.clone();
+            *tampered.last_mut().expect("not empty") ^= 1;
+            assert!(import_into(&mut store, &tampered, &archive_key)
+                .await
+                .is_err());
+
+            let mut wrong_version = archive.clone();
+            wrong_version[0] = SESSION_ARCHIVE_VERSION + 1;
+            assert!(import_into(&mut store, &wrong_version, &archive_key)
+                .await
+                .is_err());
+
+            // A different local identity can't take over the sessions.
+            let mut other_store =
+                InMemSignalProtocolStore::new(IdentityKeyPair::generate(&mut rng), 1)
+                    .expect("valid");
+            assert!(import_into(&mut other_store, &archive, &archive_key)
+                .await
+                .is_err());
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+}
@@ -8,12 +8,16 @@ use std::time::SystemTime;
 use rand::{CryptoRng, Rng};
 
 use crate::consts::{MAX_FORWARD_JUMPS, MAX_UNACKNOWLEDGED_SESSION_AGE};
+use crate::crypto::zero_output_on_error;
+use crate::padding::{pad_plaintext, strip_padding, PaddingScheme};
 use crate::ratchet::{ChainKey, MessageKeyGenerator, UsePQRatchet};
 use crate::state::{InvalidSessionError, SessionState};
+use crate::storage::run_in_transaction;
 use crate::{
-    session, CiphertextMessage, CiphertextMessageType, Direction, IdentityKeyStore, KeyPair,
-    KyberPayload, KyberPreKeyStore, PreKeySignalMessage, PreKeyStore, ProtocolAddress, PublicKey,
-    Result, SessionRecord, SessionStore, SignalMessage, SignalProtocolError, SignedPreKeyStore,
+    session, CiphertextMessage, CiphertextMessageType, Direction, FrankingKey, FrankingTag,
+    IdentityKeyStore, KeyPair, KyberPayload, KyberPreKeyStore, PreKeySignalMessage, PreKeyStore,
+    ProtocolAddress, ProtocolLimits, PublicKey, Result, SessionRecord, SessionStore, SignalMessage,
+    SignalProtocolError, SignedPreKeyStore, TransactionalStores, FRANKING_KEY_LEN,
 };
 
 pub async fn message_encrypt<R: Rng + CryptoRng>(
@@ -147,6 +151,8 @@ pub async fn message_encrypt<R: Rng + CryptoRng>(
         ));
     }
 
+    session_record.mark_current_session_used(now);
+
     // XXX this could be combined with the above call to the identity store (in a new API)
     identity_store
         .save_identity(remote_address, &their_identity_key)
@@ -158,6 +164,59 @@ pub async fn message_encrypt<R: Rng + CryptoRng>(
     Ok(message)
 }
 
+/// Like [`message_encrypt`], but pads `ptext` according to `padding` first.
+///
+/// The recipient removes the padding with [`message_decrypt_padded`], or by passing the output of
+/// any other decryption function to [`strip_padding`].
+pub async fn message_encrypt_padded<R: Rng + CryptoRng>(
+    ptext: &[u8],
+    padding: &dyn PaddingScheme,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    now: SystemTime,
+    csprng: &mut R,
+) -> Result<CiphertextMessage> {
+    message_encrypt(
+        &pad_plaintext(ptext, padding),
+        remote_address,
+        session_store,
+        identity_store,
+        now,
+        csprng,
+    )
+    .await
+}
+
+/// Like [`message_encrypt`], but makes the message reportable with message franking.
+///
+/// A fresh [`FrankingKey`] is encrypted along with `ptext`, and then used to commit to `ptext`,
+/// `local_address` as the sender, and the serialized ciphertext. The returned [`FrankingTag`] must
+/// be sent alongside the ciphertext, and the recipient must decrypt with
+/// [`message_decrypt_franked`].
+pub async fn message_encrypt_franked<R: Rng + CryptoRng>(
+    ptext: &[u8],
+    local_address: &ProtocolAddress,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    now: SystemTime,
+    csprng: &mut R,
+) -> Result<(CiphertextMessage, FrankingTag)> {
+    let franking_key = FrankingKey::generate(csprng);
+    let message = message_encrypt(
+        &[&franking_key.as_bytes()[..], ptext].concat(),
+        remote_address,
+        session_store,
+        identity_store,
+        now,
+        csprng,
+    )
+    .await?;
+    let tag = franking_key.commit(local_address, message.serialize(), ptext);
+    Ok((message, tag))
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn message_decrypt<R: Rng + CryptoRng>(
     ciphertext: &CiphertextMessage,
@@ -195,6 +254,175 @@ pub async fn message_decrypt<R: Rng + CryptoRng>(
     }
 }
 
+/// Like [`message_decrypt`], but writes the plaintext into the start of `output` and returns its
+/// length.
+///
+/// See [`message_decrypt_signal_into`] and [`message_decrypt_prekey_into`].
+#[allow(clippy::too_many_arguments)]
+pub async fn message_decrypt_into<R: Rng + CryptoRng>(
+    ciphertext: &CiphertextMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    output: &mut [u8],
+    csprng: &mut R,
+    use_pq_ratchet: UsePQRatchet,
+) -> Result<usize> {
+    match ciphertext {
+        CiphertextMessage::SignalMessage(m) => {
+            message_decrypt_signal_into(
+                m,
+                remote_address,
+                session_store,
+                identity_store,
+                output,
+                csprng,
+            )
+            .await
+        }
+        CiphertextMessage::PreKeySignalMessage(m) => {
+            message_decrypt_prekey_into(
+                m,
+                remote_address,
+                session_store,
+                identity_store,
+                pre_key_store,
+                signed_pre_key_store,
+                kyber_pre_key_store,
+                output,
+                csprng,
+                use_pq_ratchet,
+            )
+            .await
+        }
+        _ => Err(SignalProtocolError::InvalidArgument(format!(
+            "message_decrypt_into cannot be used to decrypt {:?} messages",
+            ciphertext.message_type()
+        ))),
+    }
+}
+
+/// Like [`message_decrypt`], but with all store accesses made inside a transaction of
+/// `transaction`, so that a failure (or crash) can't leave a one-time pre-key consumed without the
+/// session that used it being saved.
+#[allow(clippy::too_many_arguments)]
+pub async fn message_decrypt_in_transaction<R: Rng + CryptoRng>(
+    ciphertext: &CiphertextMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    transaction: &mut dyn TransactionalStores,
+    csprng: &mut R,
+    use_pq_ratchet: UsePQRatchet,
+) -> Result<Vec<u8>> {
+    run_in_transaction(
+        transaction,
+        message_decrypt(
+            ciphertext,
+            remote_address,
+            session_store,
+            identity_store,
+            pre_key_store,
+            signed_pre_key_store,
+            kyber_pre_key_store,
+            csprng,
+            use_pq_ratchet,
+        ),
+    )
+    .await
+}
+
+/// Like [`message_decrypt`], but also removes the padding added by [`message_encrypt_padded`],
+/// whichever [`PaddingScheme`] the sender used.
+#[allow(clippy::too_many_arguments)]
+pub async fn message_decrypt_padded<R: Rng + CryptoRng>(
+    ciphertext: &CiphertextMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    csprng: &mut R,
+    use_pq_ratchet: UsePQRatchet,
+) -> Result<Vec<u8>> {
+    let mut ptext = message_decrypt(
+        ciphertext,
+        remote_address,
+        session_store,
+        identity_store,
+        pre_key_store,
+        signed_pre_key_store,
+        kyber_pre_key_store,
+        csprng,
+        use_pq_ratchet,
+    )
+    .await?;
+    let unpadded_len = strip_padding(&ptext)?.len();
+    ptext.truncate(unpadded_len);
+    Ok(ptext)
+}
+
+/// Like [`message_decrypt`], for messages encrypted with [`message_encrypt_franked`].
+///
+/// Returns the plaintext and the [`FrankingKey`] that opens `franking_tag`, which the recipient
+/// keeps in case they want to report the message. Fails if `franking_tag` doesn't match the
+/// decrypted contents, the sender, and `ciphertext`, so that a sender can't deliver a message its
+/// recipient would be unable to report. In that case the session has still advanced past the
+/// message, as for any other message that's rejected after decryption.
+#[allow(clippy::too_many_arguments)]
+pub async fn message_decrypt_franked<R: Rng + CryptoRng>(
+    ciphertext: &CiphertextMessage,
+    franking_tag: &FrankingTag,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    csprng: &mut R,
+    use_pq_ratchet: UsePQRatchet,
+) -> Result<(Vec<u8>, FrankingKey)> {
+    let mut ptext = message_decrypt(
+        ciphertext,
+        remote_address,
+        session_store,
+        identity_store,
+        pre_key_store,
+        signed_pre_key_store,
+        kyber_pre_key_store,
+        csprng,
+        use_pq_ratchet,
+    )
+    .await?;
+    if ptext.len() < FRANKING_KEY_LEN {
+        return Err(SignalProtocolError::InvalidMessage(
+            ciphertext.message_type(),
+            "too short to contain a franking key",
+        ));
+    }
+    let contents = ptext.split_off(FRANKING_KEY_LEN);
+    let franking_key = FrankingKey::try_from(&ptext[..])?;
+    if !franking_tag.verify(
+        &franking_key,
+        remote_address,
+        ciphertext.serialize(),
+        &contents,
+    ) {
+        return Err(SignalProtocolError::InvalidMessage(
+            ciphertext.message_type(),
+            "franking tag does not match message",
+        ));
+    }
+    Ok((contents, franking_key))
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn message_decrypt_prekey<R: Rng + CryptoRng>(
     ciphertext: &PreKeySignalMessage,
@@ -207,13 +435,81 @@ pub async fn message_decrypt_prekey<R: Rng + CryptoRng>(
     csprng: &mut R,
     use_pq_ratchet: UsePQRatchet,
 ) -> Result<Vec<u8>> {
+    let mut ptext = vec![0; ciphertext.message().body().len()];
+    let ptext_len = message_decrypt_prekey_into(
+        ciphertext,
+        remote_address,
+        session_store,
+        identity_store,
+        pre_key_store,
+        signed_pre_key_store,
+        kyber_pre_key_store,
+        &mut ptext,
+        csprng,
+        use_pq_ratchet,
+    )
+    .await?;
+    ptext.truncate(ptext_len);
+    Ok(ptext)
+}
+
+/// Like [`message_decrypt_prekey`], but writes the plaintext into the start of `output` and
+/// returns its length.
+///
+/// `output` must be at least as long as the message body; if it isn't, this fails before touching
+/// any stores. If this fails for any reason, `output` is zeroed.
+#[allow(clippy::too_many_arguments)]
+pub async fn message_decrypt_prekey_into<R: Rng + CryptoRng>(
+    ciphertext: &PreKeySignalMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    output: &mut [u8],
+    csprng: &mut R,
+    use_pq_ratchet: UsePQRatchet,
+) -> Result<usize> {
+    let result = message_decrypt_prekey_into_impl(
+        ciphertext,
+        remote_address,
+        session_store,
+        identity_store,
+        pre_key_store,
+        signed_pre_key_store,
+        kyber_pre_key_store,
+        output,
+        csprng,
+        use_pq_ratchet,
+    )
+    .await;
+    zero_output_on_error(output, result)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn message_decrypt_prekey_into_impl<R: Rng + CryptoRng>(
+    ciphertext: &PreKeySignalMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    pre_key_store: &mut dyn PreKeyStore,
+    signed_pre_key_store: &dyn SignedPreKeyStore,
+    kyber_pre_key_store: &mut dyn KyberPreKeyStore,
+    output: &mut [u8],
+    csprng: &mut R,
+    use_pq_ratchet: UsePQRatchet,
+) -> Result<usize> {
+    check_output_len(ciphertext.message(), output)?;
+    let now = session_store.current_time();
+    let limits = session_store.session_limits();
     let mut session_record = session_store
         .load_session(remote_address)
         .await?
         .unwrap_or_else(SessionRecord::new_fresh);
 
     // Make sure we log the session state if we fail to process the pre-key.
-    let process_prekey_result = session::process_prekey(
+    let process_prekey_result = session::process_prekey_with_limits(
         ciphertext,
         remote_address,
         &mut session_record,
@@ -221,6 +517,8 @@ pub async fn message_decrypt_prekey<R: Rng + CryptoRng>(
         pre_key_store,
         signed_pre_key_store,
         kyber_pre_key_store,
+        &limits,
+        now,
         use_pq_ratchet,
     )
     .await;
@@ -243,13 +541,17 @@ pub async fn message_decrypt_prekey<R: Rng + CryptoRng>(
         }
     };
 
-    let ptext = decrypt_message_with_record(
+    let ptext_len = decrypt_message_with_record(
         remote_address,
         &mut session_record,
         ciphertext.message(),
         CiphertextMessageType::PreKey,
+        &limits,
+        now,
+        output,
         csprng,
     )?;
+    session_record.mark_current_session_used(now);
 
     identity_store
         .save_identity(
@@ -272,7 +574,7 @@ pub async fn message_decrypt_prekey<R: Rng + CryptoRng>(
             .await?;
     }
 
-    Ok(ptext)
+    Ok(ptext_len)
 }
 
 pub async fn message_decrypt_signal<R: Rng + CryptoRng>(
@@ -282,18 +584,73 @@ pub async fn message_decrypt_signal<R: Rng + CryptoRng>(
     identity_store: &mut dyn IdentityKeyStore,
     csprng: &mut R,
 ) -> Result<Vec<u8>> {
+    let mut ptext = vec![0; ciphertext.body().len()];
+    let ptext_len = message_decrypt_signal_into(
+        ciphertext,
+        remote_address,
+        session_store,
+        identity_store,
+        &mut ptext,
+        csprng,
+    )
+    .await?;
+    ptext.truncate(ptext_len);
+    Ok(ptext)
+}
+
+/// Like [`message_decrypt_signal`], but writes the plaintext into the start of `output` and
+/// returns its length.
+///
+/// `output` must be at least as long as the message body; if it isn't, this fails before touching
+/// any stores. If this fails for any reason, including an untrusted identity discovered after
+/// decryption, `output` is zeroed.
+pub async fn message_decrypt_signal_into<R: Rng + CryptoRng>(
+    ciphertext: &SignalMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    output: &mut [u8],
+    csprng: &mut R,
+) -> Result<usize> {
+    let result = message_decrypt_signal_into_impl(
+        ciphertext,
+        remote_address,
+        session_store,
+        identity_store,
+        output,
+        csprng,
+    )
+    .await;
+    zero_output_on_error(output, result)
+}
+
+async fn message_decrypt_signal_into_impl<R: Rng + CryptoRng>(
+    ciphertext: &SignalMessage,
+    remote_address: &ProtocolAddress,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    output: &mut [u8],
+    csprng: &mut R,
+) -> Result<usize> {
+    check_output_len(ciphertext, output)?;
+    let now = session_store.current_time();
+    let limits = session_store.session_limits();
     let mut session_record = session_store
         .load_session(remote_address)
         .await?
         .ok_or_else(|| SignalProtocolError::SessionNotFound(remote_address.clone()))?;
 
-    let ptext = decrypt_message_with_record(
+    let ptext_len = decrypt_message_with_record(
         remote_address,
         &mut session_record,
         ciphertext,
         CiphertextMessageType::Whisper,
+        &limits,
+        now,
+        output,
         csprng,
     )?;
+    session_record.mark_current_session_used(now);
 
     // Why are we performing this check after decryption instead of before?
     let their_identity_key = session_record
@@ -325,7 +682,20 @@ pub async fn message_decrypt_signal<R: Rng + CryptoRng>(
         .store_session(remote_address, &session_record)
         .await?;
 
-    Ok(ptext)
+    Ok(ptext_len)
+}
+
+/// Checks that `output` can hold the plaintext of `ciphertext`, which is never longer than its
+/// body.
+fn check_output_len(ciphertext: &SignalMessage, output: &[u8]) -> Result<()> {
+    if output.len() < ciphertext.body().len() {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "output buffer must be at least {} bytes, but is only {}",
+            ciphertext.body().len(),
+            output.len()
+        )));
+    }
+    Ok(())
 }
 
 fn create_decryption_failure_log(
@@ -424,8 +794,11 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
     record: &mut SessionRecord,
     ciphertext: &SignalMessage,
     original_message_type: CiphertextMessageType,
+    limits: &ProtocolLimits,
+    now: SystemTime,
+    output: &mut [u8],
     csprng: &mut R,
-) -> Result<Vec<u8>> {
+) -> Result<usize> {
     debug_assert!(matches!(
         original_message_type,
         CiphertextMessageType::Whisper | CiphertextMessageType::PreKey
@@ -457,11 +830,13 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
             ciphertext,
             original_message_type,
             remote_address,
+            limits,
+            output,
             csprng,
         );
 
         match result {
-            Ok(ptext) => {
+            Ok(ptext_len) => {
                 log::info!(
                     "decrypted {:?} message from {} with current session state (base key {})",
                     original_message_type,
@@ -471,7 +846,7 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
                         .expect("successful decrypt always has a valid base key"),
                 );
                 record.set_session_state(current_state); // update the state
-                return Ok(ptext);
+                return Ok(ptext_len);
             }
             Err(SignalProtocolError::DuplicatedMessage(_, _)) => {
                 return result;
@@ -520,11 +895,13 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
             ciphertext,
             original_message_type,
             remote_address,
+            limits,
+            output,
             csprng,
         );
 
         match result {
-            Ok(ptext) => {
+            Ok(ptext_len) => {
                 log::info!(
                     "decrypted {:?} message from {} with PREVIOUS session state (base key {})",
                     original_message_type,
@@ -533,7 +910,7 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
                         .sender_ratchet_key_for_logging()
                         .expect("successful decrypt always has a valid base key"),
                 );
-                updated_session = Some((ptext, idx, previous));
+                updated_session = Some((ptext_len, idx, previous));
                 break;
             }
             Err(SignalProtocolError::DuplicatedMessage(_, _)) => {
@@ -546,9 +923,9 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
         }
     }
 
-    if let Some((ptext, idx, updated_session)) = updated_session {
-        record.promote_old_session(idx, updated_session);
-        Ok(ptext)
+    if let Some((ptext_len, idx, updated_session)) = updated_session {
+        record.promote_old_session(idx, updated_session, limits.max_archived_states(), now);
+        Ok(ptext_len)
     } else {
         let previous_state_count = || record.previous_session_states().len();
 
@@ -599,8 +976,10 @@ fn decrypt_message_with_state<R: Rng + CryptoRng>(
     ciphertext: &SignalMessage,
     original_message_type: CiphertextMessageType,
     remote_address: &ProtocolAddress,
+    limits: &ProtocolLimits,
+    output: &mut [u8],
     csprng: &mut R,
-) -> Result<Vec<u8>> {
+) -> Result<usize> {
     // Check for a completely empty or invalid session state before we do anything else.
     let _ = state.root_key().map_err(|_| {
         SignalProtocolError::InvalidMessage(
@@ -626,6 +1005,7 @@ fn decrypt_message_with_state<R: Rng + CryptoRng>(
         original_message_type,
         &chain_key,
         counter,
+        limits.max_message_keys(),
     )?;
     let pqr_key = state
         .pq_ratchet_recv(ciphertext.pq_ratchet())
@@ -664,18 +1044,22 @@ fn decrypt_message_with_state<R: Rng + CryptoRng>(
         ));
     }
 
-    let ptext = match signal_crypto::aes_256_cbc_decrypt(
+    let ptext_len = match signal_crypto::aes_256_cbc_decrypt_into(
         ciphertext.body(),
         message_keys.cipher_key(),
         message_keys.iv(),
+        output,
     ) {
-        Ok(ptext) => ptext,
+        Ok(ptext_len) => ptext_len,
         Err(signal_crypto::DecryptionError::BadKeyOrIv) => {
             log::warn!("{current_or_previous} session state corrupt for {remote_address}",);
             return Err(SignalProtocolError::InvalidSessionStructure(
                 "invalid receiver chain message keys",
             ));
         }
+        Err(signal_crypto::DecryptionError::OutputTooSmall) => {
+            unreachable!("checked by check_output_len")
+        }
         Err(signal_crypto::DecryptionError::BadCiphertext(msg)) => {
             log::warn!("failed to decrypt 1:1 message: {msg}");
             return Err(SignalProtocolError::InvalidMessage(
@@ -687,7 +1071,7 @@ fn decrypt_message_with_state<R: Rng + CryptoRng>(
 
     state.clear_unacknowledged_pre_key_message();
 
-    Ok(ptext)
+    Ok(ptext_len)
 }
 
 fn get_or_create_chain_key<R: Rng + CryptoRng>(
@@ -733,6 +1117,7 @@ fn get_or_create_message_key(
     original_message_type: CiphertextMessageType,
     chain_key: &ChainKey,
     counter: u32,
+    max_message_keys: usize,
 ) -> Result<MessageKeyGenerator> {
     let chain_index = chain_key.index();
 
@@ -770,7 +1155,7 @@ fn get_or_create_message_key(
 
     while chain_key.index() < counter {
         let message_keys = chain_key.message_keys();
-        state.set_message_keys(their_ephemeral, message_keys)?;
+        state.set_message_keys(their_ephemeral, message_keys, max_message_keys)?;
         chain_key = chain_key.next_chain_key();
     }
 
@@ -0,0 +1,210 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Private-key operations that don't require access to the private key itself.
+
+#![warn(missing_docs)]
+
+use rand::CryptoRng;
+
+use crate::{IdentityKey, IdentityKeyPair, IdentityKeyTransition, KeyPair, PublicKey, Result};
+
+/// Performs signing and key agreement with a private key that may not be exportable.
+///
+/// libsignal uses this for the local identity key (see
+/// [`IdentityKeyStore::get_identity_key_signer`]), so that the key can be kept in a platform
+/// keystore such as the Secure Enclave, StrongBox, or a TPM. [`IdentityKeyPair`] and [`KeyPair`]
+/// implement it for keys held in memory.
+///
+/// [`IdentityKeyStore::get_identity_key_signer`]: crate::IdentityKeyStore::get_identity_key_signer
+pub trait PrivateKeySigner {
+    /// Return the public key corresponding to the private key.
+    fn public_key(&self) -> &PublicKey;
+
+    /// Calculate an XEdDSA signature over the concatenation of `message`.
+    ///
+    /// Must be verifiable with [`PublicKey::verify_signature_for_multipart_message`].
+    fn calculate_signature_for_multipart_message(
+        &self,
+        message: &[&[u8]],
+        rng: &mut dyn CryptoRng,
+    ) -> Result<Box<[u8]>>;
+
+    /// Calculate the X25519 shared secret between the private key and `their_key`.
+    fn calculate_agreement(&self, their_key: &PublicKey) -> Result<Box<[u8]>>;
+
+    /// Calculate an XEdDSA signature over `message`.
+    fn calculate_signature(&self, message: &[u8], rng: &mut dyn CryptoRng) -> Result<Box<[u8]>> {
+        self.calculate_signature_for_multipart_message(&[message], rng)
+    }
+
+    /// Return the public key as an identity.
+    fn identity_key(&self) -> IdentityKey {
+        IdentityKey::new(*self.public_key())
+    }
+
+    /// Return the in-memory key pair behind this signer, if there is one.
+    ///
+    /// Signers backed by a platform keystore return `None`.
+    fn as_identity_key_pair(&self) -> Option<&IdentityKeyPair> {
+        None
+    }
+
+    /// Generate a signature claiming that `other` represents the same user as this identity.
+    ///
+    /// Equivalent to [`IdentityKeyPair::sign_alternate_identity`].
+    fn sign_alternate_identity(
+        &self,
+        other: &IdentityKey,
+        rng: &mut dyn CryptoRng,
+    ) -> Result<Box<[u8]>> {
+        crate::identity_key::sign_alternate_identity(self, other, rng)
+    }
+
+    /// Generate a statement that this identity is being replaced by `new_identity`.
+    ///
+    /// Equivalent to [`IdentityKeyPair::sign_identity_transition`].
+    fn sign_identity_transition(
+        &self,
+        new_identity: &IdentityKey,
+        rng: &mut dyn CryptoRng,
+    ) -> Result<IdentityKeyTransition> {
+        IdentityKeyTransition::sign(self, new_identity, rng)
+    }
+}
+
+impl PrivateKeySigner for KeyPair {
+    fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    fn calculate_signature_for_multipart_message(
+        &self,
+        message: &[&[u8]],
+        mut rng: &mut dyn CryptoRng,
+    ) -> Result<Box<[u8]>> {
+        Ok(self
+            .private_key
+            .calculate_signature_for_multipart_message(message, &mut rng)?)
+    }
+
+    fn calculate_agreement(&self, their_key: &PublicKey) -> Result<Box<[u8]>> {
+        Ok(self.private_key.calculate_agreement(their_key)?)
+    }
+}
+
+impl PrivateKeySigner for IdentityKeyPair {
+    fn public_key(&self) -> &PublicKey {
+        IdentityKeyPair::public_key(self)
+    }
+
+    fn calculate_signature_for_multipart_message(
+        &self,
+        message: &[&[u8]],
+        mut rng: &mut dyn CryptoRng,
+    ) -> Result<Box<[u8]>> {
+        Ok(self
+            .private_key()
+            .calculate_signature_for_multipart_message(message, &mut rng)?)
+    }
+
+    fn calculate_agreement(&self, their_key: &PublicKey) -> Result<Box<[u8]>> {
+        Ok(self.private_key().calculate_agreement(their_key)?)
+    }
+
+    fn identity_key(&self) -> IdentityKey {
+        *IdentityKeyPair::identity_key(self)
+    }
+
+    fn as_identity_key_pair(&self) -> Option<&IdentityKeyPair> {
+        Some(self)
+    }
+}
+
+impl<T: PrivateKeySigner + ?Sized> PrivateKeySigner for Box<T> {
+    fn public_key(&self) -> &PublicKey {
+        (**self).public_key()
+    }
+
+    fn calculate_signature_for_multipart_message(
+        &self,
+        message: &[&[u8]],
+        rng: &mut dyn CryptoRng,
+    ) -> Result<Box<[u8]>> {
+        (**self).calculate_signature_for_multipart_message(message, rng)
+    }
+
+    fn calculate_agreement(&self, their_key: &PublicKey) -> Result<Box<[u8]>> {
+        (**self).calculate_agreement(their_key)
+    }
+
+    fn identity_key(&self) -> IdentityKey {
+        (**self).identity_key()
+    }
+
+    fn as_identity_key_pair(&self) -> Option<&IdentityKeyPair> {
+        (**self).as_identity_key_pair()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::OsRng;
+    use rand::TryRngCore as _;
+
+    use super::*;
+
+    #[test]
+    fn software_signer_matches_private_key() {
+        let mut rng = OsRng.unwrap_err();
+        let identity = IdentityKeyPair::generate(&mut rng);
+        let other = KeyPair::generate(&mut rng);
+
+        let signer: Box<dyn PrivateKeySigner> = Box::new(identity);
+        assert_eq!(signer.identity_key(), *identity.identity_key());
+
+        let signature = signer
+            .calculate_signature(b"message", &mut rng)
+            .expect("can sign");
+        assert!(identity
+            .public_key()
+            .verify_signature(b"message", &signature));
+
+        assert_eq!(
+            signer
+                .calculate_agreement(&other.public_key)
+                .expect("can agree"),
+            other
+                .calculate_agreement(identity.public_key())
+                .expect("can agree"),
+        );
+    }
+
+    #[test]
+    fn identity_statements_go_through_signer() {
+        let mut rng = OsRng.unwrap_err();
+        let identity = IdentityKeyPair::generate(&mut rng);
+        let new_identity = IdentityKeyPair::generate(&mut rng);
+
+        let signer: Box<dyn PrivateKeySigner> = Box::new(identity);
+        assert!(signer
+            .as_identity_key_pair()
+            .is_some_and(|pair| pair.identity_key() == identity.identity_key()));
+        assert!(KeyPair::generate(&mut rng).as_identity_key_pair().is_none());
+
+        let transition = signer
+            .sign_identity_transition(new_identity.identity_key(), &mut rng)
+            .expect("can sign");
+        assert!(transition.verify_from(identity.identity_key()));
+
+        let signature = signer
+            .sign_alternate_identity(new_identity.identity_key(), &mut rng)
+            .expect("can sign");
+        assert!(identity
+            .identity_key()
+            .verify_alternate_identity(new_identity.identity_key(), &signature)
+            .expect("valid key"));
+    }
+}
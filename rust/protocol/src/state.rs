@@ -9,9 +9,9 @@ mod prekey;
 mod session;
 mod signed_prekey;
 
-pub use bundle::{PreKeyBundle, PreKeyBundleContent};
+pub use bundle::{PreKeyBundle, PreKeyBundleContent, VerifiedPreKeyBundle};
 pub use kyber_prekey::{KyberPreKeyId, KyberPreKeyRecord};
 pub use prekey::{PreKeyId, PreKeyRecord};
 pub(crate) use session::{InvalidSessionError, SessionState};
-pub use session::{SessionRecord, SessionUsabilityRequirements};
+pub use session::{SessionDiagnostics, SessionRecord, SessionUsabilityRequirements};
 pub use signed_prekey::{GenericSignedPreKey, SignedPreKeyId, SignedPreKeyRecord};
@@ -5,6 +5,8 @@
 
 use std::clone::Clone;
 
+use rand::{CryptoRng, Rng};
+
 use crate::state::{PreKeyId, SignedPreKeyId};
 use crate::{kem, DeviceId, IdentityKey, KyberPreKeyId, PublicKey, Result, SignalProtocolError};
 
@@ -228,4 +230,88 @@ impl PreKeyBundle {
         modify(&mut content);
         content.try_into()
     }
+
+    /// Checks the signed pre-key and Kyber pre-key signatures of every bundle in `bundles`,
+    /// returning one result per bundle, in order.
+    ///
+    /// All of the signatures are first checked together as a batch, which is much faster than
+    /// checking them one at a time when (as is usual) they're all valid. If the batch fails, each
+    /// bundle is checked individually to find the bad ones, which fail with
+    /// [`SignalProtocolError::SignatureValidationFailed`].
+    ///
+    /// Bundles that pass can be given to
+    /// [`process_verified_prekey_bundle`](crate::process_verified_prekey_bundle), which doesn't
+    /// check their signatures again.
+    pub fn verify_batch<R: Rng + CryptoRng>(
+        bundles: Vec<PreKeyBundle>,
+        csprng: &mut R,
+    ) -> Vec<Result<VerifiedPreKeyBundle>> {
+        let signed_messages: Vec<_> = bundles
+            .iter()
+            .map(|bundle| {
+                (
+                    bundle.ec_signed_pre_key.public_key.serialize(),
+                    bundle.kyber_pre_key.public_key.serialize(),
+                )
+            })
+            .collect();
+        let signatures: Vec<_> = bundles
+            .iter()
+            .zip(&signed_messages)
+            .flat_map(|(bundle, (signed_pre_key, kyber_pre_key))| {
+                let identity_key = bundle.identity_key.public_key();
+                [
+                    (
+                        identity_key,
+                        &signed_pre_key[..],
+                        &bundle.ec_signed_pre_key.signature[..],
+                    ),
+                    (
+                        identity_key,
+                        &kyber_pre_key[..],
+                        &bundle.kyber_pre_key.signature[..],
+                    ),
+                ]
+            })
+            .collect();
+
+        let valid: Vec<bool> = if PublicKey::verify_signatures_batch(&signatures, csprng) {
+            vec![true; bundles.len()]
+        } else {
+            signatures
+                .chunks_exact(2)
+                .map(|bundle_signatures| {
+                    bundle_signatures
+                        .iter()
+                        .all(|(key, message, signature)| key.verify_signature(message, signature))
+                })
+                .collect()
+        };
+
+        bundles
+            .into_iter()
+            .zip(valid)
+            .map(|(bundle, valid)| {
+                if valid {
+                    Ok(VerifiedPreKeyBundle(bundle))
+                } else {
+                    Err(SignalProtocolError::SignatureValidationFailed)
+                }
+            })
+            .collect()
+    }
+}
+
+/// A [`PreKeyBundle`] whose signatures have been checked by [`PreKeyBundle::verify_batch`].
+#[derive(Clone)]
+pub struct VerifiedPreKeyBundle(PreKeyBundle);
+
+impl VerifiedPreKeyBundle {
+    pub fn bundle(&self) -> &PreKeyBundle {
+        &self.0
+    }
+
+    pub fn into_bundle(self) -> PreKeyBundle {
+        self.0
+    }
 }
@@ -161,6 +161,8 @@ impl SessionState {
                 local_registration_id: 0,
                 alice_base_key: alice_base_key.serialize().into_vec(),
                 pq_ratchet_state,
+                archived_at: 0,
+                last_used_at: 0,
             },
         }
     }
@@ -458,6 +460,7 @@ impl SessionState {
         &mut self,
         sender: &PublicKey,
         message_keys: MessageKeyGenerator,
+        max_message_keys: usize,
     ) -> Result<(), InvalidSessionError> {
         let chain_and_index = self
             .get_receiver_chain(sender)?
@@ -465,9 +468,7 @@ impl SessionState {
         let mut updated_chain = chain_and_index.0;
         updated_chain.message_keys.insert(0, message_keys.into_pb());
 
-        if updated_chain.message_keys.len() > consts::MAX_MESSAGE_KEYS {
-            updated_chain.message_keys.pop();
-        }
+        updated_chain.message_keys.truncate(max_message_keys);
 
         self.session.receiver_chains[chain_and_index.1] = updated_chain;
 
@@ -567,6 +568,8 @@ impl SessionState {
             local_registration_id: _local_registration_id,
             alice_base_key: _alice_base_key,
             pq_ratchet_state: _pq_ratchet_state,
+            archived_at: _archived_at,
+            last_used_at: _last_used_at,
         } = &self.session;
         // ####### IMPORTANT #######
         // Don't forget to clean up new pending fields.
@@ -639,6 +642,30 @@ impl From<&SessionState> for SessionStructure {
     }
 }
 
+/// A summary of a session's ratchet state, for spotting sessions that may have fallen out of sync.
+///
+/// See [`SessionRecord::diagnostics`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionDiagnostics {
+    /// The version of the current session, or `None` if there is no current session.
+    ///
+    /// All of the fields below except `archived_state_count` describe the current session, and are
+    /// empty or zero if there isn't one.
+    pub session_version: Option<u32>,
+    /// How many messages have been sent on the current sending chain.
+    pub sender_chain_length: u32,
+    /// How many messages have been received on each receiving chain, oldest chain first.
+    pub receiver_chain_lengths: Vec<u32>,
+    /// How many message keys are being kept for skipped messages, across all receiving chains.
+    pub skipped_message_keys: usize,
+    /// Whether the session was started locally and the other party has not yet replied.
+    pub unacknowledged: bool,
+    /// When the session last encrypted or decrypted a message, if known.
+    pub last_used: Option<SystemTime>,
+    /// How many previous sessions are archived in the record.
+    pub archived_state_count: usize,
+}
+
 #[derive(Clone)]
 pub struct SessionRecord {
     current_session: Option<SessionState>,
@@ -680,6 +707,8 @@ impl SessionRecord {
         &mut self,
         version: u32,
         alice_base_key: &[u8],
+        max_archived_states: usize,
+        now: SystemTime,
     ) -> Result<bool, InvalidSessionError> {
         if let Some(current_session) = &self.current_session {
             if current_session.session_version()? == version
@@ -703,7 +732,7 @@ impl SessionRecord {
         }
 
         if let Some((i, state)) = session_to_promote {
-            self.promote_old_session(i, state);
+            self.promote_old_session(i, state, max_archived_states, now);
             return Ok(true);
         }
 
@@ -736,40 +765,134 @@ impl SessionRecord {
         &mut self,
         old_session: usize,
         updated_session: SessionState,
+        max_archived_states: usize,
+        now: SystemTime,
     ) {
         self.previous_sessions.remove(old_session);
-        self.promote_state(updated_session)
+        self.promote_state(updated_session, max_archived_states, now)
     }
 
-    pub(crate) fn promote_state(&mut self, new_state: SessionState) {
-        self.archive_current_state_inner();
+    /// Makes `new_state` the current session, archiving the existing one as of `now` and keeping
+    /// at most `max_archived_states` previous sessions.
+    pub(crate) fn promote_state(
+        &mut self,
+        mut new_state: SessionState,
+        max_archived_states: usize,
+        now: SystemTime,
+    ) {
+        self.archive_current_state_inner(max_archived_states, now);
+        new_state.session.archived_at = 0;
         self.current_session = Some(new_state);
     }
 
     // A non-fallible version of archive_current_state.
     //
     // Returns `true` if there was a session to archive, `false` if not.
-    fn archive_current_state_inner(&mut self) -> bool {
+    fn archive_current_state_inner(&mut self, max_archived_states: usize, now: SystemTime) -> bool {
         if let Some(mut current_session) = self.current_session.take() {
-            if self.previous_sessions.len() >= consts::ARCHIVED_STATES_MAX_LENGTH {
-                self.previous_sessions.pop();
-            }
             current_session.clear_unacknowledged_pre_key_message();
+            current_session.session.archived_at = now
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |age| age.as_secs());
             self.previous_sessions
                 .insert(0, current_session.session.encode_to_vec());
+            self.previous_sessions.truncate(max_archived_states);
             true
         } else {
             false
         }
     }
 
+    /// Archives the current session state, recording the current system time as when it was
+    /// archived for [`prune`](Self::prune).
     pub fn archive_current_state(&mut self) -> Result<(), SignalProtocolError> {
-        if !self.archive_current_state_inner() {
+        self.archive_current_state_at(SystemTime::now())
+    }
+
+    /// Like [`archive_current_state`](Self::archive_current_state), but records `now` as when the
+    /// state was archived.
+    pub fn archive_current_state_at(&mut self, now: SystemTime) -> Result<(), SignalProtocolError> {
+        if !self.archive_current_state_inner(consts::ARCHIVED_STATES_MAX_LENGTH, now) {
             log::info!("Skipping archive, current session state is fresh");
         }
         Ok(())
     }
 
+    /// Removes archived session states that were archived before `older_than`, then all but the
+    /// `keep_at_most` most recently archived states that remain.
+    ///
+    /// Messages that could only have been decrypted by a removed state will fail to decrypt.
+    /// States archived by versions of this library that didn't record when they were archived
+    /// (as well as any that can't be parsed) are treated as older than any `older_than`. The
+    /// current session state is never removed.
+    ///
+    /// Returns the number of states removed.
+    pub fn prune(&mut self, older_than: SystemTime, keep_at_most: usize) -> usize {
+        let original_len = self.previous_sessions.len();
+        self.previous_sessions.retain(|bytes| {
+            SessionStructure::decode(&bytes[..]).is_ok_and(|state| {
+                state.archived_at != 0
+                    && SystemTime::UNIX_EPOCH + Duration::from_secs(state.archived_at) >= older_than
+            })
+        });
+        // Archived states are ordered newest first.
+        self.previous_sessions.truncate(keep_at_most);
+        original_len - self.previous_sessions.len()
+    }
+
+    /// Removes all but the `keep_at_most` most recently skipped message keys from each receiving
+    /// chain, in the current session state and in every archived state.
+    ///
+    /// Out-of-order messages whose keys are removed will fail to decrypt if they arrive later.
+    /// Archived states that can't be parsed are left alone; see [`compact`](Self::compact).
+    ///
+    /// Returns the number of keys removed.
+    pub fn prune_skipped_message_keys(&mut self, keep_at_most: usize) -> usize {
+        fn truncate_chains(session: &mut SessionStructure, keep_at_most: usize) -> usize {
+            session
+                .receiver_chains
+                .iter_mut()
+                .map(|chain| {
+                    let original_len = chain.message_keys.len();
+                    // Skipped keys are ordered newest first.
+                    chain.message_keys.truncate(keep_at_most);
+                    original_len - chain.message_keys.len()
+                })
+                .sum()
+        }
+
+        let mut removed = self
+            .current_session
+            .as_mut()
+            .map_or(0, |state| truncate_chains(&mut state.session, keep_at_most));
+        for bytes in &mut self.previous_sessions {
+            let Ok(mut state) = SessionStructure::decode(&bytes[..]) else {
+                continue;
+            };
+            let removed_here = truncate_chains(&mut state, keep_at_most);
+            if removed_here != 0 {
+                *bytes = state.encode_to_vec();
+                removed += removed_here;
+            }
+        }
+        removed
+    }
+
+    /// Rewrites the archived session states in their most compact form.
+    ///
+    /// States that can't be parsed are removed, since they could never be used to decrypt a
+    /// message, and fields not understood by this version of the library are dropped.
+    pub fn compact(&mut self) {
+        self.previous_sessions.retain_mut(|bytes| {
+            let Ok(state) = SessionStructure::decode(&bytes[..]) else {
+                log::warn!("removing archived session state that failed to parse");
+                return false;
+            };
+            *bytes = state.encode_to_vec();
+            true
+        });
+    }
+
     pub fn serialize(&self) -> Result<Vec<u8>, SignalProtocolError> {
         let record = RecordStructure {
             current_session: self.current_session.as_ref().map(|s| s.into()),
@@ -778,6 +901,55 @@ impl SessionRecord {
         Ok(record.encode_to_vec())
     }
 
+    /// Summarizes the state of the ratchet, so that apps can detect sessions that are likely out
+    /// of sync (such as one with many skipped messages that hasn't been used in a long time) and
+    /// decide to re-establish them.
+    pub fn diagnostics(&self) -> Result<SessionDiagnostics, SignalProtocolError> {
+        let archived_state_count = self.previous_sessions.len();
+        let Some(state) = &self.current_session else {
+            return Ok(SessionDiagnostics {
+                session_version: None,
+                sender_chain_length: 0,
+                receiver_chain_lengths: vec![],
+                skipped_message_keys: 0,
+                unacknowledged: false,
+                last_used: None,
+                archived_state_count,
+            });
+        };
+        let session = &state.session;
+
+        let chain_length = |chain: &session_structure::Chain| {
+            chain
+                .chain_key
+                .as_ref()
+                .map_or(0, |chain_key| chain_key.index)
+        };
+        Ok(SessionDiagnostics {
+            session_version: Some(state.session_version()?),
+            sender_chain_length: session.sender_chain.as_ref().map_or(0, chain_length),
+            receiver_chain_lengths: session.receiver_chains.iter().map(chain_length).collect(),
+            skipped_message_keys: session
+                .receiver_chains
+                .iter()
+                .map(|chain| chain.message_keys.len())
+                .sum(),
+            unacknowledged: session.pending_pre_key.is_some(),
+            last_used: (session.last_used_at != 0)
+                .then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(session.last_used_at)),
+            archived_state_count,
+        })
+    }
+
+    /// Records that the current session was used to encrypt or decrypt a message at `now`.
+    pub(crate) fn mark_current_session_used(&mut self, now: SystemTime) {
+        if let Some(state) = &mut self.current_session {
+            state.session.last_used_at = now
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs());
+        }
+    }
+
     pub fn current_pq_state(&self) -> Option<&spqr::SerializedState> {
         self.current_session.as_ref().map(|s| s.pq_ratchet_state())
     }
@@ -3,18 +3,23 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-//! Interfaces in [traits] and reference implementations in [inmem] for various mutable stores.
+//! Interfaces in [traits] and reference implementations in [inmem] for various mutable stores,
+//! plus [encrypted] for encrypting records at rest.
 
 #![warn(missing_docs)]
 
+mod encrypted;
 mod inmem;
 mod traits;
 
+pub use encrypted::{EncryptedStore, PreKeyBlobStore, SessionBlobStore, ENCRYPTED_RECORD_VERSION};
 pub use inmem::{
     InMemIdentityKeyStore, InMemKyberPreKeyStore, InMemPreKeyStore, InMemSenderKeyStore,
     InMemSessionStore, InMemSignalProtocolStore, InMemSignedPreKeyStore,
 };
+pub(crate) use traits::run_in_transaction;
 pub use traits::{
-    Direction, IdentityChange, IdentityKeyStore, KyberPreKeyStore, PreKeyStore, ProtocolStore,
-    SenderKeyStore, SessionStore, SignedPreKeyStore,
+    Direction, IdentityChange, IdentityKeyStore, KyberPreKeyStore, PreKeyInventoryStore,
+    PreKeyStore, ProtocolStore, SenderKeyStore, SessionStore, SignedPreKeyStore,
+    TransactionalStores,
 };
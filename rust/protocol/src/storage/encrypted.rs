@@ -0,0 +1,369 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Encryption at rest for session and pre-key records.
+//!
+//! [`EncryptedStore`] implements [`SessionStore`] and [`PreKeyStore`] on top of a backend that
+//! only ever sees encrypted blobs, for platforms where the database holding them isn't encrypted
+//! as a whole. Each blob is serialized as:
+//!
+//! ```text
+//! EncryptedRecord {
+//!     version: u8,
+//!     ciphertext: [u8], // AES-256-GCM-SIV, including the 16-byte tag
+//! }
+//! ```
+//!
+//! The nonce is derived from the record's address or ID, which is also authenticated along with
+//! the version, so a blob can't be moved to a different entry without failing to decrypt.
+//! Because AES-GCM-SIV is resistant to nonce reuse, repeatedly overwriting the same entry only
+//! reveals whether its contents are unchanged.
+
+use aes_gcm_siv::{AeadInPlace, Aes256GcmSiv, KeyInit};
+use async_trait::async_trait;
+
+use crate::crypto::hmac_sha256;
+use crate::storage::traits::{PreKeyStore, SessionStore};
+use crate::{
+    PreKeyId, PreKeyRecord, ProtocolAddress, ProtocolLimits, Result, SessionRecord,
+    SignalProtocolError,
+};
+
+/// The current (and only) encrypted record format version.
+pub const ENCRYPTED_RECORD_VERSION: u8 = 1;
+
+const KDF_LABEL: &[u8] = b"Signal Encrypted Record Store";
+const SESSION_LABEL: &[u8] = b"session";
+const PRE_KEY_LABEL: &[u8] = b"pre-key";
+const NONCE_LEN: usize = 12;
+
+/// Stores encrypted session records for an [`EncryptedStore`].
+#[async_trait(?Send)]
+pub trait SessionBlobStore {
+    /// Look up the blob stored for `address`.
+    async fn load_session_blob(&self, address: &ProtocolAddress) -> Result<Option<Vec<u8>>>;
+
+    /// Set the entry for `address` to `blob`.
+    async fn store_session_blob(&mut self, address: &ProtocolAddress, blob: &[u8]) -> Result<()>;
+
+    /// The limits for the [`EncryptedStore`] to report from [`SessionStore::session_limits`].
+    fn session_limits(&self) -> ProtocolLimits {
+        ProtocolLimits::default()
+    }
+}
+
+/// Stores encrypted pre-key records for an [`EncryptedStore`].
+#[async_trait(?Send)]
+pub trait PreKeyBlobStore {
+    /// Look up the blob stored for `prekey_id`.
+    async fn load_pre_key_blob(&self, prekey_id: PreKeyId) -> Result<Option<Vec<u8>>>;
+
+    /// Set the entry for `prekey_id` to `blob`.
+    async fn store_pre_key_blob(&mut self, prekey_id: PreKeyId, blob: &[u8]) -> Result<()>;
+
+    /// Remove the entry for `prekey_id`.
+    async fn remove_pre_key_blob(&mut self, prekey_id: PreKeyId) -> Result<()>;
+}
+
+/// Encrypts records with a caller-provided key before handing them to `inner`.
+///
+/// Implements [`SessionStore`] if `inner` is a [`SessionBlobStore`], and [`PreKeyStore`] if it is
+/// a [`PreKeyBlobStore`].
+pub struct EncryptedStore<S> {
+    inner: S,
+    cipher: Aes256GcmSiv,
+    nonce_key: [u8; 32],
+}
+
+impl<S> EncryptedStore<S> {
+    /// Wrap `inner`, encrypting everything stored in it with keys derived from `storage_key`.
+    pub fn new(inner: S, storage_key: &[u8; 32]) -> Self {
+        let mut keys = [0; 64];
+        hkdf::Hkdf::<sha2::Sha256>::new(None, storage_key)
+            .expand(KDF_LABEL, &mut keys)
+            .expect("valid output length");
+        let (cipher_key, nonce_key) = keys.split_at(32);
+        let cipher_key: [u8; 32] = cipher_key.try_into().expect("correct length");
+        Self {
+            inner,
+            cipher: Aes256GcmSiv::new(&cipher_key.into()),
+            nonce_key: nonce_key.try_into().expect("correct length"),
+        }
+    }
+
+    /// The wrapped store.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwrap the store, discarding the keys.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn encrypt(&self, entry: &[u8], record: Vec<u8>) -> Vec<u8> {
+        let (nonce, associated_data) = self.nonce_and_associated_data(entry);
+        let mut ciphertext = record;
+        self.cipher
+            .encrypt_in_place(&nonce.into(), &associated_data, &mut ciphertext)
+            .expect("AES-GCM-SIV encryption should not fail with a valid key");
+
+        let mut blob = Vec::with_capacity(1 + ciphertext.len());
+        blob.push(ENCRYPTED_RECORD_VERSION);
+        blob.extend_from_slice(&ciphertext);
+        blob
+    }
+
+    fn decrypt(&self, entry: &[u8], blob: &[u8]) -> Result<Vec<u8>> {
+        let Some((&version, ciphertext)) = blob.split_first() else {
+            return Err(SignalProtocolError::InvalidState(
+                "decrypt_record",
+                "stored record is empty".to_string(),
+            ));
+        };
+        if version != ENCRYPTED_RECORD_VERSION {
+            return Err(SignalProtocolError::InvalidState(
+                "decrypt_record",
+                format!("unrecognized encrypted record version {version}"),
+            ));
+        }
+
+        let (nonce, associated_data) = self.nonce_and_associated_data(entry);
+        let mut plaintext = ciphertext.to_vec();
+        self.cipher
+            .decrypt_in_place(&nonce.into(), &associated_data, &mut plaintext)
+            .map_err(|_| {
+                SignalProtocolError::InvalidState(
+                    "decrypt_record",
+                    "stored record failed integrity check".to_string(),
+                )
+            })?;
+        Ok(plaintext)
+    }
+
+    fn nonce_and_associated_data(&self, entry: &[u8]) -> ([u8; NONCE_LEN], Vec<u8>) {
+        let mut associated_data = Vec::with_capacity(1 + entry.len());
+        associated_data.push(ENCRYPTED_RECORD_VERSION);
+        associated_data.extend_from_slice(entry);
+        let nonce = hmac_sha256(&self.nonce_key, &associated_data)[..NONCE_LEN]
+            .try_into()
+            .expect("correct length");
+        (nonce, associated_data)
+    }
+}
+
+fn session_entry(address: &ProtocolAddress) -> Vec<u8> {
+    let name = address.name().as_bytes();
+    [
+        SESSION_LABEL,
+        &u32::try_from(name.len())
+            .expect("address names are short")
+            .to_be_bytes(),
+        name,
+        &[u8::from(address.device_id())],
+    ]
+    .concat()
+}
+
+fn pre_key_entry(prekey_id: PreKeyId) -> Vec<u8> {
+    [PRE_KEY_LABEL, &u32::from(prekey_id).to_be_bytes()].concat()
+}
+
+#[async_trait(?Send)]
+impl<S: SessionBlobStore> SessionStore for EncryptedStore<S> {
+    async fn load_session(&self, address: &ProtocolAddress) -> Result<Option<SessionRecord>> {
+        let Some(blob) = self.inner.load_session_blob(address).await? else {
+            return Ok(None);
+        };
+        let record = self.decrypt(&session_entry(address), &blob)?;
+        Ok(Some(SessionRecord::deserialize(&record)?))
+    }
+
+    async fn store_session(
+        &mut self,
+        address: &ProtocolAddress,
+        record: &SessionRecord,
+    ) -> Result<()> {
+        let blob = self.encrypt(&session_entry(address), record.serialize()?);
+        self.inner.store_session_blob(address, &blob).await
+    }
+
+    fn session_limits(&self) -> ProtocolLimits {
+        self.inner.session_limits()
+    }
+}
+
+#[async_trait(?Send)]
+impl<S: PreKeyBlobStore> PreKeyStore for EncryptedStore<S> {
+    async fn get_pre_key(&self, prekey_id: PreKeyId) -> Result<PreKeyRecord> {
+        let blob = self
+            .inner
+            .load_pre_key_blob(prekey_id)
+            .await?
+            .ok_or(SignalProtocolError::InvalidPreKeyId)?;
+        let record = self.decrypt(&pre_key_entry(prekey_id), &blob)?;
+        PreKeyRecord::deserialize(&record)
+    }
+
+    async fn save_pre_key(&mut self, prekey_id: PreKeyId, record: &PreKeyRecord) -> Result<()> {
+        let blob = self.encrypt(&pre_key_entry(prekey_id), record.serialize()?);
+        self.inner.store_pre_key_blob(prekey_id, &blob).await
+    }
+
+    async fn remove_pre_key(&mut self, prekey_id: PreKeyId) -> Result<()> {
+        self.inner.remove_pre_key_blob(prekey_id).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use assert_matches::assert_matches;
+    use futures_util::FutureExt as _;
+    use rand::rngs::OsRng;
+    use rand::TryRngCore as _;
+
+    use super::*;
+    use crate::{DeviceId, KeyPair};
+
+    #[derive(Default)]
+    struct Blobs {
+        sessions: HashMap<ProtocolAddress, Vec<u8>>,
+        pre_keys: HashMap<PreKeyId, Vec<u8>>,
+        limits: ProtocolLimits,
+    }
+
+    #[async_trait(?Send)]
+    impl SessionBlobStore for Blobs {
+        async fn load_session_blob(&self, address: &ProtocolAddress) -> Result<Option<Vec<u8>>> {
+            Ok(self.sessions.get(address).cloned())
+        }
+
+        async fn store_session_blob(
+            &mut self,
+            address: &ProtocolAddress,
+            blob: &[u8],
+        ) -> Result<()> {
+            self.sessions.insert(address.clone(), blob.to_vec());
+            Ok(())
+        }
+
+        fn session_limits(&self) -> ProtocolLimits {
+            self.limits
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl PreKeyBlobStore for Blobs {
+        async fn load_pre_key_blob(&self, prekey_id: PreKeyId) -> Result<Option<Vec<u8>>> {
+            Ok(self.pre_keys.get(&prekey_id).cloned())
+        }
+
+        async fn store_pre_key_blob(&mut self, prekey_id: PreKeyId, blob: &[u8]) -> Result<()> {
+            self.pre_keys.insert(prekey_id, blob.to_vec());
+            Ok(())
+        }
+
+        async fn remove_pre_key_blob(&mut self, prekey_id: PreKeyId) -> Result<()> {
+            self.pre_keys.remove(&prekey_id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn round_trip_and_tampering() {
+        async {
+            let mut rng = OsRng.unwrap_err();
+            let mut store = EncryptedStore::new(Blobs::default(), &[7; 32]);
+
+            let key_pair = KeyPair::generate(&mut rng);
+            let record = PreKeyRecord::new(1.into(), &key_pair);
+            store
+                .save_pre_key(1.into(), &record)
+                .await
+                .expect("can save");
+            store
+                .save_pre_key(2.into(), &record)
+                .await
+                .expect("can save");
+            assert_eq!(
+                store
+                    .get_pre_key(1.into())
+                    .await
+                    .expect("can load")
+                    .serialize()
+                    .expect("can serialize"),
+                record.serialize().expect("can serialize"),
+            );
+
+            let private_key = key_pair.private_key.serialize();
+            let blob = &store.inner().pre_keys[&PreKeyId::from(1)];
+            assert!(!blob
+                .windows(private_key.len())
+                .any(|window| window == private_key.as_slice()));
+            assert_ne!(blob, &store.inner().pre_keys[&PreKeyId::from(2)]);
+
+            // A blob moved to another entry doesn't decrypt.
+            let moved = blob.clone();
+            store.inner.pre_keys.insert(2.into(), moved);
+            assert_matches!(
+                store.get_pre_key(2.into()).await,
+                Err(SignalProtocolError::InvalidState(_, _))
+            );
+
+            // Nor does one read with the wrong key.
+            let store = EncryptedStore::new(store.into_inner(), &[8; 32]);
+            assert_matches!(
+                store.get_pre_key(1.into()).await,
+                Err(SignalProtocolError::InvalidState(_, _))
+            );
+        }
+        .now_or_never()
+        .expect("sync");
+    }
+
+    #[test]
+    fn sessions() {
+        async {
+            let mut store = EncryptedStore::new(Blobs::default(), &[7; 32]);
+            let alice = ProtocolAddress::new("alice".to_owned(), DeviceId::new(1).unwrap());
+            let alice_2 = ProtocolAddress::new("alice".to_owned(), DeviceId::new(2).unwrap());
+
+            assert!(store.load_session(&alice).await.expect("valid").is_none());
+
+            let record = SessionRecord::new_fresh();
+            store
+                .store_session(&alice, &record)
+                .await
+                .expect("can store");
+            assert!(store.load_session(&alice).await.expect("valid").is_some());
+
+            let blob = store.inner().sessions[&alice].clone();
+            store.inner.sessions.insert(alice_2.clone(), blob);
+            assert_matches!(
+                store.load_session(&alice_2).await,
+                Err(SignalProtocolError::InvalidState(_, _))
+            );
+        }
+        .now_or_never()
+        .expect("sync");
+    }
+
+    #[test]
+    fn session_limits_come_from_inner_store() {
+        let limits = ProtocolLimits::default()
+            .with_max_message_keys(10)
+            .expect("valid");
+        let store = EncryptedStore::new(
+            Blobs {
+                limits,
+                ..Blobs::default()
+            },
+            &[7; 32],
+        );
+        assert_eq!(store.session_limits(), limits);
+    }
+}
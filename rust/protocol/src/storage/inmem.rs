@@ -8,16 +8,17 @@
 //! These implementations are purely in-memory, and therefore most likely useful for testing.
 
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
 
 use async_trait::async_trait;
 use uuid::Uuid;
 
 use crate::storage::traits::{self, IdentityChange};
 use crate::{
-    IdentityKey, IdentityKeyPair, KyberPreKeyId, KyberPreKeyRecord, PreKeyId, PreKeyRecord,
-    ProtocolAddress, Result, SenderKeyRecord, SessionRecord, SignalProtocolError, SignedPreKeyId,
-    SignedPreKeyRecord,
+    IdentityKey, IdentityKeyPair, IdentityKeyTransition, KyberPreKeyId, KyberPreKeyRecord,
+    PreKeyId, PreKeyRecord, PrivateKeySigner, ProtocolAddress, ProtocolLimits, Result,
+    SenderKeyRecord, SessionRecord, SignalProtocolError, SignedPreKeyId, SignedPreKeyRecord,
 };
 
 /// Reference implementation of [traits::IdentityKeyStore].
@@ -26,6 +27,7 @@ pub struct InMemIdentityKeyStore {
     key_pair: IdentityKeyPair,
     registration_id: u32,
     known_keys: HashMap<ProtocolAddress, IdentityKey>,
+    verified: HashSet<ProtocolAddress>,
 }
 
 impl InMemIdentityKeyStore {
@@ -38,12 +40,30 @@ impl InMemIdentityKeyStore {
             key_pair,
             registration_id,
             known_keys: HashMap::new(),
+            verified: HashSet::new(),
         }
     }
 
     /// Clear the mapping of known keys.
     pub fn reset(&mut self) {
         self.known_keys.clear();
+        self.verified.clear();
+    }
+
+    /// Mark the identity currently known for `address` as verified by the user, or not.
+    ///
+    /// Saving a different identity for `address` clears this, but a verified transition keeps it.
+    pub fn set_verified(&mut self, address: &ProtocolAddress, verified: bool) {
+        if verified {
+            self.verified.insert(address.clone());
+        } else {
+            self.verified.remove(address);
+        }
+    }
+
+    /// Whether the identity currently known for `address` was marked as verified.
+    pub fn is_verified(&self, address: &ProtocolAddress) -> bool {
+        self.verified.contains(address)
     }
 }
 
@@ -70,11 +90,29 @@ impl traits::IdentityKeyStore for InMemIdentityKeyStore {
             Some(k) if k == identity => Ok(IdentityChange::NewOrUnchanged),
             Some(_k) => {
                 self.known_keys.insert(address.clone(), *identity);
+                self.verified.remove(address);
                 Ok(IdentityChange::ReplacedExisting)
             }
         }
     }
 
+    async fn save_identity_transition(
+        &mut self,
+        address: &ProtocolAddress,
+        transition: &IdentityKeyTransition,
+    ) -> Result<IdentityChange> {
+        if self.known_keys.get(address) != Some(transition.old_identity()) {
+            return Err(SignalProtocolError::UntrustedIdentity(address.clone()));
+        }
+        if !transition.verify() {
+            return Err(SignalProtocolError::SignatureValidationFailed);
+        }
+        // Unlike save_identity, this leaves the verified flag alone.
+        self.known_keys
+            .insert(address.clone(), *transition.new_identity());
+        Ok(IdentityChange::RotatedVerified)
+    }
+
     async fn is_trusted_identity(
         &self,
         address: &ProtocolAddress,
@@ -248,6 +286,8 @@ impl traits::KyberPreKeyStore for InMemKyberPreKeyStore {
 #[derive(Clone)]
 pub struct InMemSessionStore {
     sessions: HashMap<ProtocolAddress, SessionRecord>,
+    limits: ProtocolLimits,
+    fixed_time: Option<SystemTime>,
 }
 
 impl InMemSessionStore {
@@ -255,9 +295,26 @@ impl InMemSessionStore {
     pub fn new() -> Self {
         Self {
             sessions: HashMap::new(),
+            limits: ProtocolLimits::default(),
+            fixed_time: None,
         }
     }
 
+    /// Changes the limits returned by [`SessionStore::session_limits`].
+    ///
+    /// [`SessionStore::session_limits`]: crate::SessionStore::session_limits
+    pub fn set_limits(&mut self, limits: ProtocolLimits) {
+        self.limits = limits;
+    }
+
+    /// Fixes the time returned by [`SessionStore::current_time`], or goes back to the system clock
+    /// if `None`.
+    ///
+    /// [`SessionStore::current_time`]: crate::SessionStore::current_time
+    pub fn set_current_time(&mut self, now: Option<SystemTime>) {
+        self.fixed_time = now;
+    }
+
     /// Bulk version of [`SessionStore::load_session`].
     ///
     /// Useful for [crate::sealed_sender_multi_recipient_encrypt].
@@ -301,6 +358,14 @@ impl traits::SessionStore for InMemSessionStore {
         self.sessions.insert(address.clone(), record.clone());
         Ok(())
     }
+
+    fn session_limits(&self) -> ProtocolLimits {
+        self.limits
+    }
+
+    fn current_time(&self) -> SystemTime {
+        self.fixed_time.unwrap_or_else(SystemTime::now)
+    }
 }
 
 /// Reference implementation of [traits::SenderKeyStore].
@@ -309,6 +374,7 @@ pub struct InMemSenderKeyStore {
     // We use Cow keys in order to store owned values but compare to referenced ones.
     // See https://users.rust-lang.org/t/hashmap-with-tuple-keys/12711/6.
     keys: HashMap<(Cow<'static, ProtocolAddress>, Uuid), SenderKeyRecord>,
+    limits: ProtocolLimits,
 }
 
 impl InMemSenderKeyStore {
@@ -316,8 +382,16 @@ impl InMemSenderKeyStore {
     pub fn new() -> Self {
         Self {
             keys: HashMap::new(),
+            limits: ProtocolLimits::default(),
         }
     }
+
+    /// Changes the limits returned by [`SenderKeyStore::sender_key_limits`].
+    ///
+    /// [`SenderKeyStore::sender_key_limits`]: crate::SenderKeyStore::sender_key_limits
+    pub fn set_limits(&mut self, limits: ProtocolLimits) {
+        self.limits = limits;
+    }
 }
 
 impl Default for InMemSenderKeyStore {
@@ -351,6 +425,10 @@ impl traits::SenderKeyStore for InMemSenderKeyStore {
             .get(&(Cow::Borrowed(sender), distribution_id))
             .cloned())
     }
+
+    fn sender_key_limits(&self) -> ProtocolLimits {
+        self.limits
+    }
 }
 
 /// Reference implementation of [traits::ProtocolStore].
@@ -401,6 +479,10 @@ impl traits::IdentityKeyStore for InMemSignalProtocolStore {
         self.identity_store.get_identity_key_pair().await
     }
 
+    async fn get_identity_key_signer(&self) -> Result<Box<dyn PrivateKeySigner>> {
+        self.identity_store.get_identity_key_signer().await
+    }
+
     async fn get_local_registration_id(&self) -> Result<u32> {
         self.identity_store.get_local_registration_id().await
     }
@@ -413,6 +495,16 @@ impl traits::IdentityKeyStore for InMemSignalProtocolStore {
         self.identity_store.save_identity(address, identity).await
     }
 
+    async fn save_identity_transition(
+        &mut self,
+        address: &ProtocolAddress,
+        transition: &IdentityKeyTransition,
+    ) -> Result<IdentityChange> {
+        self.identity_store
+            .save_identity_transition(address, transition)
+            .await
+    }
+
     async fn is_trusted_identity(
         &self,
         address: &ProtocolAddress,
@@ -499,6 +591,14 @@ impl traits::SessionStore for InMemSignalProtocolStore {
     ) -> Result<()> {
         self.session_store.store_session(address, record).await
     }
+
+    fn session_limits(&self) -> ProtocolLimits {
+        self.session_store.session_limits()
+    }
+
+    fn current_time(&self) -> SystemTime {
+        self.session_store.current_time()
+    }
 }
 
 #[async_trait(?Send)]
@@ -523,6 +623,27 @@ impl traits::SenderKeyStore for InMemSignalProtocolStore {
             .load_sender_key(sender, distribution_id)
             .await
     }
+
+    fn sender_key_limits(&self) -> ProtocolLimits {
+        self.sender_key_store.sender_key_limits()
+    }
+}
+
+/// The in-memory stores don't distinguish one-time Kyber pre-keys from last-resort ones, and keep
+/// both after they are used, so every Kyber pre-key is reported as unused.
+#[async_trait(?Send)]
+impl traits::PreKeyInventoryStore for InMemSignalProtocolStore {
+    async fn unused_pre_key_ids(&self) -> Result<Vec<PreKeyId>> {
+        Ok(self.all_pre_key_ids().copied().collect())
+    }
+
+    async fn unused_one_time_kyber_pre_key_ids(&self) -> Result<Vec<KyberPreKeyId>> {
+        Ok(self.all_kyber_pre_key_ids().copied().collect())
+    }
+
+    async fn signed_pre_key_ids(&self) -> Result<Vec<SignedPreKeyId>> {
+        Ok(self.all_signed_pre_key_ids().copied().collect())
+    }
 }
 
 impl traits::ProtocolStore for InMemSignalProtocolStore {}
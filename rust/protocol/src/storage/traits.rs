@@ -5,6 +5,9 @@
 
 //! Traits defining several stores used throughout the Signal Protocol.
 
+use std::future::Future;
+use std::time::SystemTime;
+
 use async_trait::async_trait;
 use uuid::Uuid;
 
@@ -14,7 +17,10 @@ use crate::state::{
     KyberPreKeyId, KyberPreKeyRecord, PreKeyId, PreKeyRecord, SessionRecord, SignedPreKeyId,
     SignedPreKeyRecord,
 };
-use crate::{IdentityKey, IdentityKeyPair, ProtocolAddress};
+use crate::{
+    IdentityKey, IdentityKeyPair, IdentityKeyTransition, PrivateKeySigner, ProtocolAddress,
+    ProtocolLimits, SignalProtocolError,
+};
 
 // TODO: consider moving this enum into utils.rs?
 /// Each Signal message can be considered to have exactly two participants, a sender and receiver.
@@ -38,6 +44,11 @@ pub enum IdentityChange {
     NewOrUnchanged,
     /// The new identity key replaced a different key for the protocol address.
     ReplacedExisting,
+    /// The new identity key replaced the previous one by way of a transition signed with it, so
+    /// the peer should stay as trusted as it was.
+    ///
+    /// Only returned by [`IdentityKeyStore::save_identity_transition`].
+    RotatedVerified,
 }
 
 /// Interface defining the identity store, which may be in-memory, on-disk, etc.
@@ -50,6 +61,15 @@ pub trait IdentityKeyStore {
     /// Return the single specific identity the store is assumed to represent, with private key.
     async fn get_identity_key_pair(&self) -> Result<IdentityKeyPair>;
 
+    /// Return a [PrivateKeySigner] for the identity the store represents.
+    ///
+    /// libsignal uses this instead of [Self::get_identity_key_pair] whenever it needs the local
+    /// identity. Stores that keep the private key somewhere it can't be read out, such as a
+    /// platform keystore, should override this, and may then fail `get_identity_key_pair`.
+    async fn get_identity_key_signer(&self) -> Result<Box<dyn PrivateKeySigner>> {
+        Ok(Box::new(self.get_identity_key_pair().await?))
+    }
+
     /// Return a [u32] specific to this store instance.
     ///
     /// This local registration id is separate from the per-device identifier used in
@@ -79,6 +99,33 @@ pub trait IdentityKeyStore {
 
     /// Return the public identity for the given `address`, if known.
     async fn get_identity(&self, address: &ProtocolAddress) -> Result<Option<IdentityKey>>;
+
+    /// Replace the identity for `address` with the new identity from `transition`.
+    ///
+    /// The transition is only accepted if it was signed by the identity currently recorded for
+    /// `address`; otherwise [`SignalProtocolError::UntrustedIdentity`] or
+    /// [`SignalProtocolError::SignatureValidationFailed`] is returned and the store is left
+    /// unchanged. Implementations that override this should preserve any verification state of
+    /// the old identity, since the peer vouched for the new one with it.
+    ///
+    /// On success, returns [`IdentityChange::RotatedVerified`] rather than
+    /// [`IdentityChange::ReplacedExisting`], so that callers don't treat it as an unverified key
+    /// change.
+    async fn save_identity_transition(
+        &mut self,
+        address: &ProtocolAddress,
+        transition: &IdentityKeyTransition,
+    ) -> Result<IdentityChange> {
+        if self.get_identity(address).await?.as_ref() != Some(transition.old_identity()) {
+            return Err(SignalProtocolError::UntrustedIdentity(address.clone()));
+        }
+        if !transition.verify() {
+            return Err(SignalProtocolError::SignatureValidationFailed);
+        }
+        self.save_identity(address, transition.new_identity())
+            .await?;
+        Ok(IdentityChange::RotatedVerified)
+    }
 }
 
 /// Interface for storing pre-keys downloaded from a server.
@@ -149,6 +196,38 @@ pub trait SessionStore {
         address: &ProtocolAddress,
         record: &SessionRecord,
     ) -> Result<()>;
+
+    /// The limits to apply to session records handled with this store.
+    ///
+    /// Only [`max_message_keys`](ProtocolLimits::max_message_keys) and
+    /// [`max_archived_states`](ProtocolLimits::max_archived_states) are relevant to sessions.
+    fn session_limits(&self) -> ProtocolLimits {
+        ProtocolLimits::default()
+    }
+
+    /// The time to record when decrypting with a session from this store, both as the session's
+    /// last use (see [`SessionRecord::diagnostics`]) and as when any state it replaces was
+    /// archived.
+    ///
+    /// Defaults to the system clock. Stores can override this to supply a different clock, such as
+    /// a fixed one in tests.
+    fn current_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Lists what is in the pre-key stores, for [`PreKeyInventory`](crate::PreKeyInventory).
+#[async_trait(?Send)]
+pub trait PreKeyInventoryStore {
+    /// The IDs of all one-time pre-keys that have not been used yet.
+    async fn unused_pre_key_ids(&self) -> Result<Vec<PreKeyId>>;
+
+    /// The IDs of all one-time Kyber pre-keys that have not been used yet, not including
+    /// last-resort keys.
+    async fn unused_one_time_kyber_pre_key_ids(&self) -> Result<Vec<KyberPreKeyId>>;
+
+    /// The IDs of all signed pre-keys.
+    async fn signed_pre_key_ids(&self) -> Result<Vec<SignedPreKeyId>>;
 }
 
 /// Interface for storing sender key records, allowing multiple keys per user.
@@ -169,6 +248,57 @@ pub trait SenderKeyStore {
         sender: &ProtocolAddress,
         distribution_id: Uuid,
     ) -> Result<Option<SenderKeyRecord>>;
+
+    /// The limits to apply to sender key records handled with this store.
+    ///
+    /// Only [`max_message_keys`](ProtocolLimits::max_message_keys) and
+    /// [`max_sender_key_states`](ProtocolLimits::max_sender_key_states) are relevant to sender
+    /// keys.
+    fn sender_key_limits(&self) -> ProtocolLimits {
+        ProtocolLimits::default()
+    }
+}
+
+/// Hooks for stores backed by storage that supports transactions.
+///
+/// Operations like [`message_decrypt_in_transaction`](crate::message_decrypt_in_transaction)
+/// make several writes to different stores. They call [`begin`](Self::begin) before the first
+/// store access, then [`commit`](Self::commit) if the operation succeeded or
+/// [`rollback`](Self::rollback) if it failed, so that a backend can make the writes atomic.
+#[async_trait(?Send)]
+pub trait TransactionalStores {
+    /// Start a transaction covering all subsequent store accesses.
+    async fn begin(&mut self) -> Result<()>;
+
+    /// Make the writes since [`Self::begin`] permanent.
+    async fn commit(&mut self) -> Result<()>;
+
+    /// Discard the writes since [`Self::begin`].
+    async fn rollback(&mut self) -> Result<()>;
+}
+
+/// Runs `operation` between [`TransactionalStores::begin`] and either
+/// [`TransactionalStores::commit`] or [`TransactionalStores::rollback`].
+///
+/// `operation` must not have been polled yet.
+pub(crate) async fn run_in_transaction<T>(
+    transaction: &mut dyn TransactionalStores,
+    operation: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    transaction.begin().await?;
+    match operation.await {
+        Ok(result) => {
+            transaction.commit().await?;
+            Ok(result)
+        }
+        Err(e) => {
+            if let Err(rollback_error) = transaction.rollback().await {
+                // Report the original failure, which is more likely to be actionable.
+                log::error!("failed to roll back store transaction: {rollback_error}");
+            }
+            Err(e)
+        }
+    }
 }
 
 /// Mixes in all the store interfaces defined in this module.
@@ -0,0 +1,391 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Deterministic harness for testing the protocol, available with the `test-util` feature.
+//!
+//! All of the ciphers in this crate already take their randomness and the current time as
+//! arguments. A [`Simulation`] drives them with a seeded RNG and a manually advanced
+//! [`TestClock`], for a set of parties with in-memory stores. Messages are held "in flight" until a
+//! test delivers them, so that out-of-order delivery and interleavings between several parties can
+//! be reproduced exactly from a seed.
+
+use std::time::{Duration, SystemTime};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    kem, message_decrypt, message_encrypt, process_prekey_bundle, CiphertextMessage, DeviceId,
+    GenericSignedPreKey, IdentityKeyPair, IdentityKeyStore, InMemSignalProtocolStore, KeyPair,
+    KyberPreKeyRecord, KyberPreKeyStore, PreKeyBundle, PreKeyRecord, PreKeyStore, ProtocolAddress,
+    Result, SignedPreKeyRecord, SignedPreKeyStore, Timestamp, UsePQRatchet,
+};
+
+/// A seeded RNG suitable for passing to any of this crate's APIs.
+///
+/// The output for a given seed is stable for a given version of `rand`, but not across versions.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// A clock that only moves when told to.
+#[derive(Clone, Debug)]
+pub struct TestClock {
+    now: SystemTime,
+}
+
+impl TestClock {
+    /// An arbitrary but fixed starting point, used by [`Default`].
+    pub const DEFAULT_START: Duration = Duration::from_secs(1_700_000_000);
+
+    pub fn new(start: SystemTime) -> Self {
+        Self { now: start }
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        self.now += by;
+    }
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH + Self::DEFAULT_START)
+    }
+}
+
+/// One participant in a [`Simulation`].
+pub struct Party {
+    pub address: ProtocolAddress,
+    pub store: InMemSignalProtocolStore,
+    next_pre_key_id: u32,
+}
+
+impl Party {
+    /// Generates and saves a fresh set of pre-keys, returning them as a bundle.
+    pub async fn create_pre_key_bundle(
+        &mut self,
+        rng: &mut StdRng,
+        now: SystemTime,
+    ) -> Result<PreKeyBundle> {
+        let id = self.next_pre_key_id;
+        self.next_pre_key_id += 1;
+
+        let identity_key_pair = self.store.get_identity_key_pair().await?;
+        let pre_key_pair = KeyPair::generate(rng);
+        let signed_pre_key_pair = KeyPair::generate(rng);
+        let kyber_pre_key_pair = kem::KeyPair::generate(kem::KeyType::Kyber1024, rng);
+
+        let signed_pre_key_signature = identity_key_pair
+            .private_key()
+            .calculate_signature(&signed_pre_key_pair.public_key.serialize(), rng)?;
+        let kyber_pre_key_signature = identity_key_pair
+            .private_key()
+            .calculate_signature(&kyber_pre_key_pair.public_key.serialize(), rng)?;
+
+        let timestamp = Timestamp::from_epoch_millis(
+            now.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+                .try_into()
+                .expect("reasonable time"),
+        );
+        self.store
+            .save_pre_key(id.into(), &PreKeyRecord::new(id.into(), &pre_key_pair))
+            .await?;
+        self.store
+            .save_signed_pre_key(
+                id.into(),
+                &SignedPreKeyRecord::new(
+                    id.into(),
+                    timestamp,
+                    &signed_pre_key_pair,
+                    &signed_pre_key_signature,
+                ),
+            )
+            .await?;
+        self.store
+            .save_kyber_pre_key(
+                id.into(),
+                &KyberPreKeyRecord::new(
+                    id.into(),
+                    timestamp,
+                    &kyber_pre_key_pair,
+                    &kyber_pre_key_signature,
+                ),
+            )
+            .await?;
+
+        PreKeyBundle::new(
+            self.store.get_local_registration_id().await?,
+            self.address.device_id(),
+            Some((id.into(), pre_key_pair.public_key)),
+            id.into(),
+            signed_pre_key_pair.public_key,
+            signed_pre_key_signature.to_vec(),
+            id.into(),
+            kyber_pre_key_pair.public_key,
+            kyber_pre_key_signature.to_vec(),
+            *identity_key_pair.identity_key(),
+        )
+    }
+}
+
+/// A message that has been encrypted but not yet delivered.
+#[derive(Debug)]
+pub struct InFlightMessage {
+    /// Index of the sending party.
+    pub sender: usize,
+    /// Index of the receiving party.
+    pub recipient: usize,
+    pub ciphertext: CiphertextMessage,
+    pub plaintext: Vec<u8>,
+}
+
+/// A group of parties exchanging messages, with fully deterministic randomness and time.
+pub struct Simulation {
+    pub rng: StdRng,
+    pub clock: TestClock,
+    pub parties: Vec<Party>,
+    pub use_pq_ratchet: UsePQRatchet,
+    in_flight: Vec<InFlightMessage>,
+}
+
+impl Simulation {
+    /// Creates `party_count` parties, whose identities are derived from `seed`.
+    pub fn new(seed: u64, party_count: usize) -> Result<Self> {
+        let mut rng = seeded_rng(seed);
+        let parties = (0..party_count)
+            .map(|i| {
+                let identity = IdentityKeyPair::generate(&mut rng);
+                // Valid registration IDs fit in 14 bits.
+                let registration_id = rng.random_range(1..0x4000);
+                Ok(Party {
+                    address: ProtocolAddress::new(
+                        format!("party-{i}"),
+                        DeviceId::new(1).expect("valid"),
+                    ),
+                    store: InMemSignalProtocolStore::new(identity, registration_id)?,
+                    next_pre_key_id: 1,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rng,
+            clock: TestClock::default(),
+            parties,
+            use_pq_ratchet: UsePQRatchet::Yes,
+            in_flight: vec![],
+        })
+    }
+
+    /// Has `from` start a session with `to` using a freshly generated pre-key bundle.
+    pub async fn establish_session(&mut self, from: usize, to: usize) -> Result<()> {
+        let now = self.clock.now();
+        let bundle = self.parties[to]
+            .create_pre_key_bundle(&mut self.rng, now)
+            .await?;
+        let to_address = self.parties[to].address.clone();
+        let from_store = &mut self.parties[from].store;
+        process_prekey_bundle(
+            &to_address,
+            &mut from_store.session_store,
+            &mut from_store.identity_store,
+            &bundle,
+            now,
+            &mut self.rng,
+            self.use_pq_ratchet,
+        )
+        .await
+    }
+
+    /// Establishes a session for every pair of parties, initiated by the lower-numbered party.
+    pub async fn establish_all_sessions(&mut self) -> Result<()> {
+        for from in 0..self.parties.len() {
+            for to in from + 1..self.parties.len() {
+                self.establish_session(from, to).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypts `plaintext` from `from` to `to`, leaving it in flight.
+    pub async fn send(&mut self, from: usize, to: usize, plaintext: &[u8]) -> Result<()> {
+        let to_address = self.parties[to].address.clone();
+        let from_store = &mut self.parties[from].store;
+        let ciphertext = message_encrypt(
+            plaintext,
+            &to_address,
+            &mut from_store.session_store,
+            &mut from_store.identity_store,
+            self.clock.now(),
+            &mut self.rng,
+        )
+        .await?;
+        self.in_flight.push(InFlightMessage {
+            sender: from,
+            recipient: to,
+            ciphertext,
+            plaintext: plaintext.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Messages that have been sent but not delivered, oldest first.
+    pub fn in_flight(&self) -> &[InFlightMessage] {
+        &self.in_flight
+    }
+
+    /// Removes the in-flight message at `index` and decrypts it, returning the decrypted
+    /// plaintext.
+    pub async fn deliver(&mut self, index: usize) -> Result<Vec<u8>> {
+        let message = self.in_flight.remove(index);
+        let sender_address = self.parties[message.sender].address.clone();
+        let recipient_store = &mut self.parties[message.recipient].store;
+        recipient_store
+            .session_store
+            .set_current_time(Some(self.clock.now()));
+        message_decrypt(
+            &message.ciphertext,
+            &sender_address,
+            &mut recipient_store.session_store,
+            &mut recipient_store.identity_store,
+            &mut recipient_store.pre_key_store,
+            &recipient_store.signed_pre_key_store,
+            &mut recipient_store.kyber_pre_key_store,
+            &mut self.rng,
+            self.use_pq_ratchet,
+        )
+        .await
+    }
+
+    /// Delivers every in-flight message in an order chosen by the simulation's RNG, checking that
+    /// each decrypts to what was sent.
+    ///
+    /// # Panics
+    ///
+    /// If a message decrypts to the wrong plaintext.
+    pub async fn deliver_all_shuffled(&mut self) -> Result<()> {
+        while !self.in_flight.is_empty() {
+            let index = self.rng.random_range(0..self.in_flight.len());
+            let expected = self.in_flight[index].plaintext.clone();
+            let actual = self.deliver(index).await?;
+            assert_eq!(actual, expected, "decrypted to the wrong plaintext");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures_util::FutureExt;
+
+    use super::*;
+    use crate::{consts, SessionStore, SignalProtocolError};
+
+    #[test]
+    fn interleaved_delivery() -> Result<()> {
+        async {
+            let mut simulation = Simulation::new(42, 3)?;
+            simulation.establish_all_sessions().await?;
+
+            // The responders don't have sessions until they receive something.
+            for from in 0..3 {
+                for to in from + 1..3 {
+                    simulation.send(from, to, b"hello").await?;
+                }
+            }
+            simulation.deliver_all_shuffled().await?;
+
+            for round in 0..5u8 {
+                for from in 0..3 {
+                    for to in 0..3 {
+                        if from != to {
+                            let from_byte = u8::try_from(from).expect("few parties");
+                            simulation.send(from, to, &[round, from_byte]).await?;
+                        }
+                    }
+                }
+                simulation.deliver_all_shuffled().await?;
+            }
+            assert!(simulation.in_flight().is_empty());
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+
+    #[test]
+    fn same_seed_same_ciphertexts() -> Result<()> {
+        async {
+            let mut ciphertexts = vec![];
+            for _ in 0..2 {
+                let mut simulation = Simulation::new(7, 2)?;
+                simulation.establish_session(0, 1).await?;
+                simulation.send(0, 1, b"hello").await?;
+                ciphertexts.push(simulation.in_flight()[0].ciphertext.serialize().to_vec());
+            }
+            assert_eq!(ciphertexts[0], ciphertexts[1]);
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+
+    #[test]
+    fn decryption_uses_simulated_time() -> Result<()> {
+        async {
+            let mut simulation = Simulation::new(3, 2)?;
+            simulation.establish_session(0, 1).await?;
+            simulation.send(0, 1, b"hello").await?;
+            simulation.clock.advance(Duration::from_secs(60));
+            simulation.deliver(0).await?;
+
+            let sender_address = simulation.parties[0].address.clone();
+            let session = simulation.parties[1]
+                .store
+                .load_session(&sender_address)
+                .await?
+                .expect("present");
+            assert_eq!(
+                session.diagnostics()?.last_used,
+                Some(simulation.clock.now())
+            );
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+
+    #[test]
+    fn unacknowledged_session_expires() -> Result<()> {
+        async {
+            let mut simulation = Simulation::new(1, 2)?;
+            simulation.establish_session(0, 1).await?;
+            simulation.send(0, 1, b"first").await?;
+
+            simulation
+                .clock
+                .advance(consts::MAX_UNACKNOWLEDGED_SESSION_AGE + Duration::from_secs(1));
+            assert!(matches!(
+                simulation.send(0, 1, b"too late").await,
+                Err(SignalProtocolError::SessionNotFound(_))
+            ));
+
+            // A reply acknowledges the session again.
+            assert_eq!(simulation.deliver(0).await?, b"first");
+            simulation.send(1, 0, b"reply").await?;
+            assert_eq!(simulation.deliver(0).await?, b"reply");
+            simulation.send(0, 1, b"second").await?;
+            assert_eq!(simulation.deliver(0).await?, b"second");
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+}
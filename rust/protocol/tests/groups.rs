@@ -142,6 +142,234 @@ fn group_basic_encrypt_decrypt() -> Result<(), SignalProtocolError> {
     .expect("sync")
 }
 
+#[test]
+fn group_rotate_sender_key() -> Result<(), SignalProtocolError> {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+
+        let sender_address =
+            ProtocolAddress::new("+14159999111".to_owned(), DeviceId::new(1).unwrap());
+        let distribution_id = Uuid::from_u128(0xd1d1d1d1_7000_11eb_b32a_33b8a8a487a6);
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+        let mut carol_store = test_in_memory_protocol_store()?;
+
+        let original_distribution_message = create_sender_key_distribution_message(
+            &sender_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+        for store in [&mut bob_store, &mut carol_store] {
+            process_sender_key_distribution_message(
+                &sender_address,
+                &original_distribution_message,
+                store,
+            )
+            .await?;
+        }
+
+        let before_rotation = group_encrypt(
+            &mut alice_store,
+            &sender_address,
+            distribution_id,
+            "before".as_bytes(),
+            &mut csprng,
+        )
+        .await?;
+
+        // Carol leaves the group.
+        let rotated_distribution_message = rotate_sender_key(
+            &sender_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+        assert_ne!(
+            rotated_distribution_message.signing_key()?,
+            original_distribution_message.signing_key()?
+        );
+        assert_eq!(rotated_distribution_message.iteration()?, 0);
+
+        // Later requests for the distribution message describe the new key.
+        let recreated_distribution_message = create_sender_key_distribution_message(
+            &sender_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+        assert_eq!(
+            recreated_distribution_message.serialized(),
+            rotated_distribution_message.serialized()
+        );
+
+        process_sender_key_distribution_message(
+            &sender_address,
+            &rotated_distribution_message,
+            &mut bob_store,
+        )
+        .await?;
+
+        let after_rotation = group_encrypt(
+            &mut alice_store,
+            &sender_address,
+            distribution_id,
+            "after".as_bytes(),
+            &mut csprng,
+        )
+        .await?;
+
+        assert_eq!(
+            group_decrypt(after_rotation.serialized(), &mut bob_store, &sender_address).await?,
+            b"after"
+        );
+        // Messages sent before the rotation still decrypt.
+        assert_eq!(
+            group_decrypt(
+                before_rotation.serialized(),
+                &mut bob_store,
+                &sender_address
+            )
+            .await?,
+            b"before"
+        );
+        assert_eq!(
+            group_decrypt(
+                before_rotation.serialized(),
+                &mut carol_store,
+                &sender_address
+            )
+            .await?,
+            b"before"
+        );
+
+        assert!(matches!(
+            group_decrypt(
+                after_rotation.serialized(),
+                &mut carol_store,
+                &sender_address
+            )
+            .await,
+            Err(SignalProtocolError::NoSenderKeyState { .. })
+        ));
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn group_sender_key_store_limits() -> Result<(), SignalProtocolError> {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+
+        let sender_address =
+            ProtocolAddress::new("+14159999111".to_owned(), DeviceId::new(1).unwrap());
+        let distribution_id = Uuid::from_u128(0xd1d1d1d1_7000_11eb_b32a_33b8a8a487a6);
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+        bob_store.sender_key_store.set_limits(
+            ProtocolLimits::default()
+                .with_max_sender_key_states(1)?
+                .with_max_message_keys(0)?,
+        );
+
+        let original_distribution_message = create_sender_key_distribution_message(
+            &sender_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+        process_sender_key_distribution_message(
+            &sender_address,
+            &original_distribution_message,
+            &mut bob_store,
+        )
+        .await?;
+
+        let mut before_rotation = vec![];
+        for text in ["skipped", "received"] {
+            before_rotation.push(
+                group_encrypt(
+                    &mut alice_store,
+                    &sender_address,
+                    distribution_id,
+                    text.as_bytes(),
+                    &mut csprng,
+                )
+                .await?,
+            );
+        }
+
+        // With no skipped message keys kept, an out-of-order message is lost.
+        assert_eq!(
+            group_decrypt(
+                before_rotation[1].serialized(),
+                &mut bob_store,
+                &sender_address
+            )
+            .await?,
+            b"received"
+        );
+        assert!(matches!(
+            group_decrypt(
+                before_rotation[0].serialized(),
+                &mut bob_store,
+                &sender_address
+            )
+            .await,
+            Err(SignalProtocolError::DuplicatedMessage(2, 0))
+        ));
+
+        // With only one state kept, the old key is dropped as soon as the new one arrives.
+        let rotated_distribution_message = rotate_sender_key(
+            &sender_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+        process_sender_key_distribution_message(
+            &sender_address,
+            &rotated_distribution_message,
+            &mut bob_store,
+        )
+        .await?;
+        let late_message = group_encrypt(
+            &mut alice_store,
+            &sender_address,
+            distribution_id,
+            "after".as_bytes(),
+            &mut csprng,
+        )
+        .await?;
+        assert_eq!(
+            group_decrypt(late_message.serialized(), &mut bob_store, &sender_address).await?,
+            b"after"
+        );
+        assert!(matches!(
+            group_decrypt(
+                before_rotation[1].serialized(),
+                &mut bob_store,
+                &sender_address
+            )
+            .await,
+            Err(SignalProtocolError::NoSenderKeyState { .. })
+        ));
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
 #[test]
 fn group_sealed_sender() -> Result<(), SignalProtocolError> {
     async {
@@ -1295,3 +1523,65 @@ fn group_message_key_limit() -> Result<(), SignalProtocolError> {
     .now_or_never()
     .expect("sync")
 }
+
+#[test]
+fn group_decrypt_into_buffer() -> Result<(), SignalProtocolError> {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+
+        let sender_address =
+            ProtocolAddress::new("+14159999111".to_owned(), DeviceId::new(1).unwrap());
+        let distribution_id = Uuid::from_u128(0xd1d1d1d1_7000_11eb_b32a_33b8a8a487a6);
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let sent_distribution_message = create_sender_key_distribution_message(
+            &sender_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+        process_sender_key_distribution_message(
+            &sender_address,
+            &sent_distribution_message,
+            &mut bob_store,
+        )
+        .await?;
+
+        let alice_ciphertext = group_encrypt(
+            &mut alice_store,
+            &sender_address,
+            distribution_id,
+            "space camp?".as_bytes(),
+            &mut csprng,
+        )
+        .await?;
+
+        // A buffer that's too small is rejected without using up the message key.
+        let mut buffer = [0u8; 4];
+        assert!(group_decrypt_into(
+            alice_ciphertext.serialized(),
+            &mut bob_store,
+            &sender_address,
+            &mut buffer
+        )
+        .await
+        .is_err());
+
+        let mut buffer = [0u8; 64];
+        let len = group_decrypt_into(
+            alice_ciphertext.serialized(),
+            &mut bob_store,
+            &sender_address,
+            &mut buffer,
+        )
+        .await?;
+        assert_eq!(&buffer[..len], "space camp?".as_bytes());
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
@@ -422,6 +422,329 @@ fn test_sender_key_in_sealed_sender() -> Result<(), SignalProtocolError> {
     .expect("sync")
 }
 
+#[test]
+fn test_encrypt_for_recipients() -> Result<(), SignalProtocolError> {
+    async {
+        let mut rng = OsRng.unwrap_err();
+
+        let alice_device_id = DeviceId::new(23).unwrap();
+        let bob_device_id = DeviceId::new(42).unwrap();
+        let carol_device_id = DeviceId::new(1).unwrap();
+
+        let alice_uuid = "9d0652a3-dcc3-4d11-975f-74d61598733f".to_string();
+        let bob_uuid = "796abedb-ca4e-4f18-8803-1fde5b921f9f".to_string();
+        let carol_uuid = "38381c3b-2606-4ca7-9310-7cb927f2ab4a".to_string();
+
+        let distribution_id = Uuid::from_u128(0xd1d1d1d1_7000_11eb_b32a_33b8a8a487a6);
+
+        let alice_uuid_address = ProtocolAddress::new(alice_uuid.clone(), alice_device_id);
+        let bob_uuid_address = ProtocolAddress::new(bob_uuid.clone(), bob_device_id);
+        let carol_uuid_address = ProtocolAddress::new(carol_uuid.clone(), carol_device_id);
+
+        let mut alice_store = support::test_in_memory_protocol_store()?;
+        let mut bob_store = support::test_in_memory_protocol_store()?;
+
+        let alice_pubkey = *alice_store.get_identity_key_pair().await?.public_key();
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut rng).await?;
+
+        process_prekey_bundle(
+            &bob_uuid_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut rng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let trust_root = KeyPair::generate(&mut rng);
+        let server_key = KeyPair::generate(&mut rng);
+
+        let server_cert =
+            ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, &mut rng)?;
+
+        let expires = Timestamp::from_epoch_millis(1605722925);
+
+        let sender_cert = SenderCertificate::new(
+            alice_uuid.clone(),
+            None,
+            alice_pubkey,
+            alice_device_id,
+            expires,
+            server_cert,
+            &server_key.private_key,
+            &mut rng,
+        )?;
+
+        let distribution_message = create_sender_key_distribution_message(
+            &alice_uuid_address,
+            distribution_id,
+            &mut alice_store,
+            &mut rng,
+        )
+        .await?;
+
+        process_sender_key_distribution_message(
+            &alice_uuid_address,
+            &distribution_message,
+            &mut bob_store,
+        )
+        .await?;
+
+        let result = encrypt_for_recipients(
+            &[&bob_uuid_address, &carol_uuid_address],
+            &sender_cert,
+            distribution_id,
+            "swim camp".as_bytes(),
+            ContentHint::Default,
+            None,
+            &alice_store.session_store,
+            &alice_store.identity_store,
+            &mut alice_store.sender_key_store,
+            SystemTime::now(),
+            &mut rng,
+        )
+        .await?;
+
+        // Carol has no session, so she was left out.
+        let carol_service_id = ServiceId::parse_from_service_id_string(&carol_uuid).unwrap();
+        assert_eq!(
+            result.failures,
+            [(carol_service_id, RecipientEncryptionFailure::MissingSession)]
+        );
+
+        let message = result.message.expect("Bob was included");
+        let sent = SealedSenderV2SentMessage::parse(&message).expect("valid");
+        assert_eq!(sent.recipients.len(), 2);
+        let (bob_service_id, bob_recipient) = sent.recipients.get_index(0).expect("present");
+        assert_eq!(bob_service_id.service_id_string(), bob_uuid);
+        let (excluded_service_id, excluded_recipient) =
+            sent.recipients.get_index(1).expect("present");
+        assert_eq!(*excluded_service_id, carol_service_id);
+        assert!(excluded_recipient.devices.is_empty());
+        let bob_ctext = sent
+            .received_message_parts_for_recipient(bob_recipient)
+            .as_ref()
+            .concat();
+
+        let bob_usmc = sealed_sender_decrypt_to_usmc(&bob_ctext, &bob_store.identity_store).await?;
+        assert!(matches!(
+            bob_usmc.msg_type()?,
+            CiphertextMessageType::SenderKey,
+        ));
+
+        let bob_plaintext =
+            group_decrypt(bob_usmc.contents()?, &mut bob_store, &alice_uuid_address).await?;
+        assert_eq!(
+            String::from_utf8(bob_plaintext).expect("valid UTF-8"),
+            "swim camp"
+        );
+
+        // A device without a session excludes the whole recipient, even though Bob's other
+        // device is fine. With nobody left to send to, nothing is encrypted.
+        let bob_other_address = ProtocolAddress::new(bob_uuid.clone(), carol_device_id);
+        let result = encrypt_for_recipients(
+            &[&bob_uuid_address, &bob_other_address, &carol_uuid_address],
+            &sender_cert,
+            distribution_id,
+            "swim camp".as_bytes(),
+            ContentHint::Default,
+            None,
+            &alice_store.session_store,
+            &alice_store.identity_store,
+            &mut alice_store.sender_key_store,
+            SystemTime::now(),
+            &mut rng,
+        )
+        .await?;
+        assert!(result.message.is_none());
+        assert_eq!(
+            result.failures,
+            [
+                (*bob_service_id, RecipientEncryptionFailure::MissingSession),
+                (carol_service_id, RecipientEncryptionFailure::MissingSession),
+            ]
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn test_sealed_sender_v2_sent_message_builder() -> Result<(), SignalProtocolError> {
+    async {
+        let mut rng = OsRng.unwrap_err();
+
+        let alice_device_id = DeviceId::new(23).unwrap();
+        let bob_device_id = DeviceId::new(42).unwrap();
+        let dave_device_id = DeviceId::new(1).unwrap();
+
+        let alice_uuid = "9d0652a3-dcc3-4d11-975f-74d61598733f".to_string();
+        let bob_service_id =
+            ServiceId::parse_from_service_id_string("796abedb-ca4e-4f18-8803-1fde5b921f9f")
+                .expect("valid");
+        let carol_service_id =
+            ServiceId::parse_from_service_id_string("38381c3b-2606-4ca7-9310-7cb927f2ab4a")
+                .expect("valid");
+        let dave_service_id =
+            ServiceId::parse_from_service_id_string("PNI:2b0ba9d3-0e8b-4a1e-9f0e-6a4ba1d0c2f7")
+                .expect("valid");
+
+        let bob_uuid_address =
+            ProtocolAddress::new(bob_service_id.service_id_string(), bob_device_id);
+
+        let mut alice_store = support::test_in_memory_protocol_store()?;
+        let mut bob_store = support::test_in_memory_protocol_store()?;
+
+        let alice_pubkey = *alice_store.get_identity_key_pair().await?.public_key();
+        let bob_registration_id = bob_store.get_local_registration_id().await?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut rng).await?;
+        process_prekey_bundle(
+            &bob_uuid_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut rng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let trust_root = KeyPair::generate(&mut rng);
+        let server_key = KeyPair::generate(&mut rng);
+        let server_cert =
+            ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, &mut rng)?;
+        let sender_cert = SenderCertificate::new(
+            alice_uuid,
+            None,
+            alice_pubkey,
+            alice_device_id,
+            Timestamp::from_epoch_millis(1605722925),
+            server_cert,
+            &server_key.private_key,
+            &mut rng,
+        )?;
+        let usmc = UnidentifiedSenderMessageContent::new(
+            CiphertextMessageType::SenderKey,
+            sender_cert,
+            b"sender key message".to_vec(),
+            ContentHint::Default,
+            None,
+        )?;
+
+        let mut builder = SealedSenderV2SentMessageBuilder::new();
+        builder.add_recipient(bob_service_id, [(bob_device_id, bob_registration_id ^ 1)]);
+        assert!(matches!(
+            builder
+                .build(
+                    &usmc,
+                    &alice_store.session_store,
+                    &alice_store.identity_store,
+                    &mut rng
+                )
+                .await,
+            Err(SealedSenderV2BuildError::RegistrationIdMismatch { address, expected, found })
+                if address == bob_uuid_address
+                    && expected == bob_registration_id ^ 1
+                    && found == bob_registration_id
+        ));
+
+        let mut builder = SealedSenderV2SentMessageBuilder::new();
+        builder
+            .add_recipient(bob_service_id, [(bob_device_id, bob_registration_id)])
+            .add_recipient(dave_service_id, [(dave_device_id, 1234)]);
+        assert!(matches!(
+            builder
+                .build(
+                    &usmc,
+                    &alice_store.session_store,
+                    &alice_store.identity_store,
+                    &mut rng
+                )
+                .await,
+            Err(SealedSenderV2BuildError::MissingSession(address))
+                if address.name() == dave_service_id.service_id_string()
+        ));
+
+        let mut builder = SealedSenderV2SentMessageBuilder::new();
+        builder
+            .add_recipient(bob_service_id, [(bob_device_id, bob_registration_id)])
+            .exclude_recipient(bob_service_id);
+        assert!(matches!(
+            builder
+                .build(
+                    &usmc,
+                    &alice_store.session_store,
+                    &alice_store.identity_store,
+                    &mut rng
+                )
+                .await,
+            Err(SealedSenderV2BuildError::DuplicateRecipient(service_id))
+                if service_id == bob_service_id
+        ));
+
+        let mut builder = SealedSenderV2SentMessageBuilder::new();
+        builder
+            .exclude_recipient(carol_service_id)
+            .add_recipient(bob_service_id, [(bob_device_id, bob_registration_id)]);
+        let sent_message = builder
+            .build(
+                &usmc,
+                &alice_store.session_store,
+                &alice_store.identity_store,
+                &mut rng,
+            )
+            .await
+            .expect("valid");
+
+        let parsed = SealedSenderV2SentMessage::parse(&sent_message)?;
+        assert_eq!(parsed.recipients.len(), 2);
+        assert_eq!(
+            parsed.recipients[&carol_service_id].devices,
+            Vec::<(DeviceId, u16)>::new()
+        );
+        let bob_recipient = &parsed.recipients[&bob_service_id];
+        assert_eq!(
+            bob_recipient.devices,
+            [(
+                bob_device_id,
+                u16::try_from(bob_registration_id).expect("valid")
+            )]
+        );
+
+        let bob_ctext = parsed
+            .received_message_parts_for_recipient(bob_recipient)
+            .as_ref()
+            .concat();
+        let bob_usmc = sealed_sender_decrypt_to_usmc(&bob_ctext, &bob_store.identity_store).await?;
+        assert_eq!(bob_usmc.contents()?, b"sender key message");
+
+        assert_eq!(
+            parsed.excluded_recipients().collect::<Vec<_>>(),
+            [&carol_service_id]
+        );
+        let fanned_out = parsed.fan_out()?;
+        assert_eq!(
+            fanned_out,
+            [SealedSenderV2ReceivedMessage {
+                service_id: bob_service_id,
+                device_id: bob_device_id,
+                registration_id: u16::try_from(bob_registration_id).expect("valid"),
+                message: bob_ctext,
+            }]
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
 #[test]
 fn test_sealed_sender_multi_recipient() -> Result<(), SignalProtocolError> {
     async {
@@ -1119,6 +1442,25 @@ fn test_sealed_sender_multi_recipient_redundant_empty_devices() -> Result<(), Si
         .await?;
         assert!(SealedSenderV2SentMessage::parse(&recipient_excluded_twice).is_err());
 
+        // Listing a device twice parses, but can't be fanned out.
+        let device_listed_twice = sealed_sender_multi_recipient_encrypt(
+            &[&bob_uuid_address, &bob_uuid_address],
+            &alice_store
+                .session_store
+                .load_existing_sessions(&[&bob_uuid_address, &bob_uuid_address])?,
+            [],
+            &alice_usmc,
+            &alice_store.identity_store,
+            &mut csprng,
+        )
+        .await?;
+        let parsed = SealedSenderV2SentMessage::parse(&device_listed_twice)?;
+        assert_eq!(parsed.recipients.len(), 1);
+        assert!(matches!(
+            parsed.fan_out(),
+            Err(SignalProtocolError::InvalidSealedSenderMessage(_))
+        ));
+
         Ok(())
     }
     .now_or_never()
@@ -798,6 +798,73 @@ fn test_optional_one_time_prekey() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn test_prekey_bundle_with_either_kem_type() -> TestResult {
+    for key_type in [kem::KeyType::Kyber1024, kem::KeyType::MLKEM1024] {
+        run(key_type)?;
+    }
+
+    fn run(key_type: kem::KeyType) -> TestResult {
+        async {
+            let mut csprng = OsRng.unwrap_err();
+            let alice_address =
+                ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+            let bob_address =
+                ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+            let mut alice_store = TestStoreBuilder::new().store;
+            let mut bob_store_builder = TestStoreBuilder::new()
+                .with_signed_pre_key(22.into())
+                .with_kyber_pre_key_of_type(8000.into(), key_type);
+
+            let bob_pre_key_bundle =
+                bob_store_builder.make_bundle_with_latest_keys(DeviceId::new(1).unwrap());
+            assert_eq!(
+                bob_pre_key_bundle.kyber_pre_key_public()?.key_type(),
+                key_type
+            );
+
+            process_prekey_bundle(
+                &bob_address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                &bob_pre_key_bundle,
+                SystemTime::now(),
+                &mut csprng,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+            assert_eq!(
+                alice_store.session_version(&bob_address)?,
+                KYBER_AWARE_MESSAGE_VERSION
+            );
+
+            let outgoing_message = encrypt(&mut alice_store, &bob_address, "hi bob").await?;
+            let incoming_message = CiphertextMessage::PreKeySignalMessage(
+                PreKeySignalMessage::try_from(outgoing_message.serialize())?,
+            );
+            let ptext = decrypt(
+                &mut bob_store_builder.store,
+                &alice_address,
+                &incoming_message,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+            assert_eq!(ptext, b"hi bob");
+
+            let reply = encrypt(&mut bob_store_builder.store, &alice_address, "hi alice").await?;
+            let ptext = decrypt(&mut alice_store, &bob_address, &reply, UsePQRatchet::Yes).await?;
+            assert_eq!(ptext, b"hi alice");
+
+            Ok(())
+        }
+        .now_or_never()
+        .expect("sync")
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_basic_session() -> TestResult {
     let (alice_session, bob_session) = initialize_sessions_v4()?;
@@ -3553,3 +3620,1557 @@ fn x3dh_established_session_is_or_is_not_usable() {
     .now_or_never()
     .expect("sync")
 }
+
+#[test]
+fn session_archive_round_trip() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let (alice_session, _bob_session) = initialize_sessions_v4()?;
+
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+        let distribution_id = uuid::Uuid::from_u128(0xd1d1d1d1_7000_11eb_b32a_33b8a8a487a6);
+        let archive_key = [1; 32];
+
+        let mut old_device = test_in_memory_protocol_store()?;
+        old_device
+            .store_session(&bob_address, &alice_session)
+            .await?;
+        old_device
+            .save_identity(
+                &bob_address,
+                &IdentityKey::decode(
+                    &alice_session.remote_identity_key_bytes()?.expect("present"),
+                )?,
+            )
+            .await?;
+        create_sender_key_distribution_message(
+            &alice_address,
+            distribution_id,
+            &mut old_device,
+            &mut csprng,
+        )
+        .await?;
+
+        let archive = export_session_archive(
+            std::slice::from_ref(&bob_address),
+            &[(alice_address.clone(), distribution_id)],
+            &old_device.session_store,
+            &old_device.identity_store,
+            &mut old_device.sender_key_store,
+            &archive_key,
+            &mut csprng,
+        )
+        .await?;
+
+        let mut new_device = InMemSignalProtocolStore::new(
+            old_device.get_identity_key_pair().await?,
+            old_device.get_local_registration_id().await?,
+        )?;
+        let import = import_session_archive(
+            &archive,
+            &archive_key,
+            &mut new_device.session_store,
+            &mut new_device.identity_store,
+            &mut new_device.sender_key_store,
+            ArchiveCollision::KeepExisting,
+        )
+        .await?;
+        assert_eq!(
+            import,
+            SessionArchiveImport {
+                sessions: 1,
+                sender_keys: 1,
+                identities: 1,
+                kept_existing: vec![],
+            }
+        );
+        assert_eq!(
+            new_device
+                .load_session(&bob_address)
+                .await?
+                .expect("imported")
+                .serialize()?,
+            alice_session.serialize()?
+        );
+        assert_eq!(
+            new_device
+                .load_sender_key(&alice_address, distribution_id)
+                .await?
+                .expect("imported")
+                .serialize()?,
+            old_device
+                .load_sender_key(&alice_address, distribution_id)
+                .await?
+                .expect("present")
+                .serialize()?
+        );
+
+        // Importing again keeps what's there, except for the identity, which is unchanged.
+        let import = import_session_archive(
+            &archive,
+            &archive_key,
+            &mut new_device.session_store,
+            &mut new_device.identity_store,
+            &mut new_device.sender_key_store,
+            ArchiveCollision::KeepExisting,
+        )
+        .await?;
+        assert_eq!(
+            import,
+            SessionArchiveImport {
+                sessions: 0,
+                sender_keys: 0,
+                identities: 0,
+                kept_existing: vec![bob_address.clone(), alice_address],
+            }
+        );
+
+        // A device that has since seen a different identity for Bob keeps it, and so can't use
+        // the archived session, which was established with the old one.
+        let mut other_device = InMemSignalProtocolStore::new(
+            old_device.get_identity_key_pair().await?,
+            old_device.get_local_registration_id().await?,
+        )?;
+        other_device
+            .save_identity(
+                &bob_address,
+                IdentityKeyPair::generate(&mut csprng).identity_key(),
+            )
+            .await?;
+        let import = import_session_archive(
+            &archive,
+            &archive_key,
+            &mut other_device.session_store,
+            &mut other_device.identity_store,
+            &mut other_device.sender_key_store,
+            ArchiveCollision::KeepExisting,
+        )
+        .await?;
+        assert_eq!(
+            import,
+            SessionArchiveImport {
+                sessions: 0,
+                sender_keys: 1,
+                identities: 0,
+                kept_existing: vec![bob_address.clone(), bob_address.clone()],
+            }
+        );
+        assert!(other_device.load_session(&bob_address).await?.is_none());
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn migrate_pni_address_to_aci() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let (alice_session, _bob_session) = initialize_sessions_v4()?;
+        let bob_identity =
+            IdentityKey::decode(&alice_session.remote_identity_key_bytes()?.expect("present"))?;
+
+        let bob_pni_address = ProtocolAddress::new(
+            "PNI:2b0ba9d3-0e8b-4a1e-9f0e-6a4ba1d0c2f7".to_owned(),
+            DeviceId::new(1).unwrap(),
+        );
+        let bob_aci_address = ProtocolAddress::new(
+            "796abedb-ca4e-4f18-8803-1fde5b921f9f".to_owned(),
+            DeviceId::new(1).unwrap(),
+        );
+        let distribution_id = uuid::Uuid::from_u128(0xd1d1d1d1_7000_11eb_b32a_33b8a8a487a6);
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        alice_store
+            .store_session(&bob_pni_address, &alice_session)
+            .await?;
+        alice_store
+            .save_identity(&bob_pni_address, &bob_identity)
+            .await?;
+        create_sender_key_distribution_message(
+            &bob_pni_address,
+            distribution_id,
+            &mut alice_store,
+            &mut csprng,
+        )
+        .await?;
+
+        let migrate =
+            |alice_store: &mut InMemSignalProtocolStore, new: &ProtocolAddress, on_collision| {
+                migrate_address(
+                    &bob_pni_address,
+                    new,
+                    &[distribution_id],
+                    &mut alice_store.session_store,
+                    &mut alice_store.identity_store,
+                    &mut alice_store.sender_key_store,
+                    on_collision,
+                )
+                .now_or_never()
+                .expect("sync")
+            };
+
+        assert_eq!(
+            migrate(
+                &mut alice_store,
+                &bob_aci_address,
+                ArchiveCollision::KeepExisting
+            )?,
+            AddressMigration {
+                session: true,
+                identity: true,
+                sender_keys: vec![distribution_id],
+                kept_existing: false,
+            }
+        );
+        assert_eq!(
+            alice_store
+                .load_session(&bob_aci_address)
+                .await?
+                .expect("migrated")
+                .serialize()?,
+            alice_session.serialize()?
+        );
+        assert_eq!(
+            alice_store.get_identity(&bob_aci_address).await?,
+            Some(bob_identity)
+        );
+        assert!(alice_store
+            .load_sender_key(&bob_aci_address, distribution_id)
+            .await?
+            .is_some());
+
+        // An address that already trusts a different identity keeps it, along with its session.
+        let other_address = ProtocolAddress::new(
+            "38381c3b-2606-4ca7-9310-7cb927f2ab4a".to_owned(),
+            DeviceId::new(1).unwrap(),
+        );
+        let other_identity = *IdentityKeyPair::generate(&mut csprng).identity_key();
+        alice_store
+            .save_identity(&other_address, &other_identity)
+            .await?;
+        assert_eq!(
+            migrate(
+                &mut alice_store,
+                &other_address,
+                ArchiveCollision::KeepExisting
+            )?,
+            AddressMigration {
+                session: false,
+                identity: false,
+                sender_keys: vec![distribution_id],
+                kept_existing: true,
+            }
+        );
+        assert!(alice_store.load_session(&other_address).await?.is_none());
+        assert_eq!(
+            alice_store.get_identity(&other_address).await?,
+            Some(other_identity)
+        );
+
+        assert_matches!(
+            migrate(
+                &mut alice_store,
+                &bob_pni_address.clone(),
+                ArchiveCollision::Replace
+            ),
+            Err(SignalProtocolError::InvalidArgument(_))
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn identity_transition_accepted_by_store() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let bob_old_identity = IdentityKeyPair::generate(&mut csprng);
+        let bob_new_identity = IdentityKeyPair::generate(&mut csprng);
+        let transition = bob_old_identity
+            .sign_identity_transition(bob_new_identity.identity_key(), &mut csprng)?;
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+
+        // Nothing to transition from.
+        assert_matches!(
+            alice_store
+                .save_identity_transition(&bob_address, &transition)
+                .await,
+            Err(SignalProtocolError::UntrustedIdentity(address)) if address == bob_address
+        );
+
+        alice_store
+            .save_identity(&bob_address, bob_old_identity.identity_key())
+            .await?;
+        alice_store.identity_store.set_verified(&bob_address, true);
+
+        // A statement not signed by the old identity is rejected.
+        let unrelated = IdentityKeyPair::generate(&mut csprng);
+        let forged =
+            unrelated.sign_identity_transition(bob_new_identity.identity_key(), &mut csprng)?;
+        assert_matches!(
+            alice_store
+                .save_identity_transition(&bob_address, &forged)
+                .await,
+            Err(SignalProtocolError::UntrustedIdentity(_))
+        );
+        // The signature is the last field, so this only corrupts the signature.
+        let mut tampered = transition.serialize().into_vec();
+        *tampered.last_mut().expect("non-empty") ^= 1;
+        let tampered = IdentityKeyTransition::try_from(&tampered[..])?;
+        assert_matches!(
+            alice_store
+                .save_identity_transition(&bob_address, &tampered)
+                .await,
+            Err(SignalProtocolError::SignatureValidationFailed)
+        );
+
+        assert_eq!(
+            alice_store
+                .save_identity_transition(&bob_address, &transition)
+                .await?,
+            IdentityChange::RotatedVerified
+        );
+        for direction in [Direction::Sending, Direction::Receiving] {
+            assert!(
+                alice_store
+                    .is_trusted_identity(&bob_address, bob_new_identity.identity_key(), direction)
+                    .await?
+            );
+        }
+        assert!(alice_store.identity_store.is_verified(&bob_address));
+        assert_eq!(
+            alice_store.get_identity(&bob_address).await?.as_ref(),
+            Some(bob_new_identity.identity_key())
+        );
+
+        // The old statement can't be replayed once the new identity is in place.
+        assert_matches!(
+            alice_store
+                .save_identity_transition(&bob_address, &transition)
+                .await,
+            Err(SignalProtocolError::UntrustedIdentity(_))
+        );
+
+        // An ordinary key change still loses the verified state.
+        assert_eq!(
+            alice_store
+                .save_identity(&bob_address, unrelated.identity_key())
+                .await?,
+            IdentityChange::ReplacedExisting
+        );
+        assert!(!alice_store.identity_store.is_verified(&bob_address));
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn prune_and_compact_archived_sessions() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        // Each new session archives the previous one, a minute after it was created.
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for minutes in 0..4 {
+            let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+            process_prekey_bundle(
+                &bob_address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                &bob_pre_key_bundle,
+                start + Duration::from_secs(60 * minutes),
+                &mut csprng,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+        }
+
+        let mut record = alice_store
+            .load_session(&bob_address)
+            .await?
+            .expect("session found");
+        let original_size = record.serialize()?.len();
+
+        assert_eq!(record.prune(SystemTime::UNIX_EPOCH, usize::MAX), 0);
+        assert_eq!(record.prune(SystemTime::UNIX_EPOCH, 2), 1);
+        assert!(record.serialize()?.len() < original_size);
+        assert_eq!(
+            record.prune(start + Duration::from_secs(150), usize::MAX),
+            1
+        );
+        assert_eq!(
+            record.prune(start + Duration::from_secs(3600), usize::MAX),
+            1
+        );
+        assert_eq!(record.prune(SystemTime::UNIX_EPOCH, 0), 0);
+
+        // The current session is untouched.
+        assert!(record.has_usable_sender_chain(
+            start + Duration::from_secs(3600),
+            SessionUsabilityRequirements::all()
+        )?);
+
+        // Append an unparseable archived state.
+        let mut corrupted = record.serialize()?;
+        let valid_size = corrupted.len();
+        corrupted.extend_from_slice(&[0x12, 3, 0xFF, 0xFF, 0xFF]);
+        let mut record = SessionRecord::deserialize(&corrupted)?;
+        record.compact();
+        assert_eq!(record.serialize()?.len(), valid_size);
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn prune_skipped_message_keys() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let mut messages = vec![];
+        for i in 0..5 {
+            messages.push(encrypt(&mut alice_store, &bob_address, &format!("message {i}")).await?);
+        }
+        decrypt(
+            &mut bob_store,
+            &alice_address,
+            &messages[4],
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let mut record = bob_store
+            .load_session(&alice_address)
+            .await?
+            .expect("present");
+        assert_eq!(record.diagnostics()?.skipped_message_keys, 4);
+        assert_eq!(record.prune_skipped_message_keys(usize::MAX), 0);
+        assert_eq!(record.prune_skipped_message_keys(2), 2);
+        assert_eq!(record.diagnostics()?.skipped_message_keys, 2);
+        bob_store.store_session(&alice_address, &record).await?;
+
+        // The most recently skipped messages can still be decrypted; the oldest can't.
+        assert_eq!(
+            decrypt(
+                &mut bob_store,
+                &alice_address,
+                &messages[3],
+                UsePQRatchet::Yes
+            )
+            .await?,
+            b"message 3"
+        );
+        assert_matches!(
+            decrypt(
+                &mut bob_store,
+                &alice_address,
+                &messages[0],
+                UsePQRatchet::Yes
+            )
+            .await,
+            Err(SignalProtocolError::DuplicatedMessage(..))
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn operations_in_transaction() -> TestResult {
+    #[derive(Default)]
+    struct RecordingTransaction {
+        events: Vec<&'static str>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl TransactionalStores for RecordingTransaction {
+        async fn begin(&mut self) -> Result<(), SignalProtocolError> {
+            self.events.push("begin");
+            Ok(())
+        }
+
+        async fn commit(&mut self) -> Result<(), SignalProtocolError> {
+            self.events.push("commit");
+            Ok(())
+        }
+
+        async fn rollback(&mut self) -> Result<(), SignalProtocolError> {
+            self.events.push("rollback");
+            Ok(())
+        }
+    }
+
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+        let mut alice_transaction = RecordingTransaction::default();
+        let mut bob_transaction = RecordingTransaction::default();
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle_in_transaction(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &mut alice_transaction,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+        assert_eq!(alice_transaction.events, ["begin", "commit"]);
+
+        let message = encrypt(&mut alice_store, &bob_address, "hi bob").await?;
+        let decrypt_in_transaction =
+            |bob_store: &mut InMemSignalProtocolStore,
+             bob_transaction: &mut RecordingTransaction,
+             message: &CiphertextMessage| {
+                let mut csprng = OsRng.unwrap_err();
+                message_decrypt_in_transaction(
+                    message,
+                    &alice_address,
+                    &mut bob_store.session_store,
+                    &mut bob_store.identity_store,
+                    &mut bob_store.pre_key_store,
+                    &bob_store.signed_pre_key_store,
+                    &mut bob_store.kyber_pre_key_store,
+                    bob_transaction,
+                    &mut csprng,
+                    UsePQRatchet::Yes,
+                )
+                .now_or_never()
+                .expect("sync")
+            };
+
+        assert_eq!(
+            decrypt_in_transaction(&mut bob_store, &mut bob_transaction, &message)?,
+            b"hi bob"
+        );
+        assert_eq!(bob_transaction.events, ["begin", "commit"]);
+
+        // Replaying the message fails, and so the transaction is rolled back.
+        assert!(decrypt_in_transaction(&mut bob_store, &mut bob_transaction, &message).is_err());
+        assert_eq!(
+            bob_transaction.events,
+            ["begin", "commit", "begin", "rollback"]
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn session_diagnostics() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let no_session = SessionRecord::new_fresh().diagnostics()?;
+        assert_eq!(no_session.session_version, None);
+        assert_eq!(no_session.archived_state_count, 0);
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let diagnostics = alice_store
+            .load_session(&bob_address)
+            .await?
+            .expect("present")
+            .diagnostics()?;
+        assert_eq!(
+            diagnostics,
+            SessionDiagnostics {
+                session_version: Some(KYBER_AWARE_MESSAGE_VERSION),
+                sender_chain_length: 0,
+                receiver_chain_lengths: vec![],
+                skipped_message_keys: 0,
+                unacknowledged: true,
+                last_used: None,
+                archived_state_count: 0,
+            }
+        );
+
+        let sent_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut messages = vec![];
+        for i in 0..3 {
+            messages.push(
+                message_encrypt(
+                    format!("message {i}").as_bytes(),
+                    &bob_address,
+                    &mut alice_store.session_store,
+                    &mut alice_store.identity_store,
+                    sent_at,
+                    &mut csprng,
+                )
+                .await?,
+            );
+        }
+
+        let diagnostics = alice_store
+            .load_session(&bob_address)
+            .await?
+            .expect("present")
+            .diagnostics()?;
+        assert_eq!(diagnostics.sender_chain_length, 3);
+        assert_eq!(diagnostics.last_used, Some(sent_at));
+
+        // Bob only receives the last message, so he keeps keys for the first two.
+        decrypt(
+            &mut bob_store,
+            &alice_address,
+            messages.last().expect("sent"),
+            UsePQRatchet::Yes,
+        )
+        .await?;
+        let diagnostics = bob_store
+            .load_session(&alice_address)
+            .await?
+            .expect("present")
+            .diagnostics()?;
+        assert_eq!(diagnostics.receiver_chain_lengths, [3]);
+        assert_eq!(diagnostics.skipped_message_keys, 2);
+        assert!(!diagnostics.unacknowledged);
+        assert!(diagnostics.last_used.is_some());
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn session_store_limits() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let limits = ProtocolLimits::default()
+            .with_max_message_keys(10)?
+            .with_max_archived_states(1)?;
+        alice_store.session_store.set_limits(limits);
+        bob_store.session_store.set_limits(limits);
+
+        for _ in 0..3 {
+            let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+            process_prekey_bundle(
+                &bob_address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                &bob_pre_key_bundle,
+                SystemTime::now(),
+                &mut csprng,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+        }
+        let alice_session = alice_store
+            .load_session(&bob_address)
+            .await?
+            .expect("present");
+        assert_eq!(alice_session.diagnostics()?.archived_state_count, 1);
+
+        let mut messages = vec![];
+        for i in 0..20 {
+            messages.push(encrypt(&mut alice_store, &bob_address, &format!("message {i}")).await?);
+        }
+        decrypt(
+            &mut bob_store,
+            &alice_address,
+            &messages[19],
+            UsePQRatchet::Yes,
+        )
+        .await?;
+        let bob_session = bob_store
+            .load_session(&alice_address)
+            .await?
+            .expect("present");
+        assert_eq!(bob_session.diagnostics()?.skipped_message_keys, 10);
+
+        // Only the ten most recent skipped messages can still be decrypted.
+        assert_eq!(
+            decrypt(
+                &mut bob_store,
+                &alice_address,
+                &messages[9],
+                UsePQRatchet::Yes
+            )
+            .await?,
+            b"message 9"
+        );
+        assert_matches!(
+            decrypt(
+                &mut bob_store,
+                &alice_address,
+                &messages[8],
+                UsePQRatchet::Yes
+            )
+            .await,
+            Err(SignalProtocolError::DuplicatedMessage(20, 8))
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn default_archived_state_limit() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let default_limit = ProtocolLimits::default().max_archived_states();
+        for _ in 0..=default_limit {
+            let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+            process_prekey_bundle(
+                &bob_address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                &bob_pre_key_bundle,
+                SystemTime::now(),
+                &mut csprng,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+        }
+        let mut alice_session = alice_store
+            .load_session(&bob_address)
+            .await?
+            .expect("present");
+        assert_eq!(
+            alice_session.diagnostics()?.archived_state_count,
+            default_limit
+        );
+
+        // Archiving directly, without a store, uses the same default limit.
+        alice_session.archive_current_state()?;
+        assert_eq!(
+            alice_session.diagnostics()?.archived_state_count,
+            default_limit
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn retry_receipt_workflow() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+        let message = encrypt(&mut alice_store, &bob_address, "hello").await?;
+        decrypt(&mut bob_store, &alice_address, &message, UsePQRatchet::Yes).await?;
+        let reply = encrypt(&mut bob_store, &alice_address, "hi").await?;
+        decrypt(&mut alice_store, &bob_address, &reply, UsePQRatchet::Yes).await?;
+
+        // Corrupt a message so that Bob can't decrypt it.
+        let message = match encrypt(&mut alice_store, &bob_address, "lost").await? {
+            CiphertextMessage::SignalMessage(message) => message,
+            other => panic!("unexpected message type {:?}", other.message_type()),
+        };
+        let mut corrupted = message.serialized().to_vec();
+        *corrupted.last_mut().expect("not empty") ^= 1;
+        let err = message_decrypt_signal(
+            &SignalMessage::try_from(corrupted.as_slice())?,
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut bob_store.identity_store,
+            &mut csprng,
+        )
+        .await
+        .expect_err("corrupted");
+        let now = SystemTime::now();
+
+        // A failure on Bob's side gets a receipt, but isn't a reason to archive the session.
+        let response = handle_decryption_failure(
+            &SignalProtocolError::InvalidState("load_session", "store unavailable".to_owned()),
+            &corrupted,
+            CiphertextMessageType::Whisper,
+            Timestamp::from_epoch_millis(500),
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut RetryReceiptTracker::new(),
+            now,
+        )
+        .await?
+        .expect("should respond");
+        assert!(!response.session_archived);
+        assert_eq!(
+            bob_store
+                .load_session(&alice_address)
+                .await?
+                .expect("present")
+                .diagnostics()?
+                .archived_state_count,
+            0
+        );
+
+        let mut tracker = RetryReceiptTracker::new();
+        let original_timestamp = Timestamp::from_epoch_millis(1000);
+        let response = handle_decryption_failure(
+            &err,
+            &corrupted,
+            CiphertextMessageType::Whisper,
+            original_timestamp,
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut tracker,
+            now,
+        )
+        .await?
+        .expect("should respond");
+        assert!(response.session_archived);
+        let diagnostics = bob_store
+            .load_session(&alice_address)
+            .await?
+            .expect("present")
+            .diagnostics()?;
+        assert_eq!(diagnostics.session_version, None);
+        assert_eq!(diagnostics.archived_state_count, 1);
+
+        // The same message doesn't get a second receipt...
+        assert!(handle_decryption_failure(
+            &err,
+            &corrupted,
+            CiphertextMessageType::Whisper,
+            original_timestamp,
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut tracker,
+            now,
+        )
+        .await?
+        .is_none());
+        // ...and another failure soon after doesn't archive again.
+        let response_for_next = handle_decryption_failure(
+            &err,
+            &corrupted,
+            CiphertextMessageType::Whisper,
+            Timestamp::from_epoch_millis(2000),
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut tracker,
+            now,
+        )
+        .await?
+        .expect("should respond");
+        assert!(!response_for_next.session_archived);
+
+        // Duplicates aren't worth a receipt at all.
+        assert!(handle_decryption_failure(
+            &SignalProtocolError::DuplicatedMessage(2, 1),
+            &corrupted,
+            CiphertextMessageType::Whisper,
+            Timestamp::from_epoch_millis(3000),
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut tracker,
+            now,
+        )
+        .await?
+        .is_none());
+
+        // Alice archives her side too, so her resend starts a new session.
+        let receipt = DecryptionErrorMessage::try_from(response.retry_receipt.serialized())?;
+        assert_eq!(receipt.timestamp(), original_timestamp);
+        assert!(
+            handle_retry_receipt(&receipt, &bob_address, &mut alice_store.session_store, now)
+                .await?
+        );
+        assert_eq!(
+            alice_store
+                .load_session(&bob_address)
+                .await?
+                .expect("present")
+                .diagnostics()?
+                .session_version,
+            None
+        );
+        // A second copy of the receipt no longer matches the current session.
+        assert!(
+            !handle_retry_receipt(&receipt, &bob_address, &mut alice_store.session_store, now)
+                .await?
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn prekey_bundle_verify_batch() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let mut bundles = vec![];
+        for _ in 0..5 {
+            let mut store = test_in_memory_protocol_store()?;
+            bundles.push(create_pre_key_bundle(&mut store, &mut csprng).await?);
+        }
+
+        let results = PreKeyBundle::verify_batch(bundles.clone(), &mut csprng);
+        assert_eq!(results.len(), bundles.len());
+        assert!(results.iter().all(Result::is_ok));
+        assert!(PreKeyBundle::verify_batch(vec![], &mut csprng).is_empty());
+
+        bundles[1] = bundles[1].clone().modify(|content| {
+            let signature = content
+                .kyber_pre_key_signature
+                .as_mut()
+                .expect("has signature");
+            signature[0] ^= 1;
+        })?;
+        // Signatures from the wrong identity.
+        let other_identity = *bundles[0].identity_key()?;
+        bundles[3] = bundles[3].clone().modify(|content| {
+            content.identity_key = Some(other_identity);
+        })?;
+
+        let results = PreKeyBundle::verify_batch(bundles, &mut csprng);
+        for (i, result) in results.iter().enumerate() {
+            if i == 1 || i == 3 {
+                assert_matches!(result, Err(SignalProtocolError::SignatureValidationFailed));
+            } else {
+                assert_matches!(result, Ok(_));
+            }
+        }
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn process_verified_prekey_bundle_establishes_session() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        let [Ok(verified)] = &PreKeyBundle::verify_batch(vec![bob_pre_key_bundle], &mut csprng)[..]
+        else {
+            panic!("bundle should verify");
+        };
+        process_verified_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            verified,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let message = encrypt(&mut alice_store, &bob_address, "hello").await?;
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &message, UsePQRatchet::Yes).await?,
+            b"hello"
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn prekey_inventory() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let mut store = test_in_memory_protocol_store()?;
+        let identity_key_pair = store.get_identity_key_pair().await?;
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut inventory = PreKeyInventory::new(PreKeyInventory::MAX_ID - 1, 7, 100)?;
+        assert_eq!(
+            inventory
+                .report(&store, &store.signed_pre_key_store, now)
+                .await?,
+            PreKeyInventoryReport {
+                unused_pre_keys: 0,
+                unused_kyber_pre_keys: 0,
+                signed_pre_keys_due_for_rotation: vec![],
+            }
+        );
+
+        let batch = inventory.generate(&identity_key_pair, 5, true, now, &mut csprng)?;
+        batch
+            .save(
+                &mut store.pre_key_store,
+                &mut store.signed_pre_key_store,
+                &mut store.kyber_pre_key_store,
+            )
+            .await?;
+        assert_eq!(inventory.next_pre_key_id(), 4);
+        assert_eq!(inventory.next_signed_pre_key_id(), 8);
+        assert_eq!(inventory.next_kyber_pre_key_id(), 105);
+
+        let upload = batch.upload()?;
+        let pre_key_ids: Vec<u32> = upload.pre_keys.iter().map(|(id, _)| (*id).into()).collect();
+        assert_eq!(
+            pre_key_ids,
+            [
+                PreKeyInventory::MAX_ID - 1,
+                PreKeyInventory::MAX_ID,
+                1,
+                2,
+                3
+            ]
+        );
+        let (signed_pre_key_id, signed_pre_key_public, signed_pre_key_signature) =
+            upload.signed_pre_key.clone().expect("generated");
+        assert_eq!(signed_pre_key_id, SignedPreKeyId::from(7));
+        let (kyber_pre_key_id, kyber_pre_key_public, kyber_pre_key_signature) =
+            upload.kyber_pre_keys[0].clone();
+        assert_eq!(kyber_pre_key_id, KyberPreKeyId::from(100));
+
+        // The uploaded keys make a valid bundle.
+        let bundle = PreKeyBundle::new(
+            store.get_local_registration_id().await?,
+            DeviceId::new(1).unwrap(),
+            Some(upload.pre_keys[0]),
+            signed_pre_key_id,
+            signed_pre_key_public,
+            signed_pre_key_signature,
+            kyber_pre_key_id,
+            kyber_pre_key_public,
+            kyber_pre_key_signature,
+            *identity_key_pair.identity_key(),
+        )?;
+        assert_matches!(
+            PreKeyBundle::verify_batch(vec![bundle], &mut csprng)[..],
+            [Ok(_)]
+        );
+
+        let report = inventory
+            .report(&store, &store.signed_pre_key_store, now)
+            .await?;
+        assert_eq!(report.unused_pre_keys, 5);
+        assert_eq!(report.unused_kyber_pre_keys, 5);
+        assert!(report.signed_pre_keys_due_for_rotation.is_empty());
+
+        let later = now + PreKeyInventory::DEFAULT_SIGNED_PRE_KEY_ROTATION_AGE;
+        let report = inventory
+            .report(&store, &store.signed_pre_key_store, later)
+            .await?;
+        assert_eq!(
+            report.signed_pre_keys_due_for_rotation,
+            [SignedPreKeyId::from(7)]
+        );
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn padded_messages() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let schemes: [&dyn PaddingScheme; 3] =
+            [&NoPadding, &BlockPadding::default(), &PadmePadding];
+        for scheme in schemes {
+            let ptext = b"a message of some length";
+            let ctext = message_encrypt_padded(
+                ptext,
+                scheme,
+                &bob_address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                SystemTime::now(),
+                &mut csprng,
+            )
+            .await?;
+            let decrypted = message_decrypt_padded(
+                &ctext,
+                &alice_address,
+                &mut bob_store.session_store,
+                &mut bob_store.identity_store,
+                &mut bob_store.pre_key_store,
+                &bob_store.signed_pre_key_store,
+                &mut bob_store.kyber_pre_key_store,
+                &mut csprng,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+            assert_eq!(decrypted, ptext);
+        }
+
+        // Plain decryption leaves the padding in place.
+        let ctext = message_encrypt_padded(
+            b"hi",
+            &BlockPadding::default(),
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            SystemTime::now(),
+            &mut csprng,
+        )
+        .await?;
+        let padded = decrypt(&mut bob_store, &alice_address, &ctext, UsePQRatchet::Yes).await?;
+        assert_eq!(padded.len(), BlockPadding::DEFAULT_BLOCK_SIZE);
+        assert_eq!(strip_padding(&padded)?, b"hi");
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn franked_messages() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let (first, first_tag) = message_encrypt_franked(
+            b"first",
+            &alice_address,
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            SystemTime::now(),
+            &mut csprng,
+        )
+        .await?;
+        let (second, _second_tag) = message_encrypt_franked(
+            b"second",
+            &alice_address,
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            SystemTime::now(),
+            &mut csprng,
+        )
+        .await?;
+
+        let (decrypted, franking_key) = message_decrypt_franked(
+            &first,
+            &first_tag,
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut bob_store.identity_store,
+            &mut bob_store.pre_key_store,
+            &bob_store.signed_pre_key_store,
+            &mut bob_store.kyber_pre_key_store,
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+        assert_eq!(decrypted, b"first");
+        assert!(first_tag.verify(&franking_key, &alice_address, first.serialize(), &decrypted));
+
+        // A tag for a different message is rejected.
+        let result = message_decrypt_franked(
+            &second,
+            &first_tag,
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut bob_store.identity_store,
+            &mut bob_store.pre_key_store,
+            &bob_store.signed_pre_key_store,
+            &mut bob_store.kyber_pre_key_store,
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await;
+        assert_matches!(result, Err(SignalProtocolError::InvalidMessage(..)));
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn decrypt_into_buffer() -> TestResult {
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let ptext = "a message for a caller-provided buffer";
+        for _ in 0..2 {
+            let ctext = encrypt(&mut alice_store, &bob_address, ptext).await?;
+
+            // Too small a buffer fails without consuming the message.
+            let mut buffer = [0u8; 8];
+            assert_matches!(
+                message_decrypt_into(
+                    &ctext,
+                    &alice_address,
+                    &mut bob_store.session_store,
+                    &mut bob_store.identity_store,
+                    &mut bob_store.pre_key_store,
+                    &bob_store.signed_pre_key_store,
+                    &mut bob_store.kyber_pre_key_store,
+                    &mut buffer,
+                    &mut csprng,
+                    UsePQRatchet::Yes,
+                )
+                .await,
+                Err(SignalProtocolError::InvalidArgument(_))
+            );
+
+            let mut buffer = [0u8; 256];
+            let len = message_decrypt_into(
+                &ctext,
+                &alice_address,
+                &mut bob_store.session_store,
+                &mut bob_store.identity_store,
+                &mut bob_store.pre_key_store,
+                &bob_store.signed_pre_key_store,
+                &mut bob_store.kyber_pre_key_store,
+                &mut buffer,
+                &mut csprng,
+                UsePQRatchet::Yes,
+            )
+            .await?;
+            assert_eq!(&buffer[..len], ptext.as_bytes());
+        }
+
+        // Reply so the next message is a plain SignalMessage.
+        let reply = encrypt(&mut bob_store, &alice_address, "reply").await?;
+        decrypt(&mut alice_store, &bob_address, &reply, UsePQRatchet::Yes).await?;
+        let ctext = encrypt(&mut alice_store, &bob_address, ptext).await?;
+        assert_eq!(ctext.message_type(), CiphertextMessageType::Whisper);
+        let CiphertextMessage::SignalMessage(message) = &ctext else {
+            unreachable!("checked above");
+        };
+        let mut buffer = vec![0; message.body().len()];
+        let len = message_decrypt_signal_into(
+            message,
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut bob_store.identity_store,
+            &mut buffer,
+            &mut csprng,
+        )
+        .await?;
+        assert_eq!(&buffer[..len], ptext.as_bytes());
+
+        // If the sender turns out to be untrusted after decryption, the plaintext isn't left in
+        // the buffer.
+        let ctext = encrypt(&mut alice_store, &bob_address, ptext).await?;
+        let CiphertextMessage::SignalMessage(message) = &ctext else {
+            panic!("expected a SignalMessage, got {:?}", ctext.message_type());
+        };
+        bob_store
+            .save_identity(
+                &alice_address,
+                IdentityKeyPair::generate(&mut csprng).identity_key(),
+            )
+            .await?;
+        let mut buffer = vec![0xAA; message.body().len()];
+        assert_matches!(
+            message_decrypt_signal_into(
+                message,
+                &alice_address,
+                &mut bob_store.session_store,
+                &mut bob_store.identity_store,
+                &mut buffer,
+                &mut csprng,
+            )
+            .await,
+            Err(SignalProtocolError::UntrustedIdentity(_))
+        );
+        assert!(buffer.iter().all(|&b| b == 0));
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
+
+#[test]
+fn identity_key_in_platform_keystore() -> TestResult {
+    /// Stands in for a key held in a platform keystore, which can be used but never read out.
+    struct KeystoreSigner(IdentityKeyPair);
+
+    impl PrivateKeySigner for KeystoreSigner {
+        fn public_key(&self) -> &PublicKey {
+            self.0.public_key()
+        }
+
+        fn calculate_signature_for_multipart_message(
+            &self,
+            message: &[&[u8]],
+            rng: &mut dyn rand::CryptoRng,
+        ) -> Result<Box<[u8]>, SignalProtocolError> {
+            PrivateKeySigner::calculate_signature_for_multipart_message(&self.0, message, rng)
+        }
+
+        fn calculate_agreement(
+            &self,
+            their_key: &PublicKey,
+        ) -> Result<Box<[u8]>, SignalProtocolError> {
+            PrivateKeySigner::calculate_agreement(&self.0, their_key)
+        }
+    }
+
+    struct KeystoreIdentityStore(InMemIdentityKeyStore);
+
+    #[async_trait::async_trait(?Send)]
+    impl IdentityKeyStore for KeystoreIdentityStore {
+        async fn get_identity_key_pair(&self) -> Result<IdentityKeyPair, SignalProtocolError> {
+            Err(SignalProtocolError::InvalidState(
+                "get_identity_key_pair",
+                "private key cannot be exported".to_string(),
+            ))
+        }
+
+        async fn get_identity_key_signer(
+            &self,
+        ) -> Result<Box<dyn PrivateKeySigner>, SignalProtocolError> {
+            Ok(Box::new(KeystoreSigner(
+                self.0.get_identity_key_pair().await?,
+            )))
+        }
+
+        async fn get_local_registration_id(&self) -> Result<u32, SignalProtocolError> {
+            self.0.get_local_registration_id().await
+        }
+
+        async fn save_identity(
+            &mut self,
+            address: &ProtocolAddress,
+            identity: &IdentityKey,
+        ) -> Result<IdentityChange, SignalProtocolError> {
+            self.0.save_identity(address, identity).await
+        }
+
+        async fn is_trusted_identity(
+            &self,
+            address: &ProtocolAddress,
+            identity: &IdentityKey,
+            direction: Direction,
+        ) -> Result<bool, SignalProtocolError> {
+            self.0
+                .is_trusted_identity(address, identity, direction)
+                .await
+        }
+
+        async fn get_identity(
+            &self,
+            address: &ProtocolAddress,
+        ) -> Result<Option<IdentityKey>, SignalProtocolError> {
+            self.0.get_identity(address).await
+        }
+    }
+
+    async {
+        let mut csprng = OsRng.unwrap_err();
+        let alice_address =
+            ProtocolAddress::new("+14151111111".to_owned(), DeviceId::new(1).unwrap());
+        let bob_address =
+            ProtocolAddress::new("+14151111112".to_owned(), DeviceId::new(1).unwrap());
+
+        let mut alice_store = test_in_memory_protocol_store()?;
+        let mut alice_identity_store = KeystoreIdentityStore(alice_store.identity_store.clone());
+        let mut bob_store = test_in_memory_protocol_store()?;
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_identity_store,
+            &bob_pre_key_bundle,
+            SystemTime::now(),
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+
+        let message = message_encrypt(
+            b"hi bob",
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_identity_store,
+            SystemTime::now(),
+            &mut csprng,
+        )
+        .await?;
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &message, UsePQRatchet::Yes).await?,
+            b"hi bob"
+        );
+
+        let reply = encrypt(&mut bob_store, &alice_address, "hi alice").await?;
+        let decrypted = message_decrypt(
+            &reply,
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_identity_store,
+            &mut alice_store.pre_key_store,
+            &alice_store.signed_pre_key_store,
+            &mut alice_store.kyber_pre_key_store,
+            &mut csprng,
+            UsePQRatchet::Yes,
+        )
+        .await?;
+        assert_eq!(decrypted, b"hi alice");
+
+        Ok(())
+    }
+    .now_or_never()
+    .expect("sync")
+}
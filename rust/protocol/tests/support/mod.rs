@@ -296,6 +296,19 @@ impl TestStoreBuilder {
     }
 
     pub fn add_kyber_pre_key(&mut self, id_choice: IdChoice) {
+        self.add_kyber_pre_key_of_type(id_choice, kem::KeyType::Kyber1024);
+    }
+
+    pub fn with_kyber_pre_key_of_type(
+        mut self,
+        id_choice: IdChoice,
+        key_type: kem::KeyType,
+    ) -> Self {
+        self.add_kyber_pre_key_of_type(id_choice, key_type);
+        self
+    }
+
+    pub fn add_kyber_pre_key_of_type(&mut self, id_choice: IdChoice, key_type: kem::KeyType) {
         let id = self.gen_id(id_choice);
         if let Some(latest_id) = self.store.all_kyber_pre_key_ids().last() {
             assert!(
@@ -303,7 +316,7 @@ impl TestStoreBuilder {
                 "Signed pre key ids should be increasing"
             );
         }
-        let pair = kem::KeyPair::generate(kem::KeyType::Kyber1024, &mut self.rng);
+        let pair = kem::KeyPair::generate(key_type, &mut self.rng);
         let public = pair.public_key.serialize();
         let signature = self.sign(&public);
         let record = KyberPreKeyRecord::new(
@@ -54,6 +54,7 @@ pub mod credentials;
 pub mod endorsements;
 pub mod issuance;
 pub mod presentation;
+pub mod schema;
 pub mod sho;
 
 /// Helper type for implementing [`std::fmt::Debug`].
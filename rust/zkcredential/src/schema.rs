@@ -0,0 +1,432 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Declarative definitions of credential types.
+//!
+//! The proof builders in [`issuance`](crate::issuance) and [`presentation`](crate::presentation)
+//! are order-sensitive: the issuing server, the client, and the verifying server all have to add
+//! the same attributes in the same order under the same label, or the proofs will fail to verify.
+//! A [`CredentialSchema`] describes a credential's shape once, and provides issuance, reception,
+//! presentation, and verification operations that are guaranteed to agree with one another.
+//!
+//! Schemas cover the common case of [public attributes](PublicAttribute) plus
+//! [encrypted attributes](crate::attributes::Attribute). Credentials that need [blind
+//! issuance](crate::issuance::blind) or [revealed attributes](crate::attributes::RevealedAttribute)
+//! should use the builders directly.
+//!
+//! # Example
+//!
+//! ```
+//! # use curve25519_dalek::RistrettoPoint;
+//! use zkcredential::attributes::{Domain, KeyPair};
+//! use zkcredential::credentials::CredentialKeyPair;
+//! use zkcredential::schema::CredentialSchema;
+//!
+//! # type UserId = [RistrettoPoint; 2];
+//! struct UserIdEncryption;
+//! impl Domain for UserIdEncryption {
+//!     type Attribute = UserId;
+//!     const ID: &'static str = "MyCompany_UserIdEncryption_20231011";
+//!
+//!     fn G_a() -> [RistrettoPoint; 2] {
+//!         static STORAGE: std::sync::OnceLock<[RistrettoPoint; 2]> = std::sync::OnceLock::new();
+//!         *zkcredential::attributes::derive_default_generator_points::<Self>(&STORAGE)
+//!     }
+//! }
+//!
+//! /// Grants a role (as a public `u32`) to a user in some room.
+//! struct RoomRoleCredential;
+//! impl CredentialSchema for RoomRoleCredential {
+//!     const LABEL: &'static [u8] = b"MyCompany_RoomRoleCredential_20250101";
+//!     type PublicAttributes = (u64, u32);
+//!     type EncryptedAttributes = (UserIdEncryption,);
+//! }
+//!
+//! # let user_id: UserId = [RistrettoPoint::default(); 2];
+//! let server_key = CredentialKeyPair::generate([1; 32]);
+//! let redemption_day = 1_700_006_400u64;
+//! let role = 2u32;
+//!
+//! // Issuing server
+//! let response =
+//!     RoomRoleCredential::issue(&server_key, &(redemption_day, role), &(user_id,), [2; 32]);
+//!
+//! // Client
+//! let credential = RoomRoleCredential::receive(
+//!     server_key.public_key(),
+//!     &(redemption_day, role),
+//!     &(user_id,),
+//!     response,
+//! )
+//! .unwrap();
+//! let room_keys = (KeyPair::<UserIdEncryption>::derive_from(
+//!     &mut poksho::ShoHmacSha256::new(b"room secret"),
+//! ),);
+//! let presentation = RoomRoleCredential::present(
+//!     server_key.public_key(),
+//!     &credential,
+//!     &(user_id,),
+//!     &room_keys,
+//!     [3; 32],
+//! );
+//! let ciphertexts = RoomRoleCredential::encrypt(&(user_id,), &room_keys);
+//!
+//! // Verifying server
+//! let room_public_keys = (room_keys.0.public_key,);
+//! RoomRoleCredential::verify(
+//!     &server_key,
+//!     &(redemption_day, role),
+//!     &ciphertexts,
+//!     &room_public_keys,
+//!     &presentation,
+//! )
+//! .unwrap();
+//! ```
+
+use crate::attributes::{self, Ciphertext, Domain, KeyPair, PublicAttribute};
+use crate::credentials::{Credential, CredentialKeyPair, CredentialPublicKey};
+use crate::issuance::{IssuanceProof, IssuanceProofBuilder};
+use crate::presentation::{PresentationProof, PresentationProofBuilder, PresentationProofVerifier};
+use crate::{VerificationFailure, RANDOMNESS_LEN};
+
+/// Describes the attributes of a credential type.
+///
+/// Implementors only provide the associated items; the provided functions should not be
+/// overridden, since their consistency with each other is the point of this trait.
+pub trait CredentialSchema {
+    /// Uniquely identifies this credential type.
+    ///
+    /// Used as the label for every proof; make sure it's unique!
+    const LABEL: &'static [u8];
+
+    /// The attributes known to the issuing server, the client, and the verifying server.
+    ///
+    /// This is a tuple of [`PublicAttribute`]s (possibly empty).
+    type PublicAttributes: PublicAttributes;
+
+    /// The attributes hidden from the verifying server by encrypting them.
+    ///
+    /// This is a tuple of [`Domain`]s, one for each attribute (possibly empty).
+    type EncryptedAttributes: EncryptedAttributes;
+
+    /// Issues a credential over the given attributes.
+    ///
+    /// See [`IssuanceProofBuilder::issue`].
+    fn issue(
+        key_pair: &CredentialKeyPair,
+        public_attrs: &Self::PublicAttributes,
+        attrs: &<Self::EncryptedAttributes as EncryptedAttributes>::Values,
+        randomness: [u8; RANDOMNESS_LEN],
+    ) -> IssuanceProof {
+        let builder = public_attrs.add_to_issuance(IssuanceProofBuilder::new(Self::LABEL));
+        Self::EncryptedAttributes::add_to_issuance(attrs, builder).issue(key_pair, randomness)
+    }
+
+    /// Validates an issued credential against the expected attributes, and extracts it.
+    ///
+    /// See [`IssuanceProofBuilder::verify`].
+    fn receive(
+        public_key: &CredentialPublicKey,
+        public_attrs: &Self::PublicAttributes,
+        attrs: &<Self::EncryptedAttributes as EncryptedAttributes>::Values,
+        proof: IssuanceProof,
+    ) -> Result<Credential, VerificationFailure> {
+        let builder = public_attrs.add_to_issuance(IssuanceProofBuilder::new(Self::LABEL));
+        Self::EncryptedAttributes::add_to_issuance(attrs, builder).verify(public_key, proof)
+    }
+
+    /// Generates a presentation of `credential`, proving that `attrs` are correctly encrypted with
+    /// `key_pairs`.
+    ///
+    /// The verifying server will also need the encrypted attributes; see [`Self::encrypt`].
+    ///
+    /// See [`PresentationProofBuilder::present`].
+    fn present(
+        public_key: &CredentialPublicKey,
+        credential: &Credential,
+        attrs: &<Self::EncryptedAttributes as EncryptedAttributes>::Values,
+        key_pairs: &<Self::EncryptedAttributes as EncryptedAttributes>::KeyPairs,
+        randomness: [u8; RANDOMNESS_LEN],
+    ) -> PresentationProof {
+        // Public attributes aren't needed to generate a presentation.
+        Self::EncryptedAttributes::add_to_presentation(
+            attrs,
+            key_pairs,
+            PresentationProofBuilder::new(Self::LABEL),
+        )
+        .present(public_key, credential, randomness)
+    }
+
+    /// Encrypts `attrs` with `key_pairs`, to be sent along with a presentation.
+    fn encrypt(
+        attrs: &<Self::EncryptedAttributes as EncryptedAttributes>::Values,
+        key_pairs: &<Self::EncryptedAttributes as EncryptedAttributes>::KeyPairs,
+    ) -> <Self::EncryptedAttributes as EncryptedAttributes>::Ciphertexts {
+        Self::EncryptedAttributes::encrypt(attrs, key_pairs)
+    }
+
+    /// Verifies a presentation against the given public attributes and encrypted attributes.
+    ///
+    /// See [`PresentationProofVerifier::verify`].
+    fn verify(
+        key_pair: &CredentialKeyPair,
+        public_attrs: &Self::PublicAttributes,
+        ciphertexts: &<Self::EncryptedAttributes as EncryptedAttributes>::Ciphertexts,
+        public_keys: &<Self::EncryptedAttributes as EncryptedAttributes>::PublicKeys,
+        proof: &PresentationProof,
+    ) -> Result<(), VerificationFailure> {
+        let verifier =
+            public_attrs.add_to_verification(PresentationProofVerifier::new(Self::LABEL));
+        Self::EncryptedAttributes::add_to_verification(ciphertexts, public_keys, verifier)
+            .verify(key_pair, proof)
+    }
+}
+
+/// A tuple of [`PublicAttribute`]s, added to proofs in order.
+///
+/// Implemented for tuples of up to four attributes.
+pub trait PublicAttributes {
+    /// Adds each attribute to `builder`, in order.
+    fn add_to_issuance<'a>(&self, builder: IssuanceProofBuilder<'a>) -> IssuanceProofBuilder<'a>;
+
+    /// Adds each attribute to `verifier`, in order.
+    fn add_to_verification<'a>(
+        &self,
+        verifier: PresentationProofVerifier<'a>,
+    ) -> PresentationProofVerifier<'a>;
+}
+
+macro_rules! impl_public_attributes_for_tuple {
+    ($($T:ident: $i:tt),*) => {
+        impl<$($T: PublicAttribute),*> PublicAttributes for ($($T,)*) {
+            #[allow(unused_mut)]
+            fn add_to_issuance<'a>(
+                &self,
+                mut builder: IssuanceProofBuilder<'a>,
+            ) -> IssuanceProofBuilder<'a> {
+                $(builder = builder.add_public_attribute(&self.$i);)*
+                builder
+            }
+
+            #[allow(unused_mut)]
+            fn add_to_verification<'a>(
+                &self,
+                mut verifier: PresentationProofVerifier<'a>,
+            ) -> PresentationProofVerifier<'a> {
+                $(verifier = verifier.add_public_attribute(&self.$i);)*
+                verifier
+            }
+        }
+    };
+}
+
+impl_public_attributes_for_tuple!();
+impl_public_attributes_for_tuple!(A: 0);
+impl_public_attributes_for_tuple!(A: 0, B: 1);
+impl_public_attributes_for_tuple!(A: 0, B: 1, C: 2);
+impl_public_attributes_for_tuple!(A: 0, B: 1, C: 2, D: 3);
+
+/// A tuple of [`Domain`]s, describing attributes that are encrypted for the verifying server.
+///
+/// Implemented for tuples of up to three domains, the most that fit in a credential.
+pub trait EncryptedAttributes {
+    /// The attribute values, as a tuple of each domain's [`Domain::Attribute`].
+    type Values;
+    /// The keys used to encrypt the attributes, as a tuple of [`KeyPair`]s.
+    type KeyPairs;
+    /// The keys used to validate the encrypted attributes, as a tuple of
+    /// [`PublicKey`](attributes::PublicKey)s.
+    type PublicKeys;
+    /// The encrypted attributes, as a tuple of [`Ciphertext`]s.
+    type Ciphertexts;
+
+    /// Adds each attribute to `builder`, in order.
+    fn add_to_issuance<'a>(
+        values: &Self::Values,
+        builder: IssuanceProofBuilder<'a>,
+    ) -> IssuanceProofBuilder<'a>;
+
+    /// Adds each attribute to `builder` along with its key, in order.
+    fn add_to_presentation<'a>(
+        values: &Self::Values,
+        key_pairs: &Self::KeyPairs,
+        builder: PresentationProofBuilder<'a>,
+    ) -> PresentationProofBuilder<'a>;
+
+    /// Adds each encrypted attribute to `verifier` along with its key, in order.
+    fn add_to_verification<'a>(
+        ciphertexts: &Self::Ciphertexts,
+        public_keys: &Self::PublicKeys,
+        verifier: PresentationProofVerifier<'a>,
+    ) -> PresentationProofVerifier<'a>;
+
+    /// Encrypts each attribute with its key.
+    fn encrypt(values: &Self::Values, key_pairs: &Self::KeyPairs) -> Self::Ciphertexts;
+}
+
+macro_rules! impl_encrypted_attributes_for_tuple {
+    ($($D:ident: $i:tt),*) => {
+        impl<$($D: Domain),*> EncryptedAttributes for ($($D,)*) {
+            type Values = ($($D::Attribute,)*);
+            type KeyPairs = ($(KeyPair<$D>,)*);
+            type PublicKeys = ($(attributes::PublicKey<$D>,)*);
+            type Ciphertexts = ($(Ciphertext<$D>,)*);
+
+            #[allow(unused_mut, unused_variables)]
+            fn add_to_issuance<'a>(
+                values: &Self::Values,
+                mut builder: IssuanceProofBuilder<'a>,
+            ) -> IssuanceProofBuilder<'a> {
+                $(builder = builder.add_attribute(&values.$i);)*
+                builder
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn add_to_presentation<'a>(
+                values: &Self::Values,
+                key_pairs: &Self::KeyPairs,
+                mut builder: PresentationProofBuilder<'a>,
+            ) -> PresentationProofBuilder<'a> {
+                $(builder = builder.add_attribute(&values.$i, &key_pairs.$i);)*
+                builder
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn add_to_verification<'a>(
+                ciphertexts: &Self::Ciphertexts,
+                public_keys: &Self::PublicKeys,
+                mut verifier: PresentationProofVerifier<'a>,
+            ) -> PresentationProofVerifier<'a> {
+                $(verifier = verifier.add_attribute(&ciphertexts.$i, &public_keys.$i);)*
+                verifier
+            }
+
+            #[allow(unused_variables)]
+            fn encrypt(values: &Self::Values, key_pairs: &Self::KeyPairs) -> Self::Ciphertexts {
+                ($(key_pairs.$i.encrypt(&values.$i),)*)
+            }
+        }
+    };
+}
+
+impl_encrypted_attributes_for_tuple!();
+impl_encrypted_attributes_for_tuple!(A: 0);
+impl_encrypted_attributes_for_tuple!(A: 0, B: 1);
+impl_encrypted_attributes_for_tuple!(A: 0, B: 1, C: 2);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use curve25519_dalek::RistrettoPoint;
+    use poksho::ShoApi as _;
+
+    use super::*;
+    use crate::sho::ShoExt as _;
+
+    struct ExampleDomain;
+    impl Domain for ExampleDomain {
+        type Attribute = [RistrettoPoint; 2];
+        const ID: &'static str = "TestDomain";
+
+        fn G_a() -> [RistrettoPoint; 2] {
+            static STORAGE: OnceLock<[RistrettoPoint; 2]> = OnceLock::new();
+            *attributes::derive_default_generator_points::<Self>(&STORAGE)
+        }
+    }
+
+    struct ExampleCredential;
+    impl CredentialSchema for ExampleCredential {
+        const LABEL: &'static [u8] = b"ExampleCredential";
+        type PublicAttributes = (u64, [u8; 4]);
+        type EncryptedAttributes = (ExampleDomain, ExampleDomain);
+    }
+
+    fn example_attr(seed: &[u8]) -> [RistrettoPoint; 2] {
+        let mut sho = poksho::ShoHmacSha256::new(b"ExampleAttribute");
+        sho.absorb_and_ratchet(seed);
+        [sho.get_point(), sho.get_point()]
+    }
+
+    #[test]
+    fn round_trip_matches_builders() {
+        let server_key = CredentialKeyPair::generate([1; RANDOMNESS_LEN]);
+        let public_attrs = (42u64, *b"role");
+        let attrs = (example_attr(b"a"), example_attr(b"b"));
+        let key_pairs = (
+            KeyPair::<ExampleDomain>::derive_from(&mut poksho::ShoHmacSha256::new(b"key a")),
+            KeyPair::<ExampleDomain>::derive_from(&mut poksho::ShoHmacSha256::new(b"key b")),
+        );
+        let public_keys = (key_pairs.0.public_key, key_pairs.1.public_key);
+
+        let issuance_proof =
+            ExampleCredential::issue(&server_key, &public_attrs, &attrs, [2; RANDOMNESS_LEN]);
+
+        // The schema should produce exactly the same credential as using the builder by hand.
+        let credential = IssuanceProofBuilder::new(ExampleCredential::LABEL)
+            .add_public_attribute(&public_attrs.0)
+            .add_public_attribute(&public_attrs.1)
+            .add_attribute(&attrs.0)
+            .add_attribute(&attrs.1)
+            .verify(server_key.public_key(), issuance_proof.clone())
+            .expect("valid");
+
+        ExampleCredential::receive(
+            server_key.public_key(),
+            &(43, *b"role"),
+            &attrs,
+            issuance_proof.clone(),
+        )
+        .expect_err("wrong public attributes");
+        ExampleCredential::receive(
+            server_key.public_key(),
+            &public_attrs,
+            &(attrs.1, attrs.0),
+            issuance_proof.clone(),
+        )
+        .expect_err("attributes in the wrong order");
+        ExampleCredential::receive(
+            server_key.public_key(),
+            &public_attrs,
+            &attrs,
+            issuance_proof,
+        )
+        .expect("valid");
+
+        let presentation = ExampleCredential::present(
+            server_key.public_key(),
+            &credential,
+            &attrs,
+            &key_pairs,
+            [3; RANDOMNESS_LEN],
+        );
+        let ciphertexts = ExampleCredential::encrypt(&attrs, &key_pairs);
+
+        ExampleCredential::verify(
+            &server_key,
+            &public_attrs,
+            &ciphertexts,
+            &public_keys,
+            &presentation,
+        )
+        .expect("valid");
+        ExampleCredential::verify(
+            &server_key,
+            &(43, *b"role"),
+            &ciphertexts,
+            &public_keys,
+            &presentation,
+        )
+        .expect_err("wrong public attributes");
+        ExampleCredential::verify(
+            &server_key,
+            &public_attrs,
+            &(ciphertexts.1, ciphertexts.0),
+            &public_keys,
+            &presentation,
+        )
+        .expect_err("ciphertexts in the wrong order");
+    }
+}
@@ -12,7 +12,10 @@ pub mod receipts;
 
 pub mod generic_server_params;
 pub mod server_params;
+pub mod server_params_rotation;
 
 pub use server_params::{
-    EndorsementPublicKey, EndorsementServerRootKeyPair, ServerPublicParams, ServerSecretParams,
+    EndorsementPublicKey, EndorsementServerRootKeyPair, ServerParamsId, ServerPublicParams,
+    ServerSecretParams,
 };
+pub use server_params_rotation::{RotatingServerPublicParams, RotatingServerSecretParams};
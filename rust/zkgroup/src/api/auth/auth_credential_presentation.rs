@@ -5,6 +5,7 @@ use crate::api;
 use crate::auth::AuthCredentialWithPniZkcPresentation;
 use crate::common::constants::*;
 use crate::common::errors::*;
+use crate::common::expiration::CredentialExpiration;
 use crate::common::simple_types::*;
 
 #[derive(derive_more::From)]
@@ -61,6 +62,20 @@ impl AnyAuthCredentialPresentation {
     }
 }
 
+impl CredentialExpiration for AnyAuthCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        match self {
+            Self::V4(inner) => inner.expiration_time(),
+        }
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        match self {
+            Self::V4(inner) => inner.is_valid_at(now),
+        }
+    }
+}
+
 impl Serialize for AnyAuthCredentialPresentation {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -10,7 +10,8 @@ use serde::Serialize;
 use crate::auth::AnyAuthCredentialPresentation;
 use crate::groups::GroupSecretParams;
 use crate::{
-    RandomnessBytes, ServerPublicParams, ZkGroupDeserializationFailure, ZkGroupVerificationFailure,
+    CredentialExpiration, RandomnessBytes, ServerPublicParams, Timestamp,
+    ZkGroupDeserializationFailure, ZkGroupVerificationFailure,
 };
 
 mod zkc;
@@ -69,6 +70,20 @@ impl AuthCredentialWithPni {
     }
 }
 
+impl CredentialExpiration for AuthCredentialWithPni {
+    fn expiration_time(&self) -> Timestamp {
+        match self {
+            Self::Zkc(inner) => inner.expiration_time(),
+        }
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        match self {
+            Self::Zkc(inner) => inner.is_valid_at(now),
+        }
+    }
+}
+
 impl AuthCredentialWithPniResponse {
     pub fn new(bytes: &[u8]) -> Result<Self, ZkGroupDeserializationFailure> {
         let first = bytes
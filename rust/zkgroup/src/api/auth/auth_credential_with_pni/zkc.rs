@@ -10,7 +10,8 @@ use zkcredential::credentials::{CredentialKeyPair, CredentialPublicKey};
 
 use crate::api::auth::auth_credential_with_pni::AuthCredentialWithPniVersion;
 use crate::common::constants::PRESENTATION_VERSION_4;
-use crate::common::serialization::VersionByte;
+use crate::common::expiration::{self, CredentialExpiration};
+use crate::common::serialization::{VersionByte, VersionedSerialization};
 use crate::common::simple_types::{RandomnessBytes, Timestamp};
 use crate::crypto::uid_encryption;
 use crate::crypto::uid_struct::UidStruct;
@@ -32,6 +33,10 @@ pub struct AuthCredentialWithPniZkc {
     redemption_time: Timestamp,
 }
 
+impl VersionedSerialization for AuthCredentialWithPniZkc {
+    const VERSION: u8 = 1;
+}
+
 #[derive(Clone, Serialize, Deserialize, PartialDefault)]
 pub struct AuthCredentialWithPniZkcResponse {
     version: VersionByte<{ AuthCredentialWithPniVersion::Zkc as u8 }>,
@@ -171,6 +176,16 @@ impl AuthCredentialWithPniZkc {
     }
 }
 
+impl CredentialExpiration for AuthCredentialWithPniZkc {
+    fn expiration_time(&self) -> Timestamp {
+        expiration::redemption_window_end(self.redemption_time)
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        expiration::is_in_redemption_window(self.redemption_time, now)
+    }
+}
+
 impl AuthCredentialWithPniZkcPresentation {
     pub fn verify(
         &self,
@@ -224,6 +239,16 @@ impl AuthCredentialWithPniZkcPresentation {
     }
 }
 
+impl CredentialExpiration for AuthCredentialWithPniZkcPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        expiration::redemption_window_end(self.redemption_time)
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        expiration::is_in_redemption_window(self.redemption_time, now)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use zkcredential::RANDOMNESS_LEN;
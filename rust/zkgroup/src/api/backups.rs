@@ -8,5 +8,6 @@ mod auth_credential;
 pub use auth_credential::{
     BackupAuthCredential, BackupAuthCredentialPresentation, BackupAuthCredentialRequest,
     BackupAuthCredentialRequestContext, BackupAuthCredentialResponse, BackupCredentialType,
-    BackupLevel,
+    BackupLevel, ExpiringBackupAuthCredential, ExpiringBackupAuthCredentialPresentation,
+    ExpiringBackupAuthCredentialResponse,
 };
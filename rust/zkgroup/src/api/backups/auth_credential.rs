@@ -14,13 +14,19 @@
 //!
 //! The BackupAuthCredential has the additional constraint that it should be deterministically reproducible. Rather than a randomly
 //! seeded blinding key pair, the key pair is derived from, you guessed it, the client's AEP.
+//!
+//! ExpiringBackupAuthCredential is issued from the same request, but replaces the redemption time with an expiration time
+//! aligned to the hour, so the chat server can hand out credentials that are valid for anywhere from an hour to a week.
+//! As with BackupAuthCredential, the backup level is revealed to the verifying server so it can apply the right quota,
+//! while the blinded backup-id keeps the chat server from linking the account to the backup.
 
 use curve25519_dalek_signal::ristretto::RistrettoPoint;
 use partial_default::PartialDefault;
 use poksho::ShoApi;
 use serde::{Deserialize, Serialize};
 
-use crate::common::serialization::ReservedByte;
+use crate::common::expiration::{self, CredentialExpiration};
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::common::sho::Sho;
 use crate::common::simple_types::*;
 use crate::generic_server_params::{GenericServerPublicParams, GenericServerSecretParams};
@@ -42,6 +48,10 @@ impl zkcredential::attributes::RevealedAttribute for BackupIdPoint {
 }
 
 const CREDENTIAL_LABEL: &[u8] = b"20231003_Signal_BackupAuthCredential";
+const EXPIRING_CREDENTIAL_LABEL: &[u8] = b"20251016_Signal_ExpiringBackupAuthCredential";
+
+/// The longest a client will accept an [`ExpiringBackupAuthCredential`] for.
+const MAX_EXPIRING_CREDENTIAL_LIFETIME: u64 = 7 * SECONDS_PER_DAY;
 
 // We make sure we serialize BackupLevel and BackupType with plenty of room to expand to other u64
 // values later. But since they fit in a byte today, we stick to just a u8 in the in-memory and
@@ -237,6 +247,10 @@ pub struct BackupAuthCredential {
     backup_id: libsignal_account_keys::BackupId,
 }
 
+impl VersionedSerialization for BackupAuthCredential {
+    const VERSION: u8 = 1;
+}
+
 impl BackupAuthCredential {
     pub fn present(
         &self,
@@ -268,6 +282,16 @@ impl BackupAuthCredential {
     }
 }
 
+impl CredentialExpiration for BackupAuthCredential {
+    fn expiration_time(&self) -> Timestamp {
+        expiration::redemption_window_end(self.redemption_time)
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        expiration::is_in_redemption_window(self.redemption_time, now)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialDefault)]
 pub struct BackupAuthCredentialPresentation {
     version: ReservedByte,
@@ -319,12 +343,220 @@ impl BackupAuthCredentialPresentation {
     }
 }
 
+impl CredentialExpiration for BackupAuthCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        expiration::redemption_window_end(self.redemption_time)
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        expiration::is_in_redemption_window(self.redemption_time, now)
+    }
+}
+
+impl BackupAuthCredentialRequest {
+    /// Issues a credential that is valid until `expiration`.
+    ///
+    /// The client will reject the credential unless `expiration` is on an hour boundary and no
+    /// more than a week away.
+    pub fn issue_expiring(
+        &self,
+        expiration: Timestamp,
+        backup_level: BackupLevel,
+        credential_type: BackupCredentialType,
+        params: &GenericServerSecretParams,
+        randomness: RandomnessBytes,
+    ) -> ExpiringBackupAuthCredentialResponse {
+        ExpiringBackupAuthCredentialResponse {
+            reserved: Default::default(),
+            expiration,
+            backup_level,
+            credential_type,
+            blinded_credential: zkcredential::issuance::IssuanceProofBuilder::new(
+                EXPIRING_CREDENTIAL_LABEL,
+            )
+            .add_public_attribute(&expiration)
+            .add_public_attribute(&u64::from(backup_level))
+            .add_public_attribute(&u64::from(credential_type))
+            .add_blinded_revealed_attribute(&self.blinded_backup_id)
+            .issue(&params.credential_key, &self.public_key, randomness),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialDefault)]
+pub struct ExpiringBackupAuthCredentialResponse {
+    reserved: ReservedByte,
+    expiration: Timestamp,
+    backup_level: BackupLevel,
+    credential_type: BackupCredentialType,
+    blinded_credential: zkcredential::issuance::blind::BlindedIssuanceProof,
+}
+
+impl BackupAuthCredentialRequestContext {
+    pub fn receive_expiring(
+        self,
+        response: ExpiringBackupAuthCredentialResponse,
+        params: &GenericServerPublicParams,
+        current_time: Timestamp,
+    ) -> Result<ExpiringBackupAuthCredential, ZkGroupVerificationFailure> {
+        // Only accept a small set of expirations, so the server can't use them to tell clients
+        // apart.
+        if !response.expiration.is_hour_aligned() {
+            return Err(ZkGroupVerificationFailure);
+        }
+        let seconds_remaining = response.expiration.saturating_seconds_since(current_time);
+        if seconds_remaining == 0 || seconds_remaining > MAX_EXPIRING_CREDENTIAL_LIFETIME {
+            return Err(ZkGroupVerificationFailure);
+        }
+
+        Ok(ExpiringBackupAuthCredential {
+            reserved: Default::default(),
+            expiration: response.expiration,
+            backup_level: response.backup_level,
+            credential_type: response.credential_type,
+            credential: zkcredential::issuance::IssuanceProofBuilder::new(
+                EXPIRING_CREDENTIAL_LABEL,
+            )
+            .add_public_attribute(&response.expiration)
+            .add_public_attribute(&u64::from(response.backup_level))
+            .add_public_attribute(&u64::from(response.credential_type))
+            .add_blinded_revealed_attribute(&self.blinded_backup_id)
+            .verify(
+                &params.credential_key,
+                &self.key_pair,
+                response.blinded_credential,
+            )
+            .map_err(|_| ZkGroupVerificationFailure)?,
+            backup_id: self.backup_id,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialDefault)]
+pub struct ExpiringBackupAuthCredential {
+    reserved: ReservedByte,
+    expiration: Timestamp,
+    backup_level: BackupLevel,
+    credential_type: BackupCredentialType,
+    credential: zkcredential::credentials::Credential,
+    backup_id: libsignal_account_keys::BackupId,
+}
+
+impl VersionedSerialization for ExpiringBackupAuthCredential {
+    const VERSION: u8 = 1;
+}
+
+impl ExpiringBackupAuthCredential {
+    pub fn present(
+        &self,
+        server_params: &GenericServerPublicParams,
+        randomness: RandomnessBytes,
+    ) -> ExpiringBackupAuthCredentialPresentation {
+        ExpiringBackupAuthCredentialPresentation {
+            version: Default::default(),
+            expiration: self.expiration,
+            backup_level: self.backup_level,
+            credential_type: self.credential_type,
+            backup_id: self.backup_id,
+            proof: zkcredential::presentation::PresentationProofBuilder::new(
+                EXPIRING_CREDENTIAL_LABEL,
+            )
+            .add_revealed_attribute(&BackupIdPoint::new(&self.backup_id))
+            .present(&server_params.credential_key, &self.credential, randomness),
+        }
+    }
+
+    pub fn backup_id(&self) -> libsignal_account_keys::BackupId {
+        self.backup_id
+    }
+
+    pub fn backup_level(&self) -> BackupLevel {
+        self.backup_level
+    }
+
+    pub fn credential_type(&self) -> BackupCredentialType {
+        self.credential_type
+    }
+
+    pub fn expiration(&self) -> Timestamp {
+        self.expiration
+    }
+}
+
+impl CredentialExpiration for ExpiringBackupAuthCredential {
+    fn expiration_time(&self) -> Timestamp {
+        self.expiration
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.expiration
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialDefault)]
+pub struct ExpiringBackupAuthCredentialPresentation {
+    version: ReservedByte,
+    backup_level: BackupLevel,
+    credential_type: BackupCredentialType,
+    expiration: Timestamp,
+    proof: zkcredential::presentation::PresentationProof,
+    backup_id: libsignal_account_keys::BackupId,
+}
+
+impl ExpiringBackupAuthCredentialPresentation {
+    pub fn verify(
+        &self,
+        current_time: Timestamp,
+        server_params: &GenericServerSecretParams,
+    ) -> Result<(), ZkGroupVerificationFailure> {
+        if self.expiration <= current_time {
+            return Err(ZkGroupVerificationFailure);
+        }
+
+        zkcredential::presentation::PresentationProofVerifier::new(EXPIRING_CREDENTIAL_LABEL)
+            .add_public_attribute(&self.expiration)
+            .add_public_attribute(&u64::from(self.backup_level))
+            .add_public_attribute(&u64::from(self.credential_type))
+            .add_revealed_attribute(&BackupIdPoint::new(&self.backup_id))
+            .verify(&server_params.credential_key, &self.proof)
+            .map_err(|_| ZkGroupVerificationFailure)
+    }
+
+    pub fn backup_level(&self) -> BackupLevel {
+        self.backup_level
+    }
+
+    pub fn credential_type(&self) -> BackupCredentialType {
+        self.credential_type
+    }
+
+    pub fn backup_id(&self) -> libsignal_account_keys::BackupId {
+        self.backup_id
+    }
+
+    pub fn expiration(&self) -> Timestamp {
+        self.expiration
+    }
+}
+
+impl CredentialExpiration for ExpiringBackupAuthCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        self.expiration
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.expiration
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
 
     use super::*;
-    use crate::{common, RandomnessBytes, Timestamp, RANDOMNESS_LEN, SECONDS_PER_DAY};
+    use crate::{
+        common, RandomnessBytes, Timestamp, RANDOMNESS_LEN, SECONDS_PER_DAY, SECONDS_PER_HOUR,
+    };
 
     const DAY_ALIGNED_TIMESTAMP: Timestamp = Timestamp::from_epoch_seconds(1681344000); // 2023-04-13 00:00:00 UTC
     const KEY: libsignal_account_keys::BackupKey = libsignal_account_keys::BackupKey([0x42u8; 32]);
@@ -475,6 +707,88 @@ mod tests {
         );
     }
 
+    fn generate_expiring_credential(
+        expiration: Timestamp,
+        current_time: Timestamp,
+    ) -> Result<ExpiringBackupAuthCredential, ZkGroupVerificationFailure> {
+        let request_context = BackupAuthCredentialRequestContext::new(&KEY, ACI.into());
+        let request = request_context.get_request();
+        let blinded_credential = request.issue_expiring(
+            expiration,
+            BackupLevel::Paid,
+            BackupCredentialType::Media,
+            &server_secret_params(),
+            ISSUE_RAND,
+        );
+        request_context.receive_expiring(
+            blinded_credential,
+            &server_secret_params().get_public_params(),
+            current_time,
+        )
+    }
+
+    #[test]
+    fn test_expiring_credential() {
+        let expiration = DAY_ALIGNED_TIMESTAMP.add_seconds(3 * SECONDS_PER_HOUR);
+        let credential = generate_expiring_credential(expiration, DAY_ALIGNED_TIMESTAMP)
+            .expect("credential should be valid");
+        assert_eq!(credential.expiration(), expiration);
+        assert_eq!(credential.backup_level(), BackupLevel::Paid);
+
+        let presentation =
+            credential.present(&server_secret_params().get_public_params(), PRESENT_RAND);
+        assert_eq!(presentation.backup_level(), BackupLevel::Paid);
+        assert_eq!(presentation.credential_type(), BackupCredentialType::Media);
+        assert_eq!(presentation.backup_id(), credential.backup_id());
+
+        presentation
+            .verify(DAY_ALIGNED_TIMESTAMP, &server_secret_params())
+            .expect("presentation should be valid");
+        presentation
+            .verify(expiration.sub_seconds(1), &server_secret_params())
+            .expect("presentation should be valid until expiration");
+        presentation
+            .verify(expiration, &server_secret_params())
+            .expect_err("presentation should not be valid at expiration");
+
+        let invalid_presentation = ExpiringBackupAuthCredentialPresentation {
+            backup_level: BackupLevel::Free,
+            ..credential.present(&server_secret_params().get_public_params(), PRESENT_RAND)
+        };
+        invalid_presentation
+            .verify(DAY_ALIGNED_TIMESTAMP, &server_secret_params())
+            .expect_err("credential should not be valid with wrong backup level");
+
+        let invalid_presentation = ExpiringBackupAuthCredentialPresentation {
+            expiration: expiration.add_seconds(SECONDS_PER_HOUR),
+            ..credential.present(&server_secret_params().get_public_params(), PRESENT_RAND)
+        };
+        invalid_presentation
+            .verify(DAY_ALIGNED_TIMESTAMP, &server_secret_params())
+            .expect_err("credential should not be valid with altered expiration");
+    }
+
+    #[test]
+    fn test_client_enforces_expiration() {
+        generate_expiring_credential(
+            DAY_ALIGNED_TIMESTAMP.add_seconds(SECONDS_PER_HOUR + 1),
+            DAY_ALIGNED_TIMESTAMP,
+        )
+        .expect_err("client should require that expiration is on an hour boundary");
+        generate_expiring_credential(DAY_ALIGNED_TIMESTAMP, DAY_ALIGNED_TIMESTAMP)
+            .expect_err("client should reject a credential that has already expired");
+        generate_expiring_credential(
+            DAY_ALIGNED_TIMESTAMP.add_seconds(7 * SECONDS_PER_DAY + SECONDS_PER_HOUR),
+            DAY_ALIGNED_TIMESTAMP,
+        )
+        .expect_err("client should reject a credential that lasts more than a week");
+        generate_expiring_credential(
+            DAY_ALIGNED_TIMESTAMP.add_seconds(7 * SECONDS_PER_DAY),
+            DAY_ALIGNED_TIMESTAMP,
+        )
+        .expect("a week is allowed");
+    }
+
     #[test]
     fn test_backup_level_serialization() {
         let free_bytes = common::serialization::serialize(&BackupLevel::Free);
@@ -13,7 +13,8 @@ use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
 
 use super::{CallLinkPublicParams, CallLinkSecretParams};
-use crate::common::serialization::ReservedByte;
+use crate::common::expiration::{self, CredentialExpiration};
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::common::simple_types::*;
 use crate::crypto::uid_encryption;
 use crate::crypto::uid_struct::UidStruct;
@@ -78,6 +79,10 @@ pub struct CallLinkAuthCredential {
     // Does not include the redemption time because that's used as a key to lookup up this credential.
 }
 
+impl VersionedSerialization for CallLinkAuthCredential {
+    const VERSION: u8 = 1;
+}
+
 impl CallLinkAuthCredential {
     pub fn present(
         &self,
@@ -134,3 +139,13 @@ impl CallLinkAuthCredentialPresentation {
         }
     }
 }
+
+impl CredentialExpiration for CallLinkAuthCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        expiration::redemption_window_end(self.redemption_time)
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        expiration::is_in_redemption_window(self.redemption_time, now)
+    }
+}
@@ -16,6 +16,7 @@ use poksho::ShoApi;
 use serde::{Deserialize, Serialize};
 
 use super::{CallLinkPublicParams, CallLinkSecretParams};
+use crate::common::expiration::CredentialExpiration;
 use crate::common::serialization::ReservedByte;
 use crate::common::sho::Sho;
 use crate::common::simple_types::*;
@@ -42,6 +43,9 @@ impl zkcredential::attributes::RevealedAttribute for CallLinkRoomIdPoint {
 
 const CREDENTIAL_LABEL: &[u8] = b"20230413_Signal_CreateCallLinkCredential";
 
+/// How long a [`CreateCallLinkCredential`] is valid for: 30 hours, to account for clock skew.
+const VALIDITY_PERIOD: u64 = 30 * 60 * 60;
+
 #[derive(Serialize, Deserialize, PartialDefault)]
 pub struct CreateCallLinkCredentialRequestContext {
     reserved: ReservedByte,
@@ -181,6 +185,18 @@ impl CreateCallLinkCredential {
     }
 }
 
+impl CredentialExpiration for CreateCallLinkCredential {
+    fn expiration_time(&self) -> Timestamp {
+        self.timestamp
+            .checked_add_seconds(VALIDITY_PERIOD)
+            .unwrap_or(Timestamp::from_epoch_seconds(u64::MAX))
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        (self.timestamp..self.expiration_time()).contains(&now)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialDefault)]
 pub struct CreateCallLinkCredentialPresentation {
     reserved: ReservedByte,
@@ -200,7 +216,7 @@ impl CreateCallLinkCredentialPresentation {
     ) -> Result<(), ZkGroupVerificationFailure> {
         let expiration = self
             .timestamp
-            .checked_add_seconds(30 * 60 * 60) // 30 hours, to account for clock skew
+            .checked_add_seconds(VALIDITY_PERIOD)
             .ok_or(ZkGroupVerificationFailure)?;
 
         if !(self.timestamp..expiration).contains(&current_time) {
@@ -222,3 +238,15 @@ impl CreateCallLinkCredentialPresentation {
         }
     }
 }
+
+impl CredentialExpiration for CreateCallLinkCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        self.timestamp
+            .checked_add_seconds(VALIDITY_PERIOD)
+            .unwrap_or(Timestamp::from_epoch_seconds(u64::MAX))
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        (self.timestamp..self.expiration_time()).contains(&now)
+    }
+}
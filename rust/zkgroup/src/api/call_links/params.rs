@@ -7,7 +7,7 @@ use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
 
 use crate::common::errors::*;
-use crate::common::serialization::ReservedByte;
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::common::sho::*;
 use crate::crypto::uid_encryption;
 use crate::{api, crypto};
@@ -19,6 +19,10 @@ pub struct CallLinkSecretParams {
         zkcredential::attributes::KeyPair<crypto::uid_encryption::UidEncryptionDomain>,
 }
 
+impl VersionedSerialization for CallLinkSecretParams {
+    const VERSION: u8 = 1;
+}
+
 impl AsRef<uid_encryption::KeyPair> for CallLinkSecretParams {
     fn as_ref(&self) -> &uid_encryption::KeyPair {
         &self.uid_enc_key_pair
@@ -6,7 +6,7 @@
 use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
 
-use crate::common::serialization::ReservedByte;
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::RandomnessBytes;
 
 #[derive(Serialize, Deserialize, PartialDefault)]
@@ -15,6 +15,10 @@ pub struct GenericServerSecretParams {
     pub(crate) credential_key: zkcredential::credentials::CredentialKeyPair,
 }
 
+impl VersionedSerialization for GenericServerSecretParams {
+    const VERSION: u8 = 1;
+}
+
 impl GenericServerSecretParams {
     pub fn generate(randomness: RandomnessBytes) -> Self {
         Self {
@@ -29,6 +33,13 @@ impl GenericServerSecretParams {
             credential_key: self.credential_key.public_key().clone(),
         }
     }
+
+    /// The key used to issue and verify credentials.
+    ///
+    /// Useful for defining new credential types with [`zkcredential::schema`].
+    pub fn credential_key(&self) -> &zkcredential::credentials::CredentialKeyPair {
+        &self.credential_key
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialDefault)]
@@ -36,3 +47,16 @@ pub struct GenericServerPublicParams {
     version: ReservedByte,
     pub(crate) credential_key: zkcredential::credentials::CredentialPublicKey,
 }
+
+impl VersionedSerialization for GenericServerPublicParams {
+    const VERSION: u8 = 1;
+}
+
+impl GenericServerPublicParams {
+    /// The key used to validate issued credentials and generate presentations.
+    ///
+    /// Useful for defining new credential types with [`zkcredential::schema`].
+    pub fn credential_key(&self) -> &zkcredential::credentials::CredentialPublicKey {
+        &self.credential_key
+    }
+}
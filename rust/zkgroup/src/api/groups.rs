@@ -3,15 +3,19 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+pub mod banned_member_ciphertext;
 pub mod group_params;
 mod group_send_endorsement;
+mod group_send_endorsement_cache;
 pub mod profile_key_ciphertext;
 pub mod uuid_ciphertext;
 
+pub use banned_member_ciphertext::BannedMemberCiphertext;
 pub use group_params::{GroupMasterKey, GroupPublicParams, GroupSecretParams};
 pub use group_send_endorsement::{
     GroupSendDerivedKeyPair, GroupSendEndorsement, GroupSendEndorsementsResponse,
     GroupSendFullToken, GroupSendToken,
 };
+pub use group_send_endorsement_cache::GroupSendEndorsementCache;
 pub use profile_key_ciphertext::ProfileKeyCiphertext;
 pub use uuid_ciphertext::UuidCiphertext;
@@ -0,0 +1,51 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use partial_default::PartialDefault;
+use serde::{Deserialize, Serialize};
+
+use crate::api::auth::AnyAuthCredentialPresentation;
+use crate::api::groups::UuidCiphertext;
+use crate::common::serialization::ReservedByte;
+use crate::crypto;
+
+/// A banned member's service ID, encrypted for the group's ban list.
+///
+/// Service IDs are encrypted deterministically under the group's key, so the group server can
+/// tell whether a member presenting a credential is on the ban list by comparing ciphertexts,
+/// without being able to decrypt either. Create these with
+/// [`GroupSecretParams::encrypt_banned_member`](crate::groups::GroupSecretParams::encrypt_banned_member).
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, PartialDefault)]
+pub struct BannedMemberCiphertext {
+    pub(crate) reserved: ReservedByte,
+    pub(crate) ciphertext: crypto::uid_encryption::Ciphertext,
+}
+
+impl BannedMemberCiphertext {
+    /// Returns whether this entry bans the member that created `presentation`, by either their
+    /// ACI or their PNI.
+    ///
+    /// This does not verify the presentation; check it with
+    /// [`ServerSecretParams::verify_auth_credential_presentation`](crate::ServerSecretParams::verify_auth_credential_presentation)
+    /// first.
+    pub fn matches(&self, presentation: &AnyAuthCredentialPresentation) -> bool {
+        self.matches_ciphertext(&presentation.get_aci_ciphertext())
+            || self.matches_ciphertext(&presentation.get_pni_ciphertext())
+    }
+
+    /// Returns whether this entry bans the member whose encrypted service ID is `ciphertext`.
+    pub fn matches_ciphertext(&self, ciphertext: &UuidCiphertext) -> bool {
+        self.ciphertext == ciphertext.ciphertext
+    }
+}
+
+impl From<UuidCiphertext> for BannedMemberCiphertext {
+    fn from(value: UuidCiphertext) -> Self {
+        Self {
+            reserved: Default::default(),
+            ciphertext: value.ciphertext,
+        }
+    }
+}
@@ -7,11 +7,12 @@ use aes_gcm_siv::aead::generic_array::GenericArray;
 use aes_gcm_siv::aead::Aead;
 use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
 use partial_default::PartialDefault;
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 use serde::{Deserialize, Serialize};
 
 use crate::common::constants::*;
 use crate::common::errors::*;
-use crate::common::serialization::ReservedByte;
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::common::sho::*;
 use crate::common::simple_types::*;
 use crate::crypto::uid_encryption;
@@ -32,6 +33,10 @@ pub struct GroupSecretParams {
     pub(crate) profile_key_enc_key_pair: crypto::profile_key_encryption::KeyPair,
 }
 
+impl VersionedSerialization for GroupSecretParams {
+    const VERSION: u8 = 1;
+}
+
 impl AsRef<uid_encryption::KeyPair> for GroupSecretParams {
     fn as_ref(&self) -> &uid_encryption::KeyPair {
         &self.uid_enc_key_pair
@@ -131,6 +136,46 @@ impl GroupSecretParams {
         )
     }
 
+    /// Encrypts `service_id` for the group's ban list.
+    ///
+    /// The result matches the ciphertexts in any presentation `service_id` makes for this group,
+    /// so the server can enforce the ban without learning who is banned.
+    pub fn encrypt_banned_member(
+        &self,
+        service_id: libsignal_core::ServiceId,
+    ) -> api::groups::BannedMemberCiphertext {
+        self.encrypt_service_id(service_id).into()
+    }
+
+    /// Decrypts an entry of the group's ban list, as returned by the group server.
+    ///
+    /// Fails if the entry was not encrypted with this group's key.
+    pub fn decrypt_banned_member(
+        &self,
+        ciphertext: api::groups::BannedMemberCiphertext,
+    ) -> Result<libsignal_core::ServiceId, ZkGroupVerificationFailure> {
+        crypto::uid_encryption::UidEncryptionDomain::decrypt(
+            &self.uid_enc_key_pair,
+            &ciphertext.ciphertext,
+        )
+    }
+
+    /// Decrypts many service IDs at once, such as a group's member list.
+    ///
+    /// The results are in the same order as `ciphertexts`. This is faster than calling
+    /// [`Self::decrypt_service_id`] for each one.
+    pub fn decrypt_service_ids_batch(
+        &self,
+        ciphertexts: &[api::groups::UuidCiphertext],
+    ) -> Vec<Result<libsignal_core::ServiceId, ZkGroupVerificationFailure>> {
+        crypto::uid_encryption::UidEncryptionDomain::decrypt_batch(
+            &self.uid_enc_key_pair,
+            ciphertexts
+                .par_iter()
+                .map(|ciphertext| ciphertext.ciphertext),
+        )
+    }
+
     pub fn encrypt_profile_key(
         &self,
         profile_key: api::profiles::ProfileKey,
@@ -171,6 +216,32 @@ impl GroupSecretParams {
         })
     }
 
+    /// Decrypts many profile keys at once, each paired with the ACI of the member it belongs to.
+    ///
+    /// The results are in the same order as `ciphertexts`. This is faster than calling
+    /// [`Self::decrypt_profile_key`] for each one.
+    pub fn decrypt_profile_keys_batch(
+        &self,
+        ciphertexts: &[(api::groups::ProfileKeyCiphertext, libsignal_core::Aci)],
+    ) -> Vec<Result<api::profiles::ProfileKey, ZkGroupVerificationFailure>> {
+        crypto::profile_key_encryption::ProfileKeyEncryptionDomain::decrypt_batch(
+            &self.profile_key_enc_key_pair,
+            ciphertexts.par_iter().map(|(ciphertext, user_id)| {
+                (
+                    ciphertext.ciphertext,
+                    uuid::Uuid::from(*user_id).into_bytes(),
+                )
+            }),
+        )
+        .into_iter()
+        .map(|result| {
+            result.map(|profile_key_struct| api::profiles::ProfileKey {
+                bytes: profile_key_struct.bytes,
+            })
+        })
+        .collect()
+    }
+
     pub fn encrypt_blob(&self, randomness: RandomnessBytes, plaintext: &[u8]) -> Vec<u8> {
         let mut sho = Sho::new(
             b"Signal_ZKGroup_20200424_Random_GroupSecretParams_EncryptBlob",
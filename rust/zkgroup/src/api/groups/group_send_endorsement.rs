@@ -25,12 +25,10 @@ use crate::common::serialization::ReservedByte;
 use crate::crypto::uid_encryption;
 use crate::groups::{GroupSecretParams, UuidCiphertext};
 use crate::{
-    crypto, RandomnessBytes, Timestamp, ZkGroupDeserializationFailure, ZkGroupVerificationFailure,
-    SECONDS_PER_DAY,
+    crypto, CredentialExpiration, RandomnessBytes, Timestamp, ZkGroupDeserializationFailure,
+    ZkGroupVerificationFailure, SECONDS_PER_DAY, SECONDS_PER_HOUR,
 };
 
-const SECONDS_PER_HOUR: u64 = 60 * 60;
-
 /// A key pair used to sign endorsements for a particular expiration.
 ///
 /// These are intended to be cheaply cached -- it's not a problem to regenerate them, but they're
@@ -343,6 +341,20 @@ impl GroupSendEndorsementsResponse {
     }
 }
 
+impl CredentialExpiration for GroupSendEndorsementsResponse {
+    fn expiration_time(&self) -> Timestamp {
+        self.expiration
+    }
+
+    /// Whether the response can still be received at `now`.
+    ///
+    /// Like [`Self::receive_with_service_ids`], this requires at least two hours to remain before
+    /// expiration, to allow for clock skew.
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        self.expiration.saturating_seconds_since(now) >= 2 * SECONDS_PER_HOUR
+    }
+}
+
 /// A single endorsement, for one or multiple group members.
 ///
 /// `Storage` is usually [`curve25519_dalek_signal::RistrettoPoint`], but the `receive` APIs on
@@ -585,3 +597,13 @@ impl GroupSendFullToken {
             .map_err(|_| ZkGroupVerificationFailure)
     }
 }
+
+impl CredentialExpiration for GroupSendFullToken {
+    fn expiration_time(&self) -> Timestamp {
+        self.expiration
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now <= self.expiration
+    }
+}
@@ -0,0 +1,166 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Provides GroupSendEndorsementCache, for keeping track of received GroupSendEndorsements.
+
+use std::collections::{HashMap, HashSet};
+
+use libsignal_core::ServiceId;
+
+use super::{GroupSecretParams, GroupSendEndorsement, GroupSendEndorsementsResponse};
+use crate::{GroupIdentifierBytes, Timestamp, ZkGroupVerificationFailure, SECONDS_PER_HOUR};
+
+/// How long before expiration a group's endorsements should be refreshed.
+///
+/// This is the same allowance for clock skew that clients use when validating a new
+/// [`GroupSendEndorsementsResponse`].
+const REFRESH_BEFORE_EXPIRATION: u64 = 2 * SECONDS_PER_HOUR;
+
+/// Stores the endorsements received for each group, and keeps them up to date as membership
+/// changes.
+///
+/// Endorsements can't be issued by the client, so a member added since the last
+/// [`GroupSendEndorsementsResponse`] has no endorsement until the group server is asked for a new
+/// one; [`needs_refresh`](Self::needs_refresh) reports this, along with upcoming expiration.
+/// Removed members, on the other hand, are simply dropped, and the remaining endorsements stay
+/// valid.
+#[derive(Default)]
+pub struct GroupSendEndorsementCache {
+    groups: HashMap<GroupIdentifierBytes, GroupEndorsements>,
+}
+
+struct GroupEndorsements {
+    expiration: Timestamp,
+    endorsements: HashMap<ServiceId, GroupSendEndorsement>,
+    /// All of `endorsements` combined, kept up to date so sending to the whole group is cheap.
+    combined: GroupSendEndorsement,
+    /// Members added since `endorsements` were issued.
+    unendorsed_members: HashSet<ServiceId>,
+}
+
+impl GroupSendEndorsementCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates `response` and stores its endorsements, replacing any previous endorsements for
+    /// the group.
+    ///
+    /// `members` is the full membership of the group the response was issued for, including the
+    /// current user, as for [`GroupSendEndorsementsResponse::receive_with_service_ids`].
+    pub fn receive(
+        &mut self,
+        response: GroupSendEndorsementsResponse,
+        members: &[ServiceId],
+        now: Timestamp,
+        group_params: &GroupSecretParams,
+        root_public_key: impl AsRef<zkcredential::endorsements::ServerRootPublicKey>,
+    ) -> Result<(), ZkGroupVerificationFailure> {
+        let expiration = response.expiration();
+        let received = response.receive_with_service_ids(
+            members.to_vec(),
+            now,
+            group_params,
+            root_public_key,
+        )?;
+        let endorsements: HashMap<_, _> = members
+            .iter()
+            .copied()
+            .zip(received.into_iter().map(|received| received.decompressed))
+            .collect();
+        let combined = GroupSendEndorsement::combine(endorsements.values().copied());
+        self.groups.insert(
+            group_params.get_group_identifier(),
+            GroupEndorsements {
+                expiration,
+                endorsements,
+                combined,
+                unendorsed_members: HashSet::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Records that `member` has joined the group.
+    ///
+    /// Until new endorsements are received, the group will report that it
+    /// [needs a refresh](Self::needs_refresh), and no endorsement covering the new member can be
+    /// produced.
+    pub fn member_added(&mut self, group_id: &GroupIdentifierBytes, member: ServiceId) {
+        if let Some(group) = self.groups.get_mut(group_id) {
+            if !group.endorsements.contains_key(&member) {
+                group.unendorsed_members.insert(member);
+            }
+        }
+    }
+
+    /// Records that `member` has left the group, dropping their endorsement.
+    pub fn member_removed(&mut self, group_id: &GroupIdentifierBytes, member: ServiceId) {
+        if let Some(group) = self.groups.get_mut(group_id) {
+            group.unendorsed_members.remove(&member);
+            if let Some(endorsement) = group.endorsements.remove(&member) {
+                group.combined = group.combined.remove(&endorsement);
+            }
+        }
+    }
+
+    /// Drops all endorsements for the group, such as when the current user leaves it.
+    pub fn forget_group(&mut self, group_id: &GroupIdentifierBytes) {
+        self.groups.remove(group_id);
+    }
+
+    /// Returns the expiration of the group's endorsements, if any have been received.
+    pub fn expiration(&self, group_id: &GroupIdentifierBytes) -> Option<Timestamp> {
+        self.groups.get(group_id).map(|group| group.expiration)
+    }
+
+    /// Returns whether new endorsements should be fetched from the group server.
+    ///
+    /// This is the case if no endorsements have been received for the group, if they expire
+    /// within the next two hours, or if members have been added since they were issued.
+    pub fn needs_refresh(&self, group_id: &GroupIdentifierBytes, now: Timestamp) -> bool {
+        let Some(group) = self.groups.get(group_id) else {
+            return true;
+        };
+        group.expiration.saturating_seconds_since(now) < REFRESH_BEFORE_EXPIRATION
+            || !group.unendorsed_members.is_empty()
+    }
+
+    /// Returns an endorsement covering every member of the group except `local_user`.
+    ///
+    /// Returns `None` if there are no endorsements for the group or some members are unendorsed.
+    /// This does not check expiration; use [`needs_refresh`](Self::needs_refresh) for that.
+    pub fn endorsement_for_group(
+        &self,
+        group_id: &GroupIdentifierBytes,
+        local_user: ServiceId,
+    ) -> Option<GroupSendEndorsement> {
+        let group = self.groups.get(group_id)?;
+        if !group.unendorsed_members.is_empty() {
+            return None;
+        }
+        Some(match group.endorsements.get(&local_user) {
+            Some(own_endorsement) => group.combined.remove(own_endorsement),
+            None => group.combined,
+        })
+    }
+
+    /// Returns an endorsement covering exactly `recipients`.
+    ///
+    /// Returns `None` if any of `recipients` has no endorsement. This does not check expiration;
+    /// use [`needs_refresh`](Self::needs_refresh) for that.
+    pub fn endorsement_for_members(
+        &self,
+        group_id: &GroupIdentifierBytes,
+        recipients: impl IntoIterator<Item = ServiceId>,
+    ) -> Option<GroupSendEndorsement> {
+        let group = self.groups.get(group_id)?;
+        let endorsements = recipients
+            .into_iter()
+            .map(|recipient| group.endorsements.get(&recipient).copied())
+            .collect::<Option<Vec<_>>>()?;
+        Some(GroupSendEndorsement::combine(endorsements))
+    }
+}
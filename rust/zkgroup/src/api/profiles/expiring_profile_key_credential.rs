@@ -6,7 +6,8 @@
 use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
 
-use crate::common::serialization::ReservedByte;
+use crate::common::expiration::CredentialExpiration;
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::common::simple_types::*;
 use crate::crypto;
 
@@ -19,6 +20,10 @@ pub struct ExpiringProfileKeyCredential {
     pub(crate) credential_expiration_time: Timestamp,
 }
 
+impl VersionedSerialization for ExpiringProfileKeyCredential {
+    const VERSION: u8 = 1;
+}
+
 impl ExpiringProfileKeyCredential {
     pub fn aci(&self) -> libsignal_core::Aci {
         uuid::Uuid::from_bytes(self.aci_bytes).into()
@@ -28,3 +33,13 @@ impl ExpiringProfileKeyCredential {
         self.credential_expiration_time
     }
 }
+
+impl CredentialExpiration for ExpiringProfileKeyCredential {
+    fn expiration_time(&self) -> Timestamp {
+        self.credential_expiration_time
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.credential_expiration_time
+    }
+}
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize, Serializer};
 
 use crate::common::constants::*;
 use crate::common::errors::*;
+use crate::common::expiration::CredentialExpiration;
 use crate::common::serialization::VersionByte;
 use crate::common::simple_types::*;
 use crate::{api, crypto};
@@ -90,6 +91,16 @@ impl ExpiringProfileKeyCredentialPresentation {
     }
 }
 
+impl CredentialExpiration for ExpiringProfileKeyCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        self.credential_expiration_time
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.credential_expiration_time
+    }
+}
+
 #[derive(derive_more::From)]
 pub enum AnyProfileKeyCredentialPresentation {
     V1(ProfileKeyCredentialPresentationV1),
@@ -160,6 +171,23 @@ impl AnyProfileKeyCredentialPresentation {
     }
 }
 
+impl CredentialExpiration for AnyProfileKeyCredentialPresentation {
+    // V1 and V2 presentations are no longer accepted at all.
+    fn expiration_time(&self) -> Timestamp {
+        match self {
+            Self::V1(_) | Self::V2(_) => Timestamp::from_epoch_seconds(0),
+            Self::V3(presentation) => presentation.expiration_time(),
+        }
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        match self {
+            Self::V1(_) | Self::V2(_) => false,
+            Self::V3(presentation) => presentation.is_valid_at(now),
+        }
+    }
+}
+
 impl Serialize for AnyProfileKeyCredentialPresentation {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
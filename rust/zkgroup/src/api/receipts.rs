@@ -8,9 +8,14 @@ pub mod receipt_credential_presentation;
 pub mod receipt_credential_request;
 pub mod receipt_credential_request_context;
 pub mod receipt_credential_response;
+pub mod receipt_credential_with_metadata;
 
 pub use receipt_credential::ReceiptCredential;
 pub use receipt_credential_presentation::ReceiptCredentialPresentation;
 pub use receipt_credential_request::ReceiptCredentialRequest;
 pub use receipt_credential_request_context::ReceiptCredentialRequestContext;
 pub use receipt_credential_response::ReceiptCredentialResponse;
+pub use receipt_credential_with_metadata::{
+    ReceiptCredentialWithMetadata, ReceiptCredentialWithMetadataPresentation,
+    ReceiptCredentialWithMetadataResponse,
+};
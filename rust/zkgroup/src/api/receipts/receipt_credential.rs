@@ -6,7 +6,8 @@
 use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
 
-use crate::common::serialization::ReservedByte;
+use crate::common::expiration::CredentialExpiration;
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
 use crate::common::simple_types::*;
 use crate::crypto;
 
@@ -19,6 +20,10 @@ pub struct ReceiptCredential {
     pub(crate) receipt_serial_bytes: ReceiptSerialBytes,
 }
 
+impl VersionedSerialization for ReceiptCredential {
+    const VERSION: u8 = 1;
+}
+
 impl ReceiptCredential {
     pub fn get_receipt_expiration_time(&self) -> Timestamp {
         self.receipt_expiration_time
@@ -28,3 +33,13 @@ impl ReceiptCredential {
         self.receipt_level
     }
 }
+
+impl CredentialExpiration for ReceiptCredential {
+    fn expiration_time(&self) -> Timestamp {
+        self.receipt_expiration_time
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.receipt_expiration_time
+    }
+}
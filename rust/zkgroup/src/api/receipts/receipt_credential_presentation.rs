@@ -6,6 +6,7 @@
 use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
 
+use crate::common::expiration::CredentialExpiration;
 use crate::common::serialization::ReservedByte;
 use crate::crypto::receipt_struct::ReceiptStruct;
 use crate::{crypto, ReceiptLevel, ReceiptSerialBytes, Timestamp};
@@ -24,11 +25,11 @@ pub struct ReceiptCredentialPresentation {
 
 impl ReceiptCredentialPresentation {
     pub fn get_receipt_struct(&self) -> ReceiptStruct {
-        ReceiptStruct {
-            receipt_serial_bytes: self.receipt_serial_bytes,
-            receipt_expiration_time: self.receipt_expiration_time,
-            receipt_level: self.receipt_level,
-        }
+        ReceiptStruct::new(
+            self.receipt_serial_bytes,
+            self.receipt_expiration_time,
+            self.receipt_level,
+        )
     }
 
     pub fn get_receipt_expiration_time(&self) -> Timestamp {
@@ -43,3 +44,13 @@ impl ReceiptCredentialPresentation {
         self.receipt_serial_bytes
     }
 }
+
+impl CredentialExpiration for ReceiptCredentialPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        self.receipt_expiration_time
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.receipt_expiration_time
+    }
+}
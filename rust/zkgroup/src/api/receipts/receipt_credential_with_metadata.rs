@@ -0,0 +1,114 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Receipt credentials that carry [`ReceiptMetadata`] as well as a receipt level.
+//!
+//! These are issued from an ordinary [`ReceiptCredentialRequest`](super::ReceiptCredentialRequest)
+//! and use the same server keys as [`ReceiptCredential`](super::ReceiptCredential); only the
+//! public attributes differ.
+
+use partial_default::PartialDefault;
+use serde::{Deserialize, Serialize};
+
+use crate::common::expiration::CredentialExpiration;
+use crate::common::serialization::{ReservedByte, VersionedSerialization};
+use crate::common::simple_types::*;
+use crate::crypto;
+use crate::crypto::receipt_struct::ReceiptStruct;
+
+#[derive(Serialize, Deserialize, PartialDefault)]
+pub struct ReceiptCredentialWithMetadataResponse {
+    pub(crate) reserved: ReservedByte,
+    pub(crate) receipt_expiration_time: Timestamp,
+    pub(crate) receipt_level: ReceiptLevel,
+    pub(crate) receipt_metadata: ReceiptMetadata,
+    pub(crate) blinded_credential: crypto::credentials::BlindedReceiptCredential,
+    pub(crate) proof: crypto::proofs::ReceiptCredentialIssuanceProof,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize, PartialDefault)]
+pub struct ReceiptCredentialWithMetadata {
+    pub(crate) reserved: ReservedByte,
+    pub(crate) credential: crypto::credentials::ReceiptCredential,
+    pub(crate) receipt_expiration_time: Timestamp,
+    pub(crate) receipt_level: ReceiptLevel,
+    pub(crate) receipt_metadata: ReceiptMetadata,
+    pub(crate) receipt_serial_bytes: ReceiptSerialBytes,
+}
+
+impl VersionedSerialization for ReceiptCredentialWithMetadata {
+    const VERSION: u8 = 1;
+}
+
+impl ReceiptCredentialWithMetadata {
+    pub fn get_receipt_expiration_time(&self) -> Timestamp {
+        self.receipt_expiration_time
+    }
+
+    pub fn get_receipt_level(&self) -> ReceiptLevel {
+        self.receipt_level
+    }
+
+    pub fn get_receipt_metadata(&self) -> ReceiptMetadata {
+        self.receipt_metadata
+    }
+}
+
+impl CredentialExpiration for ReceiptCredentialWithMetadata {
+    fn expiration_time(&self) -> Timestamp {
+        self.receipt_expiration_time
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.receipt_expiration_time
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialDefault)]
+pub struct ReceiptCredentialWithMetadataPresentation {
+    pub(crate) reserved: ReservedByte,
+    pub(crate) proof: crypto::proofs::ReceiptCredentialPresentationProof,
+    pub(crate) receipt_expiration_time: Timestamp,
+    pub(crate) receipt_level: ReceiptLevel,
+    pub(crate) receipt_metadata: ReceiptMetadata,
+    pub(crate) receipt_serial_bytes: ReceiptSerialBytes,
+}
+
+impl ReceiptCredentialWithMetadataPresentation {
+    pub fn get_receipt_struct(&self) -> ReceiptStruct {
+        ReceiptStruct::with_metadata(
+            self.receipt_serial_bytes,
+            self.receipt_expiration_time,
+            self.receipt_level,
+            self.receipt_metadata,
+        )
+    }
+
+    pub fn get_receipt_expiration_time(&self) -> Timestamp {
+        self.receipt_expiration_time
+    }
+
+    pub fn get_receipt_level(&self) -> ReceiptLevel {
+        self.receipt_level
+    }
+
+    pub fn get_receipt_metadata(&self) -> ReceiptMetadata {
+        self.receipt_metadata
+    }
+
+    pub fn get_receipt_serial_bytes(&self) -> ReceiptSerialBytes {
+        self.receipt_serial_bytes
+    }
+}
+
+impl CredentialExpiration for ReceiptCredentialWithMetadataPresentation {
+    fn expiration_time(&self) -> Timestamp {
+        self.receipt_expiration_time
+    }
+
+    fn is_valid_at(&self, now: Timestamp) -> bool {
+        now < self.receipt_expiration_time
+    }
+}
@@ -4,11 +4,12 @@
 //
 
 use partial_default::PartialDefault;
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 use serde::{Deserialize, Serialize};
 
 use crate::common::constants::*;
 use crate::common::errors::*;
-use crate::common::serialization::{ReservedByte, VersionByte};
+use crate::common::serialization::{ReservedByte, VersionByte, VersionedSerialization};
 use crate::common::sho::*;
 use crate::common::simple_types::*;
 use crate::{api, crypto};
@@ -41,12 +42,28 @@ pub struct ServerSecretParams {
     pub(crate) endorsement_key_pair: zkcredential::endorsements::ServerRootKeyPair,
 }
 
+impl VersionedSerialization for ServerSecretParams {
+    const VERSION: u8 = 1;
+}
+
 impl AsRef<zkcredential::endorsements::ServerRootKeyPair> for ServerSecretParams {
     fn as_ref(&self) -> &zkcredential::endorsements::ServerRootKeyPair {
         &self.endorsement_key_pair
     }
 }
 
+/// Identifies a particular [`ServerPublicParams`] (and the corresponding [`ServerSecretParams`]).
+///
+/// Derived from the public params, so clients and servers can compute it independently.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, PartialDefault)]
+pub struct ServerParamsId([u8; SERVER_PARAMS_ID_LEN]);
+
+impl ServerParamsId {
+    pub fn as_bytes(&self) -> &[u8; SERVER_PARAMS_ID_LEN] {
+        &self.0
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, PartialDefault)]
 pub struct ServerPublicParams {
     reserved: ReservedByte,
@@ -71,6 +88,10 @@ pub struct ServerPublicParams {
     pub(crate) endorsement_public_key: zkcredential::endorsements::ServerRootPublicKey,
 }
 
+impl VersionedSerialization for ServerPublicParams {
+    const VERSION: u8 = 1;
+}
+
 impl AsRef<zkcredential::endorsements::ServerRootPublicKey> for ServerPublicParams {
     fn as_ref(&self) -> &zkcredential::endorsements::ServerRootPublicKey {
         &self.endorsement_public_key
@@ -238,6 +259,52 @@ impl ServerSecretParams {
         Ok(())
     }
 
+    /// Verifies many presentations for the same group, in parallel.
+    ///
+    /// Each presentation is checked as by [`Self::verify_auth_credential_presentation`]. The
+    /// results are in the same order as `presentations`, so a failure can be traced back to the
+    /// member that presented it.
+    pub fn verify_auth_credential_presentations(
+        &self,
+        group_public_params: api::groups::GroupPublicParams,
+        presentations: &[api::auth::AnyAuthCredentialPresentation],
+        current_time: Timestamp,
+    ) -> Vec<Result<(), ZkGroupVerificationFailure>> {
+        presentations
+            .par_iter()
+            .map(|presentation| {
+                self.verify_auth_credential_presentation(
+                    group_public_params,
+                    presentation,
+                    current_time,
+                )
+            })
+            .collect()
+    }
+
+    /// Verifies many presentations for the same group, in parallel.
+    ///
+    /// Each presentation is checked as by [`Self::verify_profile_key_credential_presentation`].
+    /// The results are in the same order as `presentations`, so a failure can be traced back to
+    /// the member that presented it.
+    pub fn verify_profile_key_credential_presentations(
+        &self,
+        group_public_params: api::groups::GroupPublicParams,
+        presentations: &[api::profiles::AnyProfileKeyCredentialPresentation],
+        current_time: Timestamp,
+    ) -> Vec<Result<(), ZkGroupVerificationFailure>> {
+        presentations
+            .par_iter()
+            .map(|presentation| {
+                self.verify_profile_key_credential_presentation(
+                    group_public_params,
+                    presentation,
+                    current_time,
+                )
+            })
+            .collect()
+    }
+
     pub fn issue_expiring_profile_key_credential(
         &self,
         randomness: RandomnessBytes,
@@ -339,9 +406,77 @@ impl ServerSecretParams {
             presentation.get_receipt_struct(),
         )
     }
+
+    pub fn issue_receipt_credential_with_metadata(
+        &self,
+        randomness: RandomnessBytes,
+        request: &api::receipts::ReceiptCredentialRequest,
+        receipt_expiration_time: Timestamp,
+        receipt_level: ReceiptLevel,
+        receipt_metadata: ReceiptMetadata,
+    ) -> api::receipts::ReceiptCredentialWithMetadataResponse {
+        let mut sho = Sho::new(
+            b"Signal_ZKGroup_20251016_Random_ServerSecretParams_IssueReceiptCredentialWithMetadata",
+            &randomness,
+        );
+
+        let m1 = crypto::receipt_struct::ReceiptStruct::calc_m1_with_metadata_from(
+            receipt_expiration_time,
+            receipt_level,
+            receipt_metadata,
+        );
+        let blinded_credential_with_secret_nonce = self
+            .receipt_credentials_key_pair
+            .create_blinded_receipt_credential_for_m1(
+                request.public_key,
+                request.ciphertext,
+                m1,
+                &mut sho,
+            );
+
+        let proof = crypto::proofs::ReceiptCredentialIssuanceProof::new_for_m1(
+            self.receipt_credentials_key_pair,
+            request.public_key,
+            request.ciphertext,
+            blinded_credential_with_secret_nonce,
+            m1,
+            &mut sho,
+        );
+
+        api::receipts::ReceiptCredentialWithMetadataResponse {
+            reserved: Default::default(),
+            receipt_expiration_time,
+            receipt_level,
+            receipt_metadata,
+            blinded_credential: blinded_credential_with_secret_nonce
+                .get_blinded_receipt_credential(),
+            proof,
+        }
+    }
+
+    pub fn verify_receipt_credential_with_metadata_presentation(
+        &self,
+        presentation: &api::receipts::ReceiptCredentialWithMetadataPresentation,
+    ) -> Result<(), ZkGroupVerificationFailure> {
+        presentation.proof.verify(
+            self.receipt_credentials_key_pair,
+            presentation.get_receipt_struct(),
+        )
+    }
 }
 
 impl ServerPublicParams {
+    /// Returns an identifier for these params, for telling generations apart during key rotation.
+    ///
+    /// See [`RotatingServerPublicParams`](api::RotatingServerPublicParams).
+    pub fn id(&self) -> ServerParamsId {
+        let mut sho = Sho::new(
+            b"Signal_ZKGroup_20251016_ServerPublicParams_Id",
+            &crate::serialize(self),
+        );
+        ServerParamsId(sho.squeeze_as_array())
+    }
+
     pub fn get_endorsement_public_key(&self) -> EndorsementPublicKey {
         EndorsementPublicKey {
             reserved: Default::default(),
@@ -549,6 +684,61 @@ impl ServerPublicParams {
             receipt_serial_bytes: receipt_credential.receipt_serial_bytes,
         }
     }
+
+    pub fn receive_receipt_credential_with_metadata(
+        &self,
+        context: &api::receipts::ReceiptCredentialRequestContext,
+        response: &api::receipts::ReceiptCredentialWithMetadataResponse,
+    ) -> Result<api::receipts::ReceiptCredentialWithMetadata, ZkGroupVerificationFailure> {
+        let receipt_struct = crypto::receipt_struct::ReceiptStruct::with_metadata(
+            context.receipt_serial_bytes,
+            response.receipt_expiration_time,
+            response.receipt_level,
+            response.receipt_metadata,
+        );
+        response.proof.verify(
+            self.receipt_credentials_public_key,
+            context.key_pair.get_public_key(),
+            context.ciphertext_with_secret_nonce.get_ciphertext(),
+            response.blinded_credential,
+            receipt_struct,
+        )?;
+        let credential = context
+            .key_pair
+            .decrypt_blinded_receipt_credential(response.blinded_credential);
+        Ok(api::receipts::ReceiptCredentialWithMetadata {
+            reserved: Default::default(),
+            credential,
+            receipt_expiration_time: response.receipt_expiration_time,
+            receipt_level: response.receipt_level,
+            receipt_metadata: response.receipt_metadata,
+            receipt_serial_bytes: context.receipt_serial_bytes,
+        })
+    }
+
+    pub fn create_receipt_credential_with_metadata_presentation(
+        &self,
+        randomness: RandomnessBytes,
+        receipt_credential: &api::receipts::ReceiptCredentialWithMetadata,
+    ) -> api::receipts::ReceiptCredentialWithMetadataPresentation {
+        let mut sho = Sho::new(
+            b"Signal_ZKGroup_20251016_Random_ServerPublicParams_CreateReceiptCredentialWithMetadataPresentation",
+            &randomness,
+        );
+        let proof = crypto::proofs::ReceiptCredentialPresentationProof::new(
+            self.receipt_credentials_public_key,
+            receipt_credential.credential,
+            &mut sho,
+        );
+        api::receipts::ReceiptCredentialWithMetadataPresentation {
+            reserved: Default::default(),
+            proof,
+            receipt_expiration_time: receipt_credential.receipt_expiration_time,
+            receipt_level: receipt_credential.receipt_level,
+            receipt_metadata: receipt_credential.receipt_metadata,
+            receipt_serial_bytes: receipt_credential.receipt_serial_bytes,
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialDefault)]
@@ -0,0 +1,181 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Support for rotating [`ServerSecretParams`] without invalidating cached credentials.
+//!
+//! During a rotation, the server issues new credentials with the current params but still accepts
+//! presentations of credentials issued with the previous params, until those would have expired
+//! anyway. Clients likewise accept credentials issued with either generation, and record which
+//! one was used so they can present them correctly later.
+
+use crate::common::errors::*;
+use crate::common::simple_types::*;
+use crate::{api, ServerParamsId, ServerPublicParams, ServerSecretParams};
+
+/// The current and (optionally) previous generation of [`ServerSecretParams`].
+///
+/// Operations that issue credentials should use [`current`](Self::current); the `verify_`
+/// methods here accept presentations from either generation.
+pub struct RotatingServerSecretParams {
+    current: (ServerParamsId, ServerSecretParams),
+    previous: Option<(ServerParamsId, ServerSecretParams)>,
+}
+
+impl RotatingServerSecretParams {
+    pub fn new(current: ServerSecretParams, previous: Option<ServerSecretParams>) -> Self {
+        let with_id = |params: ServerSecretParams| (params.get_public_params().id(), params);
+        Self {
+            current: with_id(current),
+            previous: previous.map(with_id),
+        }
+    }
+
+    pub fn current(&self) -> &ServerSecretParams {
+        &self.current.1
+    }
+
+    pub fn previous(&self) -> Option<&ServerSecretParams> {
+        self.previous.as_ref().map(|(_id, params)| params)
+    }
+
+    pub fn get_public_params(&self) -> RotatingServerPublicParams {
+        RotatingServerPublicParams::new(
+            self.current.1.get_public_params(),
+            self.previous().map(ServerSecretParams::get_public_params),
+        )
+    }
+
+    /// Runs `verify` with the current params, then with the previous params if that fails.
+    ///
+    /// On success, returns the ID of the generation that succeeded.
+    pub fn verify_with_any_generation(
+        &self,
+        verify: impl Fn(&ServerSecretParams) -> Result<(), ZkGroupVerificationFailure>,
+    ) -> Result<ServerParamsId, ZkGroupVerificationFailure> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|(_id, params)| verify(params).is_ok())
+            .map(|(id, _params)| *id)
+            .ok_or(ZkGroupVerificationFailure)
+    }
+
+    pub fn verify_auth_credential_presentation(
+        &self,
+        group_public_params: api::groups::GroupPublicParams,
+        presentation: &api::auth::AnyAuthCredentialPresentation,
+        current_time: Timestamp,
+    ) -> Result<ServerParamsId, ZkGroupVerificationFailure> {
+        self.verify_with_any_generation(|params| {
+            params.verify_auth_credential_presentation(
+                group_public_params,
+                presentation,
+                current_time,
+            )
+        })
+    }
+
+    pub fn verify_profile_key_credential_presentation(
+        &self,
+        group_public_params: api::groups::GroupPublicParams,
+        presentation: &api::profiles::AnyProfileKeyCredentialPresentation,
+        current_time: Timestamp,
+    ) -> Result<ServerParamsId, ZkGroupVerificationFailure> {
+        self.verify_with_any_generation(|params| {
+            params.verify_profile_key_credential_presentation(
+                group_public_params,
+                presentation,
+                current_time,
+            )
+        })
+    }
+
+    pub fn verify_receipt_credential_presentation(
+        &self,
+        presentation: &api::receipts::ReceiptCredentialPresentation,
+    ) -> Result<ServerParamsId, ZkGroupVerificationFailure> {
+        self.verify_with_any_generation(|params| {
+            params.verify_receipt_credential_presentation(presentation)
+        })
+    }
+}
+
+/// The current and (optionally) previous generation of [`ServerPublicParams`].
+///
+/// Credentials received through the `receive_` methods here are returned along with the ID of the
+/// generation that issued them. Use [`get`](Self::get) with that ID to find the params to present
+/// the credential with.
+#[derive(Clone)]
+pub struct RotatingServerPublicParams {
+    current: (ServerParamsId, ServerPublicParams),
+    previous: Option<(ServerParamsId, ServerPublicParams)>,
+}
+
+impl RotatingServerPublicParams {
+    pub fn new(current: ServerPublicParams, previous: Option<ServerPublicParams>) -> Self {
+        let with_id = |params: ServerPublicParams| (params.id(), params);
+        Self {
+            current: with_id(current),
+            previous: previous.map(with_id),
+        }
+    }
+
+    pub fn current(&self) -> &ServerPublicParams {
+        &self.current.1
+    }
+
+    pub fn previous(&self) -> Option<&ServerPublicParams> {
+        self.previous.as_ref().map(|(_id, params)| params)
+    }
+
+    /// Returns the params with the given ID, if they're still in use.
+    ///
+    /// A credential issued by a generation that's no longer available should be discarded.
+    pub fn get(&self, id: ServerParamsId) -> Option<&ServerPublicParams> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|(candidate, _params)| *candidate == id)
+            .map(|(_id, params)| params)
+    }
+
+    /// Runs `receive` with the current params, then with the previous params if that fails.
+    ///
+    /// On success, returns the result along with the ID of the generation that succeeded.
+    pub fn receive_with_any_generation<T>(
+        &self,
+        receive: impl Fn(&ServerPublicParams) -> Result<T, ZkGroupVerificationFailure>,
+    ) -> Result<(ServerParamsId, T), ZkGroupVerificationFailure> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find_map(|(id, params)| Some((*id, receive(params).ok()?)))
+            .ok_or(ZkGroupVerificationFailure)
+    }
+
+    pub fn receive_expiring_profile_key_credential(
+        &self,
+        context: &api::profiles::ProfileKeyCredentialRequestContext,
+        response: &api::profiles::ExpiringProfileKeyCredentialResponse,
+        current_time: Timestamp,
+    ) -> Result<
+        (ServerParamsId, api::profiles::ExpiringProfileKeyCredential),
+        ZkGroupVerificationFailure,
+    > {
+        self.receive_with_any_generation(|params| {
+            params.receive_expiring_profile_key_credential(context, response, current_time)
+        })
+    }
+
+    pub fn receive_auth_credential_with_pni(
+        &self,
+        aci: libsignal_core::Aci,
+        pni: libsignal_core::Pni,
+        redemption_time: Timestamp,
+        response: &api::auth::AuthCredentialWithPniResponse,
+    ) -> Result<(ServerParamsId, api::auth::AuthCredentialWithPni), ZkGroupVerificationFailure>
+    {
+        self.receive_with_any_generation(|params| {
+            response.clone().receive(params, aci, pni, redemption_time)
+        })
+    }
+}
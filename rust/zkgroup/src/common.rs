@@ -6,6 +6,7 @@
 pub mod array_utils;
 pub mod constants;
 pub mod errors;
+pub mod expiration;
 pub mod serialization;
 pub mod sho;
 pub mod simple_types;
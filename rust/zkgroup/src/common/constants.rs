@@ -44,7 +44,9 @@ pub const RECEIPT_SERIAL_LEN: usize = 16;
 pub const RESERVED_LEN: usize = 1;
 pub const SERVER_SECRET_PARAMS_LEN: usize = 2721;
 pub const SERVER_PUBLIC_PARAMS_LEN: usize = 673;
+pub const SERVER_PARAMS_ID_LEN: usize = 16;
 pub const UUID_CIPHERTEXT_LEN: usize = 65;
+pub const BANNED_MEMBER_CIPHERTEXT_LEN: usize = 65;
 pub const RANDOMNESS_LEN: usize = 32;
 pub const SIGNATURE_LEN: usize = 64;
 pub const UUID_LEN: usize = 16;
@@ -53,6 +55,9 @@ pub const ACCESS_KEY_LEN: usize = 16;
 /// Seconds in a 24-hour cycle (ignoring leap seconds).
 pub const SECONDS_PER_DAY: u64 = 86400;
 
+/// Seconds in an hour.
+pub const SECONDS_PER_HOUR: u64 = 3600;
+
 pub const TEST_ARRAY_16: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
 
 pub const TEST_ARRAY_16_1: [u8; 16] = [
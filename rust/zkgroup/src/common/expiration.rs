@@ -0,0 +1,37 @@
+//
+// Copyright 2025 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use crate::common::constants::*;
+use crate::common::simple_types::Timestamp;
+
+/// A credential, presentation, or token that is only accepted for a limited time.
+///
+/// Clients can use this to refresh cached credentials before the server starts rejecting them.
+pub trait CredentialExpiration {
+    /// The end of the period in which this is accepted.
+    ///
+    /// Whether this exact moment is still accepted depends on the type; use
+    /// [`is_valid_at`](Self::is_valid_at) to check a particular time.
+    fn expiration_time(&self) -> Timestamp;
+
+    /// Whether the verifying server would accept this at `now`, as far as timing is concerned.
+    ///
+    /// This doesn't check anything else about the credential.
+    fn is_valid_at(&self, now: Timestamp) -> bool;
+}
+
+/// The end of the window in which a credential with `redemption_time` is accepted.
+///
+/// See [`ServerSecretParams::check_auth_credential_redemption_time`](crate::ServerSecretParams::check_auth_credential_redemption_time).
+pub(crate) fn redemption_window_end(redemption_time: Timestamp) -> Timestamp {
+    redemption_time
+        .checked_add_seconds(2 * SECONDS_PER_DAY)
+        .unwrap_or(Timestamp::from_epoch_seconds(u64::MAX))
+}
+
+/// Whether a credential with `redemption_time` is accepted at `now`.
+pub(crate) fn is_in_redemption_window(redemption_time: Timestamp, now: Timestamp) -> bool {
+    crate::ServerSecretParams::check_auth_credential_redemption_time(redemption_time, now).is_ok()
+}
@@ -97,6 +97,62 @@ impl<'de, const C: u8> Deserialize<'de> for VersionByte<C> {
 /// Value that always serializes to and from `0u8`.
 pub type ReservedByte = VersionByte<0>;
 
+/// A value serialized for storage, tagged with the version of its format.
+///
+/// zkgroup's encoding has no room for new fields: a type that gains one can no longer read what
+/// it wrote before. Clients that store credentials or params should wrap them in this envelope
+/// (with [`SerializedCredential::new`]) so that a later version of the type can still read them,
+/// via [`VersionedSerialization::migrate_from`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, PartialDefault)]
+pub struct SerializedCredential {
+    pub version: u8,
+    pub body: Vec<u8>,
+}
+
+/// A type that can be stored in a [`SerializedCredential`].
+pub trait VersionedSerialization: Serialize + for<'a> Deserialize<'a> + PartialDefault {
+    /// The version of the format [`serialize`] currently produces for this type.
+    ///
+    /// This must be increased whenever that format changes, along with handling the previous
+    /// version in [`migrate_from`](Self::migrate_from).
+    const VERSION: u8;
+
+    /// Reads a `body` that was stored with an earlier `version` of this type's format.
+    ///
+    /// The default rejects every earlier version.
+    fn migrate_from(version: u8, body: &[u8]) -> Result<Self, ZkGroupDeserializationFailure> {
+        let _ = (version, body);
+        Err(ZkGroupDeserializationFailure::new::<Self>())
+    }
+}
+
+impl SerializedCredential {
+    /// Serializes `value` using its current format version.
+    pub fn new<T: VersionedSerialization>(value: &T) -> Self {
+        Self {
+            version: T::VERSION,
+            body: serialize(value),
+        }
+    }
+
+    /// Deserializes the stored value, migrating it if it was stored with an earlier version.
+    ///
+    /// Fails if the value was stored by a newer version of the type.
+    pub fn open<T: VersionedSerialization>(&self) -> Result<T, ZkGroupDeserializationFailure> {
+        match self.version.cmp(&T::VERSION) {
+            std::cmp::Ordering::Equal => deserialize(&self.body),
+            std::cmp::Ordering::Less => T::migrate_from(self.version, &self.body),
+            std::cmp::Ordering::Greater => Err(ZkGroupDeserializationFailure::new::<T>()),
+        }
+    }
+
+    /// Returns whether the stored value uses `T`'s current format, and so doesn't need to be
+    /// rewritten after [`open`](Self::open).
+    pub fn is_current<T: VersionedSerialization>(&self) -> bool {
+        self.version == T::VERSION
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::fmt::Debug;
@@ -150,6 +206,66 @@ mod test {
         crate::deserialize::<T>(&serialized).expect_err("invalid version");
     }
 
+    #[derive(Debug, Serialize, Deserialize, PartialEq, PartialDefault)]
+    struct Versioned {
+        string: String,
+        added: u32,
+    }
+
+    impl VersionedSerialization for Versioned {
+        const VERSION: u8 = 2;
+
+        fn migrate_from(version: u8, body: &[u8]) -> Result<Self, ZkGroupDeserializationFailure> {
+            match version {
+                1 => Ok(Self {
+                    string: crate::deserialize(body)?,
+                    added: 0,
+                }),
+                _ => Err(ZkGroupDeserializationFailure::new::<Self>()),
+            }
+        }
+    }
+
+    #[test]
+    fn serialized_credential_round_trip() {
+        let value = Versioned {
+            string: "a string".to_string(),
+            added: 5,
+        };
+        let stored = crate::serialize(&SerializedCredential::new(&value));
+
+        let envelope: SerializedCredential = crate::deserialize(&stored).expect("can deserialize");
+        assert!(envelope.is_current::<Versioned>());
+        assert_eq!(envelope.open::<Versioned>().expect("current"), value);
+    }
+
+    #[test]
+    fn serialized_credential_migrates() {
+        let old = SerializedCredential {
+            version: 1,
+            body: crate::serialize(&"a string".to_string()),
+        };
+        assert!(!old.is_current::<Versioned>());
+        assert_eq!(
+            old.open::<Versioned>().expect("can migrate"),
+            Versioned {
+                string: "a string".to_string(),
+                added: 0,
+            }
+        );
+
+        let unknown_old = SerializedCredential {
+            version: 0,
+            ..old.clone()
+        };
+        unknown_old
+            .open::<Versioned>()
+            .expect_err("no migration from version 0");
+
+        let newer = SerializedCredential { version: 3, ..old };
+        newer.open::<Versioned>().expect_err("newer version");
+    }
+
     #[test]
     fn version_byte_error_message() {
         let mut bincode_serialized =
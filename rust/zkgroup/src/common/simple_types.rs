@@ -74,6 +74,11 @@ impl Timestamp {
         self.0 % SECONDS_PER_DAY == 0
     }
 
+    #[inline]
+    pub const fn is_hour_aligned(&self) -> bool {
+        self.0 % SECONDS_PER_HOUR == 0
+    }
+
     #[inline]
     pub fn to_be_bytes(self) -> [u8; 8] {
         self.0.to_be_bytes()
@@ -120,6 +125,18 @@ impl PublicAttribute for Timestamp {
 // should validate this matches their expectations.
 pub type ReceiptLevel = u64;
 
+/// Details of a payment carried in a receipt credential alongside its [`ReceiptLevel`].
+///
+/// Like the level, these are chosen by the issuing server and revealed to the server handling
+/// redemptions, so clients should validate they match their expectations.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, PartialDefault)]
+pub struct ReceiptMetadata {
+    /// ISO 4217 currency code, such as `*b"USD"`.
+    pub currency: [u8; 3],
+    /// The tier the payment amount falls into, as defined by the issuing server.
+    pub payment_level: u64,
+}
+
 pub fn encode_redemption_time(redemption_time: u32) -> Scalar {
     let mut scalar_bytes: [u8; 32] = Default::default();
     scalar_bytes[0..4].copy_from_slice(&redemption_time.to_be_bytes());
@@ -394,9 +394,25 @@ impl KeyPair<ReceiptCredential> {
         receipt_expiration_time: Timestamp,
         receipt_level: ReceiptLevel,
         sho: &mut Sho,
+    ) -> BlindedReceiptCredentialWithSecretNonce {
+        self.create_blinded_receipt_credential_for_m1(
+            public_key,
+            ciphertext,
+            ReceiptStruct::calc_m1_from(receipt_expiration_time, receipt_level),
+            sho,
+        )
+    }
+
+    /// Like [`Self::create_blinded_receipt_credential`], but taking the public attributes already
+    /// combined by [`ReceiptStruct::calc_m1`].
+    pub fn create_blinded_receipt_credential_for_m1(
+        &self,
+        public_key: receipt_credential_request::PublicKey,
+        ciphertext: receipt_credential_request::Ciphertext,
+        m1: Scalar,
+        sho: &mut Sho,
     ) -> BlindedReceiptCredentialWithSecretNonce {
         let params = SystemParams::get_hardcoded();
-        let m1 = ReceiptStruct::calc_m1_from(receipt_expiration_time, receipt_level);
         let M = [m1 * params.G_m1];
 
         let (t, U, Vprime) = self.credential_core(&M, sho);
@@ -8,7 +8,9 @@
 use std::sync::LazyLock;
 
 use curve25519_dalek_signal::ristretto::RistrettoPoint;
+use curve25519_dalek_signal::scalar::Scalar;
 use partial_default::PartialDefault;
+use rayon::iter::{IndexedParallelIterator, ParallelIterator as _};
 use serde::{Deserialize, Serialize};
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 use zkcredential::attributes::Attribute;
@@ -72,13 +74,35 @@ impl ProfileKeyEncryptionDomain {
         key_pair: &KeyPair,
         ciphertext: &Ciphertext,
         uid_bytes: UidBytes,
+    ) -> Result<profile_key_struct::ProfileKeyStruct, ZkGroupVerificationFailure> {
+        Self::decrypt_with_a1_inverse(key_pair, &key_pair.a1.invert(), ciphertext, uid_bytes)
+    }
+
+    /// Decrypts each of `ciphertexts` for its paired UID, inverting the key only once.
+    pub(crate) fn decrypt_batch(
+        key_pair: &KeyPair,
+        ciphertexts: impl IndexedParallelIterator<Item = (Ciphertext, UidBytes)>,
+    ) -> Vec<Result<profile_key_struct::ProfileKeyStruct, ZkGroupVerificationFailure>> {
+        let a1_inverse = key_pair.a1.invert();
+        ciphertexts
+            .map(|(ciphertext, uid_bytes)| {
+                Self::decrypt_with_a1_inverse(key_pair, &a1_inverse, &ciphertext, uid_bytes)
+            })
+            .collect()
+    }
+
+    fn decrypt_with_a1_inverse(
+        key_pair: &KeyPair,
+        a1_inverse: &Scalar,
+        ciphertext: &Ciphertext,
+        uid_bytes: UidBytes,
     ) -> Result<profile_key_struct::ProfileKeyStruct, ZkGroupVerificationFailure> {
         let M4 = key_pair
             .decrypt_to_second_point(ciphertext)
             .map_err(|_| ZkGroupVerificationFailure)?;
         let (mask, candidates) = M4.decode_253_bits();
 
-        let target_M3 = key_pair.a1.invert() * ciphertext.as_points()[0];
+        let target_M3 = a1_inverse * ciphertext.as_points()[0];
 
         let mut retval: profile_key_struct::ProfileKeyStruct = PartialDefault::partial_default();
         let mut n_found = 0;
@@ -7,6 +7,7 @@
 
 use curve25519_dalek_signal::constants::RISTRETTO_BASEPOINT_POINT;
 use curve25519_dalek_signal::ristretto::RistrettoPoint;
+use curve25519_dalek_signal::scalar::Scalar;
 use curve25519_dalek_signal::traits::Identity;
 use partial_default::PartialDefault;
 use serde::{Deserialize, Serialize};
@@ -352,9 +353,27 @@ impl ReceiptCredentialIssuanceProof {
         receipt_level: ReceiptLevel,
         sho: &mut Sho,
     ) -> Self {
-        let credentials_system = credentials::SystemParams::get_hardcoded();
+        Self::new_for_m1(
+            key_pair,
+            request_public_key,
+            request,
+            blinded_credential,
+            ReceiptStruct::calc_m1_from(receipt_expiration_time, receipt_level),
+            sho,
+        )
+    }
 
-        let m1 = ReceiptStruct::calc_m1_from(receipt_expiration_time, receipt_level);
+    /// Like [`Self::new`], but taking the public attributes already combined by
+    /// [`ReceiptStruct::calc_m1`].
+    pub fn new_for_m1(
+        key_pair: credentials::KeyPair<credentials::ReceiptCredential>,
+        request_public_key: receipt_credential_request::PublicKey,
+        request: receipt_credential_request::Ciphertext,
+        blinded_credential: credentials::BlindedReceiptCredentialWithSecretNonce,
+        m1: Scalar,
+        sho: &mut Sho,
+    ) -> Self {
+        let credentials_system = credentials::SystemParams::get_hardcoded();
 
         let mut scalar_args = poksho::ScalarArgs::new();
         scalar_args.add("w", key_pair.w);
@@ -7,7 +7,7 @@ use curve25519_dalek_signal::scalar::Scalar;
 use serde::{Deserialize, Serialize};
 
 use crate::common::sho::Sho;
-use crate::common::simple_types::{ReceiptLevel, ReceiptSerialBytes, Timestamp};
+use crate::common::simple_types::{ReceiptLevel, ReceiptMetadata, ReceiptSerialBytes, Timestamp};
 
 /// The full set of information known by the client after receiving the credential response from
 /// the issuing server.
@@ -23,6 +23,7 @@ pub struct ReceiptStruct {
     pub(crate) receipt_serial_bytes: ReceiptSerialBytes,
     pub(crate) receipt_expiration_time: Timestamp,
     pub(crate) receipt_level: ReceiptLevel,
+    pub(crate) receipt_metadata: Option<ReceiptMetadata>,
 }
 
 impl ReceiptStruct {
@@ -35,11 +36,33 @@ impl ReceiptStruct {
             receipt_serial_bytes,
             receipt_expiration_time,
             receipt_level,
+            receipt_metadata: None,
+        }
+    }
+
+    pub fn with_metadata(
+        receipt_serial_bytes: ReceiptSerialBytes,
+        receipt_expiration_time: Timestamp,
+        receipt_level: ReceiptLevel,
+        receipt_metadata: ReceiptMetadata,
+    ) -> Self {
+        Self {
+            receipt_serial_bytes,
+            receipt_expiration_time,
+            receipt_level,
+            receipt_metadata: Some(receipt_metadata),
         }
     }
 
     pub fn calc_m1(&self) -> Scalar {
-        Self::calc_m1_from(self.receipt_expiration_time, self.receipt_level)
+        match self.receipt_metadata {
+            None => Self::calc_m1_from(self.receipt_expiration_time, self.receipt_level),
+            Some(receipt_metadata) => Self::calc_m1_with_metadata_from(
+                self.receipt_expiration_time,
+                self.receipt_level,
+                receipt_metadata,
+            ),
+        }
     }
 
     pub fn calc_m1_from(receipt_expiration_time: Timestamp, receipt_level: ReceiptLevel) -> Scalar {
@@ -51,4 +74,34 @@ impl ReceiptStruct {
         let mut sho = Sho::new(b"Signal_ZKGroup_20210919_Receipt_CalcM1", &bytes);
         sho.get_scalar()
     }
+
+    /// Like [`Self::calc_m1_from`], but also covering `receipt_metadata`.
+    ///
+    /// Uses a different label, so a credential issued with metadata can never be presented as
+    /// one without, or vice versa.
+    pub fn calc_m1_with_metadata_from(
+        receipt_expiration_time: Timestamp,
+        receipt_level: ReceiptLevel,
+        receipt_metadata: ReceiptMetadata,
+    ) -> Scalar {
+        let ReceiptMetadata {
+            currency,
+            payment_level,
+        } = receipt_metadata;
+        let mut bytes = Vec::with_capacity(
+            std::mem::size_of::<Timestamp>()
+                + std::mem::size_of::<ReceiptLevel>()
+                + currency.len()
+                + std::mem::size_of::<u64>(),
+        );
+        bytes.extend_from_slice(&receipt_expiration_time.to_be_bytes());
+        bytes.extend_from_slice(&receipt_level.to_be_bytes());
+        bytes.extend_from_slice(&currency);
+        bytes.extend_from_slice(&payment_level.to_be_bytes());
+        let mut sho = Sho::new(
+            b"Signal_ZKGroup_20251016_Receipt_CalcM1WithMetadata",
+            &bytes,
+        );
+        sho.get_scalar()
+    }
 }
@@ -8,7 +8,9 @@
 use std::sync::LazyLock;
 
 use curve25519_dalek_signal::ristretto::RistrettoPoint;
+use curve25519_dalek_signal::scalar::Scalar;
 use partial_default::PartialDefault;
+use rayon::iter::{IndexedParallelIterator, ParallelIterator as _};
 use serde::{Deserialize, Serialize};
 use subtle::{ConditionallySelectable, ConstantTimeEq};
 use zkcredential::attributes::Attribute;
@@ -70,6 +72,25 @@ impl UidEncryptionDomain {
     pub(crate) fn decrypt(
         key_pair: &KeyPair,
         ciphertext: &Ciphertext,
+    ) -> Result<libsignal_core::ServiceId, ZkGroupVerificationFailure> {
+        Self::decrypt_with_a1_inverse(key_pair, &key_pair.a1.invert(), ciphertext)
+    }
+
+    /// Decrypts each of `ciphertexts`, inverting the key only once.
+    pub(crate) fn decrypt_batch(
+        key_pair: &KeyPair,
+        ciphertexts: impl IndexedParallelIterator<Item = Ciphertext>,
+    ) -> Vec<Result<libsignal_core::ServiceId, ZkGroupVerificationFailure>> {
+        let a1_inverse = key_pair.a1.invert();
+        ciphertexts
+            .map(|ciphertext| Self::decrypt_with_a1_inverse(key_pair, &a1_inverse, &ciphertext))
+            .collect()
+    }
+
+    fn decrypt_with_a1_inverse(
+        key_pair: &KeyPair,
+        a1_inverse: &Scalar,
+        ciphertext: &Ciphertext,
     ) -> Result<libsignal_core::ServiceId, ZkGroupVerificationFailure> {
         let M2 = key_pair
             .decrypt_to_second_point(ciphertext)
@@ -93,7 +114,7 @@ impl UidEncryptionDomain {
                 let aci_M1 = uid_struct::UidStruct::calc_M1(*decoded_aci);
                 let pni_M1 = uid_struct::UidStruct::calc_M1(*decoded_pni);
                 debug_assert!(aci_M1 != pni_M1);
-                let decrypted_M1 = key_pair.a1.invert() * ciphertext.as_points()[0];
+                let decrypted_M1 = a1_inverse * ciphertext.as_points()[0];
                 let mut index = u8::MAX;
                 index.conditional_assign(&0, decrypted_M1.ct_eq(&aci_M1));
                 index.conditional_assign(&1, decrypted_M1.ct_eq(&pni_M1));
@@ -12,5 +12,10 @@ pub mod crypto;
 pub use api::*;
 pub use common::constants::*;
 pub use common::errors::*;
-pub use common::serialization::{deserialize, serialize};
+pub use common::expiration::CredentialExpiration;
+pub use common::serialization::{
+    deserialize, serialize, SerializedCredential, VersionedSerialization,
+};
 pub use common::simple_types::*;
+/// Re-exported for defining new credential types; see [`zkcredential::schema`].
+pub use zkcredential;
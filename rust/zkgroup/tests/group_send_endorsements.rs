@@ -3,7 +3,10 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use zkgroup::{RandomnessBytes, Timestamp, RANDOMNESS_LEN, SECONDS_PER_DAY, UUID_LEN};
+use zkgroup::{
+    CredentialExpiration as _, RandomnessBytes, Timestamp, RANDOMNESS_LEN, SECONDS_PER_DAY,
+    SECONDS_PER_HOUR, UUID_LEN,
+};
 
 const DAY_ALIGNED_TIMESTAMP: Timestamp = Timestamp::from_epoch_seconds(1681344000); // 2023-04-13 00:00:00 UTC
 
@@ -228,3 +231,156 @@ fn test_client_rejects_bad_expirations() {
         DAY_ALIGNED_TIMESTAMP.add_seconds(1000 * SECONDS_PER_DAY),
     );
 }
+
+#[test]
+fn test_response_validity_matches_receive() {
+    let randomness1: RandomnessBytes = [0x43u8; RANDOMNESS_LEN];
+    let randomness2: RandomnessBytes = [0x44u8; RANDOMNESS_LEN];
+    let randomness3: RandomnessBytes = [0x45u8; RANDOMNESS_LEN];
+
+    let client_user_id = libsignal_core::Aci::from_uuid_bytes([0x04u8; UUID_LEN]);
+    let group_secret_params = zkgroup::groups::GroupSecretParams::generate(randomness1);
+    let client_user_id_ciphertext = group_secret_params.encrypt_service_id(client_user_id.into());
+
+    let server_secret_params = zkgroup::ServerSecretParams::generate(randomness2);
+    let server_public_params = server_secret_params.get_public_params();
+    let expiration = DAY_ALIGNED_TIMESTAMP.add_seconds(SECONDS_PER_DAY);
+    let key =
+        zkgroup::groups::GroupSendDerivedKeyPair::for_expiration(expiration, &server_secret_params);
+    let response = zkgroup::groups::GroupSendEndorsementsResponse::issue(
+        [client_user_id_ciphertext],
+        &key,
+        randomness3,
+    );
+
+    let last_valid = expiration.sub_seconds(2 * SECONDS_PER_HOUR);
+    for (now, expected) in [
+        (DAY_ALIGNED_TIMESTAMP, true),
+        (last_valid, true),
+        (last_valid.add_seconds(1), false),
+        (expiration, false),
+    ] {
+        let received = response.receive_with_service_ids(
+            [client_user_id.into()],
+            now,
+            &group_secret_params,
+            &server_public_params,
+        );
+        assert_eq!(received.is_ok(), expected, "now: {now:?}");
+        assert_eq!(response.is_valid_at(now), expected, "now: {now:?}");
+    }
+}
+
+#[test]
+fn test_endorsement_cache() {
+    let randomness1: RandomnessBytes = [0x43u8; RANDOMNESS_LEN];
+    let randomness2: RandomnessBytes = [0x44u8; RANDOMNESS_LEN];
+    let randomness3: RandomnessBytes = [0x45u8; RANDOMNESS_LEN];
+
+    // first set up a group
+    let client_user_id = libsignal_core::Aci::from_uuid_bytes([0x04u8; UUID_LEN]);
+
+    let moxie_user_id =
+        libsignal_core::Aci::from(uuid::uuid!("e36fdce7-36da-4c6f-a21b-9afe2b754650"));
+    let brian_user_id =
+        libsignal_core::Aci::from(uuid::uuid!("8c78cd2a-16ff-427d-83dc-1a5e36ce713d"));
+    let new_user_id = libsignal_core::Aci::from_uuid_bytes([0x05u8; UUID_LEN]);
+
+    let group_members = [
+        client_user_id.into(),
+        moxie_user_id.into(),
+        brian_user_id.into(),
+    ];
+
+    let group_secret_params = zkgroup::groups::GroupSecretParams::generate(randomness1);
+    let group_id = group_secret_params.get_group_identifier();
+    let ciphertexts: Vec<_> = group_members
+        .iter()
+        .map(|member| group_secret_params.encrypt_service_id(*member))
+        .collect();
+
+    let server_secret_params = zkgroup::ServerSecretParams::generate(randomness2);
+    let server_public_params = server_secret_params.get_public_params();
+    let expiration = DAY_ALIGNED_TIMESTAMP.add_seconds(SECONDS_PER_DAY);
+    let todays_key =
+        zkgroup::groups::GroupSendDerivedKeyPair::for_expiration(expiration, &server_secret_params);
+
+    let mut cache = zkgroup::groups::GroupSendEndorsementCache::new();
+    assert!(cache.needs_refresh(&group_id, DAY_ALIGNED_TIMESTAMP));
+    assert!(cache
+        .endorsement_for_group(&group_id, client_user_id.into())
+        .is_none());
+
+    let response = zkgroup::groups::GroupSendEndorsementsResponse::issue(
+        ciphertexts.iter().copied(),
+        &todays_key,
+        randomness3,
+    );
+    cache
+        .receive(
+            response,
+            &group_members,
+            DAY_ALIGNED_TIMESTAMP,
+            &group_secret_params,
+            &server_public_params,
+        )
+        .expect("issued endorsements should be valid");
+    assert_eq!(cache.expiration(&group_id), Some(expiration));
+    assert!(!cache.needs_refresh(&group_id, DAY_ALIGNED_TIMESTAMP));
+    assert!(cache.needs_refresh(&group_id, expiration.sub_seconds(60)));
+
+    let verify = |endorsement: zkgroup::groups::GroupSendEndorsement,
+                  recipients: &[libsignal_core::ServiceId]| {
+        endorsement
+            .to_token(group_secret_params)
+            .into_full_token(expiration)
+            .verify(
+                recipients.iter().copied(),
+                DAY_ALIGNED_TIMESTAMP,
+                &todays_key,
+            )
+    };
+
+    // Sending to the whole group.
+    verify(
+        cache
+            .endorsement_for_group(&group_id, client_user_id.into())
+            .unwrap(),
+        &[moxie_user_id.into(), brian_user_id.into()],
+    )
+    .expect("valid for the rest of the group");
+
+    // Sending to a subset.
+    verify(
+        cache
+            .endorsement_for_members(&group_id, [brian_user_id.into()])
+            .unwrap(),
+        &[brian_user_id.into()],
+    )
+    .expect("valid for the given members");
+    assert!(cache
+        .endorsement_for_members(&group_id, [new_user_id.into()])
+        .is_none());
+
+    // Removing a member keeps the remaining endorsements usable.
+    cache.member_removed(&group_id, moxie_user_id.into());
+    verify(
+        cache
+            .endorsement_for_group(&group_id, client_user_id.into())
+            .unwrap(),
+        &[brian_user_id.into()],
+    )
+    .expect("valid for the remaining members");
+
+    // Adding a member requires new endorsements.
+    cache.member_added(&group_id, new_user_id.into());
+    assert!(cache.needs_refresh(&group_id, DAY_ALIGNED_TIMESTAMP));
+    assert!(cache
+        .endorsement_for_group(&group_id, client_user_id.into())
+        .is_none());
+    cache.member_removed(&group_id, new_user_id.into());
+    assert!(!cache.needs_refresh(&group_id, DAY_ALIGNED_TIMESTAMP));
+
+    cache.forget_group(&group_id);
+    assert_eq!(cache.expiration(&group_id), None);
+}
@@ -6,7 +6,7 @@
 use const_str::hex;
 use curve25519_dalek_signal::ristretto::RistrettoPoint;
 use sha2::Sha256;
-use zkgroup::{Timestamp, SECONDS_PER_DAY};
+use zkgroup::{CredentialExpiration as _, Timestamp, SECONDS_PER_DAY};
 
 /// Simple wrapper around `assert_eq` that prints the hex-encoded values on
 /// failure.
@@ -140,6 +140,37 @@ fn test_integration_auth_zkc() {
         )
         .expect_err("credential not valid past deadline");
 
+    // The expiration introspection should agree with verification.
+    assert_eq!(
+        auth_credential.expiration_time(),
+        redemption_time.add_seconds(2 * SECONDS_PER_DAY)
+    );
+    assert_eq!(
+        presentation_any.expiration_time(),
+        auth_credential.expiration_time()
+    );
+    assert!(presentation_any.is_valid_at(redemption_time));
+    assert!(presentation_any.is_valid_at(redemption_time.add_seconds(2 * SECONDS_PER_DAY)));
+    assert!(!presentation_any.is_valid_at(redemption_time.sub_seconds(SECONDS_PER_DAY + 1)));
+    assert!(!presentation_any.is_valid_at(redemption_time.add_seconds(2 * SECONDS_PER_DAY + 2)));
+
+    let batch = [
+        zkgroup::auth::AnyAuthCredentialPresentation::new(presentation_bytes).unwrap(),
+        presentation_any,
+    ];
+    assert!(server_secret_params
+        .verify_auth_credential_presentations(group_public_params, &batch, redemption_time)
+        .into_iter()
+        .all(|result| result.is_ok()));
+    assert!(server_secret_params
+        .verify_auth_credential_presentations(
+            group_public_params,
+            &batch,
+            redemption_time.add_seconds(2 * SECONDS_PER_DAY + 2),
+        )
+        .into_iter()
+        .all(|result| result.is_err()));
+
     // Test encoding, which will also detect if the serialized lengths change.
     let mut auth_credential_response_bytes =
         [0u8; zkgroup::common::constants::AUTH_CREDENTIAL_WITH_PNI_RESPONSE_LEN];
@@ -149,6 +180,198 @@ fn test_integration_auth_zkc() {
     auth_credential_bytes.copy_from_slice(&bincode::serialize(&auth_credential).unwrap());
 }
 
+#[test]
+fn test_server_params_rotation() {
+    let old_server_secret_params = zkgroup::ServerSecretParams::generate(zkgroup::TEST_ARRAY_32);
+    let new_server_secret_params = zkgroup::ServerSecretParams::generate(zkgroup::TEST_ARRAY_32_1);
+    let old_id = old_server_secret_params.get_public_params().id();
+    let new_id = new_server_secret_params.get_public_params().id();
+    assert_ne!(old_id, new_id);
+
+    let rotating_secret_params = zkgroup::RotatingServerSecretParams::new(
+        new_server_secret_params.clone(),
+        Some(old_server_secret_params.clone()),
+    );
+    let rotating_public_params = rotating_secret_params.get_public_params();
+
+    let master_key = zkgroup::groups::GroupMasterKey::new(zkgroup::TEST_ARRAY_32_1);
+    let group_secret_params =
+        zkgroup::groups::GroupSecretParams::derive_from_master_key(master_key);
+    let group_public_params = group_secret_params.get_public_params();
+
+    let aci = libsignal_core::Aci::from(uuid::Uuid::from_bytes(zkgroup::TEST_ARRAY_16));
+    let pni = libsignal_core::Pni::from(uuid::Uuid::from_bytes(zkgroup::TEST_ARRAY_16_1));
+    let redemption_time = zkgroup::Timestamp::from_epoch_seconds(123456 * SECONDS_PER_DAY);
+
+    // A credential issued before the rotation is still accepted, and attributed to the old params.
+    let response: zkgroup::auth::AuthCredentialWithPniResponse =
+        zkgroup::auth::AuthCredentialWithPniZkcResponse::issue_credential(
+            aci,
+            pni,
+            redemption_time,
+            &old_server_secret_params,
+            zkgroup::TEST_ARRAY_32_2,
+        )
+        .into();
+    let (id, credential) = rotating_public_params
+        .receive_auth_credential_with_pni(aci, pni, redemption_time, &response)
+        .expect("issued by the previous generation");
+    assert_eq!(id, old_id);
+
+    let presentation = credential.present(
+        rotating_public_params.get(id).expect("still in use"),
+        &group_secret_params,
+        zkgroup::TEST_ARRAY_32_5,
+    );
+    assert_eq!(
+        rotating_secret_params
+            .verify_auth_credential_presentation(
+                group_public_params,
+                &presentation,
+                redemption_time,
+            )
+            .expect("valid for the previous generation"),
+        old_id
+    );
+
+    // Once the old params are dropped, so are its credentials.
+    let rotated_again_secret_params = zkgroup::RotatingServerSecretParams::new(
+        zkgroup::ServerSecretParams::generate(zkgroup::TEST_ARRAY_32_3),
+        Some(new_server_secret_params),
+    );
+    let rotated_again_public_params = rotated_again_secret_params.get_public_params();
+    assert!(rotated_again_public_params.get(old_id).is_none());
+    assert!(rotated_again_public_params.get(new_id).is_some());
+    rotated_again_secret_params
+        .verify_auth_credential_presentation(group_public_params, &presentation, redemption_time)
+        .expect_err("previous generation no longer accepted");
+    assert!(rotated_again_public_params
+        .receive_auth_credential_with_pni(aci, pni, redemption_time, &response)
+        .is_err());
+}
+
+#[test]
+fn test_banned_members() {
+    let server_secret_params = zkgroup::ServerSecretParams::generate(zkgroup::TEST_ARRAY_32);
+    let server_public_params = server_secret_params.get_public_params();
+
+    let master_key = zkgroup::groups::GroupMasterKey::new(zkgroup::TEST_ARRAY_32_1);
+    let group_secret_params =
+        zkgroup::groups::GroupSecretParams::derive_from_master_key(master_key);
+    let group_public_params = group_secret_params.get_public_params();
+    let other_group_secret_params =
+        zkgroup::groups::GroupSecretParams::generate(zkgroup::TEST_ARRAY_32_2);
+
+    let aci = libsignal_core::Aci::from(uuid::Uuid::from_bytes(zkgroup::TEST_ARRAY_16));
+    let pni = libsignal_core::Pni::from(uuid::Uuid::from_bytes(zkgroup::TEST_ARRAY_16_1));
+    let other_aci = libsignal_core::Aci::from(uuid::Uuid::from_bytes([0xAA; 16]));
+    let redemption_time = zkgroup::Timestamp::from_epoch_seconds(123456 * SECONDS_PER_DAY);
+
+    let presentation: zkgroup::auth::AnyAuthCredentialPresentation =
+        zkgroup::auth::AuthCredentialWithPniZkcResponse::issue_credential(
+            aci,
+            pni,
+            redemption_time,
+            &server_secret_params,
+            zkgroup::TEST_ARRAY_32_3,
+        )
+        .receive(aci, pni, redemption_time, &server_public_params)
+        .unwrap()
+        .present(
+            &server_public_params,
+            &group_secret_params,
+            zkgroup::TEST_ARRAY_32_4,
+        )
+        .into();
+
+    // CLIENT
+    let banned_aci = group_secret_params.encrypt_banned_member(aci.into());
+    let banned_pni = group_secret_params.encrypt_banned_member(pni.into());
+    let banned_other = group_secret_params.encrypt_banned_member(other_aci.into());
+    let banned_elsewhere = other_group_secret_params.encrypt_banned_member(aci.into());
+
+    let banned_bytes = zkgroup::serialize(&banned_aci);
+    assert_eq!(
+        banned_bytes.len(),
+        zkgroup::common::constants::BANNED_MEMBER_CIPHERTEXT_LEN
+    );
+
+    // SERVER
+    server_secret_params
+        .verify_auth_credential_presentation(group_public_params, &presentation, redemption_time)
+        .unwrap();
+    assert!(banned_aci.matches(&presentation));
+    assert!(banned_pni.matches(&presentation));
+    assert!(!banned_other.matches(&presentation));
+    assert!(!banned_elsewhere.matches(&presentation));
+
+    // CLIENT
+    let returned: zkgroup::groups::BannedMemberCiphertext =
+        zkgroup::deserialize(&banned_bytes).unwrap();
+    assert_eq!(
+        group_secret_params.decrypt_banned_member(returned).unwrap(),
+        libsignal_core::ServiceId::from(aci)
+    );
+    assert!(group_secret_params
+        .decrypt_banned_member(banned_elsewhere)
+        .is_err());
+}
+
+#[test]
+fn test_batch_decryption() {
+    let master_key = zkgroup::groups::GroupMasterKey::new(zkgroup::TEST_ARRAY_32_1);
+    let group_secret_params =
+        zkgroup::groups::GroupSecretParams::derive_from_master_key(master_key);
+    let other_group_secret_params =
+        zkgroup::groups::GroupSecretParams::generate(zkgroup::TEST_ARRAY_32_2);
+
+    let members: Vec<libsignal_core::Aci> = (0..10u8)
+        .map(|i| libsignal_core::Aci::from_uuid_bytes([i; 16]))
+        .collect();
+    let profile_keys: Vec<zkgroup::profiles::ProfileKey> = (0..10u8)
+        .map(|i| zkgroup::profiles::ProfileKey::create([i; 32]))
+        .collect();
+
+    let mut uuid_ciphertexts: Vec<_> = members
+        .iter()
+        .map(|aci| group_secret_params.encrypt_service_id((*aci).into()))
+        .collect();
+    uuid_ciphertexts.push(other_group_secret_params.encrypt_service_id(members[0].into()));
+
+    let results = group_secret_params.decrypt_service_ids_batch(&uuid_ciphertexts);
+    assert_eq!(results.len(), uuid_ciphertexts.len());
+    for (result, aci) in results.iter().zip(&members) {
+        assert_eq!(
+            *result.as_ref().unwrap(),
+            libsignal_core::ServiceId::from(*aci)
+        );
+    }
+    assert!(results.last().unwrap().is_err());
+
+    let mut profile_key_ciphertexts: Vec<_> = members
+        .iter()
+        .zip(&profile_keys)
+        .map(|(aci, profile_key)| {
+            (
+                group_secret_params.encrypt_profile_key(*profile_key, *aci),
+                *aci,
+            )
+        })
+        .collect();
+    // Paired with the wrong member.
+    profile_key_ciphertexts.push((profile_key_ciphertexts[0].0, members[1]));
+
+    let results = group_secret_params.decrypt_profile_keys_batch(&profile_key_ciphertexts);
+    assert_eq!(results.len(), profile_key_ciphertexts.len());
+    for (result, profile_key) in results.iter().zip(&profile_keys) {
+        assert_hex_eq!(
+            result.as_ref().unwrap().get_bytes(),
+            profile_key.get_bytes()
+        );
+    }
+    assert!(results.last().unwrap().is_err());
+}
+
 #[test]
 fn test_integration_expiring_profile() {
     // SERVER
@@ -270,6 +493,24 @@ fn test_integration_expiring_profile() {
         )
         .unwrap();
 
+    let batch = [
+        presentation_parsed,
+        zkgroup::profiles::AnyProfileKeyCredentialPresentation::new(
+            PROFILE_KEY_CREDENTIAL_PRESENTATION_V1,
+        )
+        .unwrap(),
+        presentation_any,
+    ];
+    let results = server_secret_params.verify_profile_key_credential_presentations(
+        group_public_params,
+        &batch,
+        expiration.sub_seconds(5),
+    );
+    assert_eq!(results.len(), batch.len());
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err(), "v1 presentations are rejected");
+    assert!(results[2].is_ok());
+
     // test encoding
     // these tests will also discover if the serialized sizes change,
     //   necessitating an update to the LEN constants
@@ -3,14 +3,16 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use zkgroup::api::receipts::ReceiptCredentialPresentation;
+use zkgroup::api::receipts::{
+    ReceiptCredentialPresentation, ReceiptCredentialWithMetadataPresentation,
+};
 use zkgroup::common::sho::Sho;
 use zkgroup::crypto::proofs::{ReceiptCredentialIssuanceProof, ReceiptCredentialPresentationProof};
 use zkgroup::crypto::receipt_struct::ReceiptStruct;
 use zkgroup::crypto::{credentials, receipt_credential_request};
 use zkgroup::{
-    RandomnessBytes, ReceiptLevel, ReceiptSerialBytes, ServerSecretParams, Timestamp,
-    RANDOMNESS_LEN, RECEIPT_SERIAL_LEN,
+    RandomnessBytes, ReceiptLevel, ReceiptMetadata, ReceiptSerialBytes, ServerSecretParams,
+    Timestamp, RANDOMNESS_LEN, RECEIPT_SERIAL_LEN,
 };
 
 #[test]
@@ -149,3 +151,61 @@ fn test_api() {
         .verify_receipt_credential_presentation(&bad_presentation)
         .expect_err("This Presentation Should Be Bad");
 }
+
+#[test]
+fn test_api_with_metadata() {
+    let randomness0: RandomnessBytes = [0x42u8; RANDOMNESS_LEN];
+    let randomness1: RandomnessBytes = [0x43u8; RANDOMNESS_LEN];
+    let randomness2: RandomnessBytes = [0x44u8; RANDOMNESS_LEN];
+    let randomness3: RandomnessBytes = [0x45u8; RANDOMNESS_LEN];
+    let receipt_serial_bytes: ReceiptSerialBytes = [0x84u8; RECEIPT_SERIAL_LEN];
+    let server_secret_params = ServerSecretParams::generate(randomness0);
+    let server_public_params = server_secret_params.get_public_params();
+
+    // client
+    let context = server_public_params
+        .create_receipt_credential_request_context(randomness1, receipt_serial_bytes);
+    let request = context.get_request();
+
+    // issuance server
+    let receipt_expiration_time: Timestamp = Timestamp::from_epoch_seconds(31337);
+    let receipt_level: ReceiptLevel = 3;
+    let receipt_metadata = ReceiptMetadata {
+        currency: *b"EUR",
+        payment_level: 2,
+    };
+    let response = server_secret_params.issue_receipt_credential_with_metadata(
+        randomness2,
+        &request,
+        receipt_expiration_time,
+        receipt_level,
+        receipt_metadata,
+    );
+
+    // client
+    let credential = server_public_params
+        .receive_receipt_credential_with_metadata(&context, &response)
+        .expect("Invalid Receipt Credential Issuance");
+    assert_eq!(credential.get_receipt_metadata(), receipt_metadata);
+    let presentation = server_public_params
+        .create_receipt_credential_with_metadata_presentation(randomness3, &credential);
+    assert_eq!(presentation.get_receipt_level(), receipt_level);
+    assert_eq!(presentation.get_receipt_metadata(), receipt_metadata);
+
+    // redemption server
+    server_secret_params
+        .verify_receipt_credential_with_metadata_presentation(&presentation)
+        .expect("Invalid Receipt Credential Presentation");
+
+    let mut presentation_bytes = bincode::serialize(&presentation).unwrap();
+    // claim a different currency
+    let i = presentation_bytes.len() - RECEIPT_SERIAL_LEN - 8 - 3;
+    presentation_bytes[i] += 1;
+    let bad_presentation =
+        bincode::deserialize::<ReceiptCredentialWithMetadataPresentation>(&presentation_bytes)
+            .unwrap();
+    assert_eq!(&bad_presentation.get_receipt_metadata().currency, b"FUR");
+    server_secret_params
+        .verify_receipt_credential_with_metadata_presentation(&bad_presentation)
+        .expect_err("This Presentation Should Be Bad");
+}
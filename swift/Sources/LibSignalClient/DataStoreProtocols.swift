@@ -14,6 +14,8 @@ public enum Direction: Sendable {
 public enum IdentityChange: Sendable {
     case newOrUnchanged
     case replacedExisting
+    /// Only produced when accepting a signed identity transition, never by `saveIdentity`.
+    case rotatedVerified
 }
 
 /// A marker protocol, which must be downcast to use in any particular store.
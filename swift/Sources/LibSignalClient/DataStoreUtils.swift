@@ -56,6 +56,7 @@ internal func withIdentityKeyStore<Result>(
             return switch try store.saveIdentity(identity, for: address, context: context) {
             case .newOrUnchanged: Int32(SignalIdentityChangeNewOrUnchanged.rawValue)
             case .replacedExisting: Int32(SignalIdentityChangeReplacedExisting.rawValue)
+            case .rotatedVerified: Int32(SignalIdentityChangeRotatedVerified.rawValue)
             }
         }
     }
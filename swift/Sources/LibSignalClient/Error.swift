@@ -54,6 +54,7 @@ public enum SignalError: Error {
     case webSocketError(String)
     case connectionTimeoutError(String)
     case requestTimeoutError(String)
+    case tooManyRequestsInFlight(String)
     case connectionFailed(String)
     case networkProtocolError(String)
     case cdsiInvalidToken(String)
@@ -213,6 +214,8 @@ internal func checkError(_ error: SignalFfiErrorRef?) throws {
         throw SignalError.connectionTimeoutError(errStr)
     case SignalErrorCodeRequestTimedOut:
         throw SignalError.requestTimeoutError(errStr)
+    case SignalErrorCodeTooManyRequestsInFlight:
+        throw SignalError.tooManyRequestsInFlight(errStr)
     case SignalErrorCodeConnectionFailed:
         throw SignalError.connectionFailed(errStr)
     case SignalErrorCodeNetworkProtocol:
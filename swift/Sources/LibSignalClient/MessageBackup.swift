@@ -252,6 +252,95 @@ extension SignalMutPointerOnlineBackupValidator: SignalMutPointer {
     }
 }
 
+/// Produces an encrypted backup file frame-by-frame.
+///
+/// Each frame is validated as it's added, with the same checks ``validateMessageBackup(key:purpose:length:makeStream:)`` performs, so frames must be added in an order that satisfies those checks (for example, a chat before any of its chat items). The frames are kept in memory until the backup is finished.
+///
+/// # Example
+///
+/// ```
+/// let writer = try MessageBackupWriter(
+///     backupInfo: backupInfoProto.serialize(),
+///     purpose: .remoteBackup)
+/// repeat {
+///   // ...generate Frames...
+///   try writer.addFrame(frameProto.serialize())
+/// }
+/// let backupFile = try writer.finishEncrypted(key: backupKey, forwardSecrecyMetadata: metadata)
+/// ```
+public class MessageBackupWriter: NativeHandleOwner<SignalMutPointerMessageBackupWriter> {
+    /// Starts a backup with the given BackupInfo protobuf message, which is written out unchanged.
+    ///
+    /// - Throws: ``MessageBackupValidationError`` on error.
+    public convenience init<Bytes: ContiguousBytes>(backupInfo: Bytes, purpose: MessageBackupPurpose) throws {
+        let handle = try backupInfo.withUnsafeBorrowedBuffer { backupInfo in
+            var outputHandle = SignalMutPointerMessageBackupWriter()
+            try checkError(signal_message_backup_writer_new(&outputHandle, backupInfo, purpose.rawValue))
+            return outputHandle
+        }
+        self.init(owned: NonNull(handle)!)
+    }
+
+    internal required init(owned handle: NonNull<SignalMutPointerMessageBackupWriter>) {
+        super.init(owned: handle)
+    }
+
+    override internal class func destroyNativeHandle(
+        _ handle: NonNull<SignalMutPointerMessageBackupWriter>
+    ) -> SignalFfiErrorRef? {
+        signal_message_backup_writer_destroy(handle.pointer)
+    }
+
+    /// Validates a single Frame protobuf message against the frames added so far, then appends it unchanged.
+    ///
+    /// If this fails, the writer should be discarded.
+    ///
+    /// - Throws: ``MessageBackupValidationError`` on error.
+    public func addFrame<Bytes: ContiguousBytes>(_ frame: Bytes) throws {
+        try withNativeHandle { handle in
+            try frame.withUnsafeBorrowedBuffer { frame in
+                try checkError(signal_message_backup_writer_add_frame(handle, frame))
+            }
+        }
+    }
+
+    /// Checks that the backup is complete, then compresses, pads, and encrypts it.
+    ///
+    /// The writer can't be used again after this, whether or not it succeeds.
+    ///
+    /// - Parameter key: The key to encrypt the backup with.
+    /// - Parameter forwardSecrecyMetadata: The serialized metadata produced by SVR-B, or `nil` to produce the legacy format with no unencrypted header.
+    /// - Returns: The complete backup file.
+    /// - Throws: ``MessageBackupValidationError`` if the backup is incomplete or can't be encrypted.
+    public func finishEncrypted(key: MessageBackupKey, forwardSecrecyMetadata: Data?) throws -> Data {
+        try withNativeHandle { handle in
+            try key.withNativeHandle { key in
+                try withUnsafeOptionalBorrowedSlice(of: forwardSecrecyMetadata) { metadata in
+                    try invokeFnReturningData {
+                        signal_message_backup_writer_finish_encrypted($0, handle, key.const(), metadata)
+                    }
+                }
+            }
+        }
+    }
+}
+
+extension SignalMutPointerMessageBackupWriter: SignalMutPointer {
+    public typealias ConstPointer = OpaquePointer?
+
+    public init(untyped: OpaquePointer?) {
+        self.init(raw: untyped)
+    }
+
+    public func toOpaque() -> OpaquePointer? {
+        self.raw
+    }
+
+    public func const() -> Self.ConstPointer {
+        nil
+    }
+}
+
 /// The outcome of a failed validation attempt.
 public struct MessageBackupValidationError: Error {
     /// The human-readable error that caused validation to fail.
@@ -167,6 +167,13 @@ typedef enum {
    * The new identity key replaced a different key for the protocol address.
    */
   SignalIdentityChangeReplacedExisting,
+  /**
+   * The new identity key replaced the previous one by way of a transition signed with it, so
+   * the peer should stay as trusted as it was.
+   *
+   * Only returned by [`IdentityKeyStore::save_identity_transition`].
+   */
+  SignalIdentityChangeRotatedVerified,
 } SignalIdentityChange;
 
 typedef enum {
@@ -233,6 +240,7 @@ typedef enum {
   SignalErrorCodeChatServiceInactive = 149,
   SignalErrorCodeRequestTimedOut = 150,
   SignalErrorCodeRateLimitChallenge = 151,
+  SignalErrorCodeTooManyRequestsInFlight = 152,
   SignalErrorCodeSvrDataMissing = 160,
   SignalErrorCodeSvrRestoreFailed = 161,
   SignalErrorCodeSvrRotationMachineTooManySteps = 162,
@@ -320,6 +328,8 @@ typedef struct SignalMessageBackupKey SignalMessageBackupKey;
 
 typedef struct SignalMessageBackupValidationOutcome SignalMessageBackupValidationOutcome;
 
+typedef struct SignalMessageBackupWriter SignalMessageBackupWriter;
+
 typedef struct SignalOnlineBackupValidator SignalOnlineBackupValidator;
 
 typedef struct SignalPinHash SignalPinHash;
@@ -1070,6 +1080,10 @@ typedef struct {
   const SignalMessageBackupValidationOutcome *raw;
 } SignalConstPointerMessageBackupValidationOutcome;
 
+typedef struct {
+  SignalMessageBackupWriter *raw;
+} SignalMutPointerMessageBackupWriter;
+
 typedef int (*SignalRead)(void *ctx, uint8_t *buf, size_t buf_len, size_t *amount_read);
 
 typedef int (*SignalSkip)(void *ctx, uint64_t amount);
@@ -1924,6 +1938,14 @@ SignalFfiError *signal_message_backup_validation_outcome_get_unknown_fields(Sign
 
 SignalFfiError *signal_message_backup_validator_validate(SignalMutPointerMessageBackupValidationOutcome *out, SignalConstPointerMessageBackupKey key, SignalConstPointerFfiInputStreamStruct first_stream, SignalConstPointerFfiInputStreamStruct second_stream, uint64_t len, uint8_t purpose);
 
+SignalFfiError *signal_message_backup_writer_add_frame(SignalMutPointerMessageBackupWriter writer, SignalBorrowedBuffer frame);
+
+SignalFfiError *signal_message_backup_writer_destroy(SignalMutPointerMessageBackupWriter p);
+
+SignalFfiError *signal_message_backup_writer_finish_encrypted(SignalOwnedBuffer *out, SignalMutPointerMessageBackupWriter writer, SignalConstPointerMessageBackupKey key, SignalOptionalBorrowedSliceOfc_uchar forward_secrecy_metadata);
+
+SignalFfiError *signal_message_backup_writer_new(SignalMutPointerMessageBackupWriter *out, SignalBorrowedBuffer backup_info_frame, uint8_t purpose);
+
 SignalFfiError *signal_message_clone(SignalMutPointerSignalMessage *new_obj, SignalConstPointerSignalMessage obj);
 
 SignalFfiError *signal_message_deserialize(SignalMutPointerSignalMessage *out, SignalBorrowedBuffer data);
@@ -112,6 +112,9 @@ final class ChatServiceTests: TestCaseBase {
         do {
             try failWithError("RequestTimedOut")
         } catch SignalError.requestTimeoutError(_) {}
+        do {
+            try failWithError("Busy")
+        } catch SignalError.tooManyRequestsInFlight(_) {}
         do {
             try failWithError("ConnectionInvalidated")
         } catch SignalError.connectionInvalidated(_) {}
@@ -187,8 +187,56 @@ class MessageBackupTests: TestCaseBase {
 
         try backup.finalize()
     }
+
+    func testWriterRoundTrip() throws {
+        var bytes = readResource(forName: "canonical-backup.binproto")
+
+        // Tiny varint-delimited frame parser, only supports two-byte lengths.
+        func nextFrame() -> Data? {
+            guard var frameLength = bytes.first.map({ Int($0) }) else {
+                return nil
+            }
+            bytes = bytes.dropFirst()
+            if frameLength >= 0x80 {
+                let secondByte = Int(bytes.first!)
+                XCTAssertLessThan(secondByte, 0x80, "at most a two-byte varint")
+                frameLength -= 0x80
+                frameLength |= secondByte << 7
+                bytes = bytes.dropFirst()
+            }
+            let frame = bytes.prefix(frameLength)
+            XCTAssertEqual(frame.count, frameLength, "unexpected EOF")
+            bytes = bytes[frame.endIndex...]
+            return frame
+        }
+
+        let writer = try MessageBackupWriter(backupInfo: nextFrame()!, purpose: .remoteBackup)
+        while let frame = nextFrame() {
+            try writer.addFrame(frame)
+        }
+        let encrypted = try writer.finishEncrypted(key: MessageBackupKey.testKey(), forwardSecrecyMetadata: nil)
+
+        let outcome = try Self.validateBackup(bytes: encrypted)
+        XCTAssertEqual(outcome.fields, [])
+    }
     #endif
 
+    func testWriterInvalidBackupInfo() throws {
+        XCTAssertThrowsError(try MessageBackupWriter(backupInfo: [], purpose: .remoteBackup))
+    }
+
+    func testWriterInvalidFrame() throws {
+        let writer = try MessageBackupWriter(backupInfo: VALID_BACKUP_INFO, purpose: .remoteBackup)
+        XCTAssertThrowsError(try writer.addFrame([]))
+    }
+
+    func testWriterIncompleteBackup() throws {
+        let writer = try MessageBackupWriter(backupInfo: VALID_BACKUP_INFO, purpose: .remoteBackup)
+        XCTAssertThrowsError(
+            try writer.finishEncrypted(key: MessageBackupKey.testKey(), forwardSecrecyMetadata: nil)
+        )
+    }
+
     static func validateBackup(bytes: some Collection<UInt8>) throws -> MessageBackupUnknownFields {
         try validateMessageBackup(
             key: MessageBackupKey.testKey(),